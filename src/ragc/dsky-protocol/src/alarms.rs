@@ -0,0 +1,116 @@
+/// Program alarm code as shown in N09 (octal) and what it means
+#[derive(Clone, Copy, Debug)]
+pub struct AlarmEntry {
+    pub code: u16,
+    pub text: &'static str,
+}
+
+/// Alarm descriptions for a given ROM version
+#[derive(Clone, Copy, Debug)]
+pub struct AlarmTable {
+    pub rom: &'static str,
+    pub alarms: &'static [AlarmEntry],
+}
+
+impl AlarmTable {
+    /// The rope's own entry, or else one every Apollo 11 rope shares
+    pub fn lookup(&self, code: u16) -> Option<&AlarmEntry> {
+        self.alarms
+            .iter()
+            .chain(COMMON_ALARMS.iter())
+            .find(|a| a.code == code)
+    }
+}
+
+const fn alarm(code: u16, text: &'static str) -> AlarmEntry {
+    AlarmEntry { code, text }
+}
+
+/// Executive, IMU and interpreter alarms of both Apollo 11 ropes
+const COMMON_ALARMS: [AlarmEntry; 34] = [
+    alarm(0o00205, "Bad PIPA reading"),
+    alarm(
+        0o00206,
+        "Zero encode not allowed with coarse align and gimbal lock",
+    ),
+    alarm(0o00207, "ISS turn-on request not present for 90 seconds"),
+    alarm(0o00210, "IMU not operating"),
+    alarm(0o00211, "Coarse align error"),
+    alarm(0o00212, "PIPA fail but PIPA is not being used"),
+    alarm(0o00213, "IMU not operating with turn-on request"),
+    alarm(0o00214, "Program using IMU when turned off"),
+    alarm(0o00217, "Bad return from IMUSTALL"),
+    alarm(0o00220, "IMU orientation unknown"),
+    alarm(0o00401, "Desired gimbal angles yield gimbal lock"),
+    alarm(0o00777, "PIPA fail caused ISS warning"),
+    alarm(0o01105, "Downlink too fast"),
+    alarm(0o01106, "Uplink too fast"),
+    alarm(
+        0o01107,
+        "Phase table failure, erasable memory assumed destroyed",
+    ),
+    alarm(0o01201, "Executive overflow: no VAC areas"),
+    alarm(0o01202, "Executive overflow: no core sets"),
+    alarm(0o01203, "Waitlist overflow: too many tasks"),
+    alarm(
+        0o01204,
+        "WAITLIST, VARDELAY or LONGCALL called with zero or negative time",
+    ),
+    alarm(
+        0o01206,
+        "Second job attempted to sleep via the keyboard and display program",
+    ),
+    alarm(0o01210, "Two programs using a device at the same time"),
+    alarm(0o01211, "Illegal interrupt of an extended verb"),
+    alarm(0o01301, "ARCSIN or ARCCOS argument too large"),
+    alarm(0o01302, "SQRT called with a negative argument"),
+    alarm(0o01407, "Velocity to be gained increasing"),
+    alarm(
+        0o01501,
+        "Keyboard and display alarm during internal use (NVSUB)",
+    ),
+    alarm(0o01502, "Illegal flash monitor"),
+    alarm(0o01520, "V37 request not permitted at this time"),
+    // ISS warnings, by which of the ICDU, IMU and PIPA failed
+    alarm(0o03777, "ICDU fail caused ISS warning"),
+    alarm(0o04777, "ICDU and PIPA fails caused ISS warning"),
+    alarm(0o07777, "IMU fail caused ISS warning"),
+    alarm(0o10777, "IMU and PIPA fails caused ISS warning"),
+    alarm(0o13777, "IMU and ICDU fails caused ISS warning"),
+    alarm(0o14777, "IMU, ICDU and PIPA fails caused ISS warning"),
+];
+
+pub const LUMINARY99_ALARMS: AlarmTable = AlarmTable {
+    rom: "LUMINARY099",
+    alarms: &[
+        alarm(0o00501, "Radar antenna out of limits"),
+        alarm(0o00503, "Radar antenna designate fail"),
+        alarm(0o00511, "Landing radar not in position"),
+        alarm(0o00520, "Radar interrupt not expected at this time"),
+        alarm(0o01410, "Unintentional overflow in guidance"),
+    ],
+};
+
+pub const COMANCHE55_ALARMS: AlarmTable = AlarmTable {
+    rom: "COMANCHE055",
+    alarms: &[alarm(
+        0o00115,
+        "Optics torque request with switch not at GC control",
+    )],
+};
+
+#[cfg(test)]
+mod alarm_tests {
+    use super::{COMANCHE55_ALARMS, LUMINARY99_ALARMS};
+
+    #[test]
+    fn tables_fall_back_to_shared_alarms() {
+        let lookup = |code| LUMINARY99_ALARMS.lookup(code).map(|a| a.text);
+        assert_eq!(lookup(0o1202), Some("Executive overflow: no core sets"));
+        assert_eq!(lookup(0o511), Some("Landing radar not in position"));
+        assert_eq!(lookup(0o07777), Some("IMU fail caused ISS warning"));
+        assert!(lookup(0o115).is_none());
+        assert!(COMANCHE55_ALARMS.lookup(0o115).is_some());
+        assert!(COMANCHE55_ALARMS.lookup(0o511).is_none());
+    }
+}
@@ -0,0 +1,106 @@
+use std::io::Write;
+use std::time::Instant;
+
+use crate::agc::parse_dsky_packet;
+
+/// Capture files start with this magic, followed by fixed-size records
+pub const CAPTURE_MAGIC: &[u8; 8] = b"RAGCCAP1";
+
+/// Bytes per record: u64 LE microseconds since capture start, then the packet
+pub const RECORD_LEN: usize = 12;
+
+/// Packet channels carried by captures
+pub const CAPTURE_KEY: u16 = 0o15; // Main DSKY keycode
+pub const CAPTURE_PROCEED: u16 = 0o32; // PRO key state
+pub const CAPTURE_UPLINK: u16 = 0o173; // INLINK uplink word
+
+/// One input packet observed at a point in time
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct CaptureRecord {
+    pub time_us: u64,
+    pub channel: u16,
+    pub value: u16,
+}
+
+/// Parse a capture file. Records with invalid packets are skipped.
+pub fn parse_capture(bytes: &[u8]) -> Result<Vec<CaptureRecord>, String> {
+    let body = bytes
+        .strip_prefix(CAPTURE_MAGIC.as_slice())
+        .ok_or("Not a capture file")?;
+    if body.len() % RECORD_LEN != 0 {
+        return Err(format!("Truncated capture ({} bytes)", bytes.len()));
+    }
+
+    let records = body
+        .chunks_exact(RECORD_LEN)
+        .filter_map(|rec| {
+            let time_us = u64::from_le_bytes(rec[..8].try_into().unwrap());
+            let (channel, value) = parse_dsky_packet(rec[8..].try_into().unwrap())?;
+            Some(CaptureRecord {
+                time_us,
+                channel,
+                value,
+            })
+        })
+        .collect();
+    Ok(records)
+}
+
+/// Appends input packets to a capture, timestamped from creation
+pub struct CaptureWriter<W: Write> {
+    out: W,
+    start: Instant,
+}
+
+impl<W: Write> CaptureWriter<W> {
+    pub fn new(mut out: W) -> std::io::Result<Self> {
+        out.write_all(CAPTURE_MAGIC)?;
+        Ok(Self {
+            out,
+            start: Instant::now(),
+        })
+    }
+
+    pub fn record(&mut self, packet: [u8; 4]) -> std::io::Result<()> {
+        let time_us = self.start.elapsed().as_micros() as u64;
+        self.write_at(time_us, packet)
+    }
+
+    /// Append a packet with an explicit timestamp
+    pub fn write_at(&mut self, time_us: u64, packet: [u8; 4]) -> std::io::Result<()> {
+        self.out.write_all(&time_us.to_le_bytes())?;
+        self.out.write_all(&packet)?;
+        self.out.flush()
+    }
+}
+
+#[cfg(test)]
+mod capture_tests {
+    use super::*;
+    use crate::agc::generate_dsky_packet;
+
+    #[test]
+    fn round_trips_records() {
+        let mut writer = CaptureWriter::new(Vec::new()).unwrap();
+        writer
+            .write_at(0, generate_dsky_packet(0o15, 0o21))
+            .unwrap();
+        writer.write_at(1500, [0xff; 4]).unwrap();
+        writer
+            .write_at(2_000_000, generate_dsky_packet(0o173, 0o12345))
+            .unwrap();
+
+        let records = parse_capture(&writer.out).unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!(
+            records[1],
+            CaptureRecord {
+                time_us: 2_000_000,
+                channel: CAPTURE_UPLINK,
+                value: 0o12345
+            }
+        );
+        assert!(parse_capture(b"junk").is_err());
+        assert!(parse_capture(&writer.out[..21]).is_err());
+    }
+}
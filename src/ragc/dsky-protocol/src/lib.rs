@@ -1 +1,5 @@
 pub mod agc;
+pub mod alarms;
+pub mod capture;
+pub mod pinball;
+pub mod uplink;
@@ -0,0 +1,226 @@
+//! Protocol debugging tool: encode and decode DSKY packets on stdin, or sit
+//! between the emulator and a frontend logging the traffic both ways
+use std::io::{BufRead, Read, Write};
+use std::net::{TcpListener, TcpStream};
+
+use dsky_protocol::agc::{generate_dsky_packet, parse_dsky_packet};
+
+/// Port the emulator serves the DSKY on
+const EMULATOR_ADDR: &str = "127.0.0.1:19697";
+
+/// `CHANNEL VALUE` in octal, as read by encode and written by decode
+fn format_word(channel: u16, value: u16) -> String {
+    format!("{:03o} {:05o}", channel, value)
+}
+
+fn parse_word(line: &str) -> Result<(usize, u16), String> {
+    let fields: Vec<_> = line
+        .split_whitespace()
+        .map(|f| u16::from_str_radix(f, 8).ok())
+        .collect();
+    match fields[..] {
+        [Some(channel), Some(value)] if channel < 0o1000 && value < 0o100000 => {
+            Ok((channel as usize, value))
+        }
+        _ => Err(format!("Expected octal CHANNEL VALUE: {}", line)),
+    }
+}
+
+fn format_packet(packet: &[u8; 4]) -> String {
+    let hex: Vec<_> = packet.iter().map(|b| format!("{:02x}", b)).collect();
+    hex.join(" ")
+}
+
+/// Four hex bytes, spaced or not
+fn parse_packet(line: &str) -> Result<[u8; 4], String> {
+    let digits: String = line.split_whitespace().collect();
+    let bytes: Option<Vec<u8>> = (0..digits.len())
+        .step_by(2)
+        .map(|i| {
+            digits
+                .get(i..i + 2)
+                .and_then(|b| u8::from_str_radix(b, 16).ok())
+        })
+        .collect();
+    bytes
+        .and_then(|b| b.try_into().ok())
+        .ok_or_else(|| format!("Expected four hex bytes: {}", line))
+}
+
+/// Reassembles packets from a byte stream. Each byte carries its position
+/// in its top two bits, so a dropped byte costs one packet at most.
+#[derive(Default)]
+struct Framer {
+    buf: [u8; 4],
+    len: usize,
+}
+
+impl Framer {
+    fn push(&mut self, byte: u8) -> Option<[u8; 4]> {
+        let pos = (byte >> 6) as usize;
+        if pos != self.len {
+            self.len = 0;
+            if pos != 0 {
+                return None;
+            }
+        }
+        self.buf[self.len] = byte;
+        self.len += 1;
+        if self.len < 4 {
+            return None;
+        }
+        self.len = 0;
+        Some(self.buf)
+    }
+}
+
+/// Translate stdin line by line, reporting bad lines on stderr
+fn filter_lines(convert: impl Fn(&str) -> Result<String, String>) {
+    let stdin = std::io::stdin();
+    let mut failed = false;
+    for line in stdin.lock().lines().map_while(Result::ok) {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        match convert(line) {
+            Ok(out) => println!("{}", out),
+            Err(e) => {
+                eprintln!("{}", e);
+                failed = true;
+            }
+        }
+    }
+    if failed {
+        std::process::exit(1);
+    }
+}
+
+/// Copy one direction of a connection, logging each packet that passes
+fn relay(mut from: TcpStream, mut to: TcpStream, direction: &str) {
+    let mut framer = Framer::default();
+    let mut buf = [0; 256];
+    loop {
+        let len = match from.read(&mut buf) {
+            Ok(0) | Err(_) => break,
+            Ok(len) => len,
+        };
+        if to.write_all(&buf[..len]).is_err() {
+            break;
+        }
+        for packet in buf[..len].iter().filter_map(|b| framer.push(*b)) {
+            match parse_dsky_packet(packet) {
+                Some((channel, value)) => {
+                    eprintln!("{} {}", direction, format_word(channel, value))
+                }
+                None => eprintln!("{} invalid {}", direction, format_packet(&packet)),
+            }
+        }
+    }
+    let _ = to.shutdown(std::net::Shutdown::Both);
+}
+
+fn proxy(listen: &str, emulator: &str) -> std::io::Result<()> {
+    let listener = TcpListener::bind(listen)?;
+    eprintln!("Waiting for a frontend on {}", listen);
+    for frontend in listener.incoming() {
+        let frontend = frontend?;
+        let upstream = match TcpStream::connect(emulator) {
+            Ok(stream) => stream,
+            Err(e) => {
+                eprintln!("{}: {}", emulator, e);
+                continue;
+            }
+        };
+        eprintln!("Frontend {} connected", frontend.peer_addr()?);
+        let (down_from, down_to) = (upstream.try_clone()?, frontend.try_clone()?);
+        let downlink = std::thread::spawn(move || relay(down_from, down_to, "agc>dsky"));
+        relay(frontend, upstream, "dsky>agc");
+        let _ = downlink.join();
+        eprintln!("Frontend disconnected");
+    }
+    Ok(())
+}
+
+fn main() {
+    let matches = clap::App::new("dsky-protocol")
+        .about("Encode, decode and proxy DSKY packets")
+        .setting(clap::AppSettings::SubcommandRequiredElseHelp)
+        .subcommand(
+            clap::SubCommand::with_name("encode")
+                .about("Read octal CHANNEL VALUE lines, write packets as hex bytes"),
+        )
+        .subcommand(
+            clap::SubCommand::with_name("decode")
+                .about("Read packets as hex bytes, write octal CHANNEL VALUE lines"),
+        )
+        .subcommand(
+            clap::SubCommand::with_name("proxy")
+                .about("Pass traffic between emulator and frontend, logging it to stderr")
+                .arg(
+                    clap::Arg::with_name("listen")
+                        .long("listen")
+                        .takes_value(true)
+                        .default_value("127.0.0.1:19698")
+                        .help("Address the frontend connects to"),
+                )
+                .arg(
+                    clap::Arg::with_name("emulator")
+                        .long("emulator")
+                        .takes_value(true)
+                        .default_value(EMULATOR_ADDR)
+                        .help("Address of the emulator's DSKY port"),
+                ),
+        )
+        .get_matches();
+
+    match matches.subcommand() {
+        ("encode", _) => filter_lines(|line| {
+            let (channel, value) = parse_word(line)?;
+            Ok(format_packet(&generate_dsky_packet(channel, value)))
+        }),
+        ("decode", _) => filter_lines(|line| {
+            let packet = parse_packet(line)?;
+            parse_dsky_packet(packet)
+                .map(|(channel, value)| format_word(channel, value))
+                .ok_or_else(|| format!("Invalid packet: {}", line))
+        }),
+        ("proxy", Some(args)) => {
+            let listen = args.value_of("listen").unwrap_or_default();
+            let emulator = args.value_of("emulator").unwrap_or(EMULATOR_ADDR);
+            if let Err(e) = proxy(listen, emulator) {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod cli_tests {
+    use super::*;
+
+    #[test]
+    fn text_forms_round_trip() {
+        let (channel, value) = parse_word("015 00021").unwrap();
+        let packet = generate_dsky_packet(channel, value);
+        let text = format_packet(&packet);
+        assert_eq!(parse_packet(&text), Ok(packet));
+        assert_eq!(parse_packet(&text.replace(' ', "")), Ok(packet));
+        let (channel, value) = parse_dsky_packet(packet).unwrap();
+        assert_eq!(format_word(channel, value), "015 00021");
+
+        assert!(parse_word("15 100000").is_err());
+        assert!(parse_packet("01 02 03").is_err());
+    }
+
+    #[test]
+    fn framer_resynchronises() {
+        let packet = generate_dsky_packet(0o10, 0o12345);
+        let mut framer = Framer::default();
+        let stream = [&packet[1..], &packet[..], &packet[..2], &packet[..]].concat();
+        let packets: Vec<_> = stream.iter().filter_map(|b| framer.push(*b)).collect();
+        assert_eq!(packets, [packet, packet]);
+    }
+}
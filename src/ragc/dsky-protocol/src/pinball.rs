@@ -0,0 +1,342 @@
+/// Relay row carrying the digit pairs and sign bits of the display
+/// registers, as written by PINBALL to channel 010.
+const ROW_R3_MINUS: u8 = 1;
+const ROW_R3_PLUS: u8 = 2;
+const ROW_R2_MINUS: u8 = 4;
+const ROW_R2_PLUS: u8 = 5;
+const ROW_R1_MINUS: u8 = 6;
+const ROW_R1_PLUS: u8 = 7;
+
+/// Convert a relay digit code into its decimal value (None for blank)
+pub fn relay_digit(code: u8) -> Option<u8> {
+    match code {
+        21 => Some(0),
+        3 => Some(1),
+        25 => Some(2),
+        27 => Some(3),
+        15 => Some(4),
+        30 => Some(5),
+        28 => Some(6),
+        19 => Some(7),
+        29 => Some(8),
+        31 => Some(9),
+        _ => None,
+    }
+}
+
+/// Decoded contents of the DSKY numeric display
+#[derive(Clone, Copy, Default)]
+pub struct DisplayState {
+    digits: [u8; 15], // R1D1..R3D5 relay codes
+    noun: [u8; 2],
+    verb: [u8; 2],
+    prog: [u8; 2],
+    plus: [bool; 3],
+    minus: [bool; 3],
+}
+
+impl DisplayState {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Update the display from a channel 010 relay word
+    pub fn apply_relay_word(&mut self, val: u16) {
+        let row = ((val >> 11) & 0xF) as u8;
+        let sign = val & (1 << 10) != 0;
+        let c = ((val >> 5) & 0x1F) as u8;
+        let d = (val & 0x1F) as u8;
+
+        match row {
+            1..=7 => {
+                let idx = 13 - (row as usize - 1) * 2;
+                self.digits[idx] = c;
+                self.digits[idx + 1] = d;
+            }
+            8 => self.digits[0] = d,
+            9 => self.noun = [c, d],
+            10 => self.verb = [c, d],
+            11 => self.prog = [c, d],
+            _ => {}
+        }
+
+        match row {
+            ROW_R1_PLUS => self.plus[0] = sign,
+            ROW_R1_MINUS => self.minus[0] = sign,
+            ROW_R2_PLUS => self.plus[1] = sign,
+            ROW_R2_MINUS => self.minus[1] = sign,
+            ROW_R3_PLUS => self.plus[2] = sign,
+            ROW_R3_MINUS => self.minus[2] = sign,
+            _ => {}
+        }
+    }
+
+    fn pair(codes: [u8; 2]) -> Option<u8> {
+        Some(relay_digit(codes[0])? * 10 + relay_digit(codes[1])?)
+    }
+
+    pub fn noun(&self) -> Option<u8> {
+        Self::pair(self.noun)
+    }
+
+    pub fn verb(&self) -> Option<u8> {
+        Self::pair(self.verb)
+    }
+
+    pub fn prog(&self) -> Option<u8> {
+        Self::pair(self.prog)
+    }
+
+    /// PROG, VERB and NOUN digits as decimal values, None where blank, so
+    /// a half-keyed verb shows as it does on the DSKY
+    pub fn pair_digits(&self) -> [[Option<u8>; 2]; 3] {
+        [self.prog, self.verb, self.noun].map(|pair| pair.map(relay_digit))
+    }
+
+    /// Digits of register `reg` (0 = R1) as decimal values, None where blank
+    pub fn register_digits(&self, reg: usize) -> [Option<u8>; 5] {
+        let mut res = [None; 5];
+        for (i, d) in res.iter_mut().enumerate() {
+            *d = relay_digit(self.digits[reg * 5 + i]);
+        }
+        res
+    }
+
+    /// Whether every digit shows 8 and every register its plus sign, as
+    /// the lamp test leaves the display
+    pub fn all_eights(&self) -> bool {
+        let eight = |code: &u8| relay_digit(*code) == Some(8);
+        let pairs = self.prog.iter().chain(&self.verb).chain(&self.noun);
+        self.digits.iter().chain(pairs).all(eight) && self.plus.iter().all(|&plus| plus)
+    }
+
+    /// Sign of register `reg`: +1, -1, or None if unsigned (octal display)
+    pub fn register_sign(&self, reg: usize) -> Option<i32> {
+        match (self.plus[reg], self.minus[reg]) {
+            (true, false) => Some(1),
+            (false, true) => Some(-1),
+            _ => None,
+        }
+    }
+
+    /// Decode register `reg` according to the given display format
+    pub fn register_value(&self, reg: usize, format: Format) -> Option<f64> {
+        let digits = self.register_digits(reg);
+        match format {
+            Format::Octal => digits
+                .iter()
+                .try_fold(0u32, |acc, d| Some(acc * 8 + (*d)? as u32))
+                .map(|v| v as f64),
+            Format::Decimal(scale) => {
+                let sign = self.register_sign(reg)?;
+                let count = digits
+                    .iter()
+                    .try_fold(0i32, |acc, d| Some(acc * 10 + (*d)? as i32))?;
+                Some((sign * count) as f64 * scale)
+            }
+            Format::MinSec => {
+                let min = digits[0]? * 10 + digits[1]?;
+                let sec = digits[3]? * 10 + digits[4]?;
+                Some(min as f64 * 60.0 + sec as f64)
+            }
+        }
+    }
+
+    /// Decode R1-R3 in engineering units using the noun table of the loaded ROM
+    pub fn decode<'t>(&self, table: &'t NounTable) -> [Option<Quantity<'t>>; 3] {
+        let mut res = [None; 3];
+        let entry = match self.noun().and_then(|n| table.lookup(n)) {
+            Some(entry) => entry,
+            None => return res,
+        };
+
+        for (reg, component) in entry.components.iter().enumerate() {
+            if let Some(component) = component {
+                res[reg] = self
+                    .register_value(reg, component.format)
+                    .map(|value| Quantity {
+                        name: &component.name,
+                        value,
+                        unit: &component.unit,
+                    });
+            }
+        }
+        res
+    }
+}
+
+/// Display format of a noun component
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Format {
+    Octal,
+    /// Signed decimal, scaled by engineering units per displayed count
+    Decimal(f64),
+    /// Minutes and seconds, shown as MMbSS (value in seconds)
+    MinSec,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Component {
+    pub name: String,
+    pub format: Format,
+    pub unit: String,
+}
+
+#[derive(Clone, Debug)]
+pub struct NounEntry {
+    pub noun: u8,
+    pub components: [Option<Component>; 3],
+}
+
+/// Noun tables shipped for the bundled ROMs
+const BUNDLED: [(&str, &str); 2] = [
+    ("luminary99", include_str!("../nouns/luminary99.txt")),
+    ("comanche55", include_str!("../nouns/comanche55.txt")),
+];
+
+/// Noun scaling table for a given ROM version, read from a data file so
+/// a new rope needs data rather than code. See `nouns/` for the format.
+#[derive(Clone, Debug)]
+pub struct NounTable {
+    pub rom: String,
+    pub nouns: Vec<NounEntry>,
+}
+
+impl NounTable {
+    /// The table shipped for a bundled ROM, such as `luminary99`
+    pub fn bundled(rom: &str) -> Option<Self> {
+        let (_, text) = BUNDLED.iter().find(|(name, _)| *name == rom)?;
+        Some(Self::parse(text).expect("bundled noun table"))
+    }
+
+    /// Read a table from a file
+    pub fn load(path: &str) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+        Self::parse(&text).map_err(|e| format!("{}: {}", path, e))
+    }
+
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut rom = None;
+        let mut nouns: Vec<NounEntry> = Vec::new();
+        for (n, line) in text.lines().enumerate() {
+            let err = |what: &str| format!("line {}: {}", n + 1, what);
+            let line = line.split('#').next().unwrap_or("").trim();
+            if let Some(name) = line.strip_prefix("rom ") {
+                rom = Some(name.trim().to_string());
+                continue;
+            }
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            if fields.is_empty() {
+                continue;
+            }
+            if fields.len() < 6 {
+                return Err(err("expected noun, register, format, scale, unit and name"));
+            }
+
+            let noun: u8 = fields[0].parse().map_err(|_| err("bad noun"))?;
+            let reg = match fields[1] {
+                "1" => 0,
+                "2" => 1,
+                "3" => 2,
+                _ => return Err(err("register must be 1, 2 or 3")),
+            };
+            let format = match (fields[2], fields[3]) {
+                ("octal", "-") => Format::Octal,
+                ("minsec", "-") => Format::MinSec,
+                ("decimal", scale) => Format::Decimal(scale.parse().map_err(|_| err("bad scale"))?),
+                _ => return Err(err("format must be octal, minsec or decimal with a scale")),
+            };
+            let component = Component {
+                name: fields[5..].join(" "),
+                format,
+                unit: fields[4].to_string(),
+            };
+
+            let idx = match nouns.iter().position(|entry| entry.noun == noun) {
+                Some(idx) => idx,
+                None => {
+                    nouns.push(NounEntry {
+                        noun,
+                        components: [None, None, None],
+                    });
+                    nouns.len() - 1
+                }
+            };
+            let slot = &mut nouns[idx].components[reg];
+            if slot.is_some() {
+                return Err(err("register given twice"));
+            }
+            *slot = Some(component);
+        }
+        Ok(NounTable {
+            rom: rom.ok_or("no rom line")?,
+            nouns,
+        })
+    }
+
+    pub fn lookup(&self, noun: u8) -> Option<&NounEntry> {
+        self.nouns.iter().find(|n| n.noun == noun)
+    }
+}
+
+/// Decoded display register value
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Quantity<'t> {
+    pub name: &'t str,
+    pub value: f64,
+    pub unit: &'t str,
+}
+
+#[cfg(test)]
+mod pinball_tests {
+    use super::{DisplayState, NounTable};
+
+    const CODES: [u16; 10] = [21, 3, 25, 27, 15, 30, 28, 19, 29, 31];
+
+    fn word(row: u16, sign: bool, c: Option<u16>, d: Option<u16>) -> u16 {
+        let code = |x: Option<u16>| x.map(|x| CODES[x as usize]).unwrap_or(0);
+        (row << 11) | ((sign as u16) << 10) | (code(c) << 5) | code(d)
+    }
+
+    #[test]
+    fn decodes_noun_44() {
+        let mut disp = DisplayState::new();
+        disp.apply_relay_word(word(9, false, Some(4), Some(4)));
+
+        // R1 +01234, R2 -00567, R3 12b34
+        disp.apply_relay_word(word(8, false, None, Some(0)));
+        disp.apply_relay_word(word(7, true, Some(1), Some(2)));
+        disp.apply_relay_word(word(6, false, Some(3), Some(4)));
+        disp.apply_relay_word(word(5, false, Some(0), Some(0)));
+        disp.apply_relay_word(word(4, true, Some(5), Some(6)));
+        disp.apply_relay_word(word(3, false, Some(7), Some(1)));
+        disp.apply_relay_word(word(2, false, Some(2), None));
+        disp.apply_relay_word(word(1, false, Some(3), Some(4)));
+
+        let table = NounTable::bundled("comanche55").unwrap();
+        let res = disp.decode(&table);
+        assert_eq!(disp.noun(), Some(44));
+        assert_eq!(res[0].unwrap().name, "apocenter altitude");
+        assert!((res[0].unwrap().value - 123.4).abs() < 1e-9);
+        assert!((res[1].unwrap().value + 56.7).abs() < 1e-9);
+        assert_eq!(res[2].unwrap().value, 12.0 * 60.0 + 34.0);
+    }
+
+    #[test]
+    fn noun_tables_load_from_data() {
+        let luminary = NounTable::bundled("luminary99").unwrap();
+        assert_eq!(luminary.rom, "LUMINARY099");
+        assert!(luminary.lookup(43).is_some() && luminary.lookup(44).is_none());
+        assert!(NounTable::bundled("sundance306").is_none());
+
+        for bad in [
+            "36 1 decimal 1 h hours",
+            "rom X\n36 4 decimal 1 h hours",
+            "rom X\n36 1 octal 1 h hours",
+            "rom X\n36 1 decimal 1 h",
+            "rom X\n36 1 decimal 1 h hours\n36 1 octal - h hours",
+        ] {
+            assert!(NounTable::parse(bad).is_err(), "{}", bad);
+        }
+    }
+}
@@ -0,0 +1,220 @@
+//! Uplink scripts: ground command loads as the Manned Space Flight Network
+//! sent them. Each DSKY keystroke crosses the link as one triply redundant
+//! 15-bit word, `C ~C C` (keycode, its complement, keycode), which UPRUPT
+//! checks before passing the keycode to PINBALL.
+//!
+//! A script holds one command per line, with `#` comments:
+//!
+//! ```text
+//! keys V37E00E          # Keystrokes: V N E C R K + - and digits
+//! V71 1501 12345 00002  # Block load: starting ECADR, then the words
+//! V72 1501 00001 1607 3 # Scattered load: ECADR and word pairs
+//! word 13505            # Raw uplink word, checked like UPRUPT does
+//! wait 500              # Milliseconds of silence
+//! ```
+//!
+//! Scripts start sending at power on, so most begin with a wait for the
+//! software to come up.
+//!
+//! Loads are keyed as the ground did: the verb, the index (component
+//! count), each component with ENTER, then V33E to accept the load.
+use crate::capture::{CaptureRecord, CAPTURE_UPLINK};
+
+/// Up-data link rate into INLINK, in bits per second
+pub const UPLINK_BIT_RATE: u32 = 1000;
+
+/// Bits per uplink word: a start bit and the 15 data bits
+const WORD_BITS: u64 = 16;
+
+/// Most words one V71 block load carries
+pub const MAX_BLOCK_WORDS: usize = 18;
+
+/// Most address and word pairs one V72 scattered load carries
+pub const MAX_SCATTER_PAIRS: usize = 9;
+
+/// One step of an uplink script
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum UplinkItem {
+    Word(u16),  // Triply redundant uplink word
+    Pause(u32), // Milliseconds
+}
+
+/// Uplink word for a DSKY keycode
+pub fn encode_key(keycode: u16) -> u16 {
+    let key = keycode & 0o37;
+    (key << 10) | ((!key & 0o37) << 5) | key
+}
+
+/// Keycode carried by an uplink word, or None if the three copies
+/// disagree and UPRUPT would reject it
+pub fn decode_word(word: u16) -> Option<u16> {
+    let key = word & 0o37;
+    let complement = (word >> 5) & 0o37;
+    let repeat = (word >> 10) & 0o37;
+    (repeat == key && complement == !key & 0o37).then_some(key)
+}
+
+fn keycode(key: char) -> Option<u16> {
+    match key {
+        'V' => Some(0o21),
+        'N' => Some(0o37),
+        'E' => Some(0o34),
+        'C' => Some(0o36),
+        'R' => Some(0o22),
+        'K' => Some(0o31),
+        '+' => Some(0o32),
+        '-' => Some(0o33),
+        '0' => Some(0o20),
+        '1'..='9' => key.to_digit(10).map(|d| d as u16),
+        _ => None,
+    }
+}
+
+fn push_keys(items: &mut Vec<UplinkItem>, keys: &str) -> Result<(), String> {
+    for key in keys.chars() {
+        let code = keycode(key).ok_or_else(|| format!("Invalid key {}", key))?;
+        items.push(UplinkItem::Word(encode_key(code)));
+    }
+    Ok(())
+}
+
+fn octal(field: &str, max: u16) -> Result<u16, String> {
+    match u16::from_str_radix(field, 8) {
+        Ok(value) if value <= max => Ok(value),
+        _ => Err(format!("Invalid octal value {}", field)),
+    }
+}
+
+/// Keystrokes for a V71 or V72 load, after checking it the way the
+/// software checks the index: V71 takes 1 to 18 words after its address,
+/// V72 1 to 9 address and word pairs
+fn load_keys(verb: &str, fields: &[&str]) -> Result<String, String> {
+    let pairs = verb == "V72";
+    let words = match pairs {
+        true if !fields.len().is_multiple_of(2) => {
+            return Err("V72 needs address and word pairs".into())
+        }
+        true => fields.len() / 2,
+        false => fields.len().saturating_sub(1),
+    };
+    let max = if pairs {
+        MAX_SCATTER_PAIRS
+    } else {
+        MAX_BLOCK_WORDS
+    };
+    if !(1..=max).contains(&words) {
+        return Err(format!(
+            "{} loads 1 to {} entries, not {}",
+            verb, max, words
+        ));
+    }
+
+    let index = fields.len() + 1;
+    let mut keys = format!("{}E{:o}E", verb, index);
+    for (idx, field) in fields.iter().enumerate() {
+        let address = idx == 0 || (pairs && idx % 2 == 0);
+        let value = match address {
+            true => octal(field, 0o3777).map_err(|_| format!("Invalid ECADR {}", field))?,
+            false => octal(field, 0o77777)?,
+        };
+        keys.push_str(&format!("{:05o}E", value));
+    }
+    keys.push_str("V33E");
+    Ok(keys)
+}
+
+/// Parse an uplink script into the words to send
+pub fn parse_uplink(text: &str) -> Result<Vec<UplinkItem>, String> {
+    let mut items = Vec::new();
+    for (idx, line) in text.lines().enumerate() {
+        let line = line.split('#').next().unwrap_or_default();
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        let invalid = |what: String| format!("line {}: {}", idx + 1, what);
+        match fields.as_slice() {
+            [] => {}
+            ["keys", keys @ ..] => push_keys(&mut items, &keys.concat()).map_err(invalid)?,
+            [verb @ ("V71" | "V72"), rest @ ..] => {
+                let keys = load_keys(verb, rest).map_err(invalid)?;
+                push_keys(&mut items, &keys).map_err(invalid)?;
+            }
+            ["word", word] => {
+                let word = octal(word, 0o77777).map_err(invalid)?;
+                if decode_word(word).is_none() {
+                    return Err(invalid(format!("{:05o} fails the redundancy check", word)));
+                }
+                items.push(UplinkItem::Word(word));
+            }
+            ["wait", ms] => {
+                let ms = ms
+                    .parse()
+                    .map_err(|_| invalid(format!("Invalid wait {}", ms)))?;
+                items.push(UplinkItem::Pause(ms));
+            }
+            [other, ..] => return Err(invalid(format!("Unknown command {}", other))),
+        }
+    }
+    Ok(items)
+}
+
+/// Time the script's words as they would arrive at `bit_rate`, starting at
+/// `start_us`, as capture records for replay
+pub fn uplink_records(items: &[UplinkItem], start_us: u64, bit_rate: u32) -> Vec<CaptureRecord> {
+    let word_us = WORD_BITS * 1_000_000 / bit_rate.max(1) as u64;
+    let mut time_us = start_us;
+    let mut records = Vec::new();
+    for item in items.iter() {
+        match *item {
+            UplinkItem::Word(value) => {
+                time_us += word_us;
+                records.push(CaptureRecord {
+                    time_us,
+                    channel: CAPTURE_UPLINK,
+                    value,
+                });
+            }
+            UplinkItem::Pause(ms) => time_us += ms as u64 * 1000,
+        }
+    }
+    records
+}
+
+#[cfg(test)]
+mod uplink_tests {
+    use super::*;
+
+    #[test]
+    fn loads_become_redundant_keystroke_words() {
+        assert_eq!(encode_key(0o21), 0o21 << 10 | 0o16 << 5 | 0o21);
+        assert_eq!(decode_word(encode_key(0o34)), Some(0o34));
+        assert_eq!(decode_word(encode_key(0o34) ^ 0o40), None);
+
+        let items = parse_uplink("V71 1501 12345 2 # Two words\nwait 100\n").unwrap();
+        let keys: Vec<u16> = items
+            .iter()
+            .filter_map(|item| match item {
+                UplinkItem::Word(word) => decode_word(*word),
+                UplinkItem::Pause(_) => None,
+            })
+            .collect();
+        // V71E 4E 01501E 12345E 00002E V33E
+        assert_eq!(&keys[..5], [0o21, 7, 1, 0o34, 4]);
+        assert_eq!(keys.len(), 4 + 2 + 3 * 6 + 4);
+        assert_eq!(items.last(), Some(&UplinkItem::Pause(100)));
+
+        let records = uplink_records(&items[..2], 1000, UPLINK_BIT_RATE);
+        assert_eq!(records[0].time_us, 17_000);
+        assert_eq!(records[1].time_us, 33_000);
+
+        for bad in [
+            "V71 1501",
+            "V72 1501 1 1607",
+            "V71 4000 1",
+            "word 12345",
+            "fly",
+        ] {
+            assert!(parse_uplink(bad).is_err(), "{}", bad);
+        }
+        let full = format!("V71 1501{}", " 1".repeat(MAX_BLOCK_WORDS + 1));
+        assert!(parse_uplink(&full).is_err());
+    }
+}
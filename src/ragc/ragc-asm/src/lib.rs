@@ -0,0 +1,538 @@
+//! Assembler for the part of yaYUL that small programs need: every basic
+//! instruction and extracode, OCT and DEC constants, names for erasable
+//! and other addresses, with the code laid out in fixed-fixed memory
+//! (4000-7777).
+//!
+//! ```text
+//! OUT0    =       10              # Names for addresses and channels
+//!         SETLOC  100             # Where the following words go
+//! COUNT   ERASE                   # Label in the first column
+//!         SETLOC  4000
+//! START   CA      LIMIT
+//!         EXTEND
+//!         WRITE   10
+//!         TCF     START
+//! LIMIT   DEC     -9
+//! ```
+//!
+//! Operands are names and octal numbers joined with + and -. As in flight
+//! sources, extracodes must follow an EXTEND, optionally through an INDEX,
+//! and DAS, DXCH, DCA and DCS name the first word of the pair. `ERASE n`
+//! reserves n+1 words of erasable. Names can be used before they are
+//! defined, except by `=`, `SETLOC` and `ERASE`. Code starts at 4000 if
+//! no `SETLOC` says otherwise.
+use std::collections::BTreeMap;
+
+use ragc_core::constants::address_space::{fixed_fixed_bank, FIXED_BANK_SIZE, FIXED_FIXED_START};
+use ragc_core::memory::rom::{encode_word, RopeImage, BANK_MAPPING};
+
+/// Operand an instruction takes, and how it is checked
+#[derive(Clone, Copy, Debug)]
+enum Operand {
+    None,           // Implied: the word is complete
+    Any,            // 12-bit address
+    Erasable,       // 10-bit erasable address
+    Fixed,          // 12-bit fixed-memory address
+    Channel,        // 9-bit I/O channel
+    DoubleAny,      // 12-bit address of a pair, assembled as its second word
+    DoubleErasable, // 10-bit erasable address of a pair, likewise
+}
+
+/// Basic instructions and the implied-address forms among them
+const BASIC: [(&str, u16, Operand); 25] = [
+    ("TC", 0o00000, Operand::Any),
+    ("CCS", 0o10000, Operand::Erasable),
+    ("TCF", 0o10000, Operand::Fixed),
+    ("DAS", 0o20000, Operand::DoubleErasable),
+    ("LXCH", 0o22000, Operand::Erasable),
+    ("INCR", 0o24000, Operand::Erasable),
+    ("ADS", 0o26000, Operand::Erasable),
+    ("CA", 0o30000, Operand::Any),
+    ("CS", 0o40000, Operand::Any),
+    ("INDEX", 0o50000, Operand::Erasable),
+    ("DXCH", 0o52000, Operand::DoubleErasable),
+    ("TS", 0o54000, Operand::Erasable),
+    ("XCH", 0o56000, Operand::Erasable),
+    ("AD", 0o60000, Operand::Any),
+    ("MASK", 0o70000, Operand::Any),
+    ("EXTEND", 0o00006, Operand::None),
+    ("INHINT", 0o00004, Operand::None),
+    ("RELINT", 0o00003, Operand::None),
+    ("RESUME", 0o50017, Operand::None),
+    ("RETURN", 0o00002, Operand::None),
+    ("DOUBLE", 0o60000, Operand::None),
+    ("DDOUBL", 0o20001, Operand::None),
+    ("COM", 0o40000, Operand::None),
+    ("NOOP", 0o30000, Operand::None),
+    ("ZL", 0o22007, Operand::None),
+];
+
+/// Extracodes, valid only after EXTEND
+const EXTRACODES: [(&str, u16, Operand); 20] = [
+    ("READ", 0o00000, Operand::Channel),
+    ("WRITE", 0o01000, Operand::Channel),
+    ("RAND", 0o02000, Operand::Channel),
+    ("WAND", 0o03000, Operand::Channel),
+    ("ROR", 0o04000, Operand::Channel),
+    ("WOR", 0o05000, Operand::Channel),
+    ("RXOR", 0o06000, Operand::Channel),
+    ("EDRUPT", 0o07000, Operand::Channel),
+    ("DV", 0o10000, Operand::Erasable),
+    ("BZF", 0o10000, Operand::Fixed),
+    ("MSU", 0o20000, Operand::Erasable),
+    ("QXCH", 0o22000, Operand::Erasable),
+    ("AUG", 0o24000, Operand::Erasable),
+    ("DIM", 0o26000, Operand::Erasable),
+    ("DCA", 0o30000, Operand::DoubleAny),
+    ("DCS", 0o40000, Operand::DoubleAny),
+    ("INDEX", 0o50000, Operand::Any),
+    ("SU", 0o60000, Operand::Erasable),
+    ("BZMF", 0o60000, Operand::Fixed),
+    ("MP", 0o70000, Operand::Any),
+];
+
+/// One source line with code on it
+struct Line<'a> {
+    number: usize,
+    label: Option<&'a str>,
+    op: &'a str,
+    operand: String,
+}
+
+fn parse(source: &str) -> Vec<Line<'_>> {
+    let mut lines = Vec::new();
+    for (idx, text) in source.lines().enumerate() {
+        let code = text.split('#').next().unwrap_or_default();
+        let mut fields = code.split_whitespace();
+        let label = match code.starts_with(|c: char| !c.is_whitespace()) {
+            true => fields.next(),
+            false => None,
+        };
+        let op = fields.next().unwrap_or_default();
+        if label.is_none() && op.is_empty() {
+            continue;
+        }
+        lines.push(Line {
+            number: idx + 1,
+            label,
+            op,
+            operand: fields.collect::<Vec<_>>().join(" "),
+        });
+    }
+    lines
+}
+
+/// Words in fixed-fixed memory and the names defined for them
+pub struct Assembly {
+    words: BTreeMap<u16, u16>,
+    symbols: BTreeMap<String, u16>,
+}
+
+impl Assembly {
+    /// Value of a name: the address a label marks, or what `=` gave it
+    pub fn symbol(&self, name: &str) -> Option<u16> {
+        self.symbols.get(name).copied()
+    }
+
+    /// Assembled words by address
+    pub fn words(&self) -> impl Iterator<Item = (u16, u16)> + '_ {
+        self.words.iter().map(|(addr, word)| (*addr, *word))
+    }
+
+    /// Rope image holding the program, TC 0 everywhere else
+    pub fn rope(&self) -> Box<RopeImage> {
+        let mut rope = Box::new([[0; 1024]; 36]);
+        for (addr, word) in self.words() {
+            let addr = addr as usize;
+            rope[BANK_MAPPING[fixed_fixed_bank(addr)]][addr % FIXED_BANK_SIZE] = encode_word(word);
+        }
+        rope
+    }
+}
+
+struct Assembler {
+    symbols: BTreeMap<String, u16>,
+    here: u16,
+}
+
+impl Assembler {
+    /// Value of `+`/`-` separated names and octal numbers
+    fn eval(&self, expr: &str) -> Result<u16, String> {
+        if expr.is_empty() {
+            return Err("missing operand".to_string());
+        }
+        let mut total: i32 = 0;
+        let mut sign = 1;
+        let mut term = String::new();
+        for c in expr.chars().chain(std::iter::once('+')) {
+            match c {
+                '+' | '-' => {
+                    if !term.is_empty() {
+                        total += sign * self.term(&term)?;
+                        term.clear();
+                    }
+                    sign = if c == '-' { -1 } else { 1 };
+                }
+                ' ' => {}
+                _ => term.push(c),
+            }
+        }
+        match total {
+            0..=0o77777 => Ok(total as u16),
+            _ => Err(format!("{} is out of range", expr)),
+        }
+    }
+
+    fn term(&self, term: &str) -> Result<i32, String> {
+        match term.chars().next() {
+            Some('0'..='9') => {
+                i32::from_str_radix(term, 8).map_err(|_| format!("{} is not an octal number", term))
+            }
+            _ => match self.symbols.get(term) {
+                Some(value) => Ok(*value as i32),
+                None => Err(format!("{} is not defined", term)),
+            },
+        }
+    }
+
+    fn define(&mut self, name: &str, value: u16) -> Result<(), String> {
+        match self.symbols.insert(name.to_string(), value) {
+            Some(_) => Err(format!("{} is defined twice", name)),
+            None => Ok(()),
+        }
+    }
+
+    /// First pass over one line: define its names, advance the location
+    /// past it and say where it starts
+    fn locate(&mut self, line: &Line) -> Result<u16, String> {
+        match line.op {
+            "=" | "EQUALS" => {
+                let name = line.label.ok_or("= needs a name")?;
+                let value = self.eval(&line.operand)?;
+                self.define(name, value)?;
+                return Ok(self.here);
+            }
+            "SETLOC" => {
+                self.here = self.eval(&line.operand)?;
+                if let Some(name) = line.label {
+                    self.define(name, self.here)?;
+                }
+                return Ok(self.here);
+            }
+            _ => {}
+        }
+
+        let start = self.here;
+        if let Some(name) = line.label {
+            self.define(name, start)?;
+        }
+        let (words, end) = match line.op {
+            "" => (0, 0o10000),
+            "ERASE" if line.operand.is_empty() => (1, 0o2000),
+            "ERASE" => (self.eval(&line.operand)? + 1, 0o2000),
+            _ => (1, 0o10000),
+        };
+        self.here = start + words;
+        match self.here <= end {
+            true => Ok(start),
+            false => Err(format!("{} runs past {:o}", line.op, end - 1)),
+        }
+    }
+
+    /// Second pass over one line: the word it assembles to, if any
+    fn encode(&self, line: &Line, extended: bool) -> Result<Option<u16>, String> {
+        match line.op {
+            "" | "=" | "EQUALS" | "ERASE" | "SETLOC" => return Ok(None),
+            "OCT" => return self.eval(&line.operand).map(Some),
+            "DEC" => return decimal(&line.operand).map(Some),
+            _ => {}
+        }
+
+        let table: &[(&str, u16, Operand)] = match extended {
+            true => &EXTRACODES,
+            false => &BASIC,
+        };
+        let (base, operand) = match table.iter().find(|(name, _, _)| *name == line.op) {
+            Some((_, base, operand)) => (*base, *operand),
+            None if EXTRACODES.iter().any(|(name, _, _)| *name == line.op) => {
+                return Err(format!("{} needs an EXTEND before it", line.op))
+            }
+            None if BASIC.iter().any(|(name, _, _)| *name == line.op) => {
+                return Err(format!("{} cannot follow EXTEND", line.op))
+            }
+            None => return Err(format!("unknown operation {}", line.op)),
+        };
+
+        let (k, limit) = match operand {
+            Operand::None if line.operand.is_empty() => return Ok(Some(base)),
+            Operand::None => return Err(format!("{} takes no operand", line.op)),
+            Operand::Any => (self.eval(&line.operand)?, 0o10000),
+            Operand::Erasable => (self.eval(&line.operand)?, 0o2000),
+            Operand::Channel => (self.eval(&line.operand)?, 0o1000),
+            Operand::DoubleAny => (self.eval(&line.operand)? + 1, 0o10000),
+            Operand::DoubleErasable => (self.eval(&line.operand)? + 1, 0o2000),
+            Operand::Fixed => match self.eval(&line.operand)? {
+                k if k < 0o2000 => return Err(format!("{} needs a fixed address", line.op)),
+                k => (k, 0o10000),
+            },
+        };
+        match k < limit {
+            true => Ok(Some(base | k)),
+            false => Err(format!("{} does not fit {}", line.operand, line.op)),
+        }
+    }
+}
+
+/// One's complement word for a signed decimal
+fn decimal(text: &str) -> Result<u16, String> {
+    let value: i32 = text
+        .replace(' ', "")
+        .parse()
+        .map_err(|_| format!("{} is not a decimal number", text))?;
+    match value {
+        0..=0o37777 => Ok(value as u16),
+        -0o37777..=-1 => Ok(!(-value) as u16 & 0o77777),
+        _ => Err(format!("{} does not fit a word", text)),
+    }
+}
+
+/// Assemble `source`; errors give the line they were found on
+pub fn assemble(source: &str) -> Result<Assembly, String> {
+    let lines = parse(source);
+    let at = |line: &Line, e: String| format!("line {}: {}", line.number, e);
+
+    let mut asm = Assembler {
+        symbols: BTreeMap::new(),
+        here: FIXED_FIXED_START as u16,
+    };
+    let mut starts = Vec::with_capacity(lines.len());
+    for line in lines.iter() {
+        starts.push(asm.locate(line).map_err(|e| at(line, e))?);
+    }
+
+    let mut words = BTreeMap::new();
+    let mut extended = false;
+    for (line, addr) in lines.iter().zip(starts) {
+        let word = match asm.encode(line, extended).map_err(|e| at(line, e))? {
+            Some(word) => word,
+            None => continue,
+        };
+        if !(FIXED_FIXED_START as u16..0o10000).contains(&addr) {
+            return Err(at(line, format!("{:o} is not in fixed-fixed memory", addr)));
+        }
+        if words.insert(addr, word).is_some() {
+            return Err(at(line, format!("{:o} is assembled twice", addr)));
+        }
+        // EXTEND carries through an INDEX to the instruction it modifies
+        extended = match line.op {
+            "EXTEND" => true,
+            "INDEX" => extended,
+            _ => false,
+        };
+    }
+    Ok(Assembly {
+        words,
+        symbols: asm.symbols,
+    })
+}
+
+#[cfg(test)]
+mod asm_tests {
+    use super::assemble;
+
+    #[test]
+    fn assembles_words_and_names() {
+        let asm = assemble(
+            "
+OUT0    =       10
+        SETLOC  100
+COUNT   ERASE
+PAIR    ERASE   1
+NEXT    ERASE
+        SETLOC  4000
+START   CA      LIMIT           # Forward reference
+        EXTEND
+        INDEX   COUNT
+        DCA     PAIR
+        EXTEND
+        WRITE   OUT0
+        DDOUBL
+        EXTEND
+        BZMF    START
+LIMIT   DEC     -9
+        OCT     77777
+",
+        )
+        .unwrap();
+        assert_eq!(
+            (asm.symbol("COUNT"), asm.symbol("PAIR"), asm.symbol("NEXT")),
+            (Some(0o100), Some(0o101), Some(0o103))
+        );
+        let words: Vec<(u16, u16)> = asm.words().collect();
+        assert_eq!(
+            words,
+            [
+                (0o4000, 0o34011),
+                (0o4001, 0o00006),
+                (0o4002, 0o50100),
+                (0o4003, 0o30102),
+                (0o4004, 0o00006),
+                (0o4005, 0o01010),
+                (0o4006, 0o20001),
+                (0o4007, 0o00006),
+                (0o4010, 0o64000),
+                (0o4011, 0o77766),
+                (0o4012, 0o77777),
+            ]
+        );
+    }
+
+    #[test]
+    fn encodes_extracodes() {
+        let asm = assemble(
+            "
+        EXTEND
+        READ    15
+        EXTEND
+        WOR     13
+        EXTEND
+        EDRUPT  0
+        EXTEND
+        DV      100
+        EXTEND
+        MSU     100
+        EXTEND
+        QXCH    100
+        EXTEND
+        AUG     100
+        EXTEND
+        DIM     100
+        EXTEND
+        DCS     100
+        EXTEND
+        SU      100
+        EXTEND
+        BZF     4000
+        EXTEND
+        BZMF    4000
+        EXTEND
+        INDEX   4000            # Extended INDEX reaches fixed memory
+        MP      4000
+",
+        )
+        .unwrap();
+        let words: Vec<u16> = asm.words().map(|(_, word)| word).collect();
+        assert_eq!(
+            words,
+            [
+                0o00006, 0o00015, 0o00006, 0o05013, 0o00006, 0o07000, 0o00006, 0o10100, 0o00006,
+                0o20100, 0o00006, 0o22100, 0o00006, 0o24100, 0o00006, 0o26100, 0o00006, 0o40101,
+                0o00006, 0o60100, 0o00006, 0o14000, 0o00006, 0o64000, 0o00006, 0o54000, 0o74000,
+            ]
+        );
+    }
+
+    /// Words assembled by hand and checked by running them before there was
+    /// an assembler: the gallery's Fibonacci program
+    #[test]
+    fn matches_a_hand_assembled_program() {
+        let asm = assemble(
+            "
+        SETLOC  100
+FIB1    ERASE
+FIB2    ERASE
+
+        SETLOC  4000
+START   CA      ONE
+        XCH     FIB1
+        CA      ONE
+        XCH     FIB2
+NEXT    CA      FIB1
+        AD      FIB2
+        XCH     FIB2
+        XCH     FIB1
+        CS      LAST
+        AD      FIB1
+        EXTEND
+        BZF     DONE
+        TCF     NEXT
+DONE    TCF     DONE
+
+ONE     DEC     1
+LAST    DEC     6765
+",
+        )
+        .unwrap();
+        let words: Vec<u16> = asm.words().map(|(_, word)| word).collect();
+        assert_eq!(
+            words,
+            [
+                0o34016, 0o56100, 0o34016, 0o56101, 0o30100, 0o60101, 0o56101, 0o56100, 0o44017,
+                0o60100, 0o00006, 0o14015, 0o14004, 0o14015, 0o00001, 0o15155,
+            ]
+        );
+    }
+
+    #[test]
+    fn errors_name_the_line() {
+        let error = |source: &str| assemble(source).err().unwrap();
+        assert_eq!(
+            error("\n        CA      NOWHERE"),
+            "line 2: NOWHERE is not defined"
+        );
+        assert_eq!(
+            error("        BZF     4000"),
+            "line 1: BZF needs an EXTEND before it"
+        );
+        assert_eq!(
+            error("        EXTEND\n        CA      0"),
+            "line 2: CA cannot follow EXTEND"
+        );
+        assert_eq!(
+            error("        TCF     100"),
+            "line 1: TCF needs a fixed address"
+        );
+        assert_eq!(
+            error("        TS      2000"),
+            "line 1: 2000 does not fit TS"
+        );
+        assert_eq!(
+            error("        SETLOC  100\n        CA      0"),
+            "line 2: 100 is not in fixed-fixed memory"
+        );
+        assert_eq!(error("        ERASE"), "line 1: ERASE runs past 1777");
+        assert_eq!(
+            error("A       =       0\nA       CA      A"),
+            "line 2: A is defined twice"
+        );
+        assert_eq!(
+            error("START   CA      0\nSTART   CA      0"),
+            "line 2: START is defined twice"
+        );
+        assert_eq!(error("        FOO     0"), "line 1: unknown operation FOO");
+        assert_eq!(
+            error("        CA      10000"),
+            "line 1: 10000 does not fit CA"
+        );
+        assert_eq!(
+            error("        EXTEND\n        READ    1000"),
+            "line 2: 1000 does not fit READ"
+        );
+        assert_eq!(
+            error("        OCT     100000"),
+            "line 1: 100000 is out of range"
+        );
+        assert_eq!(
+            error("        DEC     16384"),
+            "line 1: 16384 does not fit a word"
+        );
+        assert_eq!(
+            error("        SETLOC  7777\n        CA      0\n        CA      0"),
+            "line 3: CA runs past 7777"
+        );
+        assert_eq!(
+            error("        CA      0\n        SETLOC  4000\n        CA      0"),
+            "line 3: 4000 is assembled twice"
+        );
+    }
+}
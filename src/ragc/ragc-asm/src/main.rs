@@ -0,0 +1,35 @@
+//! `ragc-asm <source> <rope>` assembles a yaYUL source into a rope image
+//! that `ragc run --rope` loads.
+use std::process::exit;
+
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    let (source, rope) = match args.as_slice() {
+        [_, source, rope] => (source, rope),
+        _ => {
+            eprintln!("usage: ragc-asm <source> <rope>");
+            exit(2);
+        }
+    };
+
+    let text = std::fs::read_to_string(source).unwrap_or_else(|e| {
+        eprintln!("{}: {}", source, e);
+        exit(1);
+    });
+    let assembly = ragc_asm::assemble(&text).unwrap_or_else(|e| {
+        eprintln!("{}: {}", source, e);
+        exit(1);
+    });
+
+    // Image words are already big-endian; write them as they lie
+    let bytes: Vec<u8> = assembly
+        .rope()
+        .iter()
+        .flatten()
+        .flat_map(|word| word.to_ne_bytes())
+        .collect();
+    if let Err(e) = std::fs::write(rope, bytes) {
+        eprintln!("{}: {}", rope, e);
+        exit(1);
+    }
+}
@@ -0,0 +1,26 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use ragc_core::cpu::Cpu;
+use ragc_core::memory::MemoryMapBuilder;
+
+// CA 200, AD 201, XCH 202, TC 100 looping in erasable memory
+const PROGRAM: [u16; 4] = [0o30200, 0o60201, 0o56202, 0o00100];
+
+fn step(c: &mut Criterion) {
+    let mem = MemoryMapBuilder::new()
+        .erasable(0, 0o100, &PROGRAM)
+        .erasable(0, 0o200, &[0o1, 0o2])
+        .build();
+    let mut cpu = Cpu::new(mem);
+    cpu.update_pc(0o100);
+
+    c.bench_function("step_1000", |b| {
+        b.iter(|| {
+            for _ in 0..1000 {
+                black_box(cpu.step());
+            }
+        })
+    });
+}
+
+criterion_group!(benches, step);
+criterion_main!(benches);
@@ -0,0 +1,200 @@
+//! What this build of the emulator implements, for frontends and mission
+//! files to check at startup instead of failing part way through a run.
+//! Everything here is read off the tables the emulator itself runs from.
+use crate::constants::special_registers::*;
+use crate::constants::timers::*;
+use crate::disasm::RUPT_VECTORS;
+use crate::isa::{instruction_set, IsaEntry, ISA_CAPACITY};
+use crate::memory::channels::{Axis, ChannelSpec, Discrete, AXES, CHANNELS, DISCRETES};
+use crate::memory::mods::RuptRequest;
+use core::fmt;
+
+/// A counter cell and whether the emulator counts it itself
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Counter {
+    pub name: &'static str,
+    pub address: usize,
+    pub clocked: bool, // Counted by the timers rather than a peripheral
+}
+
+const fn timer(name: &'static str, address: usize) -> Counter {
+    Counter {
+        name,
+        address,
+        clocked: true,
+    }
+}
+
+const fn fed(name: &'static str, address: usize) -> Counter {
+    Counter {
+        name,
+        address,
+        clocked: false,
+    }
+}
+
+/// Counter cells that accept PINC, MINC, PCDU, MCDU, DINC, SHINC and SHANC
+pub const COUNTERS: [Counter; 28] = [
+    timer("TIME2", TIMER_2_ADDRESS),
+    timer("TIME1", TIMER_1_ADDRESS),
+    timer("TIME3", TIMER_3_ADDRESS),
+    timer("TIME4", TIMER_4_ADDRESS),
+    timer("TIME5", TIMER_5_ADDRESS),
+    fed("CDUX", SPECIAL_REGISTER_CONTROL_DISPLAY_X),
+    fed("CDUY", SPECIAL_REGISTER_CONTROL_DISPLAY_Y),
+    fed("CDUZ", SPECIAL_REGISTER_CONTROL_DISPLAY_Z),
+    fed("OPTY", SPECIAL_REGISTER_OPTICAL_Y),
+    fed("OPTX", SPECIAL_REGISTER_OPTICAL_X),
+    fed("PIPAX", SPECIAL_REGISTER_INERTIAL_X),
+    fed("PIPAY", SPECIAL_REGISTER_INERTIAL_Y),
+    fed("PIPAZ", SPECIAL_REGISTER_INERTIAL_Z),
+    fed("RHCP", SPECIAL_REGISTER_RHC_PITCH),
+    fed("RHCY", SPECIAL_REGISTER_RHC_YAW),
+    fed("RHCR", SPECIAL_REGISTER_RHC_ROLL),
+    fed("INLINK", SPECIAL_REGISTER_DATA_INPUT),
+    fed("RNRAD", SPECIAL_REGISTER_NAV_RADAR),
+    fed("GYROCTR", SPECIAL_REGISTER_GYRO_CTRL),
+    fed("CDUXCMD", SPECIAL_REGISTER_CONTROL_X_CMD),
+    fed("CDUYCMD", SPECIAL_REGISTER_CONTROL_Y_CMD),
+    fed("CDUZCMD", SPECIAL_REGISTER_CONTROL_Z_CMD),
+    fed("OPTYCMD", SPECIAL_REGISTER_OPTICAL_Y_CMD),
+    fed("OPTXCMD", SPECIAL_REGISTER_OPTICAL_X_CMD),
+    fed("THRUST", SPECIAL_REGISTER_THRUST),
+    fed("LEMONM", SPECIAL_REGISTER_MAINTENANCE),
+    fed("OUTLINK", SPECIAL_REGISTER_DATA_OUTPUT),
+    fed("ALTM", SPECIAL_REGISTER_ALTITUDE),
+];
+
+/// Interrupts a peripheral can request
+pub const INTERRUPTS: [RuptRequest; 9] = [
+    RuptRequest::T5rupt,
+    RuptRequest::T3rupt,
+    RuptRequest::T4rupt,
+    RuptRequest::Keyrupt1,
+    RuptRequest::Keyrupt2,
+    RuptRequest::Uprupt,
+    RuptRequest::Downrupt,
+    RuptRequest::Radarupt,
+    RuptRequest::Handrupt,
+];
+
+/// Cargo features this build was compiled with, of those that change what
+/// the core can do
+pub const FEATURES: &[(&str, bool)] = &[
+    ("alloc", cfg!(feature = "alloc")),
+    ("events", cfg!(feature = "events")),
+    ("ringtrace", cfg!(feature = "ringtrace")),
+    ("strict-words", cfg!(feature = "strict-words")),
+];
+
+/// What the emulator implements
+#[derive(Clone, Debug)]
+pub struct Capabilities {
+    pub instructions: heapless::Vec<IsaEntry, ISA_CAPACITY>,
+    pub channels: &'static [ChannelSpec],
+    pub counters: &'static [Counter],
+    pub interrupts: &'static [RuptRequest],
+    pub discretes: &'static [Discrete], // Inputs peripherals and scenarios assert by name
+    pub axes: &'static [Axis],
+    pub features: &'static [(&'static str, bool)],
+}
+
+/// A need the emulator doesn't meet
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Missing<'a> {
+    pub kind: &'a str,
+    pub name: &'a str,
+}
+
+impl fmt::Display for Missing<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.kind {
+            "" => write!(f, "{}: expected kind:name", self.name),
+            kind => write!(f, "ragc does not implement {} {}", kind, self.name),
+        }
+    }
+}
+
+/// Describe this build. Scans the decoder, so call it once at startup.
+pub fn capabilities() -> Capabilities {
+    Capabilities {
+        instructions: instruction_set(),
+        channels: &CHANNELS,
+        counters: &COUNTERS,
+        interrupts: &INTERRUPTS,
+        discretes: &DISCRETES,
+        axes: &AXES,
+        features: FEATURES,
+    }
+}
+
+impl Capabilities {
+    /// Whether the emulator meets `need`, written `kind:name`: e.g.
+    /// `instruction:DV`, `channel:CHAN13` or `channel:13` (octal),
+    /// `counter:PIPAX`, `interrupt:UPRUPT`, `discrete:abort`, `axis:rhc-pitch`
+    /// or `feature:events`. Instructions must execute, not just decode.
+    pub fn check<'a>(&self, need: &'a str) -> Result<(), Missing<'a>> {
+        let (kind, name) = need.split_once(':').unwrap_or(("", need));
+        let met = match kind {
+            "instruction" => self
+                .instructions
+                .iter()
+                .any(|e| e.implemented && e.mnem.name() == name),
+            "channel" => self
+                .channels
+                .iter()
+                .any(|c| c.name == name || usize::from_str_radix(name, 8) == Ok(c.channel)),
+            "counter" => self.counters.iter().any(|c| c.name == name),
+            "interrupt" => self
+                .interrupts
+                .iter()
+                .any(|r| RUPT_VECTORS[r.vector() as usize] == name),
+            "discrete" => self.discretes.iter().any(|d| d.name() == name),
+            "axis" => self.axes.iter().any(|a| a.name() == name),
+            "feature" => self.features.iter().any(|(f, on)| *on && *f == name),
+            _ => {
+                return Err(Missing {
+                    kind: "",
+                    name: need,
+                })
+            }
+        };
+        match met {
+            true => Ok(()),
+            false => Err(Missing { kind, name }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod capability_tests {
+    use super::{capabilities, Missing};
+
+    #[test]
+    fn checks_needs_against_the_tables() {
+        let caps = capabilities();
+        for need in [
+            "instruction:DV",
+            "channel:CHAN13",
+            "channel:13",
+            "counter:PIPAX",
+            "interrupt:UPRUPT",
+            "discrete:abort",
+        ] {
+            assert_eq!(caps.check(need), Ok(()), "{}", need);
+        }
+        // DDOUBL is DAS A to the decoder
+        let missing = Missing {
+            kind: "instruction",
+            name: "DDOUBL",
+        };
+        assert_eq!(caps.check("instruction:DDOUBL"), Err(missing));
+        assert!(caps.check("counter:TIME6").is_err());
+        assert!(caps.check("interrupt:T6RUPT").is_err());
+        assert_eq!(caps.check("PIPAX").unwrap_err().kind, "");
+        assert_eq!(
+            caps.check("feature:events").is_ok(),
+            cfg!(feature = "events")
+        );
+    }
+}
@@ -0,0 +1,113 @@
+//! Invariants the emulator keeps when it is right: words fit the width of
+//! where they go, banks exist, and double precision pairs hang together.
+//! A broken one is an emulator bug, and left alone it surfaces hours later
+//! as a wrong number on the DSKY. Development builds assert them where the
+//! damage is done; they are `debug_assert!`s, so release builds drop them.
+use crate::utils::adjust_overflow;
+
+/// Bits of a word in memory or a channel
+const WORD15: u16 = 0o77777;
+
+/// Largest double precision magnitude and sign, as `write_dp` takes it
+const DP_MAX: u32 = 0o3777777777;
+
+/// A word bound for 15-bit storage
+#[inline]
+pub(crate) fn word15(what: &str, value: u16) {
+    debug_assert!(
+        value & !WORD15 == 0,
+        "{} {:06o} is wider than 15 bits",
+        what,
+        value
+    );
+}
+
+/// A 16-bit word whose overflow should already have been corrected
+#[inline]
+pub(crate) fn corrected(what: &str, value: u16) {
+    debug_assert!(
+        adjust_overflow(value) == value,
+        "{} {:06o} has an uncorrected overflow",
+        what,
+        value
+    );
+}
+
+/// The magnitude of a single precision word, 14 bits
+#[inline]
+pub(crate) fn magnitude(what: &str, magnitude: u32) {
+    debug_assert!(
+        magnitude <= 0o37777,
+        "{} magnitude {:o} is wider than 14 bits",
+        what,
+        magnitude
+    );
+}
+
+/// A bank index of `banks` banks
+#[inline]
+pub(crate) fn bank(what: &str, bank: usize, banks: usize) {
+    debug_assert!(
+        bank < banks,
+        "{} bank {:o} out of range ({:o} banks)",
+        what,
+        bank,
+        banks
+    );
+}
+
+/// An offset within a bank of `size` words
+#[inline]
+pub(crate) fn offset(what: &str, offset: usize, size: usize) {
+    debug_assert!(
+        offset < size,
+        "{} offset {:o} beyond the {:o}-word bank",
+        what,
+        offset,
+        size
+    );
+}
+
+/// A double precision value as `write_dp` splits it: 28 bits of magnitude
+/// and a sign, nothing above
+#[inline]
+pub(crate) fn dp(what: &str, value: u32) {
+    debug_assert!(
+        value <= DP_MAX,
+        "{} {:o} is wider than double precision",
+        what,
+        value
+    );
+}
+
+/// A double precision pair as stored: signs agree unless a word is zero
+#[inline]
+pub(crate) fn dp_pair(what: &str, high: u16, low: u16) {
+    word15(what, high);
+    word15(what, low);
+    let zero = |word: u16| word == 0 || word == WORD15;
+    debug_assert!(
+        zero(high) || zero(low) || (high ^ low) & 0o40000 == 0,
+        "{} {:05o},{:05o} has words of opposite signs",
+        what,
+        high,
+        low
+    );
+}
+
+#[cfg(all(test, debug_assertions))]
+mod checks_tests {
+    use super::{dp_pair, word15};
+
+    #[test]
+    #[should_panic(expected = "wider than 15 bits")]
+    fn catches_a_wide_word() {
+        word15("test", 0o177777);
+    }
+
+    #[test]
+    #[should_panic(expected = "opposite signs")]
+    fn catches_mixed_signs() {
+        dp_pair("test", 0o00001, 0o77775);
+    }
+}
@@ -16,6 +16,41 @@ pub mod address_space {
     pub const VOLATILE_END: usize = VOLATILE_SIZE - 1;
     pub const PERSISTENT_START: usize = VOLATILE_SIZE;
     pub const PERSISTENT_END: usize = PERSISTENT_START + PERSISTENT_SIZE - 1;
+
+    // Bank windows: EB's bank at the top of erasable, FB's at the bottom
+    // of fixed, then the two fixed-fixed banks
+    pub const ERASABLE_BANK_SIZE: usize = 0o400;
+    pub const FIXED_BANK_SIZE: usize = 0o2000;
+    pub const CENTRAL_END: usize = VOLATILE_START - 1;
+    pub const SWITCHED_ERASABLE_START: usize = VOLATILE_END + 1 - ERASABLE_BANK_SIZE;
+    pub const SWITCHED_FIXED_START: usize = PERSISTENT_START;
+    pub const SWITCHED_FIXED_END: usize = SWITCHED_FIXED_START + FIXED_BANK_SIZE - 1;
+    pub const FIXED_FIXED_START: usize = SWITCHED_FIXED_END + 1;
+
+    /// Fixed bank wired behind a fixed-fixed address: 2 for 4000-5777, 3
+    /// for 6000-7777
+    pub const fn fixed_fixed_bank(addr: usize) -> usize {
+        addr / FIXED_BANK_SIZE
+    }
+
+    /// Address of a fixed-fixed bank's word, or None for switched banks
+    pub const fn fixed_fixed_address(bank: usize, offset: usize) -> Option<usize> {
+        match bank {
+            2 | 3 if offset < FIXED_BANK_SIZE => Some(bank * FIXED_BANK_SIZE + offset),
+            _ => None,
+        }
+    }
+
+    const _: () = assert!(VOLATILE_END + 1 == PERSISTENT_START);
+    const _: () = assert!(VOLATILE_SIZE == 4 * ERASABLE_BANK_SIZE);
+    const _: () = assert!(SWITCHED_ERASABLE_START == 0o1400);
+    const _: () = assert!(PERSISTENT_SIZE == 3 * FIXED_BANK_SIZE);
+    const _: () = assert!(fixed_fixed_bank(FIXED_FIXED_START) == 2);
+    const _: () = assert!(fixed_fixed_bank(PERSISTENT_END) == 3);
+    const _: () = assert!(matches!(
+        fixed_fixed_address(3, 0o1777),
+        Some(PERSISTENT_END)
+    ));
 }
 
 pub mod ports {
@@ -26,6 +61,7 @@ pub mod ports {
     pub const CHANNEL_LOSCALAR: usize = 0o04;
     pub const CHANNEL_PYJETS: usize = 0o05;
     pub const CHANNEL_ROLLJETS: usize = 0o06;
+    pub const CHANNEL_SUPERBNK: usize = 0o07;
     pub const CHANNEL_DSKY: usize = 0o10;
     pub const CHANNEL_DSALMOUT: usize = 0o11;
     pub const CHANNEL_CHAN12: usize = 0o12;
@@ -41,6 +77,75 @@ pub mod ports {
     pub const CHANNEL_CHAN33: usize = 0o33;
     pub const CHANNEL_CHAN34: usize = 0o34;
     pub const CHANNEL_CHAN35: usize = 0o35;
+
+    // Restart monitor (alarm cause latch)
+    pub const CHANNEL_CHAN77: usize = 0o77;
+
+    // Channel 7 fixed extension bits, the only ones the channel has
+    pub const SUPERBNK_BITS: u16 = 0o00160;
+    pub const SUPERBNK_EXTEND: u16 = 0o00100; // FB 30-37 select banks 40-47
+
+    // Channel 11 descent engine commands (LM)
+    pub const CHAN11_ENGINE_ON: u16 = 0o10000;
+    pub const CHAN11_ENGINE_OFF: u16 = 0o20000;
+
+    // Channel 12 outputs
+    pub const CHAN12_ZERO_OPTICS_CDUS: u16 = 0o00001; // Optics (CM) or rendezvous radar (LM)
+    pub const CHAN12_ENABLE_OPTICS_ERROR: u16 = 0o00002;
+    pub const CHAN12_COARSE_ALIGN: u16 = 0o00010; // CDU commands torque the gimbals
+    pub const CHAN12_ZERO_IMU_CDUS: u16 = 0o00020;
+    pub const CHAN12_ENABLE_IMU_ERROR: u16 = 0o00040; // CDU commands drive the error needles
+    pub const CHAN12_DISPLAY_INERTIAL: u16 = 0o00200; // LM; TVC enable on the CM
+    pub const CHAN12_ISS_DELAY_COMPLETE: u16 = 0o40000;
+
+    // Channel 13 control bits
+    pub const CHAN13_WORD_ORDER: u16 = 0o100; // Downlink word order code
+    pub const CHAN13_TEST_ALARMS: u16 = 0o1000; // Exercise alarm circuits, DSKY lights
+    pub const CHAN13_ENABLE_STANDBY: u16 = 0o2000; // PRO key toggles standby
+
+    // Channel 14 output drive bits (LM)
+    pub const CHAN14_ALTITUDE_RATE: u16 = 0o00002; // ALTM holds altitude rate, not altitude
+    pub const CHAN14_ALTITUDE_METER: u16 = 0o00004; // Shift ALTM out to the tape meter
+    pub const CHAN14_DRIVE_THRUST: u16 = 0o00010;
+
+    // Channel 30 inputs (active low, LM)
+    pub const CHAN30_ABORT: u16 = 0o00001; // Abort with descent stage
+    pub const CHAN30_ENGINE_ARMED: u16 = 0o00004;
+    pub const CHAN30_ABORT_STAGE: u16 = 0o00010; // Abort with ascent stage
+    pub const CHAN30_IMU_OPERATE: u16 = 0o00400;
+    pub const CHAN30_IMU_CAGE: u16 = 0o02000;
+    pub const CHAN30_ISS_TURN_ON_REQUEST: u16 = 0o20000;
+
+    // Channel 31 inputs (active low, LM hand controllers)
+    pub const CHAN31_THC_PLUS_X: u16 = 0o00100;
+    pub const CHAN31_THC_MINUS_X: u16 = 0o00200;
+    pub const CHAN31_THC_PLUS_Y: u16 = 0o00400;
+    pub const CHAN31_THC_MINUS_Y: u16 = 0o01000;
+    pub const CHAN31_THC_PLUS_Z: u16 = 0o02000;
+    pub const CHAN31_THC_MINUS_Z: u16 = 0o04000;
+    pub const CHAN31_RHC_OUT_OF_DETENT: u16 = 0o40000;
+
+    // Channel 32 inputs (active low)
+    pub const CHAN32_GIMBAL_FAIL: u16 = 0o01000; // Apparent descent engine gimbal failure
+    pub const CHAN32_PROCEED: u16 = 0o20000;
+
+    // Channel 33 inputs (active low)
+    pub const CHAN33_UPLINK_TOO_FAST: u16 = 0o02000;
+    pub const CHAN33_DOWNLINK_TOO_FAST: u16 = 0o04000;
+    pub const CHAN33_AGC_WARNING: u16 = 0o20000;
+    pub const CHAN33_OSCILLATOR_ALARM: u16 = 0o40000;
+
+    // Display unit status lights (yaDSKY channel 163)
+    pub const CHANNEL_DSKY_LIGHTS: usize = 0o163;
+    pub const DSKY_LIGHT_AGC_WARNING: u16 = 0o1;
+    pub const DSKY_LIGHT_TEMP: u16 = 0o10;
+    pub const DSKY_LIGHT_KEY_REL: u16 = 0o20;
+    pub const DSKY_LIGHT_VN_FLASH: u16 = 0o40;
+    pub const DSKY_LIGHT_OPR_ERR: u16 = 0o100;
+    pub const DSKY_LIGHT_RESTART: u16 = 0o200;
+    pub const DSKY_LIGHT_STBY: u16 = 0o400;
+    pub const DSKY_LIGHT_EL_OFF: u16 = 0o1000; // Numeric display unpowered, not a lamp
+    pub const DSKY_LIGHT_LAMPS: u16 = 0o771; // Every lamp, as the lamp test lights them
 }
 
 pub mod registers {
@@ -52,10 +157,9 @@ pub mod registers {
     pub const REGISTER_RETURN: usize = 0x2;
     pub const REGISTER_ERASABLE_BANK: usize = 0x3;
     pub const REGISTER_FIXED_BANK: usize = 0x4;
-    pub const REGISTER_ZERO: usize = 0x05;
-    pub const REGISTER_COUNTER: usize = 0x05; // Alias
+    pub const REGISTER_COUNTER: usize = 0x05; // Z: address of the next instruction
     pub const REGISTER_COMBINED_BANK: usize = 0x6;
-    pub const REGISTER_NULL: usize = 0x7;
+    pub const REGISTER_NULL: usize = 0x7; // ZERO in listings: reads 0, drops writes
 
     // Backup registers
     pub const REGISTER_ACCUMULATOR_BACKUP: usize = 0x8;
@@ -71,6 +175,7 @@ pub mod registers {
 
     // Interrupt codes
     pub const INTERRUPT_RESET: u8 = 0x0;
+    pub const INTERRUPT_TIMER5: u8 = 0x2;
     pub const INTERRUPT_TIMER3: u8 = 0x3;
     pub const INTERRUPT_TIMER4: u8 = 0x4;
     pub const INTERRUPT_KEYPRESS1: u8 = 0x5;
@@ -100,6 +205,7 @@ pub mod timers {
     pub const TIMER_1_ADDRESS: usize = 0o25;
     pub const TIMER_3_ADDRESS: usize = 0o26;
     pub const TIMER_4_ADDRESS: usize = 0o27;
+    pub const TIMER_5_ADDRESS: usize = 0o30;
 }
 
 pub mod special_registers {
@@ -112,6 +218,9 @@ pub mod special_registers {
     pub const SPECIAL_REGISTER_INERTIAL_X: usize = 0o37;
     pub const SPECIAL_REGISTER_INERTIAL_Y: usize = 0o40;
     pub const SPECIAL_REGISTER_INERTIAL_Z: usize = 0o41;
+    pub const SPECIAL_REGISTER_RHC_PITCH: usize = 0o42; // LM hand controller counters
+    pub const SPECIAL_REGISTER_RHC_YAW: usize = 0o43;
+    pub const SPECIAL_REGISTER_RHC_ROLL: usize = 0o44;
     pub const SPECIAL_REGISTER_DATA_INPUT: usize = 0o45;
     pub const SPECIAL_REGISTER_NAV_RADAR: usize = 0o46;
     pub const SPECIAL_REGISTER_GYRO_CTRL: usize = 0o47;
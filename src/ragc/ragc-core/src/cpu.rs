@@ -1,38 +1,169 @@
+use crate::checks;
 use crate::constants::ports;
 use crate::constants::registers::*;
+use crate::constants::timers;
 use crate::decoder::decoder;
+#[cfg(feature = "events")]
+use crate::events::{Event, EventSink};
+use crate::hash::Fnv1a;
+use crate::hooks::{FixedAddress, Hook, HookContext, MAX_HOOKS};
 use crate::instructions::{Arithmatic, ControlFlow, Interrupt, Io, LoadStore};
 use crate::instructions::{Instructions, Mnemonic};
-use crate::memory::MemoryMap;
+use crate::latency::{self, LatencyModel};
+use crate::logging::{debug, error, info, warn};
+use crate::memory::channels::Discrete;
+use crate::memory::mods::RuptRequest;
+use crate::memory::{Anomaly, Location, MemoryMap};
+use crate::protect::{ProtectHit, ProtectedRange, MAX_PROTECTED};
+use crate::stats::InstructionStats;
+use crate::symbols::ErasableAddress;
+#[cfg(feature = "ringtrace")]
+use crate::trace::{RingTrace, TraceDumpFn, TraceEvent, TraceRecord};
 use crate::utils::{add_s15, adjust_overflow, extend_sign_bits};
+use crate::warning::WarningFilter;
+use core::hash::{Hash, Hasher};
+
+/// MCTs taken by the RUPT sequence entering an interrupt
+const RUPT_CYCLES: u16 = 2;
+
+/// Unprogrammed sequences a CPU can hold pending unless built with another
+/// depth through `Cpu::with_queue_depth`
+pub const DEFAULT_UNPROG_DEPTH: usize = 8;
+
+/// Instructions the ring trace keeps (feature `ringtrace`) unless the CPU is
+/// built with another `TRACE_DEPTH`
+pub const DEFAULT_TRACE_DEPTH: usize = 64;
+
+/// Instructions `Cpu::idle` runs looking for the idle loop to come round
+const IDLE_PASS_STEPS: usize = 32;
+
+/// Interrupt requests raised by a DSKY key
+const KEY_RUPTS: u16 = 1 << INTERRUPT_KEYPRESS1 | 1 << INTERRUPT_KEYPRESS2;
+
+/// Highest counter cell address (counters occupy 0o24-0o60)
+const COUNTER_MAX: usize = 0o60;
+
+/// Apply a counter sequence to the current cell value
+/// PINC/MINC/DINC work in ones' complement, PCDU/MCDU in two's complement
+fn counter_step(seq: UnprogSequence, value: u16) -> u16 {
+    let value = value & 0o77777;
+    match seq {
+        UnprogSequence::PINC(_) => match value {
+            0o37777 => 0, // Positive overflow
+            _ => add_s15(value, 1),
+        },
+        UnprogSequence::MINC(_) => match value {
+            0o40000 => 0o77777, // Negative overflow
+            _ => add_s15(value, 0o77776),
+        },
+        UnprogSequence::PCDU(_) => (value + 1) & 0o77777,
+        UnprogSequence::MCDU(_) => value.wrapping_sub(1) & 0o77777,
+        UnprogSequence::DINC(_) => match value {
+            0 | 0o77777 => value, // Already at +0/-0
+            _ if value & 0o40000 != 0 => add_s15(value, 1),
+            _ => add_s15(value, 0o77776),
+        },
+        UnprogSequence::SHINC(_) => (value << 1) & 0o77777,
+        UnprogSequence::SHANC(_) => ((value << 1) | 1) & 0o77777,
+        _ => value,
+    }
+}
 
 /// Enum for representing the unprogrammed sequence instructions
+/// Counter sequences carry the address of the counter cell they update
 #[allow(dead_code)]
+#[derive(Clone, Copy, PartialEq, Hash, Debug)]
 pub enum UnprogSequence {
-    PINC,
-    PCDU,
-    MINC,
-    MCDU,
-    DINC,
-    SHINC,
-    SHANC,
+    PINC(usize),
+    PCDU(usize),
+    MINC(usize),
+    MCDU(usize),
+    DINC(usize),
+    SHINC(usize),
+    SHANC(usize),
     INOTRD,
     INOTLD,
-    FETCH,
-    STORE,
+    FETCH(usize),      // Monitor reads the word at an address
+    STORE(usize, u16), // Monitor writes a word to an address
     GOJ,
-    TCSAJ,
+    TCSAJ(u16), // Monitor sends control to an address
     RUPT,
 }
 
 /// Enum for representing overflow state
 #[allow(dead_code)]
-pub enum Overflow {
+pub(crate) enum Overflow {
     None,
     Positive,
     Negative,
 }
 
+/// Hardware conditions which force a GOJAM (restart) sequence
+#[derive(Clone, Copy, PartialEq, Debug)]
+#[non_exhaustive]
+pub enum RestartCause {
+    ParityFail,    // Fixed or erasable memory parity alarm
+    TcTrap,        // Too long executing only TC/TCF, or none at all
+    RuptLock,      // Interrupt held too long, or no interrupt for too long
+    NightWatchman, // NEWJOB location not accessed within the watch period
+    VoltageFail,   // External power supply fail discrete
+    Manual,        // Restart commanded by the host (no channel 77 bit)
+}
+
+impl RestartCause {
+    /// Bit latched into channel 77 (restart monitor) for this cause
+    pub fn channel_bit(&self) -> u16 {
+        match self {
+            RestartCause::ParityFail => 0o00001,
+            RestartCause::TcTrap => 0o00004,
+            RestartCause::RuptLock => 0o00010,
+            RestartCause::NightWatchman => 0o00020,
+            RestartCause::VoltageFail => 0o00100,
+            RestartCause::Manual => 0o00000,
+        }
+    }
+}
+
+/// Emulation faults: conditions the real hardware would never produce, or
+/// which this emulator can't execute faithfully
+#[derive(Clone, Copy, PartialEq, Debug)]
+#[non_exhaustive]
+pub enum CpuFault {
+    /// The word doesn't decode to any instruction
+    InvalidInstruction { pc: u16, data: u16 },
+    /// The instruction decoded but has no implementation
+    Unimplemented { pc: u16, mnem: Mnemonic },
+    /// A write to fixed memory, which the hardware ignores
+    RomWrite {
+        pc: u16,
+        bank: usize,
+        offset: usize,
+        value: u16,
+    },
+    /// A warn-level access, raised under `FaultPolicy::Strict` only
+    Anomaly { pc: u16, anomaly: Anomaly },
+}
+
+/// Outcome of a single CPU step
+#[derive(Clone, Copy, PartialEq, Debug)]
+#[non_exhaustive]
+pub struct StepResult {
+    pub cycles: u16,             // MCTs consumed
+    pub pc: u16,                 // Z at the start of the step
+    pub took_interrupt: bool,    // An interrupt was entered during the step
+    pub unprogrammed: bool,      // An unprogrammed sequence ran instead of an instruction
+    pub fault: Option<CpuFault>, // Fault raised during the step
+}
+
+/// What the CPU does when it raises a fault
+#[derive(Clone, Copy, PartialEq, Debug)]
+#[non_exhaustive]
+pub enum FaultPolicy {
+    Log,    // Log, record and carry on with the next instruction
+    Halt,   // Log, record and stop executing until the fault is taken
+    Strict, // Halt, and treat warn-level anomalies as faults too
+}
+
 /// Trait that defines the behavior for unprogrammed GOJ instruction
 trait UnprogInstruction {
     fn handle_goj(&mut self) -> u16;
@@ -40,7 +171,11 @@ trait UnprogInstruction {
 
 /// Struct representing the CPU and its state
 #[allow(dead_code)]
-pub struct Cpu<'a> {
+pub struct Cpu<
+    'a,
+    const UNPROG_DEPTH: usize = DEFAULT_UNPROG_DEPTH,
+    const TRACE_DEPTH: usize = DEFAULT_TRACE_DEPTH,
+> {
     mem: MemoryMap<'a>,      // Memory mapping
     pub ir: u16,             // Instruction register
     pub idx_val: u16,        // Indexed value for addressing
@@ -52,8 +187,9 @@ pub struct Cpu<'a> {
     pub gint: bool,     // Global interrupt enable
     pub is_irupt: bool, // Interrupt active status
 
-    unprog: heapless::Deque<UnprogSequence, 8>, // Queue for unprogrammed instructions
-    pub rupt: u16,                              // Interrupt request bits
+    unprog: heapless::Deque<UnprogSequence, UNPROG_DEPTH>, // Queue for unprogrammed instructions
+    dropped_counters: u64,                                 // Counter requests lost to a full queue
+    rupt: u16,                                             // Interrupt request bits
 
     nightwatch: u16,        // Nightwatch memory counter
     nightwatch_cycles: u32, // Nightwatch cycle count
@@ -62,9 +198,47 @@ pub struct Cpu<'a> {
     non_tc_count: u32, // Non-TC instruction count
 
     ruptlock_count: i32, // Interrupt lock count
+
+    restarts: u32,                      // GOJAMs taken since power on
+    warning: WarningFilter,             // Restart and oscillator fail integrator
+    last_restart: Option<RestartCause>, // Cause of the most recent GOJAM
+    program: Option<u8>,                // Major mode last written to MODREG
+    alarms: u32,                        // Alarm codes stored in FAILREG since power on
+    last_alarm: Option<u16>,            // Most recent of them
+
+    stats: InstructionStats, // Instruction mix since power on
+
+    fault: Option<CpuFault>,      // Most recent fault, until taken
+    step_fault: Option<CpuFault>, // Fault raised during the current step
+    inst_pc: u16,                 // Address of the executing instruction
+    fault_policy: FaultPolicy,    // Reaction to faults
+
+    standby: bool,      // Standby mode (program halted, STBY lit)
+    proceed_held: bool, // Standby request seen on the previous step
+    wake_on_key: bool,  // Any DSKY key ends standby
+    proceed_down: bool, // PRO down when standby last looked
+    key_presses: u32,   // DSKY keys pressed since power on
+
+    cycle_stealing: bool, // Counter updates consume program MCTs
+    stolen_cycles: [u32; COUNTER_MAX + 1], // MCTs stolen per counter cell
+
+    latency: Option<LatencyModel>, // Extra MCTs charged for memory accesses
+    latency_cycles: u64,           // MCTs the latency model has added
+
+    hooks: heapless::Vec<(FixedAddress, &'a mut dyn Hook), MAX_HOOKS>, // Host callbacks
+
+    protected: heapless::Vec<ProtectedRange, MAX_PROTECTED>, // Erasable trapping writes
+    protect_hit: Option<ProtectHit>, // Most recent trapped write, until taken
+
+    #[cfg(feature = "ringtrace")]
+    trace: RingTrace<TRACE_DEPTH>, // Recent instruction history
+    #[cfg(feature = "ringtrace")]
+    trace_dump: Option<TraceDumpFn<TRACE_DEPTH>>, // Post-mortem hook run on GOJAM and alarms
 }
 
-impl<'a> UnprogInstruction for Cpu<'a> {
+impl<'a, const UNPROG_DEPTH: usize, const TRACE_DEPTH: usize> UnprogInstruction
+    for Cpu<'a, UNPROG_DEPTH, TRACE_DEPTH>
+{
     /// GOJ: Zero specific IO channels and reset flags
     fn handle_goj(&mut self) -> u16 {
         self.write_io(ports::CHANNEL_PYJETS, 0);
@@ -77,14 +251,19 @@ impl<'a> UnprogInstruction for Cpu<'a> {
         self.write_io(ports::CHANNEL_CHAN34, 0);
         self.write_io(ports::CHANNEL_CHAN35, 0);
 
+        // Hardware reset of the channel 33 flip-flop, not a CPU write
         let val = self.read_io(ports::CHANNEL_CHAN33);
-        self.write_io(ports::CHANNEL_CHAN33, val & 0o75777);
+        self.mem
+            .set_channel_input(ports::CHANNEL_CHAN33, val & 0o75777);
 
         self.gint = false;
         self.is_irupt = false;
 
         self.tc_count = 0;
         self.non_tc_count = 0;
+        self.ruptlock_count = 0;
+        self.nightwatch = 0;
+        self.nightwatch_cycles = 0;
 
         self.restart();
 
@@ -92,7 +271,20 @@ impl<'a> UnprogInstruction for Cpu<'a> {
     }
 }
 
+/// Whether `Cpu::execute` has an implementation for the mnemonic; anything
+/// else raises `CpuFault::Unimplemented`. Pseudo-instructions never decode.
+pub const fn implemented(mnem: &Mnemonic) -> bool {
+    !matches!(mnem, Mnemonic::INVALID) && !mnem.is_pseudo()
+}
+
 impl<'a> Cpu<'a> {
+    /// Creates a new CPU instance with default values
+    pub fn new(memmap: MemoryMap<'a>) -> Self {
+        Self::with_queue_depth(memmap)
+    }
+}
+
+impl<'a, const UNPROG_DEPTH: usize, const TRACE_DEPTH: usize> Cpu<'a, UNPROG_DEPTH, TRACE_DEPTH> {
     /// Combines the IR and index for instruction calculation
     fn calculate_instr_data(&self) -> u16 {
         let mut inst_data = add_s15(self.ir, self.idx_val);
@@ -102,14 +294,17 @@ impl<'a> Cpu<'a> {
         inst_data
     }
 
-    /// Creates a new CPU instance with default values
-    pub fn new(memmap: MemoryMap) -> Cpu {
-        let mut cpu = Cpu {
+    /// Creates a CPU holding up to `UNPROG_DEPTH` pending counter updates,
+    /// for peripherals that burst more than the default allows, and keeping
+    /// `TRACE_DEPTH` instructions in its ring trace
+    pub fn with_queue_depth(memmap: MemoryMap<'a>) -> Self {
+        let mut cpu = Self {
             mem: memmap,
             ir: 0x0,
             ec_flag: false,
             idx_val: 0x0,
             unprog: heapless::Deque::new(),
+            dropped_counters: 0,
 
             total_cycles: 0,
             mct_counter: 0.0,
@@ -124,12 +319,54 @@ impl<'a> Cpu<'a> {
             tc_count: 0,
             non_tc_count: 0,
             ruptlock_count: 0,
+            restarts: 0,
+            warning: WarningFilter::default(),
+            last_restart: None,
+            program: None,
+            alarms: 0,
+            last_alarm: None,
+            stats: InstructionStats::default(),
+            fault: None,
+            step_fault: None,
+            inst_pc: 0,
+            fault_policy: FaultPolicy::Log,
+            standby: false,
+            proceed_held: false,
+            wake_on_key: false,
+            proceed_down: false,
+            key_presses: 0,
+
+            cycle_stealing: true,
+            stolen_cycles: [0; COUNTER_MAX + 1],
+
+            latency: None,
+            latency_cycles: 0,
+
+            hooks: heapless::Vec::new(),
+
+            protected: heapless::Vec::new(),
+            protect_hit: None,
+
+            #[cfg(feature = "ringtrace")]
+            trace: RingTrace::new(),
+            #[cfg(feature = "ringtrace")]
+            trace_dump: None,
         };
 
         cpu.reset();
         cpu
     }
 
+    /// Memory map for host-side inspection (symbols, state vector, dumps)
+    pub fn memory(&self) -> &MemoryMap<'a> {
+        &self.mem
+    }
+
+    /// Mutable memory map access for host-side tools
+    pub fn memory_mut(&mut self) -> &mut MemoryMap<'a> {
+        &mut self.mem
+    }
+
     /// Reset CPU to startup state
     pub fn reset(&mut self) {
         self.update_pc(0x800);
@@ -141,8 +378,449 @@ impl<'a> Cpu<'a> {
         self.update_pc(0x800);
         self.gint = false;
 
-        let io_val = self.read_io(0o163);
-        self.write_io(0o163, 0o200 | io_val);
+        let io_val = self.read_io(ports::CHANNEL_DSKY_LIGHTS);
+        self.write_io(
+            ports::CHANNEL_DSKY_LIGHTS,
+            ports::DSKY_LIGHT_RESTART | io_val,
+        );
+    }
+
+    /// Single entry point for every restart source: latches the cause into
+    /// channel 77 and replaces any pending unprogrammed work with GOJ
+    pub fn gojam(&mut self, cause: RestartCause) {
+        warn!("GOJAM: {:?}", cause);
+        #[cfg(feature = "ringtrace")]
+        if let Some(dump) = self.trace_dump {
+            dump(TraceEvent::Restart(cause), &self.trace);
+        }
+        self.mem.latch_restart_cause(cause.channel_bit());
+        self.restarts += 1;
+        self.warning.restart();
+        self.last_restart = Some(cause);
+        self.unprog.clear();
+        let _ = self.unprog.push_back(UnprogSequence::GOJ);
+    }
+
+    /// Assert or release a named input discrete. Asserting voltage fail
+    /// forces a GOJAM; the rest hold their channel bit low until released.
+    pub fn set_discrete(&mut self, discrete: Discrete, asserted: bool) -> bool {
+        match discrete {
+            Discrete::VoltageFail => {
+                if asserted {
+                    self.gojam(RestartCause::VoltageFail);
+                }
+                true
+            }
+            _ => self.mem.set_discrete(discrete, asserted),
+        }
+    }
+
+    /// Instruction mix executed since power on
+    pub fn instruction_stats(&self) -> &InstructionStats {
+        &self.stats
+    }
+
+    pub fn set_fault_policy(&mut self, policy: FaultPolicy) {
+        self.fault_policy = policy;
+        // Anomalies from before a strict run began aren't its own
+        self.mem.take_anomaly();
+    }
+
+    /// Take the most recent fault, resuming execution if it halted the CPU
+    pub fn take_fault(&mut self) -> Option<CpuFault> {
+        self.fault.take()
+    }
+
+    /// Most recent fault without clearing it
+    pub fn fault(&self) -> Option<CpuFault> {
+        self.fault
+    }
+
+    fn raise_fault(&mut self, fault: CpuFault) {
+        error!("CPU fault: {:?}", fault);
+        self.fault = Some(fault);
+        self.step_fault = Some(fault);
+    }
+
+    /// Stopped on a fault the policy won't run past
+    pub fn halted(&self) -> bool {
+        self.fault.is_some() && self.fault_policy != FaultPolicy::Log
+    }
+
+    /// Stable 64-bit hash of the machine state: memory, channels and timers
+    /// plus the CPU's own sequencing state. Equal hashes at equal times mean
+    /// the runs are indistinguishable to the program; statistics, faults and
+    /// debugging aids are left out.
+    pub fn state_hash(&self) -> u64 {
+        let mut state = Fnv1a::default();
+        self.mem.hash_state(&mut state);
+        (self.ir, self.idx_val, self.ec_flag, self.total_cycles).hash(&mut state);
+        (self.mct_counter.to_bits(), self.timer_counter).hash(&mut state);
+        (self.gint, self.is_irupt, self.rupt).hash(&mut state);
+        self.unprog.iter().for_each(|seq| seq.hash(&mut state));
+        (self.nightwatch, self.nightwatch_cycles).hash(&mut state);
+        (self.tc_count, self.non_tc_count, self.ruptlock_count).hash(&mut state);
+        (self.restarts, self.program, self.warning).hash(&mut state);
+        (self.standby, self.proceed_held).hash(&mut state);
+        state.finish()
+    }
+
+    /// Number of GOJAMs taken since power on
+    pub fn restart_count(&self) -> u32 {
+        self.restarts
+    }
+
+    pub fn last_restart(&self) -> Option<RestartCause> {
+        self.last_restart
+    }
+
+    /// Whether the AGC WARNING filter has tripped
+    pub fn agc_warning(&self) -> bool {
+        self.warning.is_lit()
+    }
+
+    /// Major mode the software last stored in MODREG. None when the rope's
+    /// MODREG address is unknown or no program is selected (-0).
+    pub fn program(&self) -> Option<u8> {
+        self.program
+    }
+
+    /// Number of alarm codes the software has stored in FAILREG. Zero when
+    /// the rope's FAILREG address is unknown.
+    pub fn alarm_count(&self) -> u32 {
+        self.alarms
+    }
+
+    /// Most recent alarm code stored in FAILREG
+    pub fn last_alarm(&self) -> Option<u16> {
+        self.last_alarm
+    }
+
+    fn track_alarm(&mut self, code: u16) {
+        debug!("Program alarm {:05o}", code);
+        self.alarms += 1;
+        self.last_alarm = Some(code);
+        #[cfg(feature = "ringtrace")]
+        if let Some(dump) = self.trace_dump {
+            dump(TraceEvent::Alarm(code), &self.trace);
+        }
+        #[cfg(feature = "events")]
+        self.mem.record(Event::Alarm { code });
+    }
+
+    /// Follow a MODREG write, logging program changes
+    fn track_program(&mut self, value: u16) {
+        let program = match value & 0o77777 {
+            value @ 0..=99 => Some(value as u8),
+            _ => None,
+        };
+        if program == self.program {
+            return;
+        }
+        match program {
+            Some(p) => info!("Program changed to P{:02}", p),
+            None => info!("Program cleared"),
+        }
+        self.program = program;
+        #[cfg(feature = "events")]
+        self.mem.record(Event::ProgramChanged { program });
+    }
+
+    /// Attach a recorder receiving every architectural state mutation
+    #[cfg(feature = "events")]
+    pub fn set_event_sink(&mut self, sink: &'a mut dyn EventSink) {
+        self.mem.set_event_sink(sink);
+    }
+
+    /// Replay a recorded event onto this CPU
+    #[cfg(feature = "events")]
+    pub fn apply_event(&mut self, event: &Event) {
+        match *event {
+            Event::RuptRaise { rupt } => self.rupt |= 1 << rupt,
+            _ => self.mem.apply(event),
+        }
+    }
+
+    /// Request an interrupt. Requests latch until serviced in priority order.
+    pub fn request_rupt(&mut self, rupt: RuptRequest) {
+        self.mem.request_rupt(rupt);
+    }
+
+    /// Mission elapsed time in centiseconds from TIME2:TIME1. A TIME1
+    /// overflow whose TIME2 PINC has not run yet is counted, as software
+    /// does by rereading TIME2 until it holds still across a TIME1 read.
+    pub fn mission_clock(&self) -> u32 {
+        let carries = self
+            .unprog
+            .iter()
+            .filter(|seq| **seq == UnprogSequence::PINC(timers::TIMER_2_ADDRESS))
+            .count() as u32;
+        self.mem.mission_clock().wrapping_add(carries << 14) & 0o1777777777
+    }
+
+    /// Interrupt requests latched by the priority encoder, one bit per RUPT
+    pub fn pending_rupts(&self) -> u16 {
+        self.rupt
+    }
+
+    /// Move requests from memory, timers and peripherals into the encoder
+    fn collect_rupts(&mut self) {
+        let raised = self.mem.take_rupts();
+        if raised & KEY_RUPTS != 0 {
+            self.key_presses += 1;
+        }
+        #[cfg(feature = "events")]
+        self.record_rupts(raised & !self.rupt);
+        self.rupt |= raised;
+    }
+
+    #[cfg(feature = "events")]
+    fn record_rupts(&mut self, raised: u16) {
+        for rupt in 0..16 {
+            if raised & (1 << rupt) != 0 {
+                self.mem.record(Event::RuptRaise { rupt });
+            }
+        }
+    }
+
+    /// Register a host callback receiving the ring trace on every GOJAM and
+    /// program alarm
+    #[cfg(feature = "ringtrace")]
+    pub fn set_trace_dump(&mut self, dump: TraceDumpFn<TRACE_DEPTH>) {
+        self.trace_dump = Some(dump);
+    }
+
+    /// Most recent instructions, for on-demand post-mortem inspection
+    #[cfg(feature = "ringtrace")]
+    pub fn ring_trace(&self) -> &RingTrace<TRACE_DEPTH> {
+        &self.trace
+    }
+
+    /// Run `hook` before every instruction fetched from `at`. Returns false
+    /// when the hook table is full.
+    pub fn add_hook(&mut self, at: FixedAddress, hook: &'a mut dyn Hook) -> bool {
+        if self.hooks.push((at, hook)).is_err() {
+            error!("Hook table full, dropping hook on {:?}", at);
+            return false;
+        }
+        true
+    }
+
+    /// Trap every program write to `range`. Returns false when the table
+    /// of protected ranges is full.
+    pub fn protect(&mut self, range: ProtectedRange) -> bool {
+        if self.protected.push(range).is_err() {
+            error!("Protected range table full, dropping {:?}", range);
+            return false;
+        }
+        true
+    }
+
+    pub fn unprotect_all(&mut self) {
+        self.protected.clear();
+    }
+
+    /// Take the most recent write to a protected range
+    pub fn take_protect_hit(&mut self) -> Option<ProtectHit> {
+        self.protect_hit.take()
+    }
+
+    /// Write on behalf of the debugger, exempt from write protection
+    pub fn poke(&mut self, idx: usize, val: u16) {
+        let protected = core::mem::take(&mut self.protected);
+        self.write(idx, val);
+        self.protected = protected;
+    }
+
+    /// Record a write to a protected word; true if it is to be dropped
+    fn trap_protected(&mut self, idx: usize, val: u16) -> bool {
+        let addr = match self.mem.locate(idx) {
+            Some(Location::Erasable(addr)) => addr,
+            Some(Location::Central(idx)) => ErasableAddress::from_flat(idx),
+            _ => return false,
+        };
+        let range = match self.protected.iter().find(|r| r.contains(addr)) {
+            Some(range) => *range,
+            None => return false,
+        };
+        let hit = ProtectHit {
+            pc: self.inst_pc,
+            addr,
+            value: val,
+            suppressed: range.suppress,
+        };
+        warn!("Write to protected erasable: {:?}", hit);
+        self.protect_hit = Some(hit);
+        range.suppress
+    }
+
+    fn run_hooks(&mut self, pc: u16) {
+        let at = match self.mem.locate(pc as usize) {
+            Some(Location::Fixed(at)) => at,
+            _ => return, // Code running from erasable
+        };
+        let fb = self.mem.read(REGISTER_FIXED_BANK);
+        let ctx = HookContext {
+            at,
+            pc,
+            a: self.mem.read(REGISTER_ACCUMULATOR),
+            l: self.mem.read(REGISTER_LINK),
+            q: self.mem.read(REGISTER_RETURN),
+            eb: self.mem.read(REGISTER_ERASABLE_BANK),
+            fb,
+            cycles: self.total_cycles,
+        };
+        for (at, hook) in self.hooks.iter_mut() {
+            if *at == ctx.at {
+                hook.call(&ctx, &self.mem);
+            }
+        }
+    }
+
+    /// Run a ground-support monitor sequence at once, between instructions,
+    /// as the test connector does with the computer stopped. Each takes two
+    /// MCTs. Returns the word fetched or stored, or the TCSAJ address;
+    /// None for sequences the monitor can't force.
+    pub fn monitor(&mut self, seq: UnprogSequence) -> Option<u16> {
+        let word = match seq {
+            UnprogSequence::TCSAJ(addr) => {
+                self.ec_flag = false;
+                self.idx_val = 0;
+                self.update_pc(addr);
+                addr
+            }
+            UnprogSequence::FETCH(addr) => self.read(addr),
+            UnprogSequence::STORE(addr, word) => {
+                self.write(addr, word);
+                word
+            }
+            _ => {
+                warn!("Not a monitor sequence: {:?}", seq);
+                return None;
+            }
+        };
+        self.update_cycles(2);
+        Some(word)
+    }
+
+    /// Request a counter cell update (PIPA, CDU, timers...)
+    /// With cycle stealing enabled the update is queued and takes one MCT away
+    /// from the program; otherwise it is applied immediately at no cost
+    pub fn request_counter(&mut self, seq: UnprogSequence) {
+        match seq {
+            UnprogSequence::PINC(_)
+            | UnprogSequence::PCDU(_)
+            | UnprogSequence::MINC(_)
+            | UnprogSequence::MCDU(_)
+            | UnprogSequence::DINC(_)
+            | UnprogSequence::SHINC(_)
+            | UnprogSequence::SHANC(_) => {}
+            _ => {
+                warn!("Not a counter sequence: {:?}", seq);
+                return;
+            }
+        }
+
+        if self.cycle_stealing {
+            if self.unprog.push_back(seq).is_err() {
+                self.dropped_counters += 1;
+                warn!("Counter queue full, dropped {:?}", seq);
+            }
+        } else {
+            self.apply_counter(seq);
+        }
+    }
+
+    /// Choose whether counter traffic steals memory cycles from the program
+    pub fn set_cycle_stealing(&mut self, enabled: bool) {
+        self.cycle_stealing = enabled;
+    }
+
+    /// MCTs stolen from the program by updates to the given counter cell
+    pub fn stolen_cycles(&self, counter: usize) -> u32 {
+        self.stolen_cycles.get(counter).copied().unwrap_or(0)
+    }
+
+    /// Counter updates lost because the unprogrammed sequence queue was full
+    pub fn dropped_counters(&self) -> u64 {
+        self.dropped_counters
+    }
+
+    /// MCTs stolen from the program by all counter traffic
+    pub fn total_stolen_cycles(&self) -> u64 {
+        self.stolen_cycles.iter().map(|c| *c as u64).sum()
+    }
+
+    /// Charge extra MCTs for memory accesses and counter steals, for
+    /// studying timing margins; `None` keeps the real AGC's timing
+    pub fn set_latency(&mut self, model: Option<LatencyModel>) {
+        self.latency = model;
+    }
+
+    pub fn latency(&self) -> Option<LatencyModel> {
+        self.latency
+    }
+
+    /// MCTs the latency model has added to the program and counter traffic
+    pub fn latency_cycles(&self) -> u64 {
+        self.latency_cycles
+    }
+
+    /// Extra MCTs the latency model charges for `inst`: its fetch and its
+    /// operand, located before it runs and switches banks
+    fn access_latency(&self, inst: &Instructions) -> u16 {
+        let model = match self.latency {
+            Some(model) => model,
+            None => return 0,
+        };
+        let fetch = model.access(self.mem.locate(inst.pc as usize));
+        let operand =
+            latency::operand_address(inst).map_or(0, |addr| model.access(self.mem.locate(addr)));
+        fetch + operand
+    }
+
+    fn apply_counter(&mut self, seq: UnprogSequence) {
+        let addr = match seq {
+            UnprogSequence::PINC(a)
+            | UnprogSequence::PCDU(a)
+            | UnprogSequence::MINC(a)
+            | UnprogSequence::MCDU(a)
+            | UnprogSequence::DINC(a)
+            | UnprogSequence::SHINC(a)
+            | UnprogSequence::SHANC(a) => a,
+            _ => return,
+        };
+        let old = self.mem.read(addr);
+        let value = counter_step(seq, old);
+        self.mem.write_counter(addr, value);
+
+        // Timer overflow raises the timer's RUPT; TIME1 carries into TIME2.
+        // The CDUs wrap silently, they have no overflow interrupt.
+        if matches!(seq, UnprogSequence::PINC(_)) && old & 0o77777 == 0o37777 {
+            if addr == timers::TIMER_1_ADDRESS {
+                self.carry_time2();
+            }
+            let rupt = match addr {
+                timers::TIMER_3_ADDRESS => Some(RuptRequest::T3rupt),
+                timers::TIMER_4_ADDRESS => Some(RuptRequest::T4rupt),
+                timers::TIMER_5_ADDRESS => Some(RuptRequest::T5rupt),
+                _ => None,
+            };
+            if let Some(rupt) = rupt {
+                self.mem.request_rupt(rupt);
+            }
+        }
+    }
+
+    /// TIME1 overflow takes the next counter cycle, ahead of traffic already
+    /// queued, so no instruction sees TIME1 wrapped for longer than one
+    /// sequence. The carry is never dropped: with the queue full it applies
+    /// at once.
+    fn carry_time2(&mut self) {
+        let carry = UnprogSequence::PINC(timers::TIMER_2_ADDRESS);
+        if !self.cycle_stealing || self.unprog.push_front(carry).is_err() {
+            self.apply_counter(carry);
+        }
     }
 
     /// Sets program counter and fetches next instruction
@@ -165,7 +843,7 @@ impl<'a> Cpu<'a> {
     // Memory read/write functions, including sign extension handling
 
     pub fn read(&mut self, idx: usize) -> u16 {
-        if idx == 0o067 {
+        if idx == self.mem.rom_info().newjob {
             self.nightwatch += 1;
         }
         self.mem.read(idx)
@@ -193,6 +871,7 @@ impl<'a> Cpu<'a> {
     }
 
     pub fn write_s15(&mut self, idx: usize, value: u16) {
+        checks::word15("write_s15 value", value);
         match idx {
             REGISTER_ACCUMULATOR | REGISTER_MULTIPLIER => self.write(idx, extend_sign_bits(value)),
             _ => self.write(idx, value & 0o77777),
@@ -200,10 +879,31 @@ impl<'a> Cpu<'a> {
     }
 
     pub fn write(&mut self, idx: usize, val: u16) {
-        if idx == 0o067 {
+        if idx == self.mem.rom_info().newjob {
             self.nightwatch += 1;
         }
-        self.mem.write(idx, val)
+        if let Some(Location::Fixed(addr)) = self.mem.locate(idx) {
+            self.mem.count_rom_write();
+            self.raise_fault(CpuFault::RomWrite {
+                pc: self.inst_pc,
+                bank: addr.bank,
+                offset: addr.offset,
+                value: val,
+            });
+            return;
+        }
+        if !self.protected.is_empty() && self.trap_protected(idx, val) {
+            return;
+        }
+        self.mem.write(idx, val);
+        if Some(idx) == self.mem.rom_info().modreg {
+            self.track_program(val);
+        }
+        if let Some(failreg) = self.mem.rom_info().failreg {
+            if (failreg..failreg + 3).contains(&idx) && val & 0o77777 != 0 {
+                self.track_alarm(val & 0o77777);
+            }
+        }
     }
 
     /// Read double precision (32-bit equivalent) value from memory
@@ -212,7 +912,7 @@ impl<'a> Cpu<'a> {
         let upper: u32 = self.read_s15(idx) as u32;
         let lower: u32 = self.read_s15(idx + 1) as u32;
 
-        match (upper & 0o40000) == (lower & 0o40000) {
+        let value = match (upper & 0o40000) == (lower & 0o40000) {
             true => (upper << 14) | (lower & 0o37777),
             false => {
                 let mut res = if lower & 0o40000 == 0o40000 {
@@ -230,13 +930,17 @@ impl<'a> Cpu<'a> {
                 }
                 res & 0o3777777777
             }
-        }
+        };
+        checks::dp("read_dp value", value);
+        value
     }
 
     /// Write double precision (32-bit equivalent) value to memory
     pub fn write_dp(&mut self, idx: usize, val: u32) {
+        checks::dp("write_dp value", val);
         let upper = ((val >> 14) & 0o77777) as u16;
         let lower = (val & 0o37777) as u16 | (upper & 0o40000);
+        checks::dp_pair("write_dp words", upper, lower);
 
         self.write_s15(idx, upper);
         self.write_s15(idx + 1, lower);
@@ -253,23 +957,22 @@ impl<'a> Cpu<'a> {
 
     // Interrupt and overflow handling
 
-    fn is_overflow(&mut self) -> bool {
-        let a = self.read(REGISTER_ACCUMULATOR);
-        a & 0xC000 != 0xC000 && a & 0xC000 != 0x0000
-    }
-
-    fn interrupt_disabled(&mut self) -> bool {
-        self.ec_flag || !self.gint || self.is_irupt || self.is_overflow()
+    // Overflow comes from the register file's cached flag rather than a
+    // read of A, which would cost a full memory-map dispatch every step
+    fn interrupt_disabled(&self) -> bool {
+        self.ec_flag || !self.gint || self.is_irupt || self.mem.accumulator_overflow()
     }
 
     fn interrupt_pending(&self) -> bool {
         self.rupt != 0
     }
 
+    // Vectors are in priority order, T6RUPT first and HANDRUPT last
     fn handle_interrupt(&mut self) {
-        for i in 0..10 {
+        for i in 0..=INTERRUPT_MANUAL as u16 {
             let mask = 1 << i;
             if self.rupt & mask != 0 {
+                crate::enter_span!("interrupt", vector = i);
                 self.gint = false;
                 let val = self.read(REGISTER_COUNTER) + 1;
                 self.write(REGISTER_COUNTER_BACKUP, val);
@@ -287,36 +990,34 @@ impl<'a> Cpu<'a> {
 
     /// Execute the instruction and return cycle count
     pub fn execute(&mut self, inst: &Instructions) -> u16 {
-        match inst.mnem {
-            Mnemonic::TC | Mnemonic::TCF => {
-                self.non_tc_count = 0;
-                self.tc_count += 1;
-            }
-            _ => {
-                self.tc_count = 0;
-                self.non_tc_count += 1;
-            }
-        }
-
         let cycles = match inst.mnem {
             Mnemonic::AD => self.ad(inst),
             Mnemonic::ADS => self.ads(inst),
+            Mnemonic::AUG => self.aug(inst),
             Mnemonic::BZF => self.bzf(inst),
+            Mnemonic::BZMF => self.bzmf(inst),
+            Mnemonic::CCS => self.ccs(inst),
+            Mnemonic::DAS => self.das(inst),
             Mnemonic::CA => self.ca(inst),
             Mnemonic::CS => self.cs(inst),
             Mnemonic::DCA => self.dca(inst),
             Mnemonic::DCS => self.dcs(inst),
             Mnemonic::DIM => self.dim(inst),
             Mnemonic::DV => self.dv(inst),
+            Mnemonic::DXCH => self.dxch(inst),
+            Mnemonic::EDRUPT => self.edrupt(inst),
             Mnemonic::EXTEND => {
                 self.ec_flag = true;
                 self.idx_val = 0x0;
-                1
+                inst.cycles()
             }
             Mnemonic::INCR => self.incr(inst),
+            Mnemonic::INDEX => self.index(inst),
             Mnemonic::INHINT => self.inhint(inst),
             Mnemonic::LXCH => self.lxch(inst),
+            Mnemonic::MASK => self.mask(inst),
             Mnemonic::MP => self.mp(inst),
+            Mnemonic::MSU => self.msu(inst),
             Mnemonic::QXCH => self.qxch(inst),
             Mnemonic::RELINT => self.relint(inst),
             Mnemonic::RESUME => self.resume(inst),
@@ -327,22 +1028,187 @@ impl<'a> Cpu<'a> {
             Mnemonic::SU => self.su(inst),
             Mnemonic::TC => self.tc(inst),
             Mnemonic::TCF => self.tcf(inst),
+            Mnemonic::TS => self.ts(inst),
             Mnemonic::WAND => self.wand(inst),
             Mnemonic::WOR => self.wor(inst),
             Mnemonic::WRITE => self.write_instr(inst),
             Mnemonic::XCH => self.xch(inst),
+            Mnemonic::INVALID => {
+                self.ec_flag = false;
+                self.idx_val = 0x0;
+                self.raise_fault(CpuFault::InvalidInstruction {
+                    pc: inst.pc,
+                    data: inst.data,
+                });
+                inst.cycles()
+            }
             _ => {
                 self.ec_flag = false;
                 self.idx_val = 0x0;
-                0
+                self.raise_fault(CpuFault::Unimplemented {
+                    pc: inst.pc,
+                    mnem: inst.mnem,
+                });
+                inst.cycles()
             }
         };
-        cycles
+
+        // Every instruction occupies at least one memory cycle
+        cycles.max(1)
     }
 
     fn update_cycles(&mut self, cycles: u16) {
         self.mct_counter += cycles as f64 * 12.0;
         self.total_cycles += cycles as usize;
+        self.mem.tick_io(cycles);
+        for &timer in self.mem.fetch_clocks().advance(cycles) {
+            self.request_counter(UnprogSequence::PINC(timer));
+        }
+        self.check_rupt_lock(cycles);
+        self.check_night_watchman(cycles);
+        self.check_warning(cycles);
+    }
+
+    /// AGC WARNING: shown in channel 33 (active low) and on the DSKY
+    /// lights channel
+    fn check_warning(&mut self, cycles: u16) {
+        let mem = &self.mem;
+        let oscillator_failed =
+            || mem.peek_channel(ports::CHANNEL_CHAN33) & ports::CHAN33_OSCILLATOR_ALARM == 0;
+        let lit = match self.warning.tick(cycles, oscillator_failed) {
+            Some(lit) => lit,
+            None => return,
+        };
+        warn!("AGC WARNING {}", if lit { "on" } else { "off" });
+        if lit {
+            self.mem
+                .pin_channel_bits(ports::CHANNEL_CHAN33, ports::CHAN33_AGC_WARNING, 0);
+        } else {
+            self.mem
+                .release_channel_bits(ports::CHANNEL_CHAN33, ports::CHAN33_AGC_WARNING);
+        }
+        let lights = self.read_io(ports::CHANNEL_DSKY_LIGHTS) & !ports::DSKY_LIGHT_AGC_WARNING;
+        let warning = if lit {
+            ports::DSKY_LIGHT_AGC_WARNING
+        } else {
+            0
+        };
+        self.write_io(ports::CHANNEL_DSKY_LIGHTS, lights | warning);
+    }
+
+    /// TC TRAP: fires when only TC/TCF, or no TC/TCF at all, has executed
+    /// for longer than the monitor period
+    fn check_tc_trap(&mut self, inst: &Instructions, cycles: u16) {
+        match inst.mnem {
+            Mnemonic::TC | Mnemonic::TCF => {
+                self.non_tc_count = 0;
+                self.tc_count += cycles as u32;
+            }
+            _ => {
+                self.tc_count = 0;
+                self.non_tc_count += cycles as u32;
+            }
+        }
+
+        let forced = self.test_alarms() && !self.is_irupt;
+        if forced || self.tc_count > MONITOR_CYCLES || self.non_tc_count > MONITOR_CYCLES {
+            self.gojam(RestartCause::TcTrap);
+        }
+    }
+
+    /// RUPT LOCK: positive count while inside an interrupt, negative while
+    /// outside; either direction exceeding the lockout forces a restart
+    fn check_rupt_lock(&mut self, cycles: u16) {
+        if self.is_irupt {
+            self.ruptlock_count = self.ruptlock_count.max(0) + cycles as i32;
+        } else {
+            self.ruptlock_count = self.ruptlock_count.min(0) - cycles as i32;
+        }
+
+        let forced = self.test_alarms() && self.is_irupt;
+        if forced || self.ruptlock_count.abs() > INTERRUPT_LOCKOUT {
+            self.gojam(RestartCause::RuptLock);
+        }
+    }
+
+    /// TEST ALARMS (channel 13 bit 10) forces the alarm monitors to trip:
+    /// set from the main program it exercises TC TRAP, from an interrupt
+    /// routine it exercises RUPT LOCK. The resulting GOJ clears channel 13.
+    fn test_alarms(&mut self) -> bool {
+        self.read_io(ports::CHANNEL_CHAN13) & ports::CHAN13_TEST_ALARMS != 0
+    }
+
+    /// Whether the computer is halted in standby
+    pub fn in_standby(&self) -> bool {
+        self.standby
+    }
+
+    /// With ENABLE STANDBY set in channel 13, holding PRO puts the computer
+    /// into standby. Holding PRO again leaves standby through a restart. The
+    /// DSKY times the hold; the CPU acts once when it completes.
+    fn check_standby(&mut self) {
+        let held = self.mem.standby_request();
+        let pressed = held && !self.proceed_held;
+        self.proceed_held = held;
+        if !pressed {
+            return;
+        }
+
+        if self.standby {
+            self.leave_standby();
+        } else if self.read_io(ports::CHANNEL_CHAN13) & ports::CHAN13_ENABLE_STANDBY != 0 {
+            self.standby = true;
+            self.proceed_down = true;
+            self.mem.set_standby_light(true);
+        }
+    }
+
+    fn leave_standby(&mut self) {
+        self.standby = false;
+        self.mem.set_standby_light(false);
+        self.gojam(RestartCause::Manual);
+    }
+
+    /// Let any DSKY key end standby through a restart, not just a PRO hold
+    pub fn set_wake_on_key(&mut self, on: bool) {
+        self.wake_on_key = on;
+    }
+
+    /// DSKY keys pressed since power on, PRO presses that woke the
+    /// computer included
+    pub fn key_presses(&self) -> u32 {
+        self.key_presses
+    }
+
+    /// Whether a key went down since standby last looked. Key requests are
+    /// drained, as the wake restarts the program; others, an uplink word
+    /// arriving with the key, stay latched.
+    fn key_waiting(&mut self) -> bool {
+        let raised = self.mem.take_rupts();
+        #[cfg(feature = "events")]
+        self.record_rupts(raised & !KEY_RUPTS & !self.rupt);
+        self.rupt |= raised & !KEY_RUPTS;
+        let keyed = raised & KEY_RUPTS != 0;
+        let down = self.read_io(ports::CHANNEL_CHAN32) & ports::CHAN32_PROCEED == 0;
+        let pressed = down && !self.proceed_down;
+        self.proceed_down = down;
+        keyed || pressed
+    }
+
+    /// NIGHT WATCHMAN: the NEWJOB location must be accessed at least once
+    /// every watch period
+    fn check_night_watchman(&mut self, cycles: u16) {
+        self.nightwatch_cycles += cycles as u32;
+        if self.nightwatch_cycles < WATCHDOG_TIMEOUT {
+            return;
+        }
+
+        let serviced = self.nightwatch != 0;
+        self.nightwatch = 0;
+        self.nightwatch_cycles = 0;
+        if !serviced {
+            self.gojam(RestartCause::NightWatchman);
+        }
     }
 
     /// Step through unprogrammed instruction
@@ -350,29 +1216,52 @@ impl<'a> Cpu<'a> {
         let instr = self.unprog.pop_front().unwrap();
         let cycles = match instr {
             UnprogSequence::GOJ
-            | UnprogSequence::TCSAJ
-            | UnprogSequence::STORE
-            | UnprogSequence::FETCH
+            | UnprogSequence::TCSAJ(_)
+            | UnprogSequence::STORE(..)
+            | UnprogSequence::FETCH(_)
             | UnprogSequence::RUPT => 2,
+            UnprogSequence::PINC(_)
+            | UnprogSequence::PCDU(_)
+            | UnprogSequence::MINC(_)
+            | UnprogSequence::MCDU(_)
+            | UnprogSequence::DINC(_)
+            | UnprogSequence::SHINC(_)
+            | UnprogSequence::SHANC(_) => {
+                let delay = self.latency.map_or(0, |model| model.counter as u16);
+                self.latency_cycles += delay as u64;
+                1 + delay
+            }
             _ => 1,
         };
 
+        if matches!(instr, UnprogSequence::GOJ) {
+            // Reset the monitors before accounting so GOJ can't re-trigger itself
+            self.handle_goj();
+            self.update_cycles(cycles);
+            return cycles;
+        }
+
         self.update_cycles(cycles);
 
         match instr {
-            UnprogSequence::GOJ => {
-                self.handle_goj();
-                return cycles;
+            UnprogSequence::PINC(addr)
+            | UnprogSequence::PCDU(addr)
+            | UnprogSequence::MINC(addr)
+            | UnprogSequence::MCDU(addr)
+            | UnprogSequence::DINC(addr)
+            | UnprogSequence::SHINC(addr)
+            | UnprogSequence::SHANC(addr) => {
+                self.apply_counter(instr);
+                if let Some(stolen) = self.stolen_cycles.get_mut(addr) {
+                    *stolen += cycles as u32;
+                }
             }
             _ => {}
-        };
+        }
 
-        if !self.interrupt_disabled() {
-            self.rupt |= self.mem.check_interrupts();
-            if self.interrupt_pending() {
-                self.handle_interrupt();
-                self.is_irupt = true;
-            }
+        if self.interrupt_pending() && !self.interrupt_disabled() {
+            self.handle_interrupt();
+            self.is_irupt = true;
         }
 
         cycles
@@ -380,17 +1269,44 @@ impl<'a> Cpu<'a> {
 
     /// Step through normal instruction execution
     fn step_programmed(&mut self) -> u16 {
-        if !self.interrupt_disabled() && self.interrupt_pending() {
+        if self.interrupt_pending() && !self.interrupt_disabled() {
             self.handle_interrupt();
             self.is_irupt = true;
-            return 0;
+            self.update_cycles(RUPT_CYCLES);
+            return RUPT_CYCLES;
         }
 
         let inst_data = self.calculate_instr_data();
         let addr: usize = (self.read(REGISTER_COUNTER) & 0xFFFF) as usize;
-        let i = decoder(addr as u16, inst_data).unwrap();
+        if !self.hooks.is_empty() {
+            self.run_hooks(addr as u16);
+        }
+
+        #[cfg(feature = "ringtrace")]
+        {
+            let record = TraceRecord {
+                pc: addr as u16,
+                data: inst_data,
+                a: self.mem.read(REGISTER_ACCUMULATOR),
+                l: self.mem.read(REGISTER_LINK),
+                q: self.mem.read(REGISTER_RETURN),
+                cycles: self.total_cycles,
+            };
+            self.trace.write(record);
+        }
+        // Undecodable words execute as INVALID, which raises a fault
+        let i = decoder(addr as u16, inst_data).unwrap_or_else(|e| {
+            warn!("Decode failure at {:05o}: {}", addr, e);
+            Instructions {
+                pc: addr as u16,
+                data: inst_data,
+                ..Instructions::new()
+            }
+        });
+        self.stats.record(i.mnem, inst_data & 0o100000 != 0);
         let next_pc = ((addr + 1) & 0xFFFF) as u16;
         self.update_pc(next_pc);
+        self.inst_pc = addr as u16;
 
         self.idx_val = 0;
 
@@ -400,18 +1316,118 @@ impl<'a> Cpu<'a> {
             }
         }
 
-        let cycles = self.execute(&i);
+        let delay = self.access_latency(&i);
+        self.latency_cycles += delay as u64;
+        let cycles = self.execute(&i) + delay;
         self.update_cycles(cycles);
+        self.check_tc_trap(&i, cycles);
+        if self.mem.take_parity_alarm() {
+            self.gojam(RestartCause::ParityFail);
+        }
         cycles
     }
 
     /// CPU execution cycle handler
-    pub fn step(&mut self) -> u16 {
-        if self.unprog.len() > 0 {
+    pub fn step(&mut self) -> StepResult {
+        let pc = self.read(REGISTER_COUNTER);
+        crate::enter_span!("step", pc = pc);
+        let was_irupt = self.is_irupt;
+        self.step_fault = None;
+
+        self.check_standby();
+        if self.standby && self.wake_on_key && self.key_waiting() {
+            self.key_presses += 1;
+            self.leave_standby();
+        }
+        let running = !self.standby && !self.halted();
+        if running {
+            self.collect_rupts();
+        }
+        let unprogrammed = running && !self.unprog.is_empty();
+        let cycles = if !running {
+            // Time still passes for the peripherals, so a PRO hold can end standby
+            self.mem.tick_io(1);
+            1
+        } else if unprogrammed {
             self.step_unprogrammed()
         } else {
             self.step_programmed()
+        };
+        if self.fault_policy == FaultPolicy::Strict {
+            if let Some(anomaly) = self.mem.take_anomaly() {
+                self.raise_fault(CpuFault::Anomaly {
+                    pc: self.inst_pc,
+                    anomaly,
+                });
+            }
+        }
+
+        StepResult {
+            cycles,
+            pc,
+            took_interrupt: !was_irupt && self.is_irupt,
+            unprogrammed,
+            fault: self.step_fault,
+        }
+    }
+
+    /// Let up to `max` MCTs pass in an idle loop without executing all of
+    /// it. Time moves to each timer pulse in turn, stopping once a counter
+    /// or an interrupt wants the CPU, so the timers and peripherals see what
+    /// the loop would have. Each stretch starts with one pass of the loop run
+    /// for real, so NIGHT WATCHMAN sees whether it reads NEWJOB, and TC TRAP
+    /// counts the rest of the stretch as more of the same pass: a loop of
+    /// nothing but TCs still trips it. Returns the MCTs passed.
+    pub fn idle(&mut self, max: u32) -> u32 {
+        let mut idled = 0;
+        while idled < max && !self.standby && !self.halted() {
+            self.collect_rupts();
+            let rupt = self.interrupt_pending() && !self.interrupt_disabled();
+            if rupt || !self.unprog.is_empty() {
+                break;
+            }
+            let (pass, round) = self.idle_pass();
+            idled += pass;
+            if !round || idled >= max || !self.unprog.is_empty() {
+                break;
+            }
+
+            let cycles = (self.mem.fetch_clocks().until_pulse() as u32).min(max - idled) as u16;
+            if self.tc_count >= pass {
+                self.tc_count += cycles as u32;
+            } else if self.non_tc_count >= pass {
+                self.non_tc_count += cycles as u32;
+            }
+            self.update_cycles(cycles);
+            idled += cycles as u32;
+            if self.tc_count > MONITOR_CYCLES || self.non_tc_count > MONITOR_CYCLES {
+                self.gojam(RestartCause::TcTrap);
+            }
+        }
+        idled
+    }
+
+    /// Run the loop at Z once round. Returns the MCTs taken and whether it
+    /// came back to where it started without an interrupt.
+    fn idle_pass(&mut self) -> (u32, bool) {
+        let start = self.read(REGISTER_COUNTER);
+        let mut cycles = 0;
+        for _ in 0..IDLE_PASS_STEPS {
+            let step = self.step();
+            cycles += step.cycles as u32;
+            if step.took_interrupt || step.unprogrammed {
+                return (cycles, false);
+            }
+            if self.read(REGISTER_COUNTER) == start {
+                return (cycles, true);
+            }
         }
+        (cycles, false)
+    }
+
+    /// Step and return only the MCTs consumed
+    pub fn step_cycles(&mut self) -> u16 {
+        self.step().cycles
     }
 }
 
@@ -419,16 +1435,858 @@ impl<'a> Cpu<'a> {
 #[cfg(feature = "std")]
 #[cfg(test)]
 mod cpu_tests {
-    use crate::cpu;
-    use crate::instructions::tests::init_agc;
+    use crate::constants::ports::{CHANNEL_DSKY_LIGHTS, DSKY_LIGHT_RESTART};
+    use crate::cpu::Cpu;
+    use crate::memory::MemoryMap;
 
     #[test]
     fn cpu_test_reset_light() {
-        let mut cpu = init_agc();
-        let dur = std::time::Duration::from_secs(5);
-        std::thread::sleep(dur);
-        println!("Restarting AGC");
+        let mut cpu = Cpu::new(MemoryMap::new_blank());
+        cpu.write_io(CHANNEL_DSKY_LIGHTS, 0);
         cpu.restart();
-        std::thread::sleep(dur);
+        assert_ne!(cpu.read_io(CHANNEL_DSKY_LIGHTS) & DSKY_LIGHT_RESTART, 0);
+    }
+}
+
+#[cfg(test)]
+mod restart_tests {
+    use super::{Cpu, RestartCause};
+    use crate::constants::ports::{
+        CHAN13_ENABLE_STANDBY, CHAN13_TEST_ALARMS, CHAN32_PROCEED, CHANNEL_CHAN13, CHANNEL_CHAN32,
+        CHANNEL_CHAN77, CHANNEL_DSKY_LIGHTS, DSKY_LIGHT_STBY,
+    };
+    use crate::constants::ports::{CHAN33_AGC_WARNING, CHANNEL_CHAN33, DSKY_LIGHT_AGC_WARNING};
+    use crate::constants::registers::{MONITOR_CYCLES, REGISTER_COUNTER};
+    use crate::memory::channels::Discrete;
+    use crate::memory::mods::{InterruptSource, IoPeriph, RuptRequest, RuptRequests};
+    use crate::memory::rom::BankFault;
+    use crate::memory::{MemoryMap, MemoryMapBuilder};
+    use crate::warning::WARNING_SAMPLE_MCTS;
+    use core::sync::atomic::{AtomicBool, AtomicU16, Ordering};
+
+    #[test]
+    fn gojam_latches_cause_and_restarts() {
+        let mut cpu = Cpu::new(MemoryMap::new_blank());
+
+        cpu.gojam(RestartCause::VoltageFail);
+        cpu.step();
+
+        assert_eq!(cpu.read(REGISTER_COUNTER), 0x800);
+        assert_eq!(cpu.read_io(CHANNEL_CHAN77), 0o00100);
+
+        // Any write to channel 77 clears the latched causes
+        cpu.write_io(CHANNEL_CHAN77, 0);
+        assert_eq!(cpu.read_io(CHANNEL_CHAN77), 0);
+    }
+
+    #[test]
+    fn voltage_fail_discrete_restarts() {
+        let mut cpu = Cpu::new(MemoryMap::new_blank());
+
+        assert!(cpu.set_discrete(Discrete::VoltageFail, true));
+        cpu.step();
+
+        assert_eq!(cpu.last_restart(), Some(RestartCause::VoltageFail));
+        assert_eq!(cpu.read(REGISTER_COUNTER), 0x800);
+        assert_eq!(cpu.read_io(CHANNEL_CHAN77), 0o00100);
+
+        // Releasing it leaves the latched cause for the software to read
+        assert!(cpu.set_discrete(Discrete::VoltageFail, false));
+        assert_eq!(cpu.read_io(CHANNEL_CHAN77), 0o00100);
+        assert_eq!(cpu.restart_count(), 1);
+    }
+
+    #[test]
+    fn repeated_restarts_light_agc_warning() {
+        let mut cpu = Cpu::new(MemoryMap::new_blank());
+
+        // One restart passes the filter
+        cpu.gojam(RestartCause::Manual);
+        while cpu.total_cycles < WARNING_SAMPLE_MCTS as usize {
+            cpu.step();
+        }
+        assert!(!cpu.agc_warning());
+
+        // A blank rope keeps tripping TC TRAP, which doesn't
+        while cpu.total_cycles < 4 * MONITOR_CYCLES as usize {
+            cpu.step();
+        }
+        assert!(cpu.agc_warning());
+        assert_eq!(cpu.read_io(CHANNEL_CHAN33) & CHAN33_AGC_WARNING, 0);
+        assert_ne!(cpu.read_io(CHANNEL_DSKY_LIGHTS) & DSKY_LIGHT_AGC_WARNING, 0);
+    }
+
+    #[test]
+    fn tc_only_loop_trips_tc_trap() {
+        let mut cpu = Cpu::new(MemoryMap::new_blank());
+
+        // A blank rope decodes as TC 0 everywhere
+        for _ in 0..(MONITOR_CYCLES * 2) {
+            cpu.step();
+        }
+        assert_ne!(
+            cpu.read_io(CHANNEL_CHAN77) & RestartCause::TcTrap.channel_bit(),
+            0
+        );
+    }
+
+    #[test]
+    fn idling_keeps_the_monitors_running() {
+        use crate::test_rom::{Op::*, TestRom};
+
+        let idle_for = |cpu: &mut Cpu, mcts: u32| {
+            let mut passed = 0;
+            while passed < mcts {
+                passed += match cpu.idle(mcts - passed) {
+                    0 => cpu.step().cycles as u32,
+                    idled => idled,
+                };
+            }
+        };
+
+        // A program hung in a TC to itself
+        let hung = TestRom::new().emit(&[TCF(0o4000)]);
+        let mut cpu = hung.cpu();
+        idle_for(&mut cpu, MONITOR_CYCLES * 2);
+        assert_ne!(
+            cpu.read_io(CHANNEL_CHAN77) & RestartCause::TcTrap.channel_bit(),
+            0
+        );
+
+        // NIGHT WATCHMAN only hears of NEWJOB reads the loop makes
+        let unwatched = TestRom::new().emit(&[CA(0o100), TCF(0o4000)]);
+        let mut cpu = unwatched.cpu();
+        idle_for(&mut cpu, MONITOR_CYCLES * 2);
+        assert_eq!((cpu.nightwatch, cpu.read_io(CHANNEL_CHAN77)), (0, 0));
+
+        let watched = TestRom::new().emit(&[CA(0o67), TCF(0o4000)]);
+        let mut cpu = watched.cpu();
+        idle_for(&mut cpu, MONITOR_CYCLES * 2);
+        assert!(cpu.nightwatch > 0);
+    }
+
+    #[test]
+    fn executes_central_registers_from_address_zero() {
+        use crate::test_rom::{Op::*, TestRom};
+
+        // TC 0 runs the word in A, then the one in L, here TC Q, which runs
+        // Q's return address as a TC back
+        let rom = TestRom::new()
+            .emit(&[DCA(0o4011), TC(0), XCH(0o7), TC(0)]) // DCA's field is K+1
+            .at(0o4010)
+            .emit(&[Word(0o24100), Word(0o00002)]); // INCR 100; TC Q
+        let mut cpu = rom.cpu();
+        for _ in 0..3 {
+            cpu.step();
+        }
+        assert_eq!(cpu.step().pc, 0); // INCR 100 from A
+        assert_eq!(cpu.step().pc, 1); // TC Q from L
+        assert_eq!(cpu.step().pc, 2); // TC 4003 from Q
+        assert_eq!((cpu.read(REGISTER_COUNTER), cpu.read(0o2)), (0o4003, 3));
+        assert_eq!(cpu.read(0o100), 1);
+
+        // XCH with ZERO clears A and leaves ZERO alone, making the next
+        // TC 0 a TC 0 forever, with Q left at 1
+        cpu.step();
+        assert_eq!((cpu.read(0o0), cpu.read(0o7)), (0, 0));
+        for _ in 0..10 {
+            cpu.step();
+        }
+        assert_eq!((cpu.read(REGISTER_COUNTER), cpu.read(0o2)), (0, 1));
+    }
+
+    #[test]
+    fn night_watchman_watches_the_roms_newjob() {
+        use crate::memory::rom::RomInfo;
+        use crate::test_rom::{Op::*, TestRom};
+
+        // Idle loop reading 70 instead of 67
+        let rom = TestRom::new().emit(&[CA(0o70), TCF(0o4000)]);
+        let accesses = |info: RomInfo| {
+            let mem = MemoryMapBuilder::new()
+                .fixed_memory(&rom)
+                .rom_info(info)
+                .build();
+            let mut cpu = Cpu::new(mem);
+            cpu.reset();
+            for _ in 0..10 {
+                cpu.step();
+            }
+            cpu.nightwatch
+        };
+        assert_eq!(accesses(RomInfo::BLOCK_II), 0);
+        let info = RomInfo {
+            newjob: 0o70,
+            ..RomInfo::BLOCK_II
+        };
+        assert_eq!(accesses(info), 5);
+    }
+
+    #[test]
+    fn modreg_writes_change_the_program() {
+        use crate::memory::rom::RomInfo;
+        use crate::test_rom::{Op::*, TestRom};
+
+        // MODREG at 100: select P63, then clear it with -0
+        let rom = TestRom::new().emit(&[
+            CA(0o4006),
+            XCH(0o100),
+            CA(0o4007),
+            XCH(0o100),
+            CA(0o67),
+            TCF(0o4004),
+            Word(63),
+            Word(0o77777),
+        ]);
+        let mem = MemoryMapBuilder::new()
+            .fixed_memory(&rom)
+            .rom_info(RomInfo {
+                modreg: Some(0o100),
+                ..RomInfo::BLOCK_II
+            })
+            .build();
+        let mut cpu = Cpu::new(mem);
+        cpu.reset();
+        assert_eq!(cpu.program(), None);
+        cpu.step();
+        cpu.step();
+        assert_eq!(cpu.program(), Some(63), "CA/XCH stored 63");
+        cpu.step();
+        cpu.step();
+        assert_eq!(cpu.program(), None, "-0 clears the program");
+    }
+
+    #[test]
+    fn failreg_writes_record_alarm_codes() {
+        use crate::memory::rom::RomInfo;
+        use crate::test_rom::{Op::*, TestRom};
+
+        // FAILREG at 100: 1202 and 1201 in the first two slots, then clear the first
+        let rom = TestRom::new().emit(&[
+            CA(0o4006),
+            XCH(0o100),
+            CA(0o4007),
+            XCH(0o101),
+            XCH(0o100),
+            Loop,
+            Word(0o1202),
+            Word(0o1201),
+        ]);
+        let mem = MemoryMapBuilder::new()
+            .fixed_memory(&rom)
+            .rom_info(RomInfo {
+                failreg: Some(0o100),
+                ..RomInfo::BLOCK_II
+            })
+            .build();
+        let mut cpu = Cpu::new(mem);
+        cpu.reset();
+        cpu.step();
+        cpu.step();
+        assert_eq!((cpu.alarm_count(), cpu.last_alarm()), (1, Some(0o1202)));
+        for _ in 0..3 {
+            cpu.step();
+        }
+        assert_eq!((cpu.alarm_count(), cpu.last_alarm()), (2, Some(0o1201)));
+    }
+
+    #[test]
+    fn faulted_fixed_bank_raises_parity_alarm() {
+        let mut mem = MemoryMap::new_blank();
+        mem.set_bank_fault(2, Some(BankFault::Parity)); // Restart address 0o4000
+        let mut cpu = Cpu::new(mem);
+
+        cpu.reset();
+        cpu.step();
+        assert_eq!(
+            cpu.read_io(CHANNEL_CHAN77),
+            RestartCause::ParityFail.channel_bit()
+        );
+        assert_eq!(cpu.last_restart(), Some(RestartCause::ParityFail));
+    }
+
+    #[test]
+    fn test_alarms_poke_lights_alarms() {
+        let mut cpu = Cpu::new(MemoryMap::new_blank());
+
+        // V25N07 on channel 13 from the main program: TC TRAP
+        cpu.write_io(CHANNEL_CHAN13, CHAN13_TEST_ALARMS);
+        cpu.step();
+        assert_eq!(
+            cpu.read_io(CHANNEL_CHAN77),
+            RestartCause::TcTrap.channel_bit()
+        );
+        cpu.step(); // GOJ
+        assert_eq!(cpu.read_io(CHANNEL_CHAN13), 0);
+
+        // Same poke while servicing an interrupt: RUPT LOCK
+        cpu.write_io(CHANNEL_CHAN77, 0);
+        cpu.is_irupt = true;
+        cpu.write_io(CHANNEL_CHAN13, CHAN13_TEST_ALARMS);
+        cpu.step();
+        assert_ne!(
+            cpu.read_io(CHANNEL_CHAN77) & RestartCause::RuptLock.channel_bit(),
+            0
+        );
+    }
+
+    static PRO_DOWN: AtomicBool = AtomicBool::new(false);
+    static LIGHTS: AtomicU16 = AtomicU16::new(0);
+
+    struct ProceedKey;
+
+    impl IoPeriph for ProceedKey {
+        fn read(&self, channel_idx: usize) -> u16 {
+            match channel_idx {
+                CHANNEL_CHAN32 if PRO_DOWN.load(Ordering::SeqCst) => 0,
+                CHANNEL_CHAN32 => CHAN32_PROCEED,
+                CHANNEL_DSKY_LIGHTS => LIGHTS.load(Ordering::SeqCst),
+                _ => 0,
+            }
+        }
+
+        fn write(&mut self, channel_idx: usize, value: u16) {
+            if channel_idx == CHANNEL_DSKY_LIGHTS {
+                LIGHTS.store(value, Ordering::SeqCst);
+            }
+        }
+
+        // The hold is timed by the DSKY; here every press counts as held
+        fn standby_request(&self) -> bool {
+            PRO_DOWN.load(Ordering::SeqCst)
+        }
+    }
+
+    impl InterruptSource for ProceedKey {
+        fn rupt_requests(&mut self) -> RuptRequests {
+            RuptRequests::NONE
+        }
+    }
+
+    fn press_proceed(cpu: &mut Cpu) -> u16 {
+        PRO_DOWN.store(false, Ordering::SeqCst);
+        cpu.step();
+        PRO_DOWN.store(true, Ordering::SeqCst);
+        cpu.step_cycles()
+    }
+
+    #[test]
+    fn proceed_enters_and_leaves_standby() {
+        static ROPE: [[u16; 1024]; 36] = [[0; 1024]; 36];
+        let (mut downlink, mut dsky) = (ProceedKey, ProceedKey);
+        let mem = MemoryMapBuilder::new()
+            .rope(&ROPE)
+            .downlink(&mut downlink)
+            .dsky(&mut dsky)
+            .build();
+        let mut cpu = Cpu::new(mem);
+
+        // PRO without ENABLE STANDBY does nothing
+        press_proceed(&mut cpu);
+        assert!(!cpu.in_standby());
+
+        cpu.write_io(CHANNEL_CHAN13, CHAN13_ENABLE_STANDBY);
+        assert_eq!(press_proceed(&mut cpu), 1);
+        assert!(cpu.in_standby());
+        assert_ne!(LIGHTS.load(Ordering::SeqCst) & DSKY_LIGHT_STBY, 0);
+
+        // Second press leaves standby through GOJAM
+        press_proceed(&mut cpu);
+        assert!(!cpu.in_standby());
+        assert_eq!(LIGHTS.load(Ordering::SeqCst) & DSKY_LIGHT_STBY, 0);
+
+        // Woken by any key, though not by the PRO hold that entered standby
+        cpu.step(); // GOJ clears channel 13
+        cpu.set_wake_on_key(true);
+        cpu.write_io(CHANNEL_CHAN13, CHAN13_ENABLE_STANDBY);
+        press_proceed(&mut cpu);
+        cpu.step();
+        assert!(cpu.in_standby());
+        cpu.request_rupt(RuptRequest::Keyrupt1);
+        cpu.step();
+        assert!(!cpu.in_standby());
+        assert_eq!(cpu.key_presses(), 1);
+    }
+}
+
+#[cfg(test)]
+mod fault_tests {
+    use super::{CpuFault, FaultPolicy};
+    use crate::memory::Anomaly;
+    use crate::test_rom::{Op::*, TestRom};
+
+    #[test]
+    fn faults_halt_until_taken() {
+        // Every instruction decodes and runs, so fault on a write to a
+        // channel with no assignment
+        let rom = TestRom::new().emit(&[WRITE(0o50)]);
+        let mut cpu = rom.cpu();
+        cpu.set_fault_policy(FaultPolicy::Strict);
+        cpu.step(); // EXTEND
+
+        let res = cpu.step();
+        assert!(res.cycles >= 1);
+        assert_eq!((res.pc, res.unprogrammed), (0o4001, false));
+        assert_eq!(res.fault, cpu.fault());
+        assert!(matches!(
+            cpu.fault(),
+            Some(CpuFault::Anomaly { pc: 0o4001, .. })
+        ));
+
+        // Halted: time passes but nothing executes until the fault is taken
+        let cycles = cpu.total_cycles;
+        assert_eq!(cpu.step_cycles(), 1);
+        assert_eq!(cpu.total_cycles, cycles);
+        assert!(cpu.take_fault().is_some());
+        cpu.step();
+        assert!(cpu.total_cycles > cycles);
+    }
+
+    #[test]
+    fn rom_write_faults_with_its_source() {
+        let rom = TestRom::new().emit(&[CA(0o4002), XCH(0o4), Word(0o12000)]);
+        let mut cpu = rom.cpu();
+        cpu.step();
+        cpu.step(); // FB 5
+
+        // Store into switched fixed memory on behalf of the XCH at 4001
+        cpu.write(0o2010, 0o123);
+        let fault = CpuFault::RomWrite {
+            pc: 0o4001,
+            bank: 5,
+            offset: 0o10,
+            value: 0o123,
+        };
+        assert_eq!(cpu.fault(), Some(fault));
+        assert_eq!(cpu.read(0o2010), 0, "fixed memory unchanged");
+    }
+
+    #[test]
+    fn strict_runs_halt_on_anomalies() {
+        // Write to an input channel, then to a channel with no assignment
+        let rom = TestRom::new().emit(&[WRITE(0o30), WRITE(0o50)]);
+        let mut cpu = rom.cpu();
+        cpu.step();
+        cpu.step();
+        assert_eq!(cpu.fault(), None, "permissive by default");
+
+        let mut cpu = rom.cpu();
+        cpu.set_fault_policy(FaultPolicy::Strict);
+        cpu.step();
+        let res = cpu.step();
+        let fault = CpuFault::Anomaly {
+            pc: 0o4001,
+            anomaly: Anomaly::InputChannelWrite(0o30),
+        };
+        assert_eq!(res.fault, Some(fault));
+        let cycles = cpu.total_cycles;
+        cpu.step();
+        assert_eq!(cpu.total_cycles, cycles, "halted");
+
+        cpu.take_fault();
+        cpu.step();
+        cpu.step();
+        assert!(matches!(
+            cpu.fault(),
+            Some(CpuFault::Anomaly {
+                anomaly: Anomaly::UnassignedChannel(0o50),
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn permissive_runs_count_their_anomalies() {
+        // Input channel write, unassigned channel write, output channel read
+        let rom = TestRom::new().emit(&[WRITE(0o30), WRITE(0o50), READ(0o10), Loop]);
+        let mut cpu = rom.cpu();
+        for _ in 0..8 {
+            cpu.step(); // Each with its EXTEND
+        }
+        cpu.write(0o4000, 0);
+        cpu.memory().read(0o20000);
+        let stats = cpu.memory().access_stats();
+        assert_eq!(
+            (
+                stats.input_writes,
+                stats.unknown_channels,
+                stats.output_reads
+            ),
+            (1, 1, 1)
+        );
+        assert_eq!(
+            (stats.rom_writes, stats.unmapped, stats.missing_banks),
+            (1, 1, 0)
+        );
+        assert_eq!(stats.total(), 5);
+    }
+}
+
+#[cfg(test)]
+mod counter_tests {
+    use super::{Cpu, UnprogSequence, DEFAULT_UNPROG_DEPTH};
+    use crate::constants::registers::REGISTER_COUNTER;
+    use crate::constants::special_registers::SPECIAL_REGISTER_CONTROL_DISPLAY_X;
+    use crate::constants::timers::{TIMER_1_ADDRESS, TIMER_2_ADDRESS};
+    use crate::memory::MemoryMap;
+
+    #[test]
+    fn counter_traffic_steals_cycles() {
+        let mut cpu = Cpu::new(MemoryMap::new_blank());
+
+        cpu.request_counter(UnprogSequence::PINC(TIMER_1_ADDRESS));
+        cpu.request_counter(UnprogSequence::MCDU(SPECIAL_REGISTER_CONTROL_DISPLAY_X));
+        assert_eq!(cpu.step_cycles(), 1);
+        let res = cpu.step();
+        assert_eq!(res.cycles, 1);
+        assert!(res.unprogrammed);
+
+        assert_eq!(cpu.read(TIMER_1_ADDRESS), 1);
+        assert_eq!(cpu.read(SPECIAL_REGISTER_CONTROL_DISPLAY_X), 0o77777);
+        assert_eq!(cpu.stolen_cycles(TIMER_1_ADDRESS), 1);
+        assert_eq!(cpu.total_stolen_cycles(), 2);
+    }
+
+    #[test]
+    fn counters_apply_immediately_without_stealing() {
+        let mut cpu = Cpu::new(MemoryMap::new_blank());
+        cpu.set_cycle_stealing(false);
+
+        cpu.request_counter(UnprogSequence::MINC(TIMER_1_ADDRESS));
+        assert_eq!(cpu.read(TIMER_1_ADDRESS), 0o37776); // -1 in 14 bits
+        assert_eq!(cpu.total_stolen_cycles(), 0);
+    }
+
+    #[test]
+    fn full_counter_queue_counts_drops() {
+        let mut cpu = Cpu::new(MemoryMap::new_blank());
+        for _ in 0..DEFAULT_UNPROG_DEPTH + 2 {
+            cpu.request_counter(UnprogSequence::PINC(TIMER_1_ADDRESS));
+        }
+        assert_eq!(cpu.dropped_counters(), 2);
+
+        // A deeper queue takes the same burst
+        let mut cpu = Cpu::<32>::with_queue_depth(MemoryMap::new_blank());
+        for _ in 0..DEFAULT_UNPROG_DEPTH + 2 {
+            cpu.request_counter(UnprogSequence::PINC(TIMER_1_ADDRESS));
+        }
+        while cpu.step().unprogrammed {}
+        assert_eq!(cpu.dropped_counters(), 0);
+        assert_eq!(cpu.read(TIMER_1_ADDRESS), DEFAULT_UNPROG_DEPTH as u16 + 2);
+    }
+
+    #[test]
+    fn monitor_sequences_fetch_store_and_transfer() {
+        let mut cpu = Cpu::new(MemoryMap::new_blank());
+        let cycles = cpu.total_cycles;
+        assert_eq!(
+            cpu.monitor(UnprogSequence::STORE(0o100, 0o12345)),
+            Some(0o12345)
+        );
+        assert_eq!(cpu.monitor(UnprogSequence::FETCH(0o100)), Some(0o12345));
+        assert_eq!(cpu.monitor(UnprogSequence::TCSAJ(0o4010)), Some(0o4010));
+        assert_eq!(cpu.read(REGISTER_COUNTER), 0o4010);
+        assert_eq!(cpu.total_cycles, cycles + 6);
+        assert_eq!(cpu.monitor(UnprogSequence::GOJ), None);
+    }
+
+    #[test]
+    fn time1_overflow_carries_into_time2() {
+        let mut cpu = Cpu::new(MemoryMap::new_blank());
+        cpu.write(TIMER_2_ADDRESS, 0o12);
+        cpu.write(TIMER_1_ADDRESS, 0o37776);
+
+        cpu.request_counter(UnprogSequence::PINC(TIMER_1_ADDRESS));
+        cpu.step();
+        assert_eq!(cpu.mission_clock(), (0o12 << 14) | 0o37777);
+
+        // TIME1 wraps first; the clock already counts the queued carry
+        cpu.request_counter(UnprogSequence::PINC(TIMER_1_ADDRESS));
+        cpu.step();
+        assert_eq!(cpu.read(TIMER_1_ADDRESS), 0);
+        assert_eq!(cpu.read(TIMER_2_ADDRESS), 0o12);
+        assert_eq!(cpu.mission_clock(), 0o13 << 14);
+
+        cpu.step();
+        assert_eq!(cpu.read(TIMER_2_ADDRESS), 0o13);
+        assert_eq!(cpu.mission_clock(), 0o13 << 14);
+
+        // The whole 28-bit clock rolls over to zero
+        cpu.write(TIMER_2_ADDRESS, 0o37777);
+        cpu.write(TIMER_1_ADDRESS, 0o37777);
+        cpu.set_cycle_stealing(false);
+        cpu.request_counter(UnprogSequence::PINC(TIMER_1_ADDRESS));
+        assert_eq!(cpu.mission_clock(), 0);
+    }
+
+    #[test]
+    fn time1_rollover_never_loses_counts() {
+        let mut cpu = Cpu::new(MemoryMap::new_blank());
+        cpu.write(TIMER_2_ADDRESS, 3);
+        cpu.write(TIMER_1_ADDRESS, 0o37000);
+        let start = cpu.mission_clock();
+
+        // Keep the queue saturated, so timer pulses and the carry compete
+        // for slots; every TIME1 PINC that runs must reach the clock
+        let mut last = start;
+        for _ in 0..4000 {
+            cpu.request_counter(UnprogSequence::PINC(TIMER_1_ADDRESS));
+            cpu.step();
+            assert!(cpu.mission_clock() >= last);
+            last = cpu.mission_clock();
+        }
+        while cpu.step().unprogrammed {}
+        let ran = cpu.stolen_cycles(TIMER_1_ADDRESS);
+        assert!(cpu.dropped_counters() > 0);
+        assert_eq!(cpu.mission_clock() - start, ran);
+
+        // The CPU rewriting TIME1 while the carry waits doesn't cancel it
+        cpu.write(TIMER_1_ADDRESS, 0o37777);
+        let time2 = cpu.read(TIMER_2_ADDRESS);
+        cpu.request_counter(UnprogSequence::PINC(TIMER_1_ADDRESS));
+        cpu.request_counter(UnprogSequence::PINC(TIMER_1_ADDRESS));
+        cpu.step();
+        cpu.write(TIMER_1_ADDRESS, 0o100);
+        cpu.step();
+        assert_eq!(cpu.read(TIMER_2_ADDRESS), time2 + 1);
+        cpu.step();
+        assert_eq!(cpu.read(TIMER_1_ADDRESS), 0o101);
+    }
+}
+
+#[cfg(test)]
+mod rupt_tests {
+    use super::Cpu;
+    use crate::constants::ports::CHANNEL_MNKEYIN;
+    use crate::constants::registers::{REGISTER_ACCUMULATOR, REGISTER_COUNTER};
+    use crate::memory::mods::RuptRequest;
+    use crate::memory::MemoryMap;
+    use crate::rng::Rng;
+    use crate::test_rom::{Op::*, TestRom};
+
+    fn vector(rupt: RuptRequest) -> u16 {
+        0o4000 + 4 * rupt.vector() as u16
+    }
+
+    #[test]
+    fn requests_latch_and_are_taken_by_priority() {
+        let mut cpu = Cpu::new(MemoryMap::new_blank());
+        let power_on = cpu.pending_rupts(); // DOWNRUPT
+
+        // Inhibited: requests latch without being taken
+        cpu.request_rupt(RuptRequest::Uprupt);
+        cpu.request_rupt(RuptRequest::Keyrupt1);
+        cpu.step();
+        let both = RuptRequest::Uprupt.mask() | RuptRequest::Keyrupt1.mask();
+        assert_eq!(cpu.pending_rupts(), power_on | both);
+
+        // KEYRUPT1 outranks UPRUPT and DOWNRUPT
+        cpu.gint = true;
+        let res = cpu.step();
+        assert!(res.took_interrupt);
+        assert_eq!(
+            cpu.read(REGISTER_COUNTER),
+            0x800 + 4 * RuptRequest::Keyrupt1.vector() as u16
+        );
+        assert_eq!(cpu.pending_rupts(), power_on | RuptRequest::Uprupt.mask());
+    }
+
+    #[test]
+    fn simultaneous_keys_and_uplink_are_all_taken() {
+        // Each handler counts its RUPT: KEYRUPT1 in 100, UPRUPT in 101
+        let rom = TestRom::new()
+            .emit(&[RELINT, Loop])
+            .at(vector(RuptRequest::Keyrupt1))
+            .emit(&[INCR(0o100), RESUME])
+            .at(vector(RuptRequest::Uprupt))
+            .emit(&[INCR(0o101), RESUME])
+            .at(vector(RuptRequest::Downrupt))
+            .emit(&[RESUME]);
+        let mut cpu = rom.cpu();
+        let mut rng = Rng::new(5018);
+        let (key, up) = (RuptRequest::Keyrupt1, RuptRequest::Uprupt);
+        let (mut keys, mut words, mut together) = (0, 0, 0);
+
+        // Each source fires again as soon as its last request is taken,
+        // often in the same MCT as the other
+        for _ in 0..20000 {
+            let idle = |rupt: RuptRequest| cpu.pending_rupts() & rupt.mask() == 0;
+            let both = rng.chance(0.2);
+            let fire_key = idle(key) && (both || rng.chance(0.1));
+            let fire_up = idle(up) && (both || rng.chance(0.1));
+            if fire_key {
+                cpu.memory_mut().set_channel_input(CHANNEL_MNKEYIN, 0o21);
+                cpu.request_rupt(key);
+                keys += 1;
+            }
+            if fire_up {
+                cpu.memory_mut().receive_uplink(words);
+                words += 1;
+            }
+            let res = cpu.step();
+            if fire_key && fire_up && res.took_interrupt {
+                assert_eq!(cpu.read(REGISTER_COUNTER), vector(key));
+                together += 1;
+            }
+        }
+        for _ in 0..100 {
+            cpu.step();
+        }
+        assert!(keys > 1000 && words > 1000 && together > 100);
+        assert_eq!((cpu.read(0o100), cpu.read(0o101)), (keys, words));
+    }
+
+    #[test]
+    fn overflow_in_a_holds_off_rupts() {
+        let mut cpu = Cpu::new(MemoryMap::new_blank());
+        cpu.gint = true;
+
+        cpu.write(REGISTER_ACCUMULATOR, 0o040000); // Positive overflow
+        assert!(!cpu.step().took_interrupt);
+        cpu.write(REGISTER_ACCUMULATOR, 0o177777);
+        assert!(cpu.step().took_interrupt);
+    }
+}
+
+#[cfg(test)]
+mod timer_tests {
+    use crate::constants::registers::REGISTER_COUNTER;
+    use crate::memory::mods::{EmuTime, RuptRequest};
+    use crate::test_rom::{Op::*, TestRom};
+
+    // Timer periods the handlers reload, in 10 ms ticks
+    const T3_TICKS: u16 = 100;
+    const T4_TICKS: u16 = 12;
+    const T5_TICKS: u16 = 10;
+
+    /// Jitter allowed between a timer pulse and its RUPT entry, in MCTs
+    const LATENCY: u64 = 20;
+
+    fn handler(timer: u16, reload: u16, count: u16) -> [crate::test_rom::Op; 4] {
+        [CA(reload), XCH(timer), INCR(count), RESUME]
+    }
+
+    #[test]
+    fn timer_rupts_fire_at_documented_rates() {
+        let rom = TestRom::new()
+            .emit(&[TCF(0o4100)])
+            .at(0o4010)
+            .emit(&handler(0o30, 0o4202, 0o100)) // T5RUPT
+            .emit(&handler(0o26, 0o4200, 0o101)) // T3RUPT
+            .emit(&handler(0o27, 0o4201, 0o102)) // T4RUPT
+            .at(0o4040)
+            .emit(&[RESUME]) // DOWNRUPT, pending at power-on
+            .at(0o4100)
+            .emit(&[
+                CA(0o4200),
+                XCH(0o26),
+                CA(0o4201),
+                XCH(0o27),
+                CA(0o4202),
+                XCH(0o30),
+                RELINT,
+                CA(0o67), // 4107: keep NIGHT WATCHMAN and TC TRAP quiet
+                TCF(0o4107),
+            ])
+            .at(0o4200)
+            .emit(&[
+                Word(0o40000 - T3_TICKS),
+                Word(0o40000 - T4_TICKS),
+                Word(0o40000 - T5_TICKS),
+            ]);
+
+        // The same again passing over the idle loop at 4107 with `idle`
+        for idling in [false, true] {
+            let (counts, steps) = timer_rupts(&rom, idling);
+            assert_eq!(counts, [100, 10, 83]);
+            assert_eq!(steps < 10_000, idling);
+        }
+    }
+
+    /// RUPTs taken over 10 s of the timer program, and the steps run
+    fn timer_rupts(rom: &TestRom, idling: bool) -> ([u32; 3], usize) {
+        let mut cpu = rom.cpu();
+
+        // Each RUPT follows its timer pulse by the same small latency:
+        // TIME3/TIME5 pulse on the 10 ms boundary, TIME4 7.5 ms later.
+        // Phases are in thirds of an MCT, which divide 10 ms exactly.
+        let period = 2560;
+        let vectors = [
+            RuptRequest::T5rupt,
+            RuptRequest::T3rupt,
+            RuptRequest::T4rupt,
+        ];
+        let mut counts = [0; 3];
+        let mut steps = 0;
+        let end = EmuTime::mcts_in_millis(10_005);
+        while (cpu.total_cycles as u64) < end {
+            let pc = cpu.read(REGISTER_COUNTER);
+            if idling && (0o4107..=0o4110).contains(&pc) {
+                let left = end - cpu.total_cycles as u64;
+                if cpu.idle(left as u32) > 0 {
+                    continue;
+                }
+            }
+            steps += 1;
+            if !cpu.step().took_interrupt {
+                continue;
+            }
+            let pc = cpu.read(REGISTER_COUNTER);
+
+            let idx = match vectors
+                .iter()
+                .position(|rupt| pc == 0o4000 + 4 * rupt.vector() as u16)
+            {
+                Some(idx) => idx,
+                None => continue,
+            };
+            counts[idx] += 1;
+
+            let pulse = match vectors[idx] {
+                RuptRequest::T4rupt => period * 3 / 4,
+                _ => 0,
+            };
+            let now = cpu.total_cycles as u64;
+            let late = (now * 3 + period - pulse) % period / 3;
+            assert!(
+                late < LATENCY,
+                "{:?} at {} is {} MCTs after its pulse",
+                vectors[idx],
+                now,
+                late
+            );
+        }
+
+        // 10 s at 100 Hz; TIME4 overflows at 117.5 ms, then every 120 ms
+        assert_eq!(cpu.read(0o100), counts[0] as u16);
+        assert_eq!(cpu.read(0o101), counts[1] as u16);
+        assert_eq!(cpu.read(0o102), counts[2] as u16);
+        (counts, steps)
+    }
+}
+
+#[cfg(test)]
+mod state_hash_tests {
+    use crate::test_rom::{Op::*, TestRom};
+
+    #[test]
+    fn equal_runs_hash_equal() {
+        let rom = TestRom::new().emit(&[CA(0o4003), XCH(0o100), Loop, Word(0o1234)]);
+        let mut a = rom.cpu();
+        let mut b = rom.cpu();
+        assert_eq!(a.state_hash(), b.state_hash());
+        for _ in 0..50 {
+            a.step();
+            b.step();
+        }
+        assert_eq!(a.read(0o100), 0o1234);
+        assert_eq!(a.state_hash(), b.state_hash());
+
+        b.write(0o101, 1);
+        assert_ne!(a.state_hash(), b.state_hash());
+        b.write(0o101, 0);
+        assert_eq!(a.state_hash(), b.state_hash());
+        a.step();
+        assert_ne!(a.state_hash(), b.state_hash());
     }
 }
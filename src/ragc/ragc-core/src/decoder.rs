@@ -1,83 +1,63 @@
-use crate::instructions::{Instructions, Mnemonic};
-use log::error;
+use crate::instructions::{ChannelCode, ExtraBits, Instructions, Mnemonic, QuarterCode};
+use crate::logging::error;
 
 /// Decode extended-format instructions
 fn decoder_extended(mut i: Instructions) -> Result<Instructions, &'static str> {
+    use ChannelCode::*;
+    use QuarterCode::*;
+
     let opbits = i.get_opcode(); // Extract the opcode bits
+    let quarter = QuarterCode::from_word(i.data);
 
-    match opbits {
-        // Opcode 0 is a special case with 3-bit extrabits
+    let (mnem, extrabits) = match opbits {
+        // Opcode 0 holds the I/O extracodes, split by peripheral code
         0 => {
-            // Extract extrabits from bits 9 to 11 (0x0E00)
-            let exb: u8 = ((i.data & 0x0E00) >> 9) as u8;
-            i.extrabits = Some(exb);
-
-            // Match specific extrabit values to extended opcodes
-            match i.extrabits {
-                Some(0) => i.mnem = Mnemonic::READ,
-                Some(1) => {
-                    i.mnem = Mnemonic::WRITE;
-                    i.mct = 2; // This instruction takes two memory cycles
-                }
-                Some(2) => i.mnem = Mnemonic::RAND,
-                Some(3) => i.mnem = Mnemonic::WAND,
-                Some(4) => i.mnem = Mnemonic::ROR,
-                Some(5) => i.mnem = Mnemonic::WOR,
-                Some(6) => i.mnem = Mnemonic::RXOR,
-                Some(7) => i.mnem = Mnemonic::EDRUPT,
-                _ => {
-                    error!(
-                        "Invalid Extrabits Encoding for {}: {:?}",
-                        opbits, i.extrabits
-                    );
-                    i.extrabits = None;
-                    return Err("Invalid Extrabits Encoding");
-                }
-            }
-            return Ok(i);
+            let code = ChannelCode::from_word(i.data);
+            let mnem = match code {
+                C0 => Mnemonic::READ,
+                C1 => Mnemonic::WRITE,
+                C2 => Mnemonic::RAND,
+                C3 => Mnemonic::WAND,
+                C4 => Mnemonic::ROR,
+                C5 => Mnemonic::WOR,
+                C6 => Mnemonic::RXOR,
+                C7 => Mnemonic::EDRUPT,
+            };
+            (mnem, ExtraBits::Channel(code))
         }
 
-        // Opcodes 1, 2, and 6 use 2-bit extrabits from bits 10-11
+        // Opcodes 1, 2, and 6 split by quarter code
         1 => {
-            let exb: u8 = ((i.data & 0x0C00) >> 10) as u8;
-            i.extrabits = Some(exb);
+            let mnem = match quarter {
+                Q0 => Mnemonic::DV,
+                _ => Mnemonic::BZF,
+            };
+            (mnem, ExtraBits::Quarter(quarter))
         }
 
         2 => {
-            let exb: u8 = ((i.data & 0x0C00) >> 10) as u8;
-            i.extrabits = Some(exb);
-
-            match i.extrabits {
-                Some(2) => i.mnem = Mnemonic::AUG,
-                Some(3) => i.mnem = Mnemonic::DIM,
-                _ => {
-                    error!(
-                        "Invalid Extrabits Encoding for {}: {:?}",
-                        opbits, i.extrabits
-                    );
-                    i.extrabits = None;
-                    return Err("Invalid Extrabits Encoding");
-                }
-            }
-            return Ok(i);
+            let mnem = match quarter {
+                Q0 => Mnemonic::MSU,
+                Q1 => Mnemonic::QXCH,
+                Q2 => Mnemonic::AUG,
+                Q3 => Mnemonic::DIM,
+            };
+            (mnem, ExtraBits::Quarter(quarter))
         }
 
-        3 => i.mnem = Mnemonic::DCA,
-        4 => i.mnem = Mnemonic::DCS,
-        5 => i.mnem = Mnemonic::INDEX,
+        3 => (Mnemonic::DCA, ExtraBits::None),
+        4 => (Mnemonic::DCS, ExtraBits::None),
+        5 => (Mnemonic::INDEX, ExtraBits::None),
 
         6 => {
-            let exb: u8 = ((i.data & 0x0C00) >> 10) as u8;
-            i.extrabits = Some(exb);
-            // SU if extrabits are 0, otherwise BZMF
-            i.mnem = if exb == 0 {
-                Mnemonic::SU
-            } else {
-                Mnemonic::BZMF
+            let mnem = match quarter {
+                Q0 => Mnemonic::SU,
+                _ => Mnemonic::BZMF,
             };
+            (mnem, ExtraBits::Quarter(quarter))
         }
 
-        7 => i.mnem = Mnemonic::MP,
+        7 => (Mnemonic::MP, ExtraBits::None),
 
         _ => {
             error!(
@@ -86,110 +66,68 @@ fn decoder_extended(mut i: Instructions) -> Result<Instructions, &'static str> {
             );
             return Err("Invalid Opcode Size");
         }
-    }
+    };
 
+    i.mnem = mnem;
+    i.extrabits = extrabits;
     Ok(i)
 }
 
 /// Decode simple-format instructions
 fn decoder_simple(mut i: Instructions) -> Result<Instructions, &'static str> {
+    use QuarterCode::*;
+
     let opbits = i.get_opcode(); // Extract the opcode bits
+    let quarter = QuarterCode::from_word(i.data);
 
-    match opbits {
+    let (mnem, extrabits) = match opbits {
         // Opcode 0 handles several special control instructions
         0 => {
-            i.mnem = match i.data & 0xFFF {
+            let mnem = match i.data & 0xFFF {
                 3 => Mnemonic::RELINT,
                 4 => Mnemonic::INHINT,
                 6 => Mnemonic::EXTEND,
                 _ => Mnemonic::TC,
             };
+            (mnem, ExtraBits::None)
         }
 
         1 => {
-            let exb: u8 = ((i.data & 0x0C00) >> 10) as u8;
-            i.extrabits = Some(exb);
-
-            match i.extrabits {
-                Some(0) => i.mnem = Mnemonic::CCS,
-                Some(1..=3) => i.mnem = Mnemonic::TCF,
-                _ => {
-                    error!(
-                        "Invalid Extrabits Encoding for {}: {:?}",
-                        opbits, i.extrabits
-                    );
-                    i.extrabits = None;
-                    return Err("Invalid Extrabits Encoding");
-                }
-            }
+            let mnem = match quarter {
+                Q0 => Mnemonic::CCS,
+                _ => Mnemonic::TCF,
+            };
+            (mnem, ExtraBits::Quarter(quarter))
         }
 
         2 => {
-            let exb: u8 = ((i.data & 0x0C00) >> 10) as u8;
-            i.extrabits = Some(exb);
-
-            match i.extrabits {
-                Some(0) => i.mnem = Mnemonic::DAS,
-                Some(1) => i.mnem = Mnemonic::LXCH,
-                Some(2) => i.mnem = Mnemonic::INCR,
-                Some(3) => i.mnem = Mnemonic::ADS,
-                _ => {
-                    error!(
-                        "Invalid Extrabits Encoding for {}: {:?}",
-                        opbits, i.extrabits
-                    );
-                    i.extrabits = None;
-                    return Err("Invalid Extrabits Encoding");
-                }
-            }
+            let mnem = match quarter {
+                Q0 => Mnemonic::DAS,
+                Q1 => Mnemonic::LXCH,
+                Q2 => Mnemonic::INCR,
+                Q3 => Mnemonic::ADS,
+            };
+            (mnem, ExtraBits::Quarter(quarter))
         }
 
-        3 => {
-            i.mnem = Mnemonic::CA;
-            i.mct = 2;
-        }
-
-        4 => {
-            i.mnem = Mnemonic::CS;
-            i.mct = 2;
-        }
+        3 => (Mnemonic::CA, ExtraBits::None),
+        4 => (Mnemonic::CS, ExtraBits::None),
 
         5 => {
-            let exb: u8 = ((i.data & 0x0C00) >> 10) as u8;
-            i.extrabits = Some(exb);
-
-            match i.extrabits {
-                Some(0) => {
-                    // Check for special RESUME instruction pattern
-                    if i.data & 0o07777 == 0o00017 {
-                        i.mnem = Mnemonic::RESUME;
-                    } else {
-                        i.mnem = Mnemonic::INDEX;
-                    }
-                }
-                Some(1) => i.mnem = Mnemonic::DXCH,
-                Some(2) => {
-                    i.mnem = Mnemonic::TS;
-                    i.mct = 2;
-                }
-                Some(3) => i.mnem = Mnemonic::XCH,
-                _ => {
-                    error!(
-                        "Invalid Extrabits Encoding for {}: {:?}",
-                        opbits, i.extrabits
-                    );
-                    i.extrabits = None;
-                    return Err("Invaid Extrabits Encoding");
-                }
-            }
+            let mnem = match quarter {
+                // INDEX 17 is the special RESUME instruction
+                Q0 if i.data & 0o07777 == 0o00017 => Mnemonic::RESUME,
+                Q0 => Mnemonic::INDEX,
+                Q1 => Mnemonic::DXCH,
+                Q2 => Mnemonic::TS,
+                Q3 => Mnemonic::XCH,
+            };
+            (mnem, ExtraBits::Quarter(quarter))
         }
 
-        6 => {
-            i.mnem = Mnemonic::AD;
-            i.mct = 2;
-        }
+        6 => (Mnemonic::AD, ExtraBits::None),
 
-        7 => i.mnem = Mnemonic::MASK,
+        7 => (Mnemonic::MASK, ExtraBits::None),
 
         _ => {
             error!(
@@ -198,8 +136,10 @@ fn decoder_simple(mut i: Instructions) -> Result<Instructions, &'static str> {
             );
             return Err("Invalid Opcode Size");
         }
-    }
+    };
 
+    i.mnem = mnem;
+    i.extrabits = extrabits;
     Ok(i)
 }
 
@@ -209,8 +149,7 @@ pub fn decoder(pc: u16, data: u16) -> Result<Instructions, &'static str> {
         pc,
         data,
         mnem: Mnemonic::INVALID, // Initial placeholder
-        extrabits: None,
-        mct: 1, // Default memory cycle count
+        extrabits: ExtraBits::None,
     };
 
     // Dispatch based on instruction type
@@ -220,3 +159,96 @@ pub fn decoder(pc: u16, data: u16) -> Result<Instructions, &'static str> {
         decoder_simple(i)
     }
 }
+
+#[cfg(test)]
+mod decoder_tests {
+    use super::decoder;
+    use crate::instructions::ExtraBits;
+    use core::fmt::Write;
+
+    /// Reference encodings, see the header of the file
+    const TABLE: &str = include_str!("decoder_table.txt");
+
+    struct Row<'t> {
+        mask: u32,
+        bits: u32,
+        mnem: &'t str,
+        extra: Option<u8>,
+        mct: u8,
+    }
+
+    fn parse_row(line: &str) -> Row<'_> {
+        let mut fields = line.split_whitespace();
+        let (mut mask, mut bits, mut width) = (0, 0, 0);
+        for c in fields.next().unwrap().chars().filter(|c| *c != '_') {
+            mask <<= 1;
+            bits <<= 1;
+            match c {
+                '0' => mask |= 1,
+                '1' => {
+                    mask |= 1;
+                    bits |= 1;
+                }
+                'x' => {}
+                _ => panic!("bad pattern in {:?}", line),
+            }
+            width += 1;
+        }
+        assert_eq!(width, 16, "pattern width in {:?}", line);
+
+        let mnem = fields.next().unwrap();
+        let extra = match fields.next().unwrap() {
+            "-" => None,
+            "Q" => Some(2),
+            "P" => Some(3),
+            other => panic!("bad extrabits {:?}", other),
+        };
+        let mct = fields.next().unwrap().parse().unwrap();
+        Row {
+            mask,
+            bits,
+            mnem,
+            extra,
+            mct,
+        }
+    }
+
+    #[test]
+    fn decodes_every_word_as_documented() {
+        let rows: heapless::Vec<Row, 64> = TABLE
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(parse_row)
+            .collect();
+
+        for word in 0..=0xFFFFu32 {
+            let row = rows
+                .iter()
+                .find(|row| word & row.mask == row.bits)
+                .unwrap_or_else(|| panic!("no reference row for {:06o}", word));
+            let inst = decoder(0o4000, word as u16).unwrap();
+
+            let mut mnem = heapless::String::<8>::new();
+            write!(mnem, "{:?}", inst.mnem).unwrap();
+            assert_eq!(mnem.as_str(), row.mnem, "mnemonic of {:06o}", word);
+            let extra = row.extra.map(|width| {
+                let shift = 12 - width;
+                ((word >> shift) & ((1 << width) - 1)) as u8
+            });
+            let decoded = match inst.extrabits {
+                ExtraBits::None => None,
+                ExtraBits::Quarter(code) => Some(code.bits()),
+                ExtraBits::Channel(code) => Some(code.bits()),
+            };
+            assert_eq!(decoded, extra, "extrabits of {:06o}", word);
+            assert_eq!(
+                matches!(inst.extrabits, ExtraBits::Channel(_)),
+                row.extra == Some(3),
+                "kind of extrabits of {:06o}",
+                word
+            );
+            assert_eq!(inst.cycles(), row.mct as u16, "mct of {:06o}", word);
+        }
+    }
+}
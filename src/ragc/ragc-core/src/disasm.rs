@@ -0,0 +1,177 @@
+use crate::constants::address_space::FIXED_FIXED_START;
+use crate::decoder::decoder;
+use crate::instructions::{ExtraBits, Mnemonic};
+use crate::memory::{AddressSpace, Region};
+use core::fmt;
+
+/// What the address field of an instruction refers to
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Operand {
+    None,         // RELINT, INHINT, EXTEND, RESUME
+    Memory(u16),  // Erasable or fixed address
+    Channel(u16), // I/O channel
+}
+
+/// One instruction word decoded for display
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Disassembled {
+    pub mnem: Mnemonic,
+    pub operand: Operand,
+    pub extended: bool,           // Preceded by EXTEND
+    pub pseudo: Option<Mnemonic>, // Idiomatic name, shown in its place
+}
+
+/// Interrupt vectors at 4000 + 4n by number, the restart entry first
+pub const RUPT_VECTORS: [&str; 11] = [
+    "GOJAM", "T6RUPT", "T5RUPT", "T3RUPT", "T4RUPT", "KEYRUPT1", "KEYRUPT2", "UPRUPT", "DOWNRUPT",
+    "RADARUPT", "HANDRUPT",
+];
+
+/// Vector a fixed-fixed address lies in, and its word within the vector
+pub const fn rupt_vector(addr: usize) -> Option<(&'static str, usize)> {
+    match addr.checked_sub(FIXED_FIXED_START) {
+        Some(n) if n < 4 * RUPT_VECTORS.len() => Some((RUPT_VECTORS[n / 4], n % 4)),
+        _ => None,
+    }
+}
+
+/// Name the original listings give the word at `addr` (as addressed in
+/// its bank's window), where it is one of the assembler's idioms
+pub fn idiom(word: u16, extended: bool, addr: u16) -> Option<Mnemonic> {
+    let word = word & 0o77777;
+    Some(match (extended, word) {
+        (false, 0o00000) => Mnemonic::XXALQ,
+        (false, 0o00001) => Mnemonic::XLQ,
+        (false, 0o00002) => Mnemonic::RETURN,
+        (false, 0o20001) => Mnemonic::DDOUBL,
+        (false, 0o22007) => Mnemonic::ZL,
+        (false, 0o30000) => Mnemonic::NOOP,
+        (false, 0o40000) => Mnemonic::COM,
+        (false, 0o52005) => Mnemonic::DTCF,
+        (false, 0o52006) => Mnemonic::DTCB,
+        (false, 0o54000) => Mnemonic::OVSK,
+        (false, 0o54005) => Mnemonic::TCAA,
+        (false, 0o60000) => Mnemonic::DOUBLE,
+        // NOOP in fixed memory assembles to TCF to the next word
+        (false, 0o10000..=0o17777) if word & 0o7777 == addr.wrapping_add(1) => Mnemonic::NOOP,
+        (true, 0o22007) => Mnemonic::ZQ,
+        (true, 0o40001) => Mnemonic::DCOM,
+        (true, 0o70000) => Mnemonic::SQUARE,
+        _ => return None,
+    })
+}
+
+/// Decode a fixed-memory word without executing it
+pub fn disassemble(word: u16, extended: bool) -> Option<Disassembled> {
+    let data = (word & 0o77777) | if extended { 0o100000 } else { 0 };
+    let inst = decoder(0, data).ok()?;
+    let operand = match (inst.mnem, inst.extrabits) {
+        (Mnemonic::RELINT | Mnemonic::INHINT | Mnemonic::EXTEND | Mnemonic::RESUME, _) => {
+            Operand::None
+        }
+        (Mnemonic::EDRUPT, _) => Operand::Memory(inst.get_address() as u16),
+        (_, ExtraBits::Channel(_)) => Operand::Channel(word & 0o777),
+        // Branches to fixed memory take the quarter code as address bits
+        (Mnemonic::TCF | Mnemonic::BZF | Mnemonic::BZMF, _) => {
+            Operand::Memory(inst.get_address() as u16)
+        }
+        // Other quartercoded instructions address erasable only
+        (_, ExtraBits::Quarter(_)) => Operand::Memory(word & 0o1777),
+        _ => Operand::Memory(inst.get_address() as u16),
+    };
+    Some(Disassembled {
+        mnem: inst.mnem,
+        operand,
+        extended,
+        pseudo: None,
+    })
+}
+
+impl Disassembled {
+    /// Fixed address control may pass to, for TC/TCF/BZF/BZMF
+    pub fn branch_target(&self) -> Option<u16> {
+        match (self.mnem, self.operand) {
+            (Mnemonic::TC | Mnemonic::TCF | Mnemonic::BZF | Mnemonic::BZMF, Operand::Memory(k)) => {
+                Some(k)
+            }
+            _ => None,
+        }
+    }
+
+    /// What the operand reaches under `space`; None for no operand, or
+    /// one in a window whose bank `space` leaves unknown
+    pub fn operand_region(&self, space: &AddressSpace) -> Option<Region> {
+        match self.operand {
+            Operand::None => None,
+            Operand::Memory(k) => space.classify(k as usize),
+            Operand::Channel(ch) => AddressSpace::classify_channel(ch as usize),
+        }
+    }
+}
+
+impl fmt::Display for Disassembled {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(pseudo) = self.pseudo {
+            return write!(f, "{}", pseudo.name());
+        }
+        let name = self.mnem.name();
+        match self.operand {
+            Operand::None => write!(f, "{}", name),
+            Operand::Memory(k) => write!(f, "{:<8}{:04o}", name, k),
+            Operand::Channel(ch) => write!(f, "{:<8}{:02o}", name, ch),
+        }
+    }
+}
+
+#[cfg(test)]
+mod disasm_tests {
+    use super::{disassemble, idiom, rupt_vector, Operand};
+    use crate::instructions::Mnemonic;
+    use crate::memory::{AddressSpace, Region};
+    use core::fmt::Write;
+
+    #[test]
+    fn decodes_operands_by_instruction_class() {
+        let tc = disassemble(0o04123, false).unwrap();
+        assert_eq!(tc.branch_target(), Some(0o4123));
+
+        let ts = disassemble(0o54123, false).unwrap();
+        assert_eq!(ts.mnem, Mnemonic::TS);
+        assert_eq!(ts.operand, Operand::Memory(0o123));
+        assert_eq!(ts.branch_target(), None);
+
+        let write = disassemble(0o01013, true).unwrap();
+        assert_eq!(write.operand, Operand::Channel(0o13));
+        let space = AddressSpace::new(4, 0o20, 0);
+        assert_eq!(write.operand_region(&space), Some(Region::Channel));
+        let ca = disassemble(0o31402, false).unwrap();
+        assert_eq!(ca.operand_region(&space), Some(Region::Erasable(4)));
+        assert_eq!(ca.operand_region(&AddressSpace::UNBANKED), None);
+
+        let mut text = heapless::String::<16>::new();
+        write!(text, "{}", write).unwrap();
+        assert_eq!(text.as_str(), "WRITE   13");
+        assert_eq!(disassemble(0o00006, false).unwrap().operand, Operand::None);
+
+        // Idioms keep the real instruction for analysis
+        let mut ret = disassemble(0o00002, false).unwrap();
+        assert_eq!(ret.pseudo, None);
+        ret.pseudo = idiom(0o00002, false, 0o4000);
+        assert_eq!(
+            (ret.mnem, ret.pseudo),
+            (Mnemonic::TC, Some(Mnemonic::RETURN))
+        );
+        let mut text = heapless::String::<16>::new();
+        write!(text, "{}", ret).unwrap();
+        assert_eq!(text.as_str(), "RETURN");
+        assert_eq!(idiom(0o22007, true, 0o4000), Some(Mnemonic::ZQ));
+        assert_eq!(idiom(0o14001, false, 0o4000), Some(Mnemonic::NOOP));
+        assert_eq!(idiom(0o14001, false, 0o4001), None);
+
+        assert_eq!(rupt_vector(0o4000), Some(("GOJAM", 0)));
+        assert_eq!(rupt_vector(0o4016), Some(("T3RUPT", 2)));
+        assert_eq!(rupt_vector(0o4050), Some(("HANDRUPT", 0)));
+        assert_eq!(rupt_vector(0o4054), None);
+        assert_eq!(rupt_vector(0o3777), None);
+    }
+}
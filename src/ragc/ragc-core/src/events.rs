@@ -0,0 +1,80 @@
+/// Architectural state mutation, captured when the `events` feature is enabled
+/// Applying the same event stream to the same starting state reproduces a run,
+/// which is the basis for replay, time-travel and delta snapshots
+#[derive(Clone, Copy, PartialEq, Debug)]
+#[non_exhaustive]
+pub enum Event {
+    RegWrite {
+        addr: usize,
+        value: u16,
+    }, // Central, editing, timer or special register
+    ErasableWrite {
+        bank: usize,
+        offset: usize,
+        value: u16,
+    }, // Physical erasable location
+    ChannelWrite {
+        channel: usize,
+        value: u16,
+    }, // I/O channel
+    RuptRaise {
+        rupt: u8,
+    }, // Interrupt request latched by the CPU
+    ProgramChanged {
+        program: Option<u8>,
+    }, // Major mode written to the ROM's MODREG (None: no program)
+    Alarm {
+        code: u16,
+    }, // Alarm code stored in the ROM's FAILREG
+    DskyFlash {
+        lamps: u16,
+    }, // Flashing DSKY lamps changed, as `MemoryMap::dsky_flash` shows them
+}
+
+/// Receiver for recorded events
+pub trait EventSink {
+    fn record(&mut self, event: Event);
+}
+
+/// Fixed-capacity event log for no_std hosts; events past capacity are dropped
+impl<const N: usize> EventSink for heapless::Vec<Event, N> {
+    fn record(&mut self, event: Event) {
+        let _ = self.push(event);
+    }
+}
+
+#[cfg(test)]
+mod event_tests {
+    use super::Event;
+    use crate::constants::registers::REGISTER_ERASABLE_BANK;
+    use crate::cpu::Cpu;
+    use crate::memory::MemoryMap;
+
+    #[test]
+    fn replay_reproduces_recorded_writes() {
+        let mut log: heapless::Vec<Event, 16> = heapless::Vec::new();
+        {
+            let mut cpu = Cpu::new(MemoryMap::new_blank());
+            cpu.set_event_sink(&mut log);
+
+            cpu.write(0o100, 0o1234);
+            cpu.write(REGISTER_ERASABLE_BANK, 0o2400);
+            cpu.write(0o1400, 0o4321); // Lands in E5 via the switched window
+            cpu.write_io(0o12, 0o7);
+        }
+        assert!(log.contains(&Event::ErasableWrite {
+            bank: 5,
+            offset: 0,
+            value: 0o4321
+        }));
+
+        let mut replay = Cpu::new(MemoryMap::new_blank());
+        for event in log.iter() {
+            replay.apply_event(event);
+        }
+
+        assert_eq!(replay.read(0o100), 0o1234);
+        assert_eq!(replay.read(0o1400), 0o4321);
+        assert_eq!(replay.read_io(0o12), 0o7);
+    }
+}
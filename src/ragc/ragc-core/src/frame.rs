@@ -0,0 +1,263 @@
+use crate::constants::ports;
+use crate::cpu::{Cpu, UnprogSequence};
+use crate::memory::mods::RuptRequest;
+
+/// Keycodes that can wait for later frames
+pub const MAX_KEYS: usize = 8;
+
+/// Counter pulses that can be queued for one frame
+pub const MAX_COUNTERS: usize = 64;
+
+/// Input channel levels applied at the start of a frame
+pub const MAX_DISCRETES: usize = 8;
+
+/// Channel writes collected during one frame: every one a 10 ms frame (853
+/// MCTs) can hold, at 3 MCTs for each EXTEND and WRITE
+pub const MAX_WRITES: usize = 320;
+
+/// Downlink words collected during one frame
+pub const MAX_DOWNLINK: usize = 32;
+
+/// Steady counter pulse trains running at once
+pub const MAX_TRAINS: usize = 4;
+
+/// A counter pulsed every `period` MCTs until stopped, across frames: too
+/// fast for per-frame queuing, like a CDU hunting without settling
+#[derive(Clone, Copy, Debug)]
+pub struct PulseTrain {
+    pub seq: UnprogSequence,
+    pub period: u32,
+    due: u32, // MCTs into the frame the next pulse is due
+}
+
+/// Peripheral traffic exchanged with the computer over one frame
+///
+/// Inputs are consumed by `Cpu::run_frame`; outputs are cleared at the start
+/// of every frame and filled while it runs.
+pub struct FrameIo {
+    /// DSKY keycodes, oldest first. One key is pressed per frame.
+    pub keys: heapless::Deque<u16, MAX_KEYS>,
+    /// Counter pulses (PIPA, CDU...), delivered one per step
+    pub counters: heapless::Deque<UnprogSequence, MAX_COUNTERS>,
+    /// Input channel levels (channels 30-33...) set before the first step
+    pub discretes: heapless::Vec<(usize, u16), MAX_DISCRETES>,
+    /// Counter pulse trains, kept from frame to frame
+    pub trains: heapless::Vec<PulseTrain, MAX_TRAINS>,
+
+    /// Channel writes in delivery order
+    pub channel_writes: heapless::Vec<(usize, u16), MAX_WRITES>,
+    /// MCTs each pitch/yaw jet bit (channel 5) was on
+    pub pyjets: [u32; 8],
+    /// MCTs each roll jet bit (channel 6) was on
+    pub rolljets: [u32; 8],
+    /// Words written to the downlink channels 34/35
+    pub downlink: heapless::Vec<u16, MAX_DOWNLINK>,
+    /// MCTs actually run, which may overshoot the request by one instruction
+    pub cycles: u32,
+    /// Channel writes and downlink words that did not fit the buffers
+    pub dropped: u32,
+
+    key_held: bool, // A keycode injected last frame, cleared at this one
+}
+
+impl FrameIo {
+    pub fn new() -> Self {
+        Self {
+            keys: heapless::Deque::new(),
+            counters: heapless::Deque::new(),
+            discretes: heapless::Vec::new(),
+            trains: heapless::Vec::new(),
+            channel_writes: heapless::Vec::new(),
+            pyjets: [0; 8],
+            rolljets: [0; 8],
+            downlink: heapless::Vec::new(),
+            cycles: 0,
+            dropped: 0,
+            key_held: false,
+        }
+    }
+
+    /// Queue a DSKY keycode for a coming frame
+    pub fn press_key(&mut self, keycode: u16) -> bool {
+        self.keys.push_back(keycode).is_ok()
+    }
+
+    /// Queue a counter pulse for the next frame
+    pub fn pulse(&mut self, seq: UnprogSequence) -> bool {
+        self.counters.push_back(seq).is_ok()
+    }
+
+    /// Pulse a counter every `period` MCTs from the next frame on
+    pub fn start_train(&mut self, seq: UnprogSequence, period: u32) -> bool {
+        let train = PulseTrain {
+            seq,
+            period: period.max(1),
+            due: 0,
+        };
+        self.trains.push(train).is_ok()
+    }
+
+    pub fn stop_trains(&mut self) {
+        self.trains.clear();
+    }
+
+    /// Drive an input channel level at the start of the next frame
+    pub fn set_discrete(&mut self, channel: usize, value: u16) -> bool {
+        self.discretes.push((channel, value)).is_ok()
+    }
+
+    fn clear_outputs(&mut self) {
+        self.channel_writes.clear();
+        self.pyjets = [0; 8];
+        self.rolljets = [0; 8];
+        self.downlink.clear();
+        self.cycles = 0;
+        self.dropped = 0;
+    }
+
+    fn record_write(&mut self, channel: usize, value: u16) {
+        if self.channel_writes.push((channel, value)).is_err() {
+            self.dropped += 1;
+        }
+        let downlink = channel == ports::CHANNEL_CHAN34 || channel == ports::CHANNEL_CHAN35;
+        if downlink && self.downlink.push(value).is_err() {
+            self.dropped += 1;
+        }
+    }
+}
+
+impl Default for FrameIo {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn accumulate_jets(on: &mut [u32; 8], bits: u16, cycles: u16) {
+    for (bit, total) in on.iter_mut().enumerate() {
+        if bits & (1 << bit) != 0 {
+            *total += cycles as u32;
+        }
+    }
+}
+
+impl<'a, const UNPROG_DEPTH: usize, const TRACE_DEPTH: usize> Cpu<'a, UNPROG_DEPTH, TRACE_DEPTH> {
+    /// Run at least `mcts` MCTs, exchanging one frame of peripheral traffic
+    pub fn run_frame(&mut self, io: &mut FrameIo, mcts: u32) -> u32 {
+        io.clear_outputs();
+
+        for (channel, value) in io.discretes.iter() {
+            self.memory_mut().set_channel_input(*channel, *value);
+        }
+        io.discretes.clear();
+        // KEYRUPT has taken last frame's key; clear it so an attached DSKY's
+        // keys show through again
+        if io.key_held {
+            self.memory_mut()
+                .set_channel_input(ports::CHANNEL_MNKEYIN, 0);
+            io.key_held = false;
+        }
+        if let Some(key) = io.keys.pop_front() {
+            self.memory_mut()
+                .set_channel_input(ports::CHANNEL_MNKEYIN, key);
+            self.request_rupt(RuptRequest::Keyrupt1);
+            io.key_held = true;
+        }
+
+        // Drained after every step, so the journal never fills
+        self.memory_mut().set_channel_journal(true);
+        while io.cycles < mcts {
+            if let Some(seq) = io.counters.pop_front() {
+                self.request_counter(seq);
+            }
+            for train in io.trains.iter_mut() {
+                while train.due <= io.cycles {
+                    self.request_counter(train.seq);
+                    train.due += train.period;
+                }
+            }
+            let cycles = self.step().cycles;
+            io.cycles += cycles as u32;
+
+            let pyjets = self.read_io(ports::CHANNEL_PYJETS);
+            let rolljets = self.read_io(ports::CHANNEL_ROLLJETS);
+            accumulate_jets(&mut io.pyjets, pyjets, cycles);
+            accumulate_jets(&mut io.rolljets, rolljets, cycles);
+            self.memory_mut()
+                .drain_channel_journal(|channel, value| io.record_write(channel, value));
+        }
+        self.memory_mut().set_channel_journal(false);
+        for train in io.trains.iter_mut() {
+            train.due -= io.cycles.min(train.due);
+        }
+
+        io.cycles
+    }
+}
+
+#[cfg(test)]
+mod frame_tests {
+    use super::FrameIo;
+    use crate::constants::ports;
+    use crate::constants::special_registers::SPECIAL_REGISTER_OPTICAL_Y;
+    use crate::cpu::UnprogSequence;
+    use crate::memory::mods::RuptRequest;
+    use crate::test_rom::{Op::*, TestRom};
+
+    #[test]
+    fn frame_exchanges_inputs_and_outputs() {
+        // Loop firing pitch/yaw jets 1 and 2
+        let rom = TestRom::new().emit(&[WRITE(0o5), TCF(0o4000)]);
+        let mut cpu = rom.cpu();
+        cpu.write(0, 0o3);
+
+        let mut io = FrameIo::new();
+        assert!(io.press_key(0o21));
+        assert!(io.set_discrete(ports::CHANNEL_CHAN30, 0o12345));
+        let cycles = cpu.run_frame(&mut io, 100);
+
+        assert!(cycles >= 100);
+        assert_eq!(cycles, io.cycles);
+        assert!(io.pyjets[0] > 0 && io.pyjets[1] > 0);
+        assert_eq!(io.pyjets[2], 0);
+        assert!(io.channel_writes.contains(&(ports::CHANNEL_PYJETS, 0o3)));
+        assert!(io.downlink.is_empty());
+        assert!(io.keys.is_empty() && io.discretes.is_empty());
+
+        assert_eq!(cpu.read_io(ports::CHANNEL_MNKEYIN), 0o21);
+        assert_eq!(cpu.read_io(ports::CHANNEL_CHAN30), 0o12345);
+        assert_ne!(cpu.pending_rupts() & RuptRequest::Keyrupt1.mask(), 0);
+
+        // Outputs only cover the latest frame, and the key is gone once its
+        // KEYRUPT has had a frame
+        cpu.run_frame(&mut io, 10);
+        assert!(io.channel_writes.len() <= 10);
+        assert_eq!(cpu.read_io(ports::CHANNEL_MNKEYIN), 0);
+
+        // A whole 10 ms frame of channel writes fits, none dropped
+        let rom = TestRom::new().emit(&[WRITE(0o5), WRITE(0o6), TCF(0o4000)]);
+        let mut busy = rom.cpu();
+        busy.run_frame(&mut io, 853);
+        assert!(io.channel_writes.len() > 200);
+        assert_eq!(io.dropped, 0);
+        assert_eq!(busy.memory().channel_journal_dropped(), 0);
+
+        // A pulse train keeps its pace across frames, each pulse stealing
+        // an MCT
+        assert!(io.start_train(UnprogSequence::PCDU(SPECIAL_REGISTER_OPTICAL_Y), 10));
+        let mut cycles = 0;
+        for _ in 0..5 {
+            cycles += cpu.run_frame(&mut io, 100);
+        }
+        let pulses = cpu.read(SPECIAL_REGISTER_OPTICAL_Y) as u32;
+        assert!(
+            pulses.abs_diff(cycles / 10) <= 1,
+            "{} in {}",
+            pulses,
+            cycles
+        );
+        assert_eq!(cpu.stolen_cycles(SPECIAL_REGISTER_OPTICAL_Y), pulses);
+        io.stop_trains();
+        cpu.run_frame(&mut io, 100);
+        assert_eq!(cpu.read(SPECIAL_REGISTER_OPTICAL_Y) as u32, pulses);
+    }
+}
@@ -0,0 +1,74 @@
+//! Host callbacks at AGC software addresses, e.g. on entry to ALARM or
+//! BANKCALL: software breakpoints that run a handler and carry on, without
+//! tracing every instruction.
+use crate::memory::MemoryMap;
+
+/// Hooks a CPU can hold
+pub const MAX_HOOKS: usize = 16;
+
+/// Fixed memory location a hook is keyed by: logical bank and offset
+/// within it, with fixed-fixed 4000-7777 as banks 2 and 3
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct FixedAddress {
+    pub bank: usize,
+    pub offset: usize,
+}
+
+impl FixedAddress {
+    pub const fn new(bank: usize, offset: usize) -> Self {
+        Self { bank, offset }
+    }
+}
+
+/// Registers as the hooked instruction is about to execute
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct HookContext {
+    pub at: FixedAddress,
+    pub pc: u16,
+    pub a: u16,
+    pub l: u16,
+    pub q: u16, // Return address of a TC into the hooked routine
+    pub eb: u16,
+    pub fb: u16,
+    pub cycles: usize, // Total MCTs elapsed
+}
+
+/// Handler run before each instruction fetched from its address
+pub trait Hook {
+    fn call(&mut self, ctx: &HookContext, mem: &MemoryMap);
+}
+
+impl<F: FnMut(&HookContext, &MemoryMap)> Hook for F {
+    fn call(&mut self, ctx: &HookContext, mem: &MemoryMap) {
+        self(ctx, mem)
+    }
+}
+
+#[cfg(test)]
+mod hook_tests {
+    use super::{FixedAddress, HookContext};
+    use crate::memory::MemoryMap;
+    use crate::test_rom::{Op::*, TestRom};
+
+    #[test]
+    fn hooks_see_each_call_with_its_caller() {
+        // Call the routine at 4010 twice, with an inline argument after
+        // each that the routine steps over on return
+        let rom = TestRom::new()
+            .emit(&[TC(0o4010), Word(0o210), TC(0o4010), Word(0o1105), Loop])
+            .at(0o4010)
+            .emit(&[INCR(0o2), TC(0o2)]);
+        let mut calls = heapless::Vec::<(u16, u16), 4>::new();
+        let mut log = |ctx: &HookContext, mem: &MemoryMap| {
+            let _ = calls.push((ctx.q, mem.read(ctx.q as usize)));
+        };
+        {
+            let mut cpu = rom.cpu();
+            assert!(cpu.add_hook(FixedAddress::new(2, 0o10), &mut log));
+            for _ in 0..12 {
+                cpu.step();
+            }
+        }
+        assert_eq!(calls, [(0o4001, 0o210), (0o4003, 0o1105)]);
+    }
+}
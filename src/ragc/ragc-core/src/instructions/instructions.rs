@@ -1,22 +1,30 @@
 use super::Instructions;
+use crate::checks;
 use crate::constants::registers::*;
 use crate::cpu::Cpu;
+use crate::logging::warn;
 use crate::utils;
 use crate::utils::{adjust_overflow, extend_sign_bits};
-use log::warn;
+use crate::word::Word15;
 
 /// AGC arithmetic operations (ones' complement implementation)
 pub trait Arithmatic {
     fn ad(&mut self, cmd: &Instructions) -> u16; // Add
     fn ads(&mut self, cmd: &Instructions) -> u16; // Add to storage
+    fn das(&mut self, cmd: &Instructions) -> u16; // Double add to storage
     fn mp(&mut self, cmd: &Instructions) -> u16; // Multiply
     fn su(&mut self, cmd: &Instructions) -> u16; // Subtract
     fn incr(&mut self, cmd: &Instructions) -> u16; // Increment
     fn dim(&mut self, cmd: &Instructions) -> u16; // Decrement if minus
     fn dv(&mut self, cmd: &Instructions) -> u16; // Divide
+    fn aug(&mut self, cmd: &Instructions) -> u16; // Augment
+    fn msu(&mut self, cmd: &Instructions) -> u16; // Modular subtract
+    fn mask(&mut self, cmd: &Instructions) -> u16; // Bitwise AND into A
 }
 
-impl<'a> Arithmatic for Cpu<'a> {
+impl<'a, const UNPROG_DEPTH: usize, const TRACE_DEPTH: usize> Arithmatic
+    for Cpu<'a, UNPROG_DEPTH, TRACE_DEPTH>
+{
     fn ad(&mut self, cmd: &Instructions) -> u16 {
         // Ones' complement addition with end-around carry
         let a = self.read_s16(REGISTER_ACCUMULATOR) as u16;
@@ -30,7 +38,7 @@ impl<'a> Arithmatic for Cpu<'a> {
 
         self.write_s16(REGISTER_ACCUMULATOR, (c & 0xFFFF) as u16);
         self.check_editing(cmd.get_address());
-        2
+        cmd.cycles()
     }
 
     fn ads(&mut self, cmd: &Instructions) -> u16 {
@@ -46,7 +54,7 @@ impl<'a> Arithmatic for Cpu<'a> {
         let res = (z & 0xFFFF) as u16;
         self.write_s16(REGISTER_ACCUMULATOR, res);
         self.write_s16(cmd.get_address_ram(), res);
-        2
+        cmd.cycles()
     }
 
     fn mp(&mut self, cmd: &Instructions) -> u16 {
@@ -60,6 +68,7 @@ impl<'a> Arithmatic for Cpu<'a> {
         };
 
         let val2 = self.read_s15(cmd.get_address());
+        self.check_editing(cmd.get_address());
         let s2 = val2 & 0o40000;
         let mag2 = if s2 != 0 {
             (!val2) & 0o37777
@@ -78,7 +87,7 @@ impl<'a> Arithmatic for Cpu<'a> {
             }
         }
         self.write_dp(REGISTER_ACCUMULATOR, output);
-        3 // Multiplication takes 3 cycles
+        cmd.cycles()
     }
 
     fn incr(&mut self, cmd: &Instructions) -> u16 {
@@ -87,12 +96,8 @@ impl<'a> Arithmatic for Cpu<'a> {
         let curr = self.read(reg) as u32;
 
         let next = match reg {
-            REGISTER_ACCUMULATOR | REGISTER_MULTIPLIER => match curr {
-                // 16-bit handling
-                0o077777 => curr & 0o177777, // Rollover from positive to negative
-                0o177777 => 0o000001,        // Rollover from negative to positive
-                _ => (curr + 1) & 0o177777,
-            },
+            // 16 bits, so +overflow runs on into -overflow
+            REGISTER_ACCUMULATOR | REGISTER_MULTIPLIER => utils::add_s16(curr as u16, 1) as u32,
             _ => match curr {
                 // 15-bit registers
                 0o37777 => 0, // Positive overflow
@@ -102,7 +107,7 @@ impl<'a> Arithmatic for Cpu<'a> {
         };
 
         self.write(reg, next as u16);
-        2
+        cmd.cycles()
     }
 
     fn su(&mut self, cmd: &Instructions) -> u16 {
@@ -115,50 +120,138 @@ impl<'a> Arithmatic for Cpu<'a> {
         }
         self.write_s16(REGISTER_ACCUMULATOR, (c & 0xFFFF) as u16);
         self.check_editing(cmd.get_address_ram());
-        2
+        cmd.cycles()
     }
 
     fn dim(&mut self, cmd: &Instructions) -> u16 {
-        // Decrement if negative (AGC-specific instruction)
+        // Diminish the magnitude by one, leaving +0 and -0 alone. The sign
+        // is bit 16, so A and Q holding an overflow count by it.
         let addr = cmd.get_address_ram();
         let val = self.read_s16(addr);
 
         match val {
-            0o177777 | 0 => {} // No action on -0 or 0
-            _ => {
-                if val & 0o40000 != 0 {
-                    // Negative value
-                    self.write_s16(addr, val + 1);
-                } else {
-                    // Positive value
-                    self.write_s16(addr, if val - 1 == 0 { 0o177777 } else { val - 1 });
-                }
-            }
+            0o177777 | 0 => self.check_editing(addr),
+            _ if val & 0o100000 != 0 => self.write_s16(addr, utils::add_s16(val, 1)),
+            _ => self.write_s16(addr, utils::add_s16(val, 0o177776)),
         };
-        2
+        cmd.cycles()
+    }
+
+    fn aug(&mut self, cmd: &Instructions) -> u16 {
+        // Grow the magnitude by one, so +0 becomes +1 and -0 becomes -1.
+        // As with DIM, A and Q holding an overflow count by bit 16.
+        let addr = cmd.get_address_ram();
+        let val = self.read_s16(addr);
+        match val & 0o100000 {
+            0 => self.write_s16(addr, utils::add_s16(val, 1)),
+            _ => self.write_s16(addr, utils::add_s16(val, 0o177776)),
+        };
+        cmd.cycles()
+    }
+
+    fn msu(&mut self, cmd: &Instructions) -> u16 {
+        // A - K as 15-bit two's-complement words, for angles such as CDU
+        // readings, with the difference converted back to ones' complement
+        let addr = cmd.get_address_ram();
+        let a = self.read_s15(REGISTER_ACCUMULATOR);
+        let k = self.read_s15(addr);
+        self.check_editing(addr);
+        let diff = a.wrapping_sub(k) & 0o77777;
+        let diff = match diff & 0o40000 {
+            0 => diff,
+            _ => diff - 1,
+        };
+        self.write_s15(REGISTER_ACCUMULATOR, diff);
+        cmd.cycles()
+    }
+
+    fn mask(&mut self, cmd: &Instructions) -> u16 {
+        // K is sign-extended into bit 16 like any operand
+        let addr = cmd.get_address();
+        let a = self.read_s16(REGISTER_ACCUMULATOR);
+        let k = self.read_s16(addr);
+        self.write_s16(REGISTER_ACCUMULATOR, a & k);
+        self.check_editing(addr);
+        cmd.cycles()
     }
 
     fn dv(&mut self, cmd: &Instructions) -> u16 {
-        // Division with null value handling (0 and -0)
-        let nulls = [0o77777, 0]; // -0 and +0 representations
-        let d = self.read_s15(cmd.get_address_ram());
+        // Divide (A,L) by K: quotient to A, remainder to L. A dividend not
+        // smaller than the divisor is undefined; saturate the quotient.
+        let divisor = self.read_s15(cmd.get_address_ram());
+        self.check_editing(cmd.get_address_ram());
         let num_high = self.read_s15(REGISTER_ACCUMULATOR);
         let num_low = self.read_s15(REGISTER_LINK);
 
-        let s_div = d & 0o40000;
-        let s_num = if nulls.contains(&num_high) {
-            num_low & 0o40000 // Use low word's sign if high word is null
-        } else {
-            num_high & 0o40000
+        // Mixed-sign words are combined before dividing; a null high word
+        // leaves the sign to the low word
+        let high = Word15::new(num_high).to_signed().get() as i32;
+        let low = Word15::new(num_low).to_signed().get() as i32;
+        let dividend = high * (1 << 14) + low;
+        let s_num = match high {
+            0 => num_low & 0o40000 != 0,
+            _ => num_high & 0o40000 != 0,
         };
+        let s_div = divisor & 0o40000 != 0;
 
-        if nulls.contains(&num_high) && nulls.contains(&num_low) {
-            self.write_s15(
-                REGISTER_ACCUMULATOR,
-                if s_num ^ s_div == 0 { 0o37777 } else { 0o40000 },
-            );
+        let num = dividend.unsigned_abs();
+        let div = Word15::new(divisor).to_signed().get().unsigned_abs() as u32;
+        let quotient = match div {
+            0 => 0o37777,
+            _ => (num / div).min(0o37777),
+        };
+        let remainder = (num - quotient * div) & 0o37777;
+
+        self.write_s15(REGISTER_ACCUMULATOR, sp_word(quotient, s_num != s_div));
+        self.write_s15(REGISTER_LINK, sp_word(remainder, s_num));
+        cmd.cycles()
+    }
+
+    fn das(&mut self, cmd: &Instructions) -> u16 {
+        // The address field holds K+1: the low word, with the high word below
+        let low_addr = cmd.get_address_ram();
+        let high_addr = low_addr.wrapping_sub(1) & 0o1777;
+
+        let mut low = utils::add_s16(self.read_s16(REGISTER_LINK), self.read_s16(low_addr));
+        let mut high = utils::add_s16(
+            self.read_s16(REGISTER_ACCUMULATOR),
+            self.read_s16(high_addr),
+        );
+        // Carry out of the low word into the high word
+        match low & 0xC000 {
+            0x4000 => high = utils::add_s16(high, 1),
+            0x8000 => high = utils::add_s16(high, 0o177776),
+            _ => {}
         }
-        6 // Division takes 6 cycles
+        low = adjust_overflow(low);
+        checks::corrected("DAS low word", low);
+
+        if high_addr == REGISTER_ACCUMULATOR {
+            // DDOUBL: the sum stays in A,L
+            self.write_s16(REGISTER_ACCUMULATOR, high);
+            self.write_s16(REGISTER_LINK, low);
+            return cmd.cycles();
+        }
+
+        self.write_s16(high_addr, high);
+        self.write_s16(low_addr, low);
+        let overflow = match high & 0xC000 {
+            0x4000 => 0o000001,
+            0x8000 => 0o177776,
+            _ => 0,
+        };
+        self.write_s16(REGISTER_ACCUMULATOR, overflow);
+        self.write_s16(REGISTER_LINK, 0);
+        cmd.cycles()
+    }
+}
+
+/// 15-bit ones'-complement word from a magnitude and sign
+fn sp_word(mag: u32, negative: bool) -> u16 {
+    checks::magnitude("DV result", mag);
+    match negative {
+        true => !(mag as u16) & 0o77777,
+        false => mag as u16,
     }
 }
 
@@ -167,9 +260,14 @@ pub trait ControlFlow {
     fn tcf(&mut self, cmd: &Instructions) -> u16; // Unconditional jump
     fn bzf(&mut self, cmd: &Instructions) -> u16; // Branch if zero
     fn tc(&mut self, cmd: &Instructions) -> u16; // Subroutine call
+    fn ccs(&mut self, cmd: &Instructions) -> u16; // Count, compare and skip
+    fn index(&mut self, cmd: &Instructions) -> u16; // Modify the next instruction
+    fn bzmf(&mut self, cmd: &Instructions) -> u16; // Branch if zero or minus
 }
 
-impl<'a> ControlFlow for Cpu<'a> {
+impl<'a, const UNPROG_DEPTH: usize, const TRACE_DEPTH: usize> ControlFlow
+    for Cpu<'a, UNPROG_DEPTH, TRACE_DEPTH>
+{
     fn bzf(&mut self, cmd: &Instructions) -> u16 {
         self.ec_flag = false; // Reset extended cycle flag
 
@@ -187,9 +285,25 @@ impl<'a> ControlFlow for Cpu<'a> {
 
                 self.write(REGISTER_COUNTER, destination);
                 self.ir = self.read(destination as usize); // Pre-fetch
-                1 // Cycle count for taken branch
+                cmd.branch_cycles()
             }
-            _ => 2, // Not taken branch cycle count
+            _ => cmd.cycles(),
+        }
+    }
+
+    fn bzmf(&mut self, cmd: &Instructions) -> u16 {
+        // As BZF, but negative A (by bit 16, so negative overflow) branches too
+        let reg_a = self.read(REGISTER_ACCUMULATOR);
+        match reg_a == 0 || reg_a & 0x8000 != 0 {
+            true => {
+                let destination = cmd.get_data() & 0xFFF;
+                if (destination & 0xC00) == 0x0 {
+                    warn!("BZMF jumping to non-fixed memory!");
+                }
+                self.update_pc(destination);
+                cmd.branch_cycles()
+            }
+            false => cmd.cycles(),
         }
     }
 
@@ -198,7 +312,7 @@ impl<'a> ControlFlow for Cpu<'a> {
         let jump_target = cmd.get_data();
         self.update_pc(jump_target);
         self.ec_flag = false; // Clear extended instruction flag
-        1 // Always 1 cycle
+        cmd.cycles()
     }
 
     fn tc(&mut self, cmd: &Instructions) -> u16 {
@@ -210,7 +324,119 @@ impl<'a> ControlFlow for Cpu<'a> {
         self.write(REGISTER_RETURN, current_pc); // Store return
         self.ec_flag = false; // Reset instruction flag
 
-        1 // Cycle count
+        cmd.cycles()
+    }
+
+    fn ccs(&mut self, cmd: &Instructions) -> u16 {
+        // A takes the diminished absolute value; the skip picks one of the
+        // four words that follow by the operand's sign and zeroness
+        let k = cmd.get_address_ram();
+        let value = self.read_s16(k);
+        let (a, skip) = match value {
+            0 => (0, 1),
+            0xFFFF => (0, 3),
+            _ if value & 0x8000 == 0 => (value - 1, 0),
+            _ => (!value - 1, 2),
+        };
+        self.write_s16(REGISTER_ACCUMULATOR, a);
+        self.check_editing(k);
+        if skip != 0 {
+            let next = self.read(REGISTER_COUNTER);
+            self.update_pc(next + skip);
+        }
+        cmd.cycles()
+    }
+
+    fn index(&mut self, cmd: &Instructions) -> u16 {
+        // Added to the next instruction as it is fetched. The extracode
+        // reaches fixed memory, and EXTEND stays in force across it.
+        let k = match cmd.is_extended() {
+            true => cmd.get_address(),
+            false => cmd.get_address_ram(),
+        };
+        self.idx_val = self.read_s15(k);
+        self.check_editing(k);
+        cmd.cycles()
+    }
+}
+
+#[cfg(test)]
+mod control_flow_tests {
+    use crate::constants::registers::REGISTER_ACCUMULATOR;
+    use crate::test_rom::{Op::*, TestRom};
+
+    #[test]
+    fn ccs_diminishes_and_skips_by_sign() {
+        let rom = TestRom::new().emit(&[
+            CCS(0o100),
+            TCF(0o4020),
+            TCF(0o4021),
+            TCF(0o4022),
+            TCF(0o4023),
+        ]);
+        for (k, a, next) in [
+            (0o00005, 0o00004, 0o4001), // Positive
+            (0o00000, 0o00000, 0o4002), // +0
+            (0o77772, 0o00004, 0o4003), // Negative
+            (0o77777, 0o00000, 0o4004), // -0
+        ] {
+            let mut cpu = rom.cpu();
+            cpu.write(0o100, k);
+            cpu.step();
+            assert_eq!(cpu.read(REGISTER_ACCUMULATOR), a, "CCS {:05o}", k);
+            assert_eq!(cpu.step().pc, next, "CCS {:05o}", k);
+            assert_eq!(cpu.read(0o100), k, "CCS {:05o} rewrote K", k);
+        }
+    }
+
+    #[test]
+    fn index_modifies_the_next_instruction() {
+        let rom = TestRom::new()
+            .emit(&[INDEX(0o100), CA(0o4010), Loop])
+            .at(0o4010)
+            .emit(&[Word(1), Word(2), Word(3)]);
+        let mut cpu = rom.cpu();
+        cpu.write(0o100, 2);
+        cpu.step();
+        cpu.step();
+        assert_eq!(cpu.read(REGISTER_ACCUMULATOR), 3);
+    }
+
+    #[test]
+    fn extended_index_reaches_fixed_memory_and_keeps_extend() {
+        // INDEX 4010 as an extracode, then SU 100 indexed to SU 101
+        let rom = TestRom::new()
+            .emit(&[EXTEND, Word(0o54010), Word(0o60100), Loop])
+            .at(0o4010)
+            .emit(&[Word(1)]);
+        let mut cpu = rom.cpu();
+        cpu.write(REGISTER_ACCUMULATOR, 10);
+        cpu.write(0o100, 1);
+        cpu.write(0o101, 3);
+        for _ in 0..3 {
+            cpu.step();
+        }
+        assert_eq!(cpu.read(REGISTER_ACCUMULATOR), 7);
+    }
+
+    #[test]
+    fn bzmf_branches_on_zero_and_minus() {
+        let rom = TestRom::new().emit(&[BZMF(0o4020), Loop]);
+        for (a, taken) in [
+            (0o000000, true),
+            (0o177777, true),  // -0
+            (0o177776, true),  // -1
+            (0o100000, true),  // Negative overflow
+            (0o000001, false), // +1
+            (0o040000, false), // Positive overflow
+        ] {
+            let mut cpu = rom.cpu();
+            cpu.write(REGISTER_ACCUMULATOR, a);
+            cpu.step(); // EXTEND
+            cpu.step();
+            let next = if taken { 0o4020 } else { 0o4002 };
+            assert_eq!(cpu.step().pc, next, "BZMF with A={:06o}", a);
+        }
     }
 }
 
@@ -221,23 +447,32 @@ pub trait Interrupt {
     fn resume(&mut self, cmd: &Instructions) -> u16; // Return from interrupt
 }
 
-impl<'a> Interrupt for Cpu<'a> {
-    fn inhint(&mut self, _cmd: &Instructions) -> u16 {
+impl<'a, const UNPROG_DEPTH: usize, const TRACE_DEPTH: usize> Interrupt
+    for Cpu<'a, UNPROG_DEPTH, TRACE_DEPTH>
+{
+    fn inhint(&mut self, cmd: &Instructions) -> u16 {
         self.gint = false; // Disable general interrupts
-        1 // 1 MCT (machine cycle time)
+        cmd.cycles()
     }
 
-    fn relint(&mut self, _cmd: &Instructions) -> u16 {
+    fn relint(&mut self, cmd: &Instructions) -> u16 {
         self.gint = true; // Re-enable interrupt processing
-        1
+        cmd.cycles()
     }
 
-    fn edrupt(&mut self, _cmd: &Instructions) -> u16 {
-        self.gint = false; // Disable during emergency
-        3 // Takes longer due to priority handling
+    fn edrupt(&mut self, cmd: &Instructions) -> u16 {
+        // An interrupt the program takes itself, for checkout: ZRUPT and
+        // BRUPT are saved as for any RUPT, and control goes to location 0
+        let val = self.read(REGISTER_COUNTER) + 1;
+        self.write(REGISTER_COUNTER_BACKUP, val);
+        self.write(REGISTER_INSTRUCTION, self.ir);
+        self.gint = false;
+        self.is_irupt = true;
+        self.update_pc(0);
+        cmd.cycles()
     }
 
-    fn resume(&mut self, _cmd: &Instructions) -> u16 {
+    fn resume(&mut self, cmd: &Instructions) -> u16 {
         // Restore pre-interrupt state from backup registers
         let shadow_pc = self.read(REGISTER_COUNTER_BACKUP) - 1; // Adjust for return
         self.write(REGISTER_COUNTER, shadow_pc);
@@ -248,15 +483,85 @@ impl<'a> Interrupt for Cpu<'a> {
         self.gint = true; // Re-enable interrupts
         self.is_irupt = false; // Clear interrupt state
 
-        2 // Resume takes 2 cycles
+        cmd.cycles()
     }
 }
 
 #[cfg(test)]
 mod interrupt_tests {
+    use crate::constants::registers::{
+        REGISTER_ACCUMULATOR, REGISTER_COUNTER, REGISTER_COUNTER_BACKUP, REGISTER_INSTRUCTION,
+    };
+    use crate::memory::mods::RuptRequest;
+    use crate::test_rom::{Op::*, TestRom};
+
+    fn vector(rupt: RuptRequest) -> u16 {
+        0o4000 + 4 * rupt.vector() as u16
+    }
+
+    #[test]
+    fn relint_admits_pending_rupt_and_resume_returns() {
+        let rom = TestRom::new()
+            .emit(&[TCF(0o4100)])
+            .at(vector(RuptRequest::Downrupt))
+            .emit(&[RESUME])
+            .at(0o4100)
+            .emit(&[RELINT, CA(0o4110), Loop])
+            .at(0o4110)
+            .emit(&[Word(0o123)]);
+        let mut cpu = rom.cpu();
+
+        // DOWNRUPT is pending from power-on but interrupts start inhibited
+        assert!(!cpu.step().took_interrupt); // TCF
+        assert!(!cpu.step().took_interrupt); // RELINT
+        assert!(cpu.step().took_interrupt);
+        assert_eq!(cpu.read(REGISTER_COUNTER), vector(RuptRequest::Downrupt));
+
+        cpu.step(); // RESUME
+        assert_eq!(cpu.read(REGISTER_COUNTER), 0o4101);
+        assert_eq!(cpu.step().pc, 0o4101);
+        assert_eq!(cpu.read(REGISTER_ACCUMULATOR), 0o123);
+    }
+
     #[test]
-    fn test_interrupt_stubs() {
-        // TODO: Implement actual interrupt tests
+    fn extracode_completes_before_rupt() {
+        let rom = TestRom::new()
+            .emit(&[TCF(0o4100)])
+            .at(vector(RuptRequest::Keyrupt1))
+            .emit(&[RESUME])
+            .at(vector(RuptRequest::Downrupt))
+            .emit(&[RESUME])
+            .at(0o4100)
+            .emit(&[RELINT, WRITE(0o12), Loop]);
+        let mut cpu = rom.cpu();
+        cpu.step(); // TCF
+        cpu.step(); // RELINT
+        assert!(cpu.step().took_interrupt); // DOWNRUPT
+        cpu.step(); // RESUME
+        assert_eq!(cpu.step().pc, 0o4101); // EXTEND
+
+        cpu.request_rupt(RuptRequest::Keyrupt1);
+        let res = cpu.step();
+        assert!(!res.took_interrupt);
+        assert_eq!(res.pc, 0o4102); // WRITE
+        assert!(cpu.step().took_interrupt);
+        assert_eq!(cpu.read(REGISTER_COUNTER), vector(RuptRequest::Keyrupt1));
+    }
+
+    #[test]
+    fn edrupt_interrupts_to_location_zero_and_resumes() {
+        let rom = TestRom::new().emit(&[EDRUPT(0), CA(0o4010), Loop]);
+        let mut cpu = rom.cpu();
+        cpu.write(REGISTER_ACCUMULATOR, 0o50017); // RESUME, run from A
+        cpu.step(); // EXTEND
+        cpu.step();
+        assert_eq!(cpu.read(REGISTER_COUNTER), 0);
+        assert_eq!(cpu.read(REGISTER_COUNTER_BACKUP), 0o4003);
+        assert_eq!(cpu.read(REGISTER_INSTRUCTION), 0o34010); // CA 4010
+
+        // RUPTs are held off, so the RESUME in A runs
+        assert!(!cpu.step().took_interrupt);
+        assert_eq!(cpu.read(REGISTER_COUNTER), 0o4002);
     }
 }
 
@@ -271,7 +576,9 @@ pub trait Io {
     fn rxor(&mut self, cmd: &Instructions) -> u16; // Read XOR
 }
 
-impl<'a> Io for Cpu<'a> {
+impl<'a, const UNPROG_DEPTH: usize, const TRACE_DEPTH: usize> Io
+    for Cpu<'a, UNPROG_DEPTH, TRACE_DEPTH>
+{
     fn ror(&mut self, cmd: &Instructions) -> u16 {
         let port = cmd.get_data() & 0x1FF; // 9-bit I/O channel address
         let port_value = self.read_io(port as usize);
@@ -288,7 +595,7 @@ impl<'a> Io for Cpu<'a> {
                 self.write_s15(REGISTER_ACCUMULATOR, masked_result & 0x7FFF);
             }
         };
-        2 // Fixed 2-cycle timing for I/O ops
+        cmd.cycles()
     }
 
     fn rand(&mut self, cmd: &Instructions) -> u16 {
@@ -305,7 +612,7 @@ impl<'a> Io for Cpu<'a> {
                 self.write_s15(REGISTER_ACCUMULATOR, masked_result & 0x7FFF);
             }
         };
-        2
+        cmd.cycles()
     }
 
     fn rxor(&mut self, cmd: &Instructions) -> u16 {
@@ -322,7 +629,7 @@ impl<'a> Io for Cpu<'a> {
                 self.write_s15(REGISTER_ACCUMULATOR, xor_result & 0x7FFF);
             }
         };
-        2
+        cmd.cycles()
     }
 
     fn wor(&mut self, cmd: &Instructions) -> u16 {
@@ -343,7 +650,7 @@ impl<'a> Io for Cpu<'a> {
                 self.write_io(port, masked_value & 0x7FFF);
             }
         };
-        2
+        cmd.cycles()
     }
 
     fn wand(&mut self, cmd: &Instructions) -> u16 {
@@ -362,7 +669,7 @@ impl<'a> Io for Cpu<'a> {
                 self.write_io(port, masked_value & 0x7FFF);
             }
         };
-        2
+        cmd.cycles()
     }
 
     fn read_instr(&mut self, cmd: &Instructions) -> u16 {
@@ -372,7 +679,7 @@ impl<'a> Io for Cpu<'a> {
             _ => extend_sign_bits(self.read_io(port as usize)), // Sign-extend 15-bit
         };
         self.write_s16(REGISTER_ACCUMULATOR, input_data);
-        2
+        cmd.cycles()
     }
 
     fn write_instr(&mut self, cmd: &Instructions) -> u16 {
@@ -388,7 +695,7 @@ impl<'a> Io for Cpu<'a> {
                 self.write_io(port as usize, adjust_overflow(data) & 0x7FFF);
             }
         }
-        2
+        cmd.cycles()
     }
 }
 
@@ -401,17 +708,21 @@ pub trait LoadStore {
     fn xch(&mut self, cmd: &Instructions) -> u16;
     fn lxch(&mut self, cmd: &Instructions) -> u16;
     fn qxch(&mut self, cmd: &Instructions) -> u16;
+    fn ts(&mut self, cmd: &Instructions) -> u16;
+    fn dxch(&mut self, cmd: &Instructions) -> u16;
 }
 
-impl<'a> LoadStore for Cpu<'a> {
+impl<'a, const UNPROG_DEPTH: usize, const TRACE_DEPTH: usize> LoadStore
+    for Cpu<'a, UNPROG_DEPTH, TRACE_DEPTH>
+{
     // Clear and Subtract - loads complement of memory into accumulator
     fn cs(&mut self, cmd: &Instructions) -> u16 {
-        let location: usize = cmd.get_data() as usize;
+        let location = cmd.get_address();
         let mut inverted_value = self.read_s16(location);
         inverted_value = !inverted_value & 0xFFFF;
         self.write_s16(REGISTER_ACCUMULATOR, inverted_value);
-        self.check_editing(cmd.get_address());
-        2
+        self.check_editing(location);
+        cmd.cycles()
     }
 
     // Double Clear and Subtract - handles two consecutive memory words
@@ -419,15 +730,15 @@ impl<'a> LoadStore for Cpu<'a> {
         // Uses big-endian format: high word at lower address
         let base_addr = cmd.get_address() - 1;
 
-        let negated_low = (!self.read_s16(base_addr + 1)) & 0xFFFF;
-        self.write(REGISTER_LINK, negated_low);
+        let negated_low = !self.read_s16(base_addr + 1);
+        self.write_s16(REGISTER_LINK, negated_low);
 
-        let negated_high = (!self.read_s16(base_addr)) & 0xFFFF;
-        self.write(REGISTER_ACCUMULATOR, negated_high);
+        let negated_high = !self.read_s16(base_addr);
+        self.write_s16(REGISTER_ACCUMULATOR, negated_high);
 
         self.check_editing(base_addr + 1);
         self.check_editing(base_addr);
-        3
+        cmd.cycles()
     }
 
     // Double Clear and Add - loads two memory words into A and L registers
@@ -442,7 +753,30 @@ impl<'a> LoadStore for Cpu<'a> {
 
         self.check_editing(base_address + 1);
         self.check_editing(base_address);
-        3
+        cmd.cycles()
+    }
+
+    // Double Exchange - swaps A,L with K,K+1, the low words first. The
+    // address field holds K+1. DXCH Z (DTCB) and DXCH FB (DTCF) jump.
+    fn dxch(&mut self, cmd: &Instructions) -> u16 {
+        let low_addr = cmd.get_address_ram();
+        let high_addr = low_addr.wrapping_sub(1) & 0o1777;
+
+        let l_reg_value = self.read_s16(REGISTER_LINK);
+        let low = self.read_s16(low_addr);
+        self.write_s16(low_addr, l_reg_value);
+        self.write_s16(REGISTER_LINK, low);
+
+        let a_reg_value = self.read_s16(REGISTER_ACCUMULATOR);
+        let high = self.read_s16(high_addr);
+        self.write_s16(high_addr, a_reg_value);
+        self.write_s16(REGISTER_ACCUMULATOR, high);
+
+        if REGISTER_COUNTER == high_addr || REGISTER_COUNTER == low_addr {
+            let target = self.read(REGISTER_COUNTER);
+            self.update_pc(target);
+        }
+        cmd.cycles()
     }
 
     // Exchange Link register with memory
@@ -454,16 +788,16 @@ impl<'a> LoadStore for Cpu<'a> {
 
         self.write_s16(REGISTER_LINK, mem_value);
         self.write_s16(swap_addr, l_reg_value);
-        2
+        cmd.cycles()
     }
 
     // Clear and Add - loads memory value into accumulator
     fn ca(&mut self, cmd: &Instructions) -> u16 {
-        let source: usize = cmd.get_data() as usize;
+        let source = cmd.get_address();
         let data = self.read_s16(source);
         self.write_s16(REGISTER_ACCUMULATOR, data);
         self.check_editing(source);
-        2
+        cmd.cycles()
     }
 
     // Exchange Q register (return address) with memory
@@ -474,7 +808,7 @@ impl<'a> LoadStore for Cpu<'a> {
 
         self.write_s16(target_addr, lr_value);
         self.write_s16(REGISTER_RETURN, temp_value);
-        2
+        cmd.cycles()
     }
 
     // Exchange accumulator with memory (with overflow adjustment)
@@ -483,9 +817,335 @@ impl<'a> LoadStore for Cpu<'a> {
         let mem_data = self.read_s16(exchange_addr);
         let a_reg_value = self.read_s16(REGISTER_ACCUMULATOR);
 
-        // Handle overflow correction when storing
-        self.write_s16(exchange_addr, utils::adjust_overflow(a_reg_value));
+        // Q takes all 16 bits; 15-bit locations take A overflow-corrected
+        self.write_s16(exchange_addr, a_reg_value);
         self.write_s16(REGISTER_ACCUMULATOR, mem_data);
-        2
+        cmd.cycles()
+    }
+
+    // Transfer to storage - stores A; on overflow A becomes +/-1 and the
+    // next instruction is skipped
+    fn ts(&mut self, cmd: &Instructions) -> u16 {
+        let target = cmd.get_address_ram();
+        let a_reg_value = self.read_s16(REGISTER_ACCUMULATOR);
+        if target != REGISTER_ACCUMULATOR {
+            self.write_s16(target, a_reg_value);
+        }
+
+        let overflow = match a_reg_value & 0xC000 {
+            0x4000 => 0o000001,
+            0x8000 => 0o177776,
+            _ => return cmd.cycles(),
+        };
+        // TS A (OVSK) keeps the overflowed value
+        if target != REGISTER_ACCUMULATOR {
+            self.write_s16(REGISTER_ACCUMULATOR, overflow);
+        }
+        let next = self.read(REGISTER_COUNTER);
+        self.update_pc(next + 1);
+        cmd.branch_cycles()
+    }
+}
+
+#[cfg(test)]
+mod load_store_tests {
+    use crate::constants::ports::CHANNEL_SUPERBNK;
+    use crate::constants::registers::{
+        REGISTER_ACCUMULATOR, REGISTER_COUNTER, REGISTER_FIXED_BANK, REGISTER_RETURN,
+    };
+    use crate::cpu::Cpu;
+    use crate::hooks::FixedAddress;
+    use crate::memory::rom::FixedMemory;
+    use crate::memory::{Location, MemoryMapBuilder};
+    use crate::test_rom::{Op::*, TestRom};
+
+    #[test]
+    fn subroutine_adds_and_stores() {
+        let rom = TestRom::new()
+            .emit(&[CA(0o4010), AD(0o4011), TC(0o4020), Loop])
+            .at(0o4010)
+            .emit(&[Word(5), Word(3)])
+            .at(0o4020)
+            .emit(&[XCH(0o100), CS(0o100), TC(REGISTER_RETURN as u16)]);
+        let mut cpu = rom.cpu();
+        for _ in 0..6 {
+            cpu.step();
+        }
+        assert_eq!(cpu.read(0o100), 0o10);
+        assert_eq!(cpu.read(REGISTER_ACCUMULATOR), 0o177767); // -8, sign extended
+
+        // TC Q runs the return address held in Q as a TC
+        assert_eq!(cpu.step().pc, REGISTER_RETURN as u16);
+        assert_eq!(cpu.step().pc, 0o4003);
+    }
+
+    /// The test program in every fixed bank, each holding its own bank
+    /// number at 210
+    struct EveryBank<'r>(&'r TestRom);
+
+    impl FixedMemory for EveryBank<'_> {
+        fn bank_count(&self) -> usize {
+            0o44
+        }
+
+        fn word(&self, bank: usize, offset: usize) -> u16 {
+            match offset {
+                0o210 => bank as u16,
+                _ => self.0.word(2, offset),
+            }
+        }
+    }
+
+    #[test]
+    fn operands_follow_the_bank_registers() {
+        // Run the routine at 200 from whichever bank FB selects; its CA of
+        // 2210 reads that bank, its CA of 4210 fixed-fixed bank 2
+        let rom = TestRom::new().emit(&[TCF(0o2200)]).at(0o4200).emit(&[
+            CA(0o2210),
+            XCH(0o100),
+            CA(0o4210),
+            XCH(0o101),
+            Loop,
+        ]);
+        let banks = EveryBank(&rom);
+        for (fb, superbank, bank) in [
+            (0o10, 0, 0o10),
+            (0o30, 0, 0o30),
+            (0o30, 0o100, 0o40),
+            (0o27, 0o100, 0o27),
+        ] {
+            let mut cpu = Cpu::new(MemoryMapBuilder::new().fixed_memory(&banks).build());
+            cpu.write(REGISTER_FIXED_BANK, fb << 10);
+            cpu.write_io(CHANNEL_SUPERBNK, superbank);
+            assert_eq!(
+                cpu.memory().locate(0o2200),
+                Some(Location::Fixed(FixedAddress::new(bank as usize, 0o200)))
+            );
+            for _ in 0..5 {
+                cpu.step();
+            }
+            assert_eq!((cpu.read(0o100), cpu.read(0o101)), (bank, 2));
+        }
+    }
+
+    #[test]
+    fn dxch_z_jumps_and_switches_banks() {
+        // DTCB: L to BB and A to Z, saving the return in A,L
+        let rom = TestRom::new()
+            .emit(&[DCA(0o4011), DXCH(REGISTER_COUNTER as u16 + 1), Loop])
+            .at(0o4010)
+            .emit(&[Word(0o2100), Word(0o4000)]);
+        let mut cpu = rom.cpu();
+        for _ in 0..3 {
+            cpu.step();
+        }
+        assert_eq!(cpu.step().pc, 0o2100);
+        assert_eq!(cpu.read(REGISTER_FIXED_BANK), 0o4000);
+        assert_eq!(cpu.read(REGISTER_ACCUMULATOR), 0o4003);
+    }
+}
+
+#[cfg(test)]
+mod arithmetic_tests {
+    use crate::constants::registers::{REGISTER_ACCUMULATOR, REGISTER_LINK};
+    use crate::rng::Rng;
+    use crate::test_rom::{Op, Op::*, TestRom};
+    use crate::utils::extend_sign_bits;
+    use num_bigint::BigInt;
+    use num_traits::{Signed, ToPrimitive, Zero};
+
+    const CASES: usize = 2000;
+    const K: u16 = 0o100;
+
+    // Textbook ones'-complement arithmetic on arbitrary-precision
+    // integers, sharing no word-width shortcuts with the emulator
+
+    fn value(word: u16, bits: u32) -> BigInt {
+        let mask = (1u32 << bits) - 1;
+        let word = word as u32 & mask;
+        match word >> (bits - 1) {
+            0 => BigInt::from(word),
+            _ => -BigInt::from(!word & mask),
+        }
+    }
+
+    fn encode(v: &BigInt, bits: u32) -> u16 {
+        let mask = (1u32 << bits) - 1;
+        let mag = v.abs().to_u32().filter(|mag| *mag < 1 << (bits - 1));
+        let mag = mag.unwrap_or_else(|| panic!("{} overflows", v));
+        match v.is_negative() {
+            true => (!mag & mask) as u16,
+            false => mag as u16,
+        }
+    }
+
+    /// Weight of the high word of a double precision pair
+    fn high_weight() -> BigInt {
+        BigInt::from(1) << 14
+    }
+
+    fn negative(word: u16) -> bool {
+        word & 0o40000 != 0
+    }
+
+    /// 15-bit word from a magnitude and sign, keeping the sign of zero
+    fn signed(mag: &BigInt, neg: bool) -> u16 {
+        let mag = mag.to_u16().filter(|mag| *mag <= 0o37777).unwrap();
+        match neg {
+            true => !mag & 0o77777,
+            false => mag,
+        }
+    }
+
+    /// Adder result: +0 only when both inputs are +0
+    fn add16(a: u16, b: u16) -> u16 {
+        let sum = value(a, 16) + value(b, 16);
+        match sum.is_zero() {
+            true if a == 0 && b == 0 => 0,
+            true => 0o177777,
+            false => encode(&sum, 16),
+        }
+    }
+
+    /// Mostly uniform 15-bit words, with the edge values weighted up
+    fn operand(rng: &mut Rng) -> u16 {
+        const EDGES: [u16; 6] = [0, 0o77777, 1, 0o77776, 0o37777, 0o40000];
+        match rng.below(4) {
+            0 => EDGES[rng.below(EDGES.len() as u64) as usize],
+            _ => rng.below(0o100000) as u16,
+        }
+    }
+
+    /// Run one instruction on operands at K (and K+1), A and L
+    fn run(op: Op, a: u16, l: u16, k: &[u16]) -> (u16, u16, [u16; 2]) {
+        let rom = TestRom::new().emit(&[op, Loop]);
+        let mut cpu = rom.cpu();
+        cpu.write(REGISTER_ACCUMULATOR, extend_sign_bits(a));
+        cpu.write(REGISTER_LINK, l);
+        for (idx, word) in k.iter().enumerate() {
+            cpu.write(K as usize + idx, *word);
+        }
+        while cpu.step().pc != 0o4000 + rom_len(op) - 1 {}
+        let a = cpu.read(REGISTER_ACCUMULATOR);
+        let l = cpu.read(REGISTER_LINK);
+        (a, l, [cpu.read(K as usize), cpu.read(K as usize + 1)])
+    }
+
+    fn rom_len(op: Op) -> u16 {
+        match op {
+            AD(_) | DAS(_) => 1,
+            _ => 2, // EXTEND
+        }
+    }
+
+    #[test]
+    fn ad_and_su_match_oracle() {
+        let mut rng = Rng::new(4933);
+        for _ in 0..CASES {
+            let (a, k) = (operand(&mut rng), operand(&mut rng));
+            let (a16, k16) = (extend_sign_bits(a), extend_sign_bits(k));
+
+            let (sum, _, _) = run(AD(K), a, 0, &[k]);
+            assert_eq!(sum, add16(a16, k16), "AD {:05o} + {:05o}", a, k);
+
+            let (diff, _, _) = run(SU(K), a, 0, &[k]);
+            assert_eq!(diff, add16(a16, !k16), "SU {:05o} - {:05o}", a, k);
+        }
+    }
+
+    #[test]
+    fn msu_subtracts_modulo_two_to_the_fifteenth() {
+        for (a, k, diff) in [
+            (0o00005, 0o00003, 0o000002),
+            (0o00003, 0o00005, 0o177775), // -2 once back in ones' complement
+            (0o00001, 0o77777, 0o000002), // 77777 is -1 in two's complement
+            (0o37777, 0o40000, 0o177776), // Wraps past the top
+        ] {
+            let (value, _, _) = run(MSU(K), a, 0, &[k]);
+            assert_eq!(value, diff, "MSU {:05o} - {:05o}", a, k);
+        }
+    }
+
+    #[test]
+    fn mp_matches_oracle() {
+        let mut rng = Rng::new(4934);
+        for _ in 0..CASES {
+            let (a, k) = (operand(&mut rng), operand(&mut rng));
+            let product = value(a, 15).abs() * value(k, 15).abs();
+            // Factors of opposite sign give -0 unless both are zero
+            let zeros = value(a, 15).is_zero() && value(k, 15).is_zero();
+            let neg = negative(a) != negative(k) && !zeros;
+
+            let (hi, lo, _) = run(MP(K), a, 0, &[k]);
+            let (high, low) = (&product / high_weight(), &product % high_weight());
+            let expected = (signed(&high, neg), signed(&low, neg));
+            assert_eq!((hi & 0o77777, lo), expected, "MP {:05o} * {:05o}", a, k);
+        }
+    }
+
+    #[test]
+    fn dv_matches_oracle() {
+        let mut rng = Rng::new(4935);
+        for _ in 0..CASES {
+            let divisor = operand(&mut rng);
+            let d = value(divisor, 15).abs();
+            if d.is_zero() {
+                continue;
+            }
+            // Dividend magnitude must stay below the divisor's
+            let hi = BigInt::from(rng.below(d.to_u64().unwrap()));
+            let hi = if rng.below(2) == 0 { hi } else { -hi };
+            let (a, l) = (encode(&hi, 15), operand(&mut rng));
+            let dividend = value(a, 15) * high_weight() + value(l, 15);
+            let dividend_neg = match value(a, 15).is_zero() {
+                true => negative(l),
+                false => negative(a),
+            };
+
+            let (q, r) = (dividend.abs() / &d, dividend.abs() % &d);
+            let expected = (
+                signed(&q, dividend_neg != negative(divisor)),
+                signed(&r, dividend_neg),
+            );
+            let (quot, rem, _) = run(DV(K), a, l, &[divisor]);
+            assert_eq!(
+                (quot & 0o77777, rem),
+                expected,
+                "DV {:05o},{:05o} / {:05o}",
+                a,
+                l,
+                divisor
+            );
+        }
+    }
+
+    #[test]
+    fn das_matches_oracle() {
+        let mut rng = Rng::new(4936);
+        for _ in 0..CASES {
+            let (a, l) = (operand(&mut rng), operand(&mut rng));
+            let (hi, lo) = (operand(&mut rng), operand(&mut rng));
+            let exact =
+                (value(a, 15) + value(hi, 15)) * high_weight() + value(l, 15) + value(lo, 15);
+
+            // DAS K assembles with K+1 in its address field
+            let (ovf, l_after, [hi2, lo2]) = run(DAS(K + 1), a, l, &[hi, lo]);
+            let stored = value(hi2, 15) * high_weight() + value(lo2, 15);
+            let ovf = value(ovf, 16);
+            assert!(
+                ovf.abs() <= BigInt::from(1) && l_after == 0,
+                "DAS overflow {}",
+                ovf
+            );
+            assert_eq!(
+                stored + ovf * high_weight() * high_weight(),
+                exact,
+                "DAS {:05o},{:05o} + {:05o},{:05o}",
+                a,
+                l,
+                hi,
+                lo
+            );
+        }
     }
 }
@@ -1,4 +1,9 @@
 pub mod instructions;
+#[cfg(test)]
+mod operand_tests;
+#[cfg(test)]
+mod sequence_tests;
+pub mod timing;
 
 // Import trait implementations for CPU instruction categories
 pub use instructions::Arithmatic;
@@ -15,7 +20,11 @@ const OPCODE_OFFSET: u16 = 12; // Bit position of primary opcode
 const OPCODE_EXTEND: u16 = 0o100000; // Bit pattern for extended instruction prefix
 
 /// Enum representing AGC instruction mnemonics
-/// Note: Not all instructions are implemented in this emulation
+/// Note: Not all instructions are implemented in this emulation. The
+/// pseudo-instructions are assembler names for particular words; the
+/// decoder never produces them, only the disassembler shows them.
+#[derive(Clone, Copy, PartialEq, Debug)]
+#[non_exhaustive]
 pub enum Mnemonic {
     AD,     // Add
     ADS,    // Add to Storage
@@ -55,16 +64,183 @@ pub enum Mnemonic {
     WOR,    // Write or OR
     WRITE,  // Write
     XCH,    // Exchange
+    // Pseudo-instructions
+    COM,    // Complement A: CS A
+    DCOM,   // Complement A,L: DCS A
+    DDOUBL, // Double A,L: DAS A
+    DOUBLE, // Double A: AD A
+    DTCB,   // Double Transfer Control, switching Both banks: DXCH Z
+    DTCF,   // Double Transfer Control, switching F bank: DXCH FB
+    NOOP,   // No operation: CA A
+    OVSK,   // Overflow Skip: TS A
+    RETURN, // Return from subroutine: TC Q
+    SQUARE, // Square A: MP A
+    TCAA,   // Transfer Control to Address in A: TS Z
+    XLQ,    // Execute using L and Q: TC L
+    XXALQ,  // Execute using A, L and Q: TC A
+    ZL,     // Zero L: LXCH 7
+    ZQ,     // Zero Q: QXCH 7
     INVALID,
 }
 
+/// Number of Mnemonic variants
+pub const MNEMONIC_COUNT: usize = 54;
+
+impl Mnemonic {
+    /// Name as written in listings
+    pub const fn name(self) -> &'static str {
+        match self {
+            Mnemonic::AD => "AD",
+            Mnemonic::ADS => "ADS",
+            Mnemonic::AUG => "AUG",
+            Mnemonic::BZF => "BZF",
+            Mnemonic::BZMF => "BZMF",
+            Mnemonic::CA => "CA",
+            Mnemonic::CS => "CS",
+            Mnemonic::CCS => "CCS",
+            Mnemonic::DAS => "DAS",
+            Mnemonic::DCA => "DCA",
+            Mnemonic::DCS => "DCS",
+            Mnemonic::DIM => "DIM",
+            Mnemonic::DV => "DV",
+            Mnemonic::DXCH => "DXCH",
+            Mnemonic::EDRUPT => "EDRUPT",
+            Mnemonic::EXTEND => "EXTEND",
+            Mnemonic::INCR => "INCR",
+            Mnemonic::INDEX => "INDEX",
+            Mnemonic::INHINT => "INHINT",
+            Mnemonic::LXCH => "LXCH",
+            Mnemonic::MASK => "MASK",
+            Mnemonic::MP => "MP",
+            Mnemonic::MSU => "MSU",
+            Mnemonic::QXCH => "QXCH",
+            Mnemonic::RAND => "RAND",
+            Mnemonic::READ => "READ",
+            Mnemonic::RELINT => "RELINT",
+            Mnemonic::RESUME => "RESUME",
+            Mnemonic::ROR => "ROR",
+            Mnemonic::RXOR => "RXOR",
+            Mnemonic::SU => "SU",
+            Mnemonic::TC => "TC",
+            Mnemonic::TCF => "TCF",
+            Mnemonic::TS => "TS",
+            Mnemonic::WAND => "WAND",
+            Mnemonic::WOR => "WOR",
+            Mnemonic::WRITE => "WRITE",
+            Mnemonic::XCH => "XCH",
+            Mnemonic::COM => "COM",
+            Mnemonic::DCOM => "DCOM",
+            Mnemonic::DDOUBL => "DDOUBL",
+            Mnemonic::DOUBLE => "DOUBLE",
+            Mnemonic::DTCB => "DTCB",
+            Mnemonic::DTCF => "DTCF",
+            Mnemonic::NOOP => "NOOP",
+            Mnemonic::OVSK => "OVSK",
+            Mnemonic::RETURN => "RETURN",
+            Mnemonic::SQUARE => "SQUARE",
+            Mnemonic::TCAA => "TCAA",
+            Mnemonic::XLQ => "XLQ",
+            Mnemonic::XXALQ => "XXALQ",
+            Mnemonic::ZL => "ZL",
+            Mnemonic::ZQ => "ZQ",
+            Mnemonic::INVALID => "INVALID",
+        }
+    }
+
+    /// An assembler name for one particular instruction word
+    pub const fn is_pseudo(self) -> bool {
+        matches!(
+            self,
+            Mnemonic::COM
+                | Mnemonic::DCOM
+                | Mnemonic::DDOUBL
+                | Mnemonic::DOUBLE
+                | Mnemonic::DTCB
+                | Mnemonic::DTCF
+                | Mnemonic::NOOP
+                | Mnemonic::OVSK
+                | Mnemonic::RETURN
+                | Mnemonic::SQUARE
+                | Mnemonic::TCAA
+                | Mnemonic::XLQ
+                | Mnemonic::XXALQ
+                | Mnemonic::ZL
+                | Mnemonic::ZQ
+        )
+    }
+}
+
+/// Quarter code: bits 12-11, which split opcodes 1, 2 and 5 and the
+/// extracodes 1, 2 and 6
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum QuarterCode {
+    Q0,
+    Q1,
+    Q2,
+    Q3,
+}
+
+impl QuarterCode {
+    pub fn from_word(word: u16) -> Self {
+        match (word >> 10) & 0o3 {
+            0 => QuarterCode::Q0,
+            1 => QuarterCode::Q1,
+            2 => QuarterCode::Q2,
+            _ => QuarterCode::Q3,
+        }
+    }
+
+    pub fn bits(self) -> u8 {
+        self as u8
+    }
+}
+
+/// Peripheral code: bits 12-10, which pick among the I/O extracodes
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ChannelCode {
+    C0,
+    C1,
+    C2,
+    C3,
+    C4,
+    C5,
+    C6,
+    C7,
+}
+
+impl ChannelCode {
+    pub fn from_word(word: u16) -> Self {
+        match (word >> 9) & 0o7 {
+            0 => ChannelCode::C0,
+            1 => ChannelCode::C1,
+            2 => ChannelCode::C2,
+            3 => ChannelCode::C3,
+            4 => ChannelCode::C4,
+            5 => ChannelCode::C5,
+            6 => ChannelCode::C6,
+            _ => ChannelCode::C7,
+        }
+    }
+
+    pub fn bits(self) -> u8 {
+        self as u8
+    }
+}
+
+/// Bits below the opcode the decoder split on, if any
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ExtraBits {
+    None,
+    Quarter(QuarterCode),
+    Channel(ChannelCode),
+}
+
 /// Structure representing a decoded AGC instruction
 pub struct Instructions {
-    pub pc: u16,               // Program counter value for this instruction
-    pub mnem: Mnemonic,        // Mnemonic representation
-    pub data: u16,             // Raw instruction word
-    pub extrabits: Option<u8>, // Additional bits for special instructions
-    pub mct: u8,               // Memory Cycle Time (MCT) count
+    pub pc: u16,              // Program counter value for this instruction
+    pub mnem: Mnemonic,       // Mnemonic representation
+    pub data: u16,            // Raw instruction word
+    pub extrabits: ExtraBits, // Code bits that selected the mnemonic
 }
 
 impl Instructions {
@@ -73,8 +249,7 @@ impl Instructions {
             pc: 0o00000,
             data: 0o00000,
             mnem: Mnemonic::INVALID,
-            extrabits: None,
-            mct: 1,
+            extrabits: ExtraBits::None,
         }
     }
 
@@ -98,6 +273,16 @@ impl Instructions {
         (self.data & MASK_RAM) as usize
     }
 
+    /// MCTs consumed by this instruction (fall-through case for branches)
+    pub fn cycles(&self) -> u16 {
+        timing::timing(&self.mnem).normal as u16
+    }
+
+    /// MCTs consumed when a conditional branch is taken
+    pub fn branch_cycles(&self) -> u16 {
+        timing::timing(&self.mnem).branch as u16
+    }
+
     /// Check if instruction uses EXTEND prefix (bit 15 set)
     pub fn is_extended(&self) -> bool {
         (self.data & OPCODE_EXTEND) == OPCODE_EXTEND
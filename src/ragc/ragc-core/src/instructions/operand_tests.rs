@@ -0,0 +1,325 @@
+//! Conformance matrix for operands in the central and editing registers
+//! (0-23), where the hardware departs from plain erasable: A and Q hold 16
+//! bits where everything else holds 15, Z reads as the next instruction's
+//! address, 7 reads zero and drops writes, EB, FB and BB mirror each other,
+//! and CYR, SR, CYL and EDOP edit every word written to them, including the
+//! rewrite that follows each read.
+//!
+//! Every instruction that works on an erasable operand is run against
+//! every register operand and checked against a model of those rules. CCS
+//! and INDEX change what runs next, and are checked in `sequence_tests`.
+use crate::constants::cycle_registers::*;
+use crate::constants::registers::*;
+use crate::cpu::Cpu;
+use crate::instructions::Mnemonic;
+use crate::rng::Rng;
+use crate::test_rom::{Op, Op::*, TestRom};
+use crate::utils::{add_s16, adjust_overflow, extend_sign_bits};
+
+const A: usize = REGISTER_ACCUMULATOR;
+const L: usize = REGISTER_LINK;
+const Q: usize = REGISTER_RETURN;
+const EB: usize = REGISTER_ERASABLE_BANK;
+const FB: usize = REGISTER_FIXED_BANK;
+const Z: usize = REGISTER_COUNTER;
+const BB: usize = REGISTER_COMBINED_BANK;
+const CYR: usize = SPECIAL_REGISTER_CYCLE_RIGHT;
+const SR: usize = SPECIAL_REGISTER_SHIFT;
+const CYL: usize = SPECIAL_REGISTER_CYCLE_LEFT;
+const EDOP: usize = SPECIAL_REGISTER_EDIT_OP;
+
+/// Operand addresses under test
+const OPERANDS: usize = 0o24;
+
+/// Plain erasable word standing in for a register in reference runs
+const PLAIN: usize = 0o100;
+
+const CASES: usize = 24;
+
+/// An instruction and how to assemble it against an operand
+type Entry = (Mnemonic, fn(u16) -> Op);
+
+const MATRIX: [Entry; 19] = [
+    (Mnemonic::AD, AD),
+    (Mnemonic::ADS, ADS),
+    (Mnemonic::AUG, AUG),
+    (Mnemonic::CA, CA),
+    (Mnemonic::CS, CS),
+    (Mnemonic::DAS, DAS),
+    (Mnemonic::DCA, DCA),
+    (Mnemonic::DCS, DCS),
+    (Mnemonic::DIM, DIM),
+    (Mnemonic::DV, DV),
+    (Mnemonic::DXCH, DXCH),
+    (Mnemonic::INCR, INCR),
+    (Mnemonic::LXCH, LXCH),
+    (Mnemonic::MASK, MASK),
+    (Mnemonic::MP, MP),
+    (Mnemonic::MSU, MSU),
+    (Mnemonic::QXCH, QXCH),
+    (Mnemonic::SU, SU),
+    (Mnemonic::XCH, XCH),
+];
+
+/// What the editing registers make of a word written to them
+fn edit(k: usize, word: u16) -> u16 {
+    match k {
+        CYR => (word >> 1) | ((word & 1) << 14),
+        SR => (word >> 1) | (word & 0o40000),
+        CYL => ((word << 1) & 0o77777) | (word >> 14),
+        _ => (word >> 7) & 0o177,
+    }
+}
+
+/// Registers 0-23 as the rules say an instruction leaves them
+#[derive(Clone, Copy)]
+struct Model {
+    regs: [u16; OPERANDS],
+    z: u16, // Address of the next instruction
+}
+
+impl Model {
+    /// 16-bit view of an operand: A and Q as held, the rest sign-extended
+    fn read(&self, k: usize) -> u16 {
+        match k {
+            A | Q => self.regs[k],
+            Z => self.z,
+            _ => extend_sign_bits(self.regs[k]),
+        }
+    }
+
+    /// Store a 16-bit result; 15-bit locations take it overflow-corrected
+    fn store(&mut self, k: usize, value: u16) {
+        let word = adjust_overflow(value) & 0o77777;
+        match k {
+            A | Q => self.regs[k] = value,
+            REGISTER_NULL => {}
+            EB => self.banks(word & 0o3400, self.regs[FB]),
+            FB => self.banks(self.regs[EB], word & 0o76000),
+            BB => self.banks((word & 0o7) << 8, word & 0o76000),
+            CYR..=EDOP => self.regs[k] = edit(k, word),
+            _ => self.regs[k] = word,
+        }
+    }
+
+    fn banks(&mut self, eb: u16, fb: u16) {
+        self.regs[EB] = eb;
+        self.regs[FB] = fb;
+        self.regs[BB] = fb | (eb >> 8);
+    }
+
+    /// The rewrite after a read, which edits an editing register again
+    fn restore(&mut self, k: usize) {
+        if let CYR..=EDOP = k {
+            self.regs[k] = edit(k, self.regs[k]);
+        }
+    }
+
+    /// Apply an instruction with operand `k` (the first word of a pair)
+    fn apply(&mut self, mnem: Mnemonic, k: usize) {
+        match mnem {
+            Mnemonic::CA | Mnemonic::CS | Mnemonic::AD | Mnemonic::SU => {
+                let (a, b) = (self.read(A), self.read(k));
+                self.restore(k);
+                let value = match mnem {
+                    Mnemonic::CA => b,
+                    Mnemonic::CS => !b,
+                    Mnemonic::AD => add_s16(a, b),
+                    _ => add_s16(a, !b),
+                };
+                self.store(A, value);
+            }
+            Mnemonic::MASK => {
+                let (a, b) = (self.read(A), self.read(k));
+                self.restore(k);
+                self.store(A, a & b);
+            }
+            Mnemonic::MSU => {
+                let a = adjust_overflow(self.read(A)) & 0o77777;
+                let b = adjust_overflow(self.read(k)) & 0o77777;
+                self.restore(k);
+                // Two's-complement difference, then back to ones'
+                let diff = a.wrapping_sub(b) & 0o77777;
+                let diff = diff - (diff >> 14);
+                self.store(A, extend_sign_bits(diff));
+            }
+            Mnemonic::ADS => {
+                let sum = add_s16(self.read(A), self.read(k));
+                self.store(k, sum);
+                self.store(A, sum);
+            }
+            Mnemonic::INCR => self.store(k, add_s16(self.read(k), 1)),
+            Mnemonic::AUG => match self.read(k) {
+                value if value & 0o100000 != 0 => self.store(k, add_s16(value, 0o177776)),
+                value => self.store(k, add_s16(value, 1)),
+            },
+            Mnemonic::DIM => match self.read(k) {
+                0 | 0o177777 => self.restore(k),
+                value if value & 0o100000 != 0 => self.store(k, add_s16(value, 1)),
+                value => self.store(k, add_s16(value, 0o177776)),
+            },
+            Mnemonic::XCH | Mnemonic::LXCH | Mnemonic::QXCH => {
+                let reg = match mnem {
+                    Mnemonic::XCH => A,
+                    Mnemonic::LXCH => L,
+                    _ => Q,
+                };
+                let (held, value) = (self.read(reg), self.read(k));
+                self.store(k, held);
+                self.store(reg, value);
+            }
+            Mnemonic::DXCH => {
+                let (l, low) = (self.read(L), self.read(k + 1));
+                self.store(k + 1, l);
+                self.store(L, low);
+                let (a, high) = (self.read(A), self.read(k));
+                self.store(k, a);
+                self.store(A, high);
+            }
+            Mnemonic::DCA | Mnemonic::DCS => {
+                let flip = |w: u16| if mnem == Mnemonic::DCS { !w } else { w };
+                let low = flip(self.read(k + 1));
+                self.restore(k + 1);
+                self.store(L, low);
+                let high = flip(self.read(k));
+                self.restore(k);
+                self.store(A, high);
+            }
+            Mnemonic::DAS => {
+                let mut low = add_s16(self.read(L), self.read(k + 1));
+                let mut high = add_s16(self.read(A), self.read(k));
+                match low & 0o140000 {
+                    0o040000 => high = add_s16(high, 1),
+                    0o100000 => high = add_s16(high, 0o177776),
+                    _ => {}
+                }
+                low = adjust_overflow(low);
+                if k == A {
+                    // DDOUBL
+                    self.store(A, high);
+                    self.store(L, low);
+                    return;
+                }
+                self.store(k, high);
+                self.store(k + 1, low);
+                let overflow = match high & 0o140000 {
+                    0o040000 => 1,
+                    0o100000 => 0o177776,
+                    _ => 0,
+                };
+                self.store(A, overflow);
+                self.store(L, 0);
+            }
+            _ => unreachable!("{:?} has no model", mnem),
+        }
+    }
+}
+
+/// Address field for operand `k`: double-word instructions name K+1
+fn field(mnem: Mnemonic, k: usize) -> u16 {
+    match mnem {
+        Mnemonic::DAS | Mnemonic::DCA | Mnemonic::DCS | Mnemonic::DXCH => k as u16 + 1,
+        _ => k as u16,
+    }
+}
+
+/// Operands an instruction is checked against. Writes to Z are jumps,
+/// covered with the branches; pairs end at the last editing register.
+fn operands(mnem: Mnemonic) -> impl Iterator<Item = usize> {
+    let writes = !matches!(
+        mnem,
+        Mnemonic::CA
+            | Mnemonic::CS
+            | Mnemonic::AD
+            | Mnemonic::SU
+            | Mnemonic::MASK
+            | Mnemonic::MSU
+            | Mnemonic::MP
+            | Mnemonic::DV
+            | Mnemonic::DCA
+            | Mnemonic::DCS
+    );
+    let double = matches!(
+        mnem,
+        Mnemonic::DAS | Mnemonic::DCA | Mnemonic::DCS | Mnemonic::DXCH
+    );
+    let end = if double { OPERANDS - 1 } else { OPERANDS };
+    (0..end).filter(move |&k| !writes || (k != Z && !(double && k + 1 == Z)))
+}
+
+/// Random register contents, with the edge values weighted up: 16 bits
+/// in A and Q, so overflow now and then
+fn randomize(cpu: &mut Cpu, rng: &mut Rng) {
+    const EDGES: [u16; 8] = [
+        0, 0o177777, 1, 0o177776, 0o037777, 0o140000, 0o077777, 0o100000,
+    ];
+    for k in (0..OPERANDS).filter(|&k| k != Z) {
+        let value = match rng.below(4) {
+            0 => EDGES[rng.below(EDGES.len() as u64) as usize],
+            _ => rng.below(0o200000) as u16,
+        };
+        match k {
+            A | Q => cpu.write(k, value),
+            _ => cpu.write(k, value & 0o77777),
+        }
+    }
+}
+
+fn registers(cpu: &Cpu) -> [u16; OPERANDS] {
+    let mut regs = [0; OPERANDS];
+    for (k, reg) in regs.iter_mut().enumerate() {
+        *reg = cpu.memory().read(k);
+    }
+    regs
+}
+
+/// Run `op` from the registers `seed` randomizes, with `plain` in the
+/// stand-in word, returning the registers before and after
+fn run(op: Op, seed: u64, plain: u16) -> ([u16; OPERANDS], [u16; OPERANDS]) {
+    let rom = TestRom::new().emit(&[op, Loop]);
+    let end = rom.here() - 1;
+    let mut cpu = rom.cpu();
+    randomize(&mut cpu, &mut Rng::new(seed));
+    cpu.write(PLAIN, plain);
+    let start = registers(&cpu);
+    while cpu.step().pc != end {}
+    (start, registers(&cpu))
+}
+
+#[test]
+fn register_operands_follow_the_hardware_rules() {
+    let mut rng = Rng::new(4991);
+    for (mnem, op) in MATRIX.iter().copied() {
+        for k in operands(mnem) {
+            for _ in 0..CASES {
+                let seed = rng.next_u64();
+                let (start, regs) = run(op(field(mnem, k)), seed, 0);
+                let next = TestRom::new().emit(&[op(0)]).here();
+                let mut model = Model {
+                    regs: start,
+                    z: next,
+                };
+                match mnem {
+                    // Products and quotients match those of the operand's
+                    // word kept in plain erasable
+                    Mnemonic::MP | Mnemonic::DV => {
+                        let word = adjust_overflow(model.read(k)) & 0o77777;
+                        let (_, plain) = run(op(PLAIN as u16), seed, word);
+                        model.restore(k);
+                        model.regs[A] = plain[A];
+                        model.regs[L] = plain[L];
+                    }
+                    _ => model.apply(mnem, k),
+                }
+
+                for reg in (0..OPERANDS).filter(|&r| r != Z) {
+                    assert_eq!(
+                        regs[reg], model.regs[reg],
+                        "{:?} {:o}: register {:o} from A={:06o} L={:06o} Q={:06o} K={:06o}",
+                        mnem, k, reg, start[A], start[L], start[Q], start[k]
+                    );
+                }
+            }
+        }
+    }
+}
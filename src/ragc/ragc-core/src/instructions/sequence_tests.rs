@@ -0,0 +1,113 @@
+//! Short sequences written the way LUMINARY writes them, checked word for
+//! word against results worked by hand. Single-instruction tests start
+//! each instruction from registers the test set up; these start it from
+//! whatever the instruction before left, which is where INDEX's change to
+//! the next instruction, a stale L under DV and the carry DDOUBL takes
+//! from L into A go wrong.
+use crate::constants::registers::{REGISTER_ACCUMULATOR, REGISTER_LINK};
+use crate::cpu::Cpu;
+use crate::test_rom::{Op::*, TestRom};
+
+const A: usize = REGISTER_ACCUMULATOR;
+const L: usize = REGISTER_LINK;
+
+const ZERO: u16 = 7; // Reads +0, as ZL and ZQ use it
+const DDOUBL: u16 = 1; // DAS A: the address field holds K+1
+
+/// Step until the program parks in a `Loop` at `end`, then give A and L
+/// as 15-bit words
+fn run_to(cpu: &mut Cpu, end: u16) -> (u16, u16) {
+    for _ in 0..200 {
+        if cpu.step().pc == end {
+            return (cpu.read(A) & 0o77777, cpu.read(L) & 0o77777);
+        }
+    }
+    panic!("never reached {:o}", end);
+}
+
+#[test]
+fn zl_clears_a_stale_l_before_dv() {
+    // CA X; ZL; EXTEND; DV Y: a single precision X over Y, as a fraction
+    let rom = TestRom::new().emit(&[CA(0o100), LXCH(ZERO), DV(0o101), Loop]);
+    let cases = [
+        // 0.25 / 0.5
+        (0o10000, 0o20000, (0o20000, 0o00000)),
+        // -0.25 / 0.75 = -0.33331, leaving -0o10000 over
+        (0o67777, 0o30000, (0o65252, 0o67777)),
+    ];
+    for (x, y, expected) in cases {
+        let mut cpu = rom.cpu();
+        cpu.write(L, 0o12345); // Left over from earlier work
+        cpu.write(0o100, x);
+        cpu.write(0o101, y);
+        assert_eq!(run_to(&mut cpu, 0o4004), expected, "{:05o} / {:05o}", x, y);
+    }
+
+    // Without the ZL the stale low word joins the dividend and comes
+    // back as the remainder
+    let rom = TestRom::new().emit(&[CA(0o100), DV(0o101), Loop]);
+    let mut cpu = rom.cpu();
+    cpu.write(L, 0o12345);
+    cpu.write(0o100, 0o10000);
+    cpu.write(0o101, 0o20000);
+    assert_eq!(run_to(&mut cpu, 0o4003), (0o20000, 0o12345));
+}
+
+#[test]
+fn ddoubl_carries_the_mp_low_word_into_a() {
+    // CA X; EXTEND; MP Y; DDOUBL: a product scaled up a place
+    let rom = TestRom::new().emit(&[CA(0o100), MP(0o101), DAS(DDOUBL), Loop]);
+    let cases = [
+        // 0.5 * 0.75 = 0.375, doubled without a low word
+        (0o20000, 0o30000, (0o30000, 0o00000)),
+        // 5349 * 10030 = 3274,9254; doubled, L overflows into A
+        (0o12345, 0o23456, (0o14625, 0o04114)),
+        // The same negated: the carry is a borrow and both words stay negative
+        (0o65432, 0o23456, (0o63152, 0o73663)),
+    ];
+    for (x, y, expected) in cases {
+        let mut cpu = rom.cpu();
+        cpu.write(0o100, x);
+        cpu.write(0o101, y);
+        assert_eq!(run_to(&mut cpu, 0o4004), expected, "{:05o} * {:05o}", x, y);
+    }
+}
+
+#[test]
+fn indexed_ccs_dispatches_on_the_selected_word() {
+    // INDEX I; CCS TABLE; then the four-way branch CCS skips into
+    let rom = TestRom::new()
+        .emit(&[INDEX(0o100), CCS(0o110)])
+        .emit(&[TCF(0o4006), TCF(0o4007), TCF(0o4010), TCF(0o4011)])
+        .emit(&[Loop, Loop, Loop, Loop]);
+    let table = [0o00005, 0o00000, 0o77772, 0o77777]; // +5, +0, -5, -0
+
+    // Branch taken and the diminished magnitude CCS leaves in A
+    let expected = [(0o4006, 0o4), (0o4007, 0), (0o4010, 0o4), (0o4011, 0)];
+    for (i, (end, a)) in expected.iter().enumerate() {
+        let mut cpu = rom.cpu();
+        for (n, word) in table.iter().enumerate() {
+            cpu.write(0o110 + n, *word);
+        }
+        cpu.write(0o100, i as u16);
+        assert_eq!(run_to(&mut cpu, *end).0, *a, "table entry {}", i);
+    }
+}
+
+#[test]
+fn ccs_counts_down_an_indexed_sum() {
+    // Sum TABLE+3 down to TABLE, counting I down with CCS:
+    //   LOOP  INDEX I; CA TABLE; AD SUM; XCH SUM
+    //         CCS I; TCF NEXT; TCF DONE; TCF DONE; TCF DONE
+    //   NEXT  XCH I; TCF LOOP
+    let rom = TestRom::new()
+        .emit(&[INDEX(0o100), CA(0o4020), AD(0o101), XCH(0o101)])
+        .emit(&[CCS(0o100), TCF(0o4011), TCF(0o4013), TCF(0o4013)])
+        .emit(&[TCF(0o4013), XCH(0o100), TCF(0o4000), Loop])
+        .at(0o4020)
+        .emit(&[Word(1), Word(2), Word(4), Word(0o10)]);
+    let mut cpu = rom.cpu();
+    cpu.write(0o100, 3);
+    run_to(&mut cpu, 0o4013);
+    assert_eq!((cpu.read(0o100), cpu.read(0o101)), (0, 0o17));
+}
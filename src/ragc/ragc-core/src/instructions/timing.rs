@@ -0,0 +1,261 @@
+use super::{Mnemonic, MNEMONIC_COUNT};
+
+/// Memory cycle time (MCT) cost of an instruction
+/// `branch` is the cost when a conditional branch is taken; for every other
+/// instruction it equals `normal`
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Timing {
+    pub normal: u8,
+    pub branch: u8,
+}
+
+const fn fixed(mct: u8) -> Timing {
+    Timing {
+        normal: mct,
+        branch: mct,
+    }
+}
+
+/// Single source of truth for instruction timing (AGC Block II instruction set)
+pub const fn timing(mnem: &Mnemonic) -> Timing {
+    match mnem {
+        Mnemonic::AD => fixed(2),
+        Mnemonic::ADS => fixed(2),
+        Mnemonic::AUG => fixed(2),
+        Mnemonic::BZF => Timing {
+            normal: 2,
+            branch: 1,
+        },
+        Mnemonic::BZMF => Timing {
+            normal: 2,
+            branch: 1,
+        },
+        Mnemonic::CA => fixed(2),
+        Mnemonic::CS => fixed(2),
+        Mnemonic::CCS => fixed(2),
+        Mnemonic::DAS => fixed(3),
+        Mnemonic::DCA => fixed(3),
+        Mnemonic::DCS => fixed(3),
+        Mnemonic::DIM => fixed(2),
+        Mnemonic::DV => fixed(6),
+        Mnemonic::DXCH => fixed(3),
+        Mnemonic::EDRUPT => fixed(3),
+        Mnemonic::EXTEND => fixed(1),
+        Mnemonic::INCR => fixed(2),
+        Mnemonic::INDEX => fixed(2),
+        Mnemonic::INHINT => fixed(1),
+        Mnemonic::LXCH => fixed(2),
+        Mnemonic::MASK => fixed(2),
+        Mnemonic::MP => fixed(3),
+        Mnemonic::MSU => fixed(2),
+        Mnemonic::QXCH => fixed(2),
+        Mnemonic::RAND => fixed(2),
+        Mnemonic::READ => fixed(2),
+        Mnemonic::RELINT => fixed(1),
+        Mnemonic::RESUME => fixed(2),
+        Mnemonic::ROR => fixed(2),
+        Mnemonic::RXOR => fixed(2),
+        Mnemonic::SU => fixed(2),
+        Mnemonic::TC => fixed(1),
+        Mnemonic::TCF => fixed(1),
+        // Overflow skips the next instruction at no extra cost
+        Mnemonic::TS => Timing {
+            normal: 2,
+            branch: 2,
+        },
+        Mnemonic::WAND => fixed(2),
+        Mnemonic::WOR => fixed(2),
+        Mnemonic::WRITE => fixed(2),
+        Mnemonic::XCH => fixed(2),
+        // Pseudo-instructions take as long as the words they stand for
+        Mnemonic::COM => fixed(2),
+        Mnemonic::DCOM => fixed(3),
+        Mnemonic::DDOUBL => fixed(3),
+        Mnemonic::DOUBLE => fixed(2),
+        Mnemonic::DTCB => fixed(3),
+        Mnemonic::DTCF => fixed(3),
+        Mnemonic::NOOP => fixed(2),
+        Mnemonic::OVSK => fixed(2),
+        Mnemonic::RETURN => fixed(1),
+        Mnemonic::SQUARE => fixed(3),
+        Mnemonic::TCAA => fixed(2),
+        Mnemonic::XLQ => fixed(1),
+        Mnemonic::XXALQ => fixed(1),
+        Mnemonic::ZL => fixed(2),
+        Mnemonic::ZQ => fixed(2),
+        Mnemonic::INVALID => fixed(1),
+    }
+}
+
+/// Every mnemonic, in declaration order (also used to audit the timing
+/// table at compile time)
+pub const ALL_MNEMONICS: [Mnemonic; MNEMONIC_COUNT] = [
+    Mnemonic::AD,
+    Mnemonic::ADS,
+    Mnemonic::AUG,
+    Mnemonic::BZF,
+    Mnemonic::BZMF,
+    Mnemonic::CA,
+    Mnemonic::CS,
+    Mnemonic::CCS,
+    Mnemonic::DAS,
+    Mnemonic::DCA,
+    Mnemonic::DCS,
+    Mnemonic::DIM,
+    Mnemonic::DV,
+    Mnemonic::DXCH,
+    Mnemonic::EDRUPT,
+    Mnemonic::EXTEND,
+    Mnemonic::INCR,
+    Mnemonic::INDEX,
+    Mnemonic::INHINT,
+    Mnemonic::LXCH,
+    Mnemonic::MASK,
+    Mnemonic::MP,
+    Mnemonic::MSU,
+    Mnemonic::QXCH,
+    Mnemonic::RAND,
+    Mnemonic::READ,
+    Mnemonic::RELINT,
+    Mnemonic::RESUME,
+    Mnemonic::ROR,
+    Mnemonic::RXOR,
+    Mnemonic::SU,
+    Mnemonic::TC,
+    Mnemonic::TCF,
+    Mnemonic::TS,
+    Mnemonic::WAND,
+    Mnemonic::WOR,
+    Mnemonic::WRITE,
+    Mnemonic::XCH,
+    Mnemonic::COM,
+    Mnemonic::DCOM,
+    Mnemonic::DDOUBL,
+    Mnemonic::DOUBLE,
+    Mnemonic::DTCB,
+    Mnemonic::DTCF,
+    Mnemonic::NOOP,
+    Mnemonic::OVSK,
+    Mnemonic::RETURN,
+    Mnemonic::SQUARE,
+    Mnemonic::TCAA,
+    Mnemonic::XLQ,
+    Mnemonic::XXALQ,
+    Mnemonic::ZL,
+    Mnemonic::ZQ,
+    Mnemonic::INVALID,
+];
+
+// Every instruction takes between 1 and 6 MCTs (DV is the longest), and a
+// taken branch is never slower than falling through
+const _: () = {
+    let mut i = 0;
+    while i < ALL_MNEMONICS.len() {
+        let t = timing(&ALL_MNEMONICS[i]);
+        assert!(t.normal >= 1 && t.normal <= 6);
+        assert!(t.branch >= 1 && t.branch <= t.normal);
+        i += 1;
+    }
+};
+const _: () = assert!(timing(&Mnemonic::DV).normal == 6);
+const _: () = assert!(timing(&Mnemonic::BZF).branch == 1);
+
+#[cfg(test)]
+mod timing_tests {
+    use crate::cpu::Cpu;
+    use crate::decoder::decoder;
+    use crate::instructions::{Instructions, Mnemonic};
+    use crate::memory::MemoryMap;
+
+    // (instruction word, EXTEND prefix, documented MCTs)
+    const DOCUMENTED: [(u16, bool, u16); 25] = [
+        (0o60100, false, 2), // AD
+        (0o26100, false, 2), // ADS
+        (0o30100, false, 2), // CA
+        (0o40100, false, 2), // CS
+        (0o00100, false, 1), // TC
+        (0o12100, false, 1), // TCF (quarter code 1)
+        (0o00003, false, 1), // RELINT
+        (0o00004, false, 1), // INHINT
+        (0o00006, false, 1), // EXTEND
+        (0o22100, false, 2), // LXCH
+        (0o24100, false, 2), // INCR
+        (0o56100, false, 2), // XCH
+        (0o54100, false, 2), // TS, no overflow
+        (0o00010, true, 2),  // READ
+        (0o01010, true, 2),  // WRITE
+        (0o02010, true, 2),  // RAND
+        (0o03010, true, 2),  // WAND
+        (0o04010, true, 2),  // ROR
+        (0o05010, true, 2),  // WOR
+        (0o06010, true, 2),  // RXOR
+        (0o30100, true, 3),  // DCA
+        (0o40100, true, 3),  // DCS
+        (0o60100, true, 2),  // SU
+        (0o70100, true, 3),  // MP
+        (0o26100, true, 2),  // DIM
+    ];
+
+    #[test]
+    fn execute_matches_documented_timing() {
+        let mut cpu = Cpu::new(MemoryMap::new_blank());
+
+        for (word, extended, expected) in DOCUMENTED.iter() {
+            let data = if *extended { word | 0o100000 } else { *word };
+            let inst = decoder(0o4000, data).unwrap();
+            assert_eq!(inst.cycles(), *expected, "decoded timing of {:o}", data);
+            assert_eq!(
+                cpu.execute(&inst),
+                *expected,
+                "executed timing of {:o}",
+                data
+            );
+        }
+    }
+
+    #[test]
+    fn bzf_timing_depends_on_branch() {
+        let mut cpu = Cpu::new(MemoryMap::new_blank());
+        let mut bzf = Instructions::new();
+        bzf.mnem = Mnemonic::BZF;
+        bzf.data = 0o12000;
+
+        cpu.write(0, 0);
+        assert_eq!(cpu.execute(&bzf), 1);
+        cpu.write(0, 1);
+        assert_eq!(cpu.execute(&bzf), 2);
+    }
+
+    #[test]
+    fn ts_overflow_skips_at_no_extra_cost() {
+        use crate::constants::registers::REGISTER_ACCUMULATOR;
+        use crate::test_rom::{Op::*, TestRom};
+
+        // 37777 + 1 overflows A: TS stores it corrected, leaves +1 in A and
+        // skips the CA; the second TS has no overflow and falls through
+        let rom = TestRom::new()
+            .emit(&[
+                CA(0o4010),
+                AD(0o4011),
+                TS(0o100),
+                CA(0o4010),
+                TS(0o101),
+                Loop,
+            ])
+            .at(0o4010)
+            .emit(&[Word(0o37777), Word(1)]);
+        let mut cpu = rom.cpu();
+        cpu.step();
+        cpu.step();
+
+        let ts = cpu.step();
+        assert_eq!((ts.pc, ts.cycles), (0o4002, 2));
+        assert_eq!(cpu.read(0o100), 0);
+        assert_eq!(cpu.read(REGISTER_ACCUMULATOR), 1);
+
+        let next = cpu.step();
+        assert_eq!((next.pc, next.cycles), (0o4004, 2));
+        assert_eq!(cpu.read(0o101), 1);
+        assert_eq!(cpu.step().pc, 0o4005);
+    }
+}
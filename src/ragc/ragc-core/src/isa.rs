@@ -0,0 +1,125 @@
+//! Instruction set table derived from the decoder and the CPU, so external
+//! documentation can be generated from what the emulator actually does
+use crate::cpu::implemented;
+use crate::decoder::decoder;
+use crate::instructions::timing::timing;
+use crate::instructions::{ExtraBits, Mnemonic};
+
+/// Distinct decodings: 21 basic instructions and 24 extracodes
+pub const ISA_CAPACITY: usize = 48;
+
+/// One instruction form: an opcode and, where the decoder looks at them,
+/// the quarter or peripheral code bits
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct IsaEntry {
+    pub mnem: Mnemonic,
+    pub extended: bool,       // Executed after EXTEND
+    pub opcode: u8,           // Bits 15-13
+    pub extrabits: ExtraBits, // Quarter code (bits 12-11) or peripheral code (bits 12-10)
+    pub word: u16,            // Lowest word with this decoding, EXTEND flag in bit 16
+    pub cycles: u8,           // MCTs, falling through for branches
+    pub branch_cycles: u8,    // MCTs when a branch is taken
+    pub implemented: bool,    // Executes rather than raising a fault
+}
+
+impl IsaEntry {
+    /// Quarter code, for the forms the decoder splits on bits 12-11
+    pub fn quarter_code(&self) -> Option<u8> {
+        match self.extrabits {
+            ExtraBits::Quarter(code) => Some(code.bits()),
+            _ => None,
+        }
+    }
+
+    /// Peripheral code of the I/O extracodes, bits 12-10
+    pub fn peripheral_code(&self) -> Option<u8> {
+        match self.extrabits {
+            ExtraBits::Channel(code) => Some(code.bits()),
+            _ => None,
+        }
+    }
+}
+
+/// Every distinct decoding of a 16-bit word, in encoding order
+pub fn instruction_set() -> heapless::Vec<IsaEntry, ISA_CAPACITY> {
+    let mut table = heapless::Vec::new();
+    for word in 0..=0xFFFFu32 {
+        let inst = match decoder(0, word as u16) {
+            Ok(inst) => inst,
+            Err(_) => continue,
+        };
+        let (extended, opcode) = (inst.is_extended(), inst.get_opcode());
+        let seen = table.iter().any(|e: &IsaEntry| {
+            (e.mnem, e.extended, e.opcode, e.extrabits)
+                == (inst.mnem, extended, opcode, inst.extrabits)
+        });
+        if seen {
+            continue;
+        }
+        let timing = timing(&inst.mnem);
+        let entry = IsaEntry {
+            mnem: inst.mnem,
+            extended,
+            opcode,
+            extrabits: inst.extrabits,
+            word: word as u16,
+            cycles: timing.normal,
+            branch_cycles: timing.branch,
+            implemented: implemented(&inst.mnem),
+        };
+        table
+            .push(entry)
+            .expect("instruction set outgrew ISA_CAPACITY");
+    }
+    table
+}
+
+#[cfg(test)]
+mod isa_tests {
+    use super::instruction_set;
+    use crate::cpu::{CpuFault, FaultPolicy};
+    use crate::instructions::timing::ALL_MNEMONICS;
+    use crate::instructions::Mnemonic;
+    use crate::test_rom::{Op::*, TestRom};
+
+    #[test]
+    fn table_covers_the_decoder_and_matches_execution() {
+        let table = instruction_set();
+        assert_eq!(table.len(), 45);
+        for mnem in ALL_MNEMONICS
+            .iter()
+            .filter(|m| **m != Mnemonic::INVALID && !m.is_pseudo())
+        {
+            assert!(table.iter().any(|e| e.mnem == *mnem), "{:?} missing", mnem);
+        }
+        let tcf: heapless::Vec<_, 4> = table
+            .iter()
+            .filter(|e| e.mnem == Mnemonic::TCF)
+            .map(|e| e.quarter_code())
+            .collect();
+        assert_eq!(tcf, [Some(1), Some(2), Some(3)]);
+        let wor = table.iter().find(|e| e.mnem == Mnemonic::WOR).unwrap();
+        assert_eq!((wor.peripheral_code(), wor.quarter_code()), (Some(5), None));
+
+        // Run each form once on an erasable operand: only unimplemented ones
+        // may fault as such. RESUME needs an interrupt to return from.
+        for entry in table.iter().filter(|e| e.mnem != Mnemonic::RESUME) {
+            let word = match entry.word & 0o1777 {
+                0 => (entry.word | 0o100) & 0o77777,
+                _ => entry.word & 0o77777, // RELINT, INHINT, EXTEND
+            };
+            let rom = match entry.extended {
+                true => TestRom::new().emit(&[EXTEND, Word(word)]),
+                false => TestRom::new().emit(&[Word(word)]),
+            };
+            let mut cpu = rom.cpu();
+            cpu.set_fault_policy(FaultPolicy::Halt);
+            cpu.step();
+            if entry.extended {
+                cpu.step();
+            }
+            let unimplemented = matches!(cpu.fault(), Some(CpuFault::Unimplemented { .. }));
+            assert_eq!(entry.implemented, !unimplemented, "{:?}", entry);
+        }
+    }
+}
@@ -0,0 +1,76 @@
+//! Memory latency model for worst-case timing studies. The real AGC gives
+//! every memory cycle the same 11.72 us; the model charges configurable
+//! extra MCTs for reaching erasable and fixed memory and for each counter
+//! increment that steals a cycle, so a routine's margins can be measured
+//! against slower memory or heavier counter traffic. Central registers are
+//! flip-flops and cost nothing extra.
+use crate::instructions::{ExtraBits, Instructions, Mnemonic};
+use crate::memory::Location;
+
+/// Extra MCTs charged per access
+#[derive(Clone, Copy, Default, PartialEq, Debug)]
+pub struct LatencyModel {
+    pub erasable: u8, // Per instruction fetch or operand in erasable
+    pub fixed: u8,    // Per instruction fetch or operand in fixed
+    pub counter: u8,  // Per counter increment stealing a cycle
+}
+
+impl LatencyModel {
+    /// Extra MCTs for one access to `at`
+    pub fn access(&self, at: Option<Location>) -> u16 {
+        match at {
+            Some(Location::Erasable(_)) => self.erasable as u16,
+            Some(Location::Fixed(_)) => self.fixed as u16,
+            Some(Location::Central(_)) | None => 0,
+        }
+    }
+}
+
+/// CPU address of the memory operand `inst` reads or writes, if it has
+/// one; branches only fetch their target, and I/O goes to channels
+pub fn operand_address(inst: &Instructions) -> Option<usize> {
+    match (inst.mnem, inst.extrabits) {
+        (_, ExtraBits::Channel(_)) => None,
+        (
+            Mnemonic::TC
+            | Mnemonic::TCF
+            | Mnemonic::BZF
+            | Mnemonic::BZMF
+            | Mnemonic::EXTEND
+            | Mnemonic::INHINT
+            | Mnemonic::RELINT
+            | Mnemonic::RESUME
+            | Mnemonic::EDRUPT
+            | Mnemonic::INVALID,
+            _,
+        ) => None,
+        (_, ExtraBits::Quarter(_)) => Some(inst.get_address_ram()),
+        _ => Some(inst.get_address()),
+    }
+}
+
+#[cfg(test)]
+mod latency_tests {
+    use crate::latency::LatencyModel;
+    use crate::test_rom::{Op::*, TestRom};
+
+    #[test]
+    fn charges_extra_mcts_per_access() {
+        let run = |model: Option<LatencyModel>| {
+            let rom = TestRom::new().emit(&[CA(0o100), AD(0o4000), Loop]);
+            let mut cpu = rom.cpu();
+            cpu.set_latency(model);
+            let cycles: [u16; 3] = core::array::from_fn(|_| cpu.step().cycles);
+            (cycles, cpu.latency_cycles())
+        };
+
+        assert_eq!(run(None), ([2, 2, 1], 0));
+        let model = LatencyModel {
+            erasable: 1,
+            fixed: 2,
+            counter: 0,
+        };
+        // Fetches from fixed; CA's operand in erasable, AD's in fixed
+        assert_eq!(run(Some(model)), ([5, 6, 3], 9));
+    }
+}
@@ -1,8 +1,40 @@
+//! Apollo Guidance Computer emulation core
+//!
+//! Frontends should import from [`prelude`]; other public modules expose
+//! lower-level pieces that are not covered by semver guarantees.
 #![no_std]
 
+#[cfg(feature = "alloc")]
+extern crate alloc;
+
+pub mod capability;
+mod checks;
 pub mod constants;
 pub mod cpu;
 pub mod decoder;
+pub mod disasm;
+#[cfg(feature = "events")]
+pub mod events;
+pub mod frame;
+pub mod hash;
+pub mod hooks;
 pub mod instructions;
+pub mod isa;
+pub mod latency;
+pub mod logging;
 pub mod memory;
+pub mod prelude;
+pub mod protect;
+pub mod rng;
+pub mod state_vector;
+pub mod stats;
+pub mod symbols;
+#[cfg(test)]
+mod test_rom;
+#[cfg(feature = "ringtrace")]
+pub mod trace;
 pub mod utils;
+pub mod warning;
+pub mod word;
+
+pub use capability::capabilities;
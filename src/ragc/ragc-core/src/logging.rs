@@ -0,0 +1,49 @@
+//! Diagnostics backend. Events go through `log` by default. With the
+//! `tracing` feature they go through `tracing` instead, inside spans for
+//! each subsystem (`step`, `interrupt`, `io`, `peripheral`) that carry the
+//! pc, channel and value as fields, so a subscriber can filter on those
+//! rather than on message text. With neither (`default-features = false`,
+//! the minimal profile for microcontrollers) events compile out, leaving
+//! no formatting code behind.
+#[cfg(not(any(feature = "log", feature = "tracing")))]
+pub use crate::{
+    __discard as debug, __discard as error, __discard as info, __discard as trace,
+    __discard as warn,
+};
+#[cfg(all(feature = "log", not(feature = "tracing")))]
+pub use log::{debug, error, info, trace, warn};
+#[cfg(feature = "tracing")]
+pub use tracing::{debug, error, info, trace, warn};
+
+#[cfg(feature = "tracing")]
+#[doc(hidden)]
+pub use tracing as __tracing;
+
+// Type-checks the arguments, as the backends do, and drops the event
+#[cfg(not(any(feature = "log", feature = "tracing")))]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __discard {
+    ($($arg:tt)*) => {
+        if false {
+            let _ = ::core::format_args!($($arg)*);
+        }
+    };
+}
+
+/// Enter a trace-level span for the rest of the enclosing block, e.g.
+/// `enter_span!("io", channel = port)`. Compiles to nothing without the
+/// `tracing` feature.
+#[cfg(feature = "tracing")]
+#[macro_export]
+macro_rules! enter_span {
+    ($name:literal $(, $field:ident = $value:expr)* $(,)?) => {
+        let _span = $crate::logging::__tracing::trace_span!($name $(, $field = $value)*).entered();
+    };
+}
+
+#[cfg(not(feature = "tracing"))]
+#[macro_export]
+macro_rules! enter_span {
+    ($name:literal $(, $field:ident = $value:expr)* $(,)?) => {};
+}
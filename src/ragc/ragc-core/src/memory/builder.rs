@@ -0,0 +1,222 @@
+use super::mods::IoPeriph;
+use super::tap::ChannelTap;
+use super::MemoryMap;
+use super::{clock, edit_registers, io, memory, registers, rom, special_registers};
+use crate::constants;
+
+/// Assembles a `MemoryMap` from whichever components a frontend has
+///
+/// ```ignore
+/// let mem = MemoryMapBuilder::new()
+///     .rope(&ROPE)
+///     .dsky(&mut dsky)
+///     .erasable(0o3, 0, &image)
+///     .build();
+/// ```
+pub struct MemoryMapBuilder<'a> {
+    map: MemoryMap<'a>,
+}
+
+impl<'a> MemoryMapBuilder<'a> {
+    /// Start from blank erasable, no rope and no peripherals
+    pub fn new() -> Self {
+        let map = MemoryMap {
+            ram: memory::Ram::new(),
+            rom: rom::ReadOnlyMemory::empty(),
+            rom_info: rom::RomInfo::default(),
+            io: io::IoController::empty(),
+            edit: edit_registers::EditRegisters::new(),
+            special: special_registers::SpecialRegisters::new(),
+            timers: clock::Clocks::new(),
+            regs: registers::Registers::new(),
+            watchers: [None; constants::MEMORY_SEGMENTS],
+            rupt_requests: 0,
+            access: Default::default(),
+            #[cfg(feature = "events")]
+            events: None,
+        };
+        Self { map }
+    }
+
+    /// Fixed memory contents
+    pub fn rope(mut self, program: &'a rom::RopeImage) -> Self {
+        self.map.rom = rom::ReadOnlyMemory::new(program);
+        self
+    }
+
+    /// Hardware configuration the rope was built for
+    pub fn rom_info(mut self, info: rom::RomInfo) -> Self {
+        self.map.rom_info = info;
+        self
+    }
+
+    /// Fixed memory with a non-standard bank layout
+    pub fn fixed_memory(mut self, fixed: &'a dyn rom::FixedMemory) -> Self {
+        self.map.rom = rom::ReadOnlyMemory::custom(fixed);
+        self
+    }
+
+    /// Fixed memory contents owned by the memory map, e.g. read from a file
+    #[cfg(feature = "alloc")]
+    pub fn owned_rope(mut self, program: alloc::boxed::Box<rom::RopeImage>) -> Self {
+        self.map.rom = rom::ReadOnlyMemory::owned(program);
+        self
+    }
+
+    /// Telemetry (downlink) peripheral
+    pub fn downlink(mut self, periph: &'a mut dyn IoPeriph) -> Self {
+        self.map.io.attach_downlink(periph);
+        self
+    }
+
+    /// Display and keyboard peripheral
+    pub fn dsky(mut self, unit: &'a mut dyn IoPeriph) -> Self {
+        self.map.io.attach_display(unit);
+        self
+    }
+
+    /// Preload erasable words starting at `offset` in `bank`, stored as
+    /// `MemoryMap::write_block` stores them
+    pub fn erasable(mut self, bank: usize, offset: usize, values: &[u16]) -> Self {
+        self.map.write_block(bank, offset, values);
+        self
+    }
+
+    /// Patch fixed-memory words over the rope
+    pub fn overlay(mut self, bank: usize, offset: usize, values: &[u16]) -> Self {
+        self.map.rom.overlay(bank, offset, values);
+        self
+    }
+
+    /// Emulate a hardware fault on a fixed bank
+    pub fn bank_fault(mut self, bank: usize, fault: rom::BankFault) -> Self {
+        self.map.rom.set_bank_fault(bank, Some(fault));
+        self
+    }
+
+    /// Register a channel tap
+    pub fn tap(mut self, channel: usize, tap: &'a mut dyn ChannelTap) -> Self {
+        self.map.io.add_tap(channel, tap);
+        self
+    }
+
+    /// Channel bits held by hardware regardless of CPU writes
+    pub fn pin_bits(mut self, channel: usize, mask: u16, bits: u16) -> Self {
+        self.map.io.pin_bits(channel, mask, bits);
+        self
+    }
+
+    /// DSKY relay update cadence in MCTs (0 = immediate)
+    pub fn relay_cadence(mut self, cadence: u32) -> Self {
+        self.map.io.set_relay_cadence(cadence);
+        self
+    }
+
+    /// Shortest uplink word and downlink pair spacing in MCTs; words sent
+    /// faster latch TOO FAST in channel 33. Zero lets a link run at any
+    /// rate.
+    pub fn link_pacing(mut self, uplink: u64, downlink: u64) -> Self {
+        self.map.io.set_link_pacing(uplink, downlink);
+        self
+    }
+
+    /// ISS turn-on delay in MCTs, shortened so tests need not run the
+    /// full 90 s
+    pub fn iss_delay(mut self, delay: u32) -> Self {
+        self.map.io.set_iss_delay(delay);
+        self
+    }
+
+    /// Anomalies reported for a strict CPU to halt on; by default all are
+    pub fn strictness(mut self, strictness: super::Strictness) -> Self {
+        self.map.io.set_strictness(strictness);
+        self
+    }
+
+    pub fn build(self) -> MemoryMap<'a> {
+        self.map
+    }
+}
+
+impl<'a> Default for MemoryMapBuilder<'a> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod builder_tests {
+    use super::MemoryMapBuilder;
+    use crate::memory::rom::FixedMemory;
+
+    #[test]
+    fn builds_with_images_and_overlays() {
+        let mem = MemoryMapBuilder::new()
+            .erasable(3, 0o10, &[0o123, 0o456])
+            .overlay(2, 0o5, &[0o7654])
+            .build();
+
+        assert_eq!(mem.read_block(3, 0o10..0o12), &[0o123, 0o456]);
+        assert_eq!(mem.read(0o4005), 0o7654); // Fixed-fixed 4000-5777 is bank 2
+        assert_eq!(mem.read(0o4006), 0);
+    }
+
+    #[test]
+    fn runs_from_a_two_bank_micro_rom() {
+        struct MicroRom([[u16; 1024]; 2]);
+        impl FixedMemory for MicroRom {
+            fn bank_count(&self) -> usize {
+                2
+            }
+            fn word(&self, bank: usize, offset: usize) -> u16 {
+                self.0[bank][offset]
+            }
+        }
+
+        let mut rom = MicroRom([[0; 1024]; 2]);
+        rom.0[1][0o5] = 0o4321;
+        let mut mem = MemoryMapBuilder::new().fixed_memory(&rom).build();
+        mem.write(0o4, 1 << 10); // FB = 1
+        assert_eq!(mem.read(0o2005), 0o4321);
+        assert_eq!(mem.read(0o4005), 0); // Fixed-fixed bank 2 is not provided
+    }
+
+    #[test]
+    fn tolerated_anomalies_are_counted_but_not_reported() {
+        use crate::memory::{Anomaly, Strictness};
+        struct OneBank([u16; 1024]);
+        impl FixedMemory for OneBank {
+            fn bank_count(&self) -> usize {
+                1
+            }
+            fn word(&self, _bank: usize, offset: usize) -> u16 {
+                self.0[offset]
+            }
+        }
+
+        let rom = OneBank([0; 1024]);
+        let mut mem = MemoryMapBuilder::new()
+            .fixed_memory(&rom)
+            .strictness(Strictness {
+                unassigned_channels: false,
+                ..Strictness::ALL
+            })
+            .build();
+        mem.read_io(0o60);
+        assert_eq!(mem.take_anomaly(), None);
+        assert_eq!(mem.access_stats().unknown_channels, 1);
+
+        mem.read(0o4000); // Fixed-fixed bank 2 is beyond the rope
+        assert_eq!(mem.take_anomaly(), Some(Anomaly::MissingBank(2)));
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn owns_a_rope_loaded_at_run_time() {
+        use crate::memory::rom::{encode_word, BANK_MAPPING};
+        let mut image = alloc::boxed::Box::new([[0; 1024]; 36]);
+        image[BANK_MAPPING[2]][0o5] = encode_word(0o1234);
+        let mem = MemoryMapBuilder::new().owned_rope(image).build();
+        assert_eq!(mem.read(0o4005), 0o1234);
+    }
+}
@@ -0,0 +1,482 @@
+use crate::constants::ports;
+use crate::constants::special_registers;
+
+/// How the CPU and the hardware use one I/O channel
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct ChannelSpec {
+    pub channel: usize,
+    pub name: &'static str,
+    pub cpu_read: bool,  // READ/RAND/ROR/RXOR return meaningful data
+    pub cpu_write: bool, // WRITE/WAND/WOR change the channel
+    pub driven: bool,    // Set by hardware or a peripheral
+    pub latched: bool,   // Holds its value until changed again
+}
+
+const fn input(channel: usize, name: &'static str) -> ChannelSpec {
+    ChannelSpec {
+        channel,
+        name,
+        cpu_read: true,
+        cpu_write: false,
+        driven: true,
+        latched: false,
+    }
+}
+
+const fn output(channel: usize, name: &'static str) -> ChannelSpec {
+    ChannelSpec {
+        channel,
+        name,
+        cpu_read: false,
+        cpu_write: true,
+        driven: false,
+        latched: true,
+    }
+}
+
+const fn register(channel: usize, name: &'static str) -> ChannelSpec {
+    ChannelSpec {
+        channel,
+        name,
+        cpu_read: true,
+        cpu_write: true,
+        driven: false,
+        latched: true,
+    }
+}
+
+/// Channel map of the Block II computer, in channel order
+pub const CHANNELS: [ChannelSpec; 22] = [
+    register(ports::CHANNEL_L, "L"),
+    register(ports::CHANNEL_Q, "Q"),
+    input(ports::CHANNEL_HISCALAR, "HISCALAR"),
+    input(ports::CHANNEL_LOSCALAR, "LOSCALAR"),
+    register(ports::CHANNEL_PYJETS, "PYJETS"),
+    register(ports::CHANNEL_ROLLJETS, "ROLLJETS"),
+    register(ports::CHANNEL_SUPERBNK, "SUPERBNK"),
+    output(ports::CHANNEL_DSKY, "OUT0"),
+    register(ports::CHANNEL_DSALMOUT, "DSALMOUT"),
+    register(ports::CHANNEL_CHAN12, "CHAN12"),
+    register(ports::CHANNEL_CHAN13, "CHAN13"),
+    register(ports::CHANNEL_CHAN14, "CHAN14"),
+    input(ports::CHANNEL_MNKEYIN, "MNKEYIN"),
+    input(ports::CHANNEL_NAVKEYIN, "NAVKEYIN"),
+    input(ports::CHANNEL_CHAN30, "CHAN30"),
+    input(ports::CHANNEL_CHAN31, "CHAN31"),
+    input(ports::CHANNEL_CHAN32, "CHAN32"),
+    // Bits 11-15 are failure flip-flops, held until reset
+    ChannelSpec {
+        latched: true,
+        ..input(ports::CHANNEL_CHAN33, "CHAN33")
+    },
+    output(ports::CHANNEL_CHAN34, "DNTM1"),
+    output(ports::CHANNEL_CHAN35, "DNTM2"),
+    // Restart monitor: hardware sets cause bits, any write clears them
+    ChannelSpec {
+        driven: true,
+        ..register(ports::CHANNEL_CHAN77, "CHAN77")
+    },
+    // Emulator-only DSKY status lights (yaDSKY)
+    ChannelSpec {
+        driven: true,
+        ..register(ports::CHANNEL_DSKY_LIGHTS, "DSKYLITE")
+    },
+];
+
+/// Look up a channel in the map
+pub fn channel_spec(channel: usize) -> Option<&'static ChannelSpec> {
+    CHANNELS.iter().find(|spec| spec.channel == channel)
+}
+
+/// A named bit (or group of bits) within a channel
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct BitField {
+    pub mask: u16,
+    pub name: &'static str,
+}
+
+const fn bit(mask: u16, name: &'static str) -> BitField {
+    BitField { mask, name }
+}
+
+const DSALMOUT_BITS: [BitField; 11] = [
+    bit(0o00001, "ISS WARNING"),
+    bit(0o00002, "COMP ACTY"),
+    bit(0o00004, "UPLINK ACTY"),
+    bit(0o00010, "TEMP"),
+    bit(0o00020, "KEY REL"),
+    bit(0o00040, "VERB/NOUN FLASH"),
+    bit(0o00100, "OPR ERR"),
+    bit(0o00400, "TEST CONNECTOR OUTBIT"),
+    bit(0o01000, "CAUTION RESET"),
+    bit(ports::CHAN11_ENGINE_ON, "ENGINE ON"),
+    bit(ports::CHAN11_ENGINE_OFF, "ENGINE OFF"),
+];
+
+const CHAN31_BITS: [BitField; 7] = [
+    bit(ports::CHAN31_THC_PLUS_X, "+X TRANSLATION"),
+    bit(ports::CHAN31_THC_MINUS_X, "-X TRANSLATION"),
+    bit(ports::CHAN31_THC_PLUS_Y, "+Y TRANSLATION"),
+    bit(ports::CHAN31_THC_MINUS_Y, "-Y TRANSLATION"),
+    bit(ports::CHAN31_THC_PLUS_Z, "+Z TRANSLATION"),
+    bit(ports::CHAN31_THC_MINUS_Z, "-Z TRANSLATION"),
+    bit(ports::CHAN31_RHC_OUT_OF_DETENT, "RHC OUT OF DETENT"),
+];
+
+const CHAN12_BITS: [BitField; 7] = [
+    bit(ports::CHAN12_ZERO_OPTICS_CDUS, "ZERO OPTICS CDUS"),
+    bit(
+        ports::CHAN12_ENABLE_OPTICS_ERROR,
+        "ENABLE OPTICS ERROR COUNTER",
+    ),
+    bit(ports::CHAN12_COARSE_ALIGN, "COARSE ALIGN ENABLE"),
+    bit(ports::CHAN12_ZERO_IMU_CDUS, "ZERO IMU CDUS"),
+    bit(ports::CHAN12_ENABLE_IMU_ERROR, "ENABLE IMU ERROR COUNTER"),
+    bit(ports::CHAN12_DISPLAY_INERTIAL, "DISPLAY INERTIAL DATA"),
+    bit(
+        ports::CHAN12_ISS_DELAY_COMPLETE,
+        "ISS TURN ON DELAY COMPLETE",
+    ),
+];
+
+const CHAN13_BITS: [BitField; 5] = [
+    bit(0o00007, "RADAR SELECT"),
+    bit(0o00010, "RADAR ACTIVITY"),
+    bit(0o00100, "DOWNLINK WORD ORDER"),
+    bit(ports::CHAN13_TEST_ALARMS, "TEST ALARMS"),
+    bit(ports::CHAN13_ENABLE_STANDBY, "ENABLE STANDBY"),
+];
+
+const CHAN14_BITS: [BitField; 3] = [
+    bit(ports::CHAN14_ALTITUDE_RATE, "ALTITUDE RATE SELECT"),
+    bit(ports::CHAN14_ALTITUDE_METER, "ALTITUDE METER DRIVE"),
+    bit(ports::CHAN14_DRIVE_THRUST, "THRUST DRIVE"),
+];
+
+const KEYIN_BITS: [BitField; 1] = [bit(0o00037, "KEYCODE")];
+
+// Inputs on channel 30 and 32 are active low
+const CHAN30_BITS: [BitField; 8] = [
+    bit(ports::CHAN30_ABORT, "ABORT"),
+    bit(ports::CHAN30_ENGINE_ARMED, "ENGINE ARMED"),
+    bit(ports::CHAN30_ABORT_STAGE, "ABORT STAGE"),
+    bit(ports::CHAN30_IMU_OPERATE, "IMU OPERATE"),
+    bit(ports::CHAN30_IMU_CAGE, "IMU CAGE"),
+    bit(0o10000, "IMU FAIL"),
+    bit(ports::CHAN30_ISS_TURN_ON_REQUEST, "ISS TURN ON REQUEST"),
+    bit(0o40000, "TEMP IN LIMITS"),
+];
+
+const CHAN32_BITS: [BitField; 2] = [
+    bit(ports::CHAN32_GIMBAL_FAIL, "DES ENG GIMBAL FAIL"),
+    bit(ports::CHAN32_PROCEED, "PROCEED"),
+];
+
+const CHAN33_BITS: [BitField; 5] = [
+    bit(ports::CHAN33_UPLINK_TOO_FAST, "UPLINK TOO FAST"),
+    bit(ports::CHAN33_DOWNLINK_TOO_FAST, "DOWNLINK TOO FAST"),
+    bit(0o10000, "PIPA FAIL"),
+    bit(ports::CHAN33_AGC_WARNING, "AGC WARNING"),
+    bit(ports::CHAN33_OSCILLATOR_ALARM, "OSCILLATOR ALARM"),
+];
+
+const CHAN77_BITS: [BitField; 5] = [
+    bit(0o00001, "PARITY FAIL"),
+    bit(0o00004, "TC TRAP"),
+    bit(0o00010, "RUPT LOCK"),
+    bit(0o00020, "NIGHT WATCHMAN"),
+    bit(0o00100, "VOLTAGE FAIL"),
+];
+
+const DSKY_LIGHT_BITS: [BitField; 8] = [
+    bit(ports::DSKY_LIGHT_AGC_WARNING, "AGC WARNING"),
+    bit(ports::DSKY_LIGHT_TEMP, "TEMP"),
+    bit(ports::DSKY_LIGHT_KEY_REL, "KEY REL"),
+    bit(ports::DSKY_LIGHT_VN_FLASH, "VERB/NOUN FLASH"),
+    bit(ports::DSKY_LIGHT_OPR_ERR, "OPR ERR"),
+    bit(ports::DSKY_LIGHT_RESTART, "RESTART"),
+    bit(ports::DSKY_LIGHT_STBY, "STBY"),
+    bit(ports::DSKY_LIGHT_EL_OFF, "EL OFF"),
+];
+
+/// Input discretes that scenarios and tools can assert by name
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Discrete {
+    Abort,             // LM ABORT pushbutton
+    AbortStage,        // LM ABORT STAGE pushbutton
+    EngineArmed,       // Engine arm switch
+    DescentGimbalFail, // Descent engine gimbal failure
+    OscillatorFail,    // Clock oscillator stopped; charges the AGC WARNING filter
+    ImuOperate,        // IMU power switch; starts the ISS turn-on sequence
+    ImuCage,           // IMU CAGE pushbutton
+    VoltageFail,       // Power supply out of limits; restarts the computer
+}
+
+/// Every named discrete
+pub const DISCRETES: [Discrete; 8] = [
+    Discrete::Abort,
+    Discrete::AbortStage,
+    Discrete::EngineArmed,
+    Discrete::DescentGimbalFail,
+    Discrete::OscillatorFail,
+    Discrete::ImuOperate,
+    Discrete::ImuCage,
+    Discrete::VoltageFail,
+];
+
+impl Discrete {
+    /// Input channel carrying the discrete
+    pub fn channel(self) -> usize {
+        match self {
+            Discrete::DescentGimbalFail => ports::CHANNEL_CHAN32,
+            Discrete::OscillatorFail => ports::CHANNEL_CHAN33,
+            Discrete::VoltageFail => ports::CHANNEL_CHAN77,
+            _ => ports::CHANNEL_CHAN30,
+        }
+    }
+
+    /// Channel bit; the inputs read 0 while asserted. Voltage fail is no
+    /// input: the restart it forces latches its channel 77 bit.
+    pub fn mask(self) -> u16 {
+        match self {
+            Discrete::Abort => ports::CHAN30_ABORT,
+            Discrete::AbortStage => ports::CHAN30_ABORT_STAGE,
+            Discrete::EngineArmed => ports::CHAN30_ENGINE_ARMED,
+            Discrete::DescentGimbalFail => ports::CHAN32_GIMBAL_FAIL,
+            Discrete::OscillatorFail => ports::CHAN33_OSCILLATOR_ALARM,
+            Discrete::ImuOperate => ports::CHAN30_IMU_OPERATE,
+            Discrete::ImuCage => ports::CHAN30_IMU_CAGE,
+            Discrete::VoltageFail => 0o00100,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Discrete::Abort => "abort",
+            Discrete::AbortStage => "abort-stage",
+            Discrete::EngineArmed => "engine-armed",
+            Discrete::DescentGimbalFail => "gimbal-fail",
+            Discrete::OscillatorFail => "oscillator-fail",
+            Discrete::ImuOperate => "imu-operate",
+            Discrete::ImuCage => "imu-cage",
+            Discrete::VoltageFail => "voltage-fail",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        DISCRETES.iter().copied().find(|d| d.name() == name)
+    }
+}
+
+/// LM hand controller axes: the rotational hand controller (RHC) and the
+/// translational hand controller (THC)
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Axis {
+    RhcPitch,
+    RhcYaw,
+    RhcRoll,
+    ThcX,
+    ThcY,
+    ThcZ,
+}
+
+/// Every hand controller axis
+pub const AXES: [Axis; 6] = [
+    Axis::RhcPitch,
+    Axis::RhcYaw,
+    Axis::RhcRoll,
+    Axis::ThcX,
+    Axis::ThcY,
+    Axis::ThcZ,
+];
+
+impl Axis {
+    /// RHC counts at the hard stop
+    pub const FULL_SCALE: i16 = 42;
+
+    /// Counter loaded with an RHC deflection; None for the THC
+    pub fn rhc_register(self) -> Option<usize> {
+        match self {
+            Axis::RhcPitch => Some(special_registers::SPECIAL_REGISTER_RHC_PITCH),
+            Axis::RhcYaw => Some(special_registers::SPECIAL_REGISTER_RHC_YAW),
+            Axis::RhcRoll => Some(special_registers::SPECIAL_REGISTER_RHC_ROLL),
+            _ => None,
+        }
+    }
+
+    /// Channel 31 bits for THC travel in the plus and minus directions
+    pub fn thc_bits(self) -> (u16, u16) {
+        match self {
+            Axis::ThcX => (ports::CHAN31_THC_PLUS_X, ports::CHAN31_THC_MINUS_X),
+            Axis::ThcY => (ports::CHAN31_THC_PLUS_Y, ports::CHAN31_THC_MINUS_Y),
+            Axis::ThcZ => (ports::CHAN31_THC_PLUS_Z, ports::CHAN31_THC_MINUS_Z),
+            _ => (0, 0),
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Axis::RhcPitch => "rhc-pitch",
+            Axis::RhcYaw => "rhc-yaw",
+            Axis::RhcRoll => "rhc-roll",
+            Axis::ThcX => "thc-x",
+            Axis::ThcY => "thc-y",
+            Axis::ThcZ => "thc-z",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        AXES.iter().copied().find(|a| a.name() == name)
+    }
+}
+
+/// Named bits of a channel, for annotating dumps; empty when undocumented
+pub fn channel_bits(channel: usize) -> &'static [BitField] {
+    match channel {
+        ports::CHANNEL_DSALMOUT => &DSALMOUT_BITS,
+        ports::CHANNEL_CHAN12 => &CHAN12_BITS,
+        ports::CHANNEL_CHAN13 => &CHAN13_BITS,
+        ports::CHANNEL_CHAN14 => &CHAN14_BITS,
+        ports::CHANNEL_MNKEYIN | ports::CHANNEL_NAVKEYIN => &KEYIN_BITS,
+        ports::CHANNEL_CHAN30 => &CHAN30_BITS,
+        ports::CHANNEL_CHAN31 => &CHAN31_BITS,
+        ports::CHANNEL_CHAN32 => &CHAN32_BITS,
+        ports::CHANNEL_CHAN33 => &CHAN33_BITS,
+        ports::CHANNEL_CHAN77 => &CHAN77_BITS,
+        ports::CHANNEL_DSKY_LIGHTS => &DSKY_LIGHT_BITS,
+        _ => &[],
+    }
+}
+
+#[cfg(test)]
+mod channel_tests {
+    use super::{channel_bits, channel_spec, Axis, Discrete, AXES, CHANNELS, DISCRETES};
+    use crate::constants::ports;
+    use crate::memory::MemoryMap;
+
+    /// yaAGC's masking as transcribed by hand, see the header of the file
+    const TABLE: &str = include_str!("channel_table.txt");
+
+    /// Single writes on a blank map, checked against the transcription and
+    /// not against yaAGC itself: a pass means we match the code as read,
+    /// not that the two emulators are shown to be compatible
+    #[test]
+    fn masks_as_the_hand_transcribed_yaagc_table() {
+        let octal = |field: Option<&str>| u16::from_str_radix(field.unwrap(), 8).unwrap();
+        let rows = TABLE
+            .lines()
+            .map(|line| line.split('#').next().unwrap().trim())
+            .filter(|line| !line.is_empty());
+        for row in rows {
+            let mut fields = row.split_whitespace();
+            let check = fields.next();
+            let (channel, written, yaagc) = (
+                octal(fields.next()),
+                octal(fields.next()),
+                octal(fields.next()),
+            );
+            let mut mem = MemoryMap::new_blank();
+            mem.write_io(channel as usize, written);
+            let ours = match check {
+                Some("write") => mem.peek_channel(channel as usize),
+                Some("read") => mem.read_io(channel as usize),
+                _ => panic!("bad row {:?}", row),
+            };
+            match fields.next() {
+                Some("same") => assert_eq!(ours, yaagc, "{}", row),
+                Some("differs") => assert_ne!(ours, yaagc, "{} now matches", row),
+                _ => panic!("bad row {:?}", row),
+            }
+        }
+    }
+
+    #[test]
+    fn map_matches_io_specification() {
+        assert!(CHANNELS.windows(2).all(|w| w[0].channel < w[1].channel));
+        for spec in CHANNELS.iter() {
+            assert!(spec.cpu_read || spec.cpu_write, "{}", spec.name);
+        }
+
+        let keys = channel_spec(ports::CHANNEL_MNKEYIN).unwrap();
+        assert!(keys.cpu_read && keys.driven && !keys.cpu_write);
+        let downlink = channel_spec(ports::CHANNEL_CHAN34).unwrap();
+        assert!(downlink.cpu_write && !downlink.cpu_read);
+        assert!(channel_spec(0o20).is_none());
+
+        // Named bits never overlap within a channel
+        for spec in CHANNELS.iter() {
+            let bits = channel_bits(spec.channel);
+            let all = bits.iter().fold(0, |all, field| {
+                assert_eq!(all & field.mask, 0, "{} {}", spec.name, field.name);
+                all | field.mask
+            });
+            assert_eq!(all & !0o77777, 0);
+        }
+    }
+
+    #[test]
+    fn writes_to_inputs_are_ignored() {
+        let mut mem = MemoryMap::new_blank();
+        mem.set_channel_input(ports::CHANNEL_CHAN30, 0o12345);
+        mem.write_io(ports::CHANNEL_CHAN30, 0);
+        assert_eq!(mem.read_io(ports::CHANNEL_CHAN30), 0o12345);
+
+        mem.write_io(ports::CHANNEL_CHAN12, 0o7);
+        assert_eq!(mem.read_io(ports::CHANNEL_CHAN12), 0o7);
+    }
+
+    #[test]
+    fn discretes_hold_until_released() {
+        let mut mem = MemoryMap::new_blank();
+        mem.set_channel_input(ports::CHANNEL_CHAN30, 0o77777);
+        assert!(mem.set_discrete(Discrete::Abort, true));
+        assert!(mem.set_discrete(Discrete::EngineArmed, true));
+        mem.set_channel_input(ports::CHANNEL_CHAN30, 0o77777);
+        assert_eq!(mem.read_io(ports::CHANNEL_CHAN30), 0o77772);
+
+        mem.set_discrete(Discrete::Abort, false);
+        assert_eq!(mem.read_io(ports::CHANNEL_CHAN30), 0o77773);
+        for discrete in DISCRETES.iter() {
+            assert_eq!(Discrete::from_name(discrete.name()), Some(*discrete));
+        }
+    }
+
+    #[test]
+    fn hand_controllers_drive_counters_and_channel_31() {
+        let mut mem = MemoryMap::new_blank();
+        mem.set_channel_input(ports::CHANNEL_CHAN31, 0o77777);
+        mem.set_axis(Axis::RhcPitch, -5);
+        mem.set_axis(Axis::ThcY, 1);
+        assert_eq!(mem.read(0o42), 0o77772);
+        assert_eq!(mem.read_io(ports::CHANNEL_CHAN31), 0o37377);
+
+        // Detent holds while any RHC axis is deflected
+        mem.set_axis(Axis::RhcRoll, 3);
+        mem.set_axis(Axis::RhcPitch, 0);
+        mem.set_axis(Axis::ThcY, -1);
+        assert_eq!(mem.read_io(ports::CHANNEL_CHAN31), 0o36777);
+        mem.set_axis(Axis::RhcRoll, 0);
+        mem.set_axis(Axis::ThcY, 0);
+        assert_eq!(mem.read_io(ports::CHANNEL_CHAN31), 0o77777);
+        for axis in AXES.iter() {
+            assert_eq!(Axis::from_name(axis.name()), Some(*axis));
+        }
+    }
+
+    #[test]
+    fn pinned_bits_override_cpu_writes() {
+        let mut mem = MemoryMap::new_blank();
+        assert!(mem.pin_channel_bits(ports::CHANNEL_CHAN12, 0o41, 0o40));
+        mem.write_io(ports::CHANNEL_CHAN12, 0o1);
+        assert_eq!(mem.read_io(ports::CHANNEL_CHAN12), 0o40);
+
+        // Later pins on the same channel merge
+        mem.pin_channel_bits(ports::CHANNEL_CHAN12, 0o2, 0o2);
+        mem.write_io(ports::CHANNEL_CHAN12, 0o4);
+        assert_eq!(mem.read_io(ports::CHANNEL_CHAN12), 0o46);
+
+        mem.release_channel_bits(ports::CHANNEL_CHAN12, 0o43);
+        mem.write_io(ports::CHANNEL_CHAN12, 0o1);
+        assert_eq!(mem.read_io(ports::CHANNEL_CHAN12), 0o1);
+    }
+}
@@ -1,8 +1,26 @@
 use crate::constants;
+use crate::constants::timers::{
+    TIMER_1_ADDRESS, TIMER_3_ADDRESS, TIMER_4_ADDRESS, TIMER_5_ADDRESS,
+};
 use crate::memory::MemoryType;
 
+/// One 10 ms timer period in thirds of an MCT (an MCT is 12/1.024 us)
+const CENTISECOND: u32 = 2560;
+
+/// TIME4 is pulsed 7.5 ms after TIME1, TIME3 and TIME5
+const TIME4_OFFSET: u32 = CENTISECOND * 3 / 4;
+
+const CENTISECOND_TIMERS: [usize; 3] = [TIMER_1_ADDRESS, TIMER_3_ADDRESS, TIMER_5_ADDRESS];
+const ALL_TIMERS: [usize; 4] = [
+    TIMER_1_ADDRESS,
+    TIMER_3_ADDRESS,
+    TIMER_5_ADDRESS,
+    TIMER_4_ADDRESS,
+];
+
 /// Manages AGC timing systems and interrupt flags
 /// Handles three distinct timer types with different behaviors
+#[derive(Hash)]
 pub struct Clocks {
     counter: u32,         // Master counter for timing reference
     pub mct_counter: u16, // Memory Cycle Time counter
@@ -10,15 +28,21 @@ pub struct Clocks {
     interrupt_flags: u8,  // Bitmask of pending interrupts
 
     timer1: u32, // 14-bit timer (T1)
+    timer2: u16, // 14-bit timer (T2) - counts T1 overflows
     timer3: u16, // 15-bit timer (T3)
     timer4: u16, // 15-bit timer (T4) - generates periodic interrupt
+    timer5: u16, // 15-bit timer (T5) - DAP interrupt
+
+    phase: u32, // Position in the 10 ms timer period, in thirds of an MCT
 }
 
 /// Identifies which timer to configure
 pub enum ClockType {
     TIMER1, // 14-bit overflow timer
+    TIMER2, // 14-bit, carries from TIMER1
     TIMER3, // 15-bit general purpose
     TIMER4, // 15-bit interrupt generator
+    TIMER5, // 15-bit interrupt generator
 }
 
 impl Clocks {
@@ -29,11 +53,39 @@ impl Clocks {
             counter: 0,
             mct_counter: 0,
             timer1: 0,
+            timer2: 0,
             timer3: 0,
             timer4: 0,
+            timer5: 0,
+            phase: 0,
+        }
+    }
+
+    /// Advance the 100 Hz timer pulses by `cycles` MCTs, returning the
+    /// timer cells due a PINC
+    pub fn advance(&mut self, cycles: u16) -> &'static [usize] {
+        let start = self.phase;
+        let end = start + cycles as u32 * 3;
+        self.phase = end % CENTISECOND;
+
+        let centisecond = end >= CENTISECOND;
+        let time4 = (start + CENTISECOND - TIME4_OFFSET) / CENTISECOND
+            != (end + CENTISECOND - TIME4_OFFSET) / CENTISECOND;
+        match (centisecond, time4) {
+            (true, true) => &ALL_TIMERS,
+            (true, false) => &CENTISECOND_TIMERS,
+            (false, true) => &ALL_TIMERS[3..],
+            (false, false) => &[],
         }
     }
 
+    /// MCTs until `advance` next pulses a timer
+    pub fn until_pulse(&self) -> u16 {
+        let to_centisecond = CENTISECOND - self.phase;
+        let to_time4 = (TIME4_OFFSET + CENTISECOND - self.phase - 1) % CENTISECOND + 1;
+        to_centisecond.min(to_time4).div_ceil(3) as u16
+    }
+
     /// Merge new interrupt flags into existing state
     pub fn update_interrupt_flags(&mut self, flags: u8) {
         self.interrupt_flags |= flags;
@@ -63,8 +115,10 @@ impl Clocks {
     pub fn set_time_value(&mut self, clock_type: ClockType, value: u16) {
         match clock_type {
             ClockType::TIMER1 => self.timer1 = value as u32, // 14-bit implicit
+            ClockType::TIMER2 => self.timer2 = value & 0o77777,
             ClockType::TIMER3 => self.timer3 = value & 0o77777, // 15-bit mask
             ClockType::TIMER4 => self.timer4 = value & 0o77777, // 15-bit mask
+            ClockType::TIMER5 => self.timer5 = value & 0o77777,
         }
     }
 
@@ -72,11 +126,18 @@ impl Clocks {
         self.counter
     }
 
+    /// TIME2:TIME1 as one 28-bit count of centiseconds
+    pub fn mission_clock(&self) -> u32 {
+        ((self.timer2 as u32 & 0o37777) << 14) | (self.timer1 & 0o37777)
+    }
+
     #[allow(dead_code)]
     pub fn reset(&mut self) {
         self.timer1 = 0;
+        self.timer2 = 0;
         self.timer3 = 0;
         self.timer4 = 0;
+        self.timer5 = 0;
     }
 }
 
@@ -86,8 +147,10 @@ impl MemoryType for Clocks {
         match address {
             // Timer1 returns 14 bits (mask 0o37777 = 16,383)
             constants::timers::TIMER_1_ADDRESS => (self.timer1 & 0o37777) as u16,
+            constants::timers::TIMER_2_ADDRESS => self.timer2,
             constants::timers::TIMER_3_ADDRESS => self.timer3,
             constants::timers::TIMER_4_ADDRESS => self.timer4,
+            constants::timers::TIMER_5_ADDRESS => self.timer5,
             _ => 0,
         }
     }
@@ -95,8 +158,10 @@ impl MemoryType for Clocks {
     fn write(&mut self, _bank: usize, address: usize, value: u16) {
         match address {
             constants::timers::TIMER_1_ADDRESS => self.set_time_value(ClockType::TIMER1, value),
+            constants::timers::TIMER_2_ADDRESS => self.set_time_value(ClockType::TIMER2, value),
             constants::timers::TIMER_3_ADDRESS => self.set_time_value(ClockType::TIMER3, value),
             constants::timers::TIMER_4_ADDRESS => self.set_time_value(ClockType::TIMER4, value),
+            constants::timers::TIMER_5_ADDRESS => self.set_time_value(ClockType::TIMER5, value),
             _ => {}
         }
     }
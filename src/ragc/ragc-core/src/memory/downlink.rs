@@ -0,0 +1,100 @@
+use crate::constants::ports;
+use crate::logging::warn;
+use crate::memory::state::{StateReader, StateWriter};
+
+/// What the hardware shifts out after each DOWNRUPT: the channel 34 and 35
+/// words, tagged with the word order code from channel 13 bit 7
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct DownlinkPair {
+    pub order: bool, // Clear for the ID/sync pair that starts a downlist
+    pub words: [u16; 2],
+}
+
+/// Pairs channel 34/35 writes into downlink frames
+///
+/// Flight software writes the first word to channel 34 and the second to 35
+/// in each DOWNRUPT. The pair is complete on the channel 35 write, and the
+/// word order code is sampled then, as the hardware does when it starts
+/// shifting the pair out. Backends receive whole pairs and never track
+/// channel 13 themselves.
+#[derive(Default)]
+pub struct DownlinkController {
+    first: Option<u16>,
+    last_first: u16,
+}
+
+impl DownlinkController {
+    /// Feed a channel write; returns the pair once its second word arrives
+    pub fn write(&mut self, port: usize, value: u16, chan13: u16) -> Option<DownlinkPair> {
+        match port {
+            ports::CHANNEL_CHAN34 => {
+                if self.first.is_some() {
+                    warn!("Downlink word {:o} replaces an unsent first word", value);
+                }
+                self.first = Some(value & 0o77777);
+                None
+            }
+            ports::CHANNEL_CHAN35 => {
+                // The channel 34 register still holds the previous word
+                let first = self.first.take().unwrap_or_else(|| {
+                    warn!("Downlink word {:o} sent without a first word", value);
+                    self.last_first
+                });
+                self.last_first = first;
+                Some(DownlinkPair {
+                    order: chan13 & ports::CHAN13_WORD_ORDER != 0,
+                    words: [first, value & 0o77777],
+                })
+            }
+            _ => None,
+        }
+    }
+
+    pub(crate) fn save(&self, out: &mut StateWriter) {
+        out.u8(self.first.is_some() as u8)
+            .u16(self.first.unwrap_or(0))
+            .u16(self.last_first);
+    }
+
+    pub(crate) fn restore(&mut self, data: &mut StateReader) -> Result<(), &'static str> {
+        let held = data.u8()? != 0;
+        let first = data.u16()?;
+        self.first = Some(first).filter(|_| held);
+        self.last_first = data.u16()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod downlink_tests {
+    use super::{DownlinkController, DownlinkPair};
+    use crate::constants::ports::{CHAN13_WORD_ORDER, CHANNEL_CHAN34, CHANNEL_CHAN35};
+
+    #[test]
+    fn pairs_words_with_sampled_order_code() {
+        let mut ctl = DownlinkController::default();
+        assert_eq!(ctl.write(CHANNEL_CHAN34, 0o77777, 0), None);
+        // Order code is taken when the pair completes, not on the first word
+        assert_eq!(
+            ctl.write(CHANNEL_CHAN35, 0o77340, CHAN13_WORD_ORDER),
+            Some(DownlinkPair {
+                order: true,
+                words: [0o77777, 0o77340]
+            })
+        );
+
+        // A lone second word goes out with the previous first word
+        assert_eq!(
+            ctl.write(CHANNEL_CHAN35, 0o1234, 0),
+            Some(DownlinkPair {
+                order: false,
+                words: [0o77777, 0o1234]
+            })
+        );
+
+        // A repeated first word replaces the unsent one
+        ctl.write(CHANNEL_CHAN34, 1, 0);
+        ctl.write(CHANNEL_CHAN34, 2, 0);
+        assert_eq!(ctl.write(CHANNEL_CHAN35, 3, 0).unwrap().words, [2, 3]);
+    }
+}
@@ -1,6 +1,6 @@
 use crate::constants::cycle_registers::*;
+use crate::logging::error;
 use crate::memory::MemoryType;
-use log::error;
 
 /// Handles AGC's special editing registers for shift/cycle operations
 pub struct EditRegisters {
@@ -0,0 +1,105 @@
+//! The DSKY's flashing lamps. KEY REL and OPR ERR flash because the
+//! software toggles their channel 11 bits from T4RUPT, so they are shown
+//! exactly as written. VERB and NOUN flash in hardware while channel 11
+//! asks for it, blanked by the computer's FLASH signal. Either way the
+//! blink comes from here, and frontends only draw what they are sent.
+use super::mods::EmuTime;
+use crate::constants::ports::{DSKY_LIGHT_KEY_REL, DSKY_LIGHT_OPR_ERR, DSKY_LIGHT_VN_FLASH};
+
+/// FLASH signal period and the part of it VERB and NOUN stay lit
+pub const FLASH_PERIOD_MS: u64 = 1000;
+pub const FLASH_LIT_MS: u64 = 750;
+
+/// Channel 11 bits of the lamps that flash: KEY REL, VERB/NOUN FLASH and
+/// OPR ERR, in the same positions as in channel 163
+pub const FLASHING_LAMPS: u16 = DSKY_LIGHT_KEY_REL | DSKY_LIGHT_VN_FLASH | DSKY_LIGHT_OPR_ERR;
+
+/// Whether the FLASH signal is in its lit phase at `now`
+pub const fn flash_lit(now: EmuTime) -> bool {
+    now.as_millis() % FLASH_PERIOD_MS < FLASH_LIT_MS
+}
+
+/// Follows the flashing lamps from channel 11 and the FLASH signal
+#[derive(Clone, Copy, Default, Debug)]
+pub struct Flash {
+    shown: u16,
+}
+
+impl Flash {
+    /// Flashing lamps as shown at `now` under channel 11 `dsalmout`: KEY
+    /// REL and OPR ERR lit as written, VERB/NOUN FLASH set while the two
+    /// displays are blanked
+    pub const fn shown_at(now: EmuTime, dsalmout: u16) -> u16 {
+        let blanked = match flash_lit(now) {
+            true => 0,
+            false => dsalmout & DSKY_LIGHT_VN_FLASH,
+        };
+        (dsalmout & (DSKY_LIGHT_KEY_REL | DSKY_LIGHT_OPR_ERR)) | blanked
+    }
+
+    /// Catch up with channel 11 and the time, returning the lamps when
+    /// they change
+    pub fn update(&mut self, now: EmuTime, dsalmout: u16) -> Option<u16> {
+        let shown = Self::shown_at(now, dsalmout);
+        match shown != self.shown {
+            true => {
+                self.shown = shown;
+                Some(shown)
+            }
+            false => None,
+        }
+    }
+
+    pub fn shown(&self) -> u16 {
+        self.shown
+    }
+
+    /// Whether VERB and NOUN are blanked by the flash right now
+    pub fn blanked(&self) -> bool {
+        self.shown & DSKY_LIGHT_VN_FLASH != 0
+    }
+}
+
+#[cfg(test)]
+mod flash_tests {
+    use super::FLASHING_LAMPS;
+    use crate::constants::ports::{
+        CHANNEL_DSALMOUT, CHANNEL_DSKY_LIGHTS, DSKY_LIGHT_KEY_REL, DSKY_LIGHT_VN_FLASH,
+    };
+    use crate::memory::MemoryMap;
+
+    // Run the I/O timing until `ms` milliseconds of emulated time
+    fn run_to(mem: &mut MemoryMap, ms: u64) {
+        while mem.now().as_millis() < ms {
+            mem.tick_io(100);
+        }
+    }
+
+    #[test]
+    fn verb_noun_blank_with_the_flash_signal() {
+        assert_eq!(FLASHING_LAMPS, 0o160);
+        let mut mem = MemoryMap::new_blank();
+        let blanked = |mem: &MemoryMap| mem.peek_channel(CHANNEL_DSKY_LIGHTS) & DSKY_LIGHT_VN_FLASH;
+
+        mem.write_io(CHANNEL_DSALMOUT, DSKY_LIGHT_VN_FLASH | DSKY_LIGHT_KEY_REL);
+        run_to(&mut mem, 100);
+        assert_eq!(blanked(&mem), 0);
+        assert_eq!(mem.dsky_flash(), DSKY_LIGHT_KEY_REL);
+        run_to(&mut mem, 800);
+        assert_ne!(blanked(&mem), 0);
+        run_to(&mut mem, 1100);
+        assert_eq!(blanked(&mem), 0);
+
+        // KEY REL follows the software's toggling, not the signal
+        mem.write_io(CHANNEL_DSALMOUT, DSKY_LIGHT_VN_FLASH);
+        run_to(&mut mem, 1200);
+        assert_eq!(mem.dsky_flash(), 0);
+
+        // Dropping the flash request shows VERB and NOUN straight away
+        run_to(&mut mem, 1800);
+        assert_ne!(blanked(&mem), 0);
+        mem.write_io(CHANNEL_DSALMOUT, 0);
+        mem.tick_io(1);
+        assert_eq!(blanked(&mem), 0);
+    }
+}
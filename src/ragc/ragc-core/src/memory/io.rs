@@ -1,24 +1,97 @@
-use super::mods::IoPeriph;
+use super::channels::channel_spec;
+use super::downlink::DownlinkController;
+use super::flash::Flash;
+use super::iss::{Iss, ISS_TURN_ON_DELAY};
+use super::mods::{EmuTime, IoPeriph};
+use super::pacing::{LinkPacer, DOWNLINK_PAIR_DEFAULT, UPLINK_WORD_DEFAULT};
+use super::relay::{DisplayFrame, RelayScheduler, RELAY_CADENCE_DEFAULT};
+use super::state::{PeriphState, StateReader, StateWriter, PERIPH_STATE_MAX};
+use super::tap::{ChannelTap, TapAction};
+use super::{AccessStats, Anomaly, Strictness};
 use crate::constants::ports;
 use crate::utils::Option;
 
-use log::{debug, error, warn};
+use crate::logging::{debug, error, warn};
+
+/// Maximum number of registered channel taps
+const MAX_TAPS: usize = 8;
+
+/// Maximum number of tap-delayed writes in flight
+const MAX_DELAYED: usize = 16;
+
+/// Maximum number of channel writes journaled between drains; more than
+/// one instruction makes, for users draining after every step
+const MAX_JOURNAL: usize = 64;
+
+/// Maximum number of channels with hardware-pinned bits
+const MAX_PINNED: usize = 8;
+
+/// Channel 30 bits driven by the ISS
+const ISS_BITS: u16 = ports::CHAN30_IMU_OPERATE | ports::CHAN30_ISS_TURN_ON_REQUEST;
+
+/// Channel 33 link flip-flops, latched low and reset by a channel 33 read
+const LINK_TOO_FAST_BITS: u16 = ports::CHAN33_UPLINK_TOO_FAST | ports::CHAN33_DOWNLINK_TOO_FAST;
+
+/// Channel write held back by a tap
+struct DelayedWrite {
+    port: usize,
+    value: u16,
+    remaining: u32,
+}
 
 /// Manages AGC I/O channel addressing and peripheral routing
 pub struct IoController<'a> {
     port_map: [u16; 256],                   // Memory-mapped I/O channels (0o00-0o77)
+    superbank: u16,                         // Channel 7 as the bank logic sees it
     downlink: Option<&'a mut dyn IoPeriph>, // Telemetry interface
     display: Option<&'a mut dyn IoPeriph>,  // DSKY interface
+    relays: RelayScheduler,                 // Channel 10 relay pacing
+    frame: DisplayFrame,                    // Relay words since the last T4RUPT
+    pairing: DownlinkController,            // Channel 34/35 word pairing
+    uplink: LinkPacer,                      // INLINK word spacing
+    downlink_pacing: LinkPacer,             // Channel 34/35 pair spacing
+    iss: Iss,                               // IMU power and turn-on sequence
+    taps: heapless::Vec<(usize, &'a mut dyn ChannelTap), MAX_TAPS>, // Channel middleware
+    delayed: heapless::Vec<DelayedWrite, MAX_DELAYED>, // Writes held back by taps
+    journal: heapless::Vec<(usize, u16), MAX_JOURNAL>, // Delivered writes, when journaling
+    journaling: bool,
+    journal_dropped: u32, // Writes lost to a full journal
+    pinned: heapless::Vec<(usize, u16, u16), MAX_PINNED>, // Hardware-held (channel, mask, bits)
+    lamp_test: core::option::Option<u16>, // Channel 163 lamps to restore after the test
+    flash: Flash,         // KEY REL, OPR ERR and the VERB/NOUN flash
+    now: EmuTime,         // Time reference handed to the peripherals
+    anomaly: core::option::Option<Anomaly>, // Most recent, until taken
+    access: AccessStats,  // Channel anomalies counted
+    strictness: Strictness, // Anomalies reported rather than only counted
 }
 
 impl<'a> IoController<'a> {
-    /// Creates controller with connected peripherals
+    /// Creates controller without attached peripherals
     /// Initializes calibration channels to max values
-    pub fn new(downlink_periph: &'a mut dyn IoPeriph, display_unit: &'a mut dyn IoPeriph) -> Self {
+    pub fn empty() -> Self {
         let mut controller = Self {
             port_map: [0; 256],
-            downlink: Option::Value(downlink_periph),
-            display: Option::Value(display_unit),
+            superbank: 0,
+            downlink: Option::Empty,
+            display: Option::Empty,
+            relays: RelayScheduler::new(RELAY_CADENCE_DEFAULT),
+            frame: DisplayFrame::default(),
+            pairing: DownlinkController::default(),
+            uplink: LinkPacer::new(UPLINK_WORD_DEFAULT),
+            downlink_pacing: LinkPacer::new(DOWNLINK_PAIR_DEFAULT),
+            iss: Iss::new(ISS_TURN_ON_DELAY),
+            taps: heapless::Vec::new(),
+            delayed: heapless::Vec::new(),
+            journal: heapless::Vec::new(),
+            journaling: false,
+            journal_dropped: 0,
+            pinned: heapless::Vec::new(),
+            lamp_test: None,
+            flash: Flash::default(),
+            now: EmuTime::ZERO,
+            anomaly: None,
+            access: AccessStats::default(),
+            strictness: Strictness::ALL,
         };
         // Initialize calibration channels (0o30-0o33)
         controller.port_map[0o30] = 0o37777; // 14-bit max (T4 cal)
@@ -28,22 +101,127 @@ impl<'a> IoController<'a> {
         controller
     }
 
-    /// Creates controller without attached peripherals
-    pub fn empty() -> Self {
-        let mut controller = Self {
-            port_map: [0; 256],
-            downlink: Option::Empty,
-            display: Option::Empty,
+    /// Connect the telemetry (downlink) peripheral
+    pub fn attach_downlink(&mut self, periph: &'a mut dyn IoPeriph) {
+        self.downlink = Option::Value(periph);
+    }
+
+    /// Connect the display and keyboard peripheral
+    pub fn attach_display(&mut self, unit: &'a mut dyn IoPeriph) {
+        self.display = Option::Value(unit);
+    }
+
+    /// Register middleware on a channel
+    pub fn add_tap(&mut self, channel: usize, tap: &'a mut dyn ChannelTap) -> bool {
+        if self.taps.push((channel, tap)).is_err() {
+            error!("Channel tap table full, dropping tap on 0o{:o}", channel);
+            return false;
+        }
+        true
+    }
+
+    /// Reads a channel through any registered taps
+    pub fn read_port(&mut self, port: usize) -> u16 {
+        crate::enter_span!("io", channel = port);
+        match channel_spec(port) {
+            Some(spec) if !spec.cpu_read => {
+                warn!("Read from output-only channel {} (0o{:o})", spec.name, port);
+                self.flag(Anomaly::OutputChannelRead(port));
+            }
+            Some(_) => {}
+            None => self.flag(Anomaly::UnassignedChannel(port)),
+        }
+        let value = self.read_channel(port);
+        let mut value = self.hardware_value(port, value);
+        for (channel, tap) in self.taps.iter_mut() {
+            if *channel != port {
+                continue;
+            }
+            value = match tap.read(port, value) {
+                TapAction::Pass(v) | TapAction::Delay(v, _) => v,
+                TapAction::Drop => return 0,
+            };
+        }
+        value
+    }
+
+    /// The most recent channel anomaly since the last call
+    pub fn take_anomaly(&mut self) -> core::option::Option<Anomaly> {
+        self.anomaly.take()
+    }
+
+    /// Channel anomalies since power on; memory counts are left at zero
+    pub fn access_stats(&self) -> AccessStats {
+        self.access
+    }
+
+    /// Anomalies to report from now on; the rest are only counted
+    pub fn set_strictness(&mut self, strictness: Strictness) {
+        self.strictness = strictness;
+    }
+
+    pub fn strictness(&self) -> Strictness {
+        self.strictness
+    }
+
+    fn flag(&mut self, anomaly: Anomaly) {
+        self.access.count(anomaly);
+        if self.strictness.reports(anomaly) {
+            self.anomaly = Some(anomaly);
+        }
+    }
+
+    /// Writes a channel through any registered taps
+    pub fn write_port(&mut self, port: usize, value: u16) {
+        crate::enter_span!("io", channel = port, value = value);
+        match channel_spec(port) {
+            Some(spec) if !spec.cpu_write => {
+                warn!(
+                    "Write to input channel {} (0o{:o}) ignored",
+                    spec.name, port
+                );
+                self.flag(Anomaly::InputChannelWrite(port));
+                return;
+            }
+            Some(_) => {}
+            None => {
+                warn!("Write to unassigned channel 0o{:o}", port);
+                self.flag(Anomaly::UnassignedChannel(port));
+            }
+        }
+        let mut value = value;
+        let mut delay = 0;
+        for (channel, tap) in self.taps.iter_mut() {
+            if *channel != port {
+                continue;
+            }
+            match tap.write(port, value) {
+                TapAction::Pass(v) => value = v,
+                TapAction::Delay(v, cycles) => {
+                    value = v;
+                    delay += cycles;
+                }
+                TapAction::Drop => return,
+            }
+        }
+
+        if delay == 0 {
+            self.write_channel(port, value);
+            return;
+        }
+        let held = DelayedWrite {
+            port,
+            value,
+            remaining: delay,
         };
-        controller.port_map[0o30] = 0o37777;
-        controller.port_map[0o31] = 0o77777;
-        controller.port_map[0o32] = 0o77777;
-        controller.port_map[0o33] = 0o77777;
-        controller
+        if self.delayed.push(held).is_err() {
+            warn!("Delayed write queue full, writing 0o{:o} now", port);
+            self.write_channel(port, value);
+        }
     }
 
     /// Handles read operations for special I/O channels
-    pub fn read_port(&mut self, port: usize) -> u16 {
+    fn read_channel(&mut self, port: usize) -> u16 {
         debug!("Reading from I/O port: 0o{:o}", port);
         match port {
             // Inertial measurement unit channels
@@ -53,10 +231,7 @@ impl<'a> IoController<'a> {
             ports::CHANNEL_PYJETS | ports::CHANNEL_ROLLJETS => self.port_map[port],
 
             // Display unit interface
-            ports::CHANNEL_DSKY => {
-                warn!("Unexpected read from display unit interface");
-                0
-            }
+            ports::CHANNEL_DSKY => 0,
 
             // Alarm system output
             ports::CHANNEL_DSALMOUT => self.port_map[ports::CHANNEL_DSALMOUT],
@@ -67,16 +242,17 @@ impl<'a> IoController<'a> {
             ports::CHANNEL_CHAN14 => self.port_map[ports::CHANNEL_CHAN14],
 
             // Display keyboard input
+            // An injected keycode takes precedence over the attached unit
             ports::CHANNEL_MNKEYIN => match &self.display {
-                Option::Value(unit) => unit.read(port),
-                Option::Empty => 0o00000,
+                Option::Value(unit) if self.port_map[port] == 0 => unit.read(port),
+                _ => self.port_map[port],
             },
 
             // Navigation keyboard (unimplemented)
             ports::CHANNEL_NAVKEYIN => 0,
 
             // Hardware status channels
-            ports::CHANNEL_CHAN31 => 0o77777, // Always ready status
+            ports::CHANNEL_CHAN30 | ports::CHANNEL_CHAN31 => self.port_map[port], // Discretes
 
             // Combined display data
             ports::CHANNEL_CHAN32 => {
@@ -87,8 +263,15 @@ impl<'a> IoController<'a> {
                 display_data | (self.port_map[0o32] & 0o57777) // Merge with backup data
             }
 
-            // Downlink status
-            ports::CHANNEL_CHAN33 => 0o77777,
+            // Downlink status; reading resets the link flip-flops
+            ports::CHANNEL_CHAN33 => {
+                let value = self.port_map[port];
+                self.port_map[port] |= LINK_TOO_FAST_BITS;
+                value
+            }
+
+            // Restart monitor causes, latched until written
+            ports::CHANNEL_CHAN77 => self.port_map[ports::CHANNEL_CHAN77],
 
             // Telemetry downlink
             ports::CHANNEL_CHAN34 | ports::CHANNEL_CHAN35 => match &self.downlink {
@@ -110,36 +293,381 @@ impl<'a> IoController<'a> {
     }
 
     /// Handles write operations with peripheral routing
-    pub fn write_port(&mut self, port: usize, value: u16) {
+    fn write_channel(&mut self, port: usize, value: u16) {
         debug!("Writing to I/O port: {:x} with value {:x}", port, value);
+        let value = self.hardware_value(port, value);
 
-        // Mirror writes to attached peripherals
-        if let Option::Value(unit) = &mut self.display {
+        if self.journaling && self.journal.push((port, value)).is_err() {
+            if self.journal_dropped == 0 {
+                warn!("Channel journal full; further dropped writes are only counted");
+            }
+            self.journal_dropped += 1;
+        }
+
+        // Mirror writes to attached peripherals, relay words at relay pace
+        let mirrored = match port {
+            ports::CHANNEL_DSKY => {
+                self.frame.push(value);
+                self.relays.push(value)
+            }
+            _ => Some(value),
+        };
+        if let (Option::Value(unit), Some(value)) = (&mut self.display, mirrored) {
             unit.write(port, value);
         }
+        let pair = self
+            .pairing
+            .write(port, value, self.port_map[ports::CHANNEL_CHAN13]);
+        if pair.is_some() && self.downlink_pacing.arrive(self.now) {
+            debug!("Downlink too fast");
+            self.port_map[ports::CHANNEL_CHAN33] &= !ports::CHAN33_DOWNLINK_TOO_FAST;
+        }
         if let Option::Value(periph) = &mut self.downlink {
             periph.write(port, value);
+            if let Some(pair) = pair {
+                periph.downlink(pair);
+            }
         }
 
         match port {
             ports::CHANNEL_DSALMOUT => self.port_map[ports::CHANNEL_DSALMOUT] = value,
             ports::CHANNEL_CHAN13 => self.port_map[ports::CHANNEL_CHAN13] = value,
-            ports::CHANNEL_CHAN32 => warn!("Write attempt to read-only port CHAN32"),
+            ports::CHANNEL_CHAN77 => self.port_map[ports::CHANNEL_CHAN77] = 0, // Any write resets
             _ => self.port_map[port] = value,
         }
+        if port == ports::CHANNEL_SUPERBNK {
+            self.latch_superbank();
+        }
+    }
+
+    /// Channel 7 as the bank logic sees it, cached whenever the channel or
+    /// its pins change so that address decoding needn't look it up
+    pub fn superbank(&self) -> u16 {
+        self.superbank
+    }
+
+    fn latch_superbank(&mut self) {
+        self.superbank = self.peek_port(ports::CHANNEL_SUPERBNK);
+    }
+
+    /// Hold `mask` bits of a channel at `bits` regardless of CPU writes
+    pub fn pin_bits(&mut self, port: usize, mask: u16, bits: u16) -> bool {
+        if let Some(pin) = self.pinned.iter_mut().find(|pin| pin.0 == port) {
+            pin.1 |= mask;
+            pin.2 = (pin.2 & !mask) | (bits & mask);
+        } else if self.pinned.push((port, mask, bits & mask)).is_err() {
+            error!("Pinned channel table full, dropping pin on 0o{:o}", port);
+            return false;
+        }
+        self.latch_superbank();
+        true
+    }
+
+    /// Hand `mask` bits of a channel back to the CPU
+    pub fn release_bits(&mut self, port: usize, mask: u16) {
+        for pin in self.pinned.iter_mut().filter(|pin| pin.0 == port) {
+            pin.1 &= !mask;
+            pin.2 &= !mask;
+        }
+        self.pinned.retain(|pin| pin.1 != 0);
+        self.latch_superbank();
     }
 
-    /// Aggregates interrupt flags from all peripherals
+    // Overlay hardware-held bits on a channel value
+    fn hardware_value(&self, port: usize, value: u16) -> u16 {
+        match self.pinned.iter().find(|pin| pin.0 == port) {
+            Some((_, mask, bits)) => (value & !mask) | bits,
+            None => value,
+        }
+    }
+
+    /// Latched value of a channel, without polling peripherals or taps
+    pub fn peek_port(&self, port: usize) -> u16 {
+        self.hardware_value(port, self.port_map[port])
+    }
+
+    /// Drive an input channel from outside, bypassing write filtering
+    pub fn set_input(&mut self, port: usize, value: u16) {
+        self.port_map[port] = value & 0o77777;
+        self.latch_superbank();
+    }
+
+    /// Start or stop recording delivered channel writes
+    pub fn set_journaling(&mut self, enabled: bool) {
+        self.journaling = enabled;
+        self.journal.clear();
+    }
+
+    /// Hand journaled writes to `f` in delivery order and clear the journal
+    pub fn drain_journal(&mut self, mut f: impl FnMut(usize, u16)) {
+        for (port, value) in self.journal.iter() {
+            f(*port, *value);
+        }
+        self.journal.clear();
+    }
+
+    /// Writes the journal had no room for since power on
+    pub fn journal_dropped(&self) -> u32 {
+        self.journal_dropped
+    }
+
+    pub fn set_relay_cadence(&mut self, cadence: u32) {
+        self.relays.set_cadence(cadence);
+    }
+
+    /// Shortest uplink word and downlink pair spacing in MCTs (0 = any)
+    pub fn set_link_pacing(&mut self, uplink: u64, downlink: u64) {
+        self.uplink.set_min_gap(uplink);
+        self.downlink_pacing.set_min_gap(downlink);
+    }
+
+    /// Time an uplink word into INLINK, latching UPLINK TOO FAST if it
+    /// overran the previous one
+    pub fn receive_uplink(&mut self) {
+        if self.uplink.arrive(self.now) {
+            debug!("Uplink too fast");
+            self.port_map[ports::CHANNEL_CHAN33] &= !ports::CHAN33_UPLINK_TOO_FAST;
+        }
+    }
+
+    /// Advance peripheral time, delivering relay words and tap-delayed
+    /// writes that have come due. Returns the flashing lamps when they
+    /// change.
+    pub fn tick(&mut self, cycles: u16) -> core::option::Option<u16> {
+        self.now.advance(cycles);
+        let flash = self.update_flash();
+        if let Option::Value(unit) = &mut self.display {
+            unit.tick(self.now);
+        }
+        if let Option::Value(periph) = &mut self.downlink {
+            periph.tick(self.now);
+        }
+
+        let mut idx = 0;
+        while idx < self.delayed.len() {
+            let held = &mut self.delayed[idx];
+            held.remaining = held.remaining.saturating_sub(cycles as u32);
+            if held.remaining == 0 {
+                let held = self.delayed.remove(idx);
+                self.write_channel(held.port, held.value);
+            } else {
+                idx += 1;
+            }
+        }
+
+        if self.iss.tick(cycles, self.port_map[ports::CHANNEL_CHAN12]) {
+            debug!("ISS turn-on request removed");
+            self.pin_iss_bits();
+        }
+
+        if let Some(word) = self.relays.tick(cycles as u32) {
+            if let Option::Value(unit) = &mut self.display {
+                unit.write(ports::CHANNEL_DSKY, word);
+            }
+        }
+        flash
+    }
+
+    // Follow channel 11 and the FLASH signal, blanking VERB and NOUN
+    // through channel 163 so every frontend blinks in step
+    fn update_flash(&mut self) -> core::option::Option<u16> {
+        let dsalmout = self.port_map[ports::CHANNEL_DSALMOUT];
+        let lamps = self.flash.update(self.now, dsalmout)?;
+        let lights = self.peek_port(ports::CHANNEL_DSKY_LIGHTS);
+        let blank = match self.flash.blanked() {
+            true => ports::DSKY_LIGHT_VN_FLASH,
+            false => 0,
+        };
+        if lights & ports::DSKY_LIGHT_VN_FLASH != blank {
+            let lights = (lights & !ports::DSKY_LIGHT_VN_FLASH) | blank;
+            self.write_port(ports::CHANNEL_DSKY_LIGHTS, lights);
+        }
+        Some(lamps)
+    }
+
+    pub fn flash(&self) -> &Flash {
+        &self.flash
+    }
+
+    /// Close the display frame on a T4RUPT, handing it to the display and
+    /// to taps on the relay channel
+    pub fn end_display_frame(&mut self) {
+        let frame = match self.frame.close(self.now) {
+            Some(frame) => frame,
+            None => return,
+        };
+        if let Option::Value(unit) = &mut self.display {
+            unit.display_frame(&frame);
+        }
+        for (channel, tap) in self.taps.iter_mut() {
+            if *channel == ports::CHANNEL_DSKY {
+                tap.display_frame(&frame);
+            }
+        }
+    }
+
+    /// Switch IMU OPERATE, starting the ISS turn-on sequence
+    pub fn set_imu_operate(&mut self, on: bool) -> bool {
+        self.iss.set_operate(on);
+        match on {
+            true => self.pin_iss_bits(),
+            false => {
+                self.release_bits(ports::CHANNEL_CHAN30, ISS_BITS);
+                true
+            }
+        }
+    }
+
+    fn pin_iss_bits(&mut self) -> bool {
+        self.pin_bits(ports::CHANNEL_CHAN30, ISS_BITS, self.iss.chan30_bits())
+    }
+
+    pub fn set_iss_delay(&mut self, delay: u32) {
+        self.iss.set_delay(delay);
+    }
+
+    pub fn iss(&self) -> &Iss {
+        &self.iss
+    }
+
+    /// Latches restart cause bits into channel 77 for the software to inspect
+    pub fn latch_restart_cause(&mut self, bits: u16) {
+        self.port_map[ports::CHANNEL_CHAN77] |= bits;
+    }
+
+    /// Drive the DSKY STBY light while the computer is in standby. The
+    /// numeric display loses power with it.
+    pub fn set_standby_light(&mut self, on: bool) {
+        const STANDBY: u16 = ports::DSKY_LIGHT_STBY | ports::DSKY_LIGHT_EL_OFF;
+        let lights = self.read_port(ports::CHANNEL_DSKY_LIGHTS);
+        let lights = match on {
+            true => lights | STANDBY,
+            false => lights & !STANDBY,
+        };
+        self.write_port(ports::CHANNEL_DSKY_LIGHTS, lights);
+    }
+
+    /// Follow channel 13 TEST ALARMS, which also tests the DSKY lamps:
+    /// every channel 163 lamp lights while it is set, and the lamps go
+    /// back to what they were when it clears
+    pub fn set_lamp_test(&mut self, on: bool) {
+        match (on, self.lamp_test) {
+            (true, None) => {
+                let lights = self.peek_port(ports::CHANNEL_DSKY_LIGHTS);
+                self.lamp_test = Some(lights);
+                self.write_port(ports::CHANNEL_DSKY_LIGHTS, lights | ports::DSKY_LIGHT_LAMPS);
+            }
+            (false, Some(lights)) => {
+                self.lamp_test = None;
+                self.write_port(ports::CHANNEL_DSKY_LIGHTS, lights);
+            }
+            _ => {}
+        }
+    }
+
+    /// Whether the DSKY lamp test is running
+    pub fn lamp_test(&self) -> bool {
+        self.lamp_test.is_some()
+    }
+
+    /// Save the channels' state, then that of each peripheral keeping any
+    pub fn save_states(&mut self, mut chunk: impl FnMut([u8; 4], &[u8])) {
+        let mut buf = [0; PERIPH_STATE_MAX];
+        if let Some(len) = self.save_state(&mut buf) {
+            chunk(self.state_tag(), &buf[..len]);
+        }
+        for unit in [&mut self.display, &mut self.downlink] {
+            let state = match unit {
+                Option::Value(unit) => unit.periph_state(),
+                Option::Empty => None,
+            };
+            if let Some(state) = state {
+                match state.save_state(&mut buf) {
+                    Some(len) => chunk(state.state_tag(), &buf[..len]),
+                    None => warn!("Peripheral state {:?} too large to save", state.state_tag()),
+                }
+            }
+        }
+    }
+
+    /// Hand saved state back to the channels or the peripheral it came
+    /// from. False when nothing attached claims `tag`.
+    pub fn restore_states(&mut self, tag: [u8; 4], data: &[u8]) -> Result<bool, &'static str> {
+        if tag == self.state_tag() {
+            return self.restore_state(data).map(|_| true);
+        }
+        for unit in [&mut self.display, &mut self.downlink] {
+            let state = match unit {
+                Option::Value(unit) => unit.periph_state(),
+                Option::Empty => None,
+            };
+            match state {
+                Some(state) if state.state_tag() == tag => {
+                    return state.restore_state(data).map(|_| true)
+                }
+                _ => {}
+            }
+        }
+        Ok(false)
+    }
+
+    /// Emulated time seen by the peripherals
+    pub fn now(&self) -> EmuTime {
+        self.now
+    }
+
+    /// Whether the DSKY asks to enter or leave standby
+    pub fn standby_request(&self) -> bool {
+        match &self.display {
+            Option::Value(unit) => unit.standby_request(),
+            Option::Empty => false,
+        }
+    }
+
+    /// Aggregates peripheral interrupt requests into priority encoder bits
     pub fn get_interrupt_status(&mut self) -> u16 {
         let mut interrupt_status = 0;
 
         if let Option::Value(unit) = &mut self.display {
-            interrupt_status |= unit.is_interrupt();
+            interrupt_status |= unit.rupt_requests().mask();
         }
         if let Option::Value(periph) = &mut self.downlink {
-            interrupt_status |= periph.is_interrupt();
+            interrupt_status |= periph.rupt_requests().mask();
         }
 
         interrupt_status
     }
 }
+
+/// Channel values, the downlink pairing, the ISS sequence and the lamp test
+impl PeriphState for IoController<'_> {
+    fn state_tag(&self) -> [u8; 4] {
+        *b"CHAN"
+    }
+
+    fn save_state(&self, out: &mut [u8]) -> core::option::Option<usize> {
+        let mut out = StateWriter::new(out);
+        for value in self.port_map.iter() {
+            out.u16(*value);
+        }
+        self.pairing.save(&mut out);
+        self.iss.save(&mut out);
+        out.u8(self.lamp_test.is_some() as u8)
+            .u16(self.lamp_test.unwrap_or(0));
+        out.finish()
+    }
+
+    fn restore_state(&mut self, data: &[u8]) -> Result<(), &'static str> {
+        let mut data = StateReader::new(data);
+        for value in self.port_map.iter_mut() {
+            *value = data.u16()?;
+        }
+        self.pairing.restore(&mut data)?;
+        self.iss.restore(&mut data)?;
+        let testing = data.u8()? != 0;
+        let lights = data.u16()?;
+        self.lamp_test = Some(lights).filter(|_| testing);
+        self.latch_superbank();
+        Ok(())
+    }
+}
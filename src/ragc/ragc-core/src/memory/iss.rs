@@ -0,0 +1,131 @@
+use crate::constants::ports;
+use crate::memory::state::{StateReader, StateWriter};
+
+/// ISS turn-on delay: 90 s of gyro run-up, in MCTs
+pub const ISS_TURN_ON_DELAY: u32 = 7_692_308;
+
+/// Inertial subsystem power and turn-on sequence, as seen on channel 30
+///
+/// Switching IMU OPERATE on raises ISS TURN ON REQUEST. The software waits
+/// out the turn-on delay (caging the IMU and zeroing its CDUs meanwhile),
+/// then answers with ISS DELAY COMPLETE on channel 12. The request drops
+/// once that answer arrives and the gyros have run up for the full delay,
+/// whichever is later.
+#[derive(Hash)]
+pub struct Iss {
+    operate: bool,
+    requesting: bool,
+    delay: u32,   // Turn-on delay, in MCTs
+    elapsed: u32, // Since IMU OPERATE was switched on
+}
+
+impl Iss {
+    pub fn new(delay: u32) -> Self {
+        Self {
+            operate: false,
+            requesting: false,
+            delay,
+            elapsed: 0,
+        }
+    }
+
+    pub fn set_delay(&mut self, delay: u32) {
+        self.delay = delay;
+    }
+
+    pub fn is_operating(&self) -> bool {
+        self.operate
+    }
+
+    /// The turn-on request is still up
+    pub fn is_requesting(&self) -> bool {
+        self.requesting
+    }
+
+    /// Switch IMU OPERATE; switching on restarts the turn-on sequence
+    pub fn set_operate(&mut self, on: bool) {
+        if on && !self.operate {
+            self.elapsed = 0;
+        }
+        self.operate = on;
+        self.requesting = on;
+    }
+
+    /// Advance by `cycles` MCTs given the channel 12 value. Returns true
+    /// when the turn-on request drops.
+    pub fn tick(&mut self, cycles: u16, chan12: u16) -> bool {
+        if !self.requesting {
+            return false;
+        }
+        self.elapsed = self.elapsed.saturating_add(cycles as u32);
+        let answered = chan12 & ports::CHAN12_ISS_DELAY_COMPLETE != 0;
+        if answered && self.elapsed >= self.delay {
+            self.requesting = false;
+            return true;
+        }
+        false
+    }
+
+    /// Channel 30 bits driven by the ISS (active low)
+    pub fn chan30_bits(&self) -> u16 {
+        let mut bits = ports::CHAN30_IMU_OPERATE | ports::CHAN30_ISS_TURN_ON_REQUEST;
+        if self.operate {
+            bits &= !ports::CHAN30_IMU_OPERATE;
+        }
+        if self.requesting {
+            bits &= !ports::CHAN30_ISS_TURN_ON_REQUEST;
+        }
+        bits
+    }
+
+    /// Power and sequence progress; the delay is configuration and stays
+    pub(crate) fn save(&self, out: &mut StateWriter) {
+        out.u8(self.operate as u8)
+            .u8(self.requesting as u8)
+            .u32(self.elapsed);
+    }
+
+    pub(crate) fn restore(&mut self, data: &mut StateReader) -> Result<(), &'static str> {
+        self.operate = data.u8()? != 0;
+        self.requesting = data.u8()? != 0;
+        self.elapsed = data.u32()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod iss_tests {
+    use crate::constants::ports;
+    use crate::memory::channels::Discrete;
+    use crate::memory::MemoryMapBuilder;
+
+    #[test]
+    fn turn_on_request_waits_for_delay_and_answer() {
+        let mut mem = MemoryMapBuilder::new().iss_delay(1000).build();
+        let chan30 = |mem: &crate::memory::MemoryMap| {
+            mem.peek_channel(ports::CHANNEL_CHAN30)
+                & (ports::CHAN30_IMU_OPERATE | ports::CHAN30_ISS_TURN_ON_REQUEST)
+        };
+        assert_eq!(chan30(&mem), 0o20400); // Off: both inputs high
+
+        mem.set_discrete(Discrete::ImuOperate, true);
+        assert_eq!(chan30(&mem), 0);
+
+        // An early answer holds until the gyros have run up
+        mem.tick_io(500);
+        mem.write_io(ports::CHANNEL_CHAN12, ports::CHAN12_ISS_DELAY_COMPLETE);
+        mem.tick_io(400);
+        assert!(mem.iss().is_requesting());
+        mem.tick_io(200);
+        assert!(!mem.iss().is_requesting());
+        assert_eq!(chan30(&mem), ports::CHAN30_ISS_TURN_ON_REQUEST);
+
+        // Cycling power starts over
+        mem.write_io(ports::CHANNEL_CHAN12, 0);
+        mem.set_discrete(Discrete::ImuOperate, false);
+        assert_eq!(chan30(&mem), 0o20400);
+        mem.set_discrete(Discrete::ImuOperate, true);
+        mem.tick_io(2000);
+        assert_eq!(chan30(&mem), 0, "unanswered request stays up");
+    }
+}
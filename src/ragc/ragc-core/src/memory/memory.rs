@@ -1,5 +1,7 @@
+use crate::checks;
 use crate::constants;
 use crate::memory::MemoryType;
+use core::ops::Range;
 
 /// Implements AGC's eraseable memory (RAM) with fixed banking
 /// Stores 15-bit words + parity bit for special registers
@@ -18,36 +20,41 @@ impl Ram {
     pub fn reset(&mut self) {
         self.memory_banks = [[0; constants::MEMORY_SEGMENT_SIZE]; constants::MEMORY_SEGMENTS];
     }
+
+    /// Raw view of a range of words within a bank
+    pub fn read_block(&self, bank_index: usize, range: Range<usize>) -> Option<&[u16]> {
+        self.memory_banks.get(bank_index)?.get(range)
+    }
+
+    /// Bulk store starting at `offset`, masked to 15 bits like regular writes
+    pub fn write_block(&mut self, bank_index: usize, offset: usize, values: &[u16]) -> bool {
+        let bank = match self.memory_banks.get_mut(bank_index) {
+            Some(bank) => bank,
+            None => return false,
+        };
+        match bank.get_mut(offset..offset + values.len()) {
+            Some(dest) => {
+                for (word, value) in dest.iter_mut().zip(values) {
+                    *word = value & 0x7FFF;
+                }
+                true
+            }
+            None => false,
+        }
+    }
 }
 
 impl MemoryType for Ram {
     fn read(&self, bank_index: usize, address_offset: usize) -> u16 {
-        let value = if bank_index == 0x0
-            && address_offset == constants::registers::REGISTER_ACCUMULATOR
-        {
-            // Preserve full 16 bits for accumulator (including sign)
-            self.memory_banks[bank_index][address_offset]
-        } else if bank_index == 0x0 && address_offset == constants::registers::REGISTER_MULTIPLIER {
-            // Preserve full 16 bits for multiplier product
-            self.memory_banks[bank_index][address_offset]
-        } else {
-            // Normal memory locations use 15-bit values
-            self.memory_banks[bank_index][address_offset] & 0x7FFF
-        };
-        value
+        checks::bank("erasable", bank_index, constants::MEMORY_SEGMENTS);
+        checks::offset("erasable", address_offset, constants::MEMORY_SEGMENT_SIZE);
+        self.memory_banks[bank_index][address_offset]
     }
 
     fn write(&mut self, bank_index: usize, address_offset: usize, value: u16) {
-        if bank_index == 0x0 && address_offset == constants::registers::REGISTER_ACCUMULATOR {
-            // Store full 16-bit accumulator value
-            self.memory_banks[bank_index][address_offset] = value;
-        } else if bank_index == 0x0 && address_offset == constants::registers::REGISTER_MULTIPLIER {
-            // Store full 16-bit multiplier value
-            self.memory_banks[bank_index][address_offset] = value;
-        } else {
-            // Truncate to 15 bits + parity for regular storage
-            let masked_value = value & 0x7FFF;
-            self.memory_banks[bank_index][address_offset] = masked_value;
-        }
+        // 15 bits; registers, including 16-bit A and Q, live in the register file
+        checks::bank("erasable", bank_index, constants::MEMORY_SEGMENTS);
+        checks::offset("erasable", address_offset, constants::MEMORY_SEGMENT_SIZE);
+        self.memory_banks[bank_index][address_offset] = value & 0x7FFF;
     }
 }
@@ -1,19 +1,58 @@
+mod builder;
+pub mod channels;
 mod clock;
+pub mod downlink;
 mod edit_registers;
-pub mod io;
+pub mod flash;
+mod io;
+pub mod iss;
 mod memory;
+pub mod pacing;
 mod registers;
-mod rom;
+pub mod relay;
+pub mod rom;
+mod space;
 mod special_registers;
+pub mod state;
+pub mod tap;
 
 pub mod mods;
+pub use builder::MemoryMapBuilder;
 pub use io::IoController;
+pub use registers::bank_register_bits;
+pub use space::{superbank_fixed_bank, AddressSpace, Location, Region};
 
-use self::mods::IoPeriph;
+use self::channels::{Axis, Discrete, AXES};
+use self::mods::{EmuTime, RuptRequest};
+use self::tap::ChannelTap;
+use crate::checks;
 use crate::constants;
 use crate::constants::address_space;
-use heapless::spsc::Producer;
-use log::error;
+use crate::constants::registers::{REGISTER_ACCUMULATOR, REGISTER_MULTIPLIER};
+#[cfg(feature = "events")]
+use crate::events::{Event, EventSink};
+use crate::logging::error;
+use crate::rng::Rng;
+use crate::word::{SignedAgc, Word15, Word16};
+use core::cell::Cell;
+use core::hash::{Hash, Hasher};
+use core::ops::Range;
+
+/// Report a 16-bit value bound for 15-bit storage, which a missing
+/// overflow correction leaves behind
+#[cfg(feature = "strict-words")]
+fn check_width(what: &str, idx: usize, value: u16) {
+    if value & 0o100000 != 0 {
+        error!(
+            "16-bit value {:06o} written to 15-bit {} {:o}",
+            value, what, idx
+        );
+        debug_assert!(false, "16-bit value in a 15-bit {}", what);
+    }
+}
+
+/// Callback notified when an erasable word changes: (bank, offset, new value)
+pub type ErasableWatchFn = fn(bank: usize, offset: usize, value: u16);
 
 /// Core memory access interface for AGC components
 trait MemoryType {
@@ -21,48 +60,126 @@ trait MemoryType {
     fn write(&mut self, bank_idx: usize, bank_offset: usize, value: u16);
 }
 
+/// Words of an erasable bank, copied out by `MemoryMap::read_block`
+pub type ErasableBlock = heapless::Vec<u16, { constants::MEMORY_SEGMENT_SIZE }>;
+
+/// Warn-level accesses the emulator carries on from, which a strict run
+/// halts on instead
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Anomaly {
+    OutputChannelRead(usize), // CPU read of an output-only channel
+    InputChannelWrite(usize), // CPU write to an input channel, ignored
+    UnassignedChannel(usize), // CPU access to a channel with no assignment
+    MissingBank(usize),       // Fixed bank beyond the rope, read as zeros
+}
+
+/// Which anomalies a map reports for a strict run to halt on. Tolerated
+/// kinds are still counted in `AccessStats`.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Strictness {
+    pub output_reads: bool,
+    pub input_writes: bool,
+    pub unassigned_channels: bool,
+    pub missing_banks: bool,
+}
+
+impl Strictness {
+    /// Every anomaly is reported
+    pub const ALL: Strictness = Strictness {
+        output_reads: true,
+        input_writes: true,
+        unassigned_channels: true,
+        missing_banks: true,
+    };
+
+    /// Nothing is reported, as if the CPU only logged
+    pub const NONE: Strictness = Strictness {
+        output_reads: false,
+        input_writes: false,
+        unassigned_channels: false,
+        missing_banks: false,
+    };
+
+    pub fn reports(&self, anomaly: Anomaly) -> bool {
+        match anomaly {
+            Anomaly::OutputChannelRead(_) => self.output_reads,
+            Anomaly::InputChannelWrite(_) => self.input_writes,
+            Anomaly::UnassignedChannel(_) => self.unassigned_channels,
+            Anomaly::MissingBank(_) => self.missing_banks,
+        }
+    }
+}
+
+impl Default for Strictness {
+    fn default() -> Self {
+        Strictness::ALL
+    }
+}
+
+/// Accesses the emulator carried on from since the map was built, for
+/// judging how cleanly a rope runs
+#[derive(Clone, Copy, PartialEq, Eq, Default, Debug)]
+pub struct AccessStats {
+    pub unmapped: u64,         // Addresses outside the address space
+    pub rom_writes: u64,       // Writes to fixed memory, ignored
+    pub missing_banks: u64,    // Reads of fixed banks beyond the rope
+    pub unknown_channels: u64, // Channels with no assignment
+    pub output_reads: u64,     // Reads of output-only channels
+    pub input_writes: u64,     // Writes to input channels, ignored
+}
+
+impl AccessStats {
+    pub fn total(&self) -> u64 {
+        self.unmapped
+            + self.rom_writes
+            + self.missing_banks
+            + self.unknown_channels
+            + self.output_reads
+            + self.input_writes
+    }
+
+    /// Count an anomaly under its kind
+    fn count(&mut self, anomaly: Anomaly) {
+        match anomaly {
+            Anomaly::OutputChannelRead(_) => self.output_reads += 1,
+            Anomaly::InputChannelWrite(_) => self.input_writes += 1,
+            Anomaly::UnassignedChannel(_) => self.unknown_channels += 1,
+            Anomaly::MissingBank(_) => self.missing_banks += 1,
+        }
+    }
+}
+
+/// Bank selection as it stands, for inspection
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Banks {
+    pub eb: usize,        // EB: erasable bank behind 1400-1777
+    pub fb: usize,        // FB, before the superbank is applied
+    pub superbank: usize, // Channel 7 bits 5-7
+    pub fixed: usize,     // Fixed bank behind 2000-3777
+}
+
 /// Central memory management unit implementing AGC address space
 /// Handles banking and peripheral I/O through component routing
 pub struct MemoryMap<'a> {
     ram: memory::Ram,                    // Eraseable memory (core rope simulator)
     rom: rom::ReadOnlyMemory<'a>,        // Fixed memory (core rope)
+    rom_info: rom::RomInfo,              // Hardware configuration of the rope
     io: io::IoController<'a>,            // I/O channel manager
     edit: edit_registers::EditRegisters, // Shift/cycle registers
     special: special_registers::SpecialRegisters, // Interrupt/control registers
     timers: clock::Clocks,               // Timing systems
     regs: registers::Registers,          // CPU registers
+    watchers: [Option<ErasableWatchFn>; constants::MEMORY_SEGMENTS], // Per-bank change callbacks
+    rupt_requests: u16,                  // Interrupt requests not yet taken by the CPU
+    access: Cell<AccessStats>,           // Memory accesses gone astray; channels count their own
+    #[cfg(feature = "events")]
+    events: Option<&'a mut dyn EventSink>, // State mutation recorder
 }
 
 impl<'a> MemoryMap<'a> {
     /// Creates blank memory map for diagnostic purposes
-    pub fn new_blank(rupt_tx: Producer<u8, 8>) -> MemoryMap {
-        MemoryMap {
-            ram: memory::Ram::new(),
-            rom: rom::ReadOnlyMemory::empty(),
-            io: io::IoController::empty(),
-            edit: edit_registers::EditRegisters::new(),
-            special: special_registers::SpecialRegisters::new(rupt_tx),
-            timers: clock::Clocks::new(),
-            regs: registers::Registers::new(),
-        }
-    }
-
-    /// Creates operational memory map with loaded program
-    pub fn new(
-        program: &'a [[u16; constants::STORAGE_SEGMENT_SIZE]; constants::STORAGE_SEGMENTS],
-        downrupt: &'a mut dyn IoPeriph, // Downlink peripheral
-        dsky: &'a mut dyn IoPeriph,     // Display interface
-        rupt_tx: Producer<u8, 8>,       // Interrupt channel
-    ) -> MemoryMap<'a> {
-        MemoryMap {
-            ram: memory::Ram::new(),
-            rom: rom::ReadOnlyMemory::new(program),
-            edit: edit_registers::EditRegisters::new(),
-            io: io::IoController::new(downrupt, dsky),
-            special: special_registers::SpecialRegisters::new(rupt_tx),
-            timers: clock::Clocks::new(),
-            regs: registers::Registers::new(),
-        }
+    pub fn new_blank() -> MemoryMap<'a> {
+        MemoryMapBuilder::new().build()
     }
 
     #[allow(dead_code)]
@@ -75,8 +192,82 @@ impl<'a> MemoryMap<'a> {
         &mut self.timers
     }
 
+    /// TIME2:TIME1 as stored, in centiseconds
+    pub fn mission_clock(&self) -> u32 {
+        self.timers.mission_clock()
+    }
+
+    /// Attach a recorder receiving every architectural state mutation
+    #[cfg(feature = "events")]
+    pub fn set_event_sink(&mut self, sink: &'a mut dyn EventSink) {
+        self.events = Some(sink);
+    }
+
+    #[cfg(feature = "events")]
+    pub fn record(&mut self, event: Event) {
+        if let Some(sink) = &mut self.events {
+            sink.record(event);
+        }
+    }
+
+    /// Re-apply a recorded event without recording it again
+    #[cfg(feature = "events")]
+    pub fn apply(&mut self, event: &Event) {
+        match *event {
+            Event::RegWrite { addr, value } => self.store(addr, value),
+            Event::ErasableWrite {
+                bank,
+                offset,
+                value,
+            } => self.write_ram(bank, offset, value),
+            Event::ChannelWrite { channel, value } => self.route_io(channel, value),
+            Event::RuptRaise { .. } => {} // Interrupt state lives in the CPU
+            Event::ProgramChanged { .. } => {} // Follows from the MODREG write
+            Event::Alarm { .. } => {}     // Follows from the FAILREG write
+            Event::DskyFlash { .. } => {} // Follows from channel 11 and the time
+        }
+    }
+
+    /// Classify a memory write into the event it represents
+    #[cfg(feature = "events")]
+    fn record_write(&mut self, idx: usize, val: u16) {
+        let event = match idx {
+            address_space::VOLATILE_START..=address_space::VOLATILE_END => {
+                let bank = if (idx >> 8) == 3 {
+                    self.regs.erasable_bank()
+                } else {
+                    idx >> 8
+                };
+                Event::ErasableWrite {
+                    bank,
+                    offset: idx & 0xff,
+                    value: val,
+                }
+            }
+            0o00..=0o60 => Event::RegWrite {
+                addr: idx,
+                value: val,
+            },
+            _ => return, // Fixed memory is not architectural state
+        };
+        self.record(event);
+    }
+
     /// Handles I/O channel writes with special register routing
     pub fn write_io(&mut self, idx: usize, value: u16) {
+        #[cfg(feature = "strict-words")]
+        if idx != constants::ports::CHANNEL_Q {
+            check_width("channel", idx, value);
+        }
+        #[cfg(feature = "events")]
+        self.record(Event::ChannelWrite {
+            channel: idx,
+            value,
+        });
+        self.route_io(idx, value);
+    }
+
+    fn route_io(&mut self, idx: usize, value: u16) {
         match idx {
             constants::ports::CHANNEL_L => {
                 // Link register
@@ -98,12 +289,52 @@ impl<'a> MemoryMap<'a> {
                 self.timers.update_interrupt_flags(2);
                 self.io.write_port(idx, value);
             }
+            constants::ports::CHANNEL_SUPERBNK => {
+                self.io
+                    .write_port(idx, value & constants::ports::SUPERBNK_BITS);
+            }
+            constants::ports::CHANNEL_CHAN13 => {
+                self.io.write_port(idx, value);
+                let test = self.io.peek_port(idx) & constants::ports::CHAN13_TEST_ALARMS;
+                self.io.set_lamp_test(test != 0);
+            }
+            constants::ports::CHANNEL_CHAN12 => {
+                self.io.write_port(idx, value);
+                for cdu in constants::special_registers::SPECIAL_REGISTER_CONTROL_DISPLAY_X
+                    ..=constants::special_registers::SPECIAL_REGISTER_OPTICAL_X
+                {
+                    if self.cdu_zeroed(cdu) {
+                        self.special.set_counter(cdu, 0);
+                    }
+                }
+            }
             _ => {
                 self.io.write_port(idx, value);
             }
         };
     }
 
+    /// CDU counter held at zero by a channel 12 zero bit
+    fn cdu_zeroed(&self, idx: usize) -> bool {
+        use constants::special_registers::*;
+        let chan12 = self.io.peek_port(constants::ports::CHANNEL_CHAN12);
+        let zero = match idx {
+            SPECIAL_REGISTER_CONTROL_DISPLAY_X..=SPECIAL_REGISTER_CONTROL_DISPLAY_Z => {
+                constants::ports::CHAN12_ZERO_IMU_CDUS
+            }
+            SPECIAL_REGISTER_OPTICAL_Y | SPECIAL_REGISTER_OPTICAL_X => {
+                constants::ports::CHAN12_ZERO_OPTICS_CDUS
+            }
+            _ => 0,
+        };
+        chan12 & zero != 0
+    }
+
+    /// A holds an uncorrected overflow, which inhibits interrupts
+    pub fn accumulator_overflow(&self) -> bool {
+        self.regs.overflow()
+    }
+
     /// Handles I/O channel reads with timer value splitting
     pub fn read_io(&mut self, idx: usize) -> u16 {
         match idx {
@@ -125,88 +356,704 @@ impl<'a> MemoryMap<'a> {
         }
     }
 
+    /// Typed channel read
+    pub fn read_io_word(&mut self, idx: usize) -> Word15 {
+        Word15::new(self.read_io(idx))
+    }
+
+    /// Typed channel write
+    pub fn write_io_word(&mut self, idx: usize, word: Word15) {
+        self.write_io(idx, word.raw());
+    }
+
+    /// Channel value for inspection: what was last written or driven, with
+    /// pinned bits applied. Unlike `read_io`, peripherals aren't polled.
+    pub fn peek_channel(&self, idx: usize) -> u16 {
+        match idx {
+            constants::ports::CHANNEL_L => self.regs.read(0, constants::registers::REGISTER_LINK),
+            constants::ports::CHANNEL_Q => {
+                self.regs.read(0, constants::registers::REGISTER_MULTIPLIER)
+            }
+            _ => self.io.peek_port(idx),
+        }
+    }
+
+    /// Typed read of a location. A and Q are read with overflow correction,
+    /// as a store to memory would see them.
+    pub fn read_word(&self, idx: usize) -> Word15 {
+        match idx {
+            REGISTER_ACCUMULATOR | REGISTER_MULTIPLIER => Word16::new(self.read(idx)).to_word15(),
+            _ => Word15::new(self.read(idx)),
+        }
+    }
+
+    /// Typed write of a location, sign-extended into A and Q
+    pub fn write_word(&mut self, idx: usize, word: Word15) {
+        match idx {
+            REGISTER_ACCUMULATOR | REGISTER_MULTIPLIER => self.write(idx, word.to_word16().raw()),
+            _ => self.write(idx, word.raw()),
+        }
+    }
+
     /// Main memory write handler with bank switching
     pub fn write(&mut self, idx: usize, val: u16) {
-        match idx {
-            0o00..=0o17 => {
-                // CPU registers
-                self.regs.write(0, idx, val);
+        #[cfg(feature = "strict-words")]
+        if !matches!(idx, REGISTER_ACCUMULATOR | REGISTER_MULTIPLIER) {
+            check_width("location", idx, val);
+        }
+        #[cfg(feature = "events")]
+        self.record_write(idx, val);
+        self.store(idx, val);
+    }
+
+    fn store(&mut self, idx: usize, val: u16) {
+        match self.locate(idx) {
+            Some(Location::Central(idx)) => match idx {
+                0o00..=0o17 => self.regs.write(0, idx, val), // CPU registers
+                0o20..=0o23 => self.edit.write(0, idx, val), // Edit registers
+                0o24..=0o31 => self.timers.write(0, idx, val), // Timer registers
+                _ => self.special.write(0, idx, val),        // Control registers
+            },
+            Some(Location::Erasable(addr)) => self.write_ram(addr.bank, addr.offset, val),
+            Some(Location::Fixed(addr)) => {
+                self.count_rom_write();
+                self.rom.write(addr.bank, addr.offset, val)
             }
-            0o20..=0o23 => {
-                // Edit registers
-                self.edit.write(0, idx, val);
+            None => {
+                self.count_access(|stats| stats.unmapped += 1);
+                error!("Unimplemented Memory Map Write (Addr: 0x{:x}", idx);
             }
-            0o24..=0o31 => {
-                // Timer registers
-                self.timers.write(0, idx, val);
+        }
+    }
+
+    /// Read a range of words from an erasable bank in one call. E0's
+    /// central and special registers (0-60) read as the CPU reads them, so
+    /// the edit registers, timers and counters come from where they live.
+    /// A range outside the bank reads as empty.
+    pub fn read_block(&self, bank: usize, range: Range<usize>) -> ErasableBlock {
+        let mut block = ErasableBlock::new();
+        let start = range.start;
+        match self.ram.read_block(bank, range) {
+            Some(words) => block.extend_from_slice(words).expect("a bank fits a block"),
+            None => error!("Erasable block out of range (E{})", bank),
+        }
+        if bank == 0 {
+            let central = block.iter_mut().zip(start..address_space::VOLATILE_START);
+            for (word, offset) in central {
+                *word = self.read(offset);
             }
-            0o32..=0o60 => {
-                // Special control registers
-                self.special.write(0, idx, val);
+        }
+        block
+    }
+
+    /// Feed everything a program can observe into `state`: registers,
+    /// counters, erasable memory, channels, timers and pending interrupts
+    pub fn hash_state<H: Hasher>(&self, state: &mut H) {
+        for idx in 0..address_space::VOLATILE_START {
+            self.read(idx).hash(state);
+        }
+        for bank in 0..constants::MEMORY_SEGMENTS {
+            let words = self.ram.read_block(bank, 0..constants::MEMORY_SEGMENT_SIZE);
+            words.unwrap_or_default().hash(state);
+        }
+        for spec in channels::CHANNELS.iter() {
+            (spec.channel, self.peek_channel(spec.channel)).hash(state);
+        }
+        self.timers.hash(state);
+        self.io.iss().hash(state);
+        self.rupt_requests.hash(state);
+    }
+
+    /// Store consecutive words into an erasable bank, notifying its watcher
+    pub fn write_block(&mut self, bank: usize, offset: usize, values: &[u16]) {
+        // Watchers hear only of words that change, as with `write_ram`
+        let notify = self.watchers.get(bank).copied().flatten();
+        let mut old = [0; constants::MEMORY_SEGMENT_SIZE];
+        if notify.is_some() {
+            for (i, word) in old.iter_mut().take(values.len()).enumerate() {
+                *word = self.block_word(bank, offset + i);
             }
-            address_space::VOLATILE_START..=address_space::VOLATILE_END => {
-                // RAM
-                // Handle erasable bank switching (bank 3 is switchable)
-                if (idx >> 8) == 3 {
-                    self.ram
-                        .write(self.regs.erasable_bank, (idx & 0xff) as usize, val)
-                } else {
-                    self.ram.write(idx >> 8, (idx & 0xff) as usize, val)
+        }
+        let in_range = bank < constants::MEMORY_SEGMENTS
+            && offset + values.len() <= constants::MEMORY_SEGMENT_SIZE;
+        if !in_range {
+            error!("Erasable block out of range (E{},{:o})", bank, offset);
+            return;
+        }
+        // E0's central and special registers take their words as CPU
+        // stores do: edited, or routed to the timers and counters
+        let split = match bank {
+            0 => values
+                .len()
+                .min(address_space::VOLATILE_START.saturating_sub(offset)),
+            _ => 0,
+        };
+        for (i, value) in values[..split].iter().enumerate() {
+            self.store(offset + i, *value);
+        }
+        self.ram.write_block(bank, offset + split, &values[split..]);
+        if let Some(notify) = notify {
+            for (i, old) in old.iter().take(values.len()).enumerate() {
+                let new = self.block_word(bank, offset + i);
+                if new != *old {
+                    notify(bank, offset + i, new);
                 }
             }
-            address_space::PERSISTENT_START..=address_space::PERSISTENT_END => {
-                // ROM
-                let bank_idx = idx >> 10;
-                if bank_idx == 1 {
-                    // Fixed-fixed bank switching
-                    self.rom
-                        .write(self.regs.fixed_bank, (idx & 0x3ff) as usize, val)
-                } else {
-                    self.rom.write(bank_idx, (idx & 0x3ff) as usize, val)
-                }
+        }
+    }
+
+    /// An erasable word as `write_block` stores it, registers included
+    fn block_word(&self, bank: usize, offset: usize) -> u16 {
+        match bank {
+            0 if offset < address_space::VOLATILE_START => self.read(offset) & 0x7FFF,
+            _ => self
+                .ram
+                .read_block(bank, offset..offset + 1)
+                .map_or(0, |word| word[0]),
+        }
+    }
+
+    /// Fill erasable memory with random contents, as found at power on
+    pub fn randomize_erasable(&mut self, rng: &mut Rng) {
+        let mut words = [0; constants::MEMORY_SEGMENT_SIZE];
+        for bank in 0..constants::MEMORY_SEGMENTS {
+            for word in words.iter_mut() {
+                *word = rng.below(0o100000) as u16;
             }
-            _ => {
-                error!("Unimplemented Memory Map Write (Addr: 0x{:x}", idx);
+            self.write_block(bank, 0, &words);
+        }
+    }
+
+    /// Register a callback for every change to the given erasable bank
+    pub fn watch_bank(&mut self, bank: usize, notify: ErasableWatchFn) {
+        match self.watchers.get_mut(bank) {
+            Some(watcher) => *watcher = Some(notify),
+            None => error!("Cannot watch erasable bank E{}", bank),
+        }
+    }
+
+    /// Remove the change callback for the given erasable bank
+    pub fn unwatch_bank(&mut self, bank: usize) {
+        if let Some(watcher) = self.watchers.get_mut(bank) {
+            *watcher = None;
+        }
+    }
+
+    fn write_ram(&mut self, bank: usize, offset: usize, val: u16) {
+        let notify = self.watchers[bank];
+        if let Some(notify) = notify {
+            let old = self.ram.read(bank, offset);
+            self.ram.write(bank, offset, val);
+            let new = self.ram.read(bank, offset);
+            if old != new {
+                notify(bank, offset, new);
             }
+        } else {
+            self.ram.write(bank, offset, val);
+        }
+    }
+
+    /// Counter cell update from an unprogrammed sequence
+    /// Unlike CPU writes, this may update input counters (CDUs, PIPAs)
+    pub fn write_counter(&mut self, idx: usize, val: u16) {
+        let val = if self.cdu_zeroed(idx) { 0 } else { val };
+        #[cfg(feature = "events")]
+        self.record_write(idx, val);
+        match idx {
+            0o32..=0o60 => self.special.set_counter(idx, val),
+            _ => self.store(idx, val),
         }
     }
 
+    /// Hardware configuration of the loaded rope
+    pub fn rom_info(&self) -> &rom::RomInfo {
+        &self.rom_info
+    }
+
+    /// Drain an output counter (THRUST, CDU commands...) as its drive
+    /// hardware would, returning the pulses software left in it
+    pub fn drain_output_counter(&mut self, idx: usize) -> u16 {
+        self.special.drain_command(idx)
+    }
+
     /// Main memory read handler with bank switching
     pub fn read(&self, idx: usize) -> u16 {
-        let val = match idx {
-            0o00..=0o17 => self.regs.read(0, (idx & 0xff) as usize), // CPU regs
-            0o20..=0o23 => self.edit.read(0, idx),                   // Edit regs
-            0o24..=0o31 => self.timers.read(0, idx),                 // Timers
-            0o32..=0o60 => self.special.read(0, idx),                // Control regs
-            address_space::VOLATILE_START..=address_space::VOLATILE_END => {
-                // RAM
-                // Handle erasable bank selection
-                if (idx >> 8) == 3 {
-                    self.ram
-                        .read(self.regs.erasable_bank, (idx & 0xff) as usize)
-                } else {
-                    self.ram.read(idx >> 8, (idx & 0xff) as usize)
-                }
-            }
-            address_space::PERSISTENT_START..=address_space::PERSISTENT_END => {
-                // ROM
-                // Handle fixed bank selection
-                if (idx >> 10) == 1 {
-                    self.rom.read(self.regs.fixed_bank, (idx & 0x3ff) as usize)
-                } else {
-                    self.rom.read(idx >> 10, (idx & 0x3ff) as usize)
+        let value = match self.locate(idx) {
+            Some(Location::Central(idx)) => match idx {
+                0o00..=0o17 => self.regs.read(0, idx),   // CPU regs
+                0o20..=0o23 => self.edit.read(0, idx),   // Edit regs
+                0o24..=0o31 => self.timers.read(0, idx), // Timers
+                _ => self.special.read(0, idx),          // Control regs
+            },
+            Some(Location::Erasable(addr)) => self.ram.read(addr.bank, addr.offset),
+            Some(Location::Fixed(addr)) => {
+                if addr.bank >= self.rom.bank_count() {
+                    self.count_access(|stats| stats.count(Anomaly::MissingBank(addr.bank)));
                 }
+                self.rom.read(addr.bank, addr.offset)
             }
-            _ => {
+            None => {
+                self.count_access(|stats| stats.unmapped += 1);
                 error!("Unimplemented Memory Map Read (Addr: 0x{:x}", idx);
                 0
             }
         };
-        val
+        if !matches!(idx, REGISTER_ACCUMULATOR | REGISTER_MULTIPLIER) {
+            checks::word15("location", value);
+        }
+        value
+    }
+
+    /// Where a CPU address lands with the bank registers as they stand: EB
+    /// selects the bank behind 1400-1777, FB the one behind 2000-3777, and
+    /// the superbank bit of channel 7 moves FB 30-37 up to banks 40-47
+    pub fn locate(&self, idx: usize) -> Option<Location> {
+        self.space().locate(idx)
+    }
+
+    /// Address decoding under the bank registers as they stand
+    pub fn space(&self) -> AddressSpace {
+        let superbank = self.io.superbank();
+        AddressSpace::new(self.regs.erasable_bank(), self.regs.fixed_bank(), superbank)
+    }
+
+    /// EB, FB and the superbank, and the fixed bank they select
+    pub fn banks(&self) -> Banks {
+        let superbank = self.io.superbank();
+        Banks {
+            eb: self.regs.erasable_bank(),
+            fb: self.regs.fixed_bank(),
+            superbank: ((superbank & constants::ports::SUPERBNK_BITS) >> 4) as usize,
+            fixed: self.space().fixed.unwrap_or_default(),
+        }
+    }
+
+    /// Record the cause of a hardware restart in channel 77
+    pub fn latch_restart_cause(&mut self, bits: u16) {
+        self.io.latch_restart_cause(bits);
+    }
+
+    /// Fixed banks the rope provides
+    pub fn fixed_bank_count(&self) -> usize {
+        self.rom.bank_count()
+    }
+
+    /// Identity of the loaded rope: a hash of every word as wired, so
+    /// injected faults and overlays don't change it
+    pub fn rom_hash(&self) -> u64 {
+        let mut state = crate::hash::Fnv1a::default();
+        for bank in 0..self.rom.bank_count() {
+            for offset in 0..constants::STORAGE_SEGMENT_SIZE {
+                self.rom.stored_word(bank, offset).hash(&mut state);
+            }
+        }
+        state.finish()
+    }
+
+    /// Load another rope in place of this one, keeping erasable, the
+    /// channels and the peripherals wired to them
+    pub fn swap_rope(&mut self, rom: rom::ReadOnlyMemory<'a>, info: rom::RomInfo) {
+        self.rom = rom;
+        self.rom_info = info;
+    }
+
+    /// Emulate a fixed-memory hardware fault on a bank (None clears it)
+    pub fn set_bank_fault(&mut self, bank: usize, fault: Option<rom::BankFault>) {
+        self.rom.set_bank_fault(bank, fault);
+    }
+
+    /// Returns whether a fixed-memory parity failure occurred since the last call
+    pub fn take_parity_alarm(&self) -> bool {
+        self.rom.take_parity_alarm()
+    }
+
+    /// Counts of unmapped, fixed-memory and channel accesses the emulator
+    /// carried on from, since the map was built
+    pub fn access_stats(&self) -> AccessStats {
+        let own = self.access.get();
+        AccessStats {
+            unmapped: own.unmapped,
+            rom_writes: own.rom_writes,
+            missing_banks: own.missing_banks,
+            ..self.io.access_stats()
+        }
+    }
+
+    /// A write to fixed memory, which the CPU stops short of the map
+    pub(crate) fn count_rom_write(&self) {
+        self.count_access(|stats| stats.rom_writes += 1);
+    }
+
+    fn count_access(&self, count: impl FnOnce(&mut AccessStats)) {
+        let mut stats = self.access.get();
+        count(&mut stats);
+        self.access.set(stats);
+    }
+
+    /// The most recent anomaly since the last call, if any
+    pub fn take_anomaly(&mut self) -> Option<Anomaly> {
+        let strictness = self.io.strictness();
+        let bank = self
+            .rom
+            .take_missing_bank()
+            .map(Anomaly::MissingBank)
+            .filter(|anomaly| strictness.reports(*anomaly));
+        self.io.take_anomaly().or(bank)
+    }
+
+    /// Register a tap on an I/O channel. Returns false when the tap table is full.
+    pub fn add_tap(&mut self, channel: usize, tap: &'a mut dyn ChannelTap) -> bool {
+        self.io.add_tap(channel, tap)
+    }
+
+    /// Drive an input channel (keys, discretes) from outside the computer
+    pub fn set_channel_input(&mut self, channel: usize, value: u16) {
+        self.io.set_input(channel, value);
+    }
+
+    /// Pin channel bits to hardware-true values, e.g. a peripheral flip-flop.
+    /// CPU writes and read-modify-write instructions see the pinned bits.
+    pub fn pin_channel_bits(&mut self, channel: usize, mask: u16, bits: u16) -> bool {
+        self.io.pin_bits(channel, mask, bits)
+    }
+
+    /// Assert or release a named input discrete. Asserted discretes hold
+    /// their channel bit low until released. Voltage fail restarts the
+    /// computer, so only `Cpu::set_discrete` takes it.
+    pub fn set_discrete(&mut self, discrete: Discrete, asserted: bool) -> bool {
+        match discrete {
+            Discrete::ImuOperate => return self.io.set_imu_operate(asserted),
+            Discrete::VoltageFail => return false,
+            _ => {}
+        }
+        match asserted {
+            true => self.io.pin_bits(discrete.channel(), discrete.mask(), 0),
+            false => {
+                self.io.release_bits(discrete.channel(), discrete.mask());
+                true
+            }
+        }
+    }
+
+    /// Deflect a hand controller axis. RHC axes load their counter with the
+    /// deflection in counts and hold the out-of-detent bit while any of
+    /// them is off centre; THC axes assert the bit for the direction of
+    /// travel.
+    pub fn set_axis(&mut self, axis: Axis, deflection: i16) {
+        let channel = constants::ports::CHANNEL_CHAN31;
+        if let Some(reg) = axis.rhc_register() {
+            self.write_counter(reg, SignedAgc::new(deflection).to_word15().raw());
+            let deflected = AXES
+                .iter()
+                .filter_map(|a| a.rhc_register())
+                .any(|r| Word15::new(self.read(r)).to_signed().get() != 0);
+            let detent = constants::ports::CHAN31_RHC_OUT_OF_DETENT;
+            match deflected {
+                true => self.io.pin_bits(channel, detent, 0),
+                false => {
+                    self.io.release_bits(channel, detent);
+                    true
+                }
+            };
+            return;
+        }
+        let (plus, minus) = axis.thc_bits();
+        self.io.release_bits(channel, plus | minus);
+        match deflection {
+            d if d > 0 => self.io.pin_bits(channel, plus, 0),
+            d if d < 0 => self.io.pin_bits(channel, minus, 0),
+            _ => true,
+        };
+    }
+
+    /// Release pinned channel bits back to CPU control
+    pub fn release_channel_bits(&mut self, channel: usize, mask: u16) {
+        self.io.release_bits(channel, mask);
+    }
+
+    /// Start or stop journaling delivered channel writes
+    pub fn set_channel_journal(&mut self, enabled: bool) {
+        self.io.set_journaling(enabled);
+    }
+
+    /// Drain journaled channel writes in delivery order
+    pub fn drain_channel_journal(&mut self, f: impl FnMut(usize, u16)) {
+        self.io.drain_journal(f);
+    }
+
+    /// Channel writes lost to a full journal since power on
+    pub fn channel_journal_dropped(&self) -> u32 {
+        self.io.journal_dropped()
     }
 
-    /// Aggregate interrupt status from I/O subsystems
-    pub fn check_interrupts(&mut self) -> u16 {
-        self.io.get_interrupt_status()
+    /// Set the DSKY relay update cadence in MCTs (0 = immediate)
+    pub fn set_relay_cadence(&mut self, cadence: u32) {
+        self.io.set_relay_cadence(cadence);
+    }
+
+    /// Set the shortest uplink word and downlink pair spacing in MCTs
+    /// (0 = any)
+    pub fn set_link_pacing(&mut self, uplink: u64, downlink: u64) {
+        self.io.set_link_pacing(uplink, downlink);
+    }
+
+    /// An uplink word arrives in INLINK and requests UPRUPT
+    pub fn receive_uplink(&mut self, word: u16) {
+        self.io.receive_uplink();
+        self.write_counter(
+            constants::special_registers::SPECIAL_REGISTER_DATA_INPUT,
+            word & 0o77777,
+        );
+        self.request_rupt(RuptRequest::Uprupt);
+    }
+
+    /// Set the ISS turn-on delay in MCTs, e.g. shortened for tests
+    pub fn set_iss_delay(&mut self, delay: u32) {
+        self.io.set_iss_delay(delay);
+    }
+
+    /// ISS power and turn-on state
+    pub fn iss(&self) -> &iss::Iss {
+        self.io.iss()
+    }
+
+    /// Advance I/O timing models by the given number of MCTs
+    pub fn tick_io(&mut self, cycles: u16) {
+        let flash = self.io.tick(cycles);
+        #[cfg(feature = "events")]
+        if let Some(lamps) = flash {
+            self.record(Event::DskyFlash { lamps });
+        }
+        #[cfg(not(feature = "events"))]
+        let _ = flash;
+    }
+
+    pub fn set_standby_light(&mut self, on: bool) {
+        self.io.set_standby_light(on);
+    }
+
+    /// Save I/O channel and peripheral state for a snapshot, one chunk
+    /// per tag
+    pub fn save_periph_states(&mut self, chunk: impl FnMut([u8; 4], &[u8])) {
+        self.io.save_states(chunk);
+    }
+
+    /// Restore a chunk from `save_periph_states`; false when nothing
+    /// attached claims its tag
+    pub fn restore_periph_state(
+        &mut self,
+        tag: [u8; 4],
+        data: &[u8],
+    ) -> Result<bool, &'static str> {
+        self.io.restore_states(tag, data)
+    }
+
+    /// Flashing DSKY lamps as shown now, in channel 11 positions: KEY REL
+    /// and OPR ERR lit, VERB/NOUN FLASH set while VERB and NOUN are blanked
+    pub fn dsky_flash(&self) -> u16 {
+        self.io.flash().shown()
+    }
+
+    /// Whether channel 13 has the DSKY lamp test running
+    pub fn lamp_test(&self) -> bool {
+        self.io.lamp_test()
+    }
+
+    /// Emulated time since power-on
+    pub fn now(&self) -> EmuTime {
+        self.io.now()
+    }
+
+    /// PRO held long enough to toggle standby, as timed by the DSKY
+    pub fn standby_request(&self) -> bool {
+        self.io.standby_request()
+    }
+
+    /// Latch an interrupt request until the CPU takes it
+    pub fn request_rupt(&mut self, rupt: RuptRequest) {
+        if rupt == RuptRequest::T4rupt {
+            self.io.end_display_frame();
+        }
+        self.rupt_requests |= rupt.mask();
+    }
+
+    /// Drain interrupt requests latched here and raised by peripherals
+    pub fn take_rupts(&mut self) -> u16 {
+        let requests = self.rupt_requests | self.io.get_interrupt_status();
+        self.rupt_requests = 0;
+        requests
+    }
+}
+
+#[cfg(test)]
+mod block_tests {
+    use super::MemoryMap;
+    use core::sync::atomic::{AtomicUsize, Ordering};
+
+    static CHANGES: AtomicUsize = AtomicUsize::new(0);
+
+    fn count_change(bank: usize, _offset: usize, _value: u16) {
+        assert_eq!(bank, 4);
+        CHANGES.fetch_add(1, Ordering::SeqCst);
+    }
+
+    #[test]
+    fn block_access_and_notifications() {
+        let mut mem = MemoryMap::new_blank();
+        mem.watch_bank(4, count_change);
+
+        mem.write_block(4, 0o10, &[1, 2, 0o177777]);
+        assert_eq!(mem.read_block(4, 0o10..0o13), &[1, 2, 0o77777]);
+        assert_eq!(CHANGES.load(Ordering::SeqCst), 3);
+
+        // Rewriting the same words, or a zero over a zero, is no change
+        mem.write_block(4, 0o10, &[1, 2, 0o77777, 0]);
+        assert_eq!(CHANGES.load(Ordering::SeqCst), 3);
+
+        // Program writes through the switched window notify only on change
+        mem.write(0o3, 0o2000); // EB = E4
+        mem.write(0o1410, 1);
+        mem.write(0o1411, 7);
+        assert_eq!(CHANGES.load(Ordering::SeqCst), 4);
+        assert_eq!(mem.read_block(4, 0o11..0o12), &[7]);
+
+        assert!(mem.read_block(8, 0..1).is_empty());
+    }
+}
+
+#[cfg(test)]
+mod register_file_tests {
+    use super::MemoryMap;
+    use crate::constants::cycle_registers::SPECIAL_REGISTER_CYCLE_RIGHT;
+    use crate::constants::ports::{CHANNEL_L, CHANNEL_Q};
+    use crate::constants::registers::*;
+    use crate::constants::timers::TIMER_1_ADDRESS;
+    use crate::word::Word15;
+
+    #[test]
+    fn e0_registers_alias_the_register_file() {
+        let mut mem = MemoryMap::new_blank();
+
+        // A and Q keep 16 bits through every path
+        mem.write(REGISTER_ACCUMULATOR, 0o100000); // Negative overflow
+        mem.write_io(CHANNEL_Q, 0o140001);
+        assert_eq!(mem.read_block(0, 0..3), &[0o100000, 0, 0o140001]);
+        assert_eq!(mem.read(REGISTER_MULTIPLIER), 0o140001);
+        assert!(mem.accumulator_overflow());
+
+        // Typed access corrects overflow out of A and sign-extends into it
+        assert_eq!(mem.read_word(REGISTER_ACCUMULATOR), Word15::new(0o40000));
+        mem.write_word(REGISTER_ACCUMULATOR, Word15::new(0o77775));
+        assert_eq!(mem.read(REGISTER_ACCUMULATOR), 0o177775);
+
+        // Block writes over E0 land in registers, then the edit registers,
+        // edited as a CPU store would be
+        mem.write_block(0, REGISTER_LINK, &[0o177777]);
+        assert_eq!(mem.read_io(CHANNEL_L), 0o77777);
+        mem.write_block(0, REGISTER_INSTRUCTION, &[0o12345, 0o54321, 0o54321]);
+        assert_eq!(mem.read(REGISTER_INSTRUCTION), 0o12345);
+        assert_eq!(mem.read(SPECIAL_REGISTER_CYCLE_RIGHT), 0o66150);
+        assert_eq!(mem.read_block(0, 0o20..0o22), &[0o66150, 0o66150]);
+
+        // Z is 12 bits, BB splits into EB and FB, the null register reads zero
+        mem.write_block(0, REGISTER_COUNTER, &[0o17777, 0o2405, 0o1234]);
+        assert_eq!(mem.read(REGISTER_COUNTER), 0o7777);
+        assert_eq!(mem.read_block(0, 3..5), &[0o2400, 0o2000]);
+        assert_eq!(mem.read(REGISTER_NULL), 0);
+
+        // Timers and counters are where the CPU finds them, and a block may
+        // run from the registers through them into RAM
+        mem.write_block(0, TIMER_1_ADDRESS - 1, &[0o4321, 0o1234, 0o7]);
+        assert_eq!(mem.read(TIMER_1_ADDRESS), 0o1234);
+        mem.write(0o61, 0o777);
+        let block = mem.read_block(0, 0..0o62);
+        assert_eq!(block.len(), 0o62);
+        assert_eq!(block[REGISTER_INSTRUCTION], 0o12345);
+        assert_eq!(
+            block[TIMER_1_ADDRESS - 1..=TIMER_1_ADDRESS],
+            [0o4321, 0o1234]
+        );
+        assert_eq!(block[0o61], 0o777);
+    }
+
+    #[test]
+    fn superbank_extends_fb_30_to_37() {
+        use super::{Banks, Location};
+        use crate::constants::ports::CHANNEL_SUPERBNK;
+        use crate::hooks::FixedAddress;
+        let mut mem = MemoryMap::new_blank();
+
+        // Only bits 5-7 exist
+        mem.write_io(CHANNEL_SUPERBNK, 0o77777);
+        assert_eq!(mem.read_io(CHANNEL_SUPERBNK), 0o160);
+
+        for (fb, superbank, fixed) in [
+            (0o27, 0o4, 0o27),
+            (0o30, 0o3, 0o30),
+            (0o33, 0o0, 0o33),
+            (0o30, 0o4, 0o40),
+            (0o33, 0o4, 0o43),
+            (0o37, 0o7, 0o47),
+        ] {
+            mem.write(REGISTER_FIXED_BANK, (fb as u16) << 10);
+            mem.write_io(CHANNEL_SUPERBNK, (superbank as u16) << 4);
+            let banks = Banks {
+                eb: 0,
+                fb,
+                superbank,
+                fixed,
+            };
+            assert_eq!(mem.banks(), banks);
+            assert_eq!(
+                mem.locate(0o2001),
+                Some(Location::Fixed(FixedAddress::new(fixed, 1)))
+            );
+        }
+
+        // Decoding follows pins on the channel as well as writes
+        mem.write(REGISTER_FIXED_BANK, 0o30 << 10);
+        mem.write_io(CHANNEL_SUPERBNK, 0);
+        mem.pin_channel_bits(CHANNEL_SUPERBNK, 0o100, 0o100);
+        assert_eq!(mem.banks().fixed, 0o40);
+        mem.release_channel_bits(CHANNEL_SUPERBNK, 0o100);
+        assert_eq!(mem.banks().fixed, 0o30);
+    }
+
+    #[test]
+    fn chan12_zero_bits_hold_cdus() {
+        use crate::constants::ports::*;
+        use crate::constants::special_registers::*;
+        let mut mem = MemoryMap::new_blank();
+        mem.write_counter(SPECIAL_REGISTER_CONTROL_DISPLAY_Y, 0o1234);
+        mem.write_counter(SPECIAL_REGISTER_OPTICAL_X, 0o4321);
+
+        mem.write_io(CHANNEL_CHAN12, CHAN12_ZERO_IMU_CDUS);
+        assert_eq!(mem.read(SPECIAL_REGISTER_CONTROL_DISPLAY_Y), 0);
+        mem.write_counter(SPECIAL_REGISTER_CONTROL_DISPLAY_Y, 1);
+        assert_eq!(mem.read(SPECIAL_REGISTER_CONTROL_DISPLAY_Y), 0, "held");
+        assert_eq!(mem.read(SPECIAL_REGISTER_OPTICAL_X), 0o4321);
+
+        mem.write_io(CHANNEL_CHAN12, CHAN12_ZERO_OPTICS_CDUS);
+        assert_eq!(mem.read(SPECIAL_REGISTER_OPTICAL_X), 0);
+        mem.write_counter(SPECIAL_REGISTER_CONTROL_DISPLAY_Y, 1);
+        assert_eq!(mem.read(SPECIAL_REGISTER_CONTROL_DISPLAY_Y), 1, "released");
+    }
+
+    #[test]
+    fn output_counters_hold_until_drained() {
+        use crate::constants::special_registers::*;
+        let mut mem = MemoryMap::new_blank();
+
+        mem.write(SPECIAL_REGISTER_THRUST, 0o177);
+        assert_eq!(mem.read(SPECIAL_REGISTER_THRUST), 0o177);
+        assert_eq!(mem.drain_output_counter(SPECIAL_REGISTER_THRUST), 0o177);
+        assert_eq!(mem.read(SPECIAL_REGISTER_THRUST), 0);
+        assert_eq!(mem.drain_output_counter(SPECIAL_REGISTER_INERTIAL_X), 0);
+
+        // RNRAD is loaded by the radar interface, not by software
+        mem.write(SPECIAL_REGISTER_NAV_RADAR, 0o123);
+        assert_eq!(mem.read(SPECIAL_REGISTER_NAV_RADAR), 0);
+        mem.write_counter(SPECIAL_REGISTER_NAV_RADAR, 0o123);
+        assert_eq!(mem.read(SPECIAL_REGISTER_NAV_RADAR), 0o123);
     }
 }
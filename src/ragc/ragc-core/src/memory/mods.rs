@@ -1,15 +1,175 @@
+use crate::constants::registers;
+use crate::memory::downlink::DownlinkPair;
+use crate::memory::relay::DisplayFrame;
+use crate::memory::state::PeriphState;
+use core::iter::FromIterator;
+
+/// Interrupt a peripheral can request, named after its RUPT vector
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[non_exhaustive]
+pub enum RuptRequest {
+    T5rupt,   // TIME5 overflow (digital autopilot)
+    T3rupt,   // TIME3 overflow (WAITLIST)
+    T4rupt,   // TIME4 overflow (DSKY/IMU servicing)
+    Keyrupt1, // Main DSKY keypress
+    Keyrupt2, // Navigation DSKY keypress
+    Uprupt,   // Uplink word received
+    Downrupt, // Telemetry word wanted
+    Radarupt, // Radar data ready
+    Handrupt, // Hand controller / RUPT10
+}
+
+impl RuptRequest {
+    /// Vector number used by the priority encoder
+    pub fn vector(self) -> u8 {
+        match self {
+            RuptRequest::T5rupt => registers::INTERRUPT_TIMER5,
+            RuptRequest::T3rupt => registers::INTERRUPT_TIMER3,
+            RuptRequest::T4rupt => registers::INTERRUPT_TIMER4,
+            RuptRequest::Keyrupt1 => registers::INTERRUPT_KEYPRESS1,
+            RuptRequest::Keyrupt2 => registers::INTERRUPT_KEYPRESS2,
+            RuptRequest::Uprupt => registers::INTERRUPT_UPLINK,
+            RuptRequest::Downrupt => registers::INTERRUPT_DOWNLINK,
+            RuptRequest::Radarupt => registers::INTERRUPT_RADAR,
+            RuptRequest::Handrupt => registers::INTERRUPT_MANUAL,
+        }
+    }
+
+    /// Request bit in the priority encoder
+    pub fn mask(self) -> u16 {
+        1 << self.vector()
+    }
+}
+
+/// Interrupts raised together, as priority encoder bits
+#[derive(Clone, Copy, PartialEq, Eq, Default, Debug)]
+pub struct RuptRequests(u16);
+
+impl RuptRequests {
+    pub const NONE: RuptRequests = RuptRequests(0);
+
+    pub fn insert(&mut self, rupt: RuptRequest) {
+        self.0 |= rupt.mask();
+    }
+
+    pub fn contains(self, rupt: RuptRequest) -> bool {
+        self.0 & rupt.mask() != 0
+    }
+
+    pub fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+
+    /// Request bits in the priority encoder
+    pub fn mask(self) -> u16 {
+        self.0
+    }
+
+    /// The requests, highest priority first
+    pub fn iter(self) -> impl Iterator<Item = RuptRequest> {
+        crate::capability::INTERRUPTS
+            .iter()
+            .copied()
+            .filter(move |rupt| self.contains(*rupt))
+    }
+}
+
+impl From<RuptRequest> for RuptRequests {
+    fn from(rupt: RuptRequest) -> Self {
+        RuptRequests(rupt.mask())
+    }
+}
+
+impl FromIterator<RuptRequest> for RuptRequests {
+    fn from_iter<I: IntoIterator<Item = RuptRequest>>(rupts: I) -> Self {
+        let mut requests = RuptRequests::NONE;
+        rupts.into_iter().for_each(|rupt| requests.insert(rupt));
+        requests
+    }
+}
+
+/// Length of an MCT in seconds: 12 cycles of the 1.024 MHz clock, 11.71875 us
+pub const MCT_SECONDS: f64 = 12.0 / 1_024_000.0;
+
+/// Emulated time: MCTs elapsed since power-on. Runs with the CPU, so it
+/// stops while paused and scales with emulation speed.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug, Default)]
+pub struct EmuTime(u64);
+
+impl EmuTime {
+    pub const ZERO: EmuTime = EmuTime(0);
+
+    pub const fn from_mcts(mcts: u64) -> Self {
+        Self(mcts)
+    }
+
+    pub const fn mcts(self) -> u64 {
+        self.0
+    }
+
+    /// Milliseconds since power-on; one MCT is 12 cycles of the 1.024 MHz clock
+    pub const fn as_millis(self) -> u64 {
+        self.0 * 3 / 256
+    }
+
+    /// Microseconds since power-on
+    pub const fn as_micros(self) -> u64 {
+        self.0 * 375 / 32
+    }
+
+    /// Seconds since power-on
+    pub fn as_secs_f64(self) -> f64 {
+        self.0 as f64 * MCT_SECONDS
+    }
+
+    /// MCTs from `earlier` to now, zero if `earlier` is later
+    pub const fn since(self, earlier: EmuTime) -> u64 {
+        self.0.saturating_sub(earlier.0)
+    }
+
+    /// MCTs in a number of milliseconds, for durations and deadlines
+    pub const fn mcts_in_millis(ms: u64) -> u64 {
+        ms * 256 / 3
+    }
+
+    /// MCTs in a number of seconds, rounded down
+    pub fn mcts_in_secs(secs: f64) -> u64 {
+        (secs / MCT_SECONDS) as u64
+    }
+
+    pub(crate) fn advance(&mut self, mcts: u16) {
+        self.0 += mcts as u64;
+    }
+}
+
 /// Base trait for interrupt-capable peripherals
-pub trait Peripheral {
-    /// Check interrupt status and return identifier
-    fn is_interrupt(&mut self) -> u16;
+pub trait InterruptSource {
+    /// Interrupts raised since the last poll
+    fn rupt_requests(&mut self) -> RuptRequests;
 }
 
 /// Interface for channel-based I/O devices
-pub trait IoPeriph {
+pub trait IoPeriph: InterruptSource {
     /// Read and write from specified channel (port/register)
     fn read(&self, _channel_idx: usize) -> u16;
     fn write(&mut self, channel_idx: usize, value: u16);
 
-    /// Check device-specific interrupt status
-    fn is_interrupt(&mut self) -> u16;
+    /// Called as emulated time advances, before reads and writes at `now`
+    fn tick(&mut self, _now: EmuTime) {}
+
+    /// A complete downlink word pair, as shifted out after a DOWNRUPT
+    fn downlink(&mut self, _pair: DownlinkPair) {}
+
+    /// The relay words of one display cycle, once its burst has completed
+    fn display_frame(&mut self, _frame: &DisplayFrame) {}
+
+    /// PRO has been held long enough to switch standby on or off
+    fn standby_request(&self) -> bool {
+        false
+    }
+
+    /// State to keep in snapshots, for peripherals holding any
+    fn periph_state(&mut self) -> Option<&mut dyn PeriphState> {
+        None
+    }
 }
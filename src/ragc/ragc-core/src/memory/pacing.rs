@@ -0,0 +1,98 @@
+use super::mods::EmuTime;
+
+/// Shortest uplink word spacing the hardware accepts: 16 bits at 1 kbit/s,
+/// less a millisecond for words delivered on instruction boundaries
+pub const UPLINK_WORD_DEFAULT: u64 = EmuTime::mcts_in_millis(15);
+
+/// Shortest downlink pair spacing: 50 pairs a second at the high bit rate
+pub const DOWNLINK_PAIR_DEFAULT: u64 = EmuTime::mcts_in_millis(20);
+
+/// Times words on a link against the fastest the hardware carries them
+///
+/// A word arriving sooner than `min_gap` MCTs after the previous one would
+/// overrun the link, which the hardware latches in channel 33 as UPLINK or
+/// DOWNLINK TOO FAST for the software to alarm on. A gap of zero lets
+/// words through at any rate.
+pub struct LinkPacer {
+    min_gap: u64,
+    last: Option<EmuTime>,
+}
+
+impl LinkPacer {
+    pub const fn new(min_gap: u64) -> Self {
+        Self {
+            min_gap,
+            last: None,
+        }
+    }
+
+    pub fn set_min_gap(&mut self, min_gap: u64) {
+        self.min_gap = min_gap;
+    }
+
+    /// Note a word at `now`; true if it came too soon after the last
+    pub fn arrive(&mut self, now: EmuTime) -> bool {
+        let early = match self.last {
+            Some(last) => now.since(last) < self.min_gap,
+            None => false,
+        };
+        self.last = Some(now);
+        early
+    }
+}
+
+#[cfg(test)]
+mod pacing_tests {
+    use super::LinkPacer;
+    use crate::constants::ports::{
+        CHAN33_DOWNLINK_TOO_FAST, CHAN33_UPLINK_TOO_FAST, CHANNEL_CHAN33, CHANNEL_CHAN34,
+        CHANNEL_CHAN35,
+    };
+    use crate::memory::mods::EmuTime;
+    use crate::memory::MemoryMap;
+
+    fn encode(key: u16) -> u16 {
+        (key << 10) | ((!key & 0o37) << 5) | key
+    }
+
+    #[test]
+    fn words_inside_the_gap_are_early() {
+        let mut pacer = LinkPacer::new(100);
+        assert!(!pacer.arrive(EmuTime::from_mcts(50)));
+        assert!(!pacer.arrive(EmuTime::from_mcts(150)));
+        assert!(pacer.arrive(EmuTime::from_mcts(249)));
+
+        pacer.set_min_gap(0);
+        assert!(!pacer.arrive(EmuTime::from_mcts(249)));
+    }
+
+    #[test]
+    fn overruns_latch_channel_33_until_read() {
+        let mut mem = MemoryMap::new_blank();
+        mem.set_link_pacing(100, 100);
+        let link_bits = CHAN33_UPLINK_TOO_FAST | CHAN33_DOWNLINK_TOO_FAST;
+
+        mem.receive_uplink(encode(0o21));
+        mem.tick_io(100);
+        mem.receive_uplink(encode(0o37));
+        assert_eq!(mem.read_io(CHANNEL_CHAN33) & link_bits, link_bits);
+        mem.tick_io(99);
+        mem.receive_uplink(encode(0o34));
+        assert_eq!(mem.read(0o45), encode(0o34)); // The word still lands
+        assert_eq!(
+            mem.read_io(CHANNEL_CHAN33) & link_bits,
+            CHAN33_DOWNLINK_TOO_FAST
+        );
+        assert_eq!(mem.read_io(CHANNEL_CHAN33) & link_bits, link_bits);
+
+        // Two downlink pairs in one DOWNRUPT
+        let writes = [(CHANNEL_CHAN34, 1), (CHANNEL_CHAN35, 2)];
+        for (channel, word) in writes.iter().chain(writes.iter()).copied() {
+            mem.write_io(channel, word);
+        }
+        assert_eq!(
+            mem.read_io(CHANNEL_CHAN33) & link_bits,
+            CHAN33_UPLINK_TOO_FAST
+        );
+    }
+}
@@ -1,96 +1,163 @@
-use crate::constants;
+use crate::constants::registers::*;
 use crate::memory::MemoryType;
 
-/// Struct representing AGC (Apollo Guidance Computer) registers
+/// Central register file: the only storage for erasable addresses 0-17
 pub struct Registers {
-    registers: [u16; 32],     // Array of 16-bit general-purpose registers
-    pub fixed_bank: usize,    // Currently selected fixed memory bank
-    pub erasable_bank: usize, // Currently selected erasable memory bank
+    registers: [u16; REGISTER_MAX], // Stored as read, already masked to width
+    overflow: bool,                 // A holds an uncorrected overflow (bits 16-15 differ)
+}
+
+/// Bank register layouts: EB holds the erasable bank in bits 9-11, FB the
+/// fixed bank in bits 11-15, and BB both, fixed in 11-15 and erasable in 1-3
+const EB_MASK: u16 = 0o3400;
+const FB_MASK: u16 = 0o76000;
+const BB_EB_MASK: u16 = 0o7;
+
+/// Bits a bank register keeps when written, None for other registers. Any
+/// other bits are silently dropped by the hardware.
+pub const fn bank_register_bits(reg: usize) -> Option<u16> {
+    match reg {
+        REGISTER_ERASABLE_BANK => Some(EB_MASK),
+        REGISTER_FIXED_BANK => Some(FB_MASK),
+        REGISTER_COMBINED_BANK => Some(FB_MASK | BB_EB_MASK),
+        _ => None,
+    }
+}
+
+/// Bits a register holds: A and Q keep the overflow bit, Z is 12 bits and
+/// the null register reads as zero
+const fn width_mask(reg: usize) -> u16 {
+    match reg {
+        REGISTER_ACCUMULATOR | REGISTER_MULTIPLIER => 0o177777,
+        REGISTER_COUNTER => 0o7777,
+        REGISTER_NULL => 0,
+        _ => 0o77777,
+    }
 }
 
 impl Registers {
     /// Constructs a new AgcRegs instance with all registers and banks initialized to zero
     pub fn new() -> Self {
         Self {
-            registers: [0; 32],
-            fixed_bank: 0,
-            erasable_bank: 0,
+            registers: [0; REGISTER_MAX],
+            overflow: false,
         }
     }
     #[allow(dead_code)]
     pub fn reset(&mut self) {
-        self.registers = [0; 32];
-        self.fixed_bank = 0;
-        self.erasable_bank = 0;
+        self.registers = [0; REGISTER_MAX];
+        self.overflow = false;
     }
 
-    /// Updates the special bank-selection registers with the current fixed and erasable bank values
-    fn refresh_bank_registers(&mut self) {
-        let erasable_value = ((self.erasable_bank & 0x7) << 8) as u16; // Only lower 3 bits are used, shifted to bits 8–10
-        let fixed_value = ((self.fixed_bank & 0x1F) << 10) as u16; // Only lower 5 bits are used, shifted to bits 10–14
+    /// Overflow state of A, kept up to date on every write to it
+    pub fn overflow(&self) -> bool {
+        self.overflow
+    }
 
-        // Combined value includes fixed bank bits only
-        let combined_bank_value = (erasable_value >> 8) | fixed_value;
+    /// Erasable bank switched into 1400-1777
+    pub fn erasable_bank(&self) -> usize {
+        (self.registers[REGISTER_ERASABLE_BANK] >> 8) as usize
+    }
+
+    /// Fixed bank switched into 2000-3777
+    pub fn fixed_bank(&self) -> usize {
+        (self.registers[REGISTER_FIXED_BANK] >> 10) as usize
+    }
 
-        self.registers[constants::registers::REGISTER_ERASABLE_BANK] = erasable_value;
-        self.registers[constants::registers::REGISTER_FIXED_BANK] = fixed_value;
-        self.registers[constants::registers::REGISTER_COMBINED_BANK] = combined_bank_value;
+    /// Single update path for EB, FB and BB, so the three always agree
+    fn set_banks(&mut self, eb: u16, fb: u16) {
+        self.registers[REGISTER_ERASABLE_BANK] = eb & EB_MASK;
+        self.registers[REGISTER_FIXED_BANK] = fb & FB_MASK;
+        self.registers[REGISTER_COMBINED_BANK] = (fb & FB_MASK) | ((eb & EB_MASK) >> 8);
     }
 }
 
 impl MemoryType for Registers {
-    /// Reads a value from a register with appropriate masking for specific registers
     fn read(&self, _bank_index: usize, address_offset: usize) -> u16 {
-        match address_offset {
-            // ACCUMULATOR and MULTIPLIER are returned directly
-            constants::registers::REGISTER_ACCUMULATOR
-            | constants::registers::REGISTER_MULTIPLIER => self.registers[address_offset],
-
-            // REGISTER_ZERO is always masked to 12 bits
-            constants::registers::REGISTER_ZERO => self.registers[address_offset] & 0o7777,
-
-            constants::registers::REGISTER_NULL => 0o00000,
-
-            _ => self.registers[address_offset] & 0o77777,
-        }
+        self.registers[address_offset]
     }
 
     /// Writes a value to a register, with special handling for bank-selection registers
     fn write(&mut self, _bank_index: usize, address_offset: usize, new_value: u16) {
+        let eb = self.registers[REGISTER_ERASABLE_BANK];
+        let fb = self.registers[REGISTER_FIXED_BANK];
         match address_offset {
-            // Combined bank register: extract and update both erasable and fixed banks
-            constants::registers::REGISTER_COMBINED_BANK => {
-                self.erasable_bank = (new_value & 0x7) as usize; // Lower 3 bits
-                self.fixed_bank = ((new_value & 0x7C00) >> 10) as usize; // Bits 10–14
-                self.refresh_bank_registers();
-                return;
-            }
+            REGISTER_COMBINED_BANK => self.set_banks((new_value & BB_EB_MASK) << 8, new_value),
+            REGISTER_FIXED_BANK => self.set_banks(eb, new_value),
+            REGISTER_ERASABLE_BANK => self.set_banks(new_value, fb),
 
-            // Fixed bank register: update and refresh only fixed bank
-            constants::registers::REGISTER_FIXED_BANK => {
-                self.fixed_bank = ((new_value & 0x7C00) >> 10) as usize;
-                self.refresh_bank_registers();
-                return;
+            REGISTER_ACCUMULATOR => {
+                self.registers[address_offset] = new_value;
+                self.overflow = matches!(new_value & 0xC000, 0x4000 | 0x8000);
             }
 
-            // Erasable bank register: update and refresh only erasable bank
-            constants::registers::REGISTER_ERASABLE_BANK => {
-                self.erasable_bank = ((new_value & 0x0700) >> 8) as usize;
-                self.refresh_bank_registers();
-                return;
+            _ => {
+                self.registers[address_offset] = new_value & width_mask(address_offset);
             }
+        }
+    }
+}
 
-            constants::registers::REGISTER_ZERO => {
-                self.registers[address_offset] = new_value & 0o7777;
-            }
+#[cfg(test)]
+mod bank_tests {
+    use super::Registers;
+    use crate::constants::registers::*;
+    use crate::memory::{MemoryMap, MemoryType};
 
-            constants::registers::REGISTER_NULL => {
-                return;
-            }
-            _ => {
-                self.registers[address_offset] = new_value & 0o77777;
-            }
+    fn banks(regs: &Registers) -> (u16, u16, u16) {
+        (
+            regs.read(0, REGISTER_ERASABLE_BANK),
+            regs.read(0, REGISTER_FIXED_BANK),
+            regs.read(0, REGISTER_COMBINED_BANK),
+        )
+    }
+
+    #[test]
+    fn bank_registers_round_trip() {
+        for value in 0..0o100000u16 {
+            // BB sets both banks
+            let mut regs = Registers::new();
+            regs.write(0, REGISTER_COMBINED_BANK, value);
+            let (eb, fb, bb) = banks(&regs);
+            assert_eq!(
+                (eb, fb),
+                ((value & 0o7) << 8, value & 0o76000),
+                "BB {:o}",
+                value
+            );
+            assert_eq!(bb, value & 0o76007);
+            regs.write(0, REGISTER_COMBINED_BANK, bb);
+            assert_eq!(banks(&regs), (eb, fb, bb));
+
+            // EB and FB each update only their field of BB
+            let mut regs = Registers::new();
+            regs.write(0, REGISTER_COMBINED_BANK, 0o52003);
+            regs.write(0, REGISTER_ERASABLE_BANK, value);
+            assert_eq!(
+                banks(&regs),
+                (value & 0o3400, 0o52000, 0o52000 | (value >> 8) & 0o7)
+            );
+            regs.write(0, REGISTER_FIXED_BANK, value);
+            assert_eq!(
+                banks(&regs),
+                (
+                    value & 0o3400,
+                    value & 0o76000,
+                    (value & 0o76000) | (value >> 8) & 0o7
+                )
+            );
+            assert_eq!(regs.erasable_bank(), ((value >> 8) & 0o7) as usize);
+            assert_eq!(regs.fixed_bank(), (value >> 10) as usize);
         }
-        self.registers[address_offset] = new_value;
+    }
+
+    #[test]
+    fn switched_windows_follow_bb() {
+        let mut mem = MemoryMap::new_blank();
+        mem.write_block(5, 0o10, &[0o1234]);
+        mem.write(REGISTER_COMBINED_BANK, 0o2005); // FB 1, EB 5
+        assert_eq!(mem.read(0o1410), 0o1234);
+        assert_eq!(mem.read(REGISTER_FIXED_BANK), 0o2000);
+        assert_eq!(mem.read(REGISTER_ERASABLE_BANK), 0o2400);
     }
 }
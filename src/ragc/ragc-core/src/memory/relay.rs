@@ -0,0 +1,171 @@
+use heapless::Deque;
+
+use super::mods::EmuTime;
+
+/// Default relay update cadence: one relay word per 120 ms (in MCTs)
+pub const RELAY_CADENCE_DEFAULT: u32 = 120_000_000 / 11_700;
+
+/// Rows of the DSKY relay matrix that can be pending at once
+const RELAY_ROWS: usize = 16;
+
+/// Paces channel 10 relay words to the DSKY
+///
+/// The flight software drives one relay row per display cycle under T4RUPT
+/// and the relays can't switch any faster, so frontends should observe relay
+/// words at that cadence rather than the instant they are written. A cadence
+/// of zero passes words straight through, which is useful for fast CI runs.
+pub struct RelayScheduler {
+    cadence: u32,
+    elapsed: u32,
+    pending: Deque<u16, RELAY_ROWS>,
+}
+
+impl RelayScheduler {
+    pub fn new(cadence: u32) -> Self {
+        Self {
+            cadence,
+            elapsed: 0,
+            pending: Deque::new(),
+        }
+    }
+
+    pub fn cadence(&self) -> u32 {
+        self.cadence
+    }
+
+    pub fn set_cadence(&mut self, cadence: u32) {
+        self.cadence = cadence;
+    }
+
+    /// Queue a relay word. Returns the word back if it should be delivered
+    /// immediately. A pending word for the same row is replaced, as the
+    /// relay latch only holds the most recent value.
+    pub fn push(&mut self, word: u16) -> Option<u16> {
+        if self.cadence == 0 {
+            return Some(word);
+        }
+
+        let row = word >> 11;
+        if let Some(slot) = self.pending.iter_mut().find(|w| **w >> 11 == row) {
+            *slot = word;
+        } else if self.pending.push_back(word).is_err() {
+            return Some(word);
+        }
+        None
+    }
+
+    /// Advance by `cycles` MCTs, returning the next relay word if one is due
+    pub fn tick(&mut self, cycles: u32) -> Option<u16> {
+        if self.cadence == 0 {
+            return self.pending.pop_front();
+        }
+
+        self.elapsed = self.elapsed.saturating_add(cycles);
+        if self.elapsed < self.cadence || self.pending.is_empty() {
+            return None;
+        }
+        self.elapsed = 0;
+        self.pending.pop_front()
+    }
+}
+
+/// The relay words the software wrote in one display cycle, latest per row
+///
+/// Pinball rewrites the display a few rows per T4RUPT, so a register can be
+/// half updated between two channel 10 writes. Frames close on the T4RUPT
+/// that starts the next cycle, letting frontends apply a whole burst at once.
+#[derive(Clone, Default, PartialEq, Debug)]
+pub struct DisplayFrame {
+    pub at: EmuTime, // When the frame closed
+    words: heapless::Vec<u16, RELAY_ROWS>,
+}
+
+impl DisplayFrame {
+    /// Relay words in the order their rows were first written
+    pub fn words(&self) -> &[u16] {
+        &self.words
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.words.is_empty()
+    }
+
+    /// Add a relay word, replacing an earlier one for the same row
+    pub fn push(&mut self, word: u16) {
+        let row = word >> 11;
+        match self.words.iter_mut().find(|w| **w >> 11 == row) {
+            Some(slot) => *slot = word,
+            None => {
+                // Rows are 4 bits, so a frame never holds more than RELAY_ROWS
+                let _ = self.words.push(word);
+            }
+        }
+    }
+
+    /// Close the frame at `now`, returning it if anything was written
+    pub fn close(&mut self, now: EmuTime) -> Option<DisplayFrame> {
+        if self.words.is_empty() {
+            return None;
+        }
+        let mut frame = core::mem::take(self);
+        frame.at = now;
+        Some(frame)
+    }
+}
+
+#[cfg(test)]
+mod relay_tests {
+    use super::{DisplayFrame, RelayScheduler};
+    use crate::constants::ports::CHANNEL_DSKY;
+    use crate::memory::mods::{EmuTime, RuptRequest};
+    use crate::memory::tap::ChannelTap;
+    use crate::memory::MemoryMap;
+
+    #[test]
+    fn relay_words_are_paced_and_coalesced() {
+        let mut relays = RelayScheduler::new(100);
+        assert_eq!(relays.push(0o04001), None);
+        assert_eq!(relays.push(0o10002), None);
+        assert_eq!(relays.push(0o04003), None); // Same row as the first
+
+        assert_eq!(relays.tick(50), None);
+        assert_eq!(relays.tick(50), Some(0o04003));
+        assert_eq!(relays.tick(10), None);
+        assert_eq!(relays.tick(90), Some(0o10002));
+        assert_eq!(relays.tick(200), None);
+
+        relays.set_cadence(0);
+        assert_eq!(relays.push(0o04001), Some(0o04001));
+    }
+
+    #[derive(Default)]
+    struct Frames {
+        seen: usize,
+        last: DisplayFrame,
+    }
+
+    impl ChannelTap for Frames {
+        fn display_frame(&mut self, frame: &DisplayFrame) {
+            self.seen += 1;
+            self.last = frame.clone();
+        }
+    }
+
+    #[test]
+    fn relay_bursts_close_on_t4rupt() {
+        let mut frames = Frames::default();
+        {
+            let mut mem = MemoryMap::new_blank();
+            mem.add_tap(CHANNEL_DSKY, &mut frames);
+            mem.request_rupt(RuptRequest::T4rupt); // Nothing written yet
+            mem.write_io(CHANNEL_DSKY, 0o04001);
+            mem.write_io(CHANNEL_DSKY, 0o10002);
+            mem.write_io(CHANNEL_DSKY, 0o04003);
+            mem.tick_io(10);
+            mem.request_rupt(RuptRequest::T4rupt);
+        }
+        assert_eq!(frames.seen, 1);
+        assert_eq!(frames.last.words(), [0o04003, 0o10002]);
+        assert_eq!(frames.last.at, EmuTime::from_mcts(10));
+    }
+}
@@ -1,68 +1,274 @@
+use crate::checks;
 use crate::constants;
+use crate::logging::warn;
 use crate::memory::MemoryType;
-use crate::utils::Option;
-use log::warn;
+use core::cell::Cell;
+
+/// Emulated fixed-memory hardware fault for a whole bank
+#[derive(Clone, Copy, PartialEq, Debug)]
+#[non_exhaustive]
+pub enum BankFault {
+    Zeros,  // Bank unavailable: every word reads as zero
+    Parity, // Words read back but fail the parity check
+}
 
 #[allow(dead_code)]
 const DATA_LINE_NUM_PARTS: usize = 8;
 #[allow(dead_code)]
 const DATA_LINE_PART_LEN: usize = 6;
 
+/// Maximum number of patched fixed-memory words
+pub const MAX_OVERLAY_WORDS: usize = 64;
+
+/// Rope image layout: 36 segments of 1024 big-endian words with parity
+pub type RopeImage = [[u16; constants::STORAGE_SEGMENT_SIZE]; constants::STORAGE_SEGMENTS];
+
+/// Per-rope facts the hardware monitors depend on
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct RomInfo {
+    pub newjob: usize,          // Erasable address the night watchman expects accessed
+    pub modreg: Option<usize>,  // Unswitched erasable holding the major mode, if known
+    pub failreg: Option<usize>, // First of the three unswitched alarm code words, if known
+}
+
+impl RomInfo {
+    /// Block II flight configuration: NEWJOB at 67
+    pub const BLOCK_II: RomInfo = RomInfo {
+        newjob: 0o67,
+        modreg: None,
+        failreg: None,
+    };
+}
+
+impl Default for RomInfo {
+    fn default() -> Self {
+        Self::BLOCK_II
+    }
+}
+
+/// Fixed memory with a non-standard layout, e.g. a two-bank test ROM or a
+/// padded image. Banks are logical (FB numbering); bank 2 and 3 appear at
+/// 4000 and 6000.
+pub trait FixedMemory {
+    /// Banks provided; reads beyond them return zero
+    fn bank_count(&self) -> usize;
+
+    /// 15-bit word at a logical bank and offset
+    fn word(&self, bank: usize, offset: usize) -> u16;
+}
+
+/// Rope image segment holding each logical bank. The fixed-fixed banks,
+/// 2 and 3, come first, as in yaAGC's images; loaders and listings index
+/// images through this rather than by bank.
+pub const BANK_MAPPING: [usize; constants::STORAGE_SEGMENTS] = bank_mapping();
+
+/// Logical bank stored in image segment `segment`
+pub const fn segment_bank(segment: usize) -> usize {
+    match segment {
+        0 | 1 => segment + 2,
+        2 | 3 => segment - 2,
+        _ => segment,
+    }
+}
+
+const fn bank_mapping() -> [usize; constants::STORAGE_SEGMENTS] {
+    // Swapping the first two pairs is its own inverse
+    let mut mapping = [0; constants::STORAGE_SEGMENTS];
+    let mut bank = 0;
+    while bank < mapping.len() {
+        mapping[bank] = segment_bank(bank);
+        bank += 1;
+    }
+    mapping
+}
+
+const fn is_permutation(mapping: &[usize; constants::STORAGE_SEGMENTS]) -> bool {
+    let mut bank = 0;
+    while bank < mapping.len() {
+        if segment_bank(mapping[bank]) != bank {
+            return false;
+        }
+        bank += 1;
+    }
+    true
+}
+
+const _: () = assert!(is_permutation(&BANK_MAPPING));
+const _: () = assert!(BANK_MAPPING[2] == 0 && BANK_MAPPING[0o10] == 0o10);
+
+/// Image word for a 15-bit word: big-endian, shifted over the parity bit
+pub const fn encode_word(word: u16) -> u16 {
+    ((word & 0o77777) << 1).to_be()
+}
+
+/// 15-bit word stored in an image word, parity bit dropped
+pub const fn decode_word(stored: u16) -> u16 {
+    (u16::from_be(stored) >> 1) & 0o77777
+}
+
+const _: () = assert!(decode_word(encode_word(0o54321)) == 0o54321);
+
+#[inline]
+fn image_word(image: &RopeImage, bank: usize, offset: usize) -> u16 {
+    decode_word(image[BANK_MAPPING[bank]][offset])
+}
+
+impl FixedMemory for RopeImage {
+    fn bank_count(&self) -> usize {
+        constants::STORAGE_SEGMENTS
+    }
+
+    fn word(&self, bank: usize, offset: usize) -> u16 {
+        image_word(self, bank, offset)
+    }
+}
+
+/// Where the rope image lives
+enum RopeStorage<'a> {
+    Empty,
+    Borrowed(&'a RopeImage),
+    #[cfg(feature = "alloc")]
+    Owned(alloc::boxed::Box<RopeImage>), // Loaded or generated at run time
+    Custom(&'a dyn FixedMemory),
+}
+
 /// Struct representing read-only memory (ROM), typically used for fixed program storage
 pub struct ReadOnlyMemory<'a> {
-    // Optional reference to the ROM storage layout: 36 segments, each of fixed size
-    memory_banks: Option<&'a [[u16; constants::STORAGE_SEGMENT_SIZE]; constants::STORAGE_SEGMENTS]>,
+    memory_banks: RopeStorage<'a>,
+    faults: [core::option::Option<BankFault>; constants::STORAGE_SEGMENTS], // Injected faults
+    parity_alarm: Cell<bool>, // Set by a read from a parity-failing bank
+    missing_bank: Cell<core::option::Option<usize>>, // Set by a read beyond the rope
+    overlays: heapless::Vec<(usize, usize, u16), MAX_OVERLAY_WORDS>, // Patched words
 }
 
 impl<'a> MemoryType for ReadOnlyMemory<'a> {
     fn read(&self, memory_bank: usize, bank_address: usize) -> u16 {
-        // Bounds check for memory segment and address
-        if memory_bank >= constants::STORAGE_SEGMENTS
-            || bank_address >= constants::STORAGE_SEGMENT_SIZE
-        {
+        // A bank beyond the rope is the program's doing; an offset beyond
+        // the bank is ours
+        checks::offset("fixed", bank_address, constants::STORAGE_SEGMENT_SIZE);
+        if memory_bank >= self.bank_count() || bank_address >= constants::STORAGE_SEGMENT_SIZE {
+            if memory_bank >= self.bank_count() {
+                self.missing_bank.set(Some(memory_bank));
+            }
             return 0x0;
         }
 
-        match self.memory_banks {
-            Option::Value(memory_data) => {
-                // BANK_MAPPING maps logical bank numbers to physical segment indices
-                const BANK_MAPPING: [usize; 36] = [
-                    2, 3, 0, 1, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 17, 18, 19, 20, 21,
-                    22, 23, 24, 25, 26, 27, 28, 29, 30, 31, 32, 33, 34, 35,
-                ];
-                // Data is stored in big-endian;
-                // shift right to drop unused parity bit and mask to 15 bits
-                (u16::from_be(memory_data[BANK_MAPPING[memory_bank]][bank_address]) >> 1) & 0x7FFF
-            }
-            _ => 0,
+        match self.faults.get(memory_bank).copied().flatten() {
+            Some(BankFault::Zeros) => return 0,
+            Some(BankFault::Parity) => self.parity_alarm.set(true),
+            None => {}
+        }
+
+        let patched = self
+            .overlays
+            .iter()
+            .find(|(bank, addr, _)| *bank == memory_bank && *addr == bank_address);
+        if let Some((_, _, value)) = patched {
+            return *value;
         }
+        self.stored_word(memory_bank, bank_address)
     }
 
-    fn write(&mut self, memory_bank: usize, bank_address: usize, _data_value: u16) {
+    fn write(&mut self, memory_bank: usize, bank_address: usize, data_value: u16) {
         // ROM is read-only; log a warning and do nothing on write
         if memory_bank >= constants::STORAGE_SEGMENTS
             || bank_address >= constants::STORAGE_SEGMENT_SIZE
         {
             return;
         }
-        warn!("Write operation is not allowed on ROM");
+        warn!(
+            "Ignored write of {:05o} to fixed memory {:02o},{:04o}",
+            data_value,
+            memory_bank,
+            0o2000 + bank_address
+        );
     }
 }
 
 impl<'a> ReadOnlyMemory<'a> {
     /// Creates a new ReadOnlyMemory with a reference to actual ROM storage
-    pub fn new(
-        storage: &'a [[u16; constants::STORAGE_SEGMENT_SIZE]; constants::STORAGE_SEGMENTS],
-    ) -> Self {
-        Self {
-            memory_banks: Option::Value(storage),
-        }
+    pub fn new(storage: &'a RopeImage) -> Self {
+        Self::with_storage(RopeStorage::Borrowed(storage))
+    }
+
+    /// Creates a ReadOnlyMemory owning its rope image
+    #[cfg(feature = "alloc")]
+    pub fn owned(storage: alloc::boxed::Box<RopeImage>) -> Self {
+        Self::with_storage(RopeStorage::Owned(storage))
+    }
+
+    /// Creates a ReadOnlyMemory over a non-standard fixed memory layout
+    pub fn custom(storage: &'a dyn FixedMemory) -> Self {
+        Self::with_storage(RopeStorage::Custom(storage))
     }
 
     /// Creates an empty ReadOnlyMemory instance (no data attached)
     pub fn empty() -> Self {
+        Self::with_storage(RopeStorage::Empty)
+    }
+
+    fn with_storage(memory_banks: RopeStorage<'a>) -> Self {
         Self {
-            memory_banks: Option::Empty,
+            memory_banks,
+            faults: [None; constants::STORAGE_SEGMENTS],
+            parity_alarm: Cell::new(false),
+            missing_bank: Cell::new(None),
+            overlays: heapless::Vec::new(),
         }
     }
+
+    /// Inject (or clear, with None) a hardware fault on a fixed bank
+    pub fn set_bank_fault(&mut self, bank: usize, fault: core::option::Option<BankFault>) {
+        match self.faults.get_mut(bank) {
+            Some(slot) => *slot = fault,
+            None => warn!("No fixed bank {:o} to fault", bank),
+        }
+    }
+
+    /// Patch consecutive fixed-memory words, e.g. to fix or instrument a rope
+    pub fn overlay(&mut self, bank: usize, offset: usize, values: &[u16]) -> bool {
+        for (i, value) in values.iter().enumerate() {
+            let word = (bank, offset + i, value & 0x7FFF);
+            if self.overlays.push(word).is_err() {
+                warn!("Fixed-memory overlay full at {:o},{:04o}", bank, offset + i);
+                return false;
+            }
+        }
+        true
+    }
+
+    /// Number of logical fixed banks
+    pub fn bank_count(&self) -> usize {
+        match &self.memory_banks {
+            RopeStorage::Custom(fixed) => fixed.bank_count(),
+            _ => constants::STORAGE_SEGMENTS,
+        }
+    }
+
+    /// Word as wired into the rope, without injected faults or overlays
+    pub fn stored_word(&self, bank: usize, offset: usize) -> u16 {
+        // Standard images are decoded inline; other layouts go through the trait
+        match &self.memory_banks {
+            RopeStorage::Empty => 0,
+            RopeStorage::Borrowed(image) => image_word(image, bank, offset),
+            #[cfg(feature = "alloc")]
+            RopeStorage::Owned(image) => image_word(image, bank, offset),
+            RopeStorage::Custom(fixed) => fixed.word(bank, offset) & 0x7FFF,
+        }
+    }
+
+    /// Word at a logical fixed bank and offset, as the CPU would read it
+    pub fn word(&self, bank: usize, offset: usize) -> u16 {
+        self.read(bank, offset)
+    }
+
+    /// Returns whether a parity failure occurred since the last call
+    pub fn take_parity_alarm(&self) -> bool {
+        self.parity_alarm.replace(false)
+    }
+
+    /// The fixed bank last read beyond the rope, since the last call
+    pub fn take_missing_bank(&self) -> core::option::Option<usize> {
+        self.missing_bank.take()
+    }
 }
@@ -0,0 +1,280 @@
+//! The CPU's view of memory: which bank or register an address reaches
+//! under a given bank selection. The memory map, the disassembler and the
+//! debugger's address notation all decode addresses here, so they agree on
+//! where the windows are and how the superbank moves them.
+use crate::constants::address_space::*;
+use crate::constants::ports::{CHANNEL_L, CHANNEL_Q, SUPERBNK_EXTEND};
+use crate::constants::{MEMORY_SEGMENTS, STORAGE_SEGMENTS};
+use crate::hooks::FixedAddress;
+use crate::symbols::ErasableAddress;
+use core::fmt;
+
+/// Physical location behind a CPU address
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Location {
+    Central(usize), // Registers, edit registers, counters and special cells
+    Erasable(ErasableAddress),
+    Fixed(FixedAddress), // Logical bank, fixed-fixed as banks 2 and 3
+}
+
+/// What kind of storage an address reaches
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Region {
+    Special,         // Registers and counters in 0000-0060, or channels L and Q
+    Erasable(usize), // Erasable bank: E0-E2 unswitched, or EB's at 1400-1777
+    Banked(usize),   // Fixed bank switched in at 2000-3777
+    FixedFixed,      // Banks 2 and 3 at 4000-7777
+    Channel,         // I/O channel
+}
+
+/// Fixed bank that FB selects with channel 7 holding `superbank`: banks
+/// 40-47 stand in for FB 30-37 while the extension bit is set
+pub const fn superbank_fixed_bank(fb: usize, superbank: u16) -> usize {
+    match fb {
+        0o30..=0o37 if superbank & SUPERBNK_EXTEND != 0 => fb + 0o10,
+        _ => fb,
+    }
+}
+
+/// Address decoding under a bank selection. A bank left unknown, as when
+/// reading a listing rather than a running machine, leaves its window
+/// undecoded.
+#[derive(Clone, Copy, PartialEq, Eq, Default, Debug)]
+pub struct AddressSpace {
+    pub eb: Option<usize>,    // Erasable bank behind 1400-1777
+    pub fixed: Option<usize>, // Fixed bank behind 2000-3777, superbank applied
+}
+
+impl AddressSpace {
+    /// Only the unswitched addresses decode
+    pub const UNBANKED: Self = Self {
+        eb: None,
+        fixed: None,
+    };
+
+    /// Banks as EB, FB and channel 7 select them
+    pub const fn new(eb: usize, fb: usize, superbank: u16) -> Self {
+        Self {
+            eb: Some(eb),
+            fixed: Some(superbank_fixed_bank(fb, superbank)),
+        }
+    }
+
+    /// As seen by code in fixed bank `bank`, assuming its switched-fixed
+    /// references stay in that bank. Fixed-fixed code leaves FB unknown.
+    pub const fn running_in(bank: usize) -> Self {
+        Self {
+            eb: None,
+            fixed: match fixed_fixed_address(bank, 0) {
+                Some(_) => None,
+                None => Some(bank),
+            },
+        }
+    }
+
+    /// Only the switched-fixed window, as an FB or BB word (or the bank
+    /// half of a 2CADR) selects it, without the superbank
+    pub const fn from_fb(word: u16) -> Self {
+        Self {
+            eb: None,
+            fixed: Some(((word >> 10) & 0o37) as usize),
+        }
+    }
+
+    /// Address the S register takes from an instruction or address word
+    pub const fn s_register(word: u16) -> usize {
+        (word & 0o7777) as usize
+    }
+
+    pub const fn classify(&self, addr: usize) -> Option<Region> {
+        let region = match addr {
+            0..=CENTRAL_END => Region::Special,
+            VOLATILE_START..SWITCHED_ERASABLE_START => {
+                Region::Erasable(ErasableAddress::from_flat(addr).bank)
+            }
+            SWITCHED_ERASABLE_START..=VOLATILE_END => match self.eb {
+                Some(eb) => Region::Erasable(eb),
+                None => return None,
+            },
+            SWITCHED_FIXED_START..=SWITCHED_FIXED_END => match self.fixed {
+                Some(bank) => Region::Banked(bank),
+                None => return None,
+            },
+            FIXED_FIXED_START..=PERSISTENT_END => Region::FixedFixed,
+            _ => return None,
+        };
+        Some(region)
+    }
+
+    /// Region of an I/O channel: L and Q are the registers themselves
+    pub const fn classify_channel(channel: usize) -> Option<Region> {
+        match channel {
+            CHANNEL_L | CHANNEL_Q => Some(Region::Special),
+            0..=0o777 => Some(Region::Channel),
+            _ => None,
+        }
+    }
+
+    pub const fn locate(&self, addr: usize) -> Option<Location> {
+        let location = match self.classify(addr) {
+            Some(Region::Special) => Location::Central(addr),
+            Some(Region::Erasable(bank)) => {
+                Location::Erasable(ErasableAddress::new(bank, addr % ERASABLE_BANK_SIZE))
+            }
+            Some(Region::Banked(bank)) => {
+                Location::Fixed(FixedAddress::new(bank, addr % FIXED_BANK_SIZE))
+            }
+            Some(Region::FixedFixed) => Location::Fixed(FixedAddress::new(
+                fixed_fixed_bank(addr),
+                addr % FIXED_BANK_SIZE,
+            )),
+            Some(Region::Channel) | None => return None,
+        };
+        Some(location)
+    }
+
+    /// CPU address reaching `at`, if the banks make it visible
+    pub const fn address(&self, at: Location) -> Option<usize> {
+        match at {
+            Location::Central(idx) => Some(idx),
+            Location::Erasable(ErasableAddress { bank, offset }) => match self.eb {
+                _ if bank < 3 => Some(bank * ERASABLE_BANK_SIZE + offset),
+                Some(eb) if eb == bank => Some(SWITCHED_ERASABLE_START + offset),
+                _ => None,
+            },
+            Location::Fixed(FixedAddress { bank, offset }) => {
+                match (fixed_fixed_address(bank, offset), self.fixed) {
+                    (Some(addr), _) => Some(addr),
+                    (None, Some(fixed)) if fixed == bank => Some(SWITCHED_FIXED_START + offset),
+                    _ => None,
+                }
+            }
+        }
+    }
+
+    /// Decode the notation `Location` displays: `E<bank>,AAAA` for
+    /// erasable, `BB,AAAA` for switched fixed, or a plain octal address
+    /// decoded with these banks
+    pub fn parse(&self, text: &str) -> Option<Location> {
+        let text = text.trim();
+        let (bank, addr) = match text.split_once(',') {
+            Some(banked) => banked,
+            None => return self.locate(usize::from_str_radix(text, 8).ok()?),
+        };
+        let addr = usize::from_str_radix(addr.trim(), 8).ok()?;
+        let location = match bank.trim().strip_prefix('E') {
+            Some(eb) => {
+                let eb = eb
+                    .parse::<usize>()
+                    .ok()
+                    .filter(|&eb| eb < MEMORY_SEGMENTS)?;
+                let space = Self {
+                    eb: Some(eb),
+                    ..Self::UNBANKED
+                };
+                match space.classify(addr)? {
+                    Region::Erasable(bank) if bank == eb => space.locate(addr)?,
+                    Region::Special if eb == 0 => Location::Central(addr),
+                    _ => return None,
+                }
+            }
+            None => {
+                let fixed = usize::from_str_radix(bank.trim(), 8).ok()?;
+                match (
+                    Self::running_in(fixed).classify(addr)?,
+                    fixed < STORAGE_SEGMENTS,
+                ) {
+                    (Region::Banked(_), true) => {
+                        Location::Fixed(FixedAddress::new(fixed, addr - SWITCHED_FIXED_START))
+                    }
+                    _ => return None,
+                }
+            }
+        };
+        Some(location)
+    }
+}
+
+/// As the listings write it: erasable banks E3 and up by their window,
+/// switched-fixed banks by bank number and window
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match AddressSpace::UNBANKED.address(*self) {
+            Some(addr) => write!(f, "{:04o}", addr),
+            None => match *self {
+                Location::Erasable(at) => {
+                    write!(
+                        f,
+                        "E{},{:04o}",
+                        at.bank,
+                        SWITCHED_ERASABLE_START + at.offset
+                    )
+                }
+                Location::Fixed(at) => {
+                    write!(
+                        f,
+                        "{:02o},{:04o}",
+                        at.bank,
+                        SWITCHED_FIXED_START + at.offset
+                    )
+                }
+                Location::Central(idx) => write!(f, "{:04o}", idx),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod space_tests {
+    use super::{AddressSpace, Location, Region};
+    use crate::hooks::FixedAddress;
+    use crate::symbols::ErasableAddress;
+    use core::fmt::Write;
+
+    #[test]
+    fn decodes_and_notates_addresses() {
+        let space = AddressSpace::new(5, 0o33, 0o100);
+        assert_eq!(space.fixed, Some(0o43));
+        let cases = [
+            (0o0007, Region::Special, "0007"),
+            (0o0100, Region::Erasable(0), "0100"),
+            (0o1377, Region::Erasable(2), "1377"),
+            (0o1402, Region::Erasable(5), "E5,1402"),
+            (0o2001, Region::Banked(0o43), "43,2001"),
+            (0o4000, Region::FixedFixed, "4000"),
+            (0o7777, Region::FixedFixed, "7777"),
+        ];
+        for (addr, region, text) in cases {
+            assert_eq!(space.classify(addr), Some(region), "{:o}", addr);
+            let at = space.locate(addr).unwrap();
+            assert_eq!(space.address(at), Some(addr));
+            let mut notation = heapless::String::<8>::new();
+            write!(notation, "{}", at).unwrap();
+            assert_eq!(notation.as_str(), text);
+            assert_eq!(AddressSpace::UNBANKED.parse(text), Some(at), "{}", text);
+        }
+        assert_eq!(AddressSpace::s_register(0o54123), 0o4123);
+        assert_eq!(AddressSpace::from_fb(0o54003).fixed, Some(0o26));
+        assert_eq!(space.classify(0o10000), None);
+
+        // Unknown banks leave their windows undecoded
+        let listing = AddressSpace::running_in(2);
+        assert_eq!(listing.locate(0o2000), None);
+        assert_eq!(listing.parse("1400"), None);
+        assert_eq!(
+            AddressSpace::running_in(0o17).locate(0o2345),
+            Some(Location::Fixed(FixedAddress::new(0o17, 0o345)))
+        );
+        assert_eq!(
+            AddressSpace::UNBANKED.parse("E1,0500"),
+            Some(Location::Erasable(ErasableAddress::new(1, 0o100)))
+        );
+        for bad in [
+            "E1,0100", "E8,1400", "02,2000", "44,2000", "17,4000", "E0,x",
+        ] {
+            assert_eq!(AddressSpace::UNBANKED.parse(bad), None, "{}", bad);
+        }
+        assert_eq!(AddressSpace::classify_channel(0o2), Some(Region::Special));
+        assert_eq!(AddressSpace::classify_channel(0o13), Some(Region::Channel));
+    }
+}
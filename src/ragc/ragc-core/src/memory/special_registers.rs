@@ -1,22 +1,63 @@
 use crate::constants::special_registers::*;
+use crate::logging::{error, warn};
 use crate::memory::MemoryType;
-use heapless::spsc::Producer;
-use log::{error, warn};
 
 #[derive(Clone)]
 pub struct SpecialRegisters {
     pub control_display: (u16, u16, u16),
     pub optical_sensors: (u16, u16),
     pub inertial_platform: (u16, u16, u16),
+    pub hand_controller: (u16, u16, u16), // RHC pitch, yaw, roll (LM)
+    pub data_input: u16,                  // INLINK uplink word
+    pub nav_radar: u16,                   // RNRAD, shifted in by the radar interface
+    pub commands: [u16; COMMAND_COUNT],   // Output counters CDUXCMD-ALTM
 }
 
+/// Output counters 50-60, which software loads and the hardware drains
+const COMMAND_COUNT: usize = SPECIAL_REGISTER_ALTITUDE - SPECIAL_REGISTER_CONTROL_X_CMD + 1;
+
 impl SpecialRegisters {
     /// Initializes all special registers to 0
-    pub fn new(_interrupt_tx: Producer<u8, 8>) -> Self {
+    pub fn new() -> Self {
         Self {
             control_display: (0, 0, 0),
             optical_sensors: (0, 0),
             inertial_platform: (0, 0, 0),
+            hand_controller: (0, 0, 0),
+            data_input: 0,
+            nav_radar: 0,
+            commands: [0; COMMAND_COUNT],
+        }
+    }
+
+    /// Take the pulses left in an output counter, as its drive hardware does
+    pub fn drain_command(&mut self, register_address: usize) -> u16 {
+        match register_address {
+            SPECIAL_REGISTER_CONTROL_X_CMD..=SPECIAL_REGISTER_ALTITUDE => core::mem::take(
+                &mut self.commands[register_address - SPECIAL_REGISTER_CONTROL_X_CMD],
+            ),
+            _ => 0,
+        }
+    }
+
+    /// Update an input counter driven by hardware pulses
+    pub fn set_counter(&mut self, register_address: usize, value: u16) {
+        let value = value & 0o77777;
+        match register_address {
+            SPECIAL_REGISTER_CONTROL_DISPLAY_X => self.control_display.0 = value,
+            SPECIAL_REGISTER_CONTROL_DISPLAY_Y => self.control_display.1 = value,
+            SPECIAL_REGISTER_CONTROL_DISPLAY_Z => self.control_display.2 = value,
+            SPECIAL_REGISTER_OPTICAL_X => self.optical_sensors.0 = value,
+            SPECIAL_REGISTER_OPTICAL_Y => self.optical_sensors.1 = value,
+            SPECIAL_REGISTER_INERTIAL_X => self.inertial_platform.0 = value,
+            SPECIAL_REGISTER_INERTIAL_Y => self.inertial_platform.1 = value,
+            SPECIAL_REGISTER_INERTIAL_Z => self.inertial_platform.2 = value,
+            SPECIAL_REGISTER_RHC_PITCH => self.hand_controller.0 = value,
+            SPECIAL_REGISTER_RHC_YAW => self.hand_controller.1 = value,
+            SPECIAL_REGISTER_RHC_ROLL => self.hand_controller.2 = value,
+            SPECIAL_REGISTER_DATA_INPUT => self.data_input = value,
+            SPECIAL_REGISTER_NAV_RADAR => self.nav_radar = value,
+            _ => warn!("Unsupported counter update: 0o{:o}", register_address),
         }
     }
 
@@ -43,11 +84,15 @@ impl MemoryType for SpecialRegisters {
             SPECIAL_REGISTER_INERTIAL_X => self.inertial_platform.0,
             SPECIAL_REGISTER_INERTIAL_Y => self.inertial_platform.1,
             SPECIAL_REGISTER_INERTIAL_Z => self.inertial_platform.2,
+            SPECIAL_REGISTER_RHC_PITCH => self.hand_controller.0,
+            SPECIAL_REGISTER_RHC_YAW => self.hand_controller.1,
+            SPECIAL_REGISTER_RHC_ROLL => self.hand_controller.2,
+            SPECIAL_REGISTER_DATA_INPUT => self.data_input,
+            SPECIAL_REGISTER_NAV_RADAR => self.nav_radar,
 
-            // Command registers are placeholders; currently return 0
-            SPECIAL_REGISTER_CONTROL_X_CMD
-            | SPECIAL_REGISTER_CONTROL_Y_CMD
-            | SPECIAL_REGISTER_CONTROL_Z_CMD => 0,
+            SPECIAL_REGISTER_CONTROL_X_CMD..=SPECIAL_REGISTER_ALTITUDE => {
+                self.commands[register_address - SPECIAL_REGISTER_CONTROL_X_CMD]
+            }
 
             // Any unrecognized address logs an error
             _ => {
@@ -60,8 +105,12 @@ impl MemoryType for SpecialRegisters {
         }
     }
 
-    fn write(&mut self, _memory_bank: usize, register_address: usize, _data: u16) {
+    fn write(&mut self, _memory_bank: usize, register_address: usize, data: u16) {
         match register_address {
+            SPECIAL_REGISTER_CONTROL_X_CMD..=SPECIAL_REGISTER_ALTITUDE => {
+                self.commands[register_address - SPECIAL_REGISTER_CONTROL_X_CMD] = data & 0o77777;
+            }
+
             // Writes to read-only registers are logged with a warning
             SPECIAL_REGISTER_CONTROL_DISPLAY_X
             | SPECIAL_REGISTER_CONTROL_DISPLAY_Y
@@ -70,7 +119,11 @@ impl MemoryType for SpecialRegisters {
             | SPECIAL_REGISTER_OPTICAL_Y
             | SPECIAL_REGISTER_INERTIAL_X
             | SPECIAL_REGISTER_INERTIAL_Y
-            | SPECIAL_REGISTER_INERTIAL_Z => {
+            | SPECIAL_REGISTER_INERTIAL_Z
+            | SPECIAL_REGISTER_RHC_PITCH
+            | SPECIAL_REGISTER_RHC_YAW
+            | SPECIAL_REGISTER_RHC_ROLL
+            | SPECIAL_REGISTER_NAV_RADAR => {
                 warn!("Write attempt to read-only: 0o{:o}", register_address);
             }
 
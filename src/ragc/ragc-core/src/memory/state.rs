@@ -0,0 +1,137 @@
+//! Peripheral state carried in snapshots. Channel values alone don't bring
+//! a peripheral back: the DSKY's relay latches, the downlink word pairing
+//! and the ISS turn-on sequence live inside the devices. Each keeps its
+//! state as bytes of its own layout under a tag; a restore hands the same
+//! bytes back, and the tag tells which device they belong to.
+
+/// Room each peripheral has for its state
+pub const PERIPH_STATE_MAX: usize = 1024;
+
+/// State a peripheral keeps across a snapshot and restore
+pub trait PeriphState {
+    /// Names the state in a snapshot; distinct for each kind of peripheral
+    fn state_tag(&self) -> [u8; 4];
+
+    /// Write the state into `out`, returning its length, or None if it
+    /// doesn't fit
+    fn save_state(&self, out: &mut [u8]) -> Option<usize>;
+
+    /// Take back what `save_state` wrote
+    fn restore_state(&mut self, data: &[u8]) -> Result<(), &'static str>;
+}
+
+/// Fills a state buffer, little-endian
+pub struct StateWriter<'a> {
+    out: &'a mut [u8],
+    len: usize,
+    overflowed: bool,
+}
+
+impl<'a> StateWriter<'a> {
+    pub fn new(out: &'a mut [u8]) -> Self {
+        Self {
+            out,
+            len: 0,
+            overflowed: false,
+        }
+    }
+
+    pub fn bytes(&mut self, bytes: &[u8]) -> &mut Self {
+        match self.out.get_mut(self.len..self.len + bytes.len()) {
+            Some(dst) => {
+                dst.copy_from_slice(bytes);
+                self.len += bytes.len();
+            }
+            None => self.overflowed = true,
+        }
+        self
+    }
+
+    pub fn u8(&mut self, value: u8) -> &mut Self {
+        self.bytes(&[value])
+    }
+
+    pub fn u16(&mut self, value: u16) -> &mut Self {
+        self.bytes(&value.to_le_bytes())
+    }
+
+    pub fn u32(&mut self, value: u32) -> &mut Self {
+        self.bytes(&value.to_le_bytes())
+    }
+
+    /// Length written, None if the buffer ran out
+    pub fn finish(&self) -> Option<usize> {
+        match self.overflowed {
+            true => None,
+            false => Some(self.len),
+        }
+    }
+}
+
+/// Reads back what a `StateWriter` wrote
+pub struct StateReader<'a>(&'a [u8]);
+
+impl<'a> StateReader<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        Self(data)
+    }
+
+    pub fn bytes(&mut self, n: usize) -> Result<&'a [u8], &'static str> {
+        if self.0.len() < n {
+            return Err("peripheral state is truncated");
+        }
+        let (head, rest) = self.0.split_at(n);
+        self.0 = rest;
+        Ok(head)
+    }
+
+    pub fn u8(&mut self) -> Result<u8, &'static str> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    pub fn u16(&mut self) -> Result<u16, &'static str> {
+        let b = self.bytes(2)?;
+        Ok(u16::from_le_bytes([b[0], b[1]]))
+    }
+
+    pub fn u32(&mut self) -> Result<u32, &'static str> {
+        let b = self.bytes(4)?;
+        Ok(u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+    }
+}
+
+#[cfg(test)]
+mod state_tests {
+    use crate::constants::ports;
+    use crate::memory::channels::Discrete;
+    use crate::memory::MemoryMap;
+
+    #[test]
+    fn channel_state_survives_a_restore() {
+        let mut mem = MemoryMap::new_blank();
+        mem.write_io(ports::CHANNEL_DSALMOUT, 0o20);
+        mem.write_io(ports::CHANNEL_CHAN13, ports::CHAN13_TEST_ALARMS);
+        mem.write_io(ports::CHANNEL_CHAN34, 0o1234); // First word of a pair
+        mem.set_discrete(Discrete::ImuOperate, true);
+        mem.tick_io(100);
+
+        let mut saved = heapless::Vec::<([u8; 4], heapless::Vec<u8, 1024>), 2>::new();
+        mem.save_periph_states(|tag, data| {
+            saved
+                .push((tag, heapless::Vec::from_slice(data).unwrap()))
+                .unwrap();
+        });
+        assert_eq!(saved.len(), 1); // No peripherals attached
+
+        let mut fresh = MemoryMap::new_blank();
+        for (tag, data) in saved.iter() {
+            assert_eq!(fresh.restore_periph_state(*tag, data), Ok(true));
+        }
+        for channel in [ports::CHANNEL_DSALMOUT, ports::CHANNEL_DSKY_LIGHTS] {
+            assert_eq!(fresh.peek_channel(channel), mem.peek_channel(channel));
+        }
+        assert!(fresh.lamp_test() && fresh.iss().is_requesting());
+        assert_eq!(fresh.restore_periph_state(*b"NONE", &[]), Ok(false));
+        assert!(fresh.restore_periph_state(*b"CHAN", &[0; 8]).is_err());
+    }
+}
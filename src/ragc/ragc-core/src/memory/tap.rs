@@ -0,0 +1,117 @@
+use super::relay::DisplayFrame;
+
+/// Outcome of a channel tap for a single value
+#[derive(Clone, Copy, PartialEq, Debug)]
+#[non_exhaustive]
+pub enum TapAction {
+    Pass(u16),       // Forward the (possibly modified) value
+    Delay(u16, u32), // Deliver the value after the given number of MCTs
+    Drop,            // Discard the value
+}
+
+/// Middleware observing or altering channel traffic
+///
+/// Taps run in registration order. On reads a delay has no meaning and is
+/// treated as a pass, while a dropped read returns zero.
+pub trait ChannelTap {
+    fn read(&mut self, _channel: usize, value: u16) -> TapAction {
+        TapAction::Pass(value)
+    }
+
+    fn write(&mut self, _channel: usize, value: u16) -> TapAction {
+        TapAction::Pass(value)
+    }
+
+    /// A completed display frame, for taps on the relay channel
+    fn display_frame(&mut self, _frame: &DisplayFrame) {}
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum TapDirection {
+    Read,
+    Write,
+    Both,
+}
+
+/// Canned faults for the generic fault tap
+#[derive(Clone, Copy, PartialEq, Debug)]
+#[non_exhaustive]
+pub enum Fault {
+    Xor(u16),   // Flip bits
+    Or(u16),    // Bits stuck at one
+    And(u16),   // Only these bits survive (others stuck at zero)
+    Stuck(u16), // Whole word stuck at a value
+    Drop,
+    Delay(u32), // MCTs
+}
+
+/// Ready-made tap applying a single fault to traffic in one direction
+pub struct FaultTap {
+    pub direction: TapDirection,
+    pub fault: Fault,
+    pub hits: u32, // Values affected so far
+}
+
+impl FaultTap {
+    pub fn new(direction: TapDirection, fault: Fault) -> Self {
+        Self {
+            direction,
+            fault,
+            hits: 0,
+        }
+    }
+
+    fn apply(&mut self, value: u16) -> TapAction {
+        self.hits += 1;
+        match self.fault {
+            Fault::Xor(mask) => TapAction::Pass(value ^ mask),
+            Fault::Or(mask) => TapAction::Pass(value | mask),
+            Fault::And(mask) => TapAction::Pass(value & mask),
+            Fault::Stuck(stuck) => TapAction::Pass(stuck),
+            Fault::Drop => TapAction::Drop,
+            Fault::Delay(cycles) => TapAction::Delay(value, cycles),
+        }
+    }
+}
+
+impl ChannelTap for FaultTap {
+    fn read(&mut self, _channel: usize, value: u16) -> TapAction {
+        match self.direction {
+            TapDirection::Read | TapDirection::Both => self.apply(value),
+            TapDirection::Write => TapAction::Pass(value),
+        }
+    }
+
+    fn write(&mut self, _channel: usize, value: u16) -> TapAction {
+        match self.direction {
+            TapDirection::Write | TapDirection::Both => self.apply(value),
+            TapDirection::Read => TapAction::Pass(value),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tap_tests {
+    use super::{Fault, FaultTap, TapDirection};
+    use crate::constants::ports::{CHANNEL_PYJETS, CHANNEL_ROLLJETS};
+    use crate::memory::MemoryMap;
+
+    #[test]
+    fn taps_modify_delay_and_drop_writes() {
+        let mut flip = FaultTap::new(TapDirection::Write, Fault::Xor(0o1));
+        let mut late = FaultTap::new(TapDirection::Write, Fault::Delay(10));
+        let mut mem = MemoryMap::new_blank();
+        assert!(mem.add_tap(CHANNEL_PYJETS, &mut flip));
+        assert!(mem.add_tap(CHANNEL_ROLLJETS, &mut late));
+
+        mem.write_io(CHANNEL_PYJETS, 0o3);
+        assert_eq!(mem.read_io(CHANNEL_PYJETS), 0o2);
+
+        mem.write_io(CHANNEL_ROLLJETS, 0o7);
+        assert_eq!(mem.read_io(CHANNEL_ROLLJETS), 0);
+        mem.tick_io(9);
+        assert_eq!(mem.read_io(CHANNEL_ROLLJETS), 0);
+        mem.tick_io(1);
+        assert_eq!(mem.read_io(CHANNEL_ROLLJETS), 0o7);
+    }
+}
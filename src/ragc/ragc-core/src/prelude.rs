@@ -0,0 +1,27 @@
+//! Stable surface for frontends: `use ragc_core::prelude::*;`
+//!
+//! Items re-exported here follow semver. Anything reached through other
+//! paths may move between minor releases.
+
+pub use crate::constants::{ports, registers, special_registers};
+pub use crate::cpu::{Cpu, CpuFault, FaultPolicy, RestartCause, StepResult, UnprogSequence};
+pub use crate::frame::FrameIo;
+pub use crate::hooks::{FixedAddress, Hook, HookContext};
+pub use crate::instructions::Mnemonic;
+pub use crate::memory::channels::{
+    channel_bits, channel_spec, BitField, ChannelSpec, Discrete, CHANNELS, DISCRETES,
+};
+pub use crate::memory::downlink::DownlinkPair;
+pub use crate::memory::mods::{
+    EmuTime, InterruptSource, IoPeriph, RuptRequest, RuptRequests, MCT_SECONDS,
+};
+pub use crate::memory::relay::DisplayFrame;
+pub use crate::memory::rom::{BankFault, FixedMemory, RomInfo, RopeImage};
+pub use crate::memory::tap::{ChannelTap, TapAction};
+pub use crate::memory::{
+    AccessStats, AddressSpace, Location, MemoryMap, MemoryMapBuilder, Region, Strictness,
+};
+pub use crate::protect::{ProtectHit, ProtectedRange};
+pub use crate::rng::Rng;
+pub use crate::stats::InstructionStats;
+pub use crate::word::{SignedAgc, Word15, Word16};
@@ -0,0 +1,89 @@
+//! Erasable write protection for hunting memory corruption: words the
+//! program shouldn't be writing, with every write to them trapped along
+//! with the instruction that made it.
+use crate::symbols::ErasableAddress;
+
+/// Protected ranges a CPU can hold
+pub const MAX_PROTECTED: usize = 8;
+
+/// `len` consecutive words of one erasable bank
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct ProtectedRange {
+    pub start: ErasableAddress,
+    pub len: usize,
+    pub suppress: bool, // Drop trapped writes instead of letting them through
+}
+
+impl ProtectedRange {
+    pub fn contains(&self, addr: ErasableAddress) -> bool {
+        addr.bank == self.start.bank
+            && (self.start.offset..self.start.offset + self.len).contains(&addr.offset)
+    }
+}
+
+/// A program write to a protected word
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct ProtectHit {
+    pub pc: u16, // Address of the writing instruction
+    pub addr: ErasableAddress,
+    pub value: u16,
+    pub suppressed: bool, // The word kept its old value
+}
+
+#[cfg(test)]
+mod protect_tests {
+    use super::ProtectedRange;
+    use crate::symbols::ErasableAddress;
+    use crate::test_rom::{Op::*, TestRom};
+
+    #[test]
+    fn writes_to_protected_words_are_trapped() {
+        // Store A into E0,1000 then E4,1402, through the EB window
+        let rom = TestRom::new().emit(&[
+            CA(0o4020),
+            XCH(0o1000),
+            CA(0o4021),
+            XCH(0o3),
+            CA(0o4020),
+            XCH(0o1402),
+            Loop,
+        ]);
+        let rom = rom.at(0o4020).emit(&[Word(0o12345), Word(0o2000)]);
+        let mut cpu = rom.cpu();
+        assert!(cpu.protect(ProtectedRange {
+            start: ErasableAddress::new(4, 0o1),
+            len: 2,
+            suppress: true,
+        }));
+        assert!(cpu.protect(ProtectedRange {
+            start: ErasableAddress::from_flat(0o1000),
+            len: 1,
+            suppress: false,
+        }));
+        cpu.poke(0o1000, 0o7); // The debugger itself is exempt
+        assert_eq!(cpu.take_protect_hit(), None);
+
+        for _ in 0..3 {
+            cpu.step();
+        }
+        let hit = cpu.take_protect_hit().unwrap();
+        assert_eq!(hit.addr, ErasableAddress::from_flat(0o1000));
+        assert_eq!(
+            (hit.pc, hit.value, hit.suppressed),
+            (0o4001, 0o12345, false)
+        );
+        assert_eq!(cpu.memory().read(0o1000), 0o12345);
+
+        for _ in 0..6 {
+            cpu.step();
+        }
+        let hit = cpu.take_protect_hit().unwrap();
+        assert_eq!(hit.addr, ErasableAddress::new(4, 0o2));
+        assert!(hit.suppressed);
+        assert_eq!(cpu.memory().read(0o1402), 0);
+
+        cpu.unprotect_all();
+        cpu.write(0o1402, 1);
+        assert_eq!(cpu.take_protect_hit(), None);
+    }
+}
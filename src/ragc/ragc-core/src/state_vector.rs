@@ -0,0 +1,121 @@
+use crate::memory::MemoryMap;
+use crate::symbols::{ErasableAddress, SymbolTable};
+use crate::word::Word15;
+
+/// Sphere of influence the state vector is integrated in; selects its scaling
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Sphere {
+    Earth,
+    Moon,
+}
+
+impl Sphere {
+    /// Scale of RN in meters (2^29 m Earth, 2^27 m Moon)
+    fn position_scale(&self) -> f64 {
+        match self {
+            Sphere::Earth => (1u64 << 29) as f64,
+            Sphere::Moon => (1u64 << 27) as f64,
+        }
+    }
+
+    /// Scale of VN in meters per second (2^7 m/cs Earth, 2^5 m/cs Moon)
+    fn velocity_scale(&self) -> f64 {
+        match self {
+            Sphere::Earth => (1u64 << 7) as f64 * 100.0,
+            Sphere::Moon => (1u64 << 5) as f64 * 100.0,
+        }
+    }
+}
+
+/// Scale of the state vector time tag in seconds (2^28 cs)
+const TIME_SCALE: f64 = (1u64 << 28) as f64 / 100.0;
+
+/// Erasable locations of the integrated state vector for the loaded rope
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct StateVectorLayout {
+    pub rn: ErasableAddress,   // Position vector, 3 DP words
+    pub vn: ErasableAddress,   // Velocity vector, 3 DP words
+    pub time: ErasableAddress, // Time tag (PIPTIME), DP
+}
+
+impl StateVectorLayout {
+    /// Resolve the layout from the rope's symbols (RN, VN, PIPTIME), which
+    /// are common to LUMINARY and COMANCHE
+    pub fn from_symbols<S: SymbolTable + ?Sized>(symbols: &S) -> Option<Self> {
+        Some(Self {
+            rn: symbols.erasable("RN")?,
+            vn: symbols.erasable("VN")?,
+            time: symbols.erasable("PIPTIME")?,
+        })
+    }
+}
+
+/// Navigation solution converted to SI units
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct StateVector {
+    pub position: [f64; 3], // m
+    pub velocity: [f64; 3], // m/s
+    pub time: f64,          // s
+}
+
+/// Double precision fraction (-1, 1) stored at `addr`
+pub fn read_dp_fraction(mem: &MemoryMap, addr: ErasableAddress) -> f64 {
+    match mem.read_block(addr.bank, addr.offset..addr.offset + 2)[..] {
+        [upper, lower] => {
+            let [upper, lower] =
+                [upper, lower].map(|word| Word15::new(word).to_signed().get() as i64);
+            let value = upper * (1 << 14) + lower;
+            value as f64 / (1u64 << 28) as f64
+        }
+        _ => 0.0,
+    }
+}
+
+fn read_dp_vector(mem: &MemoryMap, addr: ErasableAddress, scale: f64) -> [f64; 3] {
+    [
+        read_dp_fraction(mem, addr) * scale,
+        read_dp_fraction(mem, addr.offset_by(2)) * scale,
+        read_dp_fraction(mem, addr.offset_by(4)) * scale,
+    ]
+}
+
+/// Extract the current state vector from erasable memory
+pub fn extract(mem: &MemoryMap, layout: &StateVectorLayout, sphere: Sphere) -> StateVector {
+    StateVector {
+        position: read_dp_vector(mem, layout.rn, sphere.position_scale()),
+        velocity: read_dp_vector(mem, layout.vn, sphere.velocity_scale()),
+        time: read_dp_fraction(mem, layout.time) * TIME_SCALE,
+    }
+}
+
+#[cfg(test)]
+mod state_vector_tests {
+    use super::{extract, Sphere, StateVectorLayout};
+    use crate::memory::MemoryMap;
+    use crate::symbols::ErasableAddress;
+
+    #[test]
+    fn converts_scaled_fractions_to_si() {
+        let mut mem = MemoryMap::new_blank();
+
+        let symbols: &[(&str, ErasableAddress)] = &[
+            ("RN", ErasableAddress::new(6, 0o100)),
+            ("VN", ErasableAddress::new(6, 0o106)),
+            ("PIPTIME", ErasableAddress::new(6, 0o114)),
+        ];
+        let layout = StateVectorLayout::from_symbols(symbols).unwrap();
+
+        // X = +1/2, Y = -1/4, Z = +0 (as -0 in the lower word)
+        mem.write_block(6, 0o100, &[0o20000, 0, 0o67777, 0o77777, 0, 0o77777]);
+        mem.write_block(6, 0o106, &[0o10000, 0, 0, 0, 0, 0]);
+        mem.write_block(6, 0o114, &[0, 1]);
+
+        let sv = extract(&mem, &layout, Sphere::Moon);
+        assert_eq!(
+            sv.position,
+            [(1u64 << 26) as f64, -((1u64 << 25) as f64), 0.0]
+        );
+        assert_eq!(sv.velocity[0], 800.0); // 1/8 of 2^5 m/cs
+        assert_eq!(sv.time, 0.01); // One centisecond
+    }
+}
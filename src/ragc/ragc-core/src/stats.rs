@@ -0,0 +1,95 @@
+use crate::instructions::timing::ALL_MNEMONICS;
+use crate::instructions::{Mnemonic, MNEMONIC_COUNT};
+use core::fmt;
+
+/// Instruction mix collected while the CPU runs
+#[derive(Clone)]
+pub struct InstructionStats {
+    counts: [u64; MNEMONIC_COUNT],
+    pub basic: u64,    // Instructions executed without the EXTEND prefix
+    pub extended: u64, // Instructions executed after EXTEND
+}
+
+impl Default for InstructionStats {
+    fn default() -> Self {
+        Self {
+            counts: [0; MNEMONIC_COUNT],
+            basic: 0,
+            extended: 0,
+        }
+    }
+}
+
+impl InstructionStats {
+    pub fn record(&mut self, mnem: Mnemonic, extended: bool) {
+        self.counts[mnem as usize] += 1;
+        match extended {
+            true => self.extended += 1,
+            false => self.basic += 1,
+        }
+    }
+
+    /// Number of times the given mnemonic was executed
+    pub fn count(&self, mnem: Mnemonic) -> u64 {
+        self.counts[mnem as usize]
+    }
+
+    pub fn total(&self) -> u64 {
+        self.basic + self.extended
+    }
+
+    /// Executed mnemonics with their counts, in declaration order
+    pub fn iter(&self) -> impl Iterator<Item = (Mnemonic, u64)> + '_ {
+        ALL_MNEMONICS
+            .iter()
+            .zip(self.counts.iter())
+            .filter(|(_, count)| **count > 0)
+            .map(|(mnem, count)| (*mnem, *count))
+    }
+
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+}
+
+impl fmt::Display for InstructionStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let total = self.total().max(1) as f64;
+        writeln!(
+            f,
+            "Instructions: {} (basic {}, extended {})",
+            self.total(),
+            self.basic,
+            self.extended
+        )?;
+        for (mnem, count) in self.iter() {
+            writeln!(
+                f,
+                "  {:<8} {:>12} {:>6.2}%",
+                mnem.name(),
+                count,
+                count as f64 * 100.0 / total
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod stats_tests {
+    use super::InstructionStats;
+    use crate::instructions::Mnemonic;
+
+    #[test]
+    fn counts_by_mnemonic_and_class() {
+        let mut stats = InstructionStats::default();
+        stats.record(Mnemonic::TC, false);
+        stats.record(Mnemonic::TC, false);
+        stats.record(Mnemonic::MP, true);
+
+        assert_eq!(stats.count(Mnemonic::TC), 2);
+        assert_eq!(stats.count(Mnemonic::INVALID), 0);
+        assert_eq!((stats.basic, stats.extended), (2, 1));
+        assert_eq!(stats.iter().count(), 2);
+    }
+}
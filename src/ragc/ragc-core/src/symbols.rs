@@ -0,0 +1,42 @@
+/// Physical erasable location (bank E0-E7 and offset within the bank)
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct ErasableAddress {
+    pub bank: usize,
+    pub offset: usize,
+}
+
+impl ErasableAddress {
+    pub const fn new(bank: usize, offset: usize) -> Self {
+        Self { bank, offset }
+    }
+
+    /// Convert a flat erasable address (0o0000-0o3777) into bank and offset
+    pub const fn from_flat(addr: usize) -> Self {
+        Self {
+            bank: (addr >> 8) & 0x7,
+            offset: addr & 0xff,
+        }
+    }
+
+    /// Address of the word `n` locations further on in the same bank
+    pub const fn offset_by(&self, n: usize) -> Self {
+        Self {
+            bank: self.bank,
+            offset: self.offset + n,
+        }
+    }
+}
+
+/// Name lookup for erasable variables of the loaded rope
+pub trait SymbolTable {
+    fn erasable(&self, name: &str) -> Option<ErasableAddress>;
+}
+
+/// Static symbol tables, e.g. `&[("RN", ErasableAddress::from_flat(0o1234))]`
+impl SymbolTable for [(&str, ErasableAddress)] {
+    fn erasable(&self, name: &str) -> Option<ErasableAddress> {
+        self.iter()
+            .find(|(symbol, _)| *symbol == name)
+            .map(|(_, addr)| *addr)
+    }
+}
@@ -0,0 +1,171 @@
+//! Tiny assembler for unit-test programs
+//!
+//! `TestRom::new().at(0o4000).emit(&[CA(0o4010), XCH(0o100), Loop])` places
+//! instructions in fixed-fixed memory, so a fresh `Cpu` boots straight into
+//! them. Operands are raw address fields; extracodes get their EXTEND.
+use crate::cpu::Cpu;
+use crate::memory::rom::FixedMemory;
+use crate::memory::MemoryMapBuilder;
+
+/// One assembled word, or an extracode with its EXTEND prefix
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Clone, Copy, Debug)]
+pub enum Op {
+    // Basic instructions
+    TC(u16),
+    CCS(u16),
+    TCF(u16),
+    DAS(u16),
+    LXCH(u16),
+    INCR(u16),
+    ADS(u16),
+    CA(u16),
+    CS(u16),
+    INDEX(u16),
+    RESUME,
+    DXCH(u16),
+    TS(u16),
+    XCH(u16),
+    AD(u16),
+    MASK(u16),
+    RELINT,
+    EXTEND,
+
+    // Extracodes
+    READ(u16),
+    WRITE(u16),
+    EDRUPT(u16),
+    DV(u16),
+    MSU(u16),
+    QXCH(u16),
+    AUG(u16),
+    DIM(u16),
+    DCA(u16),
+    DCS(u16),
+    SU(u16),
+    BZMF(u16),
+    MP(u16),
+
+    Word(u16), // Constant (OCT)
+    Loop,      // TCF to itself, parks the program
+}
+
+impl Op {
+    /// Assemble at `here`: (needs EXTEND, word)
+    fn encode(self, here: u16) -> (bool, u16) {
+        let field = |base: u16, k: u16, mask: u16| {
+            assert!(k & !mask == 0, "operand {:o} does not fit {:?}", k, self);
+            base | k
+        };
+        let fixed = |base: u16, k: u16| {
+            assert!(k >= 0o2000, "{:?} needs a fixed-memory target", self);
+            field(base, k, 0o7777)
+        };
+
+        match self {
+            Op::TC(k) => (false, field(0o00000, k, 0o7777)),
+            Op::CCS(k) => (false, field(0o10000, k, 0o1777)),
+            Op::TCF(k) => (false, fixed(0o10000, k)),
+            Op::DAS(k) => (false, field(0o20000, k, 0o1777)),
+            Op::LXCH(k) => (false, field(0o22000, k, 0o1777)),
+            Op::INCR(k) => (false, field(0o24000, k, 0o1777)),
+            Op::ADS(k) => (false, field(0o26000, k, 0o1777)),
+            Op::CA(k) => (false, field(0o30000, k, 0o7777)),
+            Op::CS(k) => (false, field(0o40000, k, 0o7777)),
+            Op::INDEX(k) => (false, field(0o50000, k, 0o1777)),
+            Op::RESUME => (false, 0o50017),
+            Op::DXCH(k) => (false, field(0o52000, k, 0o1777)),
+            Op::TS(k) => (false, field(0o54000, k, 0o1777)),
+            Op::XCH(k) => (false, field(0o56000, k, 0o1777)),
+            Op::AD(k) => (false, field(0o60000, k, 0o7777)),
+            Op::MASK(k) => (false, field(0o70000, k, 0o7777)),
+            Op::RELINT => (false, 0o00003),
+            Op::EXTEND => (false, 0o00006),
+
+            Op::READ(ch) => (true, field(0o00000, ch, 0o777)),
+            Op::WRITE(ch) => (true, field(0o01000, ch, 0o777)),
+            Op::EDRUPT(k) => (true, field(0o07000, k, 0o777)),
+            Op::DV(k) => (true, field(0o10000, k, 0o1777)),
+            Op::MSU(k) => (true, field(0o20000, k, 0o1777)),
+            Op::QXCH(k) => (true, field(0o22000, k, 0o1777)),
+            Op::AUG(k) => (true, field(0o24000, k, 0o1777)),
+            Op::DIM(k) => (true, field(0o26000, k, 0o1777)),
+            Op::DCA(k) => (true, field(0o30000, k, 0o7777)),
+            Op::DCS(k) => (true, field(0o40000, k, 0o7777)),
+            Op::SU(k) => (true, field(0o60000, k, 0o1777)),
+            Op::BZMF(k) => (true, fixed(0o60000, k)),
+            Op::MP(k) => (true, field(0o70000, k, 0o7777)),
+
+            Op::Word(w) => (false, field(0, w, 0o77777)),
+            Op::Loop => (false, fixed(0o10000, here)),
+        }
+    }
+}
+
+/// Fixed-fixed memory (banks 2 and 3) built from `Op` sequences
+pub struct TestRom {
+    banks: [[u16; 1024]; 2],
+    here: u16,
+}
+
+impl TestRom {
+    /// Blank ROM (every word TC 0) with the cursor at the restart address
+    pub fn new() -> Self {
+        Self {
+            banks: [[0; 1024]; 2],
+            here: 0o4000,
+        }
+    }
+
+    /// Move the cursor to a fixed-fixed address (4000-7777)
+    pub fn at(mut self, addr: u16) -> Self {
+        assert!(
+            (0o4000..=0o7777).contains(&addr),
+            "{:o} is not fixed-fixed",
+            addr
+        );
+        self.here = addr;
+        self
+    }
+
+    /// Address the next word lands at
+    pub fn here(&self) -> u16 {
+        self.here
+    }
+
+    /// Assemble `ops` at the cursor
+    pub fn emit(mut self, ops: &[Op]) -> Self {
+        for op in ops.iter() {
+            let (extended, word) = op.encode(self.here);
+            if extended {
+                self.put(0o00006);
+            }
+            self.put(word);
+        }
+        self
+    }
+
+    fn put(&mut self, word: u16) {
+        let addr = self.here as usize - 0o4000;
+        self.banks[addr / 1024][addr % 1024] = word;
+        self.here += 1;
+    }
+
+    /// CPU booted from this ROM, with blank erasable memory
+    pub fn cpu(&self) -> Cpu<'_> {
+        Cpu::new(MemoryMapBuilder::new().fixed_memory(self).build())
+    }
+}
+
+impl FixedMemory for TestRom {
+    fn bank_count(&self) -> usize {
+        4
+    }
+
+    fn word(&self, bank: usize, offset: usize) -> u16 {
+        match bank {
+            2 | 3 => self.banks[bank - 2][offset],
+            _ => 0,
+        }
+    }
+}
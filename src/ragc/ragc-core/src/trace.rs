@@ -0,0 +1,87 @@
+use crate::cpu::{RestartCause, DEFAULT_TRACE_DEPTH};
+
+/// Snapshot of a single executed instruction
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct TraceRecord {
+    pub pc: u16,       // Address the instruction was fetched from
+    pub data: u16,     // Instruction word (bit 15 set when extended)
+    pub a: u16,        // Accumulator before execution
+    pub l: u16,        // L register before execution
+    pub q: u16,        // Q register before execution
+    pub cycles: usize, // Total MCTs elapsed when the instruction started
+}
+
+/// Fixed-size history of the most recent instructions, oldest overwritten
+/// first. Its depth is the CPU's `TRACE_DEPTH`.
+pub type RingTrace<const DEPTH: usize = DEFAULT_TRACE_DEPTH> =
+    heapless::HistoryBuffer<TraceRecord, DEPTH>;
+
+/// What made the CPU dump its trace
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum TraceEvent {
+    Restart(RestartCause), // GOJAM
+    Alarm(u16),            // Program alarm code stored in FAILREG
+}
+
+/// Host callback invoked with the trace on a GOJAM or program alarm
+/// Iterate `trace.oldest_ordered()` to walk the records chronologically
+pub type TraceDumpFn<const DEPTH: usize = DEFAULT_TRACE_DEPTH> =
+    fn(event: TraceEvent, trace: &RingTrace<DEPTH>);
+
+#[cfg(test)]
+mod trace_tests {
+    use super::{RingTrace, TraceEvent};
+    use crate::cpu::{Cpu, RestartCause, DEFAULT_TRACE_DEPTH, DEFAULT_UNPROG_DEPTH};
+    use crate::memory::rom::RomInfo;
+    use crate::memory::{MemoryMap, MemoryMapBuilder};
+    use crate::test_rom::{Op::*, TestRom};
+    use core::sync::atomic::{AtomicUsize, Ordering};
+
+    static DUMPED: AtomicUsize = AtomicUsize::new(0);
+    static ALARM: AtomicUsize = AtomicUsize::new(0);
+
+    fn dump(_event: TraceEvent, trace: &RingTrace) {
+        DUMPED.store(trace.oldest_ordered().count(), Ordering::SeqCst);
+    }
+
+    fn dump_alarm(event: TraceEvent, trace: &RingTrace<4>) {
+        if let TraceEvent::Alarm(code) = event {
+            let last = trace.recent().map_or(0, |record| record.pc as usize);
+            ALARM.store((code as usize) << 16 | last, Ordering::SeqCst);
+        }
+    }
+
+    #[test]
+    fn gojam_dumps_most_recent_instructions() {
+        let mut cpu = Cpu::new(MemoryMap::new_blank());
+        cpu.set_trace_dump(dump);
+
+        for _ in 0..(DEFAULT_TRACE_DEPTH * 2) {
+            cpu.step();
+        }
+        assert_eq!(cpu.ring_trace().len(), DEFAULT_TRACE_DEPTH);
+
+        cpu.gojam(RestartCause::Manual);
+        assert_eq!(DUMPED.load(Ordering::SeqCst), DEFAULT_TRACE_DEPTH);
+    }
+
+    #[test]
+    fn alarms_dump_a_trace_of_the_chosen_depth() {
+        let rom = TestRom::new().emit(&[CA(0o4003), XCH(0o100), Loop, Word(0o1202)]);
+        let mem = MemoryMapBuilder::new()
+            .fixed_memory(&rom)
+            .rom_info(RomInfo {
+                failreg: Some(0o100),
+                ..RomInfo::BLOCK_II
+            })
+            .build();
+        let mut cpu = Cpu::<'_, DEFAULT_UNPROG_DEPTH, 4>::with_queue_depth(mem);
+        cpu.set_trace_dump(dump_alarm);
+        cpu.reset();
+        for _ in 0..8 {
+            cpu.step();
+        }
+        assert_eq!(cpu.ring_trace().len(), 4);
+        assert_eq!(ALARM.load(Ordering::SeqCst), 0o1202 << 16 | 0o4001);
+    }
+}
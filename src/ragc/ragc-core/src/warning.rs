@@ -0,0 +1,59 @@
+//! AGC WARNING filter. Restarts and oscillator failure charge an
+//! integrator that drains at a steady rate; the warning lights while the
+//! charge is above a threshold. A lone restart stays below it, restarts
+//! closer together than about four seconds, or an oscillator failure
+//! lasting about two, light the warning.
+
+/// MCTs between filter samples (about 12 ms)
+pub const WARNING_SAMPLE_MCTS: u32 = 1024;
+
+const RESTART_CHARGE: u32 = 15000;
+const OSCILLATOR_CHARGE: u32 = 150; // Per sample while the oscillator has failed
+const DRAIN: u32 = 15; // Per sample
+const THRESHOLD: u32 = 20000;
+const MAX_CHARGE: u32 = 140000; // Holds the warning about 20 s after the cause ends
+
+#[derive(Clone, Copy, Default, Debug, Hash)]
+pub struct WarningFilter {
+    charge: u32,
+    mcts: u32, // Since the last sample
+    lit: bool,
+}
+
+impl WarningFilter {
+    /// A GOJAM charges the filter; the lamp follows at the next sample
+    pub fn restart(&mut self) {
+        self.charge = (self.charge + RESTART_CHARGE).min(MAX_CHARGE);
+    }
+
+    /// Advance the filter, sampling the oscillator alarm when a sample is
+    /// due. Returns the new warning state when it changes.
+    pub fn tick(&mut self, cycles: u16, oscillator_failed: impl FnOnce() -> bool) -> Option<bool> {
+        self.mcts += cycles as u32;
+        if self.mcts < WARNING_SAMPLE_MCTS {
+            return None;
+        }
+        self.mcts -= WARNING_SAMPLE_MCTS;
+
+        if oscillator_failed() {
+            self.charge = (self.charge + OSCILLATOR_CHARGE).min(MAX_CHARGE);
+        }
+        self.charge = self.charge.saturating_sub(DRAIN);
+        let lit = self.charge > THRESHOLD;
+        match lit != self.lit {
+            true => {
+                self.lit = lit;
+                Some(lit)
+            }
+            false => None,
+        }
+    }
+
+    pub fn is_lit(&self) -> bool {
+        self.lit
+    }
+
+    pub fn charge(&self) -> u32 {
+        self.charge
+    }
+}
@@ -0,0 +1,150 @@
+//! Typed AGC words for module boundaries
+//!
+//! Memory and channels hold 15 bits (sign in bit 15); A and Q hold 16 bits
+//! with the true sign in bit 16 and overflow showing as a bit 15 that
+//! disagrees with it. Storing an accumulator value without overflow
+//! correction corrupts the sign, so the conversions between the two are
+//! spelled out here. Internals may keep working on raw `u16`; with the
+//! `strict-words` feature, raw writes of 16-bit values into 15-bit
+//! locations are reported as they happen.
+use core::ops::Add;
+
+use crate::utils::{add_s15, add_s16, adjust_overflow, extend_sign_bits};
+
+/// 15-bit ones' complement word, as stored in memory and channels
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+#[repr(transparent)]
+pub struct Word15(u16);
+
+/// 16-bit accumulator word (A, Q) with the overflow bit
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+#[repr(transparent)]
+pub struct Word16(u16);
+
+/// Host value of a ones' complement word. -0 reads as 0.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug, Default)]
+#[repr(transparent)]
+pub struct SignedAgc(i16);
+
+impl Word15 {
+    pub const NEG_ZERO: Word15 = Word15(0o77777);
+
+    /// Take the low 15 bits of a raw value
+    pub const fn new(raw: u16) -> Self {
+        Self(raw & 0o77777)
+    }
+
+    pub const fn raw(self) -> u16 {
+        self.0
+    }
+
+    pub const fn is_negative(self) -> bool {
+        self.0 & 0o40000 != 0
+    }
+
+    /// Load into a 16-bit register, copying the sign into bit 16
+    pub fn to_word16(self) -> Word16 {
+        Word16(extend_sign_bits(self.0))
+    }
+
+    pub fn to_signed(self) -> SignedAgc {
+        match self.is_negative() {
+            true => SignedAgc(-(((!self.0) & 0o37777) as i16)),
+            false => SignedAgc(self.0 as i16),
+        }
+    }
+}
+
+impl Word16 {
+    pub const fn new(raw: u16) -> Self {
+        Self(raw)
+    }
+
+    pub const fn raw(self) -> u16 {
+        self.0
+    }
+
+    /// Bits 16 and 15 disagree: positive or negative overflow
+    pub const fn overflow(self) -> bool {
+        matches!(self.0 & 0o140000, 0o40000 | 0o100000)
+    }
+
+    /// Store to memory with overflow correction: the true sign in bit 16
+    /// becomes the stored sign
+    pub fn to_word15(self) -> Word15 {
+        Word15(adjust_overflow(self.0) & 0o77777)
+    }
+}
+
+impl SignedAgc {
+    pub const fn new(value: i16) -> Self {
+        Self(value)
+    }
+
+    pub const fn get(self) -> i16 {
+        self.0
+    }
+
+    /// Ones' complement word, saturating at the 14-bit magnitude
+    pub fn to_word15(self) -> Word15 {
+        let magnitude = self.0.unsigned_abs().min(0o37777);
+        match self.0 < 0 {
+            true => Word15(!magnitude & 0o77777),
+            false => Word15(magnitude),
+        }
+    }
+}
+
+/// Ones' complement sum, end-around carry included
+impl Add for Word15 {
+    type Output = Word15;
+
+    fn add(self, other: Word15) -> Word15 {
+        Word15(add_s15(self.0, other.0))
+    }
+}
+
+/// Ones' complement sum at 16 bits, as the adder forms it
+impl Add for Word16 {
+    type Output = Word16;
+
+    fn add(self, other: Word16) -> Word16 {
+        Word16(add_s16(self.0, other.0))
+    }
+}
+
+impl From<Word15> for Word16 {
+    fn from(word: Word15) -> Self {
+        word.to_word16()
+    }
+}
+
+impl From<Word15> for SignedAgc {
+    fn from(word: Word15) -> Self {
+        word.to_signed()
+    }
+}
+
+#[cfg(test)]
+mod word_tests {
+    use super::{SignedAgc, Word15, Word16};
+
+    #[test]
+    fn conversions_keep_sign_and_correct_overflow() {
+        let minus_two = Word15::new(0o77775);
+        assert_eq!(minus_two.to_word16(), Word16::new(0o177775));
+        assert_eq!(minus_two.to_signed(), SignedAgc::new(-2));
+        assert_eq!(SignedAgc::new(-2).to_word15(), minus_two);
+        assert_eq!(Word15::NEG_ZERO.to_signed(), SignedAgc::new(0));
+
+        // Positive overflow: 37777 + 1 stores as +0 with the sign of bit 16
+        let sum = Word16::new(0o37777) + Word16::new(1);
+        assert!(sum.overflow());
+        assert_eq!(sum.to_word15(), Word15::new(0));
+        assert_eq!(Word16::new(0o177775).to_word15(), minus_two);
+
+        assert_eq!(Word15::new(0o37777) + Word15::new(1), Word15::new(0o40000));
+        assert_eq!(minus_two + Word15::new(2), Word15::NEG_ZERO);
+        assert_eq!(Word15::new(0o177777), Word15::NEG_ZERO, "bit 16 is dropped");
+    }
+}
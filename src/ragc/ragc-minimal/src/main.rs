@@ -0,0 +1,47 @@
+//! Build check for ragc-core's minimal profile: no default features, so
+//! logging compiles out and the only dependencies are core and heapless.
+//! Building for a microcontroller target shows it still fits there. From
+//! `src/ragc`:
+//!
+//!     rustup target add thumbv7em-none-eabi
+//!     cargo build --manifest-path ragc-minimal/Cargo.toml --release --target thumbv7em-none-eabi
+//!
+//! `cargo xtask check-features minimal-bare-metal` runs the same build, and
+//! CI runs it on every push.
+//!
+//! On a host it is an ordinary binary running the same steps.
+#![cfg_attr(target_os = "none", no_std, no_main)]
+
+use ragc_core::cpu::Cpu;
+use ragc_core::memory::MemoryMap;
+
+/// Steps run, enough to cross a few timer pulses
+const STEPS: usize = 10_000;
+
+/// Power on a blank machine and run it, returning the MCTs taken
+fn run() -> usize {
+    let mut cpu = Cpu::new(MemoryMap::new_blank());
+    cpu.reset();
+    for _ in 0..STEPS {
+        core::hint::black_box(cpu.step());
+    }
+    cpu.total_cycles
+}
+
+#[cfg(target_os = "none")]
+#[no_mangle]
+pub extern "C" fn _start() -> ! {
+    core::hint::black_box(run());
+    loop {}
+}
+
+#[cfg(target_os = "none")]
+#[panic_handler]
+fn panic(_: &core::panic::PanicInfo) -> ! {
+    loop {}
+}
+
+#[cfg(not(target_os = "none"))]
+fn main() {
+    println!("{} steps, {} MCTs", STEPS, run());
+}
@@ -0,0 +1,52 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use ragc_core::cpu::Cpu;
+use ragc_core::memory::MemoryMapBuilder;
+use ragc_peripherals::tracefile::{TraceEntry, TraceFile, DEFAULT_TRACE_DEPTH};
+
+// CA 200, AD 201, XCH 202, TC 100 looping in erasable memory
+const PROGRAM: [u16; 4] = [0o30200, 0o60201, 0o56202, 0o00100];
+
+fn cpu() -> Cpu<'static> {
+    let mem = MemoryMapBuilder::new()
+        .erasable(0, 0o100, &PROGRAM)
+        .erasable(0, 0o200, &[0o1, 0o2])
+        .build();
+    let mut cpu = Cpu::new(mem);
+    cpu.update_pc(0o100);
+    cpu
+}
+
+/// Stepping flat out with and without a trace file. Flat out the writer
+/// can't keep up and most records are dropped; on a single core it also
+/// takes time from the emulator. The budget that matters, spare time per
+/// frame at the computer's own pace, is checked by the `trace_overhead`
+/// example (`cargo xtask trace-overhead`).
+fn trace(c: &mut Criterion) {
+    let mut plain = cpu();
+    c.bench_function("step_1000", |b| {
+        b.iter(|| {
+            for _ in 0..1000 {
+                black_box(plain.step());
+            }
+        })
+    });
+
+    let path = std::env::temp_dir().join("ragc-bench.trace");
+    let mut file = TraceFile::create(path.to_str().unwrap(), DEFAULT_TRACE_DEPTH).unwrap();
+    let mut traced = cpu();
+    c.bench_function("step_1000_traced", |b| {
+        b.iter(|| {
+            for _ in 0..1000 {
+                file.record(TraceEntry::capture(&traced));
+                black_box(traced.step());
+            }
+        })
+    });
+    let dropped = file.dropped();
+    let written = file.finish().unwrap();
+    println!("Trace: {} written, {} dropped", written, dropped);
+    let _ = std::fs::remove_file(path);
+}
+
+criterion_group!(benches, trace);
+criterion_main!(benches);
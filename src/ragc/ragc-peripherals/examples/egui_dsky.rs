@@ -0,0 +1,141 @@
+//! Minimal egui DSKY: the emulator runs LUMINARY on its own thread, a
+//! frame per GUI frame, and the window redraws from the shared DSKY state.
+//!
+//! cargo run -p ragc-peripherals --example egui_dsky --features egui-example
+use eframe::egui;
+use ragc_core::prelude::*;
+use ragc_peripherals::dsky::DskyDisplay;
+use ragc_peripherals::pacing::{ExternalTick, Pacer};
+use ragc_peripherals::shared_dsky::{DskyState, SharedDsky};
+
+use std::time::{Duration, Instant};
+
+/// Frame length while the window isn't drawing (hidden or minimized)
+const FALLBACK_FRAME: Duration = Duration::from_millis(20);
+
+/// Keypad rows: label and keycode
+const KEYS: [&[(&str, u16)]; 3] = [
+    &[
+        ("VERB", 0o21),
+        ("+", 0o32),
+        ("7", 7),
+        ("8", 8),
+        ("9", 9),
+        ("CLR", 0o36),
+    ],
+    &[
+        ("NOUN", 0o37),
+        ("-", 0o33),
+        ("4", 4),
+        ("5", 5),
+        ("6", 6),
+        ("PRO", 0),
+    ],
+    &[
+        ("KEY REL", 0o31),
+        ("0", 0o20),
+        ("1", 1),
+        ("2", 2),
+        ("3", 3),
+        ("ENTR", 0o34),
+    ],
+];
+
+fn run_emulator(shared: SharedDsky, mut dsky: DskyDisplay, mut pacer: ExternalTick) {
+    let mut taps = shared.taps();
+    let mut builder = MemoryMapBuilder::new()
+        .rope(ragc_binaries::LUMINARY99_ROPE)
+        .dsky(&mut dsky);
+    for (channel, tap) in taps.iter_mut() {
+        builder = builder.tap(*channel, tap);
+    }
+    let mut cpu = Cpu::new(builder.build());
+    cpu.reset();
+    let mut last = Instant::now();
+    loop {
+        pacer.wait(last);
+        // Catch up with the time since the last frame
+        let now = Instant::now();
+        let mcts = EmuTime::mcts_in_secs((now - last).as_secs_f64());
+        last = now;
+        let mut cycles = 0;
+        while cycles < mcts {
+            cycles += cpu.step_cycles() as u64;
+        }
+    }
+}
+
+fn pair(value: Option<u8>) -> String {
+    value
+        .map(|v| format!("{:02}", v))
+        .unwrap_or_else(|| "  ".into())
+}
+
+fn register(state: &DskyState, reg: usize) -> String {
+    let sign = match state.display.register_sign(reg) {
+        Some(1) => '+',
+        Some(_) => '-',
+        None => ' ',
+    };
+    let digits: String = state
+        .display
+        .register_digits(reg)
+        .iter()
+        .map(|d| d.map_or(' ', |d| (b'0' + d) as char))
+        .collect();
+    format!("{}{}", sign, digits)
+}
+
+fn main() -> eframe::Result {
+    let shared = SharedDsky::new();
+    let dsky = DskyDisplay::new();
+    let keys = dsky.keypress_sender();
+    let emulator = shared.clone();
+    let (pacer, ticker) = ExternalTick::new(FALLBACK_FRAME);
+    std::thread::spawn(move || run_emulator(emulator, dsky, pacer));
+    let mut pro_held = false;
+
+    eframe::run_simple_native("RAGC DSKY", Default::default(), move |ctx, _frame| {
+        ticker.tick();
+        let state = shared.snapshot();
+        egui::CentralPanel::default().show(ctx, |ui| {
+            let lamp = |on: bool, name: &str| match on {
+                true => egui::RichText::new(name).strong(),
+                false => egui::RichText::new(name).weak(),
+            };
+            ui.horizontal(|ui| {
+                ui.label(lamp(state.lamps & 0o2 != 0, "COMP ACTY"));
+                ui.label(lamp(state.lamps & 0o20 != 0, "KEY REL"));
+                ui.label(lamp(state.relay_lights & 0o400 != 0, "PROG"));
+                ui.label(lamp(state.lights & 0o400 != 0, "STBY"));
+            });
+            ui.monospace(format!(
+                "PROG {}  VERB {}  NOUN {}",
+                pair(state.display.prog()),
+                pair(state.display.verb()),
+                pair(state.display.noun())
+            ));
+            for reg in 0..3 {
+                ui.monospace(format!("R{} {}", reg + 1, register(&state, reg)));
+            }
+            for row in KEYS.iter() {
+                ui.horizontal(|ui| {
+                    for (label, code) in row.iter() {
+                        let button = ui.button(*label);
+                        if *label == "PRO" {
+                            // PRO is held, not pressed: bit 15 set, bit 14 clear while down
+                            let held = button.is_pointer_button_down_on();
+                            if held != pro_held {
+                                let _ = keys.send(if held { 0o40000 } else { 0o60000 });
+                                pro_held = held;
+                            }
+                        } else if button.clicked() {
+                            let _ = keys.send(*code);
+                        }
+                    }
+                });
+            }
+        });
+        ctx.request_repaint(); // Redraw, and so tick, every vsync
+    })
+}
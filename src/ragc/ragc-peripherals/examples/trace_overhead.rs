@@ -0,0 +1,100 @@
+//! What tracing to a file costs the emulation thread, as
+//! `cargo xtask trace-overhead` checks it. The program runs for 2 s of AGC
+//! time in 5 ms frames held to the computer's clock, as ragc runs it, and
+//! each frame the time spent stepping is measured; the rest of the frame is
+//! spare. Fails if a text trace takes more than 5% of the frame from the
+//! spare time, or drops records at this pace. The `trace` bench has the
+//! cost per step running flat out.
+use std::time::{Duration, Instant};
+
+use ragc_core::cpu::Cpu;
+use ragc_core::memory::mods::{EmuTime, MCT_SECONDS};
+use ragc_core::memory::MemoryMapBuilder;
+use ragc_peripherals::tracefile::{TraceEntry, TraceFile, DEFAULT_TRACE_DEPTH};
+
+// CA 200, AD 201, XCH 202, TC 100 looping in erasable memory
+const PROGRAM: [u16; 4] = [0o30200, 0o60201, 0o56202, 0o00100];
+
+const FRAMES: u32 = 400;
+const FRAME_MCTS: usize = EmuTime::mcts_in_millis(5) as usize;
+
+/// Share of a frame tracing may take
+const BUDGET: f64 = 0.05;
+
+/// Time spent stepping over all frames, and the instructions run
+struct Run {
+    busy: Duration,
+    steps: u64,
+}
+
+fn cpu() -> Cpu<'static> {
+    let mem = MemoryMapBuilder::new()
+        .erasable(0, 0o100, &PROGRAM)
+        .erasable(0, 0o200, &[0o1, 0o2])
+        .build();
+    let mut cpu = Cpu::new(mem);
+    cpu.update_pc(0o100);
+    cpu
+}
+
+fn run(mut trace: Option<&mut TraceFile>) -> Run {
+    let frame = Duration::from_secs_f64(FRAME_MCTS as f64 * MCT_SECONDS);
+    let mut cpu = cpu();
+    let mut run = Run {
+        busy: Duration::ZERO,
+        steps: 0,
+    };
+    let start = Instant::now();
+    for n in 1..=FRAMES {
+        let busy = Instant::now();
+        while cpu.total_cycles < n as usize * FRAME_MCTS {
+            if let Some(trace) = trace.as_mut() {
+                trace.record(TraceEntry::capture(&cpu));
+            }
+            cpu.step();
+            run.steps += 1;
+        }
+        run.busy += busy.elapsed();
+        if let Some(spare) = (start + frame * n).checked_duration_since(Instant::now()) {
+            std::thread::sleep(spare);
+        }
+    }
+    run
+}
+
+fn main() {
+    let plain = run(None);
+
+    let path = std::env::temp_dir().join("ragc-trace-overhead.trace");
+    let mut trace = TraceFile::create(path.to_str().unwrap(), DEFAULT_TRACE_DEPTH).unwrap();
+    let traced = run(Some(&mut trace));
+    let dropped = trace.dropped();
+    let written = trace.finish().unwrap();
+    let _ = std::fs::remove_file(path);
+
+    let frame = FRAME_MCTS as f64 * MCT_SECONDS;
+    let share = |run: &Run| run.busy.as_secs_f64() / FRAMES as f64 / frame;
+    let per_step = |run: &Run| run.busy.as_nanos() as f64 / run.steps as f64;
+    let overhead = share(&traced) - share(&plain);
+    println!(
+        "Untraced: {:.0} ns/step, {:.2}% of each frame busy",
+        per_step(&plain),
+        share(&plain) * 100.0
+    );
+    println!(
+        "Traced:   {:.0} ns/step, {:.2}% of each frame busy, {} written, {} dropped",
+        per_step(&traced),
+        share(&traced) * 100.0,
+        written,
+        dropped
+    );
+    println!("Tracing takes {:.2}% of each frame", overhead * 100.0);
+
+    if dropped > 0 || overhead > BUDGET {
+        eprintln!(
+            "Trace overhead over budget ({:.0}% of a frame, no drops)",
+            BUDGET * 100.0
+        );
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,205 @@
+use ragc_core::constants::ports::{CHANNEL_PYJETS, CHANNEL_ROLLJETS};
+use ragc_core::constants::special_registers::{
+    SPECIAL_REGISTER_CONTROL_DISPLAY_X, SPECIAL_REGISTER_CONTROL_DISPLAY_Y,
+    SPECIAL_REGISTER_CONTROL_DISPLAY_Z,
+};
+use ragc_core::cpu::{Cpu, UnprogSequence};
+use ragc_core::memory::mods::MCT_SECONDS;
+
+/// Angle represented by a single CDU pulse (2^15 pulses per revolution)
+const CDU_PULSE_DEG: f64 = 360.0 / 32768.0;
+
+const CDU_REGISTERS: [usize; 3] = [
+    SPECIAL_REGISTER_CONTROL_DISPLAY_X,
+    SPECIAL_REGISTER_CONTROL_DISPLAY_Y,
+    SPECIAL_REGISTER_CONTROL_DISPLAY_Z,
+];
+
+/// Canned attitude disturbance applied to the CDUs
+#[derive(Clone, Copy, Debug)]
+pub struct StimulusProfile {
+    pub name: &'static str,
+    pub initial_error: [f64; 3], // deg, applied once at the start of the run
+    pub rate: [f64; 3],          // deg/s, applied as a steady drift
+}
+
+pub const PROFILE_HOLD: StimulusProfile = StimulusProfile {
+    name: "hold",
+    initial_error: [0.0, 0.0, 0.0],
+    rate: [0.0, 0.0, 0.0],
+};
+
+pub const PROFILE_PITCH_STEP: StimulusProfile = StimulusProfile {
+    name: "pitch-step",
+    initial_error: [0.0, 5.0, 0.0],
+    rate: [0.0, 0.0, 0.0],
+};
+
+pub const PROFILE_ROLL_DRIFT: StimulusProfile = StimulusProfile {
+    name: "roll-drift",
+    initial_error: [0.0, 0.0, 0.0],
+    rate: [0.0, 0.0, 0.2],
+};
+
+pub const PROFILE_TUMBLE: StimulusProfile = StimulusProfile {
+    name: "tumble",
+    initial_error: [2.0, -2.0, 1.0],
+    rate: [1.0, -0.5, 0.5],
+};
+
+/// DAP response measured over a stimulus run
+#[derive(Clone, Copy, Debug, Default)]
+pub struct DapReport {
+    pub cycles: u64,              // MCTs simulated
+    pub firings: [u32; 16],       // Jet firings (channel 5 bits 0-7, channel 6 bits 8-15)
+    pub on_cycles: [u64; 16],     // Total MCTs each jet was commanded on
+    pub min_pulse: Option<u64>,   // Shortest jet pulse in MCTs
+    pub max_pulse: Option<u64>,   // Longest jet pulse in MCTs
+    pub limit_cycle: Option<u64>, // Mean MCTs between firing starts
+    pub cdu_pulses: [u32; 3],     // Pulses injected into the CDU counters
+}
+
+impl DapReport {
+    pub fn total_firings(&self) -> u32 {
+        self.firings.iter().sum()
+    }
+
+    fn record_pulse(&mut self, width: u64) {
+        self.min_pulse = Some(self.min_pulse.map_or(width, |m| m.min(width)));
+        self.max_pulse = Some(self.max_pulse.map_or(width, |m| m.max(width)));
+    }
+}
+
+/// Test harness injecting rate/attitude errors into the CDUs and reading back
+/// RCS jet commands from channels 5 and 6
+pub struct DapStimulus {
+    profile: StimulusProfile,
+    pending: [f64; 3], // Fractional CDU pulses not yet injected
+    jets: u16,
+    jet_start: [u64; 16],
+    first_start: Option<u64>,
+    last_start: u64,
+    report: DapReport,
+}
+
+impl DapStimulus {
+    pub fn new(profile: StimulusProfile) -> Self {
+        Self {
+            profile,
+            pending: [0.0; 3],
+            jets: 0,
+            jet_start: [0; 16],
+            first_start: None,
+            last_start: 0,
+            report: DapReport::default(),
+        }
+    }
+
+    /// Run the CPU for at least `cycles` MCTs under the stimulus profile
+    pub fn run(&mut self, cpu: &mut Cpu, cycles: u64) -> DapReport {
+        if self.report.cycles == 0 {
+            self.apply_initial_error(cpu);
+        }
+
+        let end = self.report.cycles + cycles;
+        while self.report.cycles < end {
+            let step = cpu.step_cycles() as u64;
+            self.report.cycles += step;
+            self.inject_rates(cpu, step);
+            self.sample_jets(cpu);
+        }
+
+        let mut report = self.report;
+        if let Some(first) = self.first_start {
+            let starts = report.total_firings() as u64;
+            if starts > 1 {
+                report.limit_cycle = Some((self.last_start - first) / (starts - 1));
+            }
+        }
+        report
+    }
+
+    fn apply_initial_error(&mut self, cpu: &mut Cpu) {
+        for (axis, err) in self.profile.initial_error.iter().enumerate() {
+            let pulses = (err / CDU_PULSE_DEG).round() as i32;
+            let addr = CDU_REGISTERS[axis];
+            let value = (cpu.read(addr) as i32 + pulses) as u16 & 0o77777;
+            cpu.memory_mut().write_counter(addr, value);
+            self.report.cdu_pulses[axis] += pulses.unsigned_abs();
+        }
+    }
+
+    /// Feed at most one pulse per axis per step, carrying the remainder so the
+    /// counter requests never overrun the unprogrammed sequence queue
+    fn inject_rates(&mut self, cpu: &mut Cpu, step: u64) {
+        let dt = step as f64 * MCT_SECONDS;
+        for (axis, addr) in CDU_REGISTERS.iter().copied().enumerate() {
+            self.pending[axis] += self.profile.rate[axis] * dt / CDU_PULSE_DEG;
+            if self.pending[axis] >= 1.0 {
+                self.pending[axis] -= 1.0;
+                cpu.request_counter(UnprogSequence::PCDU(addr));
+                self.report.cdu_pulses[axis] += 1;
+            } else if self.pending[axis] <= -1.0 {
+                self.pending[axis] += 1.0;
+                cpu.request_counter(UnprogSequence::MCDU(addr));
+                self.report.cdu_pulses[axis] += 1;
+            }
+        }
+    }
+
+    fn sample_jets(&mut self, cpu: &mut Cpu) {
+        let jets =
+            (cpu.read_io(CHANNEL_PYJETS) & 0xFF) | (cpu.read_io(CHANNEL_ROLLJETS) & 0xFF) << 8;
+        let changed = jets ^ self.jets;
+        if changed == 0 {
+            return;
+        }
+
+        let now = self.report.cycles;
+        for jet in 0..16 {
+            let bit = 1 << jet;
+            if changed & bit == 0 {
+                continue;
+            }
+            if jets & bit != 0 {
+                self.jet_start[jet] = now;
+                self.report.firings[jet] += 1;
+                self.first_start.get_or_insert(now);
+                self.last_start = now;
+            } else {
+                let width = now - self.jet_start[jet];
+                self.report.on_cycles[jet] += width;
+                self.report.record_pulse(width);
+            }
+        }
+        self.jets = jets;
+    }
+}
+
+#[cfg(test)]
+mod dap_tests {
+    use super::{DapReport, DapStimulus, PROFILE_PITCH_STEP};
+    use ragc_core::constants::ports::{CHANNEL_PYJETS, CHANNEL_ROLLJETS};
+    use ragc_core::constants::special_registers::SPECIAL_REGISTER_CONTROL_DISPLAY_Y;
+    use ragc_core::cpu::Cpu;
+    use ragc_core::memory::MemoryMap;
+
+    #[test]
+    fn blank_rope_commands_no_jets() {
+        let mut cpu = Cpu::new(MemoryMap::new_blank());
+
+        // The jet channels stay clear throughout, not just at the end
+        let mut stim = DapStimulus::new(PROFILE_PITCH_STEP);
+        let mut report = DapReport::default();
+        for _ in 0..10 {
+            report = stim.run(&mut cpu, 100);
+            assert_eq!(cpu.read_io(CHANNEL_PYJETS), 0);
+            assert_eq!(cpu.read_io(CHANNEL_ROLLJETS), 0);
+        }
+
+        assert_eq!(report.cdu_pulses[1], 455);
+        assert_eq!(cpu.read(SPECIAL_REGISTER_CONTROL_DISPLAY_Y), 455);
+        assert_eq!(report.total_firings(), 0);
+        assert_eq!(report.limit_cycle, None);
+    }
+}
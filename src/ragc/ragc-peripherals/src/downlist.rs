@@ -0,0 +1,120 @@
+use ragc_core::constants::ports::{CHANNEL_CHAN34, CHANNEL_CHAN35};
+use ragc_core::memory::downlink::DownlinkPair;
+use ragc_core::rng::Rng;
+
+/// Words in one downlist (50 double words)
+pub const DOWNLIST_WORDS: usize = 100;
+
+/// Sync word sent after the downlist ID
+pub const DOWNLIST_SYNC: u16 = 0o77340;
+
+/// Milliseconds between DOWNRUPTs (one word pair each)
+pub const DOWNRUPT_MS: u64 = 20;
+
+/// Contents of the data words in a synthetic downlist
+#[derive(Clone, Copy, Debug)]
+pub enum Pattern {
+    Counter,       // Word index within the downlist
+    Constant(u16), // The same word everywhere
+    Random,        // Seeded pseudo-random words
+    Frame,         // Frame number, to spot dropped downlists
+}
+
+/// Generates valid downlists without running flight software
+pub struct DownlistSynth {
+    id: u16,
+    pattern: Pattern,
+    rng: Rng,
+    frame: u32,
+    word: usize,
+}
+
+impl DownlistSynth {
+    pub fn new(id: u16, pattern: Pattern, rng: Rng) -> Self {
+        Self {
+            id: id & 0o77777,
+            pattern,
+            rng,
+            frame: 0,
+            word: 0,
+        }
+    }
+
+    /// Downlists completed so far
+    pub fn frames(&self) -> u32 {
+        self.frame
+    }
+
+    /// Next downlink word and the channel it is written to
+    pub fn next_word(&mut self) -> (usize, u16) {
+        let value = match self.word {
+            0 => self.id,
+            1 => DOWNLIST_SYNC,
+            idx => match self.pattern {
+                Pattern::Counter => idx as u16,
+                Pattern::Constant(value) => value,
+                Pattern::Random => self.rng.below(0o100000) as u16,
+                Pattern::Frame => (self.frame & 0o77777) as u16,
+            },
+        };
+        // Each DOWNRUPT writes the first word of a pair to 34, the second to 35
+        let channel = match self.word % 2 {
+            0 => CHANNEL_CHAN34,
+            _ => CHANNEL_CHAN35,
+        };
+
+        self.word += 1;
+        if self.word == DOWNLIST_WORDS {
+            self.word = 0;
+            self.frame += 1;
+        }
+        (channel, value & 0o77777)
+    }
+
+    /// Next word pair as one DOWNRUPT sends it, with its word order code
+    pub fn next_pair(&mut self) -> DownlinkPair {
+        let order = self.word != 0;
+        let (_, first) = self.next_word();
+        let (_, second) = self.next_word();
+        DownlinkPair {
+            order,
+            words: [first, second],
+        }
+    }
+}
+
+#[cfg(test)]
+mod downlist_tests {
+    use super::{DownlistSynth, Pattern, DOWNLIST_SYNC, DOWNLIST_WORDS};
+    use ragc_core::constants::ports::{CHANNEL_CHAN34, CHANNEL_CHAN35};
+    use ragc_core::rng::Rng;
+
+    #[test]
+    fn downlists_start_with_id_and_sync() {
+        let mut synth = DownlistSynth::new(0o77774, Pattern::Counter, Rng::new(1));
+        for frame in 0..2 {
+            assert_eq!(synth.next_word(), (CHANNEL_CHAN34, 0o77774));
+            assert_eq!(synth.next_word(), (CHANNEL_CHAN35, DOWNLIST_SYNC));
+            for idx in 2..DOWNLIST_WORDS {
+                let (_, value) = synth.next_word();
+                assert_eq!(value, idx as u16);
+            }
+            assert_eq!(synth.frames(), frame + 1);
+        }
+
+        let mut a = DownlistSynth::new(0o77777, Pattern::Random, Rng::new(7));
+        let mut b = DownlistSynth::new(0o77777, Pattern::Random, Rng::new(7));
+        for _ in 0..DOWNLIST_WORDS {
+            assert_eq!(a.next_word(), b.next_word());
+        }
+
+        let mut synth = DownlistSynth::new(0o77774, Pattern::Counter, Rng::new(1));
+        let start = synth.next_pair();
+        assert_eq!(
+            (start.order, start.words),
+            (false, [0o77774, DOWNLIST_SYNC])
+        );
+        let data = synth.next_pair();
+        assert_eq!((data.order, data.words), (true, [2, 3]));
+    }
+}
@@ -0,0 +1,259 @@
+//! LM descent engine as the vehicle sees it. Channel 11's ENGINE ON and
+//! ENGINE OFF bits and the THRUST counter's throttle pulses are commands:
+//! the valves open a little after ENGINE ON, thrust builds and tails off
+//! over a few tenths of a second, and the throttle actuator lags its
+//! setting. The model turns the commands into a continuous thrust profile
+//! for a dynamics loop and reports each change as an event.
+use ragc_core::constants::ports;
+use ragc_core::constants::special_registers::SPECIAL_REGISTER_THRUST;
+use ragc_core::memory::MemoryMap;
+use ragc_core::word::Word15;
+
+/// Thrust, throttle scaling and actuator lags of an engine
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct EngineConfig {
+    pub max_thrust: f64,     // N
+    pub min_thrust: f64,     // N, lowest throttle setting
+    pub pulse_thrust: f64,   // N per THRUST pulse
+    pub ignition_delay: f64, // s from ENGINE ON until thrust starts to build
+    pub rise: f64,           // s, time constant of the build-up
+    pub throttle_lag: f64,   // s, time constant of the throttle actuator
+    pub tailoff: f64,        // s, time constant of the decay after ENGINE OFF
+}
+
+impl EngineConfig {
+    /// Descent engine: 10,125 lbf, throttling down to 10% at 2.8 lbf a pulse
+    pub const DPS: EngineConfig = EngineConfig {
+        max_thrust: 45_040.0,
+        min_thrust: 4_504.0,
+        pulse_thrust: 12.45,
+        ignition_delay: 0.25,
+        rise: 0.35,
+        throttle_lag: 0.2,
+        tailoff: 0.2,
+    };
+}
+
+impl Default for EngineConfig {
+    fn default() -> Self {
+        Self::DPS
+    }
+}
+
+/// Where the engine is between its commands
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum EngineState {
+    Off,
+    Igniting,   // ENGINE ON seen; thrust not yet built to the setting
+    Running,    // Thrust within 10% of the setting, then following it
+    TailingOff, // ENGINE OFF seen; thrust decaying
+}
+
+/// A change the dynamics loop or a recorder may want to know about
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum EngineEvent {
+    State { at: f64, state: EngineState },
+    Throttle { at: f64, setting: f64 }, // N the actuator is now driving to
+}
+
+/// Thrust below which a tailing off engine counts as out, as a fraction
+/// of full thrust
+const OUT_FRACTION: f64 = 0.01;
+
+/// Fraction of the throttle setting at which ignition is complete
+const RUNNING_FRACTION: f64 = 0.9;
+
+pub struct EngineModel {
+    config: EngineConfig,
+    state: EngineState,
+    setting: f64, // N, throttle setting commanded so far
+    thrust: f64,  // N produced
+    time: f64,    // s the model has run
+    since: f64,   // s since the last ENGINE ON or OFF took effect
+}
+
+impl EngineModel {
+    /// Engine off, throttle at its minimum
+    pub fn new(config: EngineConfig) -> Self {
+        Self {
+            config,
+            state: EngineState::Off,
+            setting: config.min_thrust,
+            thrust: 0.0,
+            time: 0.0,
+            since: 0.0,
+        }
+    }
+
+    pub fn state(&self) -> EngineState {
+        self.state
+    }
+
+    /// Thrust being produced, N
+    pub fn thrust(&self) -> f64 {
+        self.thrust
+    }
+
+    /// Throttle setting the software has commanded, N
+    pub fn setting(&self) -> f64 {
+        self.setting
+    }
+
+    /// Take the commands the software has given since the last call, then
+    /// advance `dt` seconds. Returns the thrust at the end, N.
+    pub fn update(
+        &mut self,
+        mem: &mut MemoryMap,
+        dt: f64,
+        mut emit: impl FnMut(EngineEvent),
+    ) -> f64 {
+        // ENGINE OFF wins if the software sets both
+        let chan11 = mem.peek_channel(ports::CHANNEL_DSALMOUT);
+        match self.state {
+            EngineState::Igniting | EngineState::Running
+                if chan11 & ports::CHAN11_ENGINE_OFF != 0 =>
+            {
+                self.enter(EngineState::TailingOff, &mut emit)
+            }
+            EngineState::Off | EngineState::TailingOff
+                if chan11 & ports::CHAN11_ENGINE_OFF == 0
+                    && chan11 & ports::CHAN11_ENGINE_ON != 0 =>
+            {
+                self.enter(EngineState::Igniting, &mut emit)
+            }
+            _ => {}
+        }
+
+        if mem.peek_channel(ports::CHANNEL_CHAN14) & ports::CHAN14_DRIVE_THRUST != 0 {
+            let word = mem.drain_output_counter(SPECIAL_REGISTER_THRUST);
+            let pulses = Word15::new(word).to_signed().get();
+            if pulses != 0 {
+                self.setting = (self.setting + pulses as f64 * self.config.pulse_thrust)
+                    .clamp(self.config.min_thrust, self.config.max_thrust);
+                emit(EngineEvent::Throttle {
+                    at: self.time,
+                    setting: self.setting,
+                });
+            }
+        }
+
+        self.time += dt;
+        self.since += dt;
+        let config = self.config;
+        match self.state {
+            EngineState::Off => self.thrust = 0.0,
+            EngineState::Igniting => {
+                let building = dt.min(self.since - config.ignition_delay);
+                if building > 0.0 {
+                    self.thrust = approach(self.thrust, self.setting, building, config.rise);
+                }
+                if self.thrust >= self.setting * RUNNING_FRACTION {
+                    self.enter(EngineState::Running, &mut emit);
+                }
+            }
+            EngineState::Running => {
+                self.thrust = approach(self.thrust, self.setting, dt, config.throttle_lag)
+            }
+            EngineState::TailingOff => {
+                self.thrust = approach(self.thrust, 0.0, dt, config.tailoff);
+                if self.thrust < config.max_thrust * OUT_FRACTION {
+                    self.thrust = 0.0;
+                    self.enter(EngineState::Off, &mut emit);
+                }
+            }
+        }
+        self.thrust
+    }
+
+    fn enter(&mut self, state: EngineState, emit: &mut impl FnMut(EngineEvent)) {
+        self.state = state;
+        self.since = 0.0;
+        emit(EngineEvent::State {
+            at: self.time,
+            state,
+        });
+    }
+}
+
+/// First order lag: `value` after `dt` seconds heading for `target`
+fn approach(value: f64, target: f64, dt: f64, tau: f64) -> f64 {
+    match tau > 0.0 {
+        true => value + (target - value) * (1.0 - (-dt / tau).exp()),
+        false => target,
+    }
+}
+
+#[cfg(test)]
+mod engine_tests {
+    use super::{EngineConfig, EngineEvent, EngineModel, EngineState};
+    use ragc_core::constants::ports;
+    use ragc_core::constants::special_registers::SPECIAL_REGISTER_THRUST;
+    use ragc_core::memory::MemoryMap;
+    use std::vec::Vec;
+
+    #[test]
+    fn thrust_lags_the_commands() {
+        let mut mem = MemoryMap::new_blank();
+        let mut engine = EngineModel::new(EngineConfig::DPS);
+        let mut events = Vec::new();
+        let mut run = |engine: &mut EngineModel, mem: &mut MemoryMap, seconds: f64| {
+            for _ in 0..(seconds / 0.01).round() as usize {
+                engine.update(mem, 0.01, |e| events.push(e));
+            }
+        };
+
+        // Nothing before the valves open, then a build-up to the setting
+        mem.write_io(ports::CHANNEL_DSALMOUT, ports::CHAN11_ENGINE_ON);
+        run(&mut engine, &mut mem, 0.2);
+        assert_eq!(
+            (engine.state(), engine.thrust()),
+            (EngineState::Igniting, 0.0)
+        );
+        run(&mut engine, &mut mem, 2.0);
+        assert_eq!(engine.state(), EngineState::Running);
+        assert!(
+            (engine.thrust() - 4504.0).abs() < 10.0,
+            "{}",
+            engine.thrust()
+        );
+
+        // Throttle up 1000 pulses: the setting jumps, thrust follows
+        mem.write(SPECIAL_REGISTER_THRUST, 1000);
+        mem.write_io(ports::CHANNEL_CHAN14, ports::CHAN14_DRIVE_THRUST);
+        run(&mut engine, &mut mem, 0.2);
+        assert_eq!(engine.setting(), 4504.0 + 12450.0);
+        assert!(engine.thrust() > 4504.0 + 6000.0 && engine.thrust() < 4504.0 + 9000.0);
+
+        // ENGINE OFF overrides ON, and the thrust tails off
+        mem.write_io(
+            ports::CHANNEL_DSALMOUT,
+            ports::CHAN11_ENGINE_ON | ports::CHAN11_ENGINE_OFF,
+        );
+        run(&mut engine, &mut mem, 0.1);
+        assert_eq!(engine.state(), EngineState::TailingOff);
+        assert!(engine.thrust() > 0.0);
+        run(&mut engine, &mut mem, 1.0);
+        assert_eq!((engine.state(), engine.thrust()), (EngineState::Off, 0.0));
+
+        let states: Vec<_> = events
+            .iter()
+            .filter_map(|e| match e {
+                EngineEvent::State { state, .. } => Some(*state),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            states,
+            [
+                EngineState::Igniting,
+                EngineState::Running,
+                EngineState::TailingOff,
+                EngineState::Off
+            ]
+        );
+        assert!(matches!(
+            events[2],
+            EngineEvent::Throttle { setting, at } if setting == 16954.0 && (at - 2.2).abs() < 1e-6
+        ));
+    }
+}
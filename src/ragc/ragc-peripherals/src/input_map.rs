@@ -0,0 +1,287 @@
+//! Host input mapping for frontends. A TOML file binds named host inputs
+//! (keyboard keys, gamepad buttons and sticks) to DSKY keys, discretes and
+//! hand controller axes; frontends translate their events to the names and
+//! ask the map what to drive.
+//!
+//! ```toml
+//! [buttons]
+//! V = "VERB"
+//! Enter = "ENTR"
+//! Space = "PRO"                  # Held while the input is held
+//! F1 = "discrete:abort"          # Likewise
+//! ArrowUp = "axis:rhc-pitch:1.0" # Deflection while held
+//! "pad:South" = "discrete:abort-stage"
+//!
+//! [axes."pad:LeftStickX"]
+//! axis = "rhc-roll"
+//! scale = 1.0      # Default 1
+//! deadband = 0.05  # Default 0
+//! ```
+use ragc_core::memory::channels::{Axis, Discrete};
+use serde::Deserialize;
+
+use std::collections::BTreeMap;
+use std::format;
+use std::path::PathBuf;
+use std::string::{String, ToString};
+use std::time::SystemTime;
+
+/// DSKY keys by their legend
+const DSKY_KEYS: [(&str, u16); 18] = [
+    ("VERB", 0o21),
+    ("NOUN", 0o37),
+    ("ENTR", 0o34),
+    ("CLR", 0o36),
+    ("RSET", 0o22),
+    ("KEY REL", 0o31),
+    ("+", 0o32),
+    ("-", 0o33),
+    ("0", 0o20),
+    ("1", 1),
+    ("2", 2),
+    ("3", 3),
+    ("4", 4),
+    ("5", 5),
+    ("6", 6),
+    ("7", 7),
+    ("8", 8),
+    ("9", 9),
+];
+
+/// Keyboard layout used without a mapping file
+const DEFAULT_BUTTONS: [(&str, &str); 19] = [
+    ("V", "VERB"),
+    ("N", "NOUN"),
+    ("Enter", "ENTR"),
+    ("Backspace", "CLR"),
+    ("R", "RSET"),
+    ("K", "KEY REL"),
+    ("P", "PRO"),
+    ("Plus", "+"),
+    ("Minus", "-"),
+    ("0", "0"),
+    ("1", "1"),
+    ("2", "2"),
+    ("3", "3"),
+    ("4", "4"),
+    ("5", "5"),
+    ("6", "6"),
+    ("7", "7"),
+    ("8", "8"),
+    ("9", "9"),
+];
+
+/// What a host button drives
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Action {
+    Key(u16),           // DSKY keycode, sent on press
+    Proceed,            // PRO, held with the button
+    Discrete(Discrete), // Asserted while held
+    Deflect(Axis, f64), // Axis deflection while held, -1 to 1
+}
+
+/// Analog input bound to a hand controller axis
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct AxisBinding {
+    pub axis: Axis,
+    pub scale: f64,
+    pub deadband: f64,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct MapFile {
+    #[serde(default)]
+    buttons: BTreeMap<String, String>,
+    #[serde(default)]
+    axes: BTreeMap<String, AxisFile>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct AxisFile {
+    axis: String,
+    scale: Option<f64>,
+    deadband: Option<f64>,
+}
+
+fn parse_action(target: &str) -> Result<Action, String> {
+    if target == "PRO" {
+        return Ok(Action::Proceed);
+    }
+    if let Some(name) = target.strip_prefix("discrete:") {
+        return Discrete::from_name(name)
+            .map(Action::Discrete)
+            .ok_or_else(|| format!("Unknown discrete: {}", name));
+    }
+    if let Some(rest) = target.strip_prefix("axis:") {
+        let (name, value) = rest.split_once(':').unwrap_or((rest, "1"));
+        let axis = Axis::from_name(name).ok_or_else(|| format!("Unknown axis: {}", name))?;
+        return match value.parse::<f64>() {
+            Ok(v) if (-1.0..=1.0).contains(&v) => Ok(Action::Deflect(axis, v)),
+            _ => Err(format!("Deflection must be from -1 to 1: {}", value)),
+        };
+    }
+    DSKY_KEYS
+        .iter()
+        .find(|(legend, _)| *legend == target)
+        .map(|(_, code)| Action::Key(*code))
+        .ok_or_else(|| format!("Unknown DSKY key: {}", target))
+}
+
+/// Bindings from host input names to actions
+#[derive(Clone, PartialEq, Debug)]
+pub struct InputMap {
+    buttons: BTreeMap<String, Action>,
+    axes: BTreeMap<String, AxisBinding>,
+}
+
+impl Default for InputMap {
+    fn default() -> Self {
+        let buttons = DEFAULT_BUTTONS
+            .iter()
+            .map(|(input, target)| (input.to_string(), parse_action(target).unwrap()))
+            .collect();
+        Self {
+            buttons,
+            axes: BTreeMap::new(),
+        }
+    }
+}
+
+impl InputMap {
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let file: MapFile = toml::from_str(text).map_err(|e| e.to_string())?;
+        let mut buttons = BTreeMap::new();
+        for (input, target) in file.buttons.iter() {
+            let action = parse_action(target).map_err(|e| format!("{}: {}", input, e))?;
+            buttons.insert(input.clone(), action);
+        }
+        let mut axes = BTreeMap::new();
+        for (input, binding) in file.axes.iter() {
+            let axis = Axis::from_name(&binding.axis)
+                .ok_or_else(|| format!("{}: Unknown axis: {}", input, binding.axis))?;
+            let deadband = binding.deadband.unwrap_or(0.0);
+            if !(0.0..1.0).contains(&deadband) {
+                return Err(format!("{}: Deadband must be from 0 to 1", input));
+            }
+            let scale = binding.scale.unwrap_or(1.0);
+            axes.insert(
+                input.clone(),
+                AxisBinding {
+                    axis,
+                    scale,
+                    deadband,
+                },
+            );
+        }
+        Ok(Self { buttons, axes })
+    }
+
+    /// Action for a host button, by name
+    pub fn button(&self, input: &str) -> Option<Action> {
+        self.buttons.get(input).copied()
+    }
+
+    /// Axis and deflection for an analog input reading from -1 to 1. The
+    /// deadband is cut out and the rest rescaled, so travel stays smooth.
+    pub fn axis(&self, input: &str, value: f64) -> Option<(Axis, f64)> {
+        let binding = self.axes.get(input)?;
+        let magnitude = match value < 0.0 {
+            true => -value,
+            false => value,
+        };
+        let live = (magnitude - binding.deadband).max(0.0) / (1.0 - binding.deadband);
+        let deflection = (live * binding.scale).clamp(0.0, 1.0);
+        match value < 0.0 {
+            true => Some((binding.axis, -deflection)),
+            false => Some((binding.axis, deflection)),
+        }
+    }
+}
+
+/// Mapping file that is re-read when it changes, so bindings can be tuned
+/// during a session
+pub struct InputMapFile {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+    map: InputMap,
+}
+
+impl InputMapFile {
+    pub fn load(path: impl Into<PathBuf>) -> Result<Self, String> {
+        let mut file = Self {
+            path: path.into(),
+            modified: None,
+            map: InputMap::default(),
+        };
+        file.reload()?;
+        Ok(file)
+    }
+
+    pub fn map(&self) -> &InputMap {
+        &self.map
+    }
+
+    fn reload(&mut self) -> Result<(), String> {
+        let path = self.path.display();
+        self.modified = std::fs::metadata(&self.path)
+            .and_then(|m| m.modified())
+            .ok();
+        let text = std::fs::read_to_string(&self.path).map_err(|e| format!("{}: {}", path, e))?;
+        self.map = InputMap::parse(&text).map_err(|e| format!("{}: {}", path, e))?;
+        Ok(())
+    }
+
+    /// Re-read the file if it changed on disk. Returns None when unchanged;
+    /// a file that fails to parse leaves the previous bindings in place.
+    pub fn reload_if_changed(&mut self) -> Option<Result<(), String>> {
+        let modified = std::fs::metadata(&self.path)
+            .and_then(|m| m.modified())
+            .ok();
+        if modified == self.modified {
+            return None;
+        }
+        Some(self.reload())
+    }
+}
+
+#[cfg(test)]
+mod input_map_tests {
+    use super::{Action, InputMap};
+    use ragc_core::memory::channels::{Axis, Discrete};
+
+    #[test]
+    fn binds_keys_discretes_and_axes() {
+        let map = InputMap::parse(
+            r#"
+            [buttons]
+            V = "VERB"
+            Space = "PRO"
+            F1 = "discrete:abort"
+            ArrowLeft = "axis:thc-y:-1"
+
+            [axes."pad:LeftStickX"]
+            axis = "rhc-roll"
+            deadband = 0.5
+            "#,
+        )
+        .unwrap();
+        assert_eq!(map.button("V"), Some(Action::Key(0o21)));
+        assert_eq!(map.button("Space"), Some(Action::Proceed));
+        assert_eq!(map.button("F1"), Some(Action::Discrete(Discrete::Abort)));
+        assert_eq!(
+            map.button("ArrowLeft"),
+            Some(Action::Deflect(Axis::ThcY, -1.0))
+        );
+        assert_eq!(map.button("N"), None);
+        assert_eq!(map.axis("pad:LeftStickX", 0.1), Some((Axis::RhcRoll, 0.0)));
+        assert_eq!(
+            map.axis("pad:LeftStickX", -0.75),
+            Some((Axis::RhcRoll, -0.5))
+        );
+
+        assert!(InputMap::parse("[buttons]\nX = \"discrete:nope\"").is_err());
+        assert_eq!(InputMap::default().button("Enter"), Some(Action::Key(0o34)));
+    }
+}
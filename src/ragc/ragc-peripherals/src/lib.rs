@@ -2,4 +2,22 @@
 
 extern crate std;
 
+pub mod dap;
+pub mod downlist;
+pub mod engine;
+#[cfg(feature = "input-map")]
+pub mod input_map;
+#[cfg(feature = "std")]
+pub mod pacing;
+#[cfg(feature = "std")]
+pub mod shared_dsky;
+pub mod tapemeter;
+#[cfg(feature = "tokio")]
+pub mod tokio_host;
 mod utils;
+pub use utils::digit_7seg;
+
+#[cfg(feature = "vagc-peripherals")]
+mod vagc;
+#[cfg(feature = "vagc-peripherals")]
+pub use vagc::*;
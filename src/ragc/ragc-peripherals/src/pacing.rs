@@ -0,0 +1,234 @@
+//! Real-time pacing for host loops. The emulator runs in frames, catching
+//! up with the wall clock in each; a pacer decides how the host waits
+//! between them. Sleeping is cheap but wakes as late as the OS scheduler
+//! likes, which shows as uneven DSKY flashing; spinning is exact but takes
+//! a core. A GUI can instead tick frames from its vsync.
+use std::boxed::Box;
+use std::fmt;
+use std::string::String;
+use std::sync::mpsc::{sync_channel, Receiver, RecvTimeoutError, SyncSender};
+use std::time::{Duration, Instant};
+
+/// Frame length for the sleeping strategies
+pub const DEFAULT_FRAME: Duration = Duration::from_millis(5);
+
+/// Tail of a hybrid frame spent spinning, beyond the scheduler's usual
+/// oversleep
+pub const HYBRID_SPIN: Duration = Duration::from_millis(1);
+
+/// Waits between the frames of a host loop
+pub trait Pacer: Send {
+    /// Return once the next frame is due, the last having run at `last`
+    fn wait(&mut self, last: Instant);
+}
+
+/// Busy-waits: lowest jitter, one core fully used
+pub struct Spin;
+
+impl Pacer for Spin {
+    fn wait(&mut self, _last: Instant) {
+        core::hint::spin_loop();
+    }
+}
+
+/// Sleeps out each frame: least CPU, jitter of the scheduler's wakeups
+pub struct Sleep {
+    pub frame: Duration,
+}
+
+impl Pacer for Sleep {
+    fn wait(&mut self, last: Instant) {
+        std::thread::sleep(self.frame.saturating_sub(last.elapsed()));
+    }
+}
+
+/// Sleeps through most of each frame and spins the rest
+pub struct Hybrid {
+    pub frame: Duration,
+    pub spin: Duration,
+}
+
+impl Pacer for Hybrid {
+    fn wait(&mut self, last: Instant) {
+        let due = last + self.frame;
+        std::thread::sleep(self.frame.saturating_sub(self.spin + last.elapsed()));
+        while Instant::now() < due {
+            core::hint::spin_loop();
+        }
+    }
+}
+
+/// Runs a frame per tick from outside, typically a GUI's vsync. Without
+/// ticks it falls back to sleeping, so a hidden window still runs.
+pub struct ExternalTick {
+    ticks: Receiver<()>,
+    fallback: Duration,
+}
+
+/// Sending half for `ExternalTick`; ticks never block the sender
+#[derive(Clone)]
+pub struct Ticker(SyncSender<()>);
+
+impl Ticker {
+    pub fn tick(&self) {
+        let _ = self.0.try_send(());
+    }
+}
+
+impl ExternalTick {
+    /// Pacer and its ticker, sleeping `fallback` when ticks stop
+    pub fn new(fallback: Duration) -> (Self, Ticker) {
+        let (tx, ticks) = sync_channel(1);
+        (Self { ticks, fallback }, Ticker(tx))
+    }
+}
+
+impl Pacer for ExternalTick {
+    fn wait(&mut self, last: Instant) {
+        let timeout = self.fallback.saturating_sub(last.elapsed());
+        if let Err(RecvTimeoutError::Disconnected) = self.ticks.recv_timeout(timeout) {
+            std::thread::sleep(timeout);
+        }
+    }
+}
+
+/// Pacing strategies by name, as configured
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Strategy {
+    Spin,
+    Sleep,
+    Hybrid,
+    External,
+}
+
+impl core::str::FromStr for Strategy {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, String> {
+        match s {
+            "spin" => Ok(Strategy::Spin),
+            "sleep" => Ok(Strategy::Sleep),
+            "hybrid" => Ok(Strategy::Hybrid),
+            "external" => Ok(Strategy::External),
+            _ => Err(std::format!("Unknown pacing strategy: {}", s)),
+        }
+    }
+}
+
+impl Strategy {
+    /// Pacer for `frame`-long frames; an external one comes with its ticker
+    pub fn pacer(self, frame: Duration) -> (Box<dyn Pacer>, Option<Ticker>) {
+        match self {
+            Strategy::Spin => (Box::new(Spin), None),
+            Strategy::Sleep => (Box::new(Sleep { frame }), None),
+            Strategy::Hybrid => {
+                let spin = HYBRID_SPIN.min(frame);
+                (Box::new(Hybrid { frame, spin }), None)
+            }
+            Strategy::External => {
+                let (pacer, ticker) = ExternalTick::new(frame);
+                (Box::new(pacer), Some(ticker))
+            }
+        }
+    }
+}
+
+/// Spread of the intervals between frame starts
+#[derive(Default, Clone, Debug)]
+pub struct Jitter {
+    last: Option<Instant>,
+    frames: u64,
+    sum: f64, // Seconds, over intervals
+    sum_sq: f64,
+    worst: Duration, // Longest interval
+}
+
+impl Jitter {
+    /// Note a frame starting at `now`
+    pub fn record(&mut self, now: Instant) {
+        if let Some(last) = self.last.replace(now) {
+            let interval = now - last;
+            let secs = interval.as_secs_f64();
+            self.frames += 1;
+            self.sum += secs;
+            self.sum_sq += secs * secs;
+            self.worst = self.worst.max(interval);
+        }
+    }
+
+    /// Intervals measured
+    pub fn frames(&self) -> u64 {
+        self.frames
+    }
+
+    pub fn mean(&self) -> Duration {
+        Duration::from_secs_f64(self.sum / self.frames.max(1) as f64)
+    }
+
+    /// Standard deviation of the intervals
+    pub fn deviation(&self) -> Duration {
+        let n = self.frames.max(1) as f64;
+        let mean = self.sum / n;
+        Duration::from_secs_f64((self.sum_sq / n - mean * mean).max(0.0).sqrt())
+    }
+
+    pub fn worst(&self) -> Duration {
+        self.worst
+    }
+}
+
+impl fmt::Display for Jitter {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let ms = |d: Duration| d.as_secs_f64() * 1e3;
+        write!(
+            f,
+            "{} frames, {:.3} ms apart, {:.3} ms deviation, {:.3} ms worst",
+            self.frames,
+            ms(self.mean()),
+            ms(self.deviation()),
+            ms(self.worst)
+        )
+    }
+}
+
+#[cfg(test)]
+mod pacing_tests {
+    use super::{Jitter, Strategy};
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn strategies_pace_frames() {
+        let frame = Duration::from_millis(2);
+        for name in ["sleep", "hybrid"] {
+            let (mut pacer, ticker) = name.parse::<Strategy>().unwrap().pacer(frame);
+            assert!(ticker.is_none());
+            let last = Instant::now();
+            pacer.wait(last);
+            assert!(last.elapsed() >= frame, "{}", name);
+        }
+
+        // Ticks wake an external pacer early; without them it sleeps a frame
+        let (mut pacer, ticker) = Strategy::External.pacer(Duration::from_secs(5));
+        let ticker = ticker.unwrap();
+        ticker.tick();
+        ticker.tick(); // Coalesced with the first
+        let last = Instant::now();
+        pacer.wait(last);
+        assert!(last.elapsed() < Duration::from_secs(1));
+        drop(ticker);
+        let (mut pacer, _) = Strategy::External.pacer(frame);
+        let last = Instant::now();
+        pacer.wait(last);
+        assert!(last.elapsed() >= frame);
+        assert!("vsync".parse::<Strategy>().is_err());
+
+        let mut jitter = Jitter::default();
+        let start = Instant::now();
+        for ms in [0, 4, 10, 14] {
+            jitter.record(start + Duration::from_millis(ms));
+        }
+        assert_eq!(jitter.frames(), 3);
+        assert!((jitter.mean().as_secs_f64() - 0.014 / 3.0).abs() < 1e-9);
+        assert_eq!(jitter.worst(), Duration::from_millis(6));
+        assert!((jitter.deviation().as_secs_f64() - 0.002 * 2f64.sqrt() / 3.0).abs() < 1e-9);
+    }
+}
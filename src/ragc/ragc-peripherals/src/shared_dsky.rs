@@ -0,0 +1,193 @@
+//! DSKY state shared with GUI threads. The emulation thread keeps it up to
+//! date through channel taps; a GUI copies it out whenever it draws, at its
+//! own frame rate, without channels and without holding up the CPU.
+//! Relay words land a display frame at a time, so a GUI never draws a
+//! register half rewritten.
+use dsky_protocol::pinball::DisplayState;
+use ragc_core::constants::ports::{
+    CHANNEL_DSALMOUT, CHANNEL_DSKY, CHANNEL_DSKY_LIGHTS, DSKY_LIGHT_EL_OFF, DSKY_LIGHT_LAMPS,
+};
+use ragc_core::memory::relay::DisplayFrame;
+use ragc_core::memory::tap::{ChannelTap, TapAction};
+
+use std::sync::{Arc, PoisonError, RwLock};
+
+/// Relay row 12 drives the caution lamps (PROG, TRACKER, ...)
+const RELAY_ROW_LIGHTS: u16 = 12;
+const RELAY_LAMPS: u16 = 0o677;
+
+/// Channel 11 bits that light DSKY lamps
+const DSALMOUT_LAMPS: u16 = 0o176;
+
+/// Everything a DSKY face shows
+#[derive(Clone, Copy, Default)]
+pub struct DskyState {
+    pub display: DisplayState, // PROG, VERB, NOUN and the registers
+    pub relay_lights: u16,     // Relay row 12 lamp bits
+    pub lamps: u16,            // Channel 11: COMP ACTY, UPLINK ACTY, KEY REL, flash
+    pub lights: u16,           // Channel 163: STBY and the other frontend lights
+    pub updates: u64,          // Changes so far, to skip redrawing unchanged frames
+    pub dim: f32,              // Panel dimmer, from 0.0 (full) to 1.0 (dark)
+}
+
+impl DskyState {
+    /// Brightness of the numeric display from 0.0 to 1.0: the dimmer
+    /// setting while it has power, dark with EL OFF (as in standby)
+    pub fn brightness(&self) -> f32 {
+        match self.lights & DSKY_LIGHT_EL_OFF {
+            0 => 1.0 - self.dim,
+            _ => 0.0,
+        }
+    }
+
+    /// Whether every segment and lamp is lit, as V35 leaves the DSKY
+    pub fn lamp_test(&self) -> bool {
+        self.display.all_eights()
+            && self.relay_lights & RELAY_LAMPS == RELAY_LAMPS
+            && self.lamps & DSALMOUT_LAMPS == DSALMOUT_LAMPS
+            && self.lights & DSKY_LIGHT_LAMPS == DSKY_LIGHT_LAMPS
+    }
+}
+
+/// Cloneable handle on the shared state. Register `taps()` with the memory
+/// map on the emulation side; call `snapshot()` from the GUI.
+#[derive(Clone, Default)]
+pub struct SharedDsky {
+    state: Arc<RwLock<DskyState>>,
+}
+
+impl SharedDsky {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Copy of the current state
+    pub fn snapshot(&self) -> DskyState {
+        *self.state.read().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Turn the panel dimmer, clamped to 0.0 (full) through 1.0 (dark)
+    pub fn set_dimmer(&self, dim: f32) {
+        let mut state = self.state.write().unwrap_or_else(PoisonError::into_inner);
+        state.dim = dim.clamp(0.0, 1.0);
+        state.updates += 1;
+    }
+
+    /// One tap per DSKY output channel, each sharing this state
+    pub fn taps(&self) -> [(usize, SharedDsky); 3] {
+        [
+            (CHANNEL_DSKY, self.clone()),
+            (CHANNEL_DSALMOUT, self.clone()),
+            (CHANNEL_DSKY_LIGHTS, self.clone()),
+        ]
+    }
+
+    fn apply(&self, channel: usize, value: u16) {
+        let mut state = self.state.write().unwrap_or_else(PoisonError::into_inner);
+        match channel {
+            CHANNEL_DSALMOUT => state.lamps = value,
+            CHANNEL_DSKY_LIGHTS => state.lights = value,
+            _ => return,
+        }
+        state.updates += 1;
+    }
+}
+
+impl ChannelTap for SharedDsky {
+    fn write(&mut self, channel: usize, value: u16) -> TapAction {
+        self.apply(channel, value);
+        TapAction::Pass(value)
+    }
+
+    fn display_frame(&mut self, frame: &DisplayFrame) {
+        let mut state = self.state.write().unwrap_or_else(PoisonError::into_inner);
+        for &word in frame.words() {
+            match word >> 11 {
+                RELAY_ROW_LIGHTS => state.relay_lights = word & 0o3777,
+                _ => state.display.apply_relay_word(word),
+            }
+        }
+        state.updates += 1;
+    }
+}
+
+#[cfg(test)]
+mod shared_dsky_tests {
+    use super::SharedDsky;
+    use ragc_core::constants::ports::{
+        CHAN13_TEST_ALARMS, CHANNEL_CHAN13, CHANNEL_DSALMOUT, CHANNEL_DSKY, CHANNEL_DSKY_LIGHTS,
+        DSKY_LIGHT_AGC_WARNING,
+    };
+    use ragc_core::memory::mods::RuptRequest;
+    use ragc_core::memory::{MemoryMap, MemoryMapBuilder};
+
+    #[test]
+    fn gui_thread_sees_emulator_writes() {
+        let shared = SharedDsky::new();
+        let mut taps = shared.taps();
+        {
+            let mut builder = MemoryMapBuilder::new();
+            for (channel, tap) in taps.iter_mut() {
+                builder = builder.tap(*channel, tap);
+            }
+            let mut mem = builder.build();
+            mem.write_io(CHANNEL_DSKY, (10 << 11) | (0o03 << 5) | 0o34); // VERB 16
+            mem.write_io(CHANNEL_DSKY, (12 << 11) | 0o400); // PROG light
+            assert_eq!(shared.snapshot().display.verb(), None);
+            mem.request_rupt(RuptRequest::T4rupt); // Ends the display frame
+            mem.write_io(CHANNEL_DSALMOUT, 0o20);
+        }
+
+        let gui = shared.clone();
+        let state = std::thread::spawn(move || gui.snapshot()).join().unwrap();
+        assert_eq!(state.display.verb(), Some(16));
+        assert_eq!(state.relay_lights, 0o400);
+        assert_eq!(state.lamps, 0o20);
+        assert_eq!(state.updates, 2);
+    }
+
+    // V35's channel traffic: 8 and a plus sign in every digit, every relay
+    // and channel 11 lamp, and TEST ALARMS for the channel 163 lamps
+    fn lamp_test(mem: &mut MemoryMap, on: bool) {
+        let (digits, sign, relay, lamps, chan13) = match on {
+            true => ((0o35 << 5) | 0o35, 0o2000, 0o677, 0o176, CHAN13_TEST_ALARMS),
+            false => (0, 0, 0, 0, 0),
+        };
+        for row in 1..=11 {
+            mem.write_io(CHANNEL_DSKY, (row << 11) | sign | digits);
+        }
+        mem.write_io(CHANNEL_DSKY, (12 << 11) | relay);
+        mem.write_io(CHANNEL_DSALMOUT, lamps);
+        mem.write_io(CHANNEL_CHAN13, chan13);
+        mem.request_rupt(RuptRequest::T4rupt);
+    }
+
+    #[test]
+    fn lamp_test_lights_everything_then_clears() {
+        let shared = SharedDsky::new();
+        let mut taps = shared.taps();
+        let mut builder = MemoryMapBuilder::new();
+        for (channel, tap) in taps.iter_mut() {
+            builder = builder.tap(*channel, tap);
+        }
+        let mut mem = builder.build();
+        mem.write_io(CHANNEL_DSKY_LIGHTS, DSKY_LIGHT_AGC_WARNING);
+
+        lamp_test(&mut mem, true);
+        assert!(mem.lamp_test());
+        assert!(shared.snapshot().lamp_test());
+
+        lamp_test(&mut mem, false);
+        let state = shared.snapshot();
+        assert!(!mem.lamp_test() && !state.lamp_test());
+        assert_eq!(state.display.register_digits(0), [None; 5]);
+        assert_eq!((state.relay_lights, state.lamps), (0, 0));
+        assert_eq!(state.lights, DSKY_LIGHT_AGC_WARNING);
+
+        assert_eq!(state.brightness(), 1.0);
+        shared.set_dimmer(0.25);
+        assert_eq!(shared.snapshot().brightness(), 0.75);
+        mem.set_standby_light(true);
+        assert_eq!(shared.snapshot().brightness(), 0.0);
+    }
+}
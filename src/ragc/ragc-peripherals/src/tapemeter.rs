@@ -0,0 +1,90 @@
+//! LM altitude/altitude-rate tape meter. The software loads ALTM with one
+//! of the two readings, selects which with channel 14 bit 2 and sets bit 3
+//! to shift the word out; the hardware resets bit 3 once it has gone.
+use ragc_core::constants::ports;
+use ragc_core::constants::special_registers::SPECIAL_REGISTER_ALTITUDE;
+use ragc_core::memory::MemoryMap;
+use ragc_core::word::Word15;
+
+/// Tape meter scales: altitude ft per bit, altitude rate ft/s per bit
+pub const ALTITUDE_SCALE: f64 = 2.345;
+pub const ALTITUDE_RATE_SCALE: f64 = 0.5;
+
+/// One reading sent to the tape meter
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum TapeReading {
+    Altitude(f64),     // ft
+    AltitudeRate(f64), // ft/s, positive climbing
+}
+
+/// Latest values commanded on each tape
+#[derive(Clone, Copy, Default, Debug)]
+pub struct TapeMeter {
+    pub altitude: Option<f64>,
+    pub altitude_rate: Option<f64>,
+}
+
+impl TapeMeter {
+    /// Take the word the software is shifting out, if any. Call after
+    /// each instruction or frame.
+    pub fn update(&mut self, mem: &mut MemoryMap) -> Option<TapeReading> {
+        let chan14 = mem.peek_channel(ports::CHANNEL_CHAN14);
+        if chan14 & ports::CHAN14_ALTITUDE_METER == 0 {
+            return None;
+        }
+        let word = mem.drain_output_counter(SPECIAL_REGISTER_ALTITUDE);
+        mem.write_io(
+            ports::CHANNEL_CHAN14,
+            chan14 & !ports::CHAN14_ALTITUDE_METER,
+        );
+
+        let reading = match chan14 & ports::CHAN14_ALTITUDE_RATE {
+            0 => TapeReading::Altitude((word & 0o77777) as f64 * ALTITUDE_SCALE),
+            _ => {
+                let rate = Word15::new(word).to_signed().get();
+                TapeReading::AltitudeRate(rate as f64 * ALTITUDE_RATE_SCALE)
+            }
+        };
+        match reading {
+            TapeReading::Altitude(ft) => self.altitude = Some(ft),
+            TapeReading::AltitudeRate(fps) => self.altitude_rate = Some(fps),
+        }
+        Some(reading)
+    }
+}
+
+#[cfg(test)]
+mod tapemeter_tests {
+    use super::{TapeMeter, TapeReading};
+    use ragc_core::constants::ports;
+    use ragc_core::constants::special_registers::SPECIAL_REGISTER_ALTITUDE;
+    use ragc_core::memory::MemoryMap;
+
+    #[test]
+    fn decodes_altitude_and_rate_words() {
+        let mut mem = MemoryMap::new_blank();
+        let mut meter = TapeMeter::default();
+        mem.write(SPECIAL_REGISTER_ALTITUDE, 1000);
+        assert_eq!(meter.update(&mut mem), None, "not driven yet");
+
+        mem.write_io(ports::CHANNEL_CHAN14, ports::CHAN14_ALTITUDE_METER);
+        assert_eq!(meter.update(&mut mem), Some(TapeReading::Altitude(2345.0)));
+        assert_eq!(mem.peek_channel(ports::CHANNEL_CHAN14), 0);
+        assert_eq!(meter.update(&mut mem), None);
+
+        // Descending at 20 ft/s
+        mem.write(SPECIAL_REGISTER_ALTITUDE, 0o77777 - 40);
+        mem.write_io(
+            ports::CHANNEL_CHAN14,
+            ports::CHAN14_ALTITUDE_METER | ports::CHAN14_ALTITUDE_RATE,
+        );
+        assert_eq!(
+            meter.update(&mut mem),
+            Some(TapeReading::AltitudeRate(-20.0))
+        );
+        assert_eq!(
+            (meter.altitude, meter.altitude_rate),
+            (Some(2345.0), Some(-20.0))
+        );
+    }
+}
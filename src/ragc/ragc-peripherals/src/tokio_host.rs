@@ -0,0 +1,174 @@
+//! Async adapter for hosts on a tokio runtime. The emulator runs in real
+//! time on its own thread, never on a runtime worker, and never waits on the
+//! host: controls are taken between frames and telemetry goes out on a
+//! broadcast channel, where a lagging subscriber skips ahead rather than
+//! stalling the computer into a night watchman restart.
+use ragc_core::cpu::{Cpu, UnprogSequence};
+use ragc_core::frame::FrameIo;
+use ragc_core::memory::mods::EmuTime;
+use ragc_core::memory::rom::RopeImage;
+use ragc_core::memory::MemoryMapBuilder;
+
+use tokio::sync::{broadcast, mpsc};
+use tokio_stream::wrappers::BroadcastStream;
+
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+/// Emulated time run between controls and telemetry
+const FRAME: Duration = Duration::from_millis(10);
+const FRAME_MCTS: u32 = EmuTime::mcts_in_millis(10) as u32;
+
+/// Telemetry buffered for each subscriber before it starts losing items
+const TELEMETRY_DEPTH: usize = 4096;
+
+/// Inputs a host can send the computer
+#[derive(Clone, Copy, PartialEq, Debug)]
+#[non_exhaustive]
+pub enum Control {
+    Key(u16),                // DSKY keycode, one per frame
+    Discrete(usize, u16),    // Input channel level
+    Counter(UnprogSequence), // Counter pulse (PIPA, CDU...)
+    Pause(bool),
+    Stop,
+}
+
+/// What the computer sends back
+#[derive(Clone, Copy, PartialEq, Debug)]
+#[non_exhaustive]
+pub enum Telemetry {
+    Channel(usize, u16), // Channel write, downlink included
+    Frame(EmuTime),      // A frame has run; a heartbeat for host watchdogs
+}
+
+/// An emulator on its own thread, driven through async channels
+pub struct AgcHost {
+    control: mpsc::UnboundedSender<Control>,
+    telemetry: broadcast::Sender<Telemetry>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl AgcHost {
+    /// Power on `rope` and run it until stopped or dropped
+    pub fn spawn(rope: &'static RopeImage) -> Self {
+        let (control, inputs) = mpsc::unbounded_channel();
+        let (telemetry, _) = broadcast::channel(TELEMETRY_DEPTH);
+        let outputs = telemetry.clone();
+        let thread = std::thread::spawn(move || run(rope, inputs, outputs));
+        Self {
+            control,
+            telemetry,
+            thread: Some(thread),
+        }
+    }
+
+    /// Sink for controls; it never blocks, so it suits any task
+    pub fn controls(&self) -> mpsc::UnboundedSender<Control> {
+        self.control.clone()
+    }
+
+    /// Stream of telemetry from now on. Items a slow reader misses show up
+    /// as lag errors.
+    pub fn telemetry(&self) -> BroadcastStream<Telemetry> {
+        BroadcastStream::new(self.telemetry.subscribe())
+    }
+
+    /// Receiver for telemetry from now on, for hosts polling it themselves
+    pub fn subscribe(&self) -> broadcast::Receiver<Telemetry> {
+        self.telemetry.subscribe()
+    }
+
+    /// Stop the emulator and wait for its thread
+    pub fn stop(mut self) {
+        self.shutdown();
+    }
+
+    fn shutdown(&mut self) {
+        let _ = self.control.send(Control::Stop);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl Drop for AgcHost {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
+fn run(
+    rope: &'static RopeImage,
+    mut inputs: mpsc::UnboundedReceiver<Control>,
+    outputs: broadcast::Sender<Telemetry>,
+) {
+    let mut cpu = Cpu::new(MemoryMapBuilder::new().rope(rope).build());
+    cpu.reset();
+    let mut io = FrameIo::new();
+    let mut paused = false;
+    loop {
+        let start = Instant::now();
+        let mut next = inputs.try_recv().ok();
+        while let Some(control) = next.take() {
+            match control {
+                Control::Key(code) => {
+                    io.press_key(code);
+                }
+                Control::Discrete(channel, value) => {
+                    io.set_discrete(channel, value);
+                }
+                Control::Counter(seq) => {
+                    io.pulse(seq);
+                }
+                Control::Pause(on) => paused = on,
+                Control::Stop => return,
+            }
+            next = match paused {
+                // Wait for the host while halted, without spinning
+                true => Some(inputs.blocking_recv().unwrap_or(Control::Stop)),
+                false => inputs.try_recv().ok(),
+            };
+        }
+        if inputs.is_closed() && inputs.is_empty() {
+            return;
+        }
+
+        cpu.run_frame(&mut io, FRAME_MCTS);
+        // Sending only fails without subscribers, who would drop it anyway
+        for (channel, value) in io.channel_writes.iter() {
+            let _ = outputs.send(Telemetry::Channel(*channel, *value));
+        }
+        let _ = outputs.send(Telemetry::Frame(cpu.memory().now()));
+        std::thread::sleep(FRAME.saturating_sub(start.elapsed()));
+    }
+}
+
+#[cfg(test)]
+mod tokio_host_tests {
+    use super::{AgcHost, Control, Telemetry};
+    use ragc_core::constants::ports::{CHANNEL_CHAN30, CHANNEL_DSALMOUT};
+    use ragc_testutils::rope_image;
+    use std::boxed::Box;
+
+    #[test]
+    fn host_streams_channel_writes() {
+        // At 4000: READ 30, WRITE 11, TCF 4000
+        let rope = rope_image(0o4000, &[0o6, 0o30, 0o6, 0o1011, 0o14000]);
+        let host = AgcHost::spawn(Box::leak(rope));
+        let mut telemetry = host.subscribe();
+        host.controls()
+            .send(Control::Discrete(CHANNEL_CHAN30, 0o12345))
+            .unwrap();
+
+        let mut frames = 0;
+        loop {
+            match telemetry.blocking_recv().unwrap() {
+                Telemetry::Channel(CHANNEL_DSALMOUT, 0o12345) => break,
+                Telemetry::Frame(_) => frames += 1,
+                _ => {}
+            }
+            assert!(frames < 100, "Discrete never echoed");
+        }
+        host.stop();
+    }
+}
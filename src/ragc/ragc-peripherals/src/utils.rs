@@ -23,6 +23,14 @@ pub fn get_7seg(agc_val: u8) -> u8 {
     }
 }
 
+/// Segments lit for a decimal digit, bit 0 = a through bit 6 = g; blank for None
+pub fn digit_7seg(digit: Option<u8>) -> u8 {
+    match digit {
+        Some(d) if d < 10 => SEVEN_SEG_TABLE[d as usize],
+        _ => SEVEN_SEG_TABLE[10],
+    }
+}
+
 // Function to combine two 7-segment values (c and d) into a 16-bit value
 #[allow(dead_code)]
 pub fn get_7seg_value(c: u8, d: u8) -> u16 {
@@ -0,0 +1,147 @@
+//! In-process downlink consumer. Embedding applications read telemetry
+//! straight from the emulator, with no socket in between; the TCP
+//! telemetry thread is just one such consumer. Reads never hold up the
+//! emulator: pairs queue up to the configured depth and then drop.
+use crossbeam_channel::{Receiver, RecvTimeoutError, TryRecvError};
+use ragc_core::memory::downlink::DownlinkPair;
+
+use std::time::Duration;
+
+/// One downlink word, numbered from the start of its downlist
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct DownlinkWord {
+    pub index: usize, // 0 is the downlist ID, 1 the sync word
+    pub value: u16,
+}
+
+/// Receiving end of a `DownruptPeriph`'s downlink
+pub struct DownlinkReceiver {
+    rx: Receiver<DownlinkPair>,
+    word: usize,                  // Index of the next word
+    second: Option<DownlinkWord>, // Held until the first of a pair is read
+}
+
+impl DownlinkReceiver {
+    pub(crate) fn new(rx: Receiver<DownlinkPair>) -> Self {
+        Self {
+            rx,
+            word: 0,
+            second: None,
+        }
+    }
+
+    /// Next pair, waiting for it; None once the emulator hangs up
+    pub fn recv_pair(&self) -> Option<DownlinkPair> {
+        self.rx.recv().ok()
+    }
+
+    /// Next pair if one is queued. Never blocks, so it suits polling from
+    /// an async task or a frame loop.
+    pub fn try_recv_pair(&self) -> Option<DownlinkPair> {
+        self.rx.try_recv().ok()
+    }
+
+    /// Next word if one is queued; Err(true) once the emulator hangs up
+    pub fn try_recv(&mut self) -> Result<DownlinkWord, bool> {
+        if let Some(word) = self.second.take() {
+            return Ok(word);
+        }
+        match self.rx.try_recv() {
+            Ok(pair) => Ok(self.split(pair)),
+            Err(TryRecvError::Empty) => Err(false),
+            Err(TryRecvError::Disconnected) => Err(true),
+        }
+    }
+
+    /// Next word, waiting up to `timeout`; Err(true) once the emulator
+    /// hangs up
+    pub fn recv_timeout(&mut self, timeout: Duration) -> Result<DownlinkWord, bool> {
+        if let Some(word) = self.second.take() {
+            return Ok(word);
+        }
+        match self.rx.recv_timeout(timeout) {
+            Ok(pair) => Ok(self.split(pair)),
+            Err(RecvTimeoutError::Timeout) => Err(false),
+            Err(RecvTimeoutError::Disconnected) => Err(true),
+        }
+    }
+
+    /// Words queued now, without waiting for more
+    pub fn try_iter(&mut self) -> impl Iterator<Item = DownlinkWord> + '_ {
+        core::iter::from_fn(move || self.try_recv().ok())
+    }
+
+    /// Pairs queued, for telling a reader that falls behind
+    pub fn len(&self) -> usize {
+        self.rx.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.second.is_none() && self.rx.is_empty()
+    }
+
+    // Number the pair's words, returning the first and holding the second
+    fn split(&mut self, pair: DownlinkPair) -> DownlinkWord {
+        if !pair.order {
+            self.word = 0;
+        }
+        let [first, second] = [0, 1].map(|n| DownlinkWord {
+            index: self.word + n,
+            value: pair.words[n],
+        });
+        self.word += 2;
+        self.second = Some(second);
+        first
+    }
+}
+
+/// Words as they come, waiting for each; ends when the emulator hangs up
+impl Iterator for DownlinkReceiver {
+    type Item = DownlinkWord;
+
+    fn next(&mut self) -> Option<DownlinkWord> {
+        match self.second.take() {
+            Some(word) => Some(word),
+            None => self.rx.recv().ok().map(|pair| self.split(pair)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod downlink_tests {
+    use super::DownlinkWord;
+    use crate::downlist::{DownlistSynth, Pattern, DOWNLIST_SYNC, DOWNLIST_WORDS};
+    use crate::downrupt::{DownruptPeriph, DEFAULT_TELEMETRY_QUEUE};
+    use ragc_core::memory::mods::IoPeriph;
+    use ragc_core::rng::Rng;
+    use std::vec::Vec;
+
+    #[test]
+    fn consumes_words_in_process() {
+        let (mut periph, mut downlink) = DownruptPeriph::in_process(DEFAULT_TELEMETRY_QUEUE);
+        assert_eq!(downlink.try_recv(), Err(false));
+
+        let mut synth = DownlistSynth::new(0o77777, Pattern::Counter, Rng::new(1));
+        for _ in 0..DOWNLIST_WORDS / 2 + 1 {
+            periph.downlink(synth.next_pair());
+        }
+        let words: Vec<_> = downlink.try_iter().collect();
+        assert_eq!(words.len(), DOWNLIST_WORDS + 2);
+        assert_eq!(
+            words[1],
+            DownlinkWord {
+                index: 1,
+                value: DOWNLIST_SYNC
+            }
+        );
+        assert_eq!(words[7], DownlinkWord { index: 7, value: 7 });
+        assert_eq!(words[DOWNLIST_WORDS].index, 0);
+        assert!(downlink.is_empty());
+
+        periph.downlink(synth.next_pair());
+        drop(periph);
+        let rest: Vec<_> = downlink.by_ref().map(|w| w.index).collect();
+        assert_eq!(rest, [2, 3]);
+        assert_eq!(downlink.try_recv(), Err(true));
+    }
+}
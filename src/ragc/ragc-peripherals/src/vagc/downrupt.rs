@@ -1,61 +1,281 @@
 use dsky_protocol::agc::generate_dsky_packet;
 
-use crossbeam_channel::{unbounded, Receiver, Sender};
+use super::downlink::DownlinkReceiver;
+use super::queue::{queue, DropCounter, OverflowPolicy, QueueConfig, QueueSender};
+use ragc_core::logging::{error, info};
 use std::io::Write;
-use std::net::TcpListener;
+use std::net::{TcpListener, TcpStream};
+use std::string::{String, ToString};
+use std::thread::JoinHandle;
+use std::time::Instant;
+use std::vec::Vec;
 
-use ragc_core::memory::periph::IoPeriph;
+use ragc_core::constants::ports::{CHANNEL_CHAN34, CHANNEL_CHAN35};
+use ragc_core::memory::downlink::DownlinkPair;
+use ragc_core::memory::mods::IoPeriph;
+
+/// Default telemetry endpoint
+pub const DEFAULT_TELEMETRY_ADDR: &str = "127.0.0.1:19800";
+
+/// Word pairs held while no ground tool is connected, about 80 s of the
+/// 50 words per second downlink
+pub const DEFAULT_TELEMETRY_QUEUE: QueueConfig = QueueConfig::new(2048, OverflowPolicy::DropOldest);
+
+/// How the telemetry connection is established
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum TelemetryMode {
+    Listen,  // Accept ground tool connections on the address
+    Connect, // Dial out to a ground tool listening on the address
+}
+
+/// Byte framing of downlink words on the wire
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum TelemetryFraming {
+    Packet, // yaAGC 4-byte channel packets, as read by yaTelemetry
+    Words,  // Bare 15-bit downlink words, 2 bytes big-endian
+}
+
+impl core::str::FromStr for TelemetryMode {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, String> {
+        match s {
+            "listen" => Ok(TelemetryMode::Listen),
+            "connect" => Ok(TelemetryMode::Connect),
+            _ => Err(std::format!("Unknown telemetry mode: {}", s)),
+        }
+    }
+}
+
+impl core::str::FromStr for TelemetryFraming {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, String> {
+        match s {
+            "packet" => Ok(TelemetryFraming::Packet),
+            "words" => Ok(TelemetryFraming::Words),
+            _ => Err(std::format!("Unknown telemetry framing: {}", s)),
+        }
+    }
+}
+
+/// Telemetry endpoint settings
+#[derive(Clone, PartialEq, Debug)]
+pub struct TelemetryConfig {
+    pub addr: String,
+    pub mode: TelemetryMode,
+    pub framing: TelemetryFraming,
+    pub decimate: u32, // Forward one downlist in this many (1 = all)
+    pub max_bytes_per_sec: Option<u32>, // Link budget; pairs over it are dropped
+    pub words: Option<Vec<usize>>, // Downlist word indices to forward (None = all)
+    pub queue: QueueConfig, // Pairs waiting for the ground tool
+}
+
+impl Default for TelemetryConfig {
+    fn default() -> Self {
+        Self {
+            addr: DEFAULT_TELEMETRY_ADDR.to_string(),
+            mode: TelemetryMode::Listen,
+            framing: TelemetryFraming::Packet,
+            decimate: 1,
+            max_bytes_per_sec: None,
+            words: None,
+            queue: DEFAULT_TELEMETRY_QUEUE,
+        }
+    }
+}
+
+/// Thins the downlink for a slow link: whole downlists by decimation,
+/// words by index, then pairs by a token bucket over the byte budget
+pub struct TelemetryFilter {
+    decimate: u32,
+    max_bytes_per_sec: Option<u32>,
+    words: Option<Vec<usize>>,
+    downlists: u32, // Downlists started, the first counting as 1
+    word: usize,    // Index of the next word within its downlist
+    tokens: f64,    // Bytes the link can take now
+    last: Option<Instant>,
+    dropped: u64, // Pairs dropped over budget
+}
+
+impl TelemetryFilter {
+    pub fn new(config: &TelemetryConfig) -> Self {
+        Self {
+            decimate: config.decimate.max(1),
+            max_bytes_per_sec: config.max_bytes_per_sec,
+            words: config.words.clone(),
+            downlists: 0,
+            word: 0,
+            tokens: config.max_bytes_per_sec.unwrap_or(0) as f64,
+            last: None,
+            dropped: 0,
+        }
+    }
+
+    /// Pairs dropped because the byte budget was spent
+    pub fn dropped(&self) -> u64 {
+        self.dropped
+    }
+
+    /// Words of `pair` to send at `now`, with their channels, given the
+    /// framing's bytes per word
+    pub fn admit(
+        &mut self,
+        pair: DownlinkPair,
+        now: Instant,
+        word_bytes: usize,
+    ) -> impl Iterator<Item = (usize, u16)> {
+        if !pair.order {
+            self.downlists += 1;
+            self.word = 0;
+        }
+        let first = self.word;
+        self.word += 2;
+
+        let sampled = (self.downlists.max(1) - 1).is_multiple_of(self.decimate);
+        let wanted = |idx: usize| self.words.as_ref().is_none_or(|w| w.contains(&idx));
+        let mut out = [
+            (CHANNEL_CHAN34, pair.words[0], wanted(first)),
+            (CHANNEL_CHAN35, pair.words[1], wanted(first + 1)),
+        ];
+        if !sampled {
+            out.iter_mut().for_each(|w| w.2 = false);
+        }
+
+        if let Some(budget) = self.max_bytes_per_sec {
+            let elapsed = self.last.map_or(0.0, |last| (now - last).as_secs_f64());
+            self.last = Some(now);
+            self.tokens = (self.tokens + elapsed * budget as f64).min(budget as f64);
+            let bytes = (out.iter().filter(|w| w.2).count() * word_bytes) as f64;
+            if bytes > self.tokens {
+                self.dropped += 1;
+                out.iter_mut().for_each(|w| w.2 = false);
+            } else {
+                self.tokens -= bytes;
+            }
+        }
+        IntoIterator::into_iter(out)
+            .filter_map(|(channel, value, keep)| keep.then_some((channel, value)))
+    }
+}
+
+impl TelemetryFraming {
+    /// Encode one downlink channel write for the wire
+    pub fn encode(&self, channel_idx: usize, value: u16, buf: &mut [u8; 4]) -> usize {
+        match self {
+            TelemetryFraming::Packet => {
+                *buf = generate_dsky_packet(channel_idx, value);
+                4
+            }
+            TelemetryFraming::Words => {
+                buf[..2].copy_from_slice(&(value & 0o77777).to_be_bytes());
+                2
+            }
+        }
+    }
+}
 
 pub struct DownruptPeriph {
-    tx: Sender<[u8; 4]>,
-    word_order: bool, // Tracks current word order for CHAN13 read behavior
-}
-
-// Thread responsible for forwarding DSKY packets over TCP to 127.0.0.1:19800
-fn downrupt_thread(rx: Receiver<[u8; 4]>, addr: &str) {
-    let listener = TcpListener::bind(addr).unwrap();
-    for stream in listener.incoming() {
-        match stream {
-            Ok(mut xa) => loop {
-                let msg = match rx.recv() {
-                    Ok(x) => x,
-                    _ => break,
-                };
-
-                match xa.write_all(&msg) {
-                    Ok(_) => {}
-                    _ => break,
-                }
-            },
-            _ => {}
+    tx: QueueSender<DownlinkPair>,
+    writer: Option<JoinHandle<()>>, // Telemetry thread, when there is one
+}
+
+// Forwards downlink words to one ground tool; false once the emulator hangs up
+fn forward(
+    rx: &DownlinkReceiver,
+    stream: &mut TcpStream,
+    framing: TelemetryFraming,
+    filter: &mut TelemetryFilter,
+) -> bool {
+    let mut buf = [0; 4];
+    let word_bytes = framing.encode(0, 0, &mut buf);
+    loop {
+        let pair = match rx.recv_pair() {
+            Some(x) => x,
+            None => return false,
         };
+        for (channel_idx, value) in filter.admit(pair, Instant::now(), word_bytes) {
+            let len = framing.encode(channel_idx, value, &mut buf);
+            if stream.write_all(&buf[..len]).is_err() {
+                info!(
+                    "Telemetry client gone, {} pairs over budget",
+                    filter.dropped()
+                );
+                return true;
+            }
+        }
+    }
+}
+
+// Thread responsible for forwarding downlink words over TCP
+fn downrupt_thread(rx: DownlinkReceiver, config: TelemetryConfig) {
+    ragc_core::enter_span!("peripheral", name = "telemetry");
+    let mut filter = TelemetryFilter::new(&config);
+    match config.mode {
+        TelemetryMode::Listen => {
+            let listener = match TcpListener::bind(&config.addr) {
+                Ok(x) => x,
+                Err(e) => {
+                    error!("Telemetry bind to {} failed: {}", config.addr, e);
+                    return;
+                }
+            };
+            for mut stream in listener.incoming().flatten() {
+                info!("Telemetry client connected");
+                if !forward(&rx, &mut stream, config.framing, &mut filter) {
+                    return;
+                }
+            }
+        }
+        TelemetryMode::Connect => loop {
+            match TcpStream::connect(&config.addr) {
+                Ok(mut stream) => {
+                    info!("Telemetry connected to {}", config.addr);
+                    if !forward(&rx, &mut stream, config.framing, &mut filter) {
+                        return;
+                    }
+                }
+                Err(_) => std::thread::sleep(std::time::Duration::from_secs(1)),
+            }
+        },
     }
 }
 
 impl DownruptPeriph {
     pub fn new() -> Self {
-        let (tx, rx) = unbounded();
+        Self::with_config(TelemetryConfig::default())
+    }
+
+    /// Create the peripheral with an explicit telemetry endpoint
+    pub fn with_config(config: TelemetryConfig) -> Self {
+        let (mut periph, rx) = Self::in_process(config.queue);
 
         // Spawn thread to handle outgoing TCP communication
-        std::thread::spawn(move || downrupt_thread(rx, "127.0.0.1:19800"));
-        DownruptPeriph {
-            tx,
-            word_order: false,
-        }
+        periph.writer = Some(std::thread::spawn(move || downrupt_thread(rx, config)));
+        periph
+    }
+
+    /// Stop sending and hand back the telemetry thread, which exits once a
+    /// ground tool has taken every queued pair
+    pub fn close(self) -> Option<JoinHandle<()>> {
+        self.writer
+    }
+
+    /// Create the peripheral with no telemetry endpoint; the application
+    /// reads the downlink from the receiver instead
+    pub fn in_process(queue_config: QueueConfig) -> (Self, DownlinkReceiver) {
+        let (tx, rx) = queue("Telemetry", queue_config);
+        let periph = DownruptPeriph { tx, writer: None };
+        (periph, DownlinkReceiver::new(rx))
+    }
+
+    /// Word pairs lost to a full queue
+    pub fn dropped_pairs(&self) -> DropCounter {
+        self.tx.dropped()
     }
 }
 
 impl IoPeriph for DownruptPeriph {
     fn read(&self, channel_idx: usize) -> u16 {
         match channel_idx {
-            ragc_core::constants::ports::CHANNEL_CHAN13 => {
-                // CHAN13 read returns a control bit based on word_order
-                if self.word_order {
-                    1 << 6
-                } else {
-                    0o00000
-                }
-            }
             // Return max value for these channels (typical AGC pattern)
             ragc_core::constants::ports::CHANNEL_CHAN30
             | ragc_core::constants::ports::CHANNEL_CHAN31
@@ -67,23 +287,75 @@ impl IoPeriph for DownruptPeriph {
         }
     }
 
-    fn write(&mut self, channel_idx: usize, value: u16) {
-        match channel_idx {
-            ragc_core::constants::ports::CHANNEL_CHAN13 => {
-                // Toggle word_order flag based on bit 6
-                self.word_order = value & (1 << 6) != 0o00000;
-            }
-            ragc_core::constants::ports::CHANNEL_CHAN34
-            | ragc_core::constants::ports::CHANNEL_CHAN35 => {
-                // Generate and send DSKY packet over channel
-                let packet = generate_dsky_packet(channel_idx, value);
-                self.tx.send(packet).unwrap();
-            }
-            _ => {}
+    fn write(&mut self, _channel_idx: usize, _value: u16) {}
+
+    // Pairs arrive whole from the core; hand them to the telemetry thread
+    // for filtering and framing
+    fn downlink(&mut self, pair: DownlinkPair) {
+        self.tx.send(pair);
+    }
+}
+
+impl ragc_core::memory::mods::InterruptSource for DownruptPeriph {
+    fn rupt_requests(&mut self) -> ragc_core::memory::mods::RuptRequests {
+        ragc_core::memory::mods::RuptRequests::NONE // This peripheral doesn't generate interrupts
+    }
+}
+
+#[cfg(test)]
+mod downrupt_tests {
+    use super::{TelemetryConfig, TelemetryFilter, TelemetryFraming, TelemetryMode};
+    use ragc_core::memory::downlink::DownlinkPair;
+    use std::time::{Duration, Instant};
+    use std::vec::Vec;
+
+    fn pair(order: bool, words: [u16; 2]) -> DownlinkPair {
+        DownlinkPair { order, words }
+    }
+
+    #[test]
+    fn filter_decimates_selects_and_throttles() {
+        let config = TelemetryConfig {
+            decimate: 2,
+            words: Some(Vec::from([0, 3])),
+            ..TelemetryConfig::default()
+        };
+        let mut filter = TelemetryFilter::new(&config);
+        let now = Instant::now();
+        let mut sent = Vec::new();
+        for downlist in 0..4 {
+            sent.extend(filter.admit(pair(false, [downlist, 0o77340]), now, 4));
+            sent.extend(filter.admit(pair(true, [2, 3]), now, 4));
         }
+        // Downlists 0 and 2, words 0 (ID) and 3
+        assert_eq!(sent, [(0o34, 0), (0o35, 3), (0o34, 2), (0o35, 3)]);
+
+        // 8 bytes/s over 2-byte words: two pairs a second
+        let config = TelemetryConfig {
+            max_bytes_per_sec: Some(8),
+            ..TelemetryConfig::default()
+        };
+        let mut filter = TelemetryFilter::new(&config);
+        let counts: Vec<usize> = [0, 0, 0, 500, 1000]
+            .iter()
+            .map(|ms| {
+                let at = now + Duration::from_millis(*ms);
+                filter.admit(pair(true, [1, 2]), at, 2).count()
+            })
+            .collect();
+        assert_eq!(counts, [2, 2, 0, 2, 2]);
+        assert_eq!(filter.dropped(), 1);
     }
 
-    fn is_interrupt(&mut self) -> u16 {
-        0 // This peripheral doesn't generate interrupts
+    #[test]
+    fn framings_encode_words() {
+        let mut buf = [0; 4];
+        assert_eq!(TelemetryFraming::Words.encode(0o34, 0o12345, &mut buf), 2);
+        assert_eq!(&buf[..2], &[0x14, 0xE5]);
+        assert_eq!(TelemetryFraming::Packet.encode(0o34, 0o12345, &mut buf), 4);
+        assert_eq!(buf[0], 0o34 >> 3);
+
+        assert_eq!("connect".parse(), Ok(TelemetryMode::Connect));
+        assert!("dial".parse::<TelemetryMode>().is_err());
     }
 }
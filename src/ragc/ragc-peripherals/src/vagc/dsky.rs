@@ -1,12 +1,35 @@
+use super::queue::{queue, DropCounter, OverflowPolicy, QueueConfig, QueueSender};
+use super::relay_pacer::RelayPacer;
 use crate::utils::{get_7seg, get_7seg_value};
 use dsky_protocol::agc::{generate_dsky_packet, parse_dsky_packet};
+use dsky_protocol::capture::CaptureWriter;
+use ragc_core::memory::mods::{EmuTime, RuptRequest, RuptRequests};
+use ragc_core::memory::state::{PeriphState, StateReader, StateWriter};
 
-use crossbeam_channel::{unbounded, Receiver, Sender};
-use log::{debug, warn};
+use crossbeam_channel::{unbounded, Receiver, RecvTimeoutError, Sender};
+use ragc_core::logging::{debug, error, warn};
 
+use std::boxed::Box;
 use std::io::{Read, Write};
 use std::net::{TcpListener, TcpStream};
 use std::println;
+use std::time::Instant;
+
+/// Address the DSKY frontend connects to
+pub const DEFAULT_DSKY_ADDR: &str = "127.0.0.1:19697";
+
+/// How long PRO must be held to switch standby
+const STANDBY_HOLD_MCTS: u64 = EmuTime::mcts_in_millis(5000);
+
+/// Display packets held for the frontend; the newest state matters most
+pub const DEFAULT_DSKY_QUEUE: QueueConfig = QueueConfig::new(1024, OverflowPolicy::DropOldest);
+
+/// Input packets waiting for the capture file
+const CAPTURE_QUEUE: QueueConfig = QueueConfig::new(1024, OverflowPolicy::DropNewest);
+
+/// Channel 11 lamps shown through channel 163: TEMP, KEY REL and OPR ERR.
+/// The VERB/NOUN blank comes from the core's flash on channel 163 itself.
+const DSALMOUT_LIGHTS: u16 = 0o130;
 
 pub struct DskyDisplay {
     digit: [u8; 15],
@@ -14,17 +37,30 @@ pub struct DskyDisplay {
     verb: u16,
     prog: u16,
     proceed: u16,
+    proceed_since: Option<EmuTime>, // When PRO went down, while held
+    now: EmuTime,
     output_flags: u16,
     keypress: Receiver<u16>,
     keypress_val: u16,
-    dsky_tx: Sender<[u8; 4]>,
-    flash_tx: Sender<u16>,
+    keypress_tx: Sender<u16>,
+    dsky_tx: QueueSender<[u8; 4]>,
+    last_lights: u16, // Channel 163 word last sent to the frontend
     last_dsalmout: u16,
     last_dskyval: u16,
+    latches: [u16; 13], // Last relay word for each row, 1-12
 }
 
 // Handles incoming keypress data from DSKY frontend
-fn handle_stream_input(stream: &mut TcpStream, keypress_tx: &Sender<u16>) {
+fn handle_stream_input(
+    stream: &mut TcpStream,
+    keypress_tx: &Sender<u16>,
+    capture_tx: Option<&QueueSender<[u8; 4]>>,
+) {
+    let capture = |buf: [u8; 4]| {
+        if let Some(tx) = capture_tx {
+            tx.send(buf);
+        }
+    };
     loop {
         let mut buf = [0; 4];
         match stream.read_exact(&mut buf) {
@@ -32,10 +68,12 @@ fn handle_stream_input(stream: &mut TcpStream, keypress_tx: &Sender<u16>) {
                 Some(res) => match res.0 {
                     0o15 => {
                         debug!("Keypress: {:o}", res.1);
+                        capture(buf);
                         let _res = keypress_tx.send(res.1);
                     }
                     0o32 => {
                         debug!("Keypress (Proceed): {:o}", res.1);
+                        capture(buf);
                         let _res = keypress_tx.send(res.1 | 0o40000);
                     }
                     _ => {
@@ -72,40 +110,55 @@ fn handle_steam_output(stream: &mut TcpStream, dsky_rx: &Receiver<[u8; 4]>) {
     println!("Disconnecting");
 }
 
-// Handles blinking segments by manipulating flash flags over time
-fn flashing_thread(flash_rx: Receiver<u16>, dsky_tx: Sender<[u8; 4]>) {
-    let mut channel_value = 0o00000;
-    let start_time = std::time::SystemTime::now();
-
+// Sends outgoing DSKY display updates no faster than the link takes them
+fn handle_paced_output(
+    stream: &mut TcpStream,
+    dsky_rx: &Receiver<[u8; 4]>,
+    max_bytes_per_sec: u32,
+) {
+    let mut pacer = RelayPacer::new(max_bytes_per_sec);
     loop {
-        while !flash_rx.is_empty() {
-            channel_value = flash_rx.recv().unwrap();
-        }
-
-        let elapsed = start_time.elapsed().unwrap().as_millis();
-        if elapsed % 1000 < 750 {
-            let mut value = channel_value;
-            if channel_value & 0o00040 == 0o00040 {
-                value &= !0o00040;
+        let msg = match pacer.due() {
+            Some(due) => dsky_rx.recv_deadline(due),
+            None => dsky_rx.recv().map_err(|_| RecvTimeoutError::Disconnected),
+        };
+        match msg {
+            Ok(x) => {
+                pacer.push(x);
+                dsky_rx.try_iter().for_each(|x| pacer.push(x));
             }
-            dsky_tx.send(generate_dsky_packet(0o0163, value)).unwrap();
-        } else {
-            if channel_value != 0o00000 {
-                let mut value = channel_value & !0o00160;
-                if channel_value & 0o00040 == 0o00040 {
-                    value |= 0o00040;
-                }
-                dsky_tx.send(generate_dsky_packet(0o0163, value)).unwrap();
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => break,
+        }
+        if let Some(msg) = pacer.pop(Instant::now()) {
+            if stream.write_all(&msg).is_err() {
+                break;
             }
         }
+    }
+    debug!("DSKY link: {} updates coalesced", pacer.coalesced());
+    println!("Disconnecting");
+}
 
-        std::thread::sleep(std::time::Duration::new(0, 10000000));
+// Writes captured input packets until the DSKY goes away
+fn capture_thread(capture_rx: Receiver<[u8; 4]>, mut writer: CaptureWriter<Box<dyn Write + Send>>) {
+    for packet in capture_rx.iter() {
+        if let Err(e) = writer.record(packet) {
+            error!("DSKY capture failed: {}", e);
+            break;
+        }
     }
 }
 
-// Starts DSKY server and handles each client serially
-fn dsky_network_thread(keypress_tx: Sender<u16>, dsky_rx: Receiver<[u8; 4]>) {
-    let listener = TcpListener::bind("127.0.0.1:19697").unwrap();
+// Runs the DSKY server, handling each client serially
+fn dsky_network_thread(
+    listener: TcpListener,
+    keypress_tx: Sender<u16>,
+    dsky_rx: Receiver<[u8; 4]>,
+    capture_tx: Option<QueueSender<[u8; 4]>>,
+    max_bytes_per_sec: Option<u32>,
+) {
+    ragc_core::enter_span!("peripheral", name = "dsky");
     for stream in listener.incoming() {
         println!("Connecting to new stream");
         match stream {
@@ -113,13 +166,19 @@ fn dsky_network_thread(keypress_tx: Sender<u16>, dsky_rx: Receiver<[u8; 4]>) {
                 match xa.try_clone() {
                     Ok(mut x) => {
                         let keypresstx = keypress_tx.clone();
-                        std::thread::spawn(move || handle_stream_input(&mut x, &keypresstx));
+                        let capturetx = capture_tx.clone();
+                        std::thread::spawn(move || {
+                            handle_stream_input(&mut x, &keypresstx, capturetx.as_ref())
+                        });
                     }
                     _ => {
                         continue;
                     }
                 }
-                handle_steam_output(&mut xa, &dsky_rx);
+                match max_bytes_per_sec {
+                    Some(rate) => handle_paced_output(&mut xa, &dsky_rx, rate),
+                    None => handle_steam_output(&mut xa, &dsky_rx),
+                }
             }
             _ => {}
         };
@@ -129,13 +188,76 @@ fn dsky_network_thread(keypress_tx: Sender<u16>, dsky_rx: Receiver<[u8; 4]>) {
 
 impl DskyDisplay {
     pub fn new() -> Self {
-        let (keypress_tx, keypress_rx) = unbounded();
-        let (dsky_tx, dsky_rx) = unbounded();
-        let (flash_tx, flash_rx) = unbounded();
+        Self::with_capture(None)
+    }
+
+    /// Create the display, recording frontend input packets to `capture`
+    pub fn with_capture(capture: Option<CaptureWriter<Box<dyn Write + Send>>>) -> Self {
+        Self::with_queue(capture, DEFAULT_DSKY_QUEUE)
+    }
+
+    /// Create the display with a custom queue of packets for the frontend,
+    /// which fills while no frontend is connected
+    pub fn with_queue(
+        capture: Option<CaptureWriter<Box<dyn Write + Send>>>,
+        dsky_queue: QueueConfig,
+    ) -> Self {
+        Self::with_link(capture, dsky_queue, None)
+    }
 
-        let flash_dsky_tx = dsky_tx.clone();
-        std::thread::spawn(move || flashing_thread(flash_rx, flash_dsky_tx));
-        std::thread::spawn(move || dsky_network_thread(keypress_tx, dsky_rx));
+    /// Create the display for a frontend that takes at most
+    /// `max_bytes_per_sec`, such as replica hardware on a serial bridge.
+    /// Updates waiting for the link are coalesced, lamps first.
+    pub fn with_link(
+        capture: Option<CaptureWriter<Box<dyn Write + Send>>>,
+        dsky_queue: QueueConfig,
+        max_bytes_per_sec: Option<u32>,
+    ) -> Self {
+        let listener = TcpListener::bind(DEFAULT_DSKY_ADDR)
+            .map_err(|e| error!("DSKY server on {}: {}", DEFAULT_DSKY_ADDR, e))
+            .ok();
+        Self::serve(listener, capture, dsky_queue, max_bytes_per_sec)
+    }
+
+    /// Create the display serving frontends on an already bound listener
+    pub fn with_listener(
+        listener: TcpListener,
+        capture: Option<CaptureWriter<Box<dyn Write + Send>>>,
+    ) -> Self {
+        Self::serve(Some(listener), capture, DEFAULT_DSKY_QUEUE, None)
+    }
+
+    /// Create the display without a network frontend, for instances whose
+    /// keys and display travel some other way
+    pub fn detached() -> Self {
+        Self::serve(None, None, DEFAULT_DSKY_QUEUE, None)
+    }
+
+    fn serve(
+        listener: Option<TcpListener>,
+        capture: Option<CaptureWriter<Box<dyn Write + Send>>>,
+        dsky_queue: QueueConfig,
+        max_bytes_per_sec: Option<u32>,
+    ) -> Self {
+        let capture_tx = capture.map(|writer| {
+            let (capture_tx, capture_rx) = queue("DSKY capture", CAPTURE_QUEUE);
+            std::thread::spawn(move || capture_thread(capture_rx, writer));
+            capture_tx
+        });
+        let (keypress_tx, keypress_rx) = unbounded();
+        let (dsky_tx, dsky_rx) = queue("DSKY output", dsky_queue);
+        let network_keypress_tx = keypress_tx.clone();
+        if let Some(listener) = listener {
+            std::thread::spawn(move || {
+                dsky_network_thread(
+                    listener,
+                    network_keypress_tx,
+                    dsky_rx,
+                    capture_tx,
+                    max_bytes_per_sec,
+                )
+            });
+        }
 
         Self {
             digit: [0; 15],
@@ -144,12 +266,16 @@ impl DskyDisplay {
             prog: 0,
             keypress: keypress_rx,
             keypress_val: 0,
+            keypress_tx,
             proceed: 0o20000,
+            proceed_since: None,
+            now: EmuTime::ZERO,
             dsky_tx,
-            flash_tx,
+            last_lights: 0,
             output_flags: 0x0,
             last_dsalmout: 0x0,
             last_dskyval: 0x0,
+            latches: [0; 13],
         }
     }
 
@@ -162,6 +288,17 @@ impl DskyDisplay {
         (a, b, c, d)
     }
 
+    /// Display packets lost to a full queue
+    pub fn dropped_packets(&self) -> DropCounter {
+        self.dsky_tx.dropped()
+    }
+
+    /// Sender for injecting keypresses as if they came from the frontend
+    /// (PRO is sent with bit 15 set, matching the network input)
+    pub fn keypress_sender(&self) -> Sender<u16> {
+        self.keypress_tx.clone()
+    }
+
     pub fn read_keypress(&self) -> u16 {
         debug!("DSKY: Reading keypress: {:?}", self.keypress_val);
         self.keypress_val & 0x1F
@@ -175,14 +312,23 @@ impl DskyDisplay {
                 } else {
                     self.output_flags &= 0o77377;
                 }
-                self.flash_tx.send(self.output_flags).unwrap();
             }
             0o163 => {
-                self.output_flags = value;
-                self.flash_tx.send(self.output_flags).unwrap();
+                self.output_flags = value | (self.last_dsalmout & DSALMOUT_LIGHTS);
             }
             _ => {}
         }
+        self.send_lights();
+    }
+
+    // Send the channel 163 lamps when they change. Flashing is already
+    // applied: the frontend keeps no blink timer of its own.
+    fn send_lights(&mut self) {
+        if self.last_lights != self.output_flags {
+            self.last_lights = self.output_flags;
+            self.dsky_tx
+                .send(generate_dsky_packet(0o163, self.output_flags));
+        }
     }
 
     pub fn get_channel_value(&self, channel_idx: usize) -> u16 {
@@ -200,12 +346,10 @@ impl DskyDisplay {
         if self.last_dsalmout != flags {
             debug!("DSKY: Setting CHANNEL_DSALMOUT Flags: {:o}", flags);
             self.last_dsalmout = flags;
-            self.dsky_tx
-                .send(generate_dsky_packet(0o11, flags))
-                .unwrap();
+            self.dsky_tx.send(generate_dsky_packet(0o11, flags));
 
-            self.output_flags = (self.output_flags & 0o77607) | (flags & 0o00170);
-            self.flash_tx.send(self.output_flags).unwrap();
+            self.output_flags = (self.output_flags & !DSALMOUT_LIGHTS) | (flags & DSALMOUT_LIGHTS);
+            self.send_lights();
         }
     }
 
@@ -217,9 +361,12 @@ impl DskyDisplay {
         }
 
         self.last_dskyval = val;
-        self.dsky_tx.send(generate_dsky_packet(0o10, val)).unwrap();
+        self.dsky_tx.send(generate_dsky_packet(0o10, val));
 
         let (a, _b, c, d) = self.parse_fields(val);
+        if let Some(latch) = self.latches.get_mut(a as usize) {
+            *latch = val;
+        }
         match a {
             1 => {
                 self.digit[13] = get_7seg(c);
@@ -270,7 +417,7 @@ impl DskyDisplay {
     }
 }
 
-impl ragc_core::memory::periph::IoPeriph for DskyDisplay {
+impl ragc_core::memory::mods::IoPeriph for DskyDisplay {
     fn read(&self, channel_idx: usize) -> u16 {
         match channel_idx {
             ragc_core::constants::ports::CHANNEL_MNKEYIN => self.read_keypress(),
@@ -295,12 +442,76 @@ impl ragc_core::memory::periph::IoPeriph for DskyDisplay {
         }
     }
 
-    fn is_interrupt(&mut self) -> u16 {
+    fn tick(&mut self, now: EmuTime) {
+        self.now = now;
+    }
+
+    fn standby_request(&self) -> bool {
+        self.proceed_since
+            .is_some_and(|since| self.now.since(since) >= STANDBY_HOLD_MCTS)
+    }
+
+    fn periph_state(&mut self) -> Option<&mut dyn PeriphState> {
+        Some(self)
+    }
+}
+
+/// The relay latches and lamps. Restoring sends them all to the frontend
+/// again, so its display matches the restored computer.
+impl PeriphState for DskyDisplay {
+    fn state_tag(&self) -> [u8; 4] {
+        *b"DSKY"
+    }
+
+    fn save_state(&self, out: &mut [u8]) -> Option<usize> {
+        let mut out = StateWriter::new(out);
+        for word in self.latches.iter() {
+            out.u16(*word);
+        }
+        out.u16(self.last_dsalmout).u16(self.output_flags);
+        out.finish()
+    }
+
+    fn restore_state(&mut self, data: &[u8]) -> Result<(), &'static str> {
+        let mut data = StateReader::new(data);
+        let mut latches = [0; 13];
+        for word in latches.iter_mut() {
+            *word = data.u16()?;
+        }
+        let dsalmout = data.u16()?;
+        let output_flags = data.u16()?;
+
+        for &word in latches.iter().skip(1) {
+            self.last_dskyval = !word;
+            self.set_channel_dsky_value(word);
+        }
+        self.last_dsalmout = !dsalmout;
+        self.set_dsalmout_flags(dsalmout);
+        self.output_flags = output_flags;
+        self.last_lights = !output_flags;
+        self.send_lights();
+        Ok(())
+    }
+}
+
+impl ragc_core::memory::mods::InterruptSource for DskyDisplay {
+    fn rupt_requests(&mut self) -> RuptRequests {
+        // One key per poll: MNKEYIN holds a single keycode
         if self.keypress.len() > 0 {
             let val = self.keypress.recv().unwrap();
             match val & 0o40000 {
+                // PRO only drives channel 32; software polls it, no KEYRUPT
                 0o40000 => {
                     self.proceed = val & 0o37777;
+                    self.proceed_since = match self.proceed & 0o20000 {
+                        0 => self.proceed_since.or(Some(self.now)),
+                        _ => None,
+                    };
+                    return RuptRequests::NONE;
+                }
+                _ if self.proceed_since.is_some() => {
+                    debug!("Keypress {:o} ignored while PRO is held", val);
+                    return RuptRequests::NONE;
                 }
                 _ => {
                     self.keypress_val = val;
@@ -310,16 +521,64 @@ impl ragc_core::memory::periph::IoPeriph for DskyDisplay {
                     }
                 }
             }
-            (1 << ragc_core::constants::registers::INTERRUPT_KEYPRESS1) as u16
+            RuptRequest::Keyrupt1.into()
         } else {
-            0
+            RuptRequests::NONE
         }
     }
 }
 
 #[cfg(test)]
 mod dsky_unittests {
-    use super::DskyDisplay;
+    use super::{DskyDisplay, STANDBY_HOLD_MCTS};
+    use ragc_core::memory::mods::{EmuTime, InterruptSource, IoPeriph, RuptRequest};
+    use ragc_core::memory::state::PeriphState;
+
+    fn at(mcts: u64) -> EmuTime {
+        EmuTime::from_mcts(mcts)
+    }
+
+    #[test]
+    fn proceed_hold_requests_standby_without_keyrupt() {
+        let mut dsky = DskyDisplay::new();
+        let keys = dsky.keypress_sender();
+
+        keys.send(0o40000).unwrap(); // PRO down
+        dsky.tick(at(100));
+        assert!(dsky.rupt_requests().is_empty());
+        assert_eq!(dsky.read(0o32), 0);
+
+        // Other keys are swallowed during the hold
+        keys.send(0o21).unwrap();
+        assert!(dsky.rupt_requests().is_empty());
+        dsky.tick(at(100 + STANDBY_HOLD_MCTS - 1));
+        assert!(!dsky.standby_request());
+        dsky.tick(at(100 + STANDBY_HOLD_MCTS));
+        assert!(dsky.standby_request());
+
+        keys.send(0o60000).unwrap(); // PRO up
+        assert!(dsky.rupt_requests().is_empty());
+        assert!(!dsky.standby_request());
+        keys.send(0o21).unwrap();
+        assert_eq!(dsky.rupt_requests(), RuptRequest::Keyrupt1.into());
+    }
+
+    #[test]
+    fn lamps_blink_only_when_the_core_flashes_them() {
+        // OPR ERR and KEY REL as channel 11 has them, VERB/NOUN FLASH left
+        // to the core
+        let mut dsky = DskyDisplay::detached();
+        dsky.write(0o11, 0o160);
+        assert_eq!(dsky.read(0o163), 0o120);
+        dsky.tick(at(EmuTime::mcts_in_millis(800)));
+        assert_eq!(dsky.read(0o163), 0o120);
+
+        // The core blanks VERB and NOUN through channel 163
+        dsky.write(0o163, 0o40);
+        assert_eq!(dsky.read(0o163), 0o160);
+        dsky.write(0o11, 0o40);
+        assert_eq!(dsky.read(0o163), 0o40);
+    }
 
     fn dsky_display_digit_index(
         dsky: &mut DskyDisplay,
@@ -331,4 +590,21 @@ mod dsky_unittests {
             (row_idx << 11) | *lower_digit_seg as u16 | ((*upper_digit_seg as u16) << 5);
         dsky.set_channel_dsky_value(value);
     }
+
+    #[test]
+    fn restored_display_matches_the_saved_one() {
+        let mut dsky = DskyDisplay::detached();
+        dsky.write(0o10, (10 << 11) | (0o25 << 5) | 0o23); // VERB 06
+        dsky.write(0o10, (8 << 11) | 0o35); // R1 digit 1: 8
+        dsky.write(0o11, 0o40);
+        let mut buf = [0; 64];
+        let len = dsky.save_state(&mut buf).unwrap();
+
+        let mut resumed = DskyDisplay::detached();
+        resumed.restore_state(&buf[..len]).unwrap();
+        assert_eq!((resumed.verb, resumed.digit), (dsky.verb, dsky.digit));
+        assert_eq!(resumed.latches, dsky.latches);
+        assert_eq!(resumed.read(0o163), dsky.read(0o163));
+        assert!(resumed.restore_state(&buf[..4]).is_err());
+    }
 }
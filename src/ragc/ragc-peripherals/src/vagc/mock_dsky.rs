@@ -0,0 +1,195 @@
+//! Scripted DSKY frontend for end-to-end tests of the DSKY server. It
+//! speaks the real packet protocol over a socket, sends keys and records
+//! every display and lamp packet the emulator sends back.
+use dsky_protocol::agc::{generate_dsky_packet, parse_dsky_packet};
+use dsky_protocol::pinball::DisplayState;
+
+use crossbeam_channel::{unbounded, Receiver};
+
+use std::io::{Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::{Duration, Instant};
+use std::vec::Vec;
+
+/// Frontend channels: keycodes in, PRO in, relay words out, lamps out
+const CHANNEL_KEY: usize = 0o15;
+const CHANNEL_PROCEED: usize = 0o32;
+const CHANNEL_RELAYS: u16 = 0o10;
+const CHANNEL_LAMPS: u16 = 0o11;
+
+/// Keycode for a key in a script: V, N, E, C (CLR), R (RSET), K (KEY REL),
+/// + and - and digits
+pub fn keycode(key: char) -> Option<u16> {
+    match key {
+        'V' => Some(0o21),
+        'N' => Some(0o37),
+        'E' => Some(0o34),
+        'C' => Some(0o36),
+        'R' => Some(0o22),
+        'K' => Some(0o31),
+        '+' => Some(0o32),
+        '-' => Some(0o33),
+        '0' => Some(0o20),
+        '1'..='9' => key.to_digit(10).map(|d| d as u16),
+        _ => None,
+    }
+}
+
+/// A DSKY frontend connected to the emulator's DSKY port
+pub struct MockDskyClient {
+    stream: TcpStream,
+    incoming: Receiver<(u16, u16)>,
+    packets: Vec<(u16, u16)>, // Channel and value, in arrival order
+    display: DisplayState,
+    lamps: u16,
+}
+
+impl MockDskyClient {
+    pub fn connect(addr: impl ToSocketAddrs) -> std::io::Result<Self> {
+        let stream = TcpStream::connect(addr)?;
+        let mut reader = stream.try_clone()?;
+        let (tx, incoming) = unbounded();
+        std::thread::spawn(move || {
+            let mut buf = [0; 4];
+            while reader.read_exact(&mut buf).is_ok() {
+                if let Some(packet) = parse_dsky_packet(buf) {
+                    if tx.send(packet).is_err() {
+                        break;
+                    }
+                }
+            }
+        });
+        Ok(Self {
+            stream,
+            incoming,
+            packets: Vec::new(),
+            display: DisplayState::new(),
+            lamps: 0,
+        })
+    }
+
+    /// Press one key by keycode
+    pub fn press(&mut self, keycode: u16) -> std::io::Result<()> {
+        self.stream
+            .write_all(&generate_dsky_packet(CHANNEL_KEY, keycode))
+    }
+
+    /// Press the keys of a script such as "V16N65E", skipping unknown keys
+    pub fn press_keys(&mut self, keys: &str) -> std::io::Result<()> {
+        for code in keys.chars().filter_map(keycode) {
+            self.press(code)?;
+        }
+        Ok(())
+    }
+
+    /// Push or release PRO
+    pub fn proceed(&mut self, down: bool) -> std::io::Result<()> {
+        let value = if down { 0 } else { 0o20000 };
+        self.stream
+            .write_all(&generate_dsky_packet(CHANNEL_PROCEED, value))
+    }
+
+    fn apply(&mut self, (channel, value): (u16, u16)) {
+        match channel {
+            CHANNEL_RELAYS => self.display.apply_relay_word(value),
+            CHANNEL_LAMPS => self.lamps = value,
+            _ => {}
+        }
+        self.packets.push((channel, value));
+    }
+
+    /// Take in packets that have arrived so far
+    pub fn poll(&mut self) {
+        while let Ok(packet) = self.incoming.try_recv() {
+            self.apply(packet);
+        }
+    }
+
+    /// Wait up to `timeout` for a packet matching `pred`, recording every
+    /// packet received on the way
+    pub fn wait_for(
+        &mut self,
+        timeout: Duration,
+        pred: impl Fn(u16, u16) -> bool,
+    ) -> Option<(u16, u16)> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            let left = deadline.checked_duration_since(Instant::now())?;
+            let packet = self.incoming.recv_timeout(left).ok()?;
+            self.apply(packet);
+            if pred(packet.0, packet.1) {
+                return Some(packet);
+            }
+        }
+    }
+
+    /// Every packet received, as (channel, value)
+    pub fn packets(&self) -> &[(u16, u16)] {
+        &self.packets
+    }
+
+    /// Display rebuilt from the relay words received
+    pub fn display(&self) -> &DisplayState {
+        &self.display
+    }
+
+    /// Last channel 11 lamp word received
+    pub fn lamps(&self) -> u16 {
+        self.lamps
+    }
+}
+
+#[cfg(test)]
+mod mock_dsky_tests {
+    use super::MockDskyClient;
+    use crate::dsky::DskyDisplay;
+    use ragc_core::constants::ports::CHANNEL_MNKEYIN;
+    use ragc_core::constants::ports::{CHANNEL_CHAN32, CHANNEL_DSALMOUT, CHANNEL_DSKY};
+    use ragc_core::memory::mods::{InterruptSource, IoPeriph, RuptRequest};
+    use std::net::TcpListener;
+    use std::time::{Duration, Instant};
+    use std::vec::Vec;
+
+    const TIMEOUT: Duration = Duration::from_secs(5);
+
+    /// Keycodes the display takes, in order, until `count` have arrived
+    fn take_keys(dsky: &mut DskyDisplay, count: usize) -> Vec<u16> {
+        let deadline = Instant::now() + TIMEOUT;
+        let mut keys = Vec::new();
+        while keys.len() < count && Instant::now() < deadline {
+            if dsky.rupt_requests().contains(RuptRequest::Keyrupt1) {
+                keys.push(dsky.read(CHANNEL_MNKEYIN));
+            }
+        }
+        keys
+    }
+
+    #[test]
+    fn scripted_client_drives_the_dsky_server() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let mut dsky = DskyDisplay::with_listener(listener, None);
+        let mut client = MockDskyClient::connect(addr).unwrap();
+
+        client.press_keys("V16N65E").unwrap();
+        assert_eq!(take_keys(&mut dsky, 7), [0o21, 1, 6, 0o37, 6, 5, 0o34]);
+
+        client.proceed(true).unwrap();
+        let deadline = Instant::now() + TIMEOUT;
+        while dsky.read(CHANNEL_CHAN32) != 0 && Instant::now() < deadline {
+            assert!(dsky.rupt_requests().is_empty(), "PRO raises no KEYRUPT");
+        }
+        assert_eq!(dsky.read(CHANNEL_CHAN32), 0);
+
+        // VERB 16 and the KEY REL lamp come back as packets
+        let verb16 = (10 << 11) | (0o03 << 5) | 0o34;
+        dsky.write(CHANNEL_DSKY, verb16);
+        dsky.write(CHANNEL_DSALMOUT, 0o20);
+        assert!(client
+            .wait_for(TIMEOUT, |channel, _| channel == 0o11)
+            .is_some());
+        assert_eq!(client.display().verb(), Some(16));
+        assert_eq!(client.lamps(), 0o20);
+        assert!(client.packets().contains(&(0o10, verb16)));
+    }
+}
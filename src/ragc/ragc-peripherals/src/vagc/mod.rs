@@ -1,2 +1,8 @@
+pub mod downlink;
 pub mod downrupt;
 pub mod dsky;
+pub mod mock_dsky;
+pub mod queue;
+pub mod relay_pacer;
+pub mod tracefile;
+pub mod tracepack;
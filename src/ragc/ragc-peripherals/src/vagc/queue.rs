@@ -0,0 +1,140 @@
+//! Bounded queues from the emulation thread to peripheral threads. A
+//! peripheral without a client never drains its queue, so each queue has a
+//! capacity, a policy for what to lose once full, and a count of the loss.
+use crossbeam_channel::{bounded, Receiver, Sender, TrySendError};
+use ragc_core::logging::warn;
+
+use std::string::String;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+/// What a full queue gives up to make room
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum OverflowPolicy {
+    DropNewest, // Keep the backlog, discard the new item
+    DropOldest, // Discard the oldest queued item, keep the new one
+}
+
+impl core::str::FromStr for OverflowPolicy {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, String> {
+        match s {
+            "drop-newest" => Ok(OverflowPolicy::DropNewest),
+            "drop-oldest" => Ok(OverflowPolicy::DropOldest),
+            _ => Err(std::format!("Unknown overflow policy: {}", s)),
+        }
+    }
+}
+
+/// Queue capacity, in items, and overflow policy
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct QueueConfig {
+    pub capacity: usize,
+    pub policy: OverflowPolicy,
+}
+
+impl QueueConfig {
+    pub const fn new(capacity: usize, policy: OverflowPolicy) -> Self {
+        Self { capacity, policy }
+    }
+}
+
+/// Items a queue has dropped, readable from any thread
+#[derive(Clone, Default, Debug)]
+pub struct DropCounter(Arc<AtomicU64>);
+
+impl DropCounter {
+    pub fn get(&self) -> u64 {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// Sending half of a bounded queue. Sends never block.
+pub struct QueueSender<T> {
+    name: &'static str,
+    tx: Sender<T>,
+    rx: Receiver<T>, // For evicting the oldest item
+    policy: OverflowPolicy,
+    dropped: DropCounter,
+}
+
+impl<T> Clone for QueueSender<T> {
+    fn clone(&self) -> Self {
+        Self {
+            name: self.name,
+            tx: self.tx.clone(),
+            rx: self.rx.clone(),
+            policy: self.policy,
+            dropped: self.dropped.clone(),
+        }
+    }
+}
+
+impl<T> QueueSender<T> {
+    /// Queue an item, dropping one per the policy when full. Returns
+    /// false when the new item was the one dropped.
+    pub fn send(&self, item: T) -> bool {
+        if let Err(TrySendError::Full(item)) = self.tx.try_send(item) {
+            if self.dropped.0.fetch_add(1, Ordering::Relaxed) == 0 {
+                warn!(
+                    "{} queue full, dropping items ({:?})",
+                    self.name, self.policy
+                );
+            }
+            if self.policy == OverflowPolicy::DropOldest {
+                let _ = self.rx.try_recv();
+                return self.tx.try_send(item).is_ok();
+            }
+            return false;
+        }
+        true
+    }
+
+    pub fn dropped(&self) -> DropCounter {
+        self.dropped.clone()
+    }
+
+    /// A send now would drop an item
+    pub fn is_full(&self) -> bool {
+        self.tx.is_full()
+    }
+}
+
+/// Create a named queue; the name appears in the overflow warning
+pub fn queue<T>(name: &'static str, config: QueueConfig) -> (QueueSender<T>, Receiver<T>) {
+    let (tx, rx) = bounded(config.capacity.max(1));
+    let sender = QueueSender {
+        name,
+        tx,
+        rx: rx.clone(),
+        policy: config.policy,
+        dropped: DropCounter::default(),
+    };
+    (sender, rx)
+}
+
+#[cfg(test)]
+mod queue_tests {
+    use super::{queue, OverflowPolicy, QueueConfig};
+    use std::vec::Vec;
+
+    #[test]
+    fn full_queues_drop_per_policy() {
+        let (tx, rx) = queue("test", QueueConfig::new(2, OverflowPolicy::DropNewest));
+        let kept: Vec<_> = (1..=4).map(|i| tx.send(i)).collect();
+        assert_eq!(kept, [true, true, false, false]);
+        assert_eq!(rx.try_iter().collect::<Vec<_>>(), [1, 2]);
+        assert_eq!(tx.dropped().get(), 2);
+
+        let (tx, rx) = queue("test", QueueConfig::new(2, OverflowPolicy::DropOldest));
+        let dropped = tx.dropped();
+        (1..=4).for_each(|i| {
+            tx.clone().send(i);
+        });
+        assert_eq!(rx.try_iter().collect::<Vec<_>>(), [3, 4]);
+        assert_eq!(dropped.get(), 2);
+
+        assert_eq!("drop-oldest".parse(), Ok(OverflowPolicy::DropOldest));
+        assert!("block".parse::<OverflowPolicy>().is_err());
+    }
+}
@@ -0,0 +1,146 @@
+//! Pacing for DSKY links slower than the emulator. A replica DSKY behind a
+//! serial bridge takes a few hundred bytes a second, while PINBALL rewrites
+//! whole rows of the display many times over during a monitor verb. Only
+//! the newest word for each relay row or lamp channel means anything, so
+//! updates waiting for the link replace older ones for the same row, and
+//! lamp words go out ahead of digits.
+use dsky_protocol::agc::parse_dsky_packet;
+
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/// Bytes in a DSKY packet
+const PACKET_BYTES: u32 = 4;
+
+/// Relay row of discrete lamps rather than digits
+const LAMP_ROW: u16 = 12;
+
+/// What a packet updates: a channel, and for channel 10 the relay row
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+struct Slot {
+    channel: u16,
+    row: u16,
+}
+
+impl Slot {
+    fn of(packet: [u8; 4]) -> Self {
+        match parse_dsky_packet(packet) {
+            Some((0o10, value)) => Slot {
+                channel: 0o10,
+                row: (value >> 11) & 0o17,
+            },
+            Some((channel, _)) => Slot { channel, row: 0 },
+            None => Slot {
+                channel: u16::MAX,
+                row: 0,
+            },
+        }
+    }
+
+    /// Lamp words, such as OPR ERR and KEY REL, go ahead of digits
+    fn is_lamp(&self) -> bool {
+        matches!(self.channel, 0o11 | 0o163) || (self.channel == 0o10 && self.row == LAMP_ROW)
+    }
+}
+
+/// Packets waiting for a link limited to `max_bytes_per_sec`, sent one at
+/// a time and evenly spaced
+pub struct RelayPacer {
+    interval: Duration,
+    next: Option<Instant>, // When the link takes another packet
+    pending: VecDeque<(Slot, [u8; 4])>,
+    coalesced: u64,
+}
+
+impl RelayPacer {
+    pub fn new(max_bytes_per_sec: u32) -> Self {
+        let per_sec = max_bytes_per_sec.max(1) as f64 / PACKET_BYTES as f64;
+        Self {
+            interval: Duration::from_secs_f64(1.0 / per_sec),
+            next: None,
+            pending: VecDeque::new(),
+            coalesced: 0,
+        }
+    }
+
+    /// Queue a packet, replacing one still waiting for the same row or
+    /// channel. The replacement keeps its place, so a row that changes
+    /// constantly still takes its turn.
+    pub fn push(&mut self, packet: [u8; 4]) {
+        let slot = Slot::of(packet);
+        match self.pending.iter_mut().find(|(s, _)| *s == slot) {
+            Some(waiting) => {
+                waiting.1 = packet;
+                self.coalesced += 1;
+            }
+            None => self.pending.push_back((slot, packet)),
+        }
+    }
+
+    /// The packet to send at `now`, if the link is ready for one
+    pub fn pop(&mut self, now: Instant) -> Option<[u8; 4]> {
+        if self.next.is_some_and(|next| now < next) {
+            return None;
+        }
+        let idx = self
+            .pending
+            .iter()
+            .position(|(slot, _)| slot.is_lamp())
+            .unwrap_or(0);
+        let (_, packet) = self.pending.remove(idx)?;
+        self.next = Some(now + self.interval);
+        Some(packet)
+    }
+
+    /// When the next packet can go, while any are waiting
+    pub fn due(&self) -> Option<Instant> {
+        match self.pending.is_empty() {
+            true => None,
+            false => Some(self.next.unwrap_or_else(Instant::now)),
+        }
+    }
+
+    /// Updates replaced before the link took them
+    pub fn coalesced(&self) -> u64 {
+        self.coalesced
+    }
+}
+
+#[cfg(test)]
+mod relay_pacer_tests {
+    use super::RelayPacer;
+    use dsky_protocol::agc::generate_dsky_packet;
+    use std::time::{Duration, Instant};
+
+    fn row(row: u16, digits: u16) -> [u8; 4] {
+        generate_dsky_packet(0o10, (row << 11) | digits)
+    }
+
+    #[test]
+    fn coalesces_rows_and_sends_lamps_first() {
+        // 40 bytes a second: a packet every 100 ms
+        let mut pacer = RelayPacer::new(40);
+        let start = Instant::now();
+        pacer.push(row(3, 0o1));
+        pacer.push(row(4, 0o1));
+        pacer.push(row(3, 0o2));
+        pacer.push(row(3, 0o3));
+        pacer.push(generate_dsky_packet(0o11, 0o40));
+        assert_eq!(pacer.coalesced(), 2);
+
+        assert_eq!(pacer.pop(start), Some(generate_dsky_packet(0o11, 0o40)));
+        assert_eq!(pacer.pop(start + Duration::from_millis(50)), None);
+        assert_eq!(pacer.due(), Some(start + Duration::from_millis(100)));
+
+        // Row 3 keeps its place with its newest word; lamp row 12 jumps it
+        let t = start + Duration::from_millis(100);
+        pacer.push(row(12, 0o4));
+        assert_eq!(pacer.pop(t), Some(row(12, 0o4)));
+        let t = t + Duration::from_millis(100);
+        assert_eq!(pacer.pop(t), Some(row(3, 0o3)));
+        let t = t + Duration::from_millis(100);
+        assert_eq!(pacer.pop(t), Some(row(4, 0o1)));
+        assert_eq!(pacer.due(), None);
+        assert_eq!(pacer.pop(t + Duration::from_secs(1)), None);
+    }
+}
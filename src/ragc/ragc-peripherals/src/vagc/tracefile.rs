@@ -0,0 +1,311 @@
+//! Instruction trace to a file. The emulation thread only copies registers
+//! into fixed-size records and pushes them onto a lock-free ring; a writer
+//! thread does the disassembly, formatting and file I/O. The emulation
+//! thread never waits: when the writer falls a whole ring behind, records
+//! are dropped and counted rather than slowing the emulator down.
+use super::tracepack::TraceEncoder;
+use ragc_core::logging::{info, warn};
+use rtrb::{Consumer, Producer, RingBuffer};
+use std::boxed::Box;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use ragc_core::constants::registers::{
+    REGISTER_ACCUMULATOR, REGISTER_COUNTER, REGISTER_FIXED_BANK, REGISTER_LINK, REGISTER_RETURN,
+};
+use ragc_core::cpu::Cpu;
+use ragc_core::disasm::{disassemble, rupt_vector, RUPT_VECTORS};
+use ragc_core::instructions::Mnemonic;
+
+/// Records waiting for the writer, about a quarter million instructions
+pub const DEFAULT_TRACE_DEPTH: usize = 1 << 18;
+
+/// Writer's sleep when the ring is empty
+const WRITER_IDLE: Duration = Duration::from_millis(1);
+
+/// Registers as an instruction starts
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub struct TraceEntry {
+    pub cycles: usize, // Total MCTs elapsed
+    pub fb: u16,       // Fixed bank register
+    pub pc: u16,
+    pub ir: u16, // Instruction word, before any INDEX
+    pub extended: bool,
+    pub a: u16,
+    pub l: u16,
+    pub q: u16,
+}
+
+impl TraceEntry {
+    /// Take the entry for the instruction the next step executes
+    pub fn capture(cpu: &Cpu) -> Self {
+        let mem = cpu.memory();
+        Self {
+            cycles: cpu.total_cycles,
+            fb: mem.read(REGISTER_FIXED_BANK),
+            pc: mem.read(REGISTER_COUNTER),
+            ir: cpu.ir,
+            extended: cpu.ec_flag,
+            a: mem.read(REGISTER_ACCUMULATOR),
+            l: mem.read(REGISTER_LINK),
+            q: mem.read(REGISTER_RETURN),
+        }
+    }
+
+    /// One line of a text trace
+    pub fn write_text(&self, out: &mut impl Write) -> io::Result<()> {
+        let inst = disassemble(self.ir, self.extended);
+        let inst = inst.map_or(std::string::String::new(), |d| std::format!("{}", d));
+        writeln!(
+            out,
+            "{:>12} {:02o}:{:04o} {:05o} {:<16} A={:05o} L={:05o} Q={:05o}",
+            self.cycles,
+            self.fb >> 10,
+            self.pc,
+            self.ir & 0o77777,
+            inst,
+            self.a,
+            self.l,
+            self.q
+        )
+    }
+
+    /// One line as yaAGC addresses and registers, without cycle counts or
+    /// disassembly, which the two emulators spell differently
+    pub fn write_yaagc(&self, out: &mut impl Write) -> io::Result<()> {
+        match self.pc {
+            0o2000..=0o3777 => write!(out, "{:02o},{:04o}", self.fb >> 10, self.pc)?,
+            _ => write!(out, "{:04o}", self.pc)?,
+        }
+        writeln!(
+            out,
+            " {:05o} A={:05o} L={:05o} Q={:05o}",
+            self.ir & 0o77777,
+            self.a,
+            self.l,
+            self.q
+        )
+    }
+}
+
+/// Marks interrupts in a text trace: a line naming the RUPT before the
+/// first instruction of its vector, and one after the RESUME ending it
+#[derive(Default)]
+pub struct RuptMarks {
+    current: Option<&'static str>,
+}
+
+impl RuptMarks {
+    /// Write `entry` as text, with the marks it calls for around it
+    pub fn write(&mut self, entry: &TraceEntry, out: &mut impl Write) -> io::Result<()> {
+        // A restart starts over rather than interrupting
+        match rupt_vector(entry.pc as usize) {
+            Some((name, 0)) if name != RUPT_VECTORS[0] => {
+                writeln!(out, "; enter {}", name)?;
+                self.current = Some(name);
+            }
+            _ => {}
+        }
+        entry.write_text(out)?;
+        let inst = disassemble(entry.ir, entry.extended);
+        if inst.is_some_and(|inst| inst.mnem == Mnemonic::RESUME) {
+            let name = self.current.take().unwrap_or("interrupt");
+            writeln!(out, "; leave {}", name)?;
+        }
+        Ok(())
+    }
+}
+
+/// Emulation-thread end of an instruction trace
+pub struct TraceFile {
+    tx: Option<Producer<TraceEntry>>,
+    dropped: u64,
+    writer: Option<JoinHandle<io::Result<u64>>>,
+}
+
+impl TraceFile {
+    /// Text trace, with room for `depth` records waiting for the writer
+    pub fn create(path: &str, depth: usize) -> io::Result<Self> {
+        let file = File::create(path)?;
+        Ok(Self::with_writer(Box::new(file), depth))
+    }
+
+    /// Packed binary trace, compressed when built with `zstd`
+    pub fn create_packed(path: &str, depth: usize) -> io::Result<Self> {
+        let encoder = TraceEncoder::new(Box::new(File::create(path)?), true)?;
+        Ok(Self::spawn(Output::Packed(encoder), depth))
+    }
+
+    /// Trace to any writer, formatted on a thread of its own
+    pub fn with_writer(out: Box<dyn Write + Send>, depth: usize) -> Self {
+        Self::spawn(
+            Output::Text(BufWriter::new(out), RuptMarks::default()),
+            depth,
+        )
+    }
+
+    fn spawn(out: Output, depth: usize) -> Self {
+        let (tx, rx) = RingBuffer::new(depth.max(1));
+        let writer = std::thread::spawn(move || write_records(out, rx));
+        Self {
+            tx: Some(tx),
+            dropped: 0,
+            writer: Some(writer),
+        }
+    }
+
+    /// Hand a record to the writer. Never waits: with the ring full the
+    /// record is dropped and counted.
+    pub fn record(&mut self, entry: TraceEntry) {
+        let tx = match &mut self.tx {
+            Some(tx) => tx,
+            None => return,
+        };
+        if tx.push(entry).is_err() {
+            if self.dropped == 0 {
+                warn!("Trace writer behind, dropping records");
+            }
+            self.dropped += 1;
+        }
+    }
+
+    /// Records lost because the writer fell behind
+    pub fn dropped(&self) -> u64 {
+        self.dropped
+    }
+
+    /// Write out what is still queued and close the file. Returns the
+    /// number of records written.
+    pub fn finish(mut self) -> io::Result<u64> {
+        self.tx = None;
+        let written = match self.writer.take().map(|w| w.join()) {
+            Some(Ok(result)) => result?,
+            _ => return Err(io::Error::other("Trace writer failed")),
+        };
+        info!(
+            "Trace: {} instructions written, {} dropped",
+            written, self.dropped
+        );
+        Ok(written)
+    }
+}
+
+/// Where the writer thread puts records
+enum Output {
+    Text(BufWriter<Box<dyn Write + Send>>, RuptMarks),
+    Packed(TraceEncoder),
+}
+
+fn write_records(mut out: Output, mut rx: Consumer<TraceEntry>) -> io::Result<u64> {
+    let mut written = 0;
+    loop {
+        // Check before reading, so records pushed just before the
+        // emulation thread let go are still written
+        let abandoned = rx.is_abandoned();
+        let chunk = match rx.read_chunk(rx.slots()) {
+            Ok(chunk) if !chunk.is_empty() => chunk,
+            _ if abandoned => break,
+            _ => {
+                std::thread::sleep(WRITER_IDLE);
+                continue;
+            }
+        };
+        let (first, second) = chunk.as_slices();
+        for entry in first.iter().chain(second) {
+            match &mut out {
+                Output::Text(out, marks) => marks.write(entry, out)?,
+                Output::Packed(encoder) => encoder.write(entry)?,
+            }
+        }
+        written += chunk.len() as u64;
+        chunk.commit_all();
+    }
+    match out {
+        Output::Text(mut out, _) => out.flush()?,
+        Output::Packed(encoder) => encoder.finish()?,
+    }
+    Ok(written)
+}
+
+#[cfg(test)]
+mod tracefile_tests {
+    use super::{RuptMarks, TraceEntry, TraceFile};
+    use ragc_core::cpu::Cpu;
+    use ragc_core::memory::MemoryMap;
+    use std::boxed::Box;
+    use std::io::Write;
+    use std::sync::{Arc, Mutex};
+    use std::vec::Vec;
+
+    #[derive(Clone, Default)]
+    struct Shared(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Shared {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// Trace `steps` instructions through a ring `depth` records deep.
+    /// Returns the records written and dropped and the text.
+    fn trace(depth: usize, steps: usize) -> (u64, u64, std::string::String) {
+        let out = Shared::default();
+        let mut trace = TraceFile::with_writer(Box::new(out.clone()), depth);
+        let mut cpu = Cpu::new(MemoryMap::new_blank());
+        for _ in 0..steps {
+            trace.record(TraceEntry::capture(&cpu));
+            cpu.step();
+        }
+        let dropped = trace.dropped();
+        let written = trace.finish().unwrap();
+        let text = std::string::String::from_utf8(out.0.lock().unwrap().clone()).unwrap();
+        (written, dropped, text)
+    }
+
+    #[test]
+    fn records_reach_the_writer_in_order() {
+        let (written, dropped, text) = trace(2048, 2000);
+        assert_eq!((written, dropped), (2000, 0));
+        let lines = text.lines().filter(|line| !line.starts_with(';'));
+        assert_eq!(lines.count() as u64, written);
+        assert!(text.lines().next().unwrap().trim_start().starts_with("0 "));
+    }
+
+    #[test]
+    fn full_rings_drop_and_count() {
+        // The writer naps when it finds the ring empty; four records
+        // don't last the nap
+        let (written, dropped, text) = trace(4, 10_000);
+        assert!(dropped > 0);
+        assert_eq!(written + dropped, 10_000);
+        let lines = text.lines().filter(|line| !line.starts_with(';'));
+        assert_eq!(lines.count() as u64, written);
+    }
+
+    #[test]
+    fn marks_interrupt_entry_and_exit() {
+        let entry = |pc, ir| TraceEntry {
+            pc,
+            ir,
+            ..TraceEntry::default()
+        };
+        let mut marks = RuptMarks::default();
+        let mut out = Vec::new();
+        for (pc, ir) in [(0o4000, 0o00006), (0o4014, 0o54000), (0o4015, 0o50017)] {
+            marks.write(&entry(pc, ir), &mut out).unwrap();
+        }
+        marks.write(&entry(0o2000, 0o50017), &mut out).unwrap();
+        let text = std::string::String::from_utf8(out).unwrap();
+        let marks: Vec<_> = text.lines().filter(|line| line.starts_with(';')).collect();
+        assert_eq!(
+            marks,
+            ["; enter T3RUPT", "; leave T3RUPT", "; leave interrupt"]
+        );
+        assert_eq!(text.lines().count(), 7);
+    }
+}
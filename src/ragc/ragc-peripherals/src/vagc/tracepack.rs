@@ -0,0 +1,319 @@
+//! Packed binary instruction traces, about a tenth the size of text ones.
+//! Each record holds only what changed since the one before: the MCTs
+//! elapsed, the PC unless it just advanced, an index into the instruction
+//! words seen so far, and whichever of FB, A, L and Q were written. The
+//! same entries always pack to the same bytes, so traces can be compared
+//! byte for byte. With the `zstd` feature the records are also compressed.
+use std::boxed::Box;
+use std::collections::HashMap;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::vec::Vec;
+
+use super::tracefile::TraceEntry;
+
+/// File signature, then a version and a flags byte
+const MAGIC: &[u8; 7] = b"RAGCTRC";
+const VERSION: u8 = 1;
+const COMPRESSED: u8 = 0x01;
+
+// Record flags
+const FB: u8 = 0x01;
+const NEW_WORD: u8 = 0x02;
+const A: u8 = 0x04;
+const L: u8 = 0x08;
+const Q: u8 = 0x10;
+const EXTENDED: u8 = 0x20;
+const JUMP: u8 = 0x40;
+
+/// Whether a file starting with `header` is a packed trace
+pub fn is_packed(header: &[u8]) -> bool {
+    header.starts_with(MAGIC)
+}
+
+enum Sink {
+    Plain(BufWriter<Box<dyn Write + Send>>),
+    #[cfg(feature = "zstd")]
+    Zstd(zstd::stream::write::Encoder<'static, BufWriter<Box<dyn Write + Send>>>),
+}
+
+impl Write for Sink {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Sink::Plain(out) => out.write(buf),
+            #[cfg(feature = "zstd")]
+            Sink::Zstd(out) => out.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Sink::Plain(out) => out.flush(),
+            #[cfg(feature = "zstd")]
+            Sink::Zstd(out) => out.flush(),
+        }
+    }
+}
+
+/// Packs trace entries into a writer
+pub struct TraceEncoder {
+    out: Sink,
+    last: TraceEntry,
+    words: HashMap<u16, u32>, // Instruction words by first appearance
+    record: Vec<u8>,
+}
+
+impl TraceEncoder {
+    /// Start a trace, compressed if asked and built with `zstd`
+    pub fn new(out: Box<dyn Write + Send>, compress: bool) -> io::Result<Self> {
+        let mut out = BufWriter::new(out);
+        let compress = compress && cfg!(feature = "zstd");
+        out.write_all(MAGIC)?;
+        out.write_all(&[VERSION, if compress { COMPRESSED } else { 0 }])?;
+        let out = match compress {
+            #[cfg(feature = "zstd")]
+            true => Sink::Zstd(zstd::stream::write::Encoder::new(out, 3)?),
+            _ => Sink::Plain(out),
+        };
+        Ok(Self {
+            out,
+            last: TraceEntry::default(),
+            words: HashMap::new(),
+            record: Vec::with_capacity(32),
+        })
+    }
+
+    pub fn write(&mut self, entry: &TraceEntry) -> io::Result<()> {
+        let last = self.last;
+        let mut flags = 0;
+        let record = &mut self.record;
+        record.clear();
+        record.push(0);
+        put_varint(record, (entry.cycles - last.cycles) as u64);
+        if entry.pc != last.pc.wrapping_add(1) {
+            flags |= JUMP;
+            put_varint(record, zigzag(entry.pc as i64 - last.pc as i64));
+        }
+        let next = self.words.len() as u32;
+        match *self.words.entry(entry.ir).or_insert(next) {
+            index if index == next => {
+                flags |= NEW_WORD;
+                record.extend_from_slice(&entry.ir.to_le_bytes());
+            }
+            index => put_varint(record, index as u64),
+        }
+        for (flag, value, old) in [
+            (FB, entry.fb, last.fb),
+            (A, entry.a, last.a),
+            (L, entry.l, last.l),
+            (Q, entry.q, last.q),
+        ] {
+            if value != old {
+                flags |= flag;
+                record.extend_from_slice(&value.to_le_bytes());
+            }
+        }
+        if entry.extended {
+            flags |= EXTENDED;
+        }
+        record[0] = flags;
+        self.last = *entry;
+        self.out.write_all(record)
+    }
+
+    /// Write out everything buffered and end the compressed stream
+    pub fn finish(self) -> io::Result<()> {
+        match self.out {
+            Sink::Plain(mut out) => out.flush(),
+            #[cfg(feature = "zstd")]
+            Sink::Zstd(out) => out.finish()?.flush(),
+        }
+    }
+}
+
+/// Unpacks a trace, one entry per item
+pub struct TraceDecoder {
+    input: Box<dyn Read>,
+    last: TraceEntry,
+    words: Vec<u16>,
+}
+
+impl TraceDecoder {
+    pub fn new(input: Box<dyn Read>) -> io::Result<Self> {
+        let mut input = BufReader::new(input);
+        let mut header = [0; 9];
+        input.read_exact(&mut header)?;
+        if &header[..7] != MAGIC || header[7] != VERSION {
+            return Err(invalid("Not a packed trace"));
+        }
+        let input: Box<dyn Read> = match header[8] & COMPRESSED != 0 {
+            #[cfg(feature = "zstd")]
+            true => Box::new(zstd::stream::read::Decoder::new(input)?),
+            #[cfg(not(feature = "zstd"))]
+            true => return Err(invalid("Compressed trace; rebuild with the zstd feature")),
+            false => Box::new(input),
+        };
+        Ok(Self {
+            input,
+            last: TraceEntry::default(),
+            words: Vec::new(),
+        })
+    }
+
+    fn read(&mut self, flags: u8) -> io::Result<TraceEntry> {
+        let mut entry = self.last;
+        entry.cycles += read_varint(&mut self.input)? as usize;
+        entry.pc = match flags & JUMP != 0 {
+            true => (entry.pc as i64 + unzigzag(read_varint(&mut self.input)?)) as u16,
+            false => entry.pc.wrapping_add(1),
+        };
+        entry.ir = match flags & NEW_WORD != 0 {
+            true => {
+                let word = read_u16(&mut self.input)?;
+                self.words.push(word);
+                word
+            }
+            false => {
+                let index = read_varint(&mut self.input)? as usize;
+                *self
+                    .words
+                    .get(index)
+                    .ok_or_else(|| invalid("Instruction index out of range"))?
+            }
+        };
+        for (flag, value) in [
+            (FB, &mut entry.fb),
+            (A, &mut entry.a),
+            (L, &mut entry.l),
+            (Q, &mut entry.q),
+        ] {
+            if flags & flag != 0 {
+                *value = read_u16(&mut self.input)?;
+            }
+        }
+        entry.extended = flags & EXTENDED != 0;
+        self.last = entry;
+        Ok(entry)
+    }
+}
+
+impl Iterator for TraceDecoder {
+    type Item = io::Result<TraceEntry>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut flags = [0];
+        match self.input.read(&mut flags) {
+            Ok(0) => None,
+            Ok(_) => Some(self.read(flags[0]).map_err(|e| match e.kind() {
+                io::ErrorKind::UnexpectedEof => invalid("Trace ends mid-record"),
+                _ => e,
+            })),
+            Err(e) => Some(Err(e)),
+        }
+    }
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+fn zigzag(value: i64) -> u64 {
+    ((value << 1) ^ (value >> 63)) as u64
+}
+
+fn unzigzag(value: u64) -> i64 {
+    (value >> 1) as i64 ^ -((value & 1) as i64)
+}
+
+/// LEB128: seven bits a byte, low first
+fn put_varint(out: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        out.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+fn read_varint(input: &mut impl Read) -> io::Result<u64> {
+    let mut value = 0;
+    for shift in (0..64).step_by(7) {
+        let mut byte = [0];
+        input.read_exact(&mut byte)?;
+        value |= ((byte[0] & 0x7f) as u64) << shift;
+        if byte[0] & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    Err(invalid("Varint too long"))
+}
+
+fn read_u16(input: &mut impl Read) -> io::Result<u16> {
+    let mut bytes = [0; 2];
+    input.read_exact(&mut bytes)?;
+    Ok(u16::from_le_bytes(bytes))
+}
+
+#[cfg(test)]
+mod tracepack_tests {
+    use super::{TraceDecoder, TraceEncoder};
+    use crate::vagc::tracefile::TraceEntry;
+    use ragc_core::cpu::Cpu;
+    use ragc_core::memory::MemoryMap;
+    use std::boxed::Box;
+    use std::io::Write;
+    use std::sync::{Arc, Mutex};
+    use std::vec::Vec;
+
+    #[derive(Clone, Default)]
+    struct Shared(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Shared {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn packed_traces_round_trip_at_a_tenth_the_size() {
+        let mut cpu = Cpu::new(MemoryMap::new_blank());
+        let mut entries = Vec::new();
+        for _ in 0..2000 {
+            entries.push(TraceEntry::capture(&cpu));
+            cpu.step();
+        }
+        entries[7].pc = 0o2000; // A backwards jump
+        entries[9].a = 0o77777;
+
+        let pack = |compress| {
+            let out = Shared::default();
+            let mut encoder = TraceEncoder::new(Box::new(out.clone()), compress).unwrap();
+            for entry in entries.iter() {
+                encoder.write(entry).unwrap();
+            }
+            encoder.finish().unwrap();
+            let bytes = out.0.lock().unwrap().clone();
+            bytes
+        };
+        let bytes = pack(false);
+        assert_eq!(bytes, pack(false));
+
+        let mut text = Vec::new();
+        for entry in entries.iter() {
+            entry.write_text(&mut text).unwrap();
+        }
+        assert!(bytes.len() * 10 < text.len());
+
+        let decoded: Vec<TraceEntry> = TraceDecoder::new(Box::new(std::io::Cursor::new(bytes)))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(decoded, entries);
+
+        let mut truncated = pack(true);
+        truncated.pop();
+        let mut decoder = TraceDecoder::new(Box::new(std::io::Cursor::new(truncated))).unwrap();
+        assert!(decoder.any(|entry| entry.is_err()));
+    }
+}
@@ -0,0 +1,122 @@
+//! JSON-RPC 2.0 control socket messages. The HTTP server's endpoints take
+//! the same parameters in their bodies and reply with the same results.
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// A request line. Without an `id` it is a notification and gets no reply.
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize, JsonSchema)]
+pub struct Request {
+    /// Always "2.0"
+    pub jsonrpc: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<Value>,
+    #[serde(flatten)]
+    pub command: Command,
+}
+
+/// Methods and their parameters. Observers may call only `auth`, `peek`,
+/// `snapshot` and `channels`; each replies `true` unless noted.
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(tag = "method", content = "params", rename_all = "snake_case")]
+pub enum Command {
+    /// Take the role a token grants; needed first when tokens are set
+    Auth {
+        token: Option<String>,
+    },
+    Pause,
+    Resume,
+    Step {
+        count: Option<u32>,
+    },
+    StepOver,
+    StepOut,
+    /// Multiple of real time, above zero
+    SetSpeed {
+        speed: f64,
+    },
+    SetDiscrete {
+        name: String,
+        asserted: Option<bool>,
+    },
+    /// Hand controller deflection from -1 to 1 of full scale
+    SetAxis {
+        name: String,
+        value: f64,
+    },
+    PressKey {
+        keycode: u16,
+    },
+    Poke {
+        addr: u16,
+        value: u16,
+    },
+    SetBanks {
+        eb: u16,
+        fb: u16,
+        superbank: Option<u16>,
+    },
+    /// Replies with the word at `addr`
+    Peek {
+        addr: u16,
+    },
+    /// Replies with a [`SnapshotReply`]
+    Snapshot,
+    /// Replies with a [`ChannelEntry`] for each channel
+    Channels,
+    /// Swap in a bundled `rom` or a `rope` image file on the server and
+    /// restart into it, keeping the connected clients. `erasable` is
+    /// `clear` (the default), `keep` or `random`. Replies with the new
+    /// rope's hash in hex. Also `POST /rope`.
+    LoadRope {
+        rom: Option<String>,
+        rope: Option<String>,
+        erasable: Option<String>,
+    },
+}
+
+/// A reply: `result` on success, `error` otherwise
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize, JsonSchema)]
+pub struct Response {
+    pub jsonrpc: String,
+    pub id: Value,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub result: Option<Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<RpcError>,
+}
+
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize, JsonSchema)]
+pub struct RpcError {
+    /// JSON-RPC codes, plus -32001 unauthorized, -32002 emulator stopped
+    /// and -32003 forbidden
+    pub code: i64,
+    pub message: String,
+}
+
+/// CPU state and erasable memory
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize, JsonSchema)]
+pub struct SnapshotReply {
+    pub seed: u64,
+    pub total_cycles: u64,
+    pub a: u16,
+    pub l: u16,
+    pub q: u16,
+    pub z: u16,
+    /// Erasable banks of 256 words; `GET /state` leaves them out
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub erasable: Option<Vec<Vec<u16>>>,
+    /// Hex digest of the CPU state
+    pub state_hash: String,
+}
+
+/// One I/O channel's value
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize, JsonSchema)]
+pub struct ChannelEntry {
+    pub channel: usize,
+    pub name: Option<String>,
+    pub value: u16,
+    /// Named bits that are set, multi-bit fields as `NAME=<octal>`
+    pub bits: Vec<String>,
+}
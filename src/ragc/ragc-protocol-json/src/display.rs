@@ -0,0 +1,33 @@
+//! What a DSKY shows, and keys to press on it
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// A DSKY face, as `GET /sessions/<id>/dsky` returns it. Digits are the
+/// characters shown, with a space for a blank position.
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize, JsonSchema)]
+pub struct DisplayState {
+    pub prog: String,
+    pub verb: String,
+    pub noun: String,
+    /// R1-R3: a sign (`+`, `-` or a space) and five digits
+    pub registers: [String; 3],
+    /// Relay row 12 lamp bits
+    pub relay_lights: u16,
+    /// Channel 11 lamp bits: COMP ACTY, UPLINK ACTY, KEY REL and the flash
+    pub lamps: u16,
+    /// Channel 163 bits: STBY and the other frontend lights
+    pub lights: u16,
+    /// Numeric display brightness from 0 (dark) to 1
+    pub brightness: f32,
+    /// Changes so far; a frontend may skip redrawing an unchanged count
+    pub updates: u64,
+}
+
+/// DSKY keys to press in turn, as `POST /keys` takes them
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct KeyInput {
+    /// V, N, E (ENTR), C (CLR), R (RSET), K (KEY REL), +, - or digits
+    pub keys: String,
+}
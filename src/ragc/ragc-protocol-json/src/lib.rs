@@ -0,0 +1,93 @@
+//! JSON messages of the emulator's network interfaces: the JSON-RPC
+//! control socket, the HTTP server and the `--stream-vars` telemetry
+//! stream. Frontends can code against these types, or against the JSON
+//! Schemas exported from them and published under `schema/v<version>`.
+//!
+//! Adding optional fields keeps the version; removing or renaming a
+//! field, or changing what one means, bumps [`PROTOCOL_VERSION`].
+use schemars::schema::RootSchema;
+use schemars::schema_for;
+
+pub mod control;
+pub mod display;
+pub mod telemetry;
+
+/// Version of the messages these types describe
+pub const PROTOCOL_VERSION: u32 = 1;
+
+/// Schema of each top-level message, by file stem
+pub fn schemas() -> Vec<(&'static str, RootSchema)> {
+    let mut schemas = vec![
+        ("display", schema_for!(display::DisplayState)),
+        ("keys", schema_for!(display::KeyInput)),
+        ("request", schema_for!(control::Request)),
+        ("response", schema_for!(control::Response)),
+        ("snapshot", schema_for!(control::SnapshotReply)),
+        ("channels", schema_for!(Vec<control::ChannelEntry>)),
+        ("telemetry", schema_for!(telemetry::TelemetryFrame)),
+    ];
+    for (name, schema) in schemas.iter_mut() {
+        let meta = schema.schema.metadata();
+        meta.id = Some(format!(
+            "ragc-protocol-json/v{}/{}.schema.json",
+            PROTOCOL_VERSION, name
+        ));
+    }
+    schemas
+}
+
+/// Schema as published: pretty JSON with a trailing newline
+pub fn to_published(schema: &RootSchema) -> String {
+    let mut text = serde_json::to_string_pretty(schema).unwrap_or_default();
+    text.push('\n');
+    text
+}
+
+#[cfg(test)]
+mod protocol_tests {
+    use super::{control, display, schemas, telemetry, to_published, PROTOCOL_VERSION};
+    use serde_json::json;
+
+    #[test]
+    fn published_schemas_are_current_and_messages_round_trip() {
+        let dir = format!(
+            "{}/schema/v{}",
+            env!("CARGO_MANIFEST_DIR"),
+            PROTOCOL_VERSION
+        );
+        for (name, schema) in schemas() {
+            let path = format!("{}/{}.schema.json", dir, name);
+            let published = std::fs::read_to_string(&path).unwrap_or_default();
+            assert!(
+                published == to_published(&schema),
+                "{} is stale; run `cargo run -p ragc-protocol-json`",
+                path
+            );
+        }
+
+        let request: control::Request = serde_json::from_value(json!({
+            "jsonrpc": "2.0", "id": 7, "method": "set_banks", "params": { "eb": 3, "fb": 0o27 }
+        }))
+        .unwrap();
+        assert_eq!(
+            request.command,
+            control::Command::SetBanks {
+                eb: 3,
+                fb: 0o27,
+                superbank: None
+            }
+        );
+        let pause: control::Request =
+            serde_json::from_str(r#"{"jsonrpc":"2.0","method":"pause"}"#).unwrap();
+        assert_eq!((pause.id, pause.command), (None, control::Command::Pause));
+
+        let keys: display::KeyInput = serde_json::from_str(r#"{"keys":"V16N65E"}"#).unwrap();
+        assert_eq!(keys.keys, "V16N65E");
+        let frame: telemetry::TelemetryFrame =
+            serde_json::from_str(r#"{"t":1.5,"RN":[1.0,2.0,3.0],"FLAGWRD0":12}"#).unwrap();
+        assert!(
+            matches!(frame, telemetry::TelemetryFrame::Sample { t, ref vars }
+            if t == 1.5 && vars.len() == 2)
+        );
+    }
+}
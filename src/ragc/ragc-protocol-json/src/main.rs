@@ -0,0 +1,25 @@
+//! Write the protocol's JSON Schemas, one file per message, to a directory
+//! (default: the crate's `schema/v<version>`)
+use ragc_protocol_json::{schemas, to_published, PROTOCOL_VERSION};
+
+fn main() {
+    let dir = std::env::args().nth(1).unwrap_or_else(|| {
+        format!(
+            "{}/schema/v{}",
+            env!("CARGO_MANIFEST_DIR"),
+            PROTOCOL_VERSION
+        )
+    });
+    if let Err(e) = std::fs::create_dir_all(&dir) {
+        eprintln!("{}: {}", dir, e);
+        std::process::exit(1);
+    }
+    for (name, schema) in schemas() {
+        let path = format!("{}/{}.schema.json", dir, name);
+        if let Err(e) = std::fs::write(&path, to_published(&schema)) {
+            eprintln!("{}: {}", path, e);
+            std::process::exit(1);
+        }
+        println!("{}", path);
+    }
+}
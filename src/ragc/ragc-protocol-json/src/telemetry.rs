@@ -0,0 +1,30 @@
+//! Lines of the NDJSON variable stream `--stream-vars` writes
+
+use std::collections::BTreeMap;
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// One stream line: a timeline marker, or a sample of the configured
+/// variables at emulated time `t`
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(untagged)]
+pub enum TelemetryFrame {
+    Marker {
+        t: f64,
+        marker: String,
+    },
+    Sample {
+        t: f64,
+        #[serde(flatten)]
+        vars: BTreeMap<String, VarValue>,
+    },
+}
+
+/// A variable's scaled value, or its consecutive values
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(untagged)]
+pub enum VarValue {
+    Scalar(f64),
+    Array(Vec<f64>),
+}
@@ -0,0 +1,115 @@
+//! Descent throughput benchmark: the P63 to P66 descent flown flat out,
+//! timed against the host clock. The figure of merit is emulated seconds
+//! per host second; a baseline kept in the tree (`baseline.txt`) holds the
+//! last release's figure so a slowdown shows up as a regression.
+use std::time::Instant;
+
+use ragc_core::memory::rom::RopeImage;
+
+use crate::landing::{Landing, Outcome, PadWord};
+use crate::vehicle::Lander;
+
+/// The baseline checked in with the crate
+pub const STORED_BASELINE: &str = include_str!("../baseline.txt");
+
+/// One timed run
+#[derive(Clone, Copy, Debug)]
+pub struct Throughput {
+    pub emulated: f64, // s of scenario time flown
+    pub host: f64,     // s of host time it took
+    pub outcome: Outcome,
+}
+
+impl Throughput {
+    /// Emulated seconds per host second
+    pub fn ratio(&self) -> f64 {
+        self.emulated / self.host.max(f64::EPSILON)
+    }
+}
+
+/// Fly the descent from power on, for at most `seconds` of scenario time
+pub fn measure(rope: &RopeImage, pad: &[PadWord], seconds: f64) -> Throughput {
+    let mut landing = Landing::new(rope, pad, Lander::pdi());
+    let mut emulated = 0.0;
+    let start = Instant::now();
+    let outcome = landing.run(seconds, |t| emulated = t.time);
+    Throughput {
+        emulated,
+        host: start.elapsed().as_secs_f64(),
+        outcome,
+    }
+}
+
+/// Reference throughput and how far below it still passes
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Baseline {
+    pub ratio: f64,
+    pub tolerance: f64, // Fraction of `ratio`
+}
+
+impl Baseline {
+    /// `key = value` lines, `#` comments: `ratio` and optionally
+    /// `tolerance` (default 0.1)
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut ratio = None;
+        let mut tolerance = 0.1;
+        for (idx, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue;
+            }
+            let value = |v: &str| match v.trim().parse::<f64>() {
+                Ok(v) if v.is_finite() && v >= 0.0 => Ok(v),
+                _ => Err(format!("Invalid value on line {}: {}", idx + 1, line)),
+            };
+            match line.split_once('=') {
+                Some((key, v)) if key.trim() == "ratio" => ratio = Some(value(v)?),
+                Some((key, v)) if key.trim() == "tolerance" => tolerance = value(v)?,
+                _ => return Err(format!("Invalid baseline line {}: {}", idx + 1, line)),
+            }
+        }
+        match ratio {
+            Some(ratio) => Ok(Self { ratio, tolerance }),
+            None => Err("Baseline has no ratio".to_string()),
+        }
+    }
+
+    pub fn to_text(&self) -> String {
+        format!(
+            "# Descent benchmark, release build: emulated seconds per host second.\n\
+             # Refresh with ragc-sim --bench --write-baseline baseline.txt\n\
+             ratio = {:.1}\ntolerance = {}\n",
+            self.ratio, self.tolerance
+        )
+    }
+
+    /// Whether `run` is slower than the baseline allows
+    pub fn regressed(&self, run: &Throughput) -> bool {
+        run.ratio() < self.ratio * (1.0 - self.tolerance)
+    }
+}
+
+#[cfg(test)]
+mod bench_tests {
+    use super::{Baseline, Throughput, STORED_BASELINE};
+    use crate::landing::Outcome;
+
+    #[test]
+    fn runs_compare_against_the_baseline() {
+        let stored = Baseline::parse(STORED_BASELINE).unwrap();
+        assert_eq!(Baseline::parse(&stored.to_text()), Ok(stored));
+
+        let baseline = Baseline::parse("ratio = 200 # release build\n").unwrap();
+        assert_eq!(baseline.tolerance, 0.1);
+        let run = |host| Throughput {
+            emulated: 600.0,
+            host,
+            outcome: Outcome::TimedOut,
+        };
+        assert!(!baseline.regressed(&run(3.2)));
+        assert!(baseline.regressed(&run(3.4)));
+
+        assert!(Baseline::parse("tolerance = 0.2").is_err());
+        assert!(Baseline::parse("ratio = fast").is_err());
+    }
+}
@@ -0,0 +1,293 @@
+//! Hardware between the computer and the vehicle: IMU counters, landing
+//! radar, descent engine and RCS
+//!
+//! The stable member is taken as aligned to the landing site: X up, Y
+//! cross-range, Z downrange. LM pitch turns about Y, so CDUY reads it.
+use log::debug;
+
+use ragc_core::constants::ports;
+use ragc_core::constants::special_registers::*;
+use ragc_core::cpu::{Cpu, UnprogSequence};
+use ragc_core::frame::{FrameIo, MAX_COUNTERS};
+use ragc_core::memory::mods::RuptRequest;
+use ragc_peripherals::engine::{EngineConfig, EngineEvent, EngineModel};
+
+use crate::vehicle::Lander;
+
+/// Velocity change per PIPA pulse (LM scaling), m/s
+pub const PIPA_SCALE: f64 = 0.01;
+
+/// Angle per CDU pulse: the counter's 2^15 steps cover a turn
+pub const CDU_SCALE: f64 = 2.0 * core::f64::consts::PI / 32768.0;
+
+/// Landing radar scales: range (low scale) and beam velocities
+const LR_RANGE_SCALE: f64 = 1.079 * 0.3048;
+const LR_VELOCITY_SCALE: f64 = 0.644 * 0.3048;
+const LR_VELOCITY_BIAS: f64 = 12288.0;
+
+/// Altitude below which the landing radar locks on, m
+const LR_ACQUISITION: f64 = 12_000.0;
+
+/// Frames between a radar read request and its RADARUPT
+const LR_READ_FRAMES: u32 = 8;
+
+/// Channel 13 radar data select codes for the landing radar
+const LR_SELECT_VX: u16 = 4;
+const LR_SELECT_VY: u16 = 5;
+const LR_SELECT_VZ: u16 = 6;
+const LR_SELECT_RANGE: u16 = 7;
+const CHAN13_RADAR_SELECT: u16 = 0o7;
+const CHAN13_RADAR_ACTIVITY: u16 = 0o10;
+
+/// Channel 33 landing radar data-good inputs (active low)
+const CHAN33_LR_VELOCITY_GOOD: u16 = 0o10;
+const CHAN33_LR_RANGE_GOOD: u16 = 0o20;
+
+/// Rendezvous radar trunnion and shaft CDUs, counting in the cells the CM
+/// uses for the optics
+const RR_CDUS: [usize; 2] = [SPECIAL_REGISTER_OPTICAL_Y, SPECIAL_REGISTER_OPTICAL_X];
+
+/// MCTs between pulses each way on each RR CDU when the radar's resolvers
+/// are excited out of phase with the computer's 800 Hz reference: the CDUs
+/// hunt without settling, some 12,600 pulses a second between them
+pub const RR_HUNT_PERIOD: u32 = 27;
+
+/// Channel 5 jets giving positive and negative pitch torque, taking quads
+/// 1 and 4 on the +Z side: +Q fires 1U, 4U, 2D and 3D
+const PITCH_UP_JETS: u16 = 0o151;
+const PITCH_DOWN_JETS: u16 = 0o226;
+
+/// Misconfigure the rendezvous radar interface as on Apollo 11's descent,
+/// leaving both RR CDUs hunting. The counters hold steady on average, but
+/// every pulse steals an MCT, about 15% of the computer's time.
+pub fn hunt_rendezvous_radar(io: &mut FrameIo) -> bool {
+    RR_CDUS.iter().all(|&cdu| {
+        io.start_train(UnprogSequence::PCDU(cdu), RR_HUNT_PERIOD)
+            && io.start_train(UnprogSequence::MCDU(cdu), RR_HUNT_PERIOD)
+    })
+}
+
+/// MCTs the rendezvous radar CDUs have stolen from the program
+pub fn rendezvous_radar_steal(cpu: &Cpu) -> u64 {
+    RR_CDUS
+        .iter()
+        .map(|&cdu| cpu.stolen_cycles(cdu) as u64)
+        .sum()
+}
+
+/// Ones' complement 15-bit counter value as a signed integer
+fn counter_value(word: u16) -> i32 {
+    if word & 0o40000 != 0 {
+        -(((!word) & 0o37777) as i32)
+    } else {
+        (word & 0o37777) as i32
+    }
+}
+
+/// Where a landing radar read is
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum RadarCycle {
+    Idle,
+    Measuring(u32, u16), // Frames left, data select
+    Shifted,             // Data is in RNRAD, RADARUPT due
+}
+
+/// Vehicle-side peripherals of the loop, updated once per frame
+pub struct Interface {
+    pipa_residue: [f64; 3], // m/s not yet sent as pulses, X/Y/Z
+    cdu_sent: i32,          // CDUY pulses delivered so far
+    coarse_align: f64,      // Gimbal pitch torqued in by coarse align, rad
+    needles: [i32; 3],      // IMU error counters driving the FDAI needles
+    display_inertial: bool,
+    radar: RadarCycle,
+    radar_activity: bool,
+    radar_good: bool,
+    engine: EngineModel,
+    engine_events: Vec<EngineEvent>,
+}
+
+impl Interface {
+    pub fn new() -> Self {
+        Self {
+            pipa_residue: [0.0; 3],
+            cdu_sent: 0,
+            coarse_align: 0.0,
+            needles: [0; 3],
+            display_inertial: false,
+            radar: RadarCycle::Idle,
+            radar_activity: false,
+            radar_good: false,
+            engine: EngineModel::new(EngineConfig::DPS),
+            engine_events: Vec::new(),
+        }
+    }
+
+    /// Thrust the engine is producing, N
+    pub fn thrust(&self) -> f64 {
+        self.engine.thrust()
+    }
+
+    pub fn engine(&self) -> &EngineModel {
+        &self.engine
+    }
+
+    /// Engine state changes and throttle settings so far, oldest first
+    pub fn engine_events(&self) -> &[EngineEvent] {
+        &self.engine_events
+    }
+
+    /// Attitude error needles, when channel 12 routes the IMU error
+    /// counters to the FDAI
+    pub fn error_needles(&self) -> Option<[i32; 3]> {
+        self.display_inertial.then_some(self.needles)
+    }
+
+    /// Read the engine, throttle and RCS outputs of the frame just run,
+    /// `dt` seconds long. Returns net positive pitch jet-seconds.
+    pub fn effectors(&mut self, cpu: &mut Cpu, io: &FrameIo, mct_seconds: f64, dt: f64) -> f64 {
+        let mem = cpu.memory_mut();
+        let events = &mut self.engine_events;
+        self.engine.update(mem, dt, |event| {
+            debug!("Engine {:?}", event);
+            events.push(event);
+        });
+
+        // CDU commands torque the gimbals in coarse align, or count into
+        // the error needles; the counters hold at zero while disabled
+        let chan12 = mem.peek_channel(ports::CHANNEL_CHAN12);
+        let commands = [
+            SPECIAL_REGISTER_CONTROL_X_CMD,
+            SPECIAL_REGISTER_CONTROL_Y_CMD,
+            SPECIAL_REGISTER_CONTROL_Z_CMD,
+        ];
+        for (axis, addr) in commands.iter().enumerate() {
+            let pulses = counter_value(mem.drain_output_counter(*addr));
+            if chan12 & ports::CHAN12_COARSE_ALIGN != 0 {
+                if *addr == SPECIAL_REGISTER_CONTROL_Y_CMD {
+                    self.coarse_align += pulses as f64 * CDU_SCALE;
+                }
+            } else if chan12 & ports::CHAN12_ENABLE_IMU_ERROR != 0 {
+                self.needles[axis] += pulses;
+            }
+        }
+        if chan12 & ports::CHAN12_ENABLE_IMU_ERROR == 0 {
+            self.needles = [0; 3];
+        }
+        self.display_inertial = chan12 & ports::CHAN12_DISPLAY_INERTIAL != 0;
+
+        let jet_mcts = |mask: u16| -> u32 {
+            (0..8)
+                .filter(|bit| mask & (1 << bit) != 0)
+                .map(|bit| io.pyjets[bit])
+                .sum()
+        };
+        (jet_mcts(PITCH_UP_JETS) as f64 - jet_mcts(PITCH_DOWN_JETS) as f64) * mct_seconds
+    }
+
+    /// Queue PIPA and CDU pulses for the vehicle's motion, and run the
+    /// landing radar interface
+    pub fn sensors(&mut self, cpu: &mut Cpu, io: &mut FrameIo, lander: &Lander, sensed: [f64; 2]) {
+        // Downrange is platform Z, up is platform X
+        self.pipa_residue[0] += sensed[1];
+        self.pipa_residue[2] += sensed[0];
+        let pipas = [
+            SPECIAL_REGISTER_INERTIAL_X,
+            SPECIAL_REGISTER_INERTIAL_Y,
+            SPECIAL_REGISTER_INERTIAL_Z,
+        ];
+        for (residue, addr) in self.pipa_residue.iter_mut().zip(pipas.iter()) {
+            while residue.abs() >= PIPA_SCALE && io.counters.len() < MAX_COUNTERS {
+                let seq = if *residue > 0.0 {
+                    UnprogSequence::PINC(*addr)
+                } else {
+                    UnprogSequence::MINC(*addr)
+                };
+                io.pulse(seq);
+                *residue -= PIPA_SCALE.copysign(*residue);
+            }
+        }
+
+        // The CDU recounts the gimbal angle from zero once released
+        let zeroed =
+            cpu.memory().peek_channel(ports::CHANNEL_CHAN12) & ports::CHAN12_ZERO_IMU_CDUS != 0;
+        if zeroed {
+            self.cdu_sent = 0;
+        }
+        let cdu_target = ((lander.pitch + self.coarse_align) / CDU_SCALE).round() as i32;
+        while !zeroed && self.cdu_sent != cdu_target && io.counters.len() < MAX_COUNTERS {
+            let step = (cdu_target - self.cdu_sent).signum();
+            io.pulse(match step {
+                1 => UnprogSequence::PCDU(SPECIAL_REGISTER_CONTROL_DISPLAY_Y),
+                _ => UnprogSequence::MCDU(SPECIAL_REGISTER_CONTROL_DISPLAY_Y),
+            });
+            self.cdu_sent += step;
+        }
+
+        self.landing_radar(cpu, io, lander);
+    }
+
+    fn landing_radar(&mut self, cpu: &mut Cpu, io: &mut FrameIo, lander: &Lander) {
+        let good = lander.altitude < LR_ACQUISITION;
+        if good != self.radar_good {
+            let mask = CHAN33_LR_VELOCITY_GOOD | CHAN33_LR_RANGE_GOOD;
+            let mem = cpu.memory_mut();
+            if good {
+                mem.pin_channel_bits(ports::CHANNEL_CHAN33, mask, 0);
+            } else {
+                mem.release_channel_bits(ports::CHANNEL_CHAN33, mask);
+            }
+            self.radar_good = good;
+        }
+
+        let chan13 = cpu.memory().peek_channel(ports::CHANNEL_CHAN13);
+        let activity = chan13 & CHAN13_RADAR_ACTIVITY != 0;
+        let requested = activity && !self.radar_activity;
+        self.radar_activity = activity;
+
+        self.radar = match self.radar {
+            RadarCycle::Idle if requested => {
+                RadarCycle::Measuring(LR_READ_FRAMES, chan13 & CHAN13_RADAR_SELECT)
+            }
+            RadarCycle::Measuring(0, select) if io.counters.len() + 15 <= MAX_COUNTERS => {
+                let data = radar_data(select, lander);
+                for bit in (0..15).rev() {
+                    io.pulse(match data & (1 << bit) {
+                        0 => UnprogSequence::SHINC(SPECIAL_REGISTER_NAV_RADAR),
+                        _ => UnprogSequence::SHANC(SPECIAL_REGISTER_NAV_RADAR),
+                    });
+                }
+                RadarCycle::Shifted
+            }
+            RadarCycle::Measuring(frames, select) => {
+                RadarCycle::Measuring(frames.saturating_sub(1), select)
+            }
+            RadarCycle::Shifted => {
+                cpu.request_rupt(RuptRequest::Radarupt);
+                RadarCycle::Idle
+            }
+            idle => idle,
+        };
+    }
+}
+
+impl Default for Interface {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Landing radar word for a data select code
+fn radar_data(select: u16, lander: &Lander) -> u16 {
+    // Beam velocities are in the LM body axes: X along thrust, Z forward
+    let (sin, cos) = lander.pitch.sin_cos();
+    let [downrange, up] = lander.velocity;
+    let velocity = |v: f64| (LR_VELOCITY_BIAS + v / LR_VELOCITY_SCALE).round();
+    let raw = match select {
+        LR_SELECT_VX => velocity(up * cos - downrange * sin),
+        LR_SELECT_VY => velocity(0.0),
+        LR_SELECT_VZ => velocity(downrange * cos + up * sin),
+        LR_SELECT_RANGE => (lander.altitude.max(0.0) / cos.max(0.1) / LR_RANGE_SCALE).round(),
+        _ => 0.0,
+    };
+    raw.clamp(0.0, 0o77777 as f64) as u16
+}
@@ -0,0 +1,526 @@
+//! The closed loop: computer, interface hardware, vehicle and a scripted
+//! crew that runs P63, answers its prompts and takes over in P66
+use log::{info, warn};
+
+use ragc_core::constants::ports;
+use ragc_core::cpu::Cpu;
+use ragc_core::frame::FrameIo;
+use ragc_core::memory::channels::Discrete;
+use ragc_core::memory::mods::MCT_SECONDS;
+use ragc_core::memory::rom::{RomInfo, RopeImage};
+use ragc_core::memory::MemoryMapBuilder;
+
+use crate::interface::{self, Interface};
+use crate::vehicle::Lander;
+
+/// Frame length; vehicle and interface update once per frame
+const FRAME_SECONDS: f64 = 0.01;
+
+/// Time the crew waits after power on before keying P63
+const BOOT_SECONDS: f64 = 4.0;
+
+/// Spacing of keypresses, long enough for PINBALL to take each key
+const KEY_SECONDS: f64 = 0.2;
+
+/// How long a flashing prompt is left before PRO, and PRO is held
+const PROMPT_SECONDS: f64 = 2.0;
+const PRO_HOLD_SECONDS: f64 = 0.5;
+
+/// Altitude at which the crew clicks the ROD switch in P64 (P66 entry)
+const P66_ALTITUDE: f64 = 150.0;
+
+/// Channel 31 mode control input (active low): guidance in AUTO
+const CHAN31_AUTO: u16 = 0o20000;
+
+/// Channel 16 rate of descent switch, decrease descent rate (active high)
+const CHAN16_ROD_MINUS: u16 = 0o100;
+
+/// Channel 11 VERB/NOUN flash
+const CHAN11_FLASH: u16 = 0o40;
+
+/// One erasable word to load before the run: bank, offset, value
+pub type PadWord = (usize, usize, u16);
+
+fn relay_digit(code: u16) -> Option<u8> {
+    match code {
+        0o25 => Some(0),
+        0o03 => Some(1),
+        0o31 => Some(2),
+        0o33 => Some(3),
+        0o17 => Some(4),
+        0o36 => Some(5),
+        0o34 => Some(6),
+        0o23 => Some(7),
+        0o35 => Some(8),
+        0o37 => Some(9),
+        _ => None,
+    }
+}
+
+/// PROG, VERB and NOUN as rebuilt from channel 10 relay words
+#[derive(Clone, Copy, Default, PartialEq, Debug)]
+struct Dsky {
+    prog: Option<u8>,
+    verb: Option<u8>,
+    noun: Option<u8>,
+}
+
+impl Dsky {
+    fn apply(&mut self, word: u16) {
+        let pair = match (relay_digit((word >> 5) & 0o37), relay_digit(word & 0o37)) {
+            (Some(tens), Some(units)) => Some(tens * 10 + units),
+            _ => None,
+        };
+        match word >> 11 {
+            11 => self.prog = pair,
+            10 => self.verb = pair,
+            9 => self.noun = pair,
+            _ => {}
+        }
+    }
+}
+
+/// Scripted crew actions
+struct Crew {
+    keys: core::str::Chars<'static>,
+    next_key: f64,
+    flashing_since: Option<f64>,
+    pro_until: Option<f64>,
+    rod_until: Option<f64>,
+    rod_clicked: bool,
+}
+
+impl Crew {
+    fn new() -> Self {
+        Self {
+            keys: "V37E63E".chars(),
+            next_key: BOOT_SECONDS,
+            flashing_since: None,
+            pro_until: None,
+            rod_until: None,
+            rod_clicked: false,
+        }
+    }
+
+    fn act(&mut self, now: f64, cpu: &mut Cpu, io: &mut FrameIo, dsky: &Dsky, lander: &Lander) {
+        if now >= self.next_key {
+            if let Some(code) = self.keys.next().and_then(keycode) {
+                io.press_key(code);
+            }
+            self.next_key = now + KEY_SECONDS;
+        }
+
+        // Proceed on flashing displays, checklists and the engine enable request
+        let flashing = cpu.memory().peek_channel(ports::CHANNEL_DSALMOUT) & CHAN11_FLASH != 0;
+        let prompt = flashing && matches!(dsky.verb, Some(6) | Some(50) | Some(99));
+        self.flashing_since = match (prompt, self.flashing_since) {
+            (true, None) => Some(now),
+            (true, since) => since,
+            (false, _) => None,
+        };
+        let mem = cpu.memory_mut();
+        if self
+            .flashing_since
+            .is_some_and(|since| now - since >= PROMPT_SECONDS)
+        {
+            info!(
+                "{:7.2} s: PRO on V{:02}N{:02}",
+                now,
+                dsky.verb.unwrap_or(0),
+                dsky.noun.unwrap_or(0)
+            );
+            mem.pin_channel_bits(ports::CHANNEL_CHAN32, ports::CHAN32_PROCEED, 0);
+            self.pro_until = Some(now + PRO_HOLD_SECONDS);
+            self.flashing_since = None;
+        }
+        if self.pro_until.is_some_and(|until| now >= until) {
+            mem.release_channel_bits(ports::CHANNEL_CHAN32, ports::CHAN32_PROCEED);
+            self.pro_until = None;
+        }
+
+        // Take over the rate of descent near the ground
+        if !self.rod_clicked && dsky.prog == Some(64) && lander.altitude < P66_ALTITUDE {
+            info!("{:7.2} s: ROD click at {:.0} m", now, lander.altitude);
+            mem.pin_channel_bits(ports::CHANNEL_NAVKEYIN, CHAN16_ROD_MINUS, CHAN16_ROD_MINUS);
+            self.rod_until = Some(now + PRO_HOLD_SECONDS);
+            self.rod_clicked = true;
+        }
+        if self.rod_until.is_some_and(|until| now >= until) {
+            mem.release_channel_bits(ports::CHANNEL_NAVKEYIN, CHAN16_ROD_MINUS);
+            self.rod_until = None;
+        }
+    }
+}
+
+fn keycode(key: char) -> Option<u16> {
+    match key {
+        'V' => Some(0o21),
+        'N' => Some(0o37),
+        'E' => Some(0o34),
+        '0' => Some(0o20),
+        '1'..='9' => key.to_digit(10).map(|d| d as u16),
+        _ => None,
+    }
+}
+
+/// One sample of the run
+#[derive(Clone, Copy, Debug)]
+pub struct Telemetry {
+    pub time: f64,        // s since power on
+    pub lander: Lander,   // Vehicle state
+    pub thrust: f64,      // N
+    pub prog: Option<u8>, // Major mode on the DSKY
+}
+
+/// How a run ended
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Outcome {
+    Landed {
+        vertical_speed: f64, // m/s at contact, positive down
+        miss: f64,           // m from the landing site, positive long
+        propellant: f64,     // kg left
+    },
+    TimedOut,
+}
+
+/// Computer and vehicle run in lockstep, one frame at a time
+pub struct Landing<'a> {
+    cpu: Cpu<'a>,
+    io: FrameIo,
+    interface: Interface,
+    crew: Crew,
+    dsky: Dsky,
+    lander: Lander,
+    time: f64,
+    mct_seconds: f64, // Scenario time per MCT of the (possibly drifting) oscillator
+}
+
+impl<'a> Landing<'a> {
+    /// Power on `rope` with the `pad` erasable load, the engine armed and
+    /// guidance in AUTO, over `lander`
+    pub fn new(rope: &'a RopeImage, pad: &[PadWord], lander: Lander) -> Self {
+        Self::with_rom_info(rope, pad, lander, RomInfo::BLOCK_II)
+    }
+
+    /// As `new`, with the rope's FAILREG and MODREG so the computer can
+    /// count alarms and follow the program
+    pub fn with_rom_info(
+        rope: &'a RopeImage,
+        pad: &[PadWord],
+        lander: Lander,
+        info: RomInfo,
+    ) -> Self {
+        let mut builder = MemoryMapBuilder::new()
+            .rope(rope)
+            .rom_info(info)
+            .relay_cadence(0);
+        for (bank, offset, value) in pad.iter() {
+            builder = builder.erasable(*bank, *offset, &[*value]);
+        }
+        let mut cpu = Cpu::new(builder.build());
+        cpu.reset();
+
+        let mem = cpu.memory_mut();
+        mem.set_discrete(Discrete::EngineArmed, true);
+        mem.pin_channel_bits(ports::CHANNEL_CHAN31, CHAN31_AUTO, 0);
+
+        Self {
+            cpu,
+            io: FrameIo::new(),
+            interface: Interface::new(),
+            crew: Crew::new(),
+            dsky: Dsky::default(),
+            lander,
+            time: 0.0,
+            mct_seconds: MCT_SECONDS,
+        }
+    }
+
+    /// Run the oscillator `ppm` parts per million fast (positive) or slow
+    /// against scenario time, so the AGC's clocks drift from the vehicle's
+    pub fn set_drift(&mut self, ppm: f64) {
+        self.mct_seconds = MCT_SECONDS / (1.0 + ppm * 1e-6);
+    }
+
+    /// Run one frame of the loop
+    pub fn step(&mut self) -> Telemetry {
+        let frame_mcts = (FRAME_SECONDS / self.mct_seconds) as u32;
+        let cycles = self.cpu.run_frame(&mut self.io, frame_mcts);
+        let dt = cycles as f64 * self.mct_seconds;
+        for (channel, value) in self.io.channel_writes.iter() {
+            if *channel == ports::CHANNEL_DSKY {
+                self.dsky.apply(*value);
+            }
+        }
+
+        let pitch_jets = self
+            .interface
+            .effectors(&mut self.cpu, &self.io, self.mct_seconds, dt);
+        let sensed = self.lander.step(dt, self.interface.thrust(), pitch_jets);
+        self.interface
+            .sensors(&mut self.cpu, &mut self.io, &self.lander, sensed);
+        self.time += dt;
+        self.crew.act(
+            self.time,
+            &mut self.cpu,
+            &mut self.io,
+            &self.dsky,
+            &self.lander,
+        );
+
+        Telemetry {
+            time: self.time,
+            lander: self.lander,
+            thrust: self.interface.thrust(),
+            prog: self.dsky.prog,
+        }
+    }
+
+    /// Run until touchdown or `seconds` have passed, sampling every frame
+    pub fn run(&mut self, seconds: f64, mut sample: impl FnMut(&Telemetry)) -> Outcome {
+        while self.time < seconds {
+            let telemetry = self.step();
+            sample(&telemetry);
+            if let Some(landed) = self.landed() {
+                return landed;
+            }
+        }
+        Outcome::TimedOut
+    }
+
+    /// How the vehicle came down, once it has
+    pub fn landed(&self) -> Option<Outcome> {
+        self.lander.landed().then(|| Outcome::Landed {
+            vertical_speed: -self.lander.velocity[1],
+            miss: self.lander.downrange,
+            propellant: self.lander.propellant(),
+        })
+    }
+
+    /// Leave the rendezvous radar CDUs hunting from the next frame on
+    pub fn hunt_rendezvous_radar(&mut self) {
+        if !interface::hunt_rendezvous_radar(&mut self.io) {
+            warn!("No room for the RR CDU pulse trains");
+        }
+    }
+
+    pub fn interface(&self) -> &Interface {
+        &self.interface
+    }
+
+    pub fn cpu(&self) -> &Cpu<'a> {
+        &self.cpu
+    }
+}
+
+#[cfg(test)]
+mod landing_tests {
+    use super::{Landing, Outcome};
+    use crate::interface::PIPA_SCALE;
+    use crate::vehicle::{Lander, DPS_MAX_THRUST, DPS_MIN_THROTTLE};
+    use ragc_core::constants::ports;
+    use ragc_core::constants::special_registers::*;
+    use ragc_core::memory::rom::{encode_word, RopeImage, BANK_MAPPING};
+    use ragc_peripherals::engine::EngineState;
+
+    /// Test rope: T5RUPT every other TIME5 tick keeps the rupt lock monitor
+    /// quiet, RADARUPT copies RNRAD to 100 and other vectors RESUME. `main`
+    /// is placed at 4056, after TIME5 is preset.
+    fn rope(main: &[u16]) -> Box<RopeImage> {
+        let mut program = [0o50017; 0o56]; // RESUME
+        program[0o00] = 0o14054; // TCF 4054
+        program[0o10..0o13].copy_from_slice(&[0o34053, 0o56030, 0o50017]); // T5RUPT
+        program[0o44..0o47].copy_from_slice(&[0o30046, 0o56100, 0o50017]); // RADARUPT
+        program[0o53..0o56].copy_from_slice(&[
+            0o37776, // 4053 TIME5 preset
+            0o34053, // 4054 CA 4053
+            0o56030, // 4055 XCH TIME5
+        ]);
+        let mut rope: Box<RopeImage> = Box::new([[0; 1024]; 36]);
+        for (offset, word) in program.iter().chain(main.iter()).enumerate() {
+            rope[BANK_MAPPING[2]][offset] = encode_word(*word);
+        }
+        rope
+    }
+
+    #[test]
+    fn engine_and_pipas_close_the_loop() {
+        // Light the engine, load 200 THRUST pulses, enable the thrust drive
+        let rope = rope(&[
+            0o34071, // 4056 CA 4071
+            0o00006, // 4057 EXTEND
+            0o01011, // 4060 WRITE 11 (ENGINE ON)
+            0o34072, // 4061 CA 4072
+            0o56055, // 4062 XCH THRUST
+            0o34073, // 4063 CA 4073
+            0o00006, // 4064 EXTEND
+            0o01014, // 4065 WRITE 14 (DRIVE THRUST)
+            0o00003, // 4066 RELINT
+            0o30067, // 4067 CA NEWJOB
+            0o14067, // 4070 TCF 4067
+            0o10000, // 4071 ENGINE ON
+            0o00310, // 4072 200 pulses
+            0o00010, // 4073 DRIVE THRUST
+        ]);
+
+        // Hover test stand: upright, stationary, engine starting at minimum
+        let lander = Lander {
+            velocity: [0.0, 0.0],
+            pitch: 0.0,
+            altitude: 1000.0,
+            ..Lander::pdi()
+        };
+        let mut landing = Landing::new(&rope, &[], lander);
+        let mut last = None;
+        let outcome = landing.run(2.0, |t| last = Some(*t));
+        assert_eq!(outcome, Outcome::TimedOut);
+
+        assert_eq!(landing.cpu().restart_count(), 0);
+
+        // The drive took the pulses at 2.8 lbf each, and the engine has
+        // built up to the setting
+        let last = last.unwrap();
+        let expected = DPS_MIN_THROTTLE * DPS_MAX_THRUST + 200.0 * 12.45;
+        assert!((landing.interface().engine().setting() - expected).abs() < 1e-6);
+        assert!(
+            (last.thrust - expected).abs() < 0.01 * expected,
+            "{}",
+            last.thrust
+        );
+        assert_eq!(landing.interface().engine().state(), EngineState::Running);
+        assert_eq!(landing.cpu().memory().read(SPECIAL_REGISTER_THRUST), 0);
+
+        // PIPAX counted the upward velocity the engine gave, but for the
+        // residue and the last frame's pulses, still queued
+        let sensed = (last.lander.velocity[1] + 1.622 * last.time) / PIPA_SCALE;
+        let pipax = landing.cpu().memory().read(SPECIAL_REGISTER_INERTIAL_X) as f64;
+        assert!((pipax - sensed).abs() <= 2.0, "{} vs {}", pipax, sensed);
+        assert!(pipax > 20.0);
+    }
+
+    #[test]
+    fn radar_and_cdu_inputs_arrive() {
+        // Request a landing radar range read, then idle
+        let rope = rope(&[
+            0o34064, // 4056 CA 4064
+            0o00006, // 4057 EXTEND
+            0o01013, // 4060 WRITE 13 (LR RANGE, RADAR ACTIVITY)
+            0o00003, // 4061 RELINT
+            0o30067, // 4062 CA NEWJOB
+            0o14062, // 4063 TCF 4062
+            0o00017, // 4064
+        ]);
+
+        let lander = Lander {
+            velocity: [0.0, 0.0],
+            pitch: 0.1,
+            altitude: 1000.0,
+            ..Lander::pdi()
+        };
+        let mut landing = Landing::new(&rope, &[], lander);
+        let mut last = None;
+        landing.run(0.3, |t| last = Some(*t));
+        let last = last.unwrap();
+        let mem = landing.cpu().memory();
+
+        assert_eq!(mem.read(SPECIAL_REGISTER_CONTROL_DISPLAY_Y), 522); // 0.1 rad
+        assert_eq!(
+            mem.peek_channel(ports::CHANNEL_CHAN33) & 0o30,
+            0,
+            "LR data good"
+        );
+
+        // Slant range at 1.079 ft per bit, shifted in ahead of RADARUPT
+        let range = mem.read(0o100) as f64 * 1.079 * 0.3048;
+        let slant = last.lander.altitude / 0.1f64.cos();
+        assert!((range - slant).abs() < 2.0, "{} vs {}", range, slant);
+        assert_eq!(landing.cpu().restart_count(), 0);
+    }
+
+    /// Write `chan12` and load CDUXCMD-CDUZCMD, then idle
+    fn chan12_rope(chan12: u16, commands: [u16; 3]) -> Box<RopeImage> {
+        rope(&[
+            0o34072, // 4056 CA 4072
+            0o00006, // 4057 EXTEND
+            0o01012, // 4060 WRITE 12
+            0o34073, // 4061 CA 4073
+            0o56050, // 4062 XCH CDUXCMD
+            0o34074, // 4063 CA 4074
+            0o56051, // 4064 XCH CDUYCMD
+            0o34075, // 4065 CA 4075
+            0o56052, // 4066 XCH CDUZCMD
+            0o00003, // 4067 RELINT
+            0o30067, // 4070 CA NEWJOB
+            0o14070, // 4071 TCF 4070
+            chan12,
+            commands[0],
+            commands[1],
+            commands[2],
+        ])
+    }
+
+    #[test]
+    fn chan12_routes_cdu_commands() {
+        let lander = Lander {
+            velocity: [0.0, 0.0],
+            pitch: 0.1,
+            altitude: 1000.0,
+            ..Lander::pdi()
+        };
+
+        // Coarse align torques the gimbal; the CDU follows it
+        let rope = chan12_rope(ports::CHAN12_COARSE_ALIGN, [0, 100, 0]);
+        let mut landing = Landing::new(&rope, &[], lander);
+        landing.run(0.3, |_| {});
+        let mem = landing.cpu().memory();
+        assert_eq!(mem.read(SPECIAL_REGISTER_CONTROL_DISPLAY_Y), 622);
+        assert_eq!(landing.interface().error_needles(), None);
+
+        // Error counters reach the needles; zeroed CDUs ignore the gimbals
+        let rope = chan12_rope(
+            ports::CHAN12_ENABLE_IMU_ERROR
+                | ports::CHAN12_DISPLAY_INERTIAL
+                | ports::CHAN12_ZERO_IMU_CDUS,
+            [5, 0o12, 0o77774],
+        );
+        let mut landing = Landing::new(&rope, &[], lander);
+        landing.run(0.3, |_| {});
+        let mem = landing.cpu().memory();
+        assert_eq!(mem.read(SPECIAL_REGISTER_CONTROL_DISPLAY_Y), 0);
+        assert_eq!(landing.interface().error_needles(), Some([5, 0o12, -3]));
+        assert_eq!(landing.cpu().restart_count(), 0);
+    }
+
+    #[test]
+    fn drifting_oscillator_gains_on_scenario_time() {
+        use ragc_core::constants::timers::TIMER_1_ADDRESS;
+
+        let rope = rope(&[
+            0o00003, // 4056 RELINT
+            0o30067, // 4057 CA NEWJOB
+            0o14057, // 4060 TCF 4057
+        ]);
+        let lander = Lander {
+            altitude: 1000.0,
+            ..Lander::pdi()
+        };
+        let mut elapsed = [0; 2];
+        for (drift, time1) in [0.0, 20000.0].iter().zip(elapsed.iter_mut()) {
+            let mut landing = Landing::new(&rope, &[], lander);
+            landing.set_drift(*drift);
+            let mut last = None;
+            landing.run(2.0, |t| last = Some(*t));
+
+            // TIME1 counts centiseconds of the drifting oscillator
+            *time1 = landing.cpu().memory().read(TIMER_1_ADDRESS);
+            let expected = last.unwrap().time * 100.0 * (1.0 + drift * 1e-6);
+            assert!(
+                (*time1 as f64 - expected).abs() <= 1.0,
+                "{} vs {}",
+                time1,
+                expected
+            );
+        }
+        assert!(elapsed[1] >= elapsed[0] + 3);
+    }
+}
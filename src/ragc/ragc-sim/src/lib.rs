@@ -0,0 +1,12 @@
+//! Closed-loop LM descent demo
+//!
+//! A planar vehicle model is driven by the computer's engine, throttle and
+//! RCS outputs, and feeds back PIPA, CDU and landing radar inputs through
+//! the same counters and channels the real interface hardware used. Run
+//! with LUMINARY and a pad load matching [`vehicle::Lander::pdi`], a
+//! scripted crew keys P63 and the loop flies it down through P64 into P66.
+pub mod bench;
+pub mod interface;
+pub mod landing;
+pub mod overload;
+pub mod vehicle;
@@ -0,0 +1,256 @@
+use log::info;
+
+use ragc_core::memory::rom::RomInfo;
+use ragc_sim::bench::{self, Baseline};
+use ragc_sim::landing::{Landing, Outcome, PadWord};
+use ragc_sim::overload;
+use ragc_sim::vehicle::Lander;
+
+/// Parse a pad load: `BANK OFFSET VALUE` per line, all octal, `#` comments
+fn parse_pad(text: &str) -> Result<Vec<PadWord>, String> {
+    let mut words = Vec::new();
+    for (idx, line) in text.lines().enumerate() {
+        let line = line.split('#').next().unwrap_or_default().trim();
+        if line.is_empty() {
+            continue;
+        }
+        let fields: Vec<_> = line
+            .split_whitespace()
+            .map(|f| u16::from_str_radix(f, 8).ok())
+            .collect();
+        match fields[..] {
+            [Some(bank), Some(offset), Some(value)] if bank < 8 && offset < 0o400 => {
+                words.push((bank as usize, offset as usize, value))
+            }
+            _ => return Err(format!("Invalid pad word on line {}: {}", idx + 1, line)),
+        }
+    }
+    Ok(words)
+}
+
+/// Fly the descent with the rendezvous radar misconfigured from P63 on,
+/// as on Apollo 11, and exit with failure unless the executive overflowed,
+/// recovered without a hardware restart and the LM landed
+fn run_overload(failreg: &str, pad: &[PadWord], drift: f64, seconds: f64) {
+    let failreg = match usize::from_str_radix(failreg, 8) {
+        Ok(addr) if addr < 0o1400 => addr,
+        _ => {
+            eprintln!("Invalid FAILREG address {}", failreg);
+            std::process::exit(1);
+        }
+    };
+    let info = RomInfo {
+        failreg: Some(failreg),
+        ..RomInfo::BLOCK_II
+    };
+    let mut landing =
+        Landing::with_rom_info(ragc_binaries::LUMINARY99_ROPE, pad, Lander::pdi(), info);
+    landing.set_drift(drift);
+    let run = overload::fly(&mut landing, |t| t.prog == Some(63), seconds);
+
+    match run.fault_at {
+        Some(at) => println!("{:7.1} s  Rendezvous radar CDUs hunting", at),
+        None => println!("P63 never came up; the radar stayed quiet"),
+    }
+    for (at, code) in run.alarms.iter() {
+        println!("{:7.1} s  Program alarm {:05o}", at, code);
+    }
+    println!(
+        "{} MCTs stolen by the RR CDUs, {} hardware restarts",
+        run.stolen, run.restarts
+    );
+    let landed = matches!(run.outcome, Outcome::Landed { .. });
+    if !landed {
+        println!("No touchdown after {} s", seconds);
+    }
+    if !run.overflowed() || !landed {
+        std::process::exit(1);
+    }
+}
+
+/// Time the descent, report its throughput and exit with failure if it
+/// fell below the baseline
+fn run_bench(matches: &clap::ArgMatches, pad: &[PadWord], seconds: f64) {
+    let baseline = match matches.value_of("baseline") {
+        Some(path) => std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e)),
+        None => Ok(bench::STORED_BASELINE.to_string()),
+    }
+    .and_then(|text| Baseline::parse(&text));
+    let baseline = match baseline {
+        Ok(baseline) => baseline,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+
+    let run = bench::measure(ragc_binaries::LUMINARY99_ROPE, pad, seconds);
+    if run.outcome == Outcome::TimedOut {
+        println!("No touchdown after {} s; timing the whole run", seconds);
+    }
+    println!(
+        "{:.1} s emulated in {:.2} s: {:.1}x real time (baseline {:.1}x)",
+        run.emulated,
+        run.host,
+        run.ratio(),
+        baseline.ratio
+    );
+
+    if let Some(path) = matches.value_of("write-baseline") {
+        let new = Baseline {
+            ratio: run.ratio(),
+            ..baseline
+        };
+        if let Err(e) = std::fs::write(path, new.to_text()) {
+            eprintln!("{}: {}", path, e);
+            std::process::exit(1);
+        }
+    }
+    if baseline.regressed(&run) {
+        println!(
+            "Regression: more than {:.0}% below the baseline",
+            baseline.tolerance * 100.0
+        );
+        std::process::exit(1);
+    }
+}
+
+fn main() {
+    env_logger::init();
+    let matches = clap::App::new("RAGC descent simulation")
+        .about("Fly LUMINARY through P63-P66 against a planar LM model")
+        .arg(
+            clap::Arg::with_name("pad")
+                .long("pad")
+                .takes_value(true)
+                .help("Erasable pad load matching the PDI state (BANK OFFSET VALUE, octal)"),
+        )
+        .arg(
+            clap::Arg::with_name("seconds")
+                .long("seconds")
+                .takes_value(true)
+                .default_value("900")
+                .help("Give up after this much emulated time"),
+        )
+        .arg(
+            clap::Arg::with_name("every")
+                .long("every")
+                .takes_value(true)
+                .default_value("10")
+                .help("Seconds between telemetry lines"),
+        )
+        .arg(
+            clap::Arg::with_name("drift-ppm")
+                .long("drift-ppm")
+                .takes_value(true)
+                .allow_hyphen_values(true)
+                .help("Oscillator error in parts per million; positive runs the AGC fast"),
+        )
+        .arg(
+            clap::Arg::with_name("bench")
+                .long("bench")
+                .help("Time the descent flat out and compare with the baseline"),
+        )
+        .arg(
+            clap::Arg::with_name("baseline")
+                .long("baseline")
+                .takes_value(true)
+                .requires("bench")
+                .help("Baseline to compare with (default: the one stored with the crate)"),
+        )
+        .arg(
+            clap::Arg::with_name("write-baseline")
+                .long("write-baseline")
+                .takes_value(true)
+                .requires("bench")
+                .help("Save this run's throughput as the new baseline"),
+        )
+        .arg(
+            clap::Arg::with_name("overload")
+                .long("overload")
+                .requires("failreg")
+                .conflicts_with("bench")
+                .help("Leave the rendezvous radar CDUs hunting from P63, as on Apollo 11"),
+        )
+        .arg(
+            clap::Arg::with_name("failreg")
+                .long("failreg")
+                .takes_value(true)
+                .help("Octal address of the rope's FAILREG, for counting alarms"),
+        )
+        .get_matches();
+
+    let pad = match matches.value_of("pad") {
+        Some(path) => match std::fs::read_to_string(path)
+            .map_err(|e| format!("{}: {}", path, e))
+            .and_then(|text| parse_pad(&text))
+        {
+            Ok(pad) => pad,
+            Err(e) => {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+        },
+        None => Vec::new(),
+    };
+    let seconds: f64 = matches
+        .value_of("seconds")
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(900.0);
+    let every: f64 = matches
+        .value_of("every")
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(10.0);
+    let drift: f64 = matches
+        .value_of("drift-ppm")
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(0.0);
+
+    info!("Loaded {} pad words", pad.len());
+    if matches.is_present("bench") {
+        run_bench(&matches, &pad, seconds);
+        return;
+    }
+    if matches.is_present("overload") {
+        run_overload(
+            matches.value_of("failreg").unwrap_or_default(),
+            &pad,
+            drift,
+            seconds,
+        );
+        return;
+    }
+    let mut landing = Landing::new(ragc_binaries::LUMINARY99_ROPE, &pad, Lander::pdi());
+    landing.set_drift(drift);
+    let mut next = 0.0;
+    let outcome = landing.run(seconds, |t| {
+        if t.time >= next {
+            let l = &t.lander;
+            println!(
+                "{:7.1} s  P{:<2} alt {:8.1} m  range {:9.1} m  vel {:7.1} {:+6.1} m/s  pitch {:+6.1}  thrust {:6.0} N  prop {:5.0} kg",
+                t.time,
+                t.prog.map(|p| format!("{:02}", p)).unwrap_or_default(),
+                l.altitude,
+                l.downrange,
+                l.velocity[0],
+                l.velocity[1],
+                l.pitch.to_degrees(),
+                t.thrust,
+                l.propellant()
+            );
+            next += every;
+        }
+    });
+
+    match outcome {
+        Outcome::Landed {
+            vertical_speed,
+            miss,
+            propellant,
+        } => println!(
+            "Contact at {:.2} m/s down, {:+.0} m from the site, {:.0} kg propellant left",
+            vertical_speed, miss, propellant
+        ),
+        Outcome::TimedOut => println!("No touchdown after {} s", seconds),
+    }
+}
@@ -0,0 +1,246 @@
+//! The Apollo 11 1201/1202 alarms
+//!
+//! With the rendezvous radar switched so its resolvers were excited out of
+//! phase with the computer's reference, both RR CDUs hunted without end
+//! and their counter increments stole about 15% of the computer's time.
+//! The descent programs, already near their limit, could no longer finish
+//! each cycle's jobs before the next were scheduled. The Executive ran out
+//! of core sets (1202) or VAC areas (1201), alarmed and restarted, shedding
+//! the backlog, and the descent carried on.
+//!
+//! The test that always runs flies a miniature executive, a stand-in for
+//! LUMINARY's: it shows the steal overflowing an executive and the
+//! recovery, not that LUMINARY's own does. That is checked on the real
+//! rope by an ignored test, run where LUMINARY99.bin is at hand.
+use log::info;
+
+use crate::interface::rendezvous_radar_steal;
+use crate::landing::{Landing, Outcome, Telemetry};
+
+/// Executive overflow alarms: no VAC areas, no core sets
+pub const EXECUTIVE_OVERFLOW: [u16; 2] = [0o1201, 0o1202];
+
+/// What an overload run saw
+#[derive(Clone, Debug)]
+pub struct Overload {
+    pub fault_at: Option<f64>,   // s since power on the radar was misconfigured
+    pub alarms: Vec<(f64, u16)>, // Time and code of each alarm stored in FAILREG
+    pub restarts: u32,           // Hardware restarts, which the overload shouldn't cause
+    pub stolen: u64,             // MCTs the RR CDUs took from the program
+    pub outcome: Outcome,
+}
+
+impl Overload {
+    /// The computer alarmed, only with executive overflows, and kept
+    /// running without a hardware restart
+    pub fn overflowed(&self) -> bool {
+        !self.alarms.is_empty()
+            && self
+                .alarms
+                .iter()
+                .all(|(_, code)| EXECUTIVE_OVERFLOW.contains(code))
+            && self.restarts == 0
+    }
+}
+
+/// Fly `landing` for up to `seconds`, misconfiguring the rendezvous radar
+/// on the first frame `trigger` accepts. The rope info must give FAILREG.
+pub fn fly(
+    landing: &mut Landing,
+    mut trigger: impl FnMut(&Telemetry) -> bool,
+    seconds: f64,
+) -> Overload {
+    let mut run = Overload {
+        fault_at: None,
+        alarms: Vec::new(),
+        restarts: 0,
+        stolen: 0,
+        outcome: Outcome::TimedOut,
+    };
+    let mut seen = landing.cpu().alarm_count();
+    loop {
+        let telemetry = landing.step();
+        if run.fault_at.is_none() && trigger(&telemetry) {
+            info!("{:7.2} s: rendezvous radar CDUs hunting", telemetry.time);
+            landing.hunt_rendezvous_radar();
+            run.fault_at = Some(telemetry.time);
+        }
+        let cpu = landing.cpu();
+        if cpu.alarm_count() != seen {
+            seen = cpu.alarm_count();
+            let code = cpu.last_alarm().unwrap_or_default();
+            info!("{:7.2} s: program alarm {:05o}", telemetry.time, code);
+            run.alarms.push((telemetry.time, code));
+        }
+        if let Some(landed) = landing.landed() {
+            run.outcome = landed;
+            break;
+        }
+        if telemetry.time >= seconds {
+            break;
+        }
+    }
+    run.restarts = landing.cpu().restart_count();
+    run.stolen = rendezvous_radar_steal(landing.cpu());
+    run
+}
+
+#[cfg(test)]
+mod overload_tests {
+    use super::{fly, EXECUTIVE_OVERFLOW};
+    use crate::landing::{Landing, Outcome};
+    use crate::vehicle::Lander;
+    use ragc_core::memory::mods::MCT_SECONDS;
+    use ragc_core::memory::rom::{encode_word, RomInfo, RopeImage, BANK_MAPPING};
+
+    const DONE: u16 = 0o102; // Jobs finished
+    const SOFT_RESTARTS: u16 = 0o103;
+    const FAILREG: usize = 0o110;
+
+    /// A stand-in for LUMINARY's executive: T5RUPT schedules a job every 20 ms, and the
+    /// background runs each in about 95% of that. With three jobs waiting
+    /// it stores 1202, drops the backlog and carries on, as BAILOUT does.
+    fn stand_in_executive(job_loops: u16) -> Box<RopeImage> {
+        let mut program = [0o50017; 0o142]; // RESUME in every vector
+        let mut at = |addr: usize, words: &[u16]| {
+            program[addr - 0o4000..addr - 0o4000 + words.len()].copy_from_slice(words)
+        };
+        at(0o4000, &[0o14100]); // TCF START
+        at(0o4010, &[0o14070]); // T5RUPT: TCF 4070
+        at(
+            0o4060,
+            &[
+                0o37776,   // 4060 TIME5 preset: T5RUPT in 20 ms
+                0o77776,   // 4061 -1
+                0o77774,   // 4062 -3, the core sets
+                0o01202,   // 4063 alarm code
+                job_loops, // 4064 busy loops per job
+                0o00000,   // 4065
+                0o14103,   // 4066 IDLE: TCF LOOP, as the TC trap wants
+            ],
+        );
+        at(
+            0o4070,
+            &[
+                0o56010, // 4070 XCH ARUPT
+                0o34060, // 4071 CA 4060
+                0o56030, // 4072 XCH TIME5
+                0o24101, // 4073 INCR JOBS
+                0o30010, // 4074 CA ARUPT
+                0o50017, // 4075 RESUME
+            ],
+        );
+        at(
+            0o4100,
+            &[
+                0o34060, // 4100 START: CA 4060
+                0o56030, // 4101 XCH TIME5
+                0o00003, // 4102 RELINT
+                0o30067, // 4103 LOOP: CA NEWJOB
+                0o30101, // 4104 CA JOBS
+                0o00006, // 4105 EXTEND
+                0o14066, // 4106 BZF IDLE
+                0o64062, // 4107 AD 4062
+                0o00006, // 4110 EXTEND
+                0o14132, // 4111 BZF ALARM
+                0o34064, // 4112 CA 4064
+                0o56104, // 4113 XCH 104
+                0o30104, // 4114 BUSY: CA 104
+                0o64061, // 4115 AD 4061
+                0o56104, // 4116 XCH 104
+                0o30104, // 4117 CA 104
+                0o00006, // 4120 EXTEND
+                0o14123, // 4121 BZF DONEJOB
+                0o14114, // 4122 TCF BUSY
+                0o00004, // 4123 DONEJOB: INHINT
+                0o30101, // 4124 CA JOBS
+                0o64061, // 4125 AD 4061
+                0o56101, // 4126 XCH JOBS
+                0o00003, // 4127 RELINT
+                0o24102, // 4130 INCR DONE
+                0o14103, // 4131 TCF LOOP
+                0o34063, // 4132 ALARM: CA 4063
+                0o56110, // 4133 XCH FAILREG
+                0o00004, // 4134 INHINT
+                0o34065, // 4135 CA 4065
+                0o56101, // 4136 XCH JOBS
+                0o00003, // 4137 RELINT
+                0o24103, // 4140 INCR SOFT_RESTARTS
+                0o14103, // 4141 TCF LOOP
+            ],
+        );
+        let mut rope: Box<RopeImage> = Box::new([[0; 1024]; 36]);
+        for (offset, word) in program.iter().enumerate() {
+            rope[BANK_MAPPING[2]][offset] = encode_word(*word);
+        }
+        rope
+    }
+
+    #[test]
+    fn hunting_radar_overflows_a_stand_in_executive_and_it_recovers() {
+        let rope = stand_in_executive(126);
+        let lander = Lander {
+            velocity: [0.0, 0.0],
+            pitch: 0.0,
+            altitude: 10_000.0,
+            ..Lander::pdi()
+        };
+        let info = RomInfo {
+            failreg: Some(FAILREG),
+            ..RomInfo::BLOCK_II
+        };
+        let mut landing = Landing::with_rom_info(&rope, &[], lander, info);
+
+        // The load is heavy but sustainable until the radar starts hunting
+        let run = fly(&mut landing, |t| t.time >= 2.0, 8.0);
+        let first = run.alarms.first().expect("no alarm").0;
+        assert!(first > 2.0, "alarm at {} s before the fault", first);
+        assert!(run.overflowed(), "{:?}", run);
+        assert!(run
+            .alarms
+            .iter()
+            .all(|(_, code)| *code == EXECUTIVE_OVERFLOW[1]));
+
+        // About 15% of the time went to the RR CDUs
+        let share = run.stolen as f64 * MCT_SECONDS / (8.0 - 2.0);
+        assert!((0.13..0.17).contains(&share), "{}", share);
+
+        // Each alarm shed the backlog and the jobs kept running after the last
+        let mem = landing.cpu().memory();
+        assert_eq!(mem.read(SOFT_RESTARTS as usize) as usize, run.alarms.len());
+        let done = mem.read(DONE as usize);
+        landing.run(9.0, |_| {});
+        assert!(landing.cpu().memory().read(DONE as usize) > done + 20);
+    }
+
+    /// Octal FAILREG of the LUMINARY99.bin at hand, as `--failreg` takes it
+    const FAILREG_VAR: &str = "RAGC_LUMINARY99_FAILREG";
+
+    #[test]
+    #[ignore = "needs the LUMINARY99.bin rope image and its FAILREG"]
+    fn hunting_radar_overflows_luminary_in_p63() {
+        let failreg = std::env::var(FAILREG_VAR)
+            .ok()
+            .and_then(|addr| usize::from_str_radix(&addr, 8).ok())
+            .unwrap_or_else(|| panic!("{} must give FAILREG in octal", FAILREG_VAR));
+        let info = RomInfo {
+            failreg: Some(failreg),
+            ..RomInfo::BLOCK_II
+        };
+        let rope = ragc_binaries::LUMINARY99_ROPE;
+        let mut landing = Landing::with_rom_info(rope, &[], Lander::pdi(), info);
+
+        // As on Apollo 11: the radar hunts from P63 on, LUMINARY raises
+        // 1201 and 1202, sheds its load without a hardware restart and
+        // carries on with the descent
+        let run = fly(&mut landing, |t| t.prog == Some(63), 900.0);
+        let fault_at = run.fault_at.expect("P63 never came up");
+        assert!(
+            run.alarms.iter().all(|(at, _)| *at >= fault_at),
+            "{:?}",
+            run
+        );
+        assert!(run.overflowed(), "{:?}", run);
+        assert_ne!(run.outcome, Outcome::TimedOut, "{:?}", run);
+    }
+}
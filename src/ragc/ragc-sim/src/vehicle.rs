@@ -0,0 +1,92 @@
+//! Planar (3-DOF) LM descent model: downrange, altitude and pitch
+
+/// Lunar surface gravity in m/s^2
+pub const LUNAR_GRAVITY: f64 = 1.622;
+
+/// Mean lunar radius in meters
+pub const LUNAR_RADIUS: f64 = 1_737_400.0;
+
+/// Descent engine thrust at full throttle, in newtons
+pub const DPS_MAX_THRUST: f64 = 45_040.0;
+
+/// Lowest throttle setting the descent engine accepts
+pub const DPS_MIN_THROTTLE: f64 = 0.1;
+
+/// Descent engine exhaust velocity (Isp 305 s), m/s
+const DPS_EXHAUST_VELOCITY: f64 = 305.0 * 9.80665;
+
+/// One RCS jet's thrust (N) and its lever arm about the pitch axis (m)
+const RCS_THRUST: f64 = 445.0;
+const RCS_ARM: f64 = 1.67;
+
+/// Pitch moment of inertia of the loaded LM, kg m^2
+const PITCH_INERTIA: f64 = 35_000.0;
+
+/// LM state in a frame fixed to the landing site's surface
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Lander {
+    pub downrange: f64,     // m, negative short of the landing site
+    pub altitude: f64,      // m
+    pub velocity: [f64; 2], // m/s, downrange and up
+    pub pitch: f64,         // rad, thrust axis from vertical; positive brakes
+    pub pitch_rate: f64,    // rad/s
+    pub mass: f64,          // kg
+    pub dry_mass: f64,      // kg, mass with the descent propellant gone
+}
+
+impl Lander {
+    /// Roughly Apollo 11 at powered descent initiation: 15 km up, 1690 m/s
+    /// and 480 km short of the site, with the engine pointed retrograde
+    pub fn pdi() -> Self {
+        Self {
+            downrange: -480_000.0,
+            altitude: 15_000.0,
+            velocity: [1_690.0, 0.0],
+            pitch: core::f64::consts::FRAC_PI_2,
+            pitch_rate: 0.0,
+            mass: 15_100.0,
+            dry_mass: 6_800.0,
+        }
+    }
+
+    /// Advance `dt` seconds with the descent engine at `thrust` newtons and
+    /// `pitch_jets` net jet-seconds of positive pitch RCS firing. Returns
+    /// the non-gravitational velocity change the accelerometers sense, as
+    /// (downrange, up).
+    pub fn step(&mut self, dt: f64, thrust: f64, pitch_jets: f64) -> [f64; 2] {
+        let thrust = if self.mass > self.dry_mass {
+            thrust
+        } else {
+            0.0
+        };
+        self.mass -= thrust / DPS_EXHAUST_VELOCITY * dt;
+
+        self.pitch_rate += pitch_jets * RCS_THRUST * RCS_ARM / PITCH_INERTIA;
+        self.pitch += self.pitch_rate * dt;
+
+        let accel = thrust / self.mass;
+        let sensed = [
+            -accel * self.pitch.sin() * dt,
+            accel * self.pitch.cos() * dt,
+        ];
+
+        // Flat surface frame, with the orbital curvature as a centrifugal term
+        let centrifugal =
+            self.velocity[0] * self.velocity[0] / (LUNAR_RADIUS + self.altitude.max(0.0));
+        self.velocity[0] += sensed[0];
+        self.velocity[1] += sensed[1] + (centrifugal - LUNAR_GRAVITY) * dt;
+        self.downrange += self.velocity[0] * dt;
+        self.altitude += self.velocity[1] * dt;
+        sensed
+    }
+
+    /// On the surface (or below it)
+    pub fn landed(&self) -> bool {
+        self.altitude <= 0.0
+    }
+
+    /// Descent propellant left, kg
+    pub fn propellant(&self) -> f64 {
+        (self.mass - self.dry_mass).max(0.0)
+    }
+}
@@ -0,0 +1,97 @@
+//! Example programs small enough to read in one sitting, written in
+//! yaYUL and assembled by ragc-asm into fixed-fixed memory from 4000,
+//! where a restart begins execution. They run on a bare computer: no
+//! interrupts, no executive, just the instructions shown, so they double
+//! as end-to-end checks of the assembler and the core and a first look at
+//! AGC code. Erasable is left as the CPU finds it and each program sets up
+//! what it uses. The sources are in `gallery/`.
+use ragc_asm::Assembly;
+use ragc_core::memory::rom::RopeImage;
+
+/// A program and its source
+pub struct Example {
+    pub name: &'static str,
+    pub source: &'static str,
+}
+
+impl Example {
+    /// The assembled program; the gallery's tests keep every source
+    /// assembling
+    pub fn assemble(&self) -> Assembly {
+        ragc_asm::assemble(self.source).unwrap_or_else(|e| panic!("{}.agc: {}", self.name, e))
+    }
+
+    pub fn rope(&self) -> Box<RopeImage> {
+        self.assemble().rope()
+    }
+}
+
+/// Fibonacci numbers in FIB1 and FIB2, stopping at DONE with F(20) = 6765
+/// and F(21) = 10946
+pub const FIBONACCI: Example = Example {
+    name: "fibonacci",
+    source: include_str!("../gallery/fibonacci.agc"),
+};
+
+/// Counts 0 to 9 over and over in the DSKY's PROG digits
+pub const DSKY_COUNTER: Example = Example {
+    name: "dsky-counter",
+    source: include_str!("../gallery/dsky-counter.agc"),
+};
+
+/// Walks one lit lamp across the DSKY's channel 11 lamps
+pub const LIGHT_CHASER: Example = Example {
+    name: "light-chaser",
+    source: include_str!("../gallery/light-chaser.agc"),
+};
+
+pub const EXAMPLES: [&Example; 3] = [&FIBONACCI, &DSKY_COUNTER, &LIGHT_CHASER];
+
+#[cfg(test)]
+mod gallery_tests {
+    use super::{DSKY_COUNTER, FIBONACCI, LIGHT_CHASER};
+    use crate::SpyPeriph;
+    use dsky_protocol::pinball::DisplayState;
+    use ragc_core::constants::ports::{CHANNEL_DSALMOUT, CHANNEL_DSKY};
+    use ragc_core::cpu::Cpu;
+    use ragc_core::memory::MemoryMapBuilder;
+
+    #[test]
+    fn examples_run_as_described() {
+        let fibonacci = FIBONACCI.assemble();
+        let rope = fibonacci.rope();
+        let mut cpu = Cpu::new(MemoryMapBuilder::new().rope(&rope).build());
+        let done = fibonacci.symbol("DONE").unwrap();
+        while cpu.step().pc != done {}
+        assert_eq!((cpu.read(0o100), cpu.read(0o101)), (6765, 10946));
+
+        let rope = DSKY_COUNTER.rope();
+        let mut dsky = SpyPeriph::new();
+        let traffic = dsky.traffic();
+        let mut cpu = Cpu::new(MemoryMapBuilder::new().rope(&rope).dsky(&mut dsky).build());
+        cpu.memory_mut().set_relay_cadence(0);
+        for _ in 0..200 {
+            cpu.step();
+        }
+        let mut display = DisplayState::new();
+        let shown: Vec<u8> = traffic
+            .writes(CHANNEL_DSKY)
+            .into_iter()
+            .filter_map(|word| {
+                display.apply_relay_word(word);
+                display.prog()
+            })
+            .collect();
+        assert_eq!(shown[..12], [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 0, 1]);
+
+        let rope = LIGHT_CHASER.rope();
+        let mut dsky = SpyPeriph::new();
+        let traffic = dsky.traffic();
+        let mut cpu = Cpu::new(MemoryMapBuilder::new().rope(&rope).dsky(&mut dsky).build());
+        for _ in 0..100 {
+            cpu.step();
+        }
+        let lamps = traffic.writes(CHANNEL_DSALMOUT);
+        assert_eq!(lamps[..7], [0o2, 0o4, 0o10, 0o20, 0o40, 0o100, 0o2]);
+    }
+}
@@ -0,0 +1,11 @@
+//! Test doubles for code that drives the emulator. Peripherals can do
+//! nothing, record all channel traffic, or play canned channel values and
+//! interrupts over emulated time. Small programs are laid into ropes with
+//! `rope_image`. Use these instead of writing a mock for each test. The
+//! gallery has complete example programs to run.
+pub mod gallery;
+pub mod periph;
+pub mod rope;
+
+pub use periph::{Event, NullPeriph, Record, ScriptedPeriph, SpyPeriph, Traffic};
+pub use rope::rope_image;
@@ -0,0 +1,348 @@
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::{Arc, Mutex, PoisonError};
+
+use ragc_core::constants::ports::CHANNEL_MNKEYIN;
+use ragc_core::memory::downlink::DownlinkPair;
+use ragc_core::memory::mods::{EmuTime, InterruptSource, IoPeriph, RuptRequest, RuptRequests};
+use ragc_core::memory::relay::DisplayFrame;
+
+/// Peripheral that reads zero, ignores writes and never interrupts
+#[derive(Clone, Copy, Default, Debug)]
+pub struct NullPeriph;
+
+impl IoPeriph for NullPeriph {
+    fn read(&self, _channel_idx: usize) -> u16 {
+        0
+    }
+
+    fn write(&mut self, _channel_idx: usize, _value: u16) {}
+}
+
+impl InterruptSource for NullPeriph {
+    fn rupt_requests(&mut self) -> RuptRequests {
+        RuptRequests::NONE
+    }
+}
+
+/// Something the computer did with a peripheral
+#[derive(Clone, PartialEq, Debug)]
+pub enum Event {
+    Read(usize, u16),
+    Write(usize, u16),
+    Downlink(DownlinkPair),
+    DisplayFrame(Vec<u16>),
+    Rupt(RuptRequest),
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct Record {
+    pub at: EmuTime,
+    pub event: Event,
+}
+
+/// What a `SpyPeriph` has seen, shared so a test can look while the
+/// memory map still holds the peripheral
+#[derive(Clone, Default)]
+pub struct Traffic(Arc<Mutex<Vec<Record>>>);
+
+impl Traffic {
+    fn push(&self, at: EmuTime, event: Event) {
+        let mut records = self.0.lock().unwrap_or_else(PoisonError::into_inner);
+        records.push(Record { at, event });
+    }
+
+    pub fn records(&self) -> Vec<Record> {
+        self.0
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    pub fn clear(&self) {
+        self.0
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clear();
+    }
+
+    /// Values written to `channel`, oldest first
+    pub fn writes(&self, channel: usize) -> Vec<u16> {
+        self.values(|event| match *event {
+            Event::Write(ch, value) if ch == channel => Some(value),
+            _ => None,
+        })
+    }
+
+    /// Values the computer read from `channel`, oldest first
+    pub fn reads(&self, channel: usize) -> Vec<u16> {
+        self.values(|event| match *event {
+            Event::Read(ch, value) if ch == channel => Some(value),
+            _ => None,
+        })
+    }
+
+    pub fn rupts(&self) -> Vec<RuptRequest> {
+        self.values(|event| match *event {
+            Event::Rupt(rupt) => Some(rupt),
+            _ => None,
+        })
+    }
+
+    fn values<T>(&self, pick: impl Fn(&Event) -> Option<T>) -> Vec<T> {
+        let records = self.0.lock().unwrap_or_else(PoisonError::into_inner);
+        records.iter().filter_map(|r| pick(&r.event)).collect()
+    }
+
+    /// Panic unless `value` was written to `channel`
+    #[track_caller]
+    pub fn assert_wrote(&self, channel: usize, value: u16) {
+        let writes = self.writes(channel);
+        assert!(
+            writes.contains(&value),
+            "{:05o} never written to channel {:o}; writes were [{}]",
+            value,
+            channel,
+            octal(&writes)
+        );
+    }
+
+    /// Panic unless `values` were written to `channel` in this order,
+    /// other writes allowed in between
+    #[track_caller]
+    pub fn assert_wrote_in_order(&self, channel: usize, values: &[u16]) {
+        let writes = self.writes(channel);
+        let mut rest = writes.iter();
+        for value in values {
+            assert!(
+                rest.any(|w| w == value),
+                "[{}] not written to channel {:o} in order; writes were [{}]",
+                octal(values),
+                channel,
+                octal(&writes)
+            );
+        }
+    }
+
+    /// Panic if anything was written to `channel`
+    #[track_caller]
+    pub fn assert_quiet(&self, channel: usize) {
+        let writes = self.writes(channel);
+        assert!(
+            writes.is_empty(),
+            "channel {:o} written: [{}]",
+            channel,
+            octal(&writes)
+        );
+    }
+}
+
+fn octal(words: &[u16]) -> String {
+    let words: Vec<String> = words.iter().map(|w| format!("{:05o}", w)).collect();
+    words.join(" ")
+}
+
+/// Wraps a peripheral, by default a `NullPeriph`, recording all traffic
+/// through it with the emulated time it happened at
+pub struct SpyPeriph<P = NullPeriph> {
+    inner: P,
+    traffic: Traffic,
+    now: EmuTime,
+}
+
+impl SpyPeriph {
+    pub fn new() -> Self {
+        Self::wrap(NullPeriph)
+    }
+}
+
+impl Default for SpyPeriph {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<P: IoPeriph> SpyPeriph<P> {
+    pub fn wrap(inner: P) -> Self {
+        Self {
+            inner,
+            traffic: Traffic::default(),
+            now: EmuTime::ZERO,
+        }
+    }
+
+    /// Handle on the recorded traffic
+    pub fn traffic(&self) -> Traffic {
+        self.traffic.clone()
+    }
+}
+
+impl<P: IoPeriph> IoPeriph for SpyPeriph<P> {
+    fn read(&self, channel_idx: usize) -> u16 {
+        let value = self.inner.read(channel_idx);
+        self.traffic.push(self.now, Event::Read(channel_idx, value));
+        value
+    }
+
+    fn write(&mut self, channel_idx: usize, value: u16) {
+        self.traffic
+            .push(self.now, Event::Write(channel_idx, value));
+        self.inner.write(channel_idx, value);
+    }
+
+    fn tick(&mut self, now: EmuTime) {
+        self.now = now;
+        self.inner.tick(now);
+    }
+
+    fn downlink(&mut self, pair: DownlinkPair) {
+        self.traffic.push(self.now, Event::Downlink(pair));
+        self.inner.downlink(pair);
+    }
+
+    fn display_frame(&mut self, frame: &DisplayFrame) {
+        let words = frame.words().to_vec();
+        self.traffic.push(self.now, Event::DisplayFrame(words));
+        self.inner.display_frame(frame);
+    }
+
+    fn standby_request(&self) -> bool {
+        self.inner.standby_request()
+    }
+}
+
+impl<P: IoPeriph> InterruptSource for SpyPeriph<P> {
+    fn rupt_requests(&mut self) -> RuptRequests {
+        let rupts = self.inner.rupt_requests();
+        for rupt in rupts.iter() {
+            self.traffic.push(self.now, Event::Rupt(rupt));
+        }
+        rupts
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+enum Cue {
+    Channel(usize, u16),
+    Rupt(RuptRequest),
+}
+
+/// Plays canned channel values and interrupts over emulated time. Each
+/// channel reads its latest value due, zero before the first; writes are
+/// ignored.
+#[derive(Clone, Default, Debug)]
+pub struct ScriptedPeriph {
+    script: VecDeque<(EmuTime, Cue)>,
+    channels: BTreeMap<usize, u16>,
+    rupts: RuptRequests, // Due and not yet polled
+}
+
+impl ScriptedPeriph {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// `channel` reads `value` from `at` on
+    pub fn channel(self, at: EmuTime, channel: usize, value: u16) -> Self {
+        self.cue(at, Cue::Channel(channel, value))
+    }
+
+    /// Request `rupt` once at `at`
+    pub fn rupt(self, at: EmuTime, rupt: RuptRequest) -> Self {
+        self.cue(at, Cue::Rupt(rupt))
+    }
+
+    /// Press a DSKY key at `at`: its keycode on channel 15 and KEYRUPT1
+    pub fn key(self, at: EmuTime, keycode: u16) -> Self {
+        self.channel(at, CHANNEL_MNKEYIN, keycode)
+            .rupt(at, RuptRequest::Keyrupt1)
+    }
+
+    fn cue(mut self, at: EmuTime, cue: Cue) -> Self {
+        // Cues for the same time play in the order given
+        let idx = self.script.partition_point(|(t, _)| *t <= at);
+        self.script.insert(idx, (at, cue));
+        self
+    }
+}
+
+impl IoPeriph for ScriptedPeriph {
+    fn read(&self, channel_idx: usize) -> u16 {
+        self.channels.get(&channel_idx).copied().unwrap_or(0)
+    }
+
+    fn write(&mut self, _channel_idx: usize, _value: u16) {}
+
+    fn tick(&mut self, now: EmuTime) {
+        while let Some(&(_, cue)) = self.script.front().filter(|(at, _)| *at <= now) {
+            self.script.pop_front();
+            match cue {
+                Cue::Channel(channel, value) => {
+                    self.channels.insert(channel, value);
+                }
+                Cue::Rupt(rupt) => self.rupts.insert(rupt),
+            }
+        }
+    }
+}
+
+impl InterruptSource for ScriptedPeriph {
+    fn rupt_requests(&mut self) -> RuptRequests {
+        std::mem::take(&mut self.rupts)
+    }
+}
+
+#[cfg(test)]
+mod periph_tests {
+    use super::{Event, ScriptedPeriph, SpyPeriph};
+    use crate::rope_image;
+    use ragc_core::constants::ports::{CHANNEL_CHAN12, CHANNEL_DSALMOUT, CHANNEL_MNKEYIN};
+    use ragc_core::cpu::Cpu;
+    use ragc_core::memory::mods::{EmuTime, InterruptSource, IoPeriph, RuptRequest};
+    use ragc_core::memory::MemoryMapBuilder;
+
+    #[test]
+    fn spy_sees_scripted_values_echoed() {
+        // At 4000: READ 15, WRITE 11, TCF 4000
+        let rope = rope_image(0o4000, &[0o6, 0o15, 0o6, 0o1011, 0o14000]);
+        let later = EmuTime::from_mcts(200);
+        let script = ScriptedPeriph::new()
+            .channel(EmuTime::ZERO, CHANNEL_MNKEYIN, 0o21)
+            .channel(later, CHANNEL_MNKEYIN, 0o22);
+        let mut dsky = SpyPeriph::wrap(script);
+        let traffic = dsky.traffic();
+        let mut cpu = Cpu::new(MemoryMapBuilder::new().rope(&rope).dsky(&mut dsky).build());
+        cpu.reset();
+        while cpu.total_cycles < 400 {
+            cpu.step();
+        }
+
+        traffic.assert_wrote_in_order(CHANNEL_DSALMOUT, &[0o21, 0o22]);
+        traffic.assert_quiet(CHANNEL_CHAN12);
+        let first_late = traffic
+            .records()
+            .into_iter()
+            .find(|r| r.event == Event::Write(CHANNEL_DSALMOUT, 0o22))
+            .unwrap();
+        assert!(first_late.at >= later);
+        assert!(traffic.reads(CHANNEL_MNKEYIN).starts_with(&[0o21]));
+    }
+
+    #[test]
+    fn one_poll_reports_every_rupt_due() {
+        let at = EmuTime::from_mcts(10);
+        let script = ScriptedPeriph::new()
+            .rupt(at, RuptRequest::Uprupt)
+            .key(at, 0o21);
+        let mut periph = SpyPeriph::wrap(script);
+        let traffic = periph.traffic();
+        periph.tick(at);
+
+        let rupts = periph.rupt_requests();
+        assert!(rupts.contains(RuptRequest::Uprupt) && rupts.contains(RuptRequest::Keyrupt1));
+        assert!(periph.rupt_requests().is_empty());
+        assert_eq!(
+            traffic.rupts(),
+            [RuptRequest::Keyrupt1, RuptRequest::Uprupt]
+        );
+    }
+}
@@ -0,0 +1,21 @@
+use ragc_core::constants::address_space::{fixed_fixed_bank, FIXED_BANK_SIZE};
+use ragc_core::memory::rom::{encode_word, RopeImage, BANK_MAPPING};
+
+/// Rope image with `words` from `origin` in fixed-fixed memory (4000-7777)
+/// and TC 0 everywhere else. Words are laid out as dumped from a real rope:
+/// bank 2 first, big-endian, above the parity bit.
+pub fn rope_image(origin: u16, words: &[u16]) -> Box<RopeImage> {
+    let end = origin as usize + words.len();
+    assert!(
+        origin >= 0o4000 && end <= 0o10000,
+        "{:o}-{:o} is not fixed-fixed",
+        origin,
+        end
+    );
+    let mut rope = Box::new([[0; 1024]; 36]);
+    for (addr, word) in (origin as usize..).zip(words) {
+        let segment = BANK_MAPPING[fixed_fixed_bank(addr)];
+        rope[segment][addr % FIXED_BANK_SIZE] = encode_word(*word);
+    }
+    rope
+}
@@ -0,0 +1,82 @@
+use std::collections::HashMap;
+
+use dsky_protocol::alarms::{AlarmTable, COMANCHE55_ALARMS, LUMINARY99_ALARMS};
+use ragc_core::symbols::SymbolTable;
+
+use crate::config::AlarmConfig;
+
+/// Alarm descriptions for a bundled ROM
+pub fn alarm_table(rom: &str) -> Option<AlarmTable> {
+    match rom {
+        "luminary99" => Some(LUMINARY99_ALARMS),
+        "comanche55" => Some(COMANCHE55_ALARMS),
+        _ => None,
+    }
+}
+
+/// FAILREG from the symbol table, when it is in unswitched erasable
+pub fn failreg<S: SymbolTable + ?Sized>(symbols: &S) -> Option<usize> {
+    let addr = symbols.erasable("FAILREG")?;
+    let flat = (addr.bank << 8) | addr.offset;
+    (flat + 2 < 0o1400).then_some(flat)
+}
+
+/// Turns alarm codes into descriptions: the configuration's own first,
+/// then the rope's table
+pub struct AlarmDecoder {
+    table: Option<AlarmTable>,
+    extra: HashMap<u16, String>,
+}
+
+impl AlarmDecoder {
+    pub fn new(table: Option<AlarmTable>, extra: &[AlarmConfig]) -> Self {
+        Self {
+            table,
+            extra: extra.iter().map(|a| (a.code, a.text.clone())).collect(),
+        }
+    }
+
+    pub fn describe(&self, code: u16) -> Option<&str> {
+        match self.extra.get(&code) {
+            Some(text) => Some(text),
+            None => self.table?.lookup(code).map(|a| a.text),
+        }
+    }
+
+    /// Log line for an alarm, e.g. `Program alarm 01202: Executive
+    /// overflow: no core sets`
+    pub fn message(&self, code: u16) -> String {
+        match self.describe(code) {
+            Some(text) => format!("Program alarm {:05o}: {}", code, text),
+            None => format!("Program alarm {:05o} (unknown code)", code),
+        }
+    }
+}
+
+#[cfg(test)]
+mod alarms_tests {
+    use super::{alarm_table, failreg, AlarmDecoder};
+    use crate::config::AlarmConfig;
+    use ragc_core::symbols::ErasableAddress;
+
+    #[test]
+    fn configured_descriptions_come_first() {
+        let extra = [AlarmConfig {
+            code: 0o1202,
+            text: "Core sets exhausted".to_string(),
+        }];
+        let decoder = AlarmDecoder::new(alarm_table("luminary99"), &extra);
+        assert_eq!(decoder.describe(0o1202), Some("Core sets exhausted"));
+        assert_eq!(
+            decoder.message(0o1201),
+            "Program alarm 01201: Executive overflow: no VAC areas"
+        );
+        assert_eq!(decoder.message(0o7), "Program alarm 00007 (unknown code)");
+        assert!(AlarmDecoder::new(None, &[]).describe(0o1202).is_none());
+
+        let symbols = [("FAILREG", ErasableAddress::from_flat(0o370))];
+        assert_eq!(failreg(&symbols[..]), Some(0o370));
+        let switched = [("FAILREG", ErasableAddress::new(5, 0o10))];
+        assert_eq!(failreg(&switched[..]), None);
+    }
+}
@@ -0,0 +1,143 @@
+use std::hash::{Hash, Hasher};
+
+use log::info;
+
+use ragc_core::cpu::Cpu;
+use ragc_core::hash::Fnv1a;
+use ragc_core::memory::rom::RomInfo;
+use ragc_core::memory::MemoryMapBuilder;
+use ragc_core::rng::{streams, Rng};
+
+use crate::compare::{self, RomRun, Step};
+use crate::ropes::{self, rom_info_by_name, rope_by_name, ROM_NAMES};
+
+/// What a scenario run left behind, hashed
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Fingerprint {
+    pub cycles: usize,
+    pub state: u64,  // Cpu::state_hash at the end of the run
+    pub output: u64, // Display transcript and channel writes
+}
+
+impl Fingerprint {
+    pub fn take(cpu: &Cpu, run: &RomRun) -> Self {
+        let mut output = Fnv1a::default();
+        run.transcript.hash(&mut output);
+        run.writes.hash(&mut output);
+
+        Self {
+            cycles: cpu.total_cycles,
+            state: cpu.state_hash(),
+            output: output.finish(),
+        }
+    }
+}
+
+/// Play a scenario from randomized erasable memory, as `--seed` would
+/// leave it
+pub fn run_seeded(
+    rope: &[[u16; 1024]; 36],
+    rom_info: RomInfo,
+    scenario: &[Step],
+    seed: u64,
+) -> Fingerprint {
+    let mut mem = MemoryMapBuilder::new()
+        .rope(rope)
+        .rom_info(rom_info)
+        .relay_cadence(0)
+        .build();
+    mem.randomize_erasable(&mut Rng::new(seed).fork(streams::ERASABLE_INIT));
+    let mut cpu = Cpu::new(mem);
+    cpu.reset();
+    let run = compare::play("audit", &mut cpu, scenario);
+    Fingerprint::take(&cpu, &run)
+}
+
+/// Play the scenario twice with the same seed. Any difference means state
+/// leaked in from outside the emulation, e.g. the wall clock or the
+/// iteration order of a hashed collection.
+pub fn audit(
+    rope: &[[u16; 1024]; 36],
+    rom_info: RomInfo,
+    scenario: &[Step],
+    seed: u64,
+) -> Result<Fingerprint, String> {
+    let first = run_seeded(rope, rom_info, scenario, seed);
+    let second = run_seeded(rope, rom_info, scenario, seed);
+    info!(
+        "Audit runs: {:016x}/{:016x} then {:016x}/{:016x}",
+        first.state, first.output, second.state, second.output
+    );
+    match (
+        first.cycles == second.cycles,
+        first.state == second.state,
+        first.output == second.output,
+    ) {
+        (false, _, _) => Err(format!(
+            "Runs took {} and {} MCTs",
+            first.cycles, second.cycles
+        )),
+        (true, false, _) => Err("Final erasable, register or channel state differs".to_string()),
+        (true, true, false) => Err("Display transcript or channel writes differ".to_string()),
+        (true, true, true) => Ok(first),
+    }
+}
+
+/// The `audit` subcommand and its options
+pub fn subcommand<'a, 'b>() -> clap::App<'a, 'b> {
+    clap::SubCommand::with_name("audit")
+        .about("Run a scenario twice from the same seed and compare the results")
+        .arg(
+            clap::Arg::with_name("rom")
+                .long("rom")
+                .takes_value(true)
+                .possible_values(&ROM_NAMES)
+                .default_value("luminary99")
+                .help("ROM to run"),
+        )
+        .arg(
+            clap::Arg::with_name("scenario")
+                .long("scenario")
+                .takes_value(true)
+                .possible_values(&compare::SCENARIO_NAMES)
+                .default_value("default")
+                .help("Scenario, as for compare"),
+        )
+}
+
+/// Plays a scenario twice from the same seed and checks both runs agree
+pub fn command(args: &clap::ArgMatches, seed: u64) -> Result<(), String> {
+    let name = args.value_of("rom").unwrap_or_default();
+    let rope = rope_by_name(name).ok_or("Invalid ROM")?;
+    let scenario_name = args.value_of("scenario").unwrap_or_default();
+    let scenario = compare::scenario(scenario_name).ok_or("Invalid scenario")?;
+    if compare::needs_lm(scenario_name) && !ropes::is_lm_rom(name) {
+        return Err(format!("{} needs an LM rope", scenario_name));
+    }
+    let rom_info = rom_info_by_name(name).unwrap_or_default();
+    let fingerprint = audit(rope, rom_info, scenario, seed)?;
+    println!(
+        "{}: deterministic over {} MCTs (state {:016x}, output {:016x})",
+        name, fingerprint.cycles, fingerprint.state, fingerprint.output
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod audit_tests {
+    use super::{audit, run_seeded};
+    use crate::compare::Step;
+    use ragc_core::memory::rom::RomInfo;
+
+    #[test]
+    fn same_seed_runs_match() {
+        let scenario = [Step::Wait(500), Step::Keys("V35E"), Step::Wait(500)];
+        let rope = ragc_binaries::LUMINARY99_ROPE;
+        let fingerprint = audit(rope, RomInfo::BLOCK_II, &scenario, 7).unwrap();
+        assert!(fingerprint.cycles > 0);
+
+        // The audit would miss leaks if the seed didn't reach the state
+        let other = run_seeded(rope, RomInfo::BLOCK_II, &scenario, 8);
+        assert_ne!(fingerprint.state, other.state);
+    }
+}
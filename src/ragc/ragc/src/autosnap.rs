@@ -0,0 +1,204 @@
+use std::collections::VecDeque;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use log::{error, info};
+
+use ragc_core::cpu::Cpu;
+use ragc_core::memory::mods::EmuTime;
+
+use crate::runtime::Snapshot;
+use crate::snapshot;
+
+/// Clock the auto-snapshot interval is measured on
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum SnapClock {
+    Emulated, // Reproducible, and unaffected by pauses or --speed
+    Wall,
+}
+
+impl std::str::FromStr for SnapClock {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, String> {
+        match s {
+            "emulated" => Ok(SnapClock::Emulated),
+            "wall" => Ok(SnapClock::Wall),
+            _ => Err(format!("Unknown clock {}, expected emulated or wall", s)),
+        }
+    }
+}
+
+/// Parse an interval such as `500ms`, `60s`, `5m` or `1h`; bare numbers
+/// are seconds
+pub fn parse_interval(text: &str) -> Result<Duration, String> {
+    let split = text
+        .find(|c: char| c.is_ascii_alphabetic())
+        .unwrap_or(text.len());
+    let (number, unit) = text.split_at(split);
+    let number: f64 = number
+        .parse()
+        .map_err(|_| format!("Invalid interval: {}", text))?;
+    let seconds = match unit {
+        "ms" => number / 1000.0,
+        "" | "s" => number,
+        "m" => number * 60.0,
+        "h" => number * 3600.0,
+        _ => return Err(format!("Invalid interval unit: {}", text)),
+    };
+    match seconds.is_finite() && seconds > 0.0 {
+        true => Ok(Duration::from_secs_f64(seconds)),
+        false => Err(format!("Interval must be positive: {}", text)),
+    }
+}
+
+/// Snapshots written every interval for `--autosnapshot`, keeping only the
+/// newest few this run wrote
+pub struct AutoSnapshot {
+    dir: PathBuf,
+    interval: Duration,
+    clock: SnapClock,
+    keep: usize,
+    next_mcts: usize,
+    next_wall: Instant,
+    kept: VecDeque<PathBuf>,
+}
+
+/// `--autosnapshot` and its options
+pub fn args<'a, 'b>() -> [clap::Arg<'a, 'b>; 4] {
+    [
+        clap::Arg::with_name("autosnapshot")
+            .long("autosnapshot")
+            .takes_value(true)
+            .help("Save a snapshot at this interval, e.g. 60s or 5m"),
+        clap::Arg::with_name("keep")
+            .long("keep")
+            .takes_value(true)
+            .default_value("10")
+            .help("Auto-snapshots to keep before deleting the oldest"),
+        clap::Arg::with_name("autosnapshot-dir")
+            .long("autosnapshot-dir")
+            .takes_value(true)
+            .default_value("snapshots")
+            .help("Directory for auto-snapshots"),
+        clap::Arg::with_name("autosnapshot-clock")
+            .long("autosnapshot-clock")
+            .takes_value(true)
+            .possible_values(&["emulated", "wall"])
+            .default_value("emulated")
+            .help("Clock the auto-snapshot interval runs on"),
+    ]
+}
+
+/// Auto-snapshots as `--autosnapshot <interval>` and its options ask for,
+/// if they do
+pub fn from_args(matches: &clap::ArgMatches) -> Result<Option<AutoSnapshot>, String> {
+    let interval = match matches.value_of("autosnapshot") {
+        Some(interval) => parse_interval(interval)?,
+        None => return Ok(None),
+    };
+    let keep = matches.value_of("keep").unwrap_or_default();
+    let keep = keep
+        .parse()
+        .map_err(|_| format!("Invalid snapshot count: {}", keep))?;
+    let clock = matches
+        .value_of("autosnapshot-clock")
+        .unwrap_or_default()
+        .parse()?;
+    let dir = matches.value_of("autosnapshot-dir").unwrap_or_default();
+    AutoSnapshot::new(dir, interval, clock, keep).map(Some)
+}
+
+impl AutoSnapshot {
+    pub fn new(
+        dir: &str,
+        interval: Duration,
+        clock: SnapClock,
+        keep: usize,
+    ) -> Result<Self, String> {
+        if keep == 0 {
+            return Err("Must keep at least one snapshot".to_string());
+        }
+        std::fs::create_dir_all(dir).map_err(|e| format!("{}: {}", dir, e))?;
+        info!(
+            "Snapshotting to {} every {:?} of {:?} time, keeping {}",
+            dir, interval, clock, keep
+        );
+        Ok(Self {
+            dir: PathBuf::from(dir),
+            interval,
+            clock,
+            keep,
+            next_mcts: Self::mcts(interval),
+            next_wall: Instant::now() + interval,
+            kept: VecDeque::new(),
+        })
+    }
+
+    fn mcts(interval: Duration) -> usize {
+        (EmuTime::mcts_in_secs(interval.as_secs_f64()) as usize).max(1)
+    }
+
+    /// Take a snapshot if one is due. Call between instructions.
+    pub fn poll(&mut self, cpu: &mut Cpu, seed: u64) {
+        let due = match self.clock {
+            SnapClock::Emulated => cpu.total_cycles >= self.next_mcts,
+            SnapClock::Wall => Instant::now() >= self.next_wall,
+        };
+        if !due {
+            return;
+        }
+        self.next_mcts = cpu.total_cycles + Self::mcts(self.interval);
+        self.next_wall = Instant::now() + self.interval;
+
+        let path = self.dir.join(format!("ragc-{:012}.snap", cpu.total_cycles));
+        let name = path.to_string_lossy().into_owned();
+        if let Err(e) = snapshot::save(&name, &Snapshot::capture(cpu, seed)) {
+            error!("Auto-snapshot failed: {}", e);
+            return;
+        }
+        self.kept.push_back(path);
+        while self.kept.len() > self.keep {
+            if let Some(old) = self.kept.pop_front() {
+                if let Err(e) = std::fs::remove_file(&old) {
+                    error!("Cannot rotate out {}: {}", old.display(), e);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod autosnap_tests {
+    use super::{parse_interval, AutoSnapshot, SnapClock};
+    use ragc_core::cpu::Cpu;
+    use ragc_core::memory::MemoryMap;
+    use std::time::Duration;
+
+    #[test]
+    fn rotates_snapshots_on_the_emulated_clock() {
+        assert_eq!(parse_interval("60s"), Ok(Duration::from_secs(60)));
+        assert_eq!(parse_interval("2m"), Ok(Duration::from_secs(120)));
+        assert_eq!(parse_interval("250ms"), Ok(Duration::from_millis(250)));
+        assert!(parse_interval("0s").is_err());
+        assert!(parse_interval("5 parsecs").is_err());
+
+        let dir = std::env::temp_dir().join(format!("ragc-autosnap-{}", std::process::id()));
+        let dir = dir.to_string_lossy().into_owned();
+        let interval = Duration::from_millis(1); // 85 MCTs
+        let mut auto = AutoSnapshot::new(&dir, interval, SnapClock::Emulated, 2).unwrap();
+        let mut cpu = Cpu::new(MemoryMap::new_blank());
+        for _ in 0..5 {
+            auto.poll(&mut cpu, 1);
+            cpu.total_cycles += 100;
+        }
+        auto.poll(&mut cpu, 1);
+
+        let mut names: Vec<_> = std::fs::read_dir(&dir)
+            .unwrap()
+            .map(|e| e.unwrap().file_name().into_string().unwrap())
+            .collect();
+        names.sort();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(names, ["ragc-000000000400.snap", "ragc-000000000500.snap"]);
+    }
+}
@@ -0,0 +1,321 @@
+use log::{error, info};
+use serde_json::{json, Value};
+
+use ragc_core::constants::ports::CHANNEL_DSKY;
+use ragc_core::cpu::{Cpu, CpuFault, FaultPolicy, RestartCause};
+use ragc_core::frame::FrameIo;
+use ragc_core::memory::mods::{EmuTime, MCT_SECONDS};
+use ragc_core::memory::tap::ChannelTap;
+use ragc_core::memory::MemoryMapBuilder;
+use ragc_core::rng::{streams, Rng};
+
+use crate::kit::Kit;
+use crate::listing::Symbols;
+use crate::report::DskyLog;
+use crate::ropes::{rom_info_by_name, ROM_NAMES};
+use crate::timeline::Timeline;
+
+/// MCTs run between checks (about 10 ms)
+const FRAME_MCTS: u32 = EmuTime::mcts_in_millis(10) as u32;
+
+/// Why a headless run stopped
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Outcome {
+    Reached,  // The --until condition came up
+    Finished, // Ran to the timeout with no condition to wait for
+    Timeout,  // The condition never came up
+    Alarm,    // The PROG alarm light came on
+    Restart(RestartCause),
+    Fault(CpuFault), // Halted under a halting fault policy, e.g. --strict
+}
+
+impl Outcome {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Outcome::Reached => "reached",
+            Outcome::Finished => "finished",
+            Outcome::Timeout => "timeout",
+            Outcome::Alarm => "alarm",
+            Outcome::Restart(_) => "restart",
+            Outcome::Fault(_) => "fault",
+        }
+    }
+
+    /// Process exit status: 0 for a clean run, 1 for an alarm, restart or
+    /// fault, 2 for a timeout
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Outcome::Reached | Outcome::Finished => 0,
+            Outcome::Alarm | Outcome::Restart(_) | Outcome::Fault(_) => 1,
+            Outcome::Timeout => 2,
+        }
+    }
+}
+
+/// Summary of a headless run, for `--json-result`
+pub struct BatchResult {
+    pub outcome: Outcome,
+    pub cycles: usize,
+    pub program: Option<u8>,
+    pub restarts: u32,
+    pub alarms: u32,
+}
+
+impl BatchResult {
+    pub fn to_json(&self, rom: &str, until: Option<&str>) -> Value {
+        let cause = match self.outcome {
+            Outcome::Restart(cause) => Some(format!("{:?}", cause)),
+            _ => None,
+        };
+        let fault = match self.outcome {
+            Outcome::Fault(fault) => Some(format!("{:?}", fault)),
+            _ => None,
+        };
+        json!({
+            "rom": rom,
+            "until": until,
+            "outcome": self.outcome.name(),
+            "exit_code": self.outcome.exit_code(),
+            "cycles": self.cycles,
+            "seconds": self.cycles as f64 * MCT_SECONDS,
+            "program": self.program,
+            "restarts": self.restarts,
+            "restart_cause": cause,
+            "fault": fault,
+            "prog_alarms": self.alarms,
+        })
+    }
+}
+
+/// Run a reset CPU flat out until `until` fires or `timeout` MCTs pass,
+/// stopping early on a PROG alarm, a restart or a halting fault. Checked
+/// every frame.
+pub fn run(cpu: &mut Cpu, mut until: Option<Timeline>, timeout: usize) -> BatchResult {
+    let mut io = FrameIo::new();
+    let mut dsky = DskyLog::default();
+    let restarts = cpu.restart_count();
+    let outcome = loop {
+        let remaining = timeout.saturating_sub(cpu.total_cycles);
+        if remaining == 0 {
+            break match until {
+                Some(_) => Outcome::Timeout,
+                None => Outcome::Finished,
+            };
+        }
+        cpu.run_frame(&mut io, FRAME_MCTS.min(remaining as u32));
+        for (channel, value) in io.channel_writes.iter() {
+            if *channel == CHANNEL_DSKY {
+                dsky.write(*channel, *value);
+            }
+        }
+
+        if let Some(fault) = cpu.fault().filter(|_| cpu.halted()) {
+            break Outcome::Fault(fault);
+        }
+        if cpu.restart_count() != restarts {
+            break Outcome::Restart(cpu.last_restart().unwrap_or(RestartCause::Manual));
+        }
+        if dsky.alarms() > 0 {
+            break Outcome::Alarm;
+        }
+        if let Some(timeline) = until.as_mut() {
+            if !timeline.poll(cpu).is_empty() {
+                break Outcome::Reached;
+            }
+        }
+    };
+    info!(
+        "Batch run: {} after {} MCTs",
+        outcome.name(),
+        cpu.total_cycles
+    );
+    BatchResult {
+        outcome,
+        cycles: cpu.total_cycles,
+        program: cpu.program(),
+        restarts: cpu.restart_count(),
+        alarms: dsky.alarms(),
+    }
+}
+
+/// The `run` subcommand: a rope to run, optionally headless for CI
+pub fn subcommand<'a, 'b>() -> clap::App<'a, 'b> {
+    clap::SubCommand::with_name("run")
+        .about("Run a bundled ROM or a rope image file")
+        .arg(
+            clap::Arg::with_name("rom")
+                .long("rom")
+                .takes_value(true)
+                .possible_values(&ROM_NAMES)
+                .default_value("luminary99")
+                .help("Bundled ROM to run"),
+        )
+        .arg(
+            clap::Arg::with_name("rope")
+                .long("rope")
+                .takes_value(true)
+                .help("Rope image file (.bin) to run instead of a bundled ROM"),
+        )
+        .arg(crate::kit::arg())
+        .arg(
+            clap::Arg::with_name("require")
+                .long("require")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .help("Fail unless the emulator has this, e.g. instruction:DV or channel:CHAN13"),
+        )
+        .arg(
+            clap::Arg::with_name("headless")
+                .long("headless")
+                .help("Run flat out with no DSKY and exit with a status (for CI)"),
+        )
+        .arg(
+            clap::Arg::with_name("until")
+                .long("until")
+                .takes_value(true)
+                .requires("headless")
+                .help("Stop condition, e.g. \"program == 63\" or \"sp(RN) < 500\""),
+        )
+        .arg(
+            clap::Arg::with_name("timeout")
+                .long("timeout")
+                .takes_value(true)
+                .default_value("8533333")
+                .help("Headless run limit in MCTs (default about 100 s)"),
+        )
+        .arg(
+            clap::Arg::with_name("json-result")
+                .long("json-result")
+                .takes_value(true)
+                .requires("headless")
+                .help("Write the headless run summary to a JSON file"),
+        )
+}
+
+/// A `run --headless`, with its stop condition and limits
+pub struct Headless<'a> {
+    args: &'a clap::ArgMatches<'a>,
+    until: Option<&'a str>,
+    timeout: usize,
+    json_result: Option<&'a str>,
+}
+
+/// The headless run asked for, if `run` was given `--headless`
+pub fn from_args<'a>(matches: &'a clap::ArgMatches<'a>) -> Result<Option<Headless<'a>>, String> {
+    let args = match matches
+        .subcommand_matches("run")
+        .filter(|args| args.is_present("headless"))
+    {
+        Some(args) => args,
+        None => return Ok(None),
+    };
+    let timeout = args.value_of("timeout").unwrap_or_default();
+    Ok(Some(Headless {
+        args,
+        until: args.value_of("until"),
+        timeout: timeout
+            .parse()
+            .map_err(|_| format!("Invalid timeout: {}", timeout))?,
+        json_result: args.value_of("json-result"),
+    }))
+}
+
+impl Headless<'_> {
+    /// Run to the condition and report how it went. Returns the exit
+    /// status.
+    pub fn run(
+        &self,
+        symbols: Option<&str>,
+        seed: u64,
+        randomize: bool,
+        policy: FaultPolicy,
+        kit: Option<&Kit>,
+    ) -> i32 {
+        match crate::load_symbols(symbols)
+            .and_then(|symbols| self.run_with(&symbols, seed, randomize, policy, kit))
+        {
+            Ok(status) => status,
+            Err(e) => {
+                error!("Batch run failed: {}", e);
+                3
+            }
+        }
+    }
+
+    fn run_with(
+        &self,
+        symbols: &Symbols,
+        seed: u64,
+        randomize: bool,
+        policy: FaultPolicy,
+        kit: Option<&Kit>,
+    ) -> Result<i32, String> {
+        let rope = crate::kit::run_rope(self.args, kit)?;
+        let (rope_path, rom_name) = crate::kit::run_source(self.args, kit);
+        let name = rope_path.or(rom_name).unwrap_or_default();
+        let padload = kit.map(|kit| kit.padload(symbols)).transpose()?.flatten();
+        let timeline = self
+            .until
+            .map(|until| Timeline::condition(until, symbols))
+            .transpose()?;
+
+        let rom_info = rom_name.and_then(rom_info_by_name).unwrap_or_default();
+        let mut mem = MemoryMapBuilder::new()
+            .rope(&rope)
+            .rom_info(rom_info)
+            .relay_cadence(0)
+            .build();
+        if randomize {
+            mem.randomize_erasable(&mut Rng::new(seed).fork(streams::ERASABLE_INIT));
+        }
+        if let Some(padload) = padload {
+            padload.apply(&mut mem);
+        }
+        let mut cpu = Cpu::new(mem);
+        cpu.set_fault_policy(policy);
+        cpu.reset();
+        let result = run(&mut cpu, timeline, self.timeout);
+
+        let summary = result.to_json(name, self.until);
+        println!("{}", summary);
+        if let Some(path) = self.json_result {
+            let text = serde_json::to_string_pretty(&summary).map_err(|e| e.to_string())?;
+            std::fs::write(path, text).map_err(|e| format!("{}: {}", path, e))?;
+        }
+        Ok(result.outcome.exit_code())
+    }
+}
+
+#[cfg(test)]
+mod batch_tests {
+    use super::{run, Outcome};
+    use crate::timeline::Timeline;
+    use ragc_core::cpu::Cpu;
+    use ragc_core::memory::MemoryMap;
+    use ragc_core::symbols::ErasableAddress;
+
+    #[test]
+    fn stops_on_condition_or_timeout() {
+        let none: &[(&str, ErasableAddress)] = &[];
+        let mut cpu = Cpu::new(MemoryMap::new_blank());
+        cpu.reset();
+        let result = run(
+            &mut cpu,
+            Some(Timeline::condition("cycles >= 100", none).unwrap()),
+            5000,
+        );
+        assert_eq!(result.outcome, Outcome::Reached);
+        assert!(result.cycles >= 100 && result.cycles < 5000);
+
+        let mut cpu = Cpu::new(MemoryMap::new_blank());
+        cpu.reset();
+        let until = Timeline::condition("program == 63", none).unwrap();
+        let result = run(&mut cpu, Some(until), 200);
+        assert_eq!(result.outcome, Outcome::Timeout);
+        assert_eq!(result.outcome.exit_code(), 2);
+        let summary = result.to_json("blank", Some("program == 63"));
+        assert_eq!(summary["outcome"], "timeout");
+        assert_eq!(summary["cycles"], result.cycles);
+    }
+}
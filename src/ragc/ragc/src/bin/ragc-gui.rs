@@ -0,0 +1,653 @@
+//! Graphical DSKY and status panel for a running emulator. The DSKY face is
+//! driven over the DSKY socket like any other frontend; the channel
+//! inspector, run controls, discretes and hand controllers go through the
+//! JSON-RPC control socket. Host keys are bound with `--input-map`; see
+//! `ragc_peripherals::input_map` for the file format.
+//!
+//! ragc --control 127.0.0.1:19698
+//! ragc-gui --control 127.0.0.1:19698
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpStream;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{Duration, Instant};
+
+use crossbeam_channel::{unbounded, Receiver, RecvTimeoutError, Sender};
+use eframe::egui::{self, Color32, Pos2, Stroke, Vec2};
+use log::{error, info, warn};
+use serde_json::{json, Value};
+
+use dsky_protocol::agc::{generate_dsky_packet, parse_dsky_packet};
+use ragc_core::memory::relay::DisplayFrame;
+use ragc_core::memory::tap::ChannelTap;
+use ragc_peripherals::digit_7seg;
+use ragc_peripherals::dsky::DEFAULT_DSKY_ADDR;
+use ragc_peripherals::input_map::{Action, InputMap, InputMapFile};
+use ragc_peripherals::shared_dsky::{DskyState, SharedDsky};
+
+/// Frontend input channels: keycodes and PRO
+const CHANNEL_KEY: usize = 0o15;
+const CHANNEL_PROCEED: usize = 0o32;
+
+/// Channel 163 bit set while VERB and NOUN are blanked by the flash
+const LIGHT_VN_BLANK: u16 = 0o40;
+
+/// How often the channel inspector is refreshed
+const CHANNEL_POLL: Duration = Duration::from_millis(500);
+
+/// How often the input map file is checked for changes
+const RELOAD_CHECK: Duration = Duration::from_secs(1);
+
+const DIGIT_SIZE: Vec2 = Vec2::new(24.0, 40.0);
+const LAMP_SIZE: Vec2 = Vec2::new(84.0, 34.0);
+const KEY_SIZE: Vec2 = Vec2::new(52.0, 44.0);
+const SEGMENT_ON: Color32 = Color32::from_rgb(0x6c, 0xff, 0x8a);
+const SEGMENT_OFF: Color32 = Color32::from_rgb(0x1a, 0x2a, 0x1d);
+const LAMP_WHITE: Color32 = Color32::from_rgb(0xf0, 0xf0, 0xe6);
+const LAMP_AMBER: Color32 = Color32::from_rgb(0xff, 0xb3, 0x2e);
+const LAMP_OFF: Color32 = Color32::from_rgb(0x4a, 0x4a, 0x46);
+
+/// Segment ends in a 1 x 2 cell, a through g
+const SEGMENT_LINES: [[(f32, f32); 2]; 7] = [
+    [(0.0, 0.0), (1.0, 0.0)],
+    [(1.0, 0.0), (1.0, 1.0)],
+    [(1.0, 1.0), (1.0, 2.0)],
+    [(0.0, 2.0), (1.0, 2.0)],
+    [(0.0, 1.0), (0.0, 2.0)],
+    [(0.0, 0.0), (0.0, 1.0)],
+    [(0.0, 1.0), (1.0, 1.0)],
+];
+
+/// Where a lamp takes its bit from
+#[derive(Clone, Copy)]
+enum LampSource {
+    Relay,    // Relay row 12
+    Dsalmout, // Channel 11
+    Lights,   // Channel 163, with flashing applied
+}
+
+/// Indicator panel as laid out on the DSKY: white status lamps on the left,
+/// amber caution lamps on the right
+const LAMPS: [[(&str, LampSource, u16); 2]; 7] = [
+    [
+        ("UPLINK ACTY", LampSource::Dsalmout, 0o4),
+        ("TEMP", LampSource::Lights, 0o10),
+    ],
+    [
+        ("NO ATT", LampSource::Relay, 0o10),
+        ("GIMBAL LOCK", LampSource::Relay, 0o40),
+    ],
+    [
+        ("STBY", LampSource::Lights, 0o400),
+        ("PROG", LampSource::Relay, 0o400),
+    ],
+    [
+        ("KEY REL", LampSource::Lights, 0o20),
+        ("RESTART", LampSource::Lights, 0o200),
+    ],
+    [
+        ("OPR ERR", LampSource::Lights, 0o100),
+        ("TRACKER", LampSource::Relay, 0o200),
+    ],
+    [
+        ("PRIO DISP", LampSource::Relay, 0o1),
+        ("ALT", LampSource::Relay, 0o20),
+    ],
+    [
+        ("NO DAP", LampSource::Relay, 0o2),
+        ("VEL", LampSource::Relay, 0o4),
+    ],
+];
+
+/// Keypad: label and keycode, blank labels leave a gap
+const KEYS: [[(&str, u16); 7]; 3] = [
+    [
+        ("VERB", 0o21),
+        ("+", 0o32),
+        ("7", 7),
+        ("8", 8),
+        ("9", 9),
+        ("CLR", 0o36),
+        ("ENTR", 0o34),
+    ],
+    [
+        ("NOUN", 0o37),
+        ("-", 0o33),
+        ("4", 4),
+        ("5", 5),
+        ("6", 6),
+        ("PRO", 0),
+        ("RSET", 0o22),
+    ],
+    [
+        ("", 0),
+        ("0", 0o20),
+        ("1", 1),
+        ("2", 2),
+        ("3", 3),
+        ("KEY REL", 0o31),
+        ("", 0),
+    ],
+];
+
+/// Connect to the DSKY port; incoming packets update `dsky` from a reader
+/// thread and the returned stream carries keys back
+fn connect_dsky(addr: &str, dsky: SharedDsky) -> std::io::Result<TcpStream> {
+    let stream = TcpStream::connect(addr)?;
+    let mut reader = stream.try_clone()?;
+    let mut tap = dsky;
+    std::thread::spawn(move || {
+        let mut buf = [0; 4];
+        while reader.read_exact(&mut buf).is_ok() {
+            match parse_dsky_packet(buf) {
+                // The port sends relay words one by one, with no frame marks
+                Some((0o10, value)) => {
+                    let mut frame = DisplayFrame::default();
+                    frame.push(value);
+                    tap.display_frame(&frame);
+                }
+                Some((channel, value)) => {
+                    tap.write(channel as usize, value);
+                }
+                None => {}
+            }
+        }
+        warn!("DSKY connection closed");
+    });
+    Ok(stream)
+}
+
+/// Line-delimited JSON-RPC client for the control socket
+struct Rpc {
+    reader: Box<dyn BufRead + Send>,
+    writer: Box<dyn Write + Send>,
+    id: u64,
+}
+
+impl Rpc {
+    fn connect(addr: &str) -> std::io::Result<Self> {
+        #[cfg(unix)]
+        if let Some(path) = addr.strip_prefix("unix:") {
+            let stream = std::os::unix::net::UnixStream::connect(path)?;
+            return Ok(Self::new(BufReader::new(stream.try_clone()?), stream));
+        }
+        let stream = TcpStream::connect(addr)?;
+        Ok(Self::new(BufReader::new(stream.try_clone()?), stream))
+    }
+
+    fn new(reader: impl BufRead + Send + 'static, writer: impl Write + Send + 'static) -> Self {
+        Self {
+            reader: Box::new(reader),
+            writer: Box::new(writer),
+            id: 0,
+        }
+    }
+
+    fn call(&mut self, method: &str, params: Value) -> Result<Value, String> {
+        self.id += 1;
+        let request =
+            json!({ "jsonrpc": "2.0", "id": self.id, "method": method, "params": params });
+        writeln!(self.writer, "{}", request).map_err(|e| e.to_string())?;
+        let mut line = String::new();
+        match self.reader.read_line(&mut line) {
+            Ok(0) => return Err("Control socket closed".to_string()),
+            Ok(_) => {}
+            Err(e) => return Err(e.to_string()),
+        }
+        let reply: Value = serde_json::from_str(&line).map_err(|e| e.to_string())?;
+        match reply.get("error") {
+            Some(e) => Err(format!("{}: {}", method, e["message"])),
+            None => Ok(reply["result"].clone()),
+        }
+    }
+}
+
+/// One row of the channel inspector
+struct ChannelRow {
+    channel: u64,
+    name: String,
+    value: u16,
+    bits: String,
+}
+
+/// What the control thread last heard from the emulator
+#[derive(Default)]
+struct Status {
+    channels: Vec<ChannelRow>,
+    error: Option<String>,
+}
+
+fn channel_rows(result: &Value) -> Vec<ChannelRow> {
+    let entries = result.as_array().map(Vec::as_slice).unwrap_or_default();
+    entries
+        .iter()
+        .map(|entry| {
+            let bits: Vec<_> = entry["bits"]
+                .as_array()
+                .map(Vec::as_slice)
+                .unwrap_or_default()
+                .iter()
+                .filter_map(Value::as_str)
+                .collect();
+            ChannelRow {
+                channel: entry["channel"].as_u64().unwrap_or_default(),
+                name: entry["name"].as_str().unwrap_or_default().to_string(),
+                value: entry["value"].as_u64().unwrap_or_default() as u16,
+                bits: bits.join(", "),
+            }
+        })
+        .collect()
+}
+
+/// Owns the control connection: sends the GUI's requests and refreshes
+/// the channels while idle
+fn control_thread(
+    addr: String,
+    token: Option<String>,
+    requests: Receiver<(&'static str, Value)>,
+    status: Arc<Mutex<Status>>,
+) {
+    let report = |result: Result<Value, String>| {
+        let mut status = status.lock().unwrap_or_else(PoisonError::into_inner);
+        status.error = result.err();
+    };
+    let mut rpc = match Rpc::connect(&addr) {
+        Ok(x) => x,
+        Err(e) => return report(Err(format!("{}: {}", addr, e))),
+    };
+    if let Some(token) = token {
+        if let Err(e) = rpc.call("auth", json!({ "token": token })) {
+            return report(Err(e));
+        }
+    }
+    loop {
+        let result = match requests.recv_timeout(CHANNEL_POLL) {
+            Ok((method, params)) => rpc.call(method, params),
+            Err(RecvTimeoutError::Timeout) => rpc.call("channels", Value::Null).inspect(|result| {
+                let rows = channel_rows(result);
+                status
+                    .lock()
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .channels = rows;
+            }),
+            Err(RecvTimeoutError::Disconnected) => return,
+        };
+        report(result);
+    }
+}
+
+/// One 7-segment digit
+fn digit(ui: &mut egui::Ui, segments: u8) {
+    let (rect, _) = ui.allocate_exact_size(DIGIT_SIZE, egui::Sense::hover());
+    let cell = rect.shrink(5.0);
+    let at = |(x, y): (f32, f32)| {
+        Pos2::new(
+            cell.min.x + x * cell.width(),
+            cell.min.y + y * cell.height() / 2.0,
+        )
+    };
+    for (i, [from, to]) in SEGMENT_LINES.iter().enumerate() {
+        let color = match segments & (1 << i) != 0 {
+            true => SEGMENT_ON,
+            false => SEGMENT_OFF,
+        };
+        ui.painter()
+            .line_segment([at(*from), at(*to)], Stroke::new(3.5, color));
+    }
+}
+
+/// Register sign: a bar for minus, a cross for plus
+fn sign(ui: &mut egui::Ui, sign: Option<i32>) {
+    let (rect, _) = ui.allocate_exact_size(DIGIT_SIZE, egui::Sense::hover());
+    let (c, r) = (rect.center(), rect.width() / 2.0 - 5.0);
+    let stroke = |lit: bool| Stroke::new(3.5, if lit { SEGMENT_ON } else { SEGMENT_OFF });
+    let painter = ui.painter();
+    painter.line_segment(
+        [c - Vec2::new(r, 0.0), c + Vec2::new(r, 0.0)],
+        stroke(sign.is_some()),
+    );
+    painter.line_segment(
+        [c - Vec2::new(0.0, r), c + Vec2::new(0.0, r)],
+        stroke(sign == Some(1)),
+    );
+}
+
+fn lamp(ui: &mut egui::Ui, name: &str, color: Color32, lit: bool) {
+    let (rect, _) = ui.allocate_exact_size(LAMP_SIZE, egui::Sense::hover());
+    let fill = if lit { color } else { LAMP_OFF };
+    ui.painter().rect_filled(rect.shrink(2.0), 3.0, fill);
+    ui.painter().text(
+        rect.center(),
+        egui::Align2::CENTER_CENTER,
+        name,
+        egui::FontId::proportional(11.0),
+        Color32::BLACK,
+    );
+}
+
+/// Labelled pair of digits: PROG, VERB or NOUN
+fn pair(ui: &mut egui::Ui, label: &str, digits: [Option<u8>; 2]) {
+    ui.vertical(|ui| {
+        ui.label(egui::RichText::new(label).small().color(SEGMENT_ON));
+        ui.horizontal(|ui| {
+            for d in digits.iter() {
+                digit(ui, digit_7seg(*d));
+            }
+        });
+    });
+}
+
+struct App {
+    dsky: SharedDsky,
+    keys: TcpStream,
+    pro_held: bool,
+    control: Option<Sender<(&'static str, Value)>>,
+    status: Arc<Mutex<Status>>,
+    paused: bool,
+    step_count: u32,
+    speed: f64,
+    default_map: InputMap,
+    map_file: Option<InputMapFile>,
+    map_checked: Instant,
+}
+
+impl App {
+    fn send_key(&mut self, channel: usize, value: u16) {
+        if let Err(e) = self.keys.write_all(&generate_dsky_packet(channel, value)) {
+            error!("DSKY key not sent: {}", e);
+        }
+    }
+
+    fn request(&self, method: &'static str, params: Value) {
+        if let Some(control) = &self.control {
+            let _ = control.send((method, params));
+        }
+    }
+
+    /// Drive the bound DSKY keys, discretes and axes from host key events
+    fn host_input(&mut self, ctx: &egui::Context) {
+        if let Some(file) = &mut self.map_file {
+            if self.map_checked.elapsed() >= RELOAD_CHECK {
+                self.map_checked = Instant::now();
+                match file.reload_if_changed() {
+                    Some(Ok(())) => info!("Input map reloaded"),
+                    Some(Err(e)) => error!("Input map not reloaded: {}", e),
+                    None => {}
+                }
+            }
+        }
+        if ctx.wants_keyboard_input() {
+            return; // Typing into a field
+        }
+        let events = ctx.input(|i| i.events.clone());
+        for event in events.iter() {
+            if let egui::Event::Key {
+                key,
+                pressed,
+                repeat: false,
+                ..
+            } = event
+            {
+                let map = self
+                    .map_file
+                    .as_ref()
+                    .map_or(&self.default_map, |f| f.map());
+                if let Some(action) = map.button(key.name()) {
+                    self.apply(action, *pressed);
+                }
+            }
+        }
+    }
+
+    fn apply(&mut self, action: Action, pressed: bool) {
+        match action {
+            Action::Key(code) if pressed => self.send_key(CHANNEL_KEY, code),
+            Action::Key(_) => {}
+            Action::Proceed => self.send_key(CHANNEL_PROCEED, if pressed { 0 } else { 0o20000 }),
+            Action::Discrete(discrete) => self.request(
+                "set_discrete",
+                json!({ "name": discrete.name(), "asserted": pressed }),
+            ),
+            Action::Deflect(axis, value) => self.request(
+                "set_axis",
+                json!({ "name": axis.name(), "value": if pressed { value } else { 0.0 } }),
+            ),
+        }
+    }
+
+    fn indicators(&self, ui: &mut egui::Ui, state: &DskyState) {
+        egui::Grid::new("lamps").spacing([4.0, 4.0]).show(ui, |ui| {
+            for row in LAMPS.iter() {
+                for (column, (name, source, mask)) in row.iter().enumerate() {
+                    let word = match source {
+                        LampSource::Relay => state.relay_lights,
+                        LampSource::Dsalmout => state.lamps,
+                        LampSource::Lights => state.lights,
+                    };
+                    let color = if column == 0 { LAMP_WHITE } else { LAMP_AMBER };
+                    lamp(ui, name, color, word & mask != 0);
+                }
+                ui.end_row();
+            }
+        });
+    }
+
+    fn display(&self, ui: &mut egui::Ui, state: &DskyState) {
+        let [prog, mut verb, mut noun] = state.display.pair_digits();
+        if state.lights & LIGHT_VN_BLANK != 0 {
+            verb = [None; 2];
+            noun = [None; 2];
+        }
+        ui.vertical(|ui| {
+            ui.horizontal(|ui| {
+                lamp(ui, "COMP ACTY", SEGMENT_ON, state.lamps & 0o2 != 0);
+                pair(ui, "PROG", prog);
+            });
+            ui.horizontal(|ui| {
+                pair(ui, "VERB", verb);
+                ui.add_space(DIGIT_SIZE.x);
+                pair(ui, "NOUN", noun);
+            });
+            for reg in 0..3 {
+                ui.separator();
+                ui.horizontal(|ui| {
+                    sign(ui, state.display.register_sign(reg));
+                    for d in state.display.register_digits(reg).iter() {
+                        digit(ui, digit_7seg(*d));
+                    }
+                });
+            }
+        });
+    }
+
+    fn keypad(&mut self, ui: &mut egui::Ui) {
+        egui::Grid::new("keys").spacing([4.0, 4.0]).show(ui, |ui| {
+            for row in KEYS.iter() {
+                for (label, code) in row.iter() {
+                    if label.is_empty() {
+                        ui.allocate_space(KEY_SIZE);
+                        continue;
+                    }
+                    let button = ui.add(egui::Button::new(*label).min_size(KEY_SIZE));
+                    if *label == "PRO" {
+                        // PRO is held, not pressed: the bit reads 0 while down
+                        let held = button.is_pointer_button_down_on();
+                        if held != self.pro_held {
+                            self.send_key(CHANNEL_PROCEED, if held { 0 } else { 0o20000 });
+                            self.pro_held = held;
+                        }
+                    } else if button.clicked() {
+                        self.send_key(CHANNEL_KEY, *code);
+                    }
+                }
+                ui.end_row();
+            }
+        });
+    }
+
+    fn run_controls(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            let label = if self.paused { "Resume" } else { "Pause" };
+            if ui.button(label).clicked() {
+                self.paused = !self.paused;
+                self.request(if self.paused { "pause" } else { "resume" }, Value::Null);
+            }
+            if ui.button("Step").clicked() {
+                self.paused = true;
+                self.request("step", json!({ "count": self.step_count }));
+            }
+            ui.add(egui::DragValue::new(&mut self.step_count).range(1..=100_000));
+        });
+        let speed = egui::Slider::new(&mut self.speed, 0.1..=20.0)
+            .logarithmic(true)
+            .text("x real time");
+        if ui.add(speed).changed() {
+            self.request("set_speed", json!({ "speed": self.speed }));
+        }
+    }
+
+    fn inspector(&self, ui: &mut egui::Ui) {
+        let status = self.status.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Some(e) = &status.error {
+            ui.colored_label(Color32::LIGHT_RED, e);
+        }
+        egui::ScrollArea::vertical().show(ui, |ui| {
+            egui::Grid::new("channels").striped(true).show(ui, |ui| {
+                for heading in ["Ch", "Name", "Value", "Bits 15..1", "Set"].iter() {
+                    ui.strong(*heading);
+                }
+                ui.end_row();
+                for row in status.channels.iter() {
+                    let bits = format!("{:015b}", row.value);
+                    let groups: Vec<_> = bits.as_bytes().chunks(3).collect();
+                    let groups: Vec<_> = groups
+                        .iter()
+                        .map(|g| std::str::from_utf8(g).unwrap_or_default())
+                        .collect();
+                    ui.monospace(format!("{:03o}", row.channel));
+                    ui.label(&row.name);
+                    ui.monospace(format!("{:05o}", row.value));
+                    ui.monospace(groups.join(" "));
+                    ui.label(&row.bits);
+                    ui.end_row();
+                }
+            });
+        });
+    }
+}
+
+impl eframe::App for App {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        self.host_input(ctx);
+        let state = self.dsky.snapshot();
+        egui::SidePanel::right("status")
+            .min_width(420.0)
+            .show(ctx, |ui| match self.control.is_some() {
+                true => {
+                    ui.heading("Run");
+                    self.run_controls(ui);
+                    ui.separator();
+                    ui.heading("Channels");
+                    self.inspector(ui);
+                }
+                false => {
+                    ui.label("Start ragc and this window with the same --control address to enable the run controls and channel inspector.");
+                }
+            });
+        egui::CentralPanel::default().show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                self.indicators(ui, &state);
+                ui.add_space(16.0);
+                egui::Frame::new()
+                    .fill(Color32::from_rgb(0x10, 0x14, 0x10))
+                    .inner_margin(8.0)
+                    .show(ui, |ui| self.display(ui, &state));
+            });
+            ui.add_space(12.0);
+            self.keypad(ui);
+        });
+        ctx.request_repaint_after(Duration::from_millis(50));
+    }
+}
+
+fn main() {
+    env_logger::init();
+
+    let matches = clap::App::new("RAGC DSKY")
+        .about("Graphical DSKY and status panel for a running emulator")
+        .arg(
+            clap::Arg::with_name("dsky")
+                .long("dsky")
+                .takes_value(true)
+                .help("DSKY socket address (default 127.0.0.1:19697)"),
+        )
+        .arg(
+            clap::Arg::with_name("control")
+                .long("control")
+                .takes_value(true)
+                .help("Emulator control socket: <host:port> or unix:<path>"),
+        )
+        .arg(
+            clap::Arg::with_name("control-token")
+                .long("control-token")
+                .takes_value(true)
+                .requires("control")
+                .help("Token for the control socket"),
+        )
+        .arg(
+            clap::Arg::with_name("input-map")
+                .long("input-map")
+                .takes_value(true)
+                .help("TOML file binding host keys; reloaded when it changes"),
+        )
+        .get_matches();
+
+    let dsky = SharedDsky::new();
+    let addr = matches.value_of("dsky").unwrap_or(DEFAULT_DSKY_ADDR);
+    let keys = match connect_dsky(addr, dsky.clone()) {
+        Ok(x) => x,
+        Err(e) => {
+            error!("DSKY at {}: {}", addr, e);
+            return;
+        }
+    };
+
+    let map_file = match matches.value_of("input-map").map(InputMapFile::load) {
+        Some(Ok(x)) => Some(x),
+        Some(Err(e)) => {
+            error!("Invalid input map: {}", e);
+            return;
+        }
+        None => None,
+    };
+
+    let status = Arc::new(Mutex::new(Status::default()));
+    let control = matches.value_of("control").map(|addr| {
+        let (tx, rx) = unbounded();
+        let addr = addr.to_string();
+        let token = matches.value_of("control-token").map(String::from);
+        let status = status.clone();
+        std::thread::spawn(move || control_thread(addr, token, rx, status));
+        tx
+    });
+
+    let app = App {
+        dsky,
+        keys,
+        pro_held: false,
+        control,
+        status,
+        paused: false,
+        step_count: 1,
+        speed: 1.0,
+        default_map: InputMap::default(),
+        map_file,
+        map_checked: Instant::now(),
+    };
+    if let Err(e) = eframe::run_native(
+        "RAGC DSKY",
+        eframe::NativeOptions::default(),
+        Box::new(|_cc| Ok(Box::new(app))),
+    ) {
+        error!("GUI failed: {}", e);
+    }
+}
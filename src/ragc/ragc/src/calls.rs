@@ -0,0 +1,309 @@
+//! Call tracking for the debugger's step-over, step-out and backtrace.
+//! The AGC has no call stack: TC leaves the return address in Q and the
+//! callee returns with TC Q, or saves Q somewhere first and comes back by
+//! any route it likes. A shadow stack of the calls seen is kept instead,
+//! each popped when the CPU reaches its return address, however it got
+//! there. This is a heuristic and can be wrong: a routine that never
+//! returns leaves its frame behind until a caller further out returns,
+//! and one that returns somewhere else is never popped. Frames made by
+//! interrupt code are dropped when the interrupt ends, and the whole stack
+//! when the executive switches jobs, as each job has its own calls.
+use ragc_core::constants::address_space::SWITCHED_FIXED_START;
+use ragc_core::constants::registers::{REGISTER_COUNTER, REGISTER_RETURN};
+use ragc_core::cpu::Cpu;
+use ragc_core::hooks::FixedAddress;
+use ragc_core::memory::{AddressSpace, Location};
+
+use crate::listing::{self, Symbols};
+
+/// Deepest the shadow stack grows; routines that never return (jobs
+/// ending in ENDOFJOB, GOTOs through TC) would otherwise pile up
+const MAX_DEPTH: usize = 256;
+
+/// Routines called as `TC <trampoline>` followed by a CADR of the target,
+/// which return past the CADR
+const TRAMPOLINES: [&str; 3] = ["BANKCALL", "SWCALL", "IBNKCALL"];
+
+/// Executive routines where the running job gives up the CPU
+const JOB_SWITCHES: [&str; 4] = ["ENDOFJOB", "JOBSLEEP", "CHANG1", "CHANJOB"];
+
+/// Routines the tracker treats specially, found in the symbol table
+#[derive(Clone, Default)]
+pub struct Markers {
+    trampolines: Vec<FixedAddress>,
+    job_switches: Vec<FixedAddress>,
+}
+
+impl Markers {
+    pub fn new(symbols: &Symbols) -> Self {
+        let find = |names: &[&str]| names.iter().filter_map(|n| symbols.fixed(n)).collect();
+        Self {
+            trampolines: find(&TRAMPOLINES),
+            job_switches: find(&JOB_SWITCHES),
+        }
+    }
+}
+
+/// A call not yet returned from
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Frame {
+    pub pc: u16, // Return address
+    pub at: Option<Location>,
+    pub entry: Option<Location>, // Routine called, unknown for one assumed from Q
+    pub irupt: bool,             // Made from interrupt code
+}
+
+impl Frame {
+    fn returns_to(&self, here: &Frame) -> bool {
+        (self.pc, self.at, self.irupt) == (here.pc, here.at, here.irupt)
+    }
+}
+
+/// Where the CPU is and the calls it is in, innermost last
+#[derive(Clone, Debug)]
+pub struct Backtrace {
+    pub pc: u16,
+    pub at: Option<Location>,
+    pub frames: Vec<Frame>,
+}
+
+#[derive(Default)]
+pub struct CallTracker {
+    stack: Vec<Frame>,
+    markers: Markers,
+    restarts: u32,
+}
+
+impl CallTracker {
+    pub fn set_markers(&mut self, markers: Markers) {
+        self.markers = markers;
+    }
+
+    pub fn backtrace(&self, cpu: &Cpu) -> Backtrace {
+        let here = frame(cpu, cpu.memory().read(REGISTER_COUNTER));
+        Backtrace {
+            pc: here.pc,
+            at: here.at,
+            frames: self.stack.clone(),
+        }
+    }
+
+    pub fn depth(&self) -> usize {
+        self.stack.len()
+    }
+
+    /// Pop the calls the CPU has returned from, as it reaches the return
+    /// address of one; returns whether any were. Reaching a job switch
+    /// unwinds the job's calls and counts as returning from them.
+    pub fn returned(&mut self, cpu: &Cpu) -> bool {
+        if cpu.restart_count() != self.restarts {
+            self.restarts = cpu.restart_count();
+            self.stack.clear();
+            return false;
+        }
+        while !cpu.is_irupt && self.stack.last().is_some_and(|f| f.irupt) {
+            self.stack.pop();
+        }
+        let here = frame(cpu, cpu.memory().read(REGISTER_COUNTER));
+        if let Some(Location::Fixed(at)) = here.at {
+            if !cpu.is_irupt && self.markers.job_switches.contains(&at) {
+                let depth = self.stack.len();
+                self.stack.clear();
+                return depth > 0;
+            }
+        }
+        // A routine returning past its callee's frame abandons that too
+        match self.stack.iter().rposition(|f| f.returns_to(&here)) {
+            Some(idx) => {
+                self.stack.truncate(idx);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Note the instruction about to execute, pushing a frame if it calls
+    pub fn entering(&mut self, cpu: &Cpu) {
+        if let Some((ret, entry)) = self.call(cpu) {
+            if self.stack.len() == MAX_DEPTH {
+                self.stack.remove(0);
+            }
+            self.stack.push(Frame {
+                entry,
+                ..frame(cpu, ret)
+            });
+        }
+    }
+
+    /// Make the caller of the running routine known, from Q, when no call
+    /// into it was seen
+    pub fn assume_caller(&mut self, cpu: &Cpu) {
+        if self.stack.is_empty() {
+            let q = cpu.memory().read(REGISTER_RETURN);
+            self.stack.push(frame(cpu, q));
+        }
+    }
+
+    /// Whether the instruction about to execute is a subroutine call
+    pub fn is_call(&self, cpu: &Cpu) -> bool {
+        self.call(cpu).is_some()
+    }
+
+    /// Return address and routine called, if the instruction about to
+    /// execute is a subroutine call
+    fn call(&self, cpu: &Cpu) -> Option<(u16, Option<Location>)> {
+        let mem = cpu.memory();
+        let pc = mem.read(REGISTER_COUNTER);
+        let word = mem.read(pc as usize) & 0o77777;
+        let k = word & 0o7777;
+        // TC Q returns, by running the TC to the return address that Q
+        // holds; TC 3, 4 and 6 are RELINT, INHINT and EXTEND
+        if cpu.ec_flag || word & 0o70000 != 0 || pc <= 2 || matches!(k, 2 | 3 | 4 | 6) {
+            return None;
+        }
+        match mem.locate(k as usize) {
+            // The routine really called is the one the CADR names
+            Some(Location::Fixed(at)) if self.markers.trampolines.contains(&at) => {
+                let cadr = mem.read(pc as usize + 1);
+                let entry = AddressSpace::from_fb(cadr)
+                    .locate(SWITCHED_FIXED_START + (cadr & 0o1777) as usize);
+                Some((pc.wrapping_add(2), entry))
+            }
+            entry => Some((pc.wrapping_add(1), entry)),
+        }
+    }
+}
+
+fn frame(cpu: &Cpu, pc: u16) -> Frame {
+    Frame {
+        pc,
+        at: cpu.memory().locate(pc as usize),
+        entry: None,
+        irupt: cpu.is_irupt,
+    }
+}
+
+/// Print the backtrace innermost first, naming addresses from `symbols`
+pub fn write_backtrace(
+    bt: &Backtrace,
+    symbols: &Symbols,
+    out: &mut dyn std::io::Write,
+) -> std::io::Result<()> {
+    let name = |pc: u16, at: Option<Location>| match at {
+        Some(Location::Fixed(at)) => {
+            let at = listing::Location::Fixed(at.bank, at.offset);
+            (at.to_string(), symbols.describe(&at).unwrap_or_default())
+        }
+        _ => (format!("{:04o}", pc), String::new()),
+    };
+    let (here, symbol) = name(bt.pc, bt.at);
+    writeln!(out, "#0  {:<8} {}", here, symbol)?;
+    for (n, frame) in bt.frames.iter().rev().enumerate() {
+        let (ret, symbol) = name(frame.pc, frame.at);
+        let mut line = format!("#{:<2} {:<8} {:<16}", n + 1, ret, symbol);
+        if let Some(entry) = frame.entry {
+            let (entry, callee) = name(0, Some(entry));
+            let callee = if callee.is_empty() { entry } else { callee };
+            line.push_str(&format!(" after call to {}", callee));
+        }
+        if frame.irupt {
+            line.push_str(" (interrupt)");
+        }
+        writeln!(out, "{}", line.trim_end())?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod calls_tests {
+    use super::{write_backtrace, CallTracker, Markers};
+    use crate::listing::Symbols;
+    use ragc_core::cpu::{Cpu, RestartCause};
+    use ragc_core::hooks::FixedAddress;
+    use ragc_core::memory::MemoryMapBuilder;
+    use ragc_testutils::rope_image;
+
+    #[test]
+    fn tracks_calls_through_trampolines() {
+        // 4000: TC 4010; TC 4020; CADR; TCF 4003
+        // 4010: TC Q
+        // 4020: INCR Q; TC Q (returning past the CADR like BANKCALL)
+        let mut words = [0; 0o22];
+        words[..4].copy_from_slice(&[0o04010, 0o04020, 0o04010, 0o14003]);
+        words[0o10] = 0o00002;
+        words[0o20..].copy_from_slice(&[0o24002, 0o00002]);
+        let rope = rope_image(0o4000, &words);
+        let mut cpu = Cpu::new(MemoryMapBuilder::new().rope(&rope).build());
+        cpu.reset();
+        let mut calls = CallTracker::default();
+        calls.set_markers(Markers {
+            trampolines: vec![FixedAddress::new(2, 0o20)],
+            job_switches: Vec::new(),
+        });
+
+        let mut depths = Vec::new();
+        for _ in 0..9 {
+            let returned = calls.returned(&cpu);
+            depths.push((returned, calls.depth()));
+            calls.entering(&cpu);
+            cpu.step();
+        }
+        assert_eq!(
+            depths,
+            [
+                (false, 0), // TC 4010
+                (false, 1), // TC Q
+                (false, 1), // TC 4001, from Q
+                (true, 0),  // TC 4020
+                (false, 1), // INCR Q
+                (false, 1), // TC Q
+                (false, 1), // TC 4003, from Q
+                (true, 0),  // TCF 4003
+                (false, 0), // TCF 4003 again
+            ]
+        );
+
+        // Restarts abandon every call
+        cpu.reset();
+        calls.entering(&cpu);
+        assert_eq!(calls.depth(), 1);
+        cpu.gojam(RestartCause::Manual);
+        assert!(!calls.returned(&cpu));
+        assert_eq!(calls.depth(), 0);
+    }
+
+    #[test]
+    fn backtrace_names_calls_and_unwinds_at_job_switches() {
+        // 4000: TC 4010. 4010: TC 4020. 4020: CA A; TCF 4030
+        let mut words = [0o30000; 0o31];
+        words[0] = 0o04010;
+        words[0o10] = 0o04020;
+        words[0o21] = 0o14030;
+        let rope = rope_image(0o4000, &words);
+        let mut cpu = Cpu::new(MemoryMapBuilder::new().rope(&rope).build());
+        cpu.reset();
+        let symbols = Symbols::parse("MAIN 4000\nSUB 4010\nLEAF 4020\nENDOFJOB 4030").unwrap();
+        let mut calls = CallTracker::default();
+        calls.set_markers(Markers::new(&symbols));
+        let step = |calls: &mut CallTracker, cpu: &mut Cpu| {
+            calls.entering(cpu);
+            cpu.step();
+            calls.returned(cpu)
+        };
+        step(&mut calls, &mut cpu);
+        step(&mut calls, &mut cpu);
+
+        let mut out = Vec::new();
+        write_backtrace(&calls.backtrace(&cpu), &symbols, &mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "#0  4020     LEAF\n\
+             #1  4011     SUB+1            after call to LEAF\n\
+             #2  4001     MAIN+1           after call to SUB\n"
+        );
+
+        assert!(!step(&mut calls, &mut cpu));
+        assert!(step(&mut calls, &mut cpu));
+        assert_eq!(calls.depth(), 0);
+    }
+}
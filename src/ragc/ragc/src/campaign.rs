@@ -0,0 +1,207 @@
+use std::io::Write;
+
+use log::info;
+use serde::Deserialize;
+
+use ragc_core::constants::ports::CHANNEL_DSKY;
+use ragc_core::cpu::Cpu;
+use ragc_core::memory::mods::{EmuTime, MCT_SECONDS};
+use ragc_core::memory::tap::{ChannelTap, TapAction};
+use ragc_core::memory::MemoryMapBuilder;
+use ragc_core::rng::{streams, Rng};
+
+/// Relay row 12 carries the PROG alarm light in bit 9
+const RELAY_ROW_LIGHTS: u16 = 12;
+const LIGHT_PROG: u16 = 0o400;
+
+/// Central registers eligible for upsets (A, L, Q, EB, FB, Z, BB)
+const UPSET_REGISTERS: u16 = 7;
+
+/// Radiation-upset campaign settings (`[campaign]` in the runtime config)
+#[derive(Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct CampaignConfig {
+    pub seed: Option<u64>, // Defaults to the runtime seed
+    pub runs: u32,
+    pub duration_ms: u32,
+    #[serde(default)]
+    pub erasable_flips_per_s: f64,
+    #[serde(default)]
+    pub register_flips_per_s: f64,
+    pub output: String,
+}
+
+/// Outcome of a single campaign run
+struct RunOutcome {
+    seed: u64,
+    erasable_flips: u32,
+    register_flips: u32,
+    restarts: u32,
+    last_cause: String,
+    prog_alarm: bool,
+}
+
+impl RunOutcome {
+    fn classification(&self) -> &'static str {
+        match (self.restarts > 0, self.prog_alarm) {
+            (true, _) => "restart",
+            (false, true) => "alarm",
+            (false, false) => "undetected",
+        }
+    }
+}
+
+/// Watches relay words for the PROG alarm light
+#[derive(Default)]
+struct AlarmTap {
+    prog_alarm: bool,
+}
+
+impl ChannelTap for AlarmTap {
+    fn write(&mut self, _channel: usize, value: u16) -> TapAction {
+        if value >> 11 == RELAY_ROW_LIGHTS && value & LIGHT_PROG != 0 {
+            self.prog_alarm = true;
+        }
+        TapAction::Pass(value)
+    }
+}
+
+/// Flips `bit` of an erasable word where the CPU will see it
+fn flip_erasable(cpu: &mut Cpu, bank: usize, offset: usize, bit: u16) {
+    // Registers, timers and counters aren't stored in the RAM banks
+    if bank == 0 {
+        let word = cpu.read(offset);
+        cpu.write(offset, word ^ bit);
+    } else {
+        let word = cpu.memory().read_block(bank, offset..offset + 1)[0];
+        cpu.memory_mut().write_block(bank, offset, &[word ^ bit]);
+    }
+}
+
+fn run_once(
+    rom: &[[u16; 1024]; 36],
+    cfg: &CampaignConfig,
+    run_rng: &Rng,
+    randomize_erasable: bool,
+) -> RunOutcome {
+    let mut rng = run_rng.fork(streams::FAULT_INJECTION);
+    let mut alarm = AlarmTap::default();
+
+    let mut mem = MemoryMapBuilder::new()
+        .rope(rom)
+        .relay_cadence(0)
+        .tap(CHANNEL_DSKY, &mut alarm)
+        .build();
+    if randomize_erasable {
+        mem.randomize_erasable(&mut run_rng.fork(streams::ERASABLE_INIT));
+    }
+    let mut cpu = Cpu::new(mem);
+    cpu.reset();
+
+    let duration = EmuTime::mcts_in_millis(cfg.duration_ms as u64) as usize;
+    let (mut erasable_flips, mut register_flips) = (0, 0);
+    while cpu.total_cycles < duration {
+        let dt = cpu.step_cycles() as f64 * MCT_SECONDS;
+
+        if rng.chance(cfg.erasable_flips_per_s * dt) {
+            let bank = rng.below(8) as usize;
+            let offset = rng.below(256) as usize;
+            let bit = 1 << rng.below(15);
+            flip_erasable(&mut cpu, bank, offset, bit);
+            erasable_flips += 1;
+        }
+        if rng.chance(cfg.register_flips_per_s * dt) {
+            let reg = rng.below(UPSET_REGISTERS as u64) as usize;
+            let bit = 1 << rng.below(16);
+            let word = cpu.read(reg);
+            cpu.write(reg, word ^ bit);
+            register_flips += 1;
+        }
+    }
+
+    let restarts = cpu.restart_count();
+    let last_cause = cpu.last_restart().map(|c| format!("{:?}", c));
+    drop(cpu); // Releases the alarm tap
+
+    RunOutcome {
+        seed: run_rng.seed(),
+        erasable_flips,
+        register_flips,
+        restarts,
+        last_cause: last_cause.unwrap_or_default(),
+        prog_alarm: alarm.prog_alarm,
+    }
+}
+
+/// Run every campaign iteration and write one CSV row per run. Each run
+/// draws from its own stream of the campaign seed, so any row can be
+/// reproduced alone from the seed it records.
+pub fn run(
+    rom: &[[u16; 1024]; 36],
+    cfg: &CampaignConfig,
+    rng: &Rng,
+    randomize_erasable: bool,
+) -> Result<(), String> {
+    let campaign_rng = cfg.seed.map(Rng::new).unwrap_or_else(|| rng.clone());
+    let mut out =
+        std::fs::File::create(&cfg.output).map_err(|e| format!("{}: {}", cfg.output, e))?;
+    let mut write_row = |row: String| writeln!(out, "{}", row).map_err(|e| e.to_string());
+
+    write_row(
+        "run,seed,erasable_flips,register_flips,restarts,last_cause,prog_alarm,outcome".to_string(),
+    )?;
+    for run in 0..cfg.runs {
+        let run_rng = campaign_rng.fork(run as u64);
+        let outcome = run_once(rom, cfg, &run_rng, randomize_erasable);
+        info!("Campaign run {}: {}", run, outcome.classification());
+        write_row(format!(
+            "{},{},{},{},{},{},{},{}",
+            run,
+            outcome.seed,
+            outcome.erasable_flips,
+            outcome.register_flips,
+            outcome.restarts,
+            outcome.last_cause,
+            outcome.prog_alarm,
+            outcome.classification()
+        ))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod campaign_tests {
+    use super::{flip_erasable, run_once, CampaignConfig};
+    use ragc_core::constants::timers::TIMER_1_ADDRESS;
+    use ragc_core::cpu::Cpu;
+    use ragc_core::memory::MemoryMapBuilder;
+    use ragc_core::rng::Rng;
+
+    #[test]
+    fn upsets_reach_the_timers() {
+        let mut cpu = Cpu::new(MemoryMapBuilder::new().build());
+        cpu.write(TIMER_1_ADDRESS, 0o1000);
+
+        flip_erasable(&mut cpu, 0, TIMER_1_ADDRESS, 0o4);
+        assert_eq!(cpu.read(TIMER_1_ADDRESS), 0o1004);
+    }
+
+    #[test]
+    fn runs_are_reproducible_from_seed() {
+        static ROPE: [[u16; 1024]; 36] = [[0; 1024]; 36];
+        let cfg = CampaignConfig {
+            seed: None,
+            runs: 1,
+            duration_ms: 50,
+            erasable_flips_per_s: 200.0,
+            register_flips_per_s: 0.0,
+            output: String::new(),
+        };
+
+        let first = run_once(&ROPE, &cfg, &Rng::new(7), true);
+        let second = run_once(&ROPE, &cfg, &Rng::new(7), true);
+        assert!(first.erasable_flips > 0);
+        assert_eq!(first.erasable_flips, second.erasable_flips);
+        assert_eq!(first.restarts, second.restarts);
+    }
+}
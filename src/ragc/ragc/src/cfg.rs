@@ -0,0 +1,347 @@
+use std::collections::BTreeSet;
+use std::io::Write;
+
+use serde::Serialize;
+
+use crate::listing::{decode_bank, Location, Rope, Symbols};
+use ragc_core::constants::{STORAGE_SEGMENTS, STORAGE_SEGMENT_SIZE};
+use ragc_core::disasm::{Disassembled, Operand};
+use ragc_core::instructions::Mnemonic;
+use ragc_core::memory::rom::ReadOnlyMemory;
+
+/// How control reaches a successor
+#[derive(Clone, Copy, PartialEq, Debug, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EdgeKind {
+    Next,     // Falls through, or continues after a call returns
+    Jump,     // TCF
+    Call,     // TC
+    Branch,   // BZF/BZMF taken
+    Skip,     // CCS skips
+    Dispatch, // INDEXed jump into a table of TC/TCF
+}
+
+#[derive(Debug, Serialize)]
+pub struct Block {
+    pub start: String,
+    pub end: String,
+    pub name: Option<String>,
+    pub words: usize,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Edge {
+    pub from: String,
+    pub to: String,
+    pub kind: EdgeKind,
+}
+
+/// Basic blocks and edges of one fixed bank. Edges may leave the bank.
+#[derive(Debug, Serialize)]
+pub struct BankGraph {
+    pub bank: usize,
+    pub blocks: Vec<Block>,
+    pub edges: Vec<Edge>,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum GraphFormat {
+    Dot,
+    Json,
+}
+
+impl std::str::FromStr for GraphFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "dot" => Ok(GraphFormat::Dot),
+            "json" => Ok(GraphFormat::Json),
+            _ => Err(format!("Unknown graph format: {}", s)),
+        }
+    }
+}
+
+/// Successors of one instruction, and whether it ends its block
+fn successors(
+    insts: &[(u16, Option<Disassembled>)],
+    bank: usize,
+    offset: usize,
+) -> (Vec<(Location, EdgeKind)>, bool) {
+    let inst = match insts[offset].1 {
+        Some(inst) => inst,
+        None => return (Vec::new(), false),
+    };
+    let next = |n: usize| {
+        Some(offset + n)
+            .filter(|o| *o < STORAGE_SEGMENT_SIZE)
+            .map(|o| Location::Fixed(bank, o))
+    };
+    let target = match inst.operand {
+        Operand::Memory(k) => Location::resolve(k, bank),
+        _ => None,
+    };
+    let fixed_target = target.filter(|t| matches!(t, Location::Fixed(..)));
+
+    // INDEX followed by a jump to the next word selects from a jump table
+    let indexed = offset > 0
+        && matches!(insts[offset - 1].1, Some(prev) if prev.mnem == Mnemonic::INDEX)
+        && fixed_target == next(1);
+    if indexed && matches!(inst.mnem, Mnemonic::TC | Mnemonic::TCF) {
+        let table = insts[offset + 1..]
+            .iter()
+            .take_while(|(_, entry)| {
+                matches!(entry, Some(e) if matches!(e.mnem, Mnemonic::TC | Mnemonic::TCF))
+            })
+            .count();
+        let edges = (1..=table)
+            .filter_map(|n| next(n).map(|loc| (loc, EdgeKind::Dispatch)))
+            .collect();
+        return (edges, true);
+    }
+
+    let mut edges = Vec::new();
+    let ends = match inst.mnem {
+        Mnemonic::TCF => {
+            edges.extend(fixed_target.map(|t| (t, EdgeKind::Jump)));
+            true
+        }
+        Mnemonic::TC => {
+            // TC to erasable is a return (TC Q) or indirect transfer
+            if let Some(t) = fixed_target {
+                edges.push((t, EdgeKind::Call));
+                edges.extend(next(1).map(|n| (n, EdgeKind::Next)));
+            }
+            true
+        }
+        Mnemonic::BZF | Mnemonic::BZMF => {
+            edges.extend(fixed_target.map(|t| (t, EdgeKind::Branch)));
+            edges.extend(next(1).map(|n| (n, EdgeKind::Next)));
+            true
+        }
+        Mnemonic::CCS => {
+            edges.extend(next(1).map(|n| (n, EdgeKind::Next)));
+            edges.extend((2..=4).filter_map(|n| next(n).map(|loc| (loc, EdgeKind::Skip))));
+            true
+        }
+        Mnemonic::RESUME => true,
+        _ => false,
+    };
+    (edges, ends)
+}
+
+/// Split a fixed bank into basic blocks
+pub fn bank_graph(rom: &ReadOnlyMemory, bank: usize, symbols: &Symbols) -> BankGraph {
+    let insts = decode_bank(rom, bank);
+    let succs: Vec<_> = (0..insts.len())
+        .map(|offset| successors(&insts, bank, offset))
+        .collect();
+
+    let mut leaders = BTreeSet::new();
+    leaders.insert(0);
+    for (offset, (edges, ends)) in succs.iter().enumerate() {
+        if *ends && offset + 1 < insts.len() {
+            leaders.insert(offset + 1);
+        }
+        for (target, _) in edges.iter() {
+            if let Location::Fixed(b, o) = *target {
+                if b == bank {
+                    leaders.insert(o);
+                }
+            }
+        }
+    }
+    for offset in 0..insts.len() {
+        if symbols.get(&Location::Fixed(bank, offset)).is_some() {
+            leaders.insert(offset);
+        }
+    }
+
+    let starts: Vec<usize> = leaders.into_iter().collect();
+    let mut blocks = Vec::new();
+    let mut edges = Vec::new();
+    for (idx, start) in starts.iter().enumerate() {
+        let end = starts.get(idx + 1).copied().unwrap_or(insts.len()) - 1;
+        let from = Location::Fixed(bank, *start);
+        blocks.push(Block {
+            start: from.to_string(),
+            end: Location::Fixed(bank, end).to_string(),
+            name: symbols.get(&from).map(String::from),
+            words: end - start + 1,
+        });
+
+        let (out, ends) = &succs[end];
+        let fall = (!*ends && end + 1 < insts.len())
+            .then(|| (Location::Fixed(bank, end + 1), EdgeKind::Next));
+        for (to, kind) in out.iter().copied().chain(fall) {
+            edges.push(Edge {
+                from: from.to_string(),
+                to: to.to_string(),
+                kind,
+            });
+        }
+    }
+    BankGraph {
+        bank,
+        blocks,
+        edges,
+    }
+}
+
+fn write_dot(graph: &BankGraph, out: &mut dyn Write) -> std::io::Result<()> {
+    writeln!(out, "digraph bank{:02o} {{", graph.bank)?;
+    writeln!(out, "  node [shape=box fontname=monospace];")?;
+    for block in graph.blocks.iter() {
+        let name = block.name.as_deref().unwrap_or_default();
+        writeln!(
+            out,
+            "  \"{}\" [label=\"{} {}\\n{} words\"];",
+            block.start, block.start, name, block.words
+        )?;
+    }
+    for edge in graph.edges.iter() {
+        let kind = format!("{:?}", edge.kind).to_lowercase();
+        writeln!(
+            out,
+            "  \"{}\" -> \"{}\" [label={}];",
+            edge.from, edge.to, kind
+        )?;
+    }
+    writeln!(out, "}}")
+}
+
+/// Write one graph file per fixed bank into `dir`
+pub fn export(
+    rope: &Rope,
+    symbols: &Symbols,
+    format: GraphFormat,
+    dir: &str,
+) -> Result<(), String> {
+    std::fs::create_dir_all(dir).map_err(|e| format!("{}: {}", dir, e))?;
+    let rom = ReadOnlyMemory::new(rope);
+    for bank in 0..STORAGE_SEGMENTS {
+        let graph = bank_graph(&rom, bank, symbols);
+        let ext = match format {
+            GraphFormat::Dot => "dot",
+            GraphFormat::Json => "json",
+        };
+        let path = std::path::Path::new(dir).join(format!("bank{:02o}.{}", bank, ext));
+        let mut file = std::io::BufWriter::new(
+            std::fs::File::create(&path).map_err(|e| format!("{}: {}", path.display(), e))?,
+        );
+        match format {
+            GraphFormat::Dot => write_dot(&graph, &mut file).map_err(|e| e.to_string())?,
+            GraphFormat::Json => {
+                serde_json::to_writer_pretty(&mut file, &graph).map_err(|e| e.to_string())?
+            }
+        }
+    }
+    Ok(())
+}
+
+/// The `cfg` subcommand and its options
+pub fn subcommand<'a, 'b>() -> clap::App<'a, 'b> {
+    clap::SubCommand::with_name("cfg")
+        .about("Export per-bank control-flow graphs of a bundled ROM")
+        .arg(
+            clap::Arg::with_name("rom")
+                .required(true)
+                .possible_values(&crate::ropes::ROM_NAMES)
+                .help("ROM to analyse"),
+        )
+        .arg(
+            clap::Arg::with_name("format")
+                .long("format")
+                .takes_value(true)
+                .possible_values(&["dot", "json"])
+                .default_value("dot")
+                .help("Graphviz DOT or JSON"),
+        )
+        .arg(
+            clap::Arg::with_name("out")
+                .long("out")
+                .takes_value(true)
+                .default_value("cfg")
+                .help("Directory for the bankNN files"),
+        )
+        .arg(
+            clap::Arg::with_name("symbols")
+                .long("symbols")
+                .takes_value(true)
+                .help("Symbol names, one `NAME ADDRESS` per line"),
+        )
+}
+
+/// Writes control-flow graphs of a ROM, one file per bank
+pub fn command(args: &clap::ArgMatches) -> Result<(), String> {
+    let rope = crate::ropes::rope_by_name(args.value_of("rom").unwrap_or_default())
+        .ok_or("Invalid ROM")?;
+    let symbols = crate::load_symbols(args.value_of("symbols"))?;
+    let format = args.value_of("format").unwrap_or_default().parse()?;
+    export(
+        rope,
+        &symbols,
+        format,
+        args.value_of("out").unwrap_or_default(),
+    )
+}
+
+#[cfg(test)]
+mod cfg_tests {
+    use super::{bank_graph, EdgeKind};
+    use crate::listing::{Rope, Symbols};
+    use ragc_core::memory::rom::{encode_word, ReadOnlyMemory, BANK_MAPPING};
+
+    #[test]
+    fn splits_blocks_at_branches_and_dispatch_tables() {
+        let mut rope: Box<Rope> = Box::new([[encode_word(0o30000); 1024]; 36]); // CA 0
+        let code = [
+            0o30000, // 4000 CA 0
+            0o04010, // 4001 TC 4010 (call)
+            0o50000, // 4002 INDEX 0
+            0o14004, // 4003 TCF 4004 (dispatch)
+            0o14000, // 4004 TCF 4000
+            0o14010, // 4005 TCF 4010
+            0o30000, // 4006 CA 0
+            0o00002, // 4007 TC Q (return)
+        ];
+        for (offset, word) in code.iter().enumerate() {
+            rope[BANK_MAPPING[2]][offset] = encode_word(*word);
+        }
+        let rom = ReadOnlyMemory::new(&rope);
+        let graph = bank_graph(&rom, 2, &Symbols::default());
+
+        let starts: Vec<&str> = graph
+            .blocks
+            .iter()
+            .take(5)
+            .map(|b| b.start.as_str())
+            .collect();
+        assert_eq!(starts, ["4000", "4002", "4004", "4005", "4006"]);
+
+        let from = |start: &str| -> Vec<(String, EdgeKind)> {
+            graph
+                .edges
+                .iter()
+                .filter(|e| e.from == start)
+                .map(|e| (e.to.clone(), e.kind))
+                .collect()
+        };
+        assert_eq!(
+            from("4000"),
+            [
+                ("4010".into(), EdgeKind::Call),
+                ("4002".into(), EdgeKind::Next)
+            ]
+        );
+        assert_eq!(
+            from("4002"),
+            [
+                ("4004".into(), EdgeKind::Dispatch),
+                ("4005".into(), EdgeKind::Dispatch)
+            ]
+        );
+        assert_eq!(from("4004"), [("4000".into(), EdgeKind::Jump)]);
+        assert!(from("4006").is_empty());
+    }
+}
@@ -0,0 +1,65 @@
+use std::io::Write;
+
+use ragc_core::memory::channels::{channel_bits, channel_spec, CHANNELS};
+
+/// Current value of every mapped channel, in channel order
+pub type ChannelValues = Vec<(usize, u16)>;
+
+/// Read every channel in the map without disturbing peripherals
+pub fn capture(mem: &ragc_core::memory::MemoryMap) -> ChannelValues {
+    CHANNELS
+        .iter()
+        .map(|spec| (spec.channel, mem.peek_channel(spec.channel)))
+        .collect()
+}
+
+/// Names of the set bits of a channel value; multi-bit fields show as NAME=value
+pub fn annotate(channel: usize, value: u16) -> Vec<String> {
+    channel_bits(channel)
+        .iter()
+        .filter(|field| value & field.mask != 0)
+        .map(|field| match field.mask.count_ones() {
+            1 => field.name.to_string(),
+            _ => format!(
+                "{}={:o}",
+                field.name,
+                (value & field.mask) >> field.mask.trailing_zeros()
+            ),
+        })
+        .collect()
+}
+
+/// One line per channel: number, name, octal value and annotated bits
+pub fn write_channels(values: &[(usize, u16)], out: &mut dyn Write) -> std::io::Result<()> {
+    for &(channel, value) in values.iter() {
+        let name = channel_spec(channel).map_or("", |spec| spec.name);
+        let line = format!(
+            "{:03o} {:<9} {:06o}  {}",
+            channel,
+            name,
+            value,
+            annotate(channel, value).join(", ")
+        );
+        writeln!(out, "{}", line.trim_end())?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod channels_tests {
+    use super::write_channels;
+
+    #[test]
+    fn annotates_set_bits() {
+        let values = [(0o11, 0o10110), (0o13, 0o1003), (0o14, 0o40000)];
+        let mut out = Vec::new();
+        write_channels(&values, &mut out).unwrap();
+        let text = String::from_utf8(out).unwrap();
+        assert_eq!(
+            text,
+            "011 DSALMOUT  010110  TEMP, OPR ERR, ENGINE ON\n\
+             013 CHAN13    001003  RADAR SELECT=3, TEST ALARMS\n\
+             014 CHAN14    040000\n"
+        );
+    }
+}
@@ -0,0 +1,698 @@
+use std::collections::BTreeMap;
+use std::io::Write;
+
+use log::{info, warn};
+
+use ragc_core::constants::ports::{CHANNEL_DSALMOUT, CHANNEL_DSKY};
+use ragc_core::cpu::Cpu;
+use ragc_core::frame::FrameIo;
+use ragc_core::memory::channels::{channel_spec, Discrete};
+use ragc_core::memory::mods::EmuTime;
+use ragc_core::memory::rom::RomInfo;
+use ragc_core::memory::MemoryMapBuilder;
+
+use crate::ropes::{self, rom_info_by_name, rope_by_name, ROM_NAMES};
+
+/// Frame length, the same 10 ms as the other frame-driven runs
+const FRAME_MS: u32 = 10;
+
+/// Frames between keypresses, long enough for PINBALL to take each key
+const KEY_FRAMES: u32 = 20;
+
+/// A selected program has settled once the display holds still this long
+const SETTLE_MS: u32 = 1000;
+
+/// Longest a program selection may take to settle
+const SELECT_TIMEOUT_MS: u32 = 20000;
+
+/// One step of a scenario
+#[derive(Clone, Copy, Debug)]
+pub enum Step {
+    Wait(u32),                // Milliseconds
+    Keys(&'static str),       // V, N, E, C (CLR), R (RSET), K (KEY REL) and digits
+    Discrete(Discrete, bool), // Assert or release an input discrete
+    WaitProgram(u8, u32),     // Until the program is entered, at most this many ms
+    Program(u8),              // V37E NNE, then run until it is running and settled
+}
+
+/// Fresh start, lamp test, then monitor V16N65 (clock time)
+pub const DEFAULT_SCENARIO: [Step; 5] = [
+    Step::Wait(4000),
+    Step::Keys("V35E"),
+    Step::Wait(8000),
+    Step::Keys("V16N65E"),
+    Step::Wait(4000),
+];
+
+/// Start P63 (braking phase), then hold ABORT until the LM software
+/// answers with P70. `abort-stage` uses ABORT STAGE and expects P71.
+const fn abort_scenario(button: Discrete, program: u8) -> [Step; 5] {
+    [
+        Step::Wait(4000),
+        Step::Program(63),
+        Step::Discrete(button, true),
+        Step::WaitProgram(program, 4000),
+        Step::Discrete(button, false),
+    ]
+}
+
+const ABORT_SCENARIO: [Step; 5] = abort_scenario(Discrete::Abort, 70);
+const ABORT_STAGE_SCENARIO: [Step; 5] = abort_scenario(Discrete::AbortStage, 71);
+
+/// Spare verb, then an 8 keyed into an octal machine address, each
+/// cleared with RSET; ends monitoring the clock to show the keyboard
+/// still takes commands
+const OPR_ERR_SCENARIO: [Step; 8] = [
+    Step::Wait(4000),
+    Step::Keys("V38E"),
+    Step::Wait(1000),
+    Step::Keys("R"),
+    Step::Keys("V21N01E8"),
+    Step::Wait(1000),
+    Step::Keys("RV34E"),
+    Step::Keys("V16N65E"),
+];
+
+/// Scenario names accepted by `compare --scenario`
+pub const SCENARIO_NAMES: [&str; 4] = ["default", "abort", "abort-stage", "opr-err"];
+
+/// Scenarios that drive LM hardware and programs, run only on LM ropes
+pub fn needs_lm(name: &str) -> bool {
+    matches!(name, "abort" | "abort-stage")
+}
+
+/// Look up a scenario by its `--scenario` name
+pub fn scenario(name: &str) -> Option<&'static [Step]> {
+    match name {
+        "default" => Some(&DEFAULT_SCENARIO),
+        "abort" => Some(&ABORT_SCENARIO),
+        "abort-stage" => Some(&ABORT_STAGE_SCENARIO),
+        "opr-err" => Some(&OPR_ERR_SCENARIO),
+        _ => None,
+    }
+}
+
+pub fn keycode(key: char) -> Option<u16> {
+    match key {
+        'V' => Some(0o21),
+        'N' => Some(0o37),
+        'E' => Some(0o34),
+        'C' => Some(0o36),
+        'R' => Some(0o22),
+        'K' => Some(0o31),
+        '+' => Some(0o32),
+        '-' => Some(0o33),
+        '0' => Some(0o20),
+        '1'..='9' => key.to_digit(10).map(|d| d as u16),
+        _ => None,
+    }
+}
+
+/// DSKY display contents rebuilt from channel 10 relay words
+#[derive(Clone, PartialEq)]
+struct Display {
+    digits: [char; 21], // PROG, VERB, NOUN, then R1-R3 five digits each
+    signs: [bool; 6],   // R1+, R1-, R2+, R2-, R3+, R3-
+    opr_err: bool,
+}
+
+impl Display {
+    fn new() -> Self {
+        Self {
+            digits: [' '; 21],
+            signs: [false; 6],
+            opr_err: false,
+        }
+    }
+
+    fn relay_digit(code: u16) -> char {
+        match code {
+            0o25 => '0',
+            0o03 => '1',
+            0o31 => '2',
+            0o33 => '3',
+            0o17 => '4',
+            0o36 => '5',
+            0o34 => '6',
+            0o23 => '7',
+            0o35 => '8',
+            0o37 => '9',
+            _ => ' ',
+        }
+    }
+
+    fn apply(&mut self, word: u16) {
+        let row = word >> 11;
+        let sign = word & 0o2000 != 0;
+        let c = Self::relay_digit((word >> 5) & 0o37);
+        let d = Self::relay_digit(word & 0o37);
+        // Digit positions driven by each relay row, and its sign bit if any
+        let (left, right, sign_idx) = match row {
+            11 => (Some(0), 1, None),
+            10 => (Some(2), 3, None),
+            9 => (Some(4), 5, None),
+            8 => (None, 6, None),
+            7 => (Some(7), 8, Some(0)),
+            6 => (Some(9), 10, Some(1)),
+            5 => (Some(11), 12, Some(2)),
+            4 => (Some(13), 14, Some(3)),
+            3 => (Some(15), 16, None),
+            2 => (Some(17), 18, Some(4)),
+            1 => (Some(19), 20, Some(5)),
+            _ => return,
+        };
+        if let Some(left) = left {
+            self.digits[left] = c;
+        }
+        self.digits[right] = d;
+        if let Some(idx) = sign_idx {
+            self.signs[idx] = sign;
+        }
+    }
+
+    /// Number shown in PROG, if both digits are lit
+    fn program(&self) -> Option<u8> {
+        let tens = self.digits[0].to_digit(10)?;
+        let units = self.digits[1].to_digit(10)?;
+        Some((tens * 10 + units) as u8)
+    }
+
+    fn sign(&self, reg: usize) -> char {
+        match (self.signs[2 * reg], self.signs[2 * reg + 1]) {
+            (true, false) => '+',
+            (false, true) => '-',
+            _ => ' ',
+        }
+    }
+
+    fn render(&self) -> String {
+        let field =
+            |range: std::ops::Range<usize>| -> String { self.digits[range].iter().collect() };
+        format!(
+            "P{} V{} N{} R1 {}{} R2 {}{} R3 {}{}{}",
+            field(0..2),
+            field(2..4),
+            field(4..6),
+            self.sign(0),
+            field(6..11),
+            self.sign(1),
+            field(11..16),
+            self.sign(2),
+            field(16..21),
+            if self.opr_err { " OPR ERR" } else { "" }
+        )
+    }
+}
+
+/// What one ROM did during a scenario
+pub struct RomRun {
+    pub name: String,
+    pub transcript: Vec<(u32, String)>, // Emulated ms, display after a change
+    pub writes: BTreeMap<usize, (u32, u16)>, // Channel: write count, last value
+    pub restarts: u32,
+    pub missed: Vec<u8>, // Programs waited for but never entered
+}
+
+/// Run a scenario headless against one rope. Programs are followed through
+/// MODREG when `rom_info` has its address, else through the PROG display.
+pub fn run_scenario(
+    name: &str,
+    rope: &[[u16; 1024]; 36],
+    rom_info: RomInfo,
+    scenario: &[Step],
+) -> RomRun {
+    let mem = MemoryMapBuilder::new()
+        .rope(rope)
+        .rom_info(rom_info)
+        .relay_cadence(0)
+        .build();
+    let mut cpu = Cpu::new(mem);
+    cpu.reset();
+    play(name, &mut cpu, scenario)
+}
+
+/// Run a scenario on a CPU that is already set up
+pub fn play(name: &str, cpu: &mut Cpu, scenario: &[Step]) -> RomRun {
+    let frame_mcts = EmuTime::mcts_in_millis(FRAME_MS as u64) as u32;
+    let mut io = FrameIo::new();
+    let mut display = Display::new();
+    let mut run = RomRun {
+        name: name.to_string(),
+        transcript: Vec::new(),
+        writes: BTreeMap::new(),
+        restarts: 0,
+        missed: Vec::new(),
+    };
+
+    let mut now_ms = 0;
+    // Runs one frame and returns the program it left running
+    let mut frame = |cpu: &mut Cpu, io: &mut FrameIo, run: &mut RomRun, now_ms: &mut u32| {
+        cpu.run_frame(io, frame_mcts);
+        *now_ms += FRAME_MS;
+        let before = display.clone();
+        for (channel, value) in io.channel_writes.iter() {
+            let entry = run.writes.entry(*channel).or_insert((0, 0));
+            *entry = (entry.0 + 1, *value);
+            match *channel {
+                CHANNEL_DSKY => display.apply(*value),
+                CHANNEL_DSALMOUT => display.opr_err = value & 0o100 != 0,
+                _ => {}
+            }
+        }
+        if display != before {
+            run.transcript.push((*now_ms, display.render()));
+        }
+        cpu.program().or_else(|| display.program())
+    };
+
+    for step in scenario.iter() {
+        let keys = match *step {
+            Step::Keys(keys) => keys.to_string(),
+            Step::Program(program) => format!("V37E{:02}E", program),
+            _ => String::new(),
+        };
+        for code in keys.chars().filter_map(keycode) {
+            io.press_key(code);
+            for _ in 0..KEY_FRAMES {
+                frame(cpu, &mut io, &mut run, &mut now_ms);
+            }
+        }
+
+        match *step {
+            Step::Wait(ms) => {
+                for _ in 0..ms / FRAME_MS {
+                    frame(cpu, &mut io, &mut run, &mut now_ms);
+                }
+            }
+            Step::Keys(_) => {}
+            Step::Program(program) => {
+                // Fast-forward until the program runs and the display is quiet
+                let mut quiet_ms = 0;
+                for _ in 0..SELECT_TIMEOUT_MS / FRAME_MS {
+                    let changes = run.transcript.len();
+                    let running = frame(cpu, &mut io, &mut run, &mut now_ms);
+                    quiet_ms = match running == Some(program) && run.transcript.len() == changes {
+                        true => quiet_ms + FRAME_MS,
+                        false => 0,
+                    };
+                    if quiet_ms >= SETTLE_MS {
+                        break;
+                    }
+                }
+                match quiet_ms >= SETTLE_MS {
+                    true => info!("{}: P{:02} settled at {} ms", name, program, now_ms),
+                    false => warn!("{}: P{:02} did not settle", name, program),
+                }
+            }
+            Step::WaitProgram(program, ms) => {
+                let frames = (0..ms / FRAME_MS)
+                    .map(|_| frame(cpu, &mut io, &mut run, &mut now_ms))
+                    .position(|running| running == Some(program));
+                if frames.is_none() {
+                    warn!("{}: P{:02} not entered within {} ms", name, program, ms);
+                    run.missed.push(program);
+                }
+            }
+            Step::Discrete(discrete, asserted) => {
+                info!("{}: {} {}", name, discrete.name(), asserted);
+                cpu.set_discrete(discrete, asserted);
+            }
+        }
+    }
+    run.restarts = cpu.restart_count();
+    info!("{}: {} display changes", name, run.transcript.len());
+    run
+}
+
+fn channel_name(channel: usize) -> String {
+    match channel_spec(channel) {
+        Some(spec) => spec.name.to_string(),
+        None => format!("{:o}", channel),
+    }
+}
+
+/// Side-by-side channel activity, then each ROM's display transcript
+pub fn write_report(runs: &[RomRun], out: &mut dyn Write) -> std::io::Result<()> {
+    writeln!(out, "# Channel activity (writes / last value)")?;
+    let mut header = format!("{:<10}", "channel");
+    for run in runs.iter() {
+        header.push_str(&format!(" {:>18}", run.name));
+    }
+    writeln!(out, "{}", header)?;
+
+    let mut channels: Vec<usize> = runs.iter().flat_map(|r| r.writes.keys().copied()).collect();
+    channels.sort_unstable();
+    channels.dedup();
+    for channel in channels {
+        let cells: Vec<Option<&(u32, u16)>> = runs.iter().map(|r| r.writes.get(&channel)).collect();
+        let mut line = format!("{:<10}", channel_name(channel));
+        for cell in cells.iter() {
+            match cell {
+                Some((count, last)) => line.push_str(&format!(" {:>10} / {:05o}", count, last)),
+                None => line.push_str(&format!(" {:>18}", "-")),
+            }
+        }
+        // Flag channels some ROMs never touch
+        if cells.iter().any(|c| c.is_none()) {
+            line.push_str("  *");
+        }
+        writeln!(out, "{}", line)?;
+    }
+
+    writeln!(out, "\n# Final display")?;
+    for run in runs.iter() {
+        let last = run.transcript.last().map(|(_, d)| d.as_str());
+        let missed: Vec<String> = run.missed.iter().map(|p| format!(" P{:02}", p)).collect();
+        writeln!(
+            out,
+            "{:<12} {} (restarts: {}{})",
+            run.name,
+            last.unwrap_or("(blank)"),
+            run.restarts,
+            match missed.is_empty() {
+                true => String::new(),
+                false => format!(", never entered{}", missed.concat()),
+            }
+        )?;
+    }
+
+    for run in runs.iter() {
+        writeln!(out, "\n# {} display transcript", run.name)?;
+        for (ms, display) in run.transcript.iter() {
+            writeln!(out, "{:>7} ms  {}", ms, display)?;
+        }
+    }
+    Ok(())
+}
+
+/// The `compare` subcommand and its options
+pub fn subcommand<'a, 'b>() -> clap::App<'a, 'b> {
+    clap::SubCommand::with_name("compare")
+        .about("Run a scripted scenario on every bundled ROM and compare")
+        .arg(
+            clap::Arg::with_name("scenario")
+                .long("scenario")
+                .takes_value(true)
+                .possible_values(&SCENARIO_NAMES)
+                .default_value("default")
+                .help("Fresh start, V35 and V16N65; or a P63 abort (LM: P70/P71)"),
+        )
+        .arg(
+            clap::Arg::with_name("out")
+                .long("out")
+                .takes_value(true)
+                .help("Report file (default: stdout)"),
+        )
+}
+
+/// Runs the comparison scenario on each bundled ROM and reports
+pub fn command(args: &clap::ArgMatches) -> Result<(), String> {
+    let scenario_name = args.value_of("scenario").unwrap_or_default();
+    let scenario = scenario(scenario_name).ok_or("Invalid scenario")?;
+    // Abort scenarios exercise LM hardware the CM and test ropes don't have
+    let runs: Vec<RomRun> = ROM_NAMES
+        .iter()
+        .filter(|name| !needs_lm(scenario_name) || ropes::is_lm_rom(name))
+        .filter_map(|name| rope_by_name(name).map(|rope| (name, rope)))
+        .map(|(name, rope)| {
+            let rom_info = rom_info_by_name(name).unwrap_or_default();
+            run_scenario(name, rope, rom_info, scenario)
+        })
+        .collect();
+    let mut out: Box<dyn std::io::Write> = match args.value_of("out") {
+        Some(path) => {
+            Box::new(std::fs::File::create(path).map_err(|e| format!("{}: {}", path, e))?)
+        }
+        None => Box::new(std::io::stdout()),
+    };
+    write_report(&runs, &mut out).map_err(|e| e.to_string())?;
+    match runs.iter().find(|run| !run.missed.is_empty()) {
+        Some(run) => Err(format!("{}: P{:02} never entered", run.name, run.missed[0])),
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod compare_tests {
+    use super::{needs_lm, play, run_scenario, scenario, write_report, Display, RomRun, Step};
+    use ragc_core::constants::ports::{
+        CHAN30_ABORT, CHAN30_ABORT_STAGE, CHANNEL_CHAN30, CHANNEL_DSALMOUT,
+    };
+    use ragc_core::cpu::Cpu;
+    use ragc_core::memory::channels::Discrete;
+    use ragc_core::memory::rom::{RomInfo, RopeImage};
+    use ragc_core::memory::{MemoryMap, MemoryMapBuilder};
+    use ragc_testutils::rope_image;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn decodes_relay_words_and_reports_differences() {
+        let mut display = Display::new();
+        display.apply((10 << 11) | (0o03 << 5) | 0o34); // VERB 16
+        display.apply((7 << 11) | 0o2000 | (0o25 << 5) | 0o33); // R1 +, digits 2-3
+        assert_eq!(
+            display.render(),
+            "P   V16 N   R1 + 03   R2        R3       "
+        );
+
+        let mut writes = BTreeMap::new();
+        writes.insert(0o10, (3, 0o12345));
+        let runs = [
+            RomRun {
+                name: "A".into(),
+                transcript: vec![(10, display.render())],
+                writes,
+                restarts: 0,
+                missed: Vec::new(),
+            },
+            RomRun {
+                name: "B".into(),
+                transcript: Vec::new(),
+                writes: BTreeMap::new(),
+                restarts: 1,
+                missed: vec![70],
+            },
+        ];
+        let mut out = Vec::new();
+        write_report(&runs, &mut out).unwrap();
+        let text = String::from_utf8(out).unwrap();
+        assert!(text.contains("OUT0                3 / 12345                  -  *"));
+        assert!(text.contains("B            (blank) (restarts: 1, never entered P70)"));
+    }
+
+    /// A stand-in for the LM software, so the scenario plumbing can be
+    /// tested without a rope: the main loop copies channel 30 to 101, and
+    /// KEYRUPT1 lights OPR ERR for an 8 or 9, which no octal verb takes,
+    /// and puts it out again on RSET. T5RUPT keeps RUPT LOCK quiet.
+    fn panel() -> Box<RopeImage> {
+        let mut program = [0o50017; 0o136]; // RESUME in every vector
+        let mut at = |addr: usize, words: &[u16]| {
+            program[addr - 0o4000..addr - 0o4000 + words.len()].copy_from_slice(words)
+        };
+        at(0o4000, &[0o14060]); // TCF START
+        at(0o4010, &[0o14070]); // T5RUPT: TCF TICK
+        at(0o4024, &[0o14100]); // KEYRUPT1: TCF KEYS
+        at(
+            0o4060,
+            &[
+                0o34130, // 4060 START: CA 4130
+                0o56030, // 4061 XCH TIME5
+                0o00003, // 4062 RELINT
+                0o30067, // 4063 LOOP: CA NEWJOB
+                0o00006, // 4064 EXTEND
+                0o00030, // 4065 READ 30
+                0o54101, // 4066 TS 101
+                0o14063, // 4067 TCF LOOP
+                0o56010, // 4070 TICK: XCH ARUPT
+                0o34130, // 4071 CA 4130
+                0o56030, // 4072 XCH TIME5
+                0o30010, // 4073 CA ARUPT
+                0o50017, // 4074 RESUME
+            ],
+        );
+        at(
+            0o4100,
+            &[
+                0o56010, // 4100 KEYS: XCH ARUPT
+                0o00006, // 4101 EXTEND
+                0o00015, // 4102 READ 15
+                0o54100, // 4103 TS 100
+                0o64131, // 4104 AD 4131
+                0o00006, // 4105 EXTEND
+                0o14116, // 4106 BZF CLEAR
+                0o30100, // 4107 CA 100
+                0o74132, // 4110 MASK 4132
+                0o64133, // 4111 AD 4133
+                0o00006, // 4112 EXTEND
+                0o14122, // 4113 BZF LIGHT
+                0o30010, // 4114 DONE: CA ARUPT
+                0o50017, // 4115 RESUME
+                0o34134, // 4116 CLEAR: CA 4134
+                0o00006, // 4117 EXTEND
+                0o01011, // 4120 WRITE DSALMOUT
+                0o14114, // 4121 TCF DONE
+                0o34135, // 4122 LIGHT: CA 4135
+                0o00006, // 4123 EXTEND
+                0o01011, // 4124 WRITE DSALMOUT
+                0o14114, // 4125 TCF DONE
+            ],
+        );
+        at(
+            0o4130,
+            &[
+                0o37776, // 4130 TIME5 preset: T5RUPT in 20 ms
+                0o77755, // 4131 -RSET
+                0o77770, // 4132 all but the low three bits
+                0o77767, // 4133 -10, the code of 8 and, masked, of 9
+                0o00000, // 4134 lamps off
+                0o00100, // 4135 OPR ERR
+            ],
+        );
+        rope_image(0o4000, &program)
+    }
+
+    #[test]
+    fn stand_in_sees_abort_discretes_on_channel_30() {
+        let rope = panel();
+        let mut cpu = Cpu::new(MemoryMapBuilder::new().rope(&rope).build());
+        cpu.reset();
+        cpu.memory_mut().set_channel_input(CHANNEL_CHAN30, 0o77777);
+        for (discrete, bit) in [
+            (Discrete::Abort, CHAN30_ABORT),
+            (Discrete::AbortStage, CHAN30_ABORT_STAGE),
+        ] {
+            let pressed = [
+                Step::Wait(50),
+                Step::Discrete(discrete, true),
+                Step::Wait(50),
+            ];
+            play("panel", &mut cpu, &pressed);
+            assert_eq!(cpu.read(0o101), 0o77777 & !bit, "{}", discrete.name());
+
+            let released = [Step::Discrete(discrete, false), Step::Wait(50)];
+            let run = play("panel", &mut cpu, &released);
+            assert_eq!(cpu.read(0o101), 0o77777, "{}", discrete.name());
+            assert_eq!(run.restarts, 0);
+        }
+    }
+
+    #[test]
+    fn stand_in_lights_opr_err_on_a_bad_verb() {
+        let rope = panel();
+        let mut cpu = Cpu::new(MemoryMapBuilder::new().rope(&rope).build());
+        cpu.reset();
+        let scenario = [
+            Step::Keys("V5E"),
+            Step::Wait(100),
+            Step::Keys("V8E"),
+            Step::Wait(100),
+            Step::Keys("R"),
+            Step::Wait(100),
+        ];
+        let run = play("panel", &mut cpu, &scenario);
+        let lamps: Vec<bool> = run
+            .transcript
+            .iter()
+            .map(|(_, d)| d.ends_with("OPR ERR"))
+            .collect();
+        assert_eq!(lamps, [true, false], "{:?}", run.transcript);
+        assert_eq!(run.writes[&CHANNEL_DSALMOUT], (2, 0));
+        assert_eq!(run.restarts, 0);
+    }
+
+    #[test]
+    fn records_programs_never_entered() {
+        let mut cpu = Cpu::new(MemoryMap::new_blank());
+        cpu.reset();
+        let run = play("blank", &mut cpu, &[Step::WaitProgram(70, 100)]);
+        assert_eq!(run.missed, [70]);
+        assert!(needs_lm("abort") && needs_lm("abort-stage") && !needs_lm("default"));
+    }
+
+    /// Run an abort scenario on Luminary; it must end in its abort program
+    fn abort_enters(name: &str, program: u8) {
+        let rope = ragc_binaries::LUMINARY99_ROPE;
+        let run = run_scenario(
+            "luminary99",
+            rope,
+            RomInfo::BLOCK_II,
+            scenario(name).unwrap(),
+        );
+        assert!(
+            run.missed.is_empty(),
+            "{}: P{:02} never entered",
+            name,
+            program
+        );
+        let (_, last) = run.transcript.last().unwrap();
+        assert!(
+            last.starts_with(&format!("P{:02}", program)),
+            "{}: ended on {}",
+            name,
+            last
+        );
+        assert_eq!(run.restarts, 0);
+    }
+
+    #[test]
+    #[ignore = "needs the LUMINARY99.bin rope image"]
+    fn abort_enters_p70() {
+        abort_enters("abort", 70);
+    }
+
+    #[test]
+    #[ignore = "needs the LUMINARY99.bin rope image"]
+    fn abort_stage_enters_p71() {
+        abort_enters("abort-stage", 71);
+    }
+
+    /// Key a bad entry into Luminary, RSET, then monitor the clock. OPR ERR
+    /// must light and go out again, and V16N65 must still be taken.
+    fn opr_err_and_recover(keys: &'static str) {
+        let scenario = [
+            Step::Wait(4000),
+            Step::Keys(keys),
+            Step::Wait(1000),
+            Step::Keys("R"),
+            Step::Wait(500),
+            Step::Keys("V34EV16N65E"),
+            Step::Wait(2000),
+        ];
+        let rope = ragc_binaries::LUMINARY99_ROPE;
+        let run = run_scenario("luminary99", rope, RomInfo::BLOCK_II, &scenario);
+        let lit = run
+            .transcript
+            .iter()
+            .position(|(_, d)| d.ends_with("OPR ERR"));
+        let lit = lit.unwrap_or_else(|| panic!("{}: OPR ERR never lit", keys));
+        assert!(
+            run.transcript[lit..]
+                .iter()
+                .any(|(_, d)| !d.ends_with("OPR ERR")),
+            "{}: OPR ERR not cleared by RSET",
+            keys
+        );
+        let (_, last) = run.transcript.last().unwrap();
+        assert!(last.contains("V16 N65"), "{}: ended on {}", keys, last);
+        assert!(!last.ends_with("OPR ERR"), "{}: ended on {}", keys, last);
+        assert_eq!(run.restarts, 0);
+    }
+
+    #[test]
+    #[ignore = "needs the LUMINARY99.bin rope image"]
+    fn spare_verb_lights_opr_err() {
+        opr_err_and_recover("V38E");
+    }
+
+    #[test]
+    #[ignore = "needs the LUMINARY99.bin rope image"]
+    fn octal_load_rejects_eight() {
+        opr_err_and_recover("V21N01E8");
+    }
+
+    #[test]
+    #[ignore = "needs the LUMINARY99.bin rope image"]
+    fn decimal_load_without_sign_lights_opr_err() {
+        opr_err_and_recover("V21N36E1E");
+    }
+}
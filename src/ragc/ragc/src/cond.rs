@@ -0,0 +1,326 @@
+use ragc_core::cpu::Cpu;
+use ragc_core::symbols::{ErasableAddress, SymbolTable};
+
+use crate::watch::{resolve_address, Read};
+
+/// Condition expression shared by `--until`, timeline markers and console
+/// breakpoints, e.g. `E7,1553 == 0o31 && chan(0o11).bit(2)`.
+///
+/// - `E<bank>,<octal address>`, or an erasable symbol: the word as stored
+/// - `raw(..)`, `sp(..)`, `dp(..)`: read as in watch expressions
+/// - `chan(n)`: an I/O channel
+/// - `cycles` (MCTs since power on), `program` (major mode, -1 if unknown)
+/// - numbers: decimal, or octal with a `0o` prefix
+/// - `x.bit(n)`: bit n of x, counted from 1 as the AGC does
+///
+/// Operators from tightest: unary `-` and `!`, `&`, comparisons
+/// (`==`, `!=`, `<`, `<=`, `>`, `>=`), `&&`, `||`. Any non-zero value is
+/// true.
+#[derive(Clone, Debug)]
+pub struct Condition {
+    pub text: String,
+    root: Node,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Op {
+    Or,
+    And,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Mask,
+}
+
+#[derive(Clone, Debug)]
+enum Node {
+    Number(i64),
+    Cycles,
+    Program,
+    Word(Read, ErasableAddress),
+    Channel(usize),
+    Bit(Box<Node>, u32),
+    Not(Box<Node>),
+    Neg(Box<Node>),
+    Binary(Op, Box<Node>, Box<Node>),
+}
+
+impl Node {
+    fn eval(&self, cpu: &Cpu) -> i64 {
+        match self {
+            Node::Number(n) => *n,
+            Node::Cycles => cpu.total_cycles as i64,
+            Node::Program => cpu.program().map_or(-1, |p| p as i64),
+            Node::Word(read, addr) => read.read(cpu.memory(), *addr),
+            Node::Channel(channel) => cpu.memory().peek_channel(*channel) as i64,
+            Node::Bit(node, bit) => (node.eval(cpu) >> (bit - 1)) & 1,
+            Node::Not(node) => (node.eval(cpu) == 0) as i64,
+            Node::Neg(node) => -node.eval(cpu),
+            Node::Binary(Op::Or, a, b) => (a.eval(cpu) != 0 || b.eval(cpu) != 0) as i64,
+            Node::Binary(Op::And, a, b) => (a.eval(cpu) != 0 && b.eval(cpu) != 0) as i64,
+            Node::Binary(op, a, b) => {
+                let (a, b) = (a.eval(cpu), b.eval(cpu));
+                match op {
+                    Op::Eq => (a == b) as i64,
+                    Op::Ne => (a != b) as i64,
+                    Op::Lt => (a < b) as i64,
+                    Op::Le => (a <= b) as i64,
+                    Op::Gt => (a > b) as i64,
+                    Op::Ge => (a >= b) as i64,
+                    _ => a & b,
+                }
+            }
+        }
+    }
+}
+
+/// Recursive descent over the expression text
+struct Parser<'a, S: SymbolTable + ?Sized> {
+    text: &'a str,
+    pos: usize,
+    symbols: &'a S,
+}
+
+impl<'a, S: SymbolTable + ?Sized> Parser<'a, S> {
+    fn rest(&self) -> &'a str {
+        &self.text[self.pos..]
+    }
+
+    fn error(&self, what: &str) -> String {
+        format!("{} at column {}: {}", what, self.pos + 1, self.text)
+    }
+
+    fn skip_space(&mut self) {
+        let rest = self.rest();
+        self.pos += rest.len() - rest.trim_start().len();
+    }
+
+    /// Consume `token` if it comes next
+    fn eat(&mut self, token: &str) -> bool {
+        self.skip_space();
+        match self.rest().starts_with(token) {
+            true => {
+                self.pos += token.len();
+                true
+            }
+            false => false,
+        }
+    }
+
+    fn expect(&mut self, token: &str) -> Result<(), String> {
+        match self.eat(token) {
+            true => Ok(()),
+            false => Err(self.error(&format!("Expected {}", token))),
+        }
+    }
+
+    /// Characters up to the first that fails `keep`
+    fn take_while(&mut self, keep: impl Fn(char) -> bool) -> &'a str {
+        self.skip_space();
+        let rest = self.rest();
+        let end = rest.find(|c| !keep(c)).unwrap_or(rest.len());
+        self.pos += end;
+        &rest[..end]
+    }
+
+    fn number(&mut self) -> Result<i64, String> {
+        let digits = self.take_while(|c| c.is_ascii_alphanumeric());
+        let value = match digits.strip_prefix("0o") {
+            Some(octal) => i64::from_str_radix(octal, 8),
+            None => digits.parse(),
+        };
+        value.map_err(|_| self.error("Invalid number"))
+    }
+
+    fn or(&mut self) -> Result<Node, String> {
+        let mut node = self.and()?;
+        while self.eat("||") {
+            node = Node::Binary(Op::Or, Box::new(node), Box::new(self.and()?));
+        }
+        Ok(node)
+    }
+
+    fn and(&mut self) -> Result<Node, String> {
+        let mut node = self.compare()?;
+        while self.eat("&&") {
+            node = Node::Binary(Op::And, Box::new(node), Box::new(self.compare()?));
+        }
+        Ok(node)
+    }
+
+    fn compare(&mut self) -> Result<Node, String> {
+        let node = self.mask()?;
+        // Two-character operators first, so `<=` isn't read as `<`
+        let ops = [
+            ("==", Op::Eq),
+            ("!=", Op::Ne),
+            ("<=", Op::Le),
+            (">=", Op::Ge),
+            ("<", Op::Lt),
+            (">", Op::Gt),
+        ];
+        match ops.iter().find(|(token, _)| self.eat(token)) {
+            Some((_, op)) => Ok(Node::Binary(*op, Box::new(node), Box::new(self.mask()?))),
+            None => Ok(node),
+        }
+    }
+
+    fn mask(&mut self) -> Result<Node, String> {
+        let mut node = self.unary()?;
+        loop {
+            self.skip_space();
+            if !self.rest().starts_with('&') || self.rest().starts_with("&&") {
+                return Ok(node);
+            }
+            self.pos += 1;
+            node = Node::Binary(Op::Mask, Box::new(node), Box::new(self.unary()?));
+        }
+    }
+
+    fn unary(&mut self) -> Result<Node, String> {
+        if self.eat("!") {
+            return Ok(Node::Not(Box::new(self.unary()?)));
+        }
+        if self.eat("-") {
+            return Ok(Node::Neg(Box::new(self.unary()?)));
+        }
+        let mut node = self.primary()?;
+        while self.eat(".bit(") {
+            let bit = self.number()?;
+            if !(1..=15).contains(&bit) {
+                return Err(self.error("Bits run from 1 to 15"));
+            }
+            self.expect(")")?;
+            node = Node::Bit(Box::new(node), bit as u32);
+        }
+        Ok(node)
+    }
+
+    fn primary(&mut self) -> Result<Node, String> {
+        if self.eat("(") {
+            let node = self.or()?;
+            self.expect(")")?;
+            return Ok(node);
+        }
+        self.skip_space();
+        if self.rest().starts_with(|c: char| c.is_ascii_digit()) {
+            return Ok(Node::Number(self.number()?));
+        }
+
+        let start = self.pos;
+        let name = self.take_while(|c| c.is_ascii_alphanumeric() || c == '_');
+        if self.eat("(") {
+            // Operands may be symbols with characters of their own, e.g. `/`
+            let end = self
+                .rest()
+                .find(')')
+                .ok_or_else(|| self.error("Expected )"))?;
+            let operand = &self.rest()[..end];
+            let node = match name {
+                "chan" => {
+                    let channel = Parser {
+                        text: operand,
+                        pos: 0,
+                        symbols: self.symbols,
+                    }
+                    .number()?;
+                    if !(0..=0o377).contains(&channel) {
+                        return Err(self.error("No such channel"));
+                    }
+                    Node::Channel(channel as usize)
+                }
+                _ => {
+                    let read = Read::parse(name)?;
+                    Node::Word(read, resolve_address(operand, read, self.symbols)?)
+                }
+            };
+            self.pos += end + 1;
+            return Ok(node);
+        }
+        match name {
+            "" => Err(self.error("Expected a value")),
+            "cycles" => Ok(Node::Cycles),
+            "program" => Ok(Node::Program),
+            _ => {
+                // `E<bank>,<address>` takes the comma and the digits after it
+                let banked = name.len() == 2 && name.starts_with('E') && self.eat(",");
+                let operand = match banked {
+                    true => {
+                        self.take_while(|c| c.is_ascii_digit());
+                        &self.text[start..self.pos]
+                    }
+                    false => name,
+                };
+                let addr = resolve_address(operand, Read::Raw, self.symbols)?;
+                Ok(Node::Word(Read::Raw, addr))
+            }
+        }
+    }
+}
+
+impl Condition {
+    pub fn parse<S: SymbolTable + ?Sized>(text: &str, symbols: &S) -> Result<Self, String> {
+        let mut parser = Parser {
+            text,
+            pos: 0,
+            symbols,
+        };
+        let root = parser.or()?;
+        parser.skip_space();
+        if !parser.rest().is_empty() {
+            return Err(parser.error("Unexpected text"));
+        }
+        Ok(Self {
+            text: text.trim().to_string(),
+            root,
+        })
+    }
+
+    pub fn eval(&self, cpu: &Cpu) -> i64 {
+        self.root.eval(cpu)
+    }
+
+    pub fn is_true(&self, cpu: &Cpu) -> bool {
+        self.eval(cpu) != 0
+    }
+}
+
+#[cfg(test)]
+mod cond_tests {
+    use super::Condition;
+    use ragc_core::cpu::Cpu;
+    use ragc_core::memory::MemoryMap;
+    use ragc_core::symbols::ErasableAddress;
+
+    #[test]
+    fn expressions_read_memory_and_channels() {
+        let symbols = [("RN", ErasableAddress::from_flat(0o1230))];
+        let mut cpu = Cpu::new(MemoryMap::new_blank());
+        cpu.memory_mut().write_block(7, 0o153, &[0o31]);
+        cpu.memory_mut().write_block(2, 0o230, &[0o77776]); // -1
+        cpu.write_io(0o11, 0o2);
+        let eval = |text: &str| Condition::parse(text, &symbols[..]).unwrap().eval(&cpu);
+
+        assert_eq!(eval("E7,1553 == 0o31 && chan(0o11).bit(2)"), 1);
+        assert_eq!(eval("E7,1553 == 0o31 && chan(0o11).bit(1)"), 0);
+        assert_eq!(eval("sp(RN) < 0 || program == 63"), 1);
+        assert_eq!(eval("RN & 0o40000"), 0o40000);
+        assert_eq!(eval("!(cycles >= 1) && -sp(E2,1630) == 1"), 1);
+        assert_eq!(eval("program"), -1);
+
+        for bad in [
+            "",
+            "E7,1553 ==",
+            "NOSUCH > 1",
+            "chan(11",
+            "chan(0o400) != 0",
+            "1 2",
+            "RN.bit(16)",
+        ] {
+            assert!(Condition::parse(bad, &symbols[..]).is_err(), "{}", bad);
+        }
+    }
+}
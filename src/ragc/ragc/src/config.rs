@@ -0,0 +1,619 @@
+use serde::Deserialize;
+
+use crate::campaign::CampaignConfig;
+use crate::sched::Scheduling;
+use ragc_core::latency::LatencyModel;
+use ragc_core::memory::mods::EmuTime;
+use ragc_core::memory::rom::BankFault;
+use ragc_core::memory::tap::{Fault, FaultTap, TapDirection};
+use ragc_peripherals::downlist::DOWNLIST_WORDS;
+use ragc_peripherals::downrupt;
+use ragc_peripherals::pacing::{self, Pacer, Strategy};
+use ragc_peripherals::queue::QueueConfig;
+
+/// Largest oscillator error accepted, far beyond any real crystal's
+pub const MAX_DRIFT_PPM: f64 = 10000.0;
+
+/// MCTs the oscillator completes per MCT of scenario time
+pub fn drift_rate(ppm: f64) -> Result<f64, String> {
+    match ppm.is_finite() && ppm.abs() <= MAX_DRIFT_PPM {
+        true => Ok(1.0 + ppm * 1e-6),
+        false => Err(format!("Drift must be within +/-{} ppm", MAX_DRIFT_PPM)),
+    }
+}
+
+/// Runtime configuration loaded with `--config <file>`
+///
+/// ```toml
+/// seed = 1969
+/// randomize_erasable = true
+/// relay_ms = 0
+/// uplink_word_ms = 15    # Closer uplink words latch UPLINK TOO FAST (0: any rate)
+/// downlink_pair_ms = 20  # Likewise for downlink pairs and DOWNLINK TOO FAST
+/// drift_ppm = 35.0 # Oscillator runs fast (positive) or slow against scenario time
+/// newjob = 0o67
+/// modreg = 0o1234 # Major mode register, for program tracking
+/// failreg = 0o1235 # First alarm code register (default: FAILREG symbol)
+///
+/// [[taps]]
+/// channel = 0o15
+/// direction = "read"
+/// fault = { stuck = 0o22 }
+///
+/// [[alarms]]      # Descriptions added to or replacing the ROM's
+/// code = 0o1202
+/// text = "Executive overflow: no core sets"
+///
+/// [[bank_faults]]
+/// bank = 0o21
+/// fault = "parity"
+///
+/// [latency]     # Extra MCTs per access, for timing studies
+/// erasable = 1
+/// fixed = 2
+/// counter = 1
+///
+/// [[idle]]            # Idle loop to pass over rather than run
+/// rom = "luminary99"  # Only with this ROM (default: any)
+/// from = "DUMMYJOB"   # First and last word: NAME, NAME+n, BB,AAAA or AAAA
+/// to = "DUMMYJOB+6"
+///
+/// [pacing]            # How the host waits between frames
+/// strategy = "hybrid" # spin, sleep (default) or hybrid
+/// frame_us = 2000     # Frame length for sleep and hybrid
+///
+/// [scheduling]        # Emulation thread on a loaded host
+/// priority = "realtime" # Or "normal" (default); falls back without privileges
+/// cpus = [2, 3]         # Cores to pin it to (default: any)
+///
+/// [kiosk]          # Unattended exhibits: standby idles the host, any key wakes it
+/// idle_minutes = 30 # Fresh start after this long without a key
+///
+/// [telemetry]
+/// addr = "127.0.0.1:19800"
+/// mode = "connect"
+/// framing = "words"
+/// decimate = 4
+/// max_bytes_per_sec = 960
+/// words = [0, 1, 12, 13]
+///
+/// [control]
+/// addr = "unix:/tmp/ragc.sock"
+///
+/// [dsky]                 # Frontend link, e.g. a replica DSKY on a serial bridge
+/// max_bytes_per_sec = 240 # Pace and coalesce display updates (default: unpaced)
+///
+/// [queues.dsky]
+/// capacity = 4096
+/// policy = "drop-newest" # Or "drop-oldest"
+/// ```
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+pub struct RuntimeConfig {
+    pub seed: Option<u64>, // Seed for every stochastic feature
+    #[serde(default)]
+    pub randomize_erasable: bool, // Random erasable contents at power on
+    pub relay_ms: Option<u32>,
+    pub uplink_word_ms: Option<u32>,
+    pub downlink_pair_ms: Option<u32>,
+    pub drift_ppm: Option<f64>, // Oscillator error, parts per million
+    pub newjob: Option<usize>,  // Night watchman address, overriding the ROM's
+    pub modreg: Option<usize>,  // MODREG address, overriding the ROM's
+    pub failreg: Option<usize>, // FAILREG address, overriding the ROM's
+    #[serde(default)]
+    pub alarms: Vec<AlarmConfig>,
+    #[serde(default)]
+    pub taps: Vec<TapConfig>,
+    #[serde(default)]
+    pub bank_faults: Vec<BankFaultConfig>,
+    pub latency: Option<LatencyConfig>,
+    #[serde(default)]
+    pub pacing: PacingConfig,
+    #[serde(default)]
+    pub scheduling: SchedulingConfig,
+    pub kiosk: Option<KioskConfig>,
+    #[serde(default)]
+    pub idle: Vec<IdleConfig>,
+    pub campaign: Option<CampaignConfig>,
+    #[serde(default)]
+    pub telemetry: TelemetryConfig,
+    pub control: Option<ControlConfig>,
+    #[serde(default)]
+    pub dsky: DskyConfig,
+    #[serde(default)]
+    pub queues: QueuesConfig,
+}
+
+/// JSON-RPC control socket
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ControlConfig {
+    pub addr: String,                   // host:port or unix:<path>
+    pub token: Option<String>,          // Operator token, required when set
+    pub observer_token: Option<String>, // Read-only access
+    #[serde(default)]
+    pub public: bool, // Clients without a token observe
+}
+
+/// DOWNRUPT telemetry endpoint; unset fields keep the defaults
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+pub struct TelemetryConfig {
+    pub addr: Option<String>,
+    pub mode: Option<String>,    // "listen" or "connect"
+    pub framing: Option<String>, // "packet" or "words"
+    pub decimate: Option<u32>,   // Forward one downlist in this many
+    pub max_bytes_per_sec: Option<u32>,
+    pub words: Option<Vec<usize>>, // Downlist word indices to forward
+}
+
+/// DSKY frontend link
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+pub struct DskyConfig {
+    pub max_bytes_per_sec: Option<u32>, // What the frontend can take
+}
+
+/// Main loop pacing; unset fields keep the defaults
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+pub struct PacingConfig {
+    pub strategy: Option<String>,
+    pub frame_us: Option<u64>,
+}
+
+/// Emulation thread scheduling; unset fields leave it as started
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+pub struct SchedulingConfig {
+    pub priority: Option<String>, // "normal" or "realtime"
+    pub cpus: Option<Vec<usize>>,
+}
+
+/// Limits on the queues feeding peripheral threads
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+pub struct QueuesConfig {
+    pub dsky: Option<QueueFile>,      // Display packets for the frontend
+    pub telemetry: Option<QueueFile>, // Downlink pairs for the ground tool
+}
+
+/// One queue; unset fields keep the peripheral's default
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct QueueFile {
+    pub capacity: Option<usize>,
+    pub policy: Option<String>, // "drop-newest" or "drop-oldest"
+}
+
+#[derive(Deserialize, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum DirectionConfig {
+    Read,
+    Write,
+    Both,
+}
+
+#[derive(Deserialize, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum FaultConfig {
+    Xor(u16),
+    Or(u16),
+    And(u16),
+    Stuck(u16),
+    Drop,
+    DelayMs(u32),
+}
+
+/// Program alarm description
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AlarmConfig {
+    pub code: u16,
+    pub text: String,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TapConfig {
+    pub channel: usize,
+    #[serde(default = "default_direction")]
+    pub direction: DirectionConfig,
+    pub fault: FaultConfig,
+}
+
+#[derive(Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum BankFaultKind {
+    Zeros,
+    Parity,
+}
+
+/// Fixed-memory hardware fault on a rope bank
+#[derive(Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct BankFaultConfig {
+    pub bank: usize,
+    pub fault: BankFaultKind,
+}
+
+impl BankFaultConfig {
+    /// Parse the CLI form `<octal bank>:<zeros|parity>`
+    pub fn parse(arg: &str) -> Result<Self, String> {
+        let (bank, fault) = arg
+            .split_once(':')
+            .ok_or_else(|| format!("Expected <bank>:<zeros|parity>, got {}", arg))?;
+        let bank = usize::from_str_radix(bank, 8).map_err(|_| format!("Invalid bank: {}", bank))?;
+        let fault = match fault {
+            "zeros" => BankFaultKind::Zeros,
+            "parity" => BankFaultKind::Parity,
+            _ => return Err(format!("Unknown bank fault: {}", fault)),
+        };
+        Ok(Self { bank, fault })
+    }
+
+    pub fn fault(&self) -> BankFault {
+        match self.fault {
+            BankFaultKind::Zeros => BankFault::Zeros,
+            BankFaultKind::Parity => BankFault::Parity,
+        }
+    }
+}
+
+/// Memory latency model; unset fields charge nothing extra
+#[derive(Deserialize, Clone, Copy, Default, PartialEq, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct LatencyConfig {
+    pub erasable: u8, // MCTs per erasable fetch or operand
+    pub fixed: u8,    // MCTs per fixed fetch or operand
+    pub counter: u8,  // MCTs per counter increment
+}
+
+impl LatencyConfig {
+    /// Parse the CLI form `erasable=<n>,fixed=<n>,counter=<n>`, any of them
+    pub fn parse(arg: &str) -> Result<Self, String> {
+        let mut config = Self::default();
+        for field in arg.split(',') {
+            let (name, mcts) = field
+                .split_once('=')
+                .ok_or_else(|| format!("Expected <access>=<mcts>, got {}", field))?;
+            let mcts = mcts
+                .parse::<u8>()
+                .map_err(|_| format!("Invalid latency: {}", mcts))?;
+            match name {
+                "erasable" => config.erasable = mcts,
+                "fixed" => config.fixed = mcts,
+                "counter" => config.counter = mcts,
+                _ => return Err(format!("Unknown memory access: {}", name)),
+            }
+        }
+        Ok(config)
+    }
+
+    pub fn model(&self) -> LatencyModel {
+        LatencyModel {
+            erasable: self.erasable,
+            fixed: self.fixed,
+            counter: self.counter,
+        }
+    }
+}
+
+/// Idle loop, its first and last word
+#[derive(Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct IdleConfig {
+    pub rom: Option<String>, // Only with this ROM
+    pub from: String,
+    pub to: String,
+}
+
+/// Kiosk profile; without `idle_minutes` there is no fresh start
+#[derive(Deserialize, Clone, Copy, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct KioskConfig {
+    pub idle_minutes: Option<u32>,
+}
+
+fn default_direction() -> DirectionConfig {
+    DirectionConfig::Both
+}
+
+/// Convert milliseconds of emulated time into MCTs
+pub fn ms_to_mcts(ms: u32) -> u32 {
+    EmuTime::mcts_in_millis(ms as u64) as u32
+}
+
+impl TapConfig {
+    pub fn build(&self) -> (usize, FaultTap) {
+        let direction = match self.direction {
+            DirectionConfig::Read => TapDirection::Read,
+            DirectionConfig::Write => TapDirection::Write,
+            DirectionConfig::Both => TapDirection::Both,
+        };
+        let fault = match self.fault {
+            FaultConfig::Xor(mask) => Fault::Xor(mask),
+            FaultConfig::Or(mask) => Fault::Or(mask),
+            FaultConfig::And(mask) => Fault::And(mask),
+            FaultConfig::Stuck(value) => Fault::Stuck(value),
+            FaultConfig::Drop => Fault::Drop,
+            FaultConfig::DelayMs(ms) => Fault::Delay(ms_to_mcts(ms)),
+        };
+        (self.channel, FaultTap::new(direction, fault))
+    }
+}
+
+impl TelemetryConfig {
+    /// `--telemetry` and the options that override the rest of the section
+    pub fn args<'a, 'b>() -> [clap::Arg<'a, 'b>; 6] {
+        [
+            clap::Arg::with_name("telemetry")
+                .long("telemetry")
+                .takes_value(true)
+                .help("Telemetry endpoint address (default: 127.0.0.1:19800)"),
+            clap::Arg::with_name("telemetry-mode")
+                .long("telemetry-mode")
+                .takes_value(true)
+                .possible_values(&["listen", "connect"])
+                .help("Accept ground tool connections or dial out to one"),
+            clap::Arg::with_name("telemetry-framing")
+                .long("telemetry-framing")
+                .takes_value(true)
+                .possible_values(&["packet", "words"])
+                .help("yaAGC channel packets (yaTelemetry) or bare downlink words"),
+            clap::Arg::with_name("telemetry-decimate")
+                .long("telemetry-decimate")
+                .takes_value(true)
+                .help("Forward one downlist in N"),
+            clap::Arg::with_name("telemetry-rate")
+                .long("telemetry-rate")
+                .takes_value(true)
+                .help("Telemetry link budget in bytes per second; word pairs over it are dropped"),
+            clap::Arg::with_name("telemetry-words")
+                .long("telemetry-words")
+                .takes_value(true)
+                .help("Comma-separated downlist word indices to forward (default: all)"),
+        ]
+    }
+
+    /// Override these settings from the command line
+    pub fn override_from(&mut self, matches: &clap::ArgMatches) -> Result<(), String> {
+        if let Some(addr) = matches.value_of("telemetry") {
+            self.addr = Some(addr.to_string());
+        }
+        if let Some(mode) = matches.value_of("telemetry-mode") {
+            self.mode = Some(mode.to_string());
+        }
+        if let Some(framing) = matches.value_of("telemetry-framing") {
+            self.framing = Some(framing.to_string());
+        }
+        if let Some(n) = matches.value_of("telemetry-decimate") {
+            let n = n
+                .parse()
+                .map_err(|_| format!("Invalid decimation: {}", n))?;
+            self.decimate = Some(n);
+        }
+        if let Some(rate) = matches.value_of("telemetry-rate") {
+            let rate = rate
+                .parse()
+                .map_err(|_| format!("Invalid rate: {}", rate))?;
+            self.max_bytes_per_sec = Some(rate);
+        }
+        if let Some(list) = matches.value_of("telemetry-words") {
+            let words: Result<Vec<usize>, _> = list.split(',').map(|w| w.trim().parse()).collect();
+            self.words = Some(words.map_err(|_| format!("Invalid word list: {}", list))?);
+        }
+        Ok(())
+    }
+
+    pub fn build(&self) -> Result<downrupt::TelemetryConfig, String> {
+        let mut config = downrupt::TelemetryConfig::default();
+        if let Some(addr) = &self.addr {
+            config.addr = addr.clone();
+        }
+        if let Some(mode) = &self.mode {
+            config.mode = mode.parse()?;
+        }
+        if let Some(framing) = &self.framing {
+            config.framing = framing.parse()?;
+        }
+        match self.decimate {
+            Some(0) => return Err("decimate must be at least 1".to_string()),
+            Some(decimate) => config.decimate = decimate,
+            None => {}
+        }
+        config.max_bytes_per_sec = self.max_bytes_per_sec;
+        if let Some(words) = &self.words {
+            if let Some(word) = words.iter().find(|w| **w >= DOWNLIST_WORDS) {
+                return Err(format!("Downlist word {} out of range", word));
+            }
+            config.words = Some(words.clone());
+        }
+        Ok(config)
+    }
+}
+
+impl DskyConfig {
+    /// Link budget for the display, if it is paced
+    pub fn build(&self) -> Result<Option<u32>, String> {
+        match self.max_bytes_per_sec {
+            Some(rate) if rate < 4 => {
+                Err("max_bytes_per_sec must fit a packet (4 bytes)".to_string())
+            }
+            rate => Ok(rate),
+        }
+    }
+}
+
+impl PacingConfig {
+    pub fn build(&self) -> Result<(Strategy, Box<dyn Pacer>), String> {
+        let strategy = match &self.strategy {
+            Some(strategy) => strategy.parse()?,
+            None => Strategy::Sleep,
+        };
+        if strategy == Strategy::External {
+            return Err("external pacing needs a host ticking frames".to_string());
+        }
+        let frame = match self.frame_us {
+            Some(0) => return Err("frame_us must be at least 1".to_string()),
+            Some(us) => std::time::Duration::from_micros(us),
+            None => pacing::DEFAULT_FRAME,
+        };
+        Ok((strategy, strategy.pacer(frame).0))
+    }
+}
+
+impl SchedulingConfig {
+    pub fn build(&self) -> Result<Scheduling, String> {
+        let mut scheduling = Scheduling::default();
+        if let Some(priority) = &self.priority {
+            scheduling.priority = priority.parse()?;
+        }
+        if let Some(cpus) = &self.cpus {
+            scheduling.cpus = cpus.clone();
+        }
+        Ok(scheduling)
+    }
+}
+
+impl QueueFile {
+    pub fn build(&self, mut config: QueueConfig) -> Result<QueueConfig, String> {
+        match self.capacity {
+            Some(0) => return Err("queue capacity must be at least 1".to_string()),
+            Some(capacity) => config.capacity = capacity,
+            None => {}
+        }
+        if let Some(policy) = &self.policy {
+            config.policy = policy.parse()?;
+        }
+        Ok(config)
+    }
+}
+
+impl QueuesConfig {
+    /// Queue config for a peripheral, from its section if there is one
+    pub fn build(file: &Option<QueueFile>, default: QueueConfig) -> Result<QueueConfig, String> {
+        file.as_ref()
+            .map_or(Ok(default), |file| file.build(default))
+    }
+}
+
+impl RuntimeConfig {
+    pub fn load(path: &str) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+        let config: Self = toml::from_str(&text).map_err(|e| format!("{}: {}", path, e))?;
+        // Switched erasable would be matched against the wrong bank
+        if config.modreg.is_some_and(|addr| addr >= 0o1400) {
+            return Err(format!("{}: modreg must be unswitched erasable", path));
+        }
+        if config.failreg.is_some_and(|addr| addr + 2 >= 0o1400) {
+            return Err(format!("{}: failreg must be unswitched erasable", path));
+        }
+        Ok(config)
+    }
+}
+
+#[cfg(test)]
+mod config_tests {
+    use super::{BankFaultConfig, BankFaultKind, LatencyConfig, QueuesConfig, RuntimeConfig};
+    use ragc_core::memory::tap::{Fault, TapDirection};
+    use ragc_peripherals::downrupt;
+    use ragc_peripherals::dsky::DEFAULT_DSKY_QUEUE;
+    use ragc_peripherals::queue::{OverflowPolicy, QueueConfig};
+
+    #[test]
+    fn parses_taps() {
+        let config: RuntimeConfig = toml::from_str(
+            r#"
+            relay_ms = 0
+            drift_ppm = -12.5
+            newjob = 0o70
+            modreg = 0o1107
+
+            [[taps]]
+            channel = 0o15
+            direction = "read"
+            fault = { stuck = 0o22 }
+
+            [[taps]]
+            channel = 0o34
+            fault = "drop"
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(config.relay_ms, Some(0));
+        assert_eq!(config.drift_ppm, Some(-12.5));
+        assert_eq!(config.newjob, Some(0o70));
+        assert_eq!(config.modreg, Some(0o1107));
+        let (channel, tap) = config.taps[0].build();
+        assert_eq!(channel, 0o15);
+        assert_eq!(tap.direction, TapDirection::Read);
+        assert_eq!(tap.fault, Fault::Stuck(0o22));
+        assert_eq!(config.taps[1].build().1.fault, Fault::Drop);
+    }
+
+    #[test]
+    fn parses_telemetry() {
+        let config: RuntimeConfig = toml::from_str(
+            r#"
+            [telemetry]
+            mode = "connect"
+            framing = "words"
+            decimate = 4
+            max_bytes_per_sec = 960
+            words = [0, 1, 12]
+            "#,
+        )
+        .unwrap();
+        let telemetry = config.telemetry.build().unwrap();
+        assert_eq!(telemetry.addr, downrupt::DEFAULT_TELEMETRY_ADDR);
+        assert_eq!(telemetry.mode, downrupt::TelemetryMode::Connect);
+        assert_eq!(telemetry.framing, downrupt::TelemetryFraming::Words);
+        assert_eq!(telemetry.decimate, 4);
+        assert_eq!(telemetry.max_bytes_per_sec, Some(960));
+        assert_eq!(telemetry.words, Some(vec![0, 1, 12]));
+
+        let bad: RuntimeConfig = toml::from_str("[telemetry]\nwords = [100]").unwrap();
+        assert!(bad.telemetry.build().is_err());
+
+        let defaults = RuntimeConfig::default().telemetry.build().unwrap();
+        assert_eq!(defaults, downrupt::TelemetryConfig::default());
+
+        let config: RuntimeConfig =
+            toml::from_str("[queues.telemetry]\ncapacity = 16\npolicy = \"drop-newest\"").unwrap();
+        let queue =
+            QueuesConfig::build(&config.queues.telemetry, downrupt::DEFAULT_TELEMETRY_QUEUE);
+        assert_eq!(queue, Ok(QueueConfig::new(16, OverflowPolicy::DropNewest)));
+        assert_eq!(
+            QueuesConfig::build(&config.queues.dsky, DEFAULT_DSKY_QUEUE),
+            Ok(DEFAULT_DSKY_QUEUE)
+        );
+
+        let config: RuntimeConfig = toml::from_str("[dsky]\nmax_bytes_per_sec = 240").unwrap();
+        assert_eq!(config.dsky.build(), Ok(Some(240)));
+        assert_eq!(RuntimeConfig::default().dsky.build(), Ok(None));
+        let bad: RuntimeConfig = toml::from_str("[dsky]\nmax_bytes_per_sec = 2").unwrap();
+        assert!(bad.dsky.build().is_err());
+    }
+
+    #[test]
+    fn parses_bank_fault_argument() {
+        let fault = BankFaultConfig::parse("21:parity").unwrap();
+        assert_eq!(fault.bank, 0o21);
+        assert_eq!(fault.fault, BankFaultKind::Parity);
+        assert!(BankFaultConfig::parse("21").is_err());
+        assert!(BankFaultConfig::parse("9:zeros").is_err());
+    }
+
+    #[test]
+    fn parses_latency_argument() {
+        let latency = LatencyConfig::parse("fixed=2,counter=1").unwrap();
+        assert_eq!(
+            (latency.erasable, latency.fixed, latency.counter),
+            (0, 2, 1)
+        );
+        assert!(LatencyConfig::parse("fixed").is_err());
+        assert!(LatencyConfig::parse("central=1").is_err());
+    }
+}
@@ -0,0 +1,294 @@
+//! The operator console: debugger commands read from stdin, one per line,
+//! and forwarded to the emulator through its runtime handle
+use std::io::BufRead;
+
+use log::{error, info, warn};
+
+use ragc_core::constants::registers::{REGISTER_ERASABLE_BANK, REGISTER_FIXED_BANK};
+use ragc_core::cpu::RestartCause;
+use ragc_core::memory;
+use ragc_core::memory::channels::Discrete;
+
+use crate::runtime::RuntimeHandle;
+use crate::{
+    calls, channels, cond, executive, listing, metadata, migrate, phases, snapshot, vardb, watch,
+};
+
+/// Read console commands on a thread of their own until stdin closes or
+/// the emulator stops
+pub fn spawn(
+    handle: RuntimeHandle,
+    symbols: listing::Symbols,
+    vars: vardb::VarDb,
+    capabilities: metadata::Capabilities,
+) {
+    std::thread::spawn(move || console_thread(handle, symbols, vars, capabilities));
+}
+
+/// Reads operator commands from stdin and forwards them to the emulator
+fn console_thread(
+    handle: RuntimeHandle,
+    symbols: listing::Symbols,
+    vars: vardb::VarDb,
+    capabilities: metadata::Capabilities,
+) {
+    let stdin = std::io::stdin();
+    for line in stdin.lock().lines() {
+        let line = match line {
+            Ok(x) => x,
+            Err(_) => break,
+        };
+        let mut words = line.split_whitespace();
+        let octal = |a: Option<&str>| a.and_then(|a| u16::from_str_radix(a, 8).ok());
+        let arg = words.nth(1);
+        let octal_arg = octal(arg);
+
+        let sent = match line.split_whitespace().next() {
+            Some("restart") => handle.restart(RestartCause::Manual),
+            Some("voltfail") => handle.set_discrete(Discrete::VoltageFail, true),
+            Some("pause") => handle.pause(),
+            Some("resume") => handle.resume(),
+            Some("step") => handle.step(arg.and_then(|a| a.parse().ok()).unwrap_or(1)),
+            Some("over") => handle.step_over(),
+            Some("out") => handle.step_out(),
+            Some("bt") => match handle.backtrace() {
+                Some(bt) => {
+                    let _ = calls::write_backtrace(&bt, &symbols, &mut std::io::stdout());
+                    true
+                }
+                None => false,
+            },
+            Some("key") => match octal_arg {
+                Some(code) => handle.press_key(code),
+                None => {
+                    warn!("Usage: key <octal keycode>");
+                    continue;
+                }
+            },
+            Some("poke") => match (octal_arg, octal(words.next())) {
+                (Some(addr), Some(value)) => handle.poke(addr as usize, value & 0o77777),
+                _ => {
+                    warn!("Usage: poke <octal address> <octal value>");
+                    continue;
+                }
+            },
+            Some("banks") if arg.is_none() => match handle.snapshot() {
+                Some(snap) => {
+                    let regs = &snap.erasable[0];
+                    let (eb, fb) = (
+                        regs[REGISTER_ERASABLE_BANK] >> 8,
+                        regs[REGISTER_FIXED_BANK] >> 10,
+                    );
+                    println!(
+                        "EB {:o}  FB {:02o}  superbank {:o}  fixed bank {:02o}",
+                        eb,
+                        fb,
+                        snap.superbank >> 4,
+                        memory::superbank_fixed_bank(fb as usize, snap.superbank)
+                    );
+                    true
+                }
+                None => false,
+            },
+            Some("banks") => {
+                let fb = octal(words.next());
+                let superbank = words.next().map_or(Some(0), |a| octal(Some(a)));
+                match (octal_arg, fb, superbank) {
+                    (Some(eb), Some(fb), Some(superbank)) => handle.set_banks(eb, fb, superbank),
+                    _ => {
+                        warn!("Usage: banks <eb> <fb> [<superbank>], in octal");
+                        continue;
+                    }
+                }
+            }
+            Some("uplink") => match octal_arg {
+                Some(word) => handle.inject_uplink(word),
+                None => {
+                    warn!("Usage: uplink <octal word>");
+                    continue;
+                }
+            },
+            Some("snapshot") => match handle.snapshot() {
+                Some(snap) => {
+                    println!(
+                        "A={:06o} L={:06o} Q={:06o} Z={:06o} cycles={} seed={} hash={:016x}",
+                        snap.a,
+                        snap.l,
+                        snap.q,
+                        snap.z,
+                        snap.total_cycles,
+                        snap.seed,
+                        snap.state_hash
+                    );
+                    if let Some(bank) = arg.and_then(|a| a.parse::<usize>().ok()) {
+                        if let Some(words) = snap.erasable.get(bank) {
+                            for (row, chunk) in words.chunks(8).enumerate() {
+                                let cells: Vec<String> =
+                                    chunk.iter().map(|w| format!("{:05o}", w)).collect();
+                                println!("E{},{:04o}: {}", bank, row * 8, cells.join(" "));
+                            }
+                        }
+                    }
+                    true
+                }
+                None => false,
+            },
+            Some("save") => match (arg, handle.snapshot()) {
+                (Some(path), Some(snap)) => {
+                    match snapshot::save(path, &snap) {
+                        Ok(()) => info!("Saved snapshot to {}", path),
+                        Err(e) => error!("Cannot save snapshot: {}", e),
+                    }
+                    true
+                }
+                (None, _) => {
+                    warn!("Usage: save <file>");
+                    continue;
+                }
+                (_, None) => false,
+            },
+            Some("load") => {
+                let mapping = words.next().map(migrate::Migration::load).transpose();
+                match (arg.map(snapshot::load), mapping) {
+                    (Some(Ok(snap)), Ok(None)) => match handle.restore(snap) {
+                        Some(Ok(())) => true,
+                        Some(Err(e)) => {
+                            error!("Cannot restore snapshot: {}", e);
+                            true
+                        }
+                        None => false,
+                    },
+                    (Some(Ok(snap)), Ok(Some(migration))) => {
+                        match handle.migrate(snap, migration) {
+                            Some(Ok(words)) => info!("Migrated {} erasable words", words),
+                            Some(Err(e)) => error!("Cannot migrate snapshot: {}", e),
+                            None => break,
+                        }
+                        true
+                    }
+                    (Some(Err(e)), _) | (_, Err(e)) => {
+                        error!("Cannot load snapshot: {}", e);
+                        continue;
+                    }
+                    (None, _) => {
+                        warn!("Usage: load <file> [<mapping>]");
+                        continue;
+                    }
+                }
+            }
+            Some("watch") => match line.trim_start()["watch".len()..].trim() {
+                "" => {
+                    warn!("Usage: watch <sp|dp|raw>(<symbol|octal address>)[*k|/k...] [as <unit>], or a variable");
+                    continue;
+                }
+                expr => match vars.get(expr).map_or_else(
+                    || watch::WatchExpr::parse(expr, &symbols),
+                    watch::WatchExpr::var,
+                ) {
+                    Ok(expr) => handle.watch(expr),
+                    Err(e) => {
+                        warn!("{}", e);
+                        continue;
+                    }
+                },
+            },
+            Some("unwatch") => handle.clear_watches(),
+            Some("break") => {
+                match line.trim_start()["break".len()..].trim() {
+                    "" => {
+                        warn!("Usage: break <condition>, e.g. break E7,1553 == 0o31 && chan(0o11).bit(2)");
+                        continue;
+                    }
+                    text => match cond::Condition::parse(text, &symbols) {
+                        Ok(condition) => handle.break_when(condition),
+                        Err(e) => {
+                            warn!("{}", e);
+                            continue;
+                        }
+                    },
+                }
+            }
+            Some("unbreak") => handle.clear_breaks(),
+            Some("protect") => match line.trim_start()["protect".len()..].trim() {
+                "" => {
+                    warn!("Usage: protect <symbol|address> [words] [suppress]");
+                    continue;
+                }
+                text => match watch::parse_protect(text, &symbols) {
+                    Ok(range) => handle.protect(range),
+                    Err(e) => {
+                        warn!("{}", e);
+                        continue;
+                    }
+                },
+            },
+            Some("unprotect") => handle.clear_protect(),
+            Some("exec") => match (executive::Layout::new(&symbols), arg) {
+                (Ok(layout), _) => match handle.snapshot() {
+                    Some(snap) => {
+                        let mut out = std::io::stdout();
+                        let _ =
+                            executive::write_schedule(&layout, &snap.erasable, &symbols, &mut out);
+                        true
+                    }
+                    None => false,
+                },
+                (Err(e), None) => {
+                    warn!(
+                        "Cannot find the executive: {}; usage: exec <core set address>",
+                        e
+                    );
+                    continue;
+                }
+                (Err(_), Some(addr)) => {
+                    match watch::resolve_address(addr, watch::Read::Raw, &symbols) {
+                        Ok(base) => match handle.snapshot() {
+                            Some(snap) => {
+                                let mut out = std::io::stdout();
+                                let _ =
+                                    executive::write_core_sets_raw(base, &snap.erasable, &mut out);
+                                true
+                            }
+                            None => false,
+                        },
+                        Err(e) => {
+                            warn!("{}", e);
+                            continue;
+                        }
+                    }
+                }
+            },
+            Some("caps") => {
+                let _ = capabilities.write(&mut std::io::stdout());
+                continue;
+            }
+            Some("phases") if vars.restart.is_empty() => {
+                warn!("No restart groups in the variable database");
+                continue;
+            }
+            Some("phases") => match handle.snapshot() {
+                Some(snap) => {
+                    let mut out = std::io::stdout();
+                    let _ = phases::write_phases(&vars.restart, &snap.erasable, &symbols, &mut out);
+                    true
+                }
+                None => false,
+            },
+            Some("channels") => match handle.channels() {
+                Some(values) => {
+                    let _ = channels::write_channels(&values, &mut std::io::stdout());
+                    true
+                }
+                None => false,
+            },
+            None => continue,
+            Some(other) => {
+                warn!("Unknown console command: {}", other);
+                continue;
+            }
+        };
+        if !sent {
+            break;
+        }
+    }
+}
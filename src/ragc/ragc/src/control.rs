@@ -0,0 +1,615 @@
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpListener;
+use std::time::Duration;
+
+use log::{error, info, warn};
+use serde_json::{json, Value};
+
+use crate::channels;
+use crate::config::ControlConfig;
+use crate::ropes;
+use crate::runtime::{ErasablePolicy, RopeSwap, RuntimeHandle};
+use ragc_core::memory::channels::{channel_spec, Axis, Discrete};
+use ragc_protocol_json::control::{ChannelEntry, SnapshotReply};
+
+/// Highest address reachable through peek/poke
+const ADDRESS_MAX: u64 = 0o7777;
+
+/// Connections quiet for longer are closed
+const IDLE_TIMEOUT: Duration = Duration::from_secs(300);
+
+// JSON-RPC 2.0 error codes
+const PARSE_ERROR: i64 = -32700;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+const UNAUTHORIZED: i64 = -32001;
+const EMULATOR_GONE: i64 = -32002;
+const FORBIDDEN: i64 = -32003;
+
+/// Methods observers may call. Erasable stays out of reach: peek and
+/// snapshot would show whatever the operator has keyed in.
+const READ_ONLY: [&str; 1] = ["channels"];
+
+/// What a networked client may do
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Role {
+    Observer, // Display, channels and telemetry only
+    Operator, // Also keys, discretes, pokes and run control
+}
+
+impl Role {
+    pub fn allows(self, method: &str) -> bool {
+        self == Role::Operator || READ_ONLY.contains(&method)
+    }
+}
+
+/// Who may connect as what. Without an operator token everyone operates,
+/// so demo streams set one and hand out the observer token, or go public.
+#[derive(Clone, Default)]
+pub struct Access {
+    pub operator: Option<String>,
+    pub observer: Option<String>,
+    pub public: bool, // Clients without a token observe
+}
+
+impl Access {
+    /// Role of a client presenting `token`; None if it is turned away
+    pub fn role(&self, token: Option<&str>) -> Option<Role> {
+        let operator = match &self.operator {
+            Some(operator) => operator,
+            None => return Some(Role::Operator),
+        };
+        let observer = |token| {
+            self.observer
+                .as_deref()
+                .is_some_and(|o| same_token(token, o))
+        };
+        match token {
+            Some(token) if same_token(token, operator) => Some(Role::Operator),
+            Some(token) if observer(token) => Some(Role::Observer),
+            None if self.public => Some(Role::Observer),
+            _ => None,
+        }
+    }
+
+    /// Rope swaps restart the AGC under everyone watching, so they are
+    /// refused outright unless an operator token says who may make them
+    pub fn may_swap_ropes(&self) -> bool {
+        self.operator.is_some()
+    }
+}
+
+/// Compare a presented token in time that does not depend on where it
+/// differs from the expected one
+fn same_token(given: &str, expected: &str) -> bool {
+    let (given, expected) = (given.as_bytes(), expected.as_bytes());
+    let mut diff = given.len() ^ expected.len();
+    for (i, byte) in expected.iter().enumerate() {
+        diff |= (given.get(i).copied().unwrap_or(0) ^ byte) as usize;
+    }
+    diff == 0
+}
+
+/// `--control` and the tokens its clients present
+pub fn args<'a, 'b>() -> [clap::Arg<'a, 'b>; 4] {
+    [
+        clap::Arg::with_name("control")
+            .long("control")
+            .takes_value(true)
+            .help("JSON-RPC control socket: <host:port> or unix:<path>"),
+        clap::Arg::with_name("control-token")
+            .long("control-token")
+            .takes_value(true)
+            .env("RAGC_CONTROL_TOKEN")
+            .help("Token control clients must present with auth"),
+        clap::Arg::with_name("control-observer-token")
+            .long("control-observer-token")
+            .takes_value(true)
+            .env("RAGC_CONTROL_OBSERVER_TOKEN")
+            .help("Token for control clients that may only read channels"),
+        clap::Arg::with_name("control-public")
+            .long("control-public")
+            .help("Let control clients without a token observe"),
+    ]
+}
+
+/// Where to serve the control socket and who may use it, if anywhere.
+/// `--control` moves a configured socket but keeps its tokens, which the
+/// token options override in turn.
+pub fn from_args(
+    matches: &clap::ArgMatches,
+    configured: Option<&ControlConfig>,
+) -> Option<(String, Access)> {
+    let addr = matches
+        .value_of("control")
+        .or(configured.map(|c| c.addr.as_str()))?;
+    let access = configured.map_or_else(Access::default, |c| Access {
+        operator: c.token.clone(),
+        observer: c.observer_token.clone(),
+        public: c.public,
+    });
+    let access = Access {
+        operator: matches
+            .value_of("control-token")
+            .map(String::from)
+            .or(access.operator),
+        observer: matches
+            .value_of("control-observer-token")
+            .map(String::from)
+            .or(access.observer),
+        public: access.public || matches.is_present("control-public"),
+    };
+    Some((addr.to_string(), access))
+}
+
+/// Serve line-delimited JSON-RPC 2.0 on `addr` (`host:port` or `unix:<path>`)
+///
+/// Connections call `auth` with a token to take a role; until then they
+/// have the role `access` gives tokenless clients, if any. Each connection
+/// is served on a thread of its own, so a client that sends nothing locks
+/// nobody else out, and is closed after `IDLE_TIMEOUT` without a request.
+pub fn spawn(addr: &str, access: Access, handle: RuntimeHandle) -> Result<(), String> {
+    if let Some(path) = addr.strip_prefix("unix:") {
+        return spawn_unix(path, access, handle);
+    }
+    let listener = TcpListener::bind(addr).map_err(|e| format!("{}: {}", addr, e))?;
+    if access.operator.is_none() && !listener.local_addr().map_or(true, |a| a.ip().is_loopback()) {
+        warn!("Control socket on {} is exposed without a token", addr);
+    }
+    info!("Control socket listening on {}", addr);
+    std::thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let reader = stream
+                .set_read_timeout(Some(IDLE_TIMEOUT))
+                .and_then(|_| stream.try_clone());
+            match reader {
+                Ok(reader) => attend(BufReader::new(reader), stream, &access, &handle),
+                Err(e) => error!("Control connection failed: {}", e),
+            }
+        }
+    });
+    Ok(())
+}
+
+#[cfg(unix)]
+fn spawn_unix(path: &str, access: Access, handle: RuntimeHandle) -> Result<(), String> {
+    use std::fs::Permissions;
+    use std::os::unix::fs::{FileTypeExt, PermissionsExt};
+    use std::os::unix::net::{UnixListener, UnixStream};
+
+    // Replace only a socket a previous run left behind
+    if let Ok(meta) = std::fs::symlink_metadata(path) {
+        if !meta.file_type().is_socket() {
+            return Err(format!("{}: exists and is not a socket", path));
+        }
+        if UnixStream::connect(path).is_ok() {
+            return Err(format!("{}: in use by another process", path));
+        }
+        std::fs::remove_file(path).map_err(|e| format!("{}: {}", path, e))?;
+    }
+    let listener = UnixListener::bind(path).map_err(|e| format!("{}: {}", path, e))?;
+    std::fs::set_permissions(path, Permissions::from_mode(0o600))
+        .map_err(|e| format!("{}: {}", path, e))?;
+    info!("Control socket listening on {}", path);
+    std::thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let reader = stream
+                .set_read_timeout(Some(IDLE_TIMEOUT))
+                .and_then(|_| stream.try_clone());
+            match reader {
+                Ok(reader) => attend(BufReader::new(reader), stream, &access, &handle),
+                Err(e) => error!("Control connection failed: {}", e),
+            }
+        }
+    });
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn spawn_unix(path: &str, _access: Access, _handle: RuntimeHandle) -> Result<(), String> {
+    Err(format!("{}: Unix sockets are not supported here", path))
+}
+
+/// Run a session on a thread of its own
+fn attend(
+    reader: impl BufRead + Send + 'static,
+    writer: impl Write + Send + 'static,
+    access: &Access,
+    handle: &RuntimeHandle,
+) {
+    let (access, handle) = (access.clone(), handle.clone());
+    std::thread::spawn(move || session(reader, writer, &access, &handle));
+}
+
+/// Answer requests from one client until it disconnects or goes quiet
+fn session(reader: impl BufRead, mut writer: impl Write, access: &Access, handle: &RuntimeHandle) {
+    let mut role = access.role(None);
+    for line in reader.lines() {
+        let line = match line {
+            Ok(x) => x,
+            Err(_) => break,
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+        if let Some(reply) = dispatch(&line, access, &mut role, handle) {
+            if writeln!(writer, "{}", reply).is_err() {
+                break;
+            }
+        }
+    }
+}
+
+/// Handle one request line; notifications (no `id`) produce no reply
+fn dispatch(
+    line: &str,
+    access: &Access,
+    role: &mut Option<Role>,
+    handle: &RuntimeHandle,
+) -> Option<Value> {
+    let request: Value = match serde_json::from_str(line) {
+        Ok(x) => x,
+        Err(e) => return Some(failure(Value::Null, PARSE_ERROR, &e.to_string())),
+    };
+    let id = request.get("id").cloned();
+    let method = request["method"].as_str().unwrap_or_default();
+    let params = &request["params"];
+
+    let result = match method {
+        "auth" => match access.role(params["token"].as_str()) {
+            Some(granted) => {
+                *role = Some(granted);
+                Ok(json!(true))
+            }
+            // A wrong token gives up the role held so far
+            None => {
+                *role = None;
+                Err((UNAUTHORIZED, "Invalid token".to_string()))
+            }
+        },
+        _ => match *role {
+            None => Err((UNAUTHORIZED, "Call auth first".to_string())),
+            Some(role) if !role.allows(method) => {
+                Err((FORBIDDEN, format!("Observers may not call {}", method)))
+            }
+            Some(_) if method == "load_rope" && !access.may_swap_ropes() => {
+                Err((FORBIDDEN, "Rope swaps need an operator token".to_string()))
+            }
+            Some(_) => call(method, params, handle),
+        },
+    };
+
+    let id = id?;
+    Some(match result {
+        Ok(value) => json!({ "jsonrpc": "2.0", "id": id, "result": value }),
+        Err((code, message)) => failure(id, code, &message),
+    })
+}
+
+/// Run one method, as for JSON-RPC and the HTTP server
+pub fn call(method: &str, params: &Value, handle: &RuntimeHandle) -> Result<Value, (i64, String)> {
+    let uint = |name: &str| {
+        params[name]
+            .as_u64()
+            .ok_or_else(|| (INVALID_PARAMS, format!("Missing integer param: {}", name)))
+    };
+    let addr = || {
+        uint("addr").and_then(|a| match a <= ADDRESS_MAX {
+            true => Ok(a as usize),
+            false => Err((INVALID_PARAMS, format!("Address out of range: {:o}", a))),
+        })
+    };
+    let gone = || (EMULATOR_GONE, "Emulator has stopped".to_string());
+
+    let sent = match method {
+        "pause" => handle.pause(),
+        "resume" => handle.resume(),
+        "step" => handle.step(params["count"].as_u64().unwrap_or(1) as u32),
+        "step_over" => handle.step_over(),
+        "step_out" => handle.step_out(),
+        "set_speed" => match params["speed"].as_f64() {
+            Some(speed) if speed.is_finite() && speed > 0.0 => handle.set_speed(speed),
+            _ => return Err((INVALID_PARAMS, "Speed must be positive".to_string())),
+        },
+        "set_discrete" => {
+            let name = params["name"].as_str().unwrap_or_default();
+            let discrete = Discrete::from_name(name)
+                .ok_or_else(|| (INVALID_PARAMS, format!("Unknown discrete: {}", name)))?;
+            handle.set_discrete(discrete, params["asserted"].as_bool().unwrap_or(true))
+        }
+        // Deflection from -1 to 1 of full scale
+        "set_axis" => {
+            let name = params["name"].as_str().unwrap_or_default();
+            let axis = Axis::from_name(name)
+                .ok_or_else(|| (INVALID_PARAMS, format!("Unknown axis: {}", name)))?;
+            let value = params["value"]
+                .as_f64()
+                .unwrap_or_default()
+                .clamp(-1.0, 1.0);
+            handle.set_axis(axis, (value * Axis::FULL_SCALE as f64) as i16)
+        }
+        "press_key" => handle.press_key(uint("keycode")? as u16),
+        "poke" => handle.poke(addr()?, (uint("value")? & 0o77777) as u16),
+        // Bank numbers; the superbank defaults to 0
+        "set_banks" => {
+            let bank = |name: &str, count: u64, value: Option<u64>| match value {
+                Some(v) if v < count => Ok(v as u16),
+                _ => Err((
+                    INVALID_PARAMS,
+                    format!("{} must be below {:o}", name, count),
+                )),
+            };
+            let eb = bank("eb", 0o10, params["eb"].as_u64())?;
+            let fb = bank("fb", 0o40, params["fb"].as_u64())?;
+            let superbank = bank(
+                "superbank",
+                0o10,
+                Some(params["superbank"].as_u64().unwrap_or(0)),
+            )?;
+            handle.set_banks(eb, fb, superbank)
+        }
+        "peek" => return handle.peek(addr()?).map(|v| json!(v)).ok_or_else(gone),
+        "snapshot" => {
+            let snap = handle.snapshot().ok_or_else(gone)?;
+            return Ok(json!(SnapshotReply {
+                seed: snap.seed,
+                total_cycles: snap.total_cycles as u64,
+                a: snap.a,
+                l: snap.l,
+                q: snap.q,
+                z: snap.z,
+                erasable: Some(snap.erasable.iter().map(|bank| bank.to_vec()).collect()),
+                state_hash: format!("{:016x}", snap.state_hash),
+            }));
+        }
+        // A bundled `rom`; erasable cleared unless `erasable` says keep or
+        // random
+        "load_rope" => {
+            let erasable = match params["erasable"].as_str() {
+                Some(policy) => policy.parse().map_err(|e| (INVALID_PARAMS, e))?,
+                None => ErasablePolicy::Clear,
+            };
+            let name = params["rom"]
+                .as_str()
+                .ok_or_else(|| (INVALID_PARAMS, "Missing ROM name: rom".to_string()))?;
+            let (rope, info) = ropes::swap_source(name).map_err(|e| (INVALID_PARAMS, e))?;
+            let swap = RopeSwap {
+                rope,
+                info,
+                erasable,
+            };
+            let hash = handle.swap_rope(swap).ok_or_else(gone)?;
+            info!("Rope swapped, now {:016x}", hash);
+            return Ok(json!(format!("{:016x}", hash)));
+        }
+        "channels" => {
+            let values = handle.channels().ok_or_else(gone)?;
+            let entries: Vec<ChannelEntry> = values
+                .iter()
+                .map(|&(channel, value)| ChannelEntry {
+                    channel,
+                    name: channel_spec(channel).map(|spec| spec.name.to_string()),
+                    value,
+                    bits: channels::annotate(channel, value),
+                })
+                .collect();
+            return Ok(json!(entries));
+        }
+        _ => return Err((METHOD_NOT_FOUND, format!("Unknown method: {}", method))),
+    };
+    match sent {
+        true => Ok(json!(true)),
+        false => Err(gone()),
+    }
+}
+
+/// HTTP status for a JSON-RPC error code
+pub fn http_status(code: i64) -> u16 {
+    match code {
+        METHOD_NOT_FOUND => 404,
+        INVALID_PARAMS | PARSE_ERROR => 400,
+        UNAUTHORIZED => 401,
+        FORBIDDEN => 403,
+        _ => 503,
+    }
+}
+
+fn failure(id: Value, code: i64, message: &str) -> Value {
+    json!({ "jsonrpc": "2.0", "id": id, "error": { "code": code, "message": message } })
+}
+
+#[cfg(test)]
+mod control_tests {
+    use super::{dispatch, Access, Role, FORBIDDEN, INVALID_PARAMS, UNAUTHORIZED};
+    use crate::runtime::Runtime;
+    use ragc_core::cpu::{Cpu, RestartCause};
+    use ragc_core::memory::MemoryMap;
+
+    #[test]
+    fn requires_token_then_serves_peek_and_poke() {
+        let (mut runtime, handle) = Runtime::new(None, 1);
+        let client = std::thread::spawn(move || {
+            let access = Access {
+                operator: Some("s3cret".to_string()),
+                ..Access::default()
+            };
+            let mut role = None;
+            let req = r#"{"jsonrpc":"2.0","id":1,"method":"pause"}"#;
+            let reply = dispatch(req, &access, &mut role, &handle).unwrap();
+            assert_eq!(reply["error"]["code"], UNAUTHORIZED);
+
+            let req = r#"{"jsonrpc":"2.0","id":2,"method":"auth","params":{"token":"s3cret"}}"#;
+            dispatch(req, &access, &mut role, &handle).unwrap();
+            assert_eq!(role, Some(Role::Operator));
+
+            let req = r#"{"jsonrpc":"2.0","method":"poke","params":{"addr":100,"value":42}}"#;
+            assert!(dispatch(req, &access, &mut role, &handle).is_none());
+            let req = r#"{"jsonrpc":"2.0","id":3,"method":"peek","params":{"addr":100}}"#;
+            let reply = dispatch(req, &access, &mut role, &handle).unwrap();
+            assert_eq!(reply["result"], 42);
+
+            let req = r#"{"jsonrpc":"2.0","id":4,"method":"peek","params":{"addr":99999}}"#;
+            assert!(dispatch(req, &access, &mut role, &handle).unwrap()["error"].is_object());
+
+            let req = r#"{"jsonrpc":"2.0","id":5,"method":"set_axis","params":{"name":"rhc-roll","value":-0.5}}"#;
+            assert_eq!(
+                dispatch(req, &access, &mut role, &handle).unwrap()["result"],
+                true
+            );
+            let req = r#"{"jsonrpc":"2.0","id":6,"method":"peek","params":{"addr":36}}"#;
+            let reply = dispatch(req, &access, &mut role, &handle).unwrap();
+            assert_eq!(reply["result"], 0o77752); // -21 counts
+
+            let req = r#"{"jsonrpc":"2.0","id":7,"method":"channels"}"#;
+            let reply = dispatch(req, &access, &mut role, &handle).unwrap();
+            assert_eq!(reply["result"][0]["name"], "L");
+        });
+
+        let mut cpu = Cpu::new(MemoryMap::new_blank());
+        while !client.is_finished() {
+            runtime.poll(&mut cpu);
+            std::thread::yield_now();
+        }
+        client.join().unwrap();
+    }
+
+    #[test]
+    fn voltage_fail_restarts_the_computer() {
+        let (mut runtime, handle) = Runtime::new(None, 1);
+        let client = std::thread::spawn(move || {
+            let (access, mut role) = (Access::default(), Some(Role::Operator));
+            let req = r#"{"jsonrpc":"2.0","id":1,"method":"set_discrete","params":{"name":"voltage-fail"}}"#;
+            let reply = dispatch(req, &access, &mut role, &handle).unwrap();
+            assert_eq!(reply["result"], true);
+
+            // The restart latches its cause in channel 77
+            let req = r#"{"jsonrpc":"2.0","id":2,"method":"channels"}"#;
+            let reply = dispatch(req, &access, &mut role, &handle).unwrap();
+            let channels = reply["result"].as_array().unwrap();
+            let chan77 = channels.iter().find(|c| c["channel"] == 0o77).unwrap();
+            assert_eq!(chan77["value"], 0o00100);
+        });
+
+        let mut cpu = Cpu::new(MemoryMap::new_blank());
+        while !client.is_finished() {
+            runtime.poll(&mut cpu);
+            std::thread::yield_now();
+        }
+        client.join().unwrap();
+        assert_eq!(cpu.last_restart(), Some(RestartCause::VoltageFail));
+        assert_eq!(cpu.restart_count(), 1);
+    }
+
+    #[test]
+    fn observers_only_read() {
+        let access = Access {
+            operator: Some("op".to_string()),
+            observer: Some("view".to_string()),
+            public: true,
+        };
+        assert_eq!(access.role(Some("op")), Some(Role::Operator));
+        assert_eq!(access.role(Some("view")), Some(Role::Observer));
+        assert_eq!(access.role(None), Some(Role::Observer));
+        assert_eq!(access.role(Some("guess")), None);
+        assert_eq!(access.role(Some("o")), None);
+        assert_eq!(access.role(Some("opp")), None);
+        assert_eq!(Access::default().role(None), Some(Role::Operator));
+
+        // Refused before reaching the runtime, so none is needed
+        let (_, handle) = crate::runtime::Runtime::new(None, 1);
+        let mut role = access.role(None);
+        for method in ["press_key", "peek", "snapshot"] {
+            let req = format!(r#"{{"jsonrpc":"2.0","id":1,"method":"{}"}}"#, method);
+            let reply = dispatch(&req, &access, &mut role, &handle).unwrap();
+            assert_eq!(reply["error"]["code"], FORBIDDEN);
+        }
+        let req = r#"{"jsonrpc":"2.0","id":2,"method":"auth","params":{"token":"guess"}}"#;
+        let reply = dispatch(req, &access, &mut role, &handle).unwrap();
+        assert_eq!(reply["error"]["code"], UNAUTHORIZED);
+        assert_eq!(role, None);
+    }
+
+    #[test]
+    fn rope_swaps_take_bundled_names_and_a_token() {
+        let (_, handle) = crate::runtime::Runtime::new(None, 1);
+        let swap = |access: &Access, params: &str| {
+            let mut role = Some(Role::Operator);
+            let req = format!(
+                r#"{{"jsonrpc":"2.0","id":1,"method":"load_rope","params":{}}}"#,
+                params
+            );
+            dispatch(&req, access, &mut role, &handle).unwrap()["error"]["code"].clone()
+        };
+        let guarded = Access {
+            operator: Some("op".to_string()),
+            ..Access::default()
+        };
+
+        // Everyone operates, so nobody swaps
+        assert_eq!(
+            swap(&Access::default(), r#"{"rom":"retread50"}"#),
+            FORBIDDEN
+        );
+        // Files on the server are out of reach
+        assert_eq!(swap(&guarded, r#"{"rope":"/etc/passwd"}"#), INVALID_PARAMS);
+        assert_eq!(swap(&guarded, r#"{"rom":"../chaser.bin"}"#), INVALID_PARAMS);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn unix_socket_replaces_only_stale_sockets() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = std::env::temp_dir().join(format!("ragc-control-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("control.sock");
+        let addr = format!("unix:{}", path.display());
+        let (_, handle) = crate::runtime::Runtime::new(None, 1);
+
+        // Anything but a socket is left alone
+        std::fs::write(&path, "keep").unwrap();
+        assert!(super::spawn(&addr, Access::default(), handle.clone()).is_err());
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "keep");
+        std::fs::remove_file(&path).unwrap();
+
+        // A socket nobody listens on is replaced, and only we may connect
+        drop(std::os::unix::net::UnixListener::bind(&path).unwrap());
+        super::spawn(&addr, Access::default(), handle.clone()).unwrap();
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+
+        // A live one is not
+        assert!(super::spawn(&addr, Access::default(), handle).is_err());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn idle_clients_hold_up_nobody() {
+        use std::io::{BufRead, BufReader, Write};
+        use std::os::unix::net::UnixStream;
+
+        let dir = std::env::temp_dir().join(format!("ragc-idle-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("control.sock");
+        let (_, handle) = crate::runtime::Runtime::new(None, 1);
+        let access = Access {
+            operator: Some("op".to_string()),
+            ..Access::default()
+        };
+        super::spawn(&format!("unix:{}", path.display()), access, handle).unwrap();
+
+        // Connected but silent
+        let _idle = UnixStream::connect(&path).unwrap();
+
+        // Answered without the runtime, so none needs polling
+        let mut busy = UnixStream::connect(&path).unwrap();
+        busy.write_all(b"{\"jsonrpc\":\"2.0\",\"id\":1,\"method\":\"auth\",\"params\":{\"token\":\"guess\"}}\n")
+            .unwrap();
+        let mut line = String::new();
+        BufReader::new(busy).read_line(&mut line).unwrap();
+        let reply: serde_json::Value = serde_json::from_str(&line).unwrap();
+        assert_eq!(reply["error"]["code"], UNAUTHORIZED);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,278 @@
+//! Erasable memory as annotated octal text, laid out like the E-memory
+//! dumps in the historical documentation: a header per bank, eight words
+//! to a line after the line's address, and the bank's checksum at the end.
+//! Exported dumps compare line by line against mission transcripts, and a
+//! dump typed in from a document can be loaded back into a snapshot.
+//!
+//! ```text
+//! # Comments run to the end of the line
+//! BANK E0
+//! 0000  00000 00000 00000 00000 00000 00000 00000 00000  # A L Q EBANK
+//! ...
+//! CHECKSUM 01234
+//! ```
+//!
+//! E0-E2 lines carry their CPU addresses; banks E3 and up carry their
+//! address in the 1400-1777 window. An imported dump may leave out lines
+//! or whole banks. A bank's checksum is the ones'-complement sum, with end
+//! around carry, of the words listed under its header.
+use std::io::Write;
+
+use log::{error, info};
+
+use crate::listing::{Location, Symbols};
+use crate::runtime::ERASABLE_BANK_WORDS;
+use crate::snapshot;
+
+/// Words on one dump line
+const LINE_WORDS: usize = 8;
+
+/// Start of the window E3 and up are dumped at
+const WINDOW: usize = 0o1400;
+
+/// Ones'-complement sum of 15-bit words, with end around carry
+pub fn checksum(words: impl IntoIterator<Item = u16>) -> u16 {
+    words.into_iter().fold(0, |sum, word| {
+        let sum = sum as u32 + (word & 0o77777) as u32;
+        match sum > 0o77777 {
+            true => ((sum & 0o77777) + 1) as u16,
+            false => sum as u16,
+        }
+    })
+}
+
+/// Address a bank's word is dumped at
+fn dump_address(bank: usize, offset: usize) -> usize {
+    match bank {
+        0..=2 => bank * ERASABLE_BANK_WORDS + offset,
+        _ => WINDOW + offset,
+    }
+}
+
+/// Write `erasable`, one bank after another, naming the symbols each
+/// unswitched line holds
+pub fn write(
+    erasable: &[[u16; ERASABLE_BANK_WORDS]],
+    symbols: &Symbols,
+    out: &mut dyn Write,
+) -> std::io::Result<()> {
+    writeln!(out, "# Erasable memory, {} banks, octal", erasable.len())?;
+    for (bank, words) in erasable.iter().enumerate() {
+        writeln!(out, "BANK E{}", bank)?;
+        for (line, chunk) in words.chunks(LINE_WORDS).enumerate() {
+            let start = dump_address(bank, line * LINE_WORDS);
+            write!(out, "{:04o} ", start)?;
+            for word in chunk {
+                write!(out, " {:05o}", word)?;
+            }
+            let names: Vec<&str> = match bank {
+                0..=2 => (start..start + chunk.len())
+                    .filter_map(|addr| symbols.get(&Location::Erasable(addr as u16)))
+                    .collect(),
+                _ => Vec::new(),
+            };
+            match names.is_empty() {
+                true => writeln!(out)?,
+                false => writeln!(out, "  # {}", names.join(" "))?,
+            }
+        }
+        writeln!(out, "CHECKSUM {:05o}", checksum(words.iter().copied()))?;
+    }
+    Ok(())
+}
+
+/// Words of a dump as flat erasable addresses and values, checking each
+/// bank's checksum where the dump gives one
+pub fn parse(text: &str) -> Result<Vec<(usize, u16)>, String> {
+    let mut words = Vec::new();
+    let mut bank = None;
+    let mut listed = Vec::new(); // Words under the current header
+    for (idx, line) in text.lines().enumerate() {
+        let err = |e: String| format!("line {}: {}", idx + 1, e);
+        let line = line.split('#').next().unwrap_or_default().trim();
+        let mut fields = line.split_whitespace();
+        match fields.next() {
+            None => {}
+            Some("BANK") => {
+                let name = fields.next().unwrap_or_default();
+                let number = name
+                    .strip_prefix('E')
+                    .and_then(|n| n.parse::<usize>().ok())
+                    .filter(|&n| n < ragc_core::constants::MEMORY_SEGMENTS);
+                bank = Some(number.ok_or_else(|| err(format!("No erasable bank {}", name)))?);
+                listed.clear();
+            }
+            Some("CHECKSUM") => {
+                let bank = bank.ok_or_else(|| err("CHECKSUM before any BANK".into()))?;
+                let expected = fields
+                    .next()
+                    .and_then(|sum| u16::from_str_radix(sum, 8).ok())
+                    .ok_or_else(|| err("Expected an octal checksum".into()))?;
+                let sum = checksum(listed.iter().copied());
+                if sum != expected {
+                    return Err(err(format!(
+                        "E{} sums to {:05o}, the dump says {:05o}",
+                        bank, sum, expected
+                    )));
+                }
+            }
+            Some(addr) => {
+                let bank = bank.ok_or_else(|| err("Words before any BANK".into()))?;
+                let addr = usize::from_str_radix(addr, 8)
+                    .map_err(|_| err(format!("Bad address {}", addr)))?;
+                let first = dump_address(bank, 0);
+                if addr < first || addr >= first + ERASABLE_BANK_WORDS {
+                    return Err(err(format!("{:04o} is not in E{}", addr, bank)));
+                }
+                for (idx, field) in fields.enumerate() {
+                    let offset = addr - first + idx;
+                    let word = u16::from_str_radix(field, 8)
+                        .ok()
+                        .filter(|&w| w <= 0o77777)
+                        .ok_or_else(|| err(format!("Bad word {}", field)))?;
+                    if offset >= ERASABLE_BANK_WORDS {
+                        return Err(err(format!("Line runs past the end of E{}", bank)));
+                    }
+                    words.push((bank * ERASABLE_BANK_WORDS + offset, word));
+                    listed.push(word);
+                }
+            }
+        }
+    }
+    Ok(words)
+}
+
+/// Write parsed dump words into `erasable`, e.g. a snapshot's
+pub fn apply(words: &[(usize, u16)], erasable: &mut [[u16; ERASABLE_BANK_WORDS]]) {
+    for &(flat, word) in words {
+        if let Some(bank) = erasable.get_mut(flat / ERASABLE_BANK_WORDS) {
+            bank[flat % ERASABLE_BANK_WORDS] = word;
+        }
+    }
+}
+
+/// The `erasable` subcommand and its options
+pub fn subcommand<'a, 'b>() -> clap::App<'a, 'b> {
+    clap::SubCommand::with_name("erasable")
+        .about("Convert erasable memory to and from octal dumps")
+        .setting(clap::AppSettings::SubcommandRequiredElseHelp)
+        .subcommand(
+            clap::SubCommand::with_name("export")
+                .about("Write a snapshot's erasable memory as an annotated octal dump")
+                .arg(
+                    clap::Arg::with_name("snapshot")
+                        .required(true)
+                        .help("Snapshot to dump"),
+                )
+                .arg(
+                    clap::Arg::with_name("symbols")
+                        .long("symbols")
+                        .takes_value(true)
+                        .help("Symbol names to annotate lines with, one `NAME ADDRESS` per line"),
+                )
+                .arg(
+                    clap::Arg::with_name("out")
+                        .long("out")
+                        .takes_value(true)
+                        .help("Dump file (default: stdout)"),
+                ),
+        )
+        .subcommand(
+            clap::SubCommand::with_name("import")
+                .about("Load an octal dump into a snapshot, e.g. to reproduce a documented state")
+                .arg(
+                    clap::Arg::with_name("dump")
+                        .required(true)
+                        .help("Dump file; checksums it gives are checked"),
+                )
+                .arg(
+                    clap::Arg::with_name("snapshot")
+                        .required(true)
+                        .help("Snapshot supplying everything the dump leaves out"),
+                )
+                .arg(
+                    clap::Arg::with_name("out")
+                        .long("out")
+                        .takes_value(true)
+                        .required(true)
+                        .help("Snapshot to write, for the console's load command"),
+                ),
+        )
+}
+
+/// Runs `erasable export` or `erasable import`, exiting with status 1 if
+/// it fails
+pub fn command(args: &clap::ArgMatches) {
+    let result = match args.subcommand() {
+        ("export", Some(args)) => export(args),
+        ("import", Some(args)) => import(args),
+        _ => Ok(()),
+    };
+    if let Err(e) = result {
+        error!("Erasable dump failed: {}", e);
+        std::process::exit(1);
+    }
+}
+
+/// Writes a snapshot's erasable memory as an octal dump
+fn export(args: &clap::ArgMatches) -> Result<(), String> {
+    let snap = snapshot::load(args.value_of("snapshot").unwrap_or_default())?;
+    let symbols = crate::load_symbols(args.value_of("symbols"))?;
+    let mut out: Box<dyn Write> = match args.value_of("out") {
+        Some(path) => Box::new(std::io::BufWriter::new(
+            std::fs::File::create(path).map_err(|e| format!("{}: {}", path, e))?,
+        )),
+        None => Box::new(std::io::BufWriter::new(std::io::stdout())),
+    };
+    write(&snap.erasable, &symbols, &mut out)
+        .and_then(|_| out.flush())
+        .map_err(|e| e.to_string())
+}
+
+/// Loads an octal dump into a copy of a snapshot
+fn import(args: &clap::ArgMatches) -> Result<(), String> {
+    let path = args.value_of("dump").unwrap_or_default();
+    let text = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+    let words = parse(&text).map_err(|e| format!("{}: {}", path, e))?;
+    let mut snap = snapshot::load(args.value_of("snapshot").unwrap_or_default())?;
+    apply(&words, &mut snap.erasable);
+    let out = args.value_of("out").unwrap_or_default();
+    snapshot::save(out, &snap)?;
+    info!("Wrote {} erasable words into {}", words.len(), out);
+    Ok(())
+}
+
+#[cfg(test)]
+mod edump_tests {
+    use super::{apply, checksum, parse, write};
+    use crate::listing::Symbols;
+    use crate::runtime::ERASABLE_BANK_WORDS;
+
+    #[test]
+    fn dumps_read_back_and_checksums_catch_typos() {
+        assert_eq!(checksum([0o77776, 0o3]), 0o2);
+        let mut erasable = vec![[0; ERASABLE_BANK_WORDS]; 8];
+        erasable[0][0o67] = 0o12345;
+        erasable[5][0o3] = 0o77777;
+        let symbols = Symbols::parse("NEWJOB 0067").unwrap();
+
+        let mut text = Vec::new();
+        write(&erasable, &symbols, &mut text).unwrap();
+        let text = String::from_utf8(text).unwrap();
+        assert!(text.contains("0060  00000 00000 00000 00000 00000 00000 00000 12345  # NEWJOB"));
+        assert!(text.contains("BANK E5\n1400  00000 00000 00000 77777"));
+
+        let mut restored = vec![[0o1; ERASABLE_BANK_WORDS]; 8];
+        apply(&parse(&text).unwrap(), &mut restored);
+        assert_eq!(restored, erasable);
+
+        // A partial dump, as typed in from a document
+        let words = parse("BANK E3\n1402 00007 00010 # FOO\nCHECKSUM 00017\n").unwrap();
+        assert_eq!(words, vec![(3 * 256 + 2, 0o7), (3 * 256 + 3, 0o10)]);
+        let typo = parse("BANK E3\n1402 00007 00011\nCHECKSUM 00017\n").unwrap_err();
+        assert!(typo.starts_with("line 3: E3 sums to 00020"), "{}", typo);
+        assert!(parse("BANK E1\n0100 00001\n").is_err()); // 0100 is in E0
+        assert!(parse("0000 00001\n").is_err());
+        assert!(parse("BANK E0\n0377 00001 00002\n").is_err());
+    }
+}
@@ -0,0 +1,58 @@
+//! `--log-entry`: log each entry to a routine with its caller
+use log::info;
+
+use ragc_core::hooks::{FixedAddress, Hook, HookContext};
+use ragc_core::memory::MemoryMap;
+
+use crate::listing::Symbols;
+
+/// The `--log-entry` option
+pub fn arg<'a, 'b>() -> clap::Arg<'a, 'b> {
+    clap::Arg::with_name("log-entry")
+        .long("log-entry")
+        .takes_value(true)
+        .multiple(true)
+        .number_of_values(1)
+        .help("Log each entry to a routine (symbol or fixed address) with its caller (repeatable)")
+}
+
+/// An entry log for each `--log-entry`, with where to hook it
+pub fn from_args(
+    matches: &clap::ArgMatches,
+    symbols: &Symbols,
+) -> Result<Vec<(FixedAddress, EntryLog)>, String> {
+    let names = matches.values_of("log-entry").into_iter().flatten();
+    names
+        .map(|name| match symbols.fixed(name) {
+            Some(at) => Ok((
+                at,
+                EntryLog {
+                    name: name.to_string(),
+                },
+            )),
+            None => Err(format!("Not a fixed-memory symbol or address: {}", name)),
+        })
+        .collect()
+}
+
+/// Logs entries to a routine for `--log-entry`, with the caller's return
+/// address and the word there (the inline argument of ALARM, BANKCALL...)
+pub struct EntryLog {
+    name: String,
+}
+
+impl Hook for EntryLog {
+    fn call(&mut self, ctx: &HookContext, mem: &MemoryMap) {
+        info!(
+            "{} from {:02o}:{:04o} at {} MCTs: A={:05o} L={:05o} Q={:05o} arg={:05o}",
+            self.name,
+            ctx.fb >> 10,
+            ctx.q.wrapping_sub(1),
+            ctx.cycles,
+            ctx.a,
+            ctx.l,
+            ctx.q,
+            mem.read(ctx.q as usize)
+        );
+    }
+}
@@ -0,0 +1,243 @@
+//! What the flight software's executive and waitlist have scheduled, read
+//! from erasable through the rope's symbol table. Jobs live in core sets
+//! of 12 words, the running job's in the first; PRIORITY holds the
+//! priority above bit 9, complemented while the job sleeps and -0 when
+//! the set is free. Waitlist tasks are 2CADRs in LST2, the first due when
+//! TIME3 overflows and each later one the -DT in LST1 after the one before.
+use std::io::Write;
+
+use ragc_core::constants::timers::TIMER_3_ADDRESS;
+use ragc_core::memory::AddressSpace;
+use ragc_core::symbols::{ErasableAddress, SymbolTable};
+
+use crate::listing::{Location, Symbols};
+use crate::runtime::ERASABLE_BANK_WORDS;
+
+const CORE_SETS: usize = 7;
+const CORE_SET_WORDS: usize = 12;
+const TASKS: usize = 9;
+
+/// Where the executive's and waitlist's variables are in this rope
+pub struct Layout {
+    priority: ErasableAddress, // PRIORITY of the first core set
+    loc: ErasableAddress,
+    bankset: ErasableAddress,
+    lst1: ErasableAddress,
+    lst2: ErasableAddress,
+    endtask: Option<Location>, // Fills empty waitlist slots
+}
+
+impl Layout {
+    pub fn new(symbols: &Symbols) -> Result<Self, String> {
+        let find = |name: &str| {
+            symbols
+                .erasable(name)
+                .ok_or_else(|| format!("{} is not in the symbol table", name))
+        };
+        Ok(Self {
+            priority: find("PRIORITY")?,
+            loc: find("LOC")?,
+            bankset: find("BANKSET")?,
+            lst1: find("LST1")?,
+            lst2: find("LST2")?,
+            endtask: symbols
+                .fixed("ENDTASK")
+                .map(|at| Location::Fixed(at.bank, at.offset)),
+        })
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum JobState {
+    Running,
+    Ready,
+    Sleeping,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Job {
+    pub core_set: usize,
+    pub priority: u16,
+    pub state: JobState,
+    pub entry: Option<Location>, // Where the job resumes
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Task {
+    pub due: u32, // Centiseconds from now
+    pub entry: Option<Location>,
+}
+
+/// Jobs in the core sets, free ones left out
+pub fn jobs(layout: &Layout, erasable: &[[u16; ERASABLE_BANK_WORDS]]) -> Vec<Job> {
+    let word = |addr: ErasableAddress, set: usize| {
+        let addr = addr.offset_by(set * CORE_SET_WORDS);
+        erasable[addr.bank][addr.offset]
+    };
+    (0..CORE_SETS)
+        .filter_map(|set| {
+            let priority = word(layout.priority, set);
+            let (priority, state) = match priority {
+                0o77777 => return None,
+                p if p & 0o40000 != 0 => (!p & 0o77777, JobState::Sleeping),
+                p if set == 0 => (p, JobState::Running),
+                p => (p, JobState::Ready),
+            };
+            Some(Job {
+                core_set: set,
+                priority: priority >> 9,
+                state,
+                entry: fixed_location(word(layout.loc, set), word(layout.bankset, set)),
+            })
+        })
+        .collect()
+}
+
+/// Waitlist tasks in the order they are due, empty slots left out
+pub fn tasks(layout: &Layout, erasable: &[[u16; ERASABLE_BANK_WORDS]]) -> Vec<Task> {
+    let word = |addr: ErasableAddress, n: usize| {
+        let addr = addr.offset_by(n);
+        erasable[addr.bank][addr.offset]
+    };
+    let time3 = erasable[0][TIMER_3_ADDRESS] & 0o37777;
+    let mut due = 0o40000 - time3 as u32;
+    let mut tasks = Vec::new();
+    for n in 0..TASKS {
+        if n > 0 {
+            due += minus_dt(word(layout.lst1, n - 1));
+        }
+        let entry = fixed_location(word(layout.lst2, 2 * n), word(layout.lst2, 2 * n + 1));
+        if entry.is_some() && entry == layout.endtask {
+            break;
+        }
+        tasks.push(Task { due, entry });
+    }
+    tasks
+}
+
+/// Centiseconds in a -DT word of LST1
+fn minus_dt(word: u16) -> u32 {
+    match word & 0o40000 {
+        0 => 0, // Never written, or not a -DT
+        _ => (!word & 0o37777) as u32,
+    }
+}
+
+/// Location of an address with the bank bits in force for it, e.g. a 2CADR
+pub fn fixed_location(addr: u16, bbank: u16) -> Option<Location> {
+    let addr = AddressSpace::s_register(addr);
+    match AddressSpace::from_fb(bbank).locate(addr) {
+        Some(at) => Location::from_space(at),
+        None => Location::resolve(addr as u16, 0),
+    }
+}
+
+pub fn write_schedule(
+    layout: &Layout,
+    erasable: &[[u16; ERASABLE_BANK_WORDS]],
+    symbols: &Symbols,
+    out: &mut dyn Write,
+) -> std::io::Result<()> {
+    let name = |entry: Option<Location>| match entry {
+        Some(at) => format!(
+            "{:<8} {}",
+            at.to_string(),
+            symbols.describe(&at).unwrap_or_default()
+        ),
+        None => "-".to_string(),
+    };
+    writeln!(out, "SET PRIO STATE    ENTRY")?;
+    for job in jobs(layout, erasable) {
+        let line = format!(
+            "{:>3} {:>4o} {:<8} {}",
+            job.core_set,
+            job.priority,
+            format!("{:?}", job.state).to_lowercase(),
+            name(job.entry)
+        );
+        writeln!(out, "{}", line.trim_end())?;
+    }
+    writeln!(out, "\nDUE (s)  TASK")?;
+    for task in tasks(layout, erasable) {
+        let line = format!("{:>7.2}  {}", task.due as f64 / 100.0, name(task.entry));
+        writeln!(out, "{}", line.trim_end())?;
+    }
+    Ok(())
+}
+
+/// The core sets as raw words from `base`, for a rope whose symbols don't
+/// place the executive. Words past the end of the bank print as dashes.
+pub fn write_core_sets_raw(
+    base: ErasableAddress,
+    erasable: &[[u16; ERASABLE_BANK_WORDS]],
+    out: &mut dyn Write,
+) -> std::io::Result<()> {
+    for set in 0..CORE_SETS {
+        let words: Vec<_> = (0..CORE_SET_WORDS)
+            .map(|n| {
+                let addr = base.offset_by(set * CORE_SET_WORDS + n);
+                match erasable
+                    .get(addr.bank)
+                    .and_then(|bank| bank.get(addr.offset))
+                {
+                    Some(word) => format!("{:05o}", word),
+                    None => "-----".to_string(),
+                }
+            })
+            .collect();
+        writeln!(out, "{:>3} {}", set, words.join(" "))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod executive_tests {
+    use super::{jobs, tasks, write_core_sets_raw, write_schedule, JobState, Layout};
+    use crate::listing::{Location, Symbols};
+
+    #[test]
+    fn decodes_core_sets_and_waitlist() {
+        let symbols = Symbols::parse(
+            "PRIORITY 0100\nLOC 0101\nBANKSET 0102\nLST1 0300\nLST2 0310\n\
+             ENDTASK 4500\nSERVICER 22,2000\nT4RUPT 4200",
+        )
+        .unwrap();
+        let layout = Layout::new(&symbols).unwrap();
+        let mut erasable = vec![[0o77777; 256]; 8];
+        let mut core_set = |set: usize, priority: u16, loc: u16, bankset: u16| {
+            let words = &mut erasable[0][0o100 + 12 * set..];
+            words[..3].copy_from_slice(&[priority, loc, bankset]);
+        };
+        core_set(0, 0o20000, 0o2007, 0o22 << 10);
+        core_set(2, !0o3000 & 0o77777, 0o4201, 0);
+        erasable[0][0o26] = 0o37700; // TIME3: 64 cs to go
+        erasable[0][0o300..0o302].copy_from_slice(&[!30 & 0o77777, 0o40000]);
+        erasable[0][0o310..0o316].copy_from_slice(&[0o4200, 0, 0o2000, 0o22 << 10, 0o4500, 0]);
+
+        let jobs = jobs(&layout, &erasable);
+        assert_eq!(jobs.len(), 2);
+        assert_eq!((jobs[0].priority, jobs[0].state), (0o20, JobState::Running));
+        assert_eq!(jobs[0].entry, Some(Location::Fixed(0o22, 7)));
+        assert_eq!((jobs[1].core_set, jobs[1].state), (2, JobState::Sleeping));
+        assert_eq!(jobs[1].priority, 3);
+
+        let tasks = tasks(&layout, &erasable);
+        assert_eq!(tasks.len(), 2);
+        assert_eq!((tasks[0].due, tasks[1].due), (64, 94));
+
+        let mut out = Vec::new();
+        write_schedule(&layout, &erasable, &symbols, &mut out).unwrap();
+        let text = String::from_utf8(out).unwrap();
+        assert!(text.contains("  0   20 running  22,2007  SERVICER+7"));
+        assert!(text.contains("  2    3 sleeping 4201     T4RUPT+1"));
+        assert!(text.contains("   0.94  22,2000  SERVICER"));
+
+        // Without the symbols, the same core sets by address
+        let mut out = Vec::new();
+        let base = ragc_core::symbols::ErasableAddress::new(0, 0o100);
+        write_core_sets_raw(base, &erasable, &mut out).unwrap();
+        let text = String::from_utf8(out).unwrap();
+        assert!(text.starts_with("  0 20000 02007 44000 77777"));
+        assert!(text.contains("\n  2 74777 04201 00000 77777"));
+    }
+}
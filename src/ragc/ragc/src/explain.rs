@@ -0,0 +1,258 @@
+//! Explain mode for teaching tools: a JSON line per step telling what the
+//! instruction did in plain terms, the operand it fetched, the registers
+//! it changed and why a branch went the way it did. Interrupts and
+//! counter updates get a line too, as they take time from the program.
+use std::io::Write;
+
+use serde_json::{json, Map, Value};
+
+use ragc_core::constants::registers::{
+    REGISTER_ACCUMULATOR, REGISTER_COUNTER, REGISTER_ERASABLE_BANK, REGISTER_FIXED_BANK,
+    REGISTER_LINK, REGISTER_RETURN,
+};
+use ragc_core::cpu::{Cpu, StepResult};
+use ragc_core::disasm::{disassemble, Disassembled, Operand};
+use ragc_core::instructions::Mnemonic;
+use ragc_core::memory::Location;
+
+use crate::listing::{self, Symbols};
+
+/// Emulation speed explain mode runs at unless told otherwise: a few
+/// instructions a second, slow enough to follow
+pub const EXPLAIN_SPEED: f64 = 0.0005;
+
+/// Registers compared before and after each step
+const REGISTERS: [(&str, usize); 5] = [
+    ("A", REGISTER_ACCUMULATOR),
+    ("L", REGISTER_LINK),
+    ("Q", REGISTER_RETURN),
+    ("EB", REGISTER_ERASABLE_BANK),
+    ("FB", REGISTER_FIXED_BANK),
+];
+
+/// The machine as a step found it
+pub struct Before {
+    mct: usize,
+    pc: u16,
+    inst: Option<Disassembled>,
+    registers: [u16; REGISTERS.len()],
+    operand: Option<u16>, // Memory operand or channel value
+}
+
+impl Before {
+    pub fn capture(cpu: &Cpu) -> Self {
+        let mem = cpu.memory();
+        let pc = mem.read(REGISTER_COUNTER);
+        let inst = disassemble(mem.read(pc as usize), cpu.ec_flag);
+        Self {
+            mct: cpu.total_cycles,
+            pc,
+            inst,
+            registers: REGISTERS.map(|(_, idx)| mem.read(idx)),
+            operand: inst.and_then(|inst| operand(cpu, &inst)),
+        }
+    }
+}
+
+/// Value of the operand `inst` reads or writes; branches have none
+fn operand(cpu: &Cpu, inst: &Disassembled) -> Option<u16> {
+    match inst.operand {
+        _ if inst.branch_target().is_some() => None,
+        Operand::Memory(k) => Some(cpu.memory().read(k as usize)),
+        Operand::Channel(ch) => Some(cpu.memory().peek_channel(ch as usize)),
+        Operand::None => None,
+    }
+}
+
+/// What an instruction does, in a phrase
+fn action(mnem: Mnemonic) -> &'static str {
+    match mnem {
+        Mnemonic::AD => "Add the operand to A",
+        Mnemonic::ADS => "Add A to the operand, leaving the sum in both",
+        Mnemonic::AUG => "Move the operand one further from zero",
+        Mnemonic::BZF => "Branch if A is zero",
+        Mnemonic::BZMF => "Branch if A is zero or negative",
+        Mnemonic::CA => "Copy the operand into A",
+        Mnemonic::CS => "Copy the operand's complement into A",
+        Mnemonic::CCS => "Count the operand down into A and skip by its sign",
+        Mnemonic::DAS => "Add A,L to the operand's double word",
+        Mnemonic::DCA => "Copy the operand's double word into A,L",
+        Mnemonic::DCS => "Copy the complement of the operand's double word into A,L",
+        Mnemonic::DIM => "Move the operand one nearer zero",
+        Mnemonic::DV => "Divide A,L by the operand",
+        Mnemonic::DXCH => "Exchange A,L with the operand's double word",
+        Mnemonic::EDRUPT => "Enter an interrupt, for testing",
+        Mnemonic::EXTEND => "Make the next instruction an extracode",
+        Mnemonic::INCR => "Add one to the operand",
+        Mnemonic::INDEX => "Add the operand to the next instruction",
+        Mnemonic::INHINT => "Inhibit interrupts",
+        Mnemonic::LXCH => "Exchange L with the operand",
+        Mnemonic::MASK => "AND the operand into A",
+        Mnemonic::MP => "Multiply A by the operand into A,L",
+        Mnemonic::MSU => "Subtract the operand from A modulo its width",
+        Mnemonic::QXCH => "Exchange Q with the operand",
+        Mnemonic::RAND => "AND the channel into A",
+        Mnemonic::READ => "Copy the channel into A",
+        Mnemonic::RELINT => "Allow interrupts",
+        Mnemonic::RESUME => "Return from the interrupt",
+        Mnemonic::ROR => "OR the channel into A",
+        Mnemonic::RXOR => "Exclusive-OR the channel into A",
+        Mnemonic::SU => "Subtract the operand from A",
+        Mnemonic::TC => "Call: leave the return address in Q and jump",
+        Mnemonic::TCF => "Jump",
+        Mnemonic::TS => "Store A in the operand, skipping on overflow",
+        Mnemonic::WAND => "AND A into the channel",
+        Mnemonic::WOR => "OR A into the channel",
+        Mnemonic::WRITE => "Copy A to the channel",
+        Mnemonic::XCH => "Exchange A with the operand",
+        _ => "Not an instruction",
+    }
+}
+
+/// Why control went where it did, for instructions that can branch
+fn branch(before: &Before, inst: &Disassembled, next: u16) -> Option<String> {
+    let taken = Some(next) == inst.branch_target();
+    let why = match inst.mnem {
+        Mnemonic::TC | Mnemonic::TCF => "always taken".to_string(),
+        Mnemonic::BZF if taken => "taken: A is zero".to_string(),
+        Mnemonic::BZF => "not taken: A is not zero".to_string(),
+        Mnemonic::BZMF if taken => "taken: A is zero or negative".to_string(),
+        Mnemonic::BZMF => "not taken: A is positive".to_string(),
+        Mnemonic::CCS => {
+            let skip = next.wrapping_sub(before.pc).wrapping_sub(1);
+            let sign = ["positive", "+0", "negative", "-0"].get(skip as usize);
+            format!("skipped {}: the operand was {}", skip, sign.unwrap_or(&"?"))
+        }
+        _ => return None,
+    };
+    Some(why)
+}
+
+/// Explanation of one step as a JSON object
+pub fn explain(before: &Before, cpu: &Cpu, step: &StepResult, symbols: &Symbols) -> Value {
+    let mem = cpu.memory();
+    let next = mem.read(REGISTER_COUNTER);
+    let mut record = Map::new();
+    record.insert("mct".into(), json!(before.mct));
+    record.insert("pc".into(), json!(format!("{:04o}", before.pc)));
+    if let Some(name) = name(cpu, before.pc, symbols) {
+        record.insert("at".into(), json!(name));
+    }
+    record.insert("cycles".into(), json!(step.cycles));
+
+    match (step.took_interrupt, step.unprogrammed, before.inst) {
+        (true, _, _) => {
+            let does = "Take an interrupt: save Z and the instruction, jump to its vector";
+            record.insert("does".into(), json!(does));
+        }
+        (_, true, _) => {
+            let does = "Update a counter, stealing a memory cycle from the program";
+            record.insert("does".into(), json!(does));
+        }
+        (_, _, Some(inst)) => {
+            record.insert("inst".into(), json!(inst.to_string().trim_end()));
+            record.insert("does".into(), json!(action(inst.mnem)));
+            if let Some(value) = before.operand {
+                let mut operand = json!({ "before": format!("{:05o}", value) });
+                let after = self::operand(cpu, &inst).unwrap_or(value);
+                if after != value {
+                    operand["after"] = json!(format!("{:05o}", after));
+                }
+                record.insert("operand".into(), operand);
+            }
+            if let Some(why) = branch(before, &inst, next) {
+                record.insert("branch".into(), json!(why));
+            }
+        }
+        (_, _, None) => {
+            record.insert("does".into(), json!("Not an instruction"));
+        }
+    }
+
+    let mut changed = Map::new();
+    for (n, (reg, idx)) in REGISTERS.iter().enumerate() {
+        let (old, new) = (before.registers[n], mem.read(*idx));
+        if old != new {
+            let change = [format!("{:05o}", old), format!("{:05o}", new)];
+            changed.insert(reg.to_string(), json!(change));
+        }
+    }
+    if !changed.is_empty() {
+        record.insert("changed".into(), Value::Object(changed));
+    }
+    record.insert("next".into(), json!(format!("{:04o}", next)));
+    if let Some(fault) = step.fault {
+        record.insert("fault".into(), json!(format!("{:?}", fault)));
+    }
+    Value::Object(record)
+}
+
+/// Symbol naming `pc`, where it is in fixed memory
+fn name(cpu: &Cpu, pc: u16, symbols: &Symbols) -> Option<String> {
+    match cpu.memory().locate(pc as usize)? {
+        Location::Fixed(at) => symbols.describe(&listing::Location::Fixed(at.bank, at.offset)),
+        _ => None,
+    }
+}
+
+/// Writes explanations for `--explain`
+pub struct Explainer {
+    out: Box<dyn Write>,
+    symbols: Symbols,
+}
+
+impl Explainer {
+    /// Explain to `path`, or standard output for `-`
+    pub fn create(path: &str, symbols: Symbols) -> Result<Self, String> {
+        let out: Box<dyn Write> = match path {
+            "-" => Box::new(std::io::stdout()),
+            _ => Box::new(std::fs::File::create(path).map_err(|e| format!("{}: {}", path, e))?),
+        };
+        Ok(Self { out, symbols })
+    }
+
+    pub fn record(&mut self, before: &Before, cpu: &Cpu, step: &StepResult) {
+        let line = explain(before, cpu, step, &self.symbols);
+        let _ = writeln!(self.out, "{}", line).and_then(|_| self.out.flush());
+    }
+}
+
+#[cfg(test)]
+mod explain_tests {
+    use super::{explain, Before};
+    use crate::listing::Symbols;
+    use ragc_core::cpu::Cpu;
+    use ragc_core::memory::MemoryMapBuilder;
+    use ragc_testutils::rope_image;
+    use serde_json::json;
+
+    #[test]
+    fn explains_operands_changes_and_branches() {
+        // 4000: CA 4010; EXTEND; BZF 4000; TCF 4000. 4010: 5
+        let mut words = [0; 0o11];
+        words[..4].copy_from_slice(&[0o34010, 0o00006, 0o14000, 0o14000]);
+        words[0o10] = 5;
+        let rope = rope_image(0o4000, &words);
+        let mut cpu = Cpu::new(MemoryMapBuilder::new().rope(&rope).build());
+        let symbols = Symbols::parse("START 4000\nFIVE 4010").unwrap();
+        let step = |cpu: &mut Cpu| {
+            let before = Before::capture(cpu);
+            let step = cpu.step();
+            explain(&before, cpu, &step, &symbols)
+        };
+
+        let ca = step(&mut cpu);
+        assert_eq!(ca["at"], "START");
+        assert_eq!(ca["inst"], "CA      4010");
+        assert_eq!(ca["does"], "Copy the operand into A");
+        assert_eq!(ca["operand"], json!({ "before": "00005" }));
+        assert_eq!(ca["changed"]["A"], json!(["00000", "00005"]));
+        assert_eq!(ca["next"], "4001");
+
+        step(&mut cpu);
+        let bzf = step(&mut cpu);
+        assert_eq!(bzf["branch"], "not taken: A is not zero");
+        assert!(bzf.get("changed").is_none());
+        assert_eq!(step(&mut cpu)["branch"], "always taken");
+    }
+}
@@ -0,0 +1,357 @@
+use log::{error, info};
+
+use ragc_core::constants::ports::{CHANNEL_CHAN34, CHANNEL_CHAN35};
+use ragc_core::memory::mods::MCT_SECONDS;
+use ragc_core::memory::MemoryMap;
+
+use crate::timeline::Marker;
+use crate::vardb::{Var, VarDb};
+
+/// Where the flight recorder keeps what it collects, stamped with emulated
+/// seconds since power-on
+pub trait FlightSink {
+    /// Timeline markers, alarms and other named events
+    fn event(&mut self, t: f64, kind: &str, detail: &str) -> Result<(), String>;
+
+    /// A channel written with a new value
+    fn channel(&mut self, t: f64, channel: usize, value: u16) -> Result<(), String>;
+
+    /// A downlink word pair (channels 34 and 35)
+    fn telemetry(&mut self, t: f64, words: [u16; 2]) -> Result<(), String>;
+
+    /// One value of a variable; vectors give one per component
+    fn sample(&mut self, t: f64, name: &str, idx: usize, value: f64) -> Result<(), String>;
+
+    /// Make everything recorded so far durable
+    fn flush(&mut self) -> Result<(), String>;
+}
+
+/// Flight recorder for `--flight-recorder <file>`: channel changes, downlink
+/// words and periodic samples of the variable database, kept in a store
+/// that post-run analysis can query, e.g. with SQL:
+///
+/// ```sql
+/// SELECT t, value FROM channels WHERE channel = 9
+///   AND t BETWEEN (SELECT t FROM events WHERE detail = 'PDI')
+///             AND (SELECT t FROM events WHERE detail = 'TOUCHDOWN');
+/// ```
+pub struct FlightRecorder {
+    sink: Option<Box<dyn FlightSink>>,
+    vars: Vec<Var>,
+    period_mcts: usize,
+    next: usize,                  // MCT count of the next sample
+    channels: [Option<u16>; 256], // Last value written, to record changes only
+    downlink: Option<u16>,        // Channel 34 word waiting for its pair
+}
+
+impl FlightRecorder {
+    /// Record into `sink`, sampling every variable of `db` at `rate_hz`
+    pub fn new(sink: Box<dyn FlightSink>, db: &VarDb, rate_hz: f64) -> Self {
+        Self {
+            sink: Some(sink),
+            vars: db.vars().to_vec(),
+            period_mcts: ((1.0 / rate_hz / MCT_SECONDS) as usize).max(1),
+            next: 0,
+            channels: [None; 256],
+            downlink: None,
+        }
+    }
+
+    /// Record into a SQLite database at `path`, replacing its tables
+    pub fn create(path: &str, db: &VarDb, rate_hz: f64) -> Result<Self, String> {
+        if rate_hz <= 0.0 {
+            return Err("sample rate must be positive".to_string());
+        }
+        let sink = sqlite::open(path).map_err(|e| format!("{}: {}", path, e))?;
+        info!(
+            "Flight recorder sampling {} variables at {} Hz",
+            db.vars().len(),
+            rate_hz
+        );
+        Ok(Self::new(sink, db, rate_hz))
+    }
+
+    /// Start journaling the channel writes `poll` records
+    pub fn attach(&self, mem: &mut MemoryMap) {
+        mem.set_channel_journal(true);
+    }
+
+    /// Record the channel writes since the last poll, and a sample of the
+    /// variables if one is due at `total_cycles`
+    pub fn poll(&mut self, mem: &mut MemoryMap, total_cycles: usize) {
+        let t = total_cycles as f64 * MCT_SECONDS;
+        let mut writes = Vec::new();
+        mem.drain_channel_journal(|channel, value| writes.push((channel, value)));
+        for (channel, value) in writes {
+            self.write(t, channel, value);
+        }
+
+        if total_cycles < self.next {
+            return;
+        }
+        self.next = total_cycles + self.period_mcts;
+        for var in self.vars.iter() {
+            for (idx, value) in var.read(mem).into_iter().enumerate() {
+                if let Some(sink) = &mut self.sink {
+                    let result = sink.sample(t, &var.name, idx, value);
+                    Self::check(&mut self.sink, result);
+                }
+            }
+        }
+    }
+
+    fn write(&mut self, t: f64, channel: usize, value: u16) {
+        let result = match channel {
+            CHANNEL_CHAN34 => {
+                self.downlink = Some(value);
+                Ok(())
+            }
+            CHANNEL_CHAN35 => match (self.downlink.take(), &mut self.sink) {
+                (Some(first), Some(sink)) => sink.telemetry(t, [first, value]),
+                _ => Ok(()),
+            },
+            _ if self.channels[channel & 0o377] == Some(value) => Ok(()),
+            _ => {
+                self.channels[channel & 0o377] = Some(value);
+                match &mut self.sink {
+                    Some(sink) => sink.channel(t, channel, value),
+                    None => Ok(()),
+                }
+            }
+        };
+        Self::check(&mut self.sink, result);
+    }
+
+    /// Note a named event at `total_cycles`
+    pub fn event(&mut self, total_cycles: usize, kind: &str, detail: &str) {
+        if let Some(sink) = &mut self.sink {
+            let result = sink.event(total_cycles as f64 * MCT_SECONDS, kind, detail);
+            Self::check(&mut self.sink, result);
+        }
+    }
+
+    pub fn mark(&mut self, marker: &Marker) {
+        self.event(marker.cycles, "marker", &marker.name);
+    }
+
+    pub fn finish(mut self) {
+        if let Some(sink) = &mut self.sink {
+            let result = sink.flush();
+            Self::check(&mut self.sink, result);
+        }
+    }
+
+    /// A failing store ends the recording without stopping the emulator
+    fn check(sink: &mut Option<Box<dyn FlightSink>>, result: Result<(), String>) {
+        if let Err(e) = result {
+            error!("Flight recorder stopped: {}", e);
+            *sink = None;
+        }
+    }
+}
+
+#[cfg(feature = "sqlite")]
+mod sqlite {
+    use rusqlite::{params, Connection};
+
+    use super::FlightSink;
+
+    /// Rows written per transaction
+    const BATCH: usize = 4096;
+
+    const SCHEMA: &str = "
+        DROP TABLE IF EXISTS events;
+        DROP TABLE IF EXISTS channels;
+        DROP TABLE IF EXISTS telemetry;
+        DROP TABLE IF EXISTS samples;
+        CREATE TABLE events (t REAL NOT NULL, kind TEXT NOT NULL, detail TEXT NOT NULL);
+        CREATE TABLE channels (t REAL NOT NULL, channel INTEGER NOT NULL, value INTEGER NOT NULL);
+        CREATE TABLE telemetry (t REAL NOT NULL, word1 INTEGER NOT NULL, word2 INTEGER NOT NULL);
+        CREATE TABLE samples (
+            t REAL NOT NULL, name TEXT NOT NULL, idx INTEGER NOT NULL, value REAL NOT NULL
+        );
+        CREATE INDEX events_t ON events (t);
+        CREATE INDEX channels_t ON channels (channel, t);
+        CREATE INDEX telemetry_t ON telemetry (t);
+        CREATE INDEX samples_t ON samples (name, t);
+    ";
+
+    struct SqliteSink {
+        conn: Connection,
+        pending: usize, // Rows in the open transaction
+    }
+
+    pub fn open(path: &str) -> Result<Box<dyn FlightSink>, String> {
+        let conn = Connection::open(path).map_err(|e| e.to_string())?;
+        conn.execute_batch(SCHEMA).map_err(|e| e.to_string())?;
+        conn.execute_batch("BEGIN").map_err(|e| e.to_string())?;
+        Ok(Box::new(SqliteSink { conn, pending: 0 }))
+    }
+
+    impl SqliteSink {
+        fn insert(&mut self, sql: &str, row: impl rusqlite::Params) -> Result<(), String> {
+            let mut stmt = self.conn.prepare_cached(sql).map_err(|e| e.to_string())?;
+            stmt.execute(row).map_err(|e| e.to_string())?;
+            drop(stmt);
+            self.pending += 1;
+            if self.pending >= BATCH {
+                self.flush()?;
+            }
+            Ok(())
+        }
+    }
+
+    impl FlightSink for SqliteSink {
+        fn event(&mut self, t: f64, kind: &str, detail: &str) -> Result<(), String> {
+            self.insert(
+                "INSERT INTO events VALUES (?1, ?2, ?3)",
+                params![t, kind, detail],
+            )
+        }
+
+        fn channel(&mut self, t: f64, channel: usize, value: u16) -> Result<(), String> {
+            self.insert(
+                "INSERT INTO channels VALUES (?1, ?2, ?3)",
+                params![t, channel as i64, value],
+            )
+        }
+
+        fn telemetry(&mut self, t: f64, words: [u16; 2]) -> Result<(), String> {
+            self.insert(
+                "INSERT INTO telemetry VALUES (?1, ?2, ?3)",
+                params![t, words[0], words[1]],
+            )
+        }
+
+        fn sample(&mut self, t: f64, name: &str, idx: usize, value: f64) -> Result<(), String> {
+            self.insert(
+                "INSERT INTO samples VALUES (?1, ?2, ?3, ?4)",
+                params![t, name, idx as i64, value],
+            )
+        }
+
+        fn flush(&mut self) -> Result<(), String> {
+            self.pending = 0;
+            self.conn
+                .execute_batch("COMMIT; BEGIN")
+                .map_err(|e| e.to_string())
+        }
+    }
+}
+
+#[cfg(not(feature = "sqlite"))]
+mod sqlite {
+    use super::FlightSink;
+
+    pub fn open(_path: &str) -> Result<Box<dyn FlightSink>, String> {
+        Err("built without the sqlite feature".to_string())
+    }
+}
+
+#[cfg(test)]
+mod flight_tests {
+    use super::{FlightRecorder, FlightSink};
+    use crate::vardb::VarDb;
+    use ragc_core::constants::ports::{CHANNEL_CHAN34, CHANNEL_CHAN35, CHANNEL_DSALMOUT};
+    use ragc_core::memory::MemoryMap;
+    use ragc_core::symbols::ErasableAddress;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    #[derive(Clone, Default)]
+    struct Rows(Rc<RefCell<Vec<String>>>);
+
+    impl Rows {
+        fn push(&self, row: String) -> Result<(), String> {
+            self.0.borrow_mut().push(row);
+            Ok(())
+        }
+    }
+
+    impl FlightSink for Rows {
+        fn event(&mut self, _t: f64, kind: &str, detail: &str) -> Result<(), String> {
+            self.push(format!("{} {}", kind, detail))
+        }
+
+        fn channel(&mut self, _t: f64, channel: usize, value: u16) -> Result<(), String> {
+            self.push(format!("ch{:o} {:o}", channel, value))
+        }
+
+        fn telemetry(&mut self, _t: f64, words: [u16; 2]) -> Result<(), String> {
+            self.push(format!("tm {:o} {:o}", words[0], words[1]))
+        }
+
+        fn sample(&mut self, _t: f64, name: &str, idx: usize, value: f64) -> Result<(), String> {
+            self.push(format!("{}[{}] {}", name, idx, value))
+        }
+
+        fn flush(&mut self) -> Result<(), String> {
+            self.push("flush".to_string())
+        }
+    }
+
+    #[test]
+    fn records_changes_telemetry_and_samples() {
+        let symbols = [("FLAG", ErasableAddress::new(0, 0o100))];
+        let text = "[[vars]]\nname = \"FLAG\"\nformat = \"raw\"\n";
+        let db = VarDb::parse(text, &symbols[..]).unwrap();
+        let rows = Rows::default();
+        let mut recorder = FlightRecorder::new(Box::new(rows.clone()), &db, 1.0);
+
+        let mut mem = MemoryMap::new_blank();
+        recorder.attach(&mut mem);
+        mem.write(0o100, 0o17);
+        for (channel, value) in [
+            (CHANNEL_DSALMOUT, 0o4),
+            (CHANNEL_DSALMOUT, 0o4),
+            (CHANNEL_CHAN34, 0o1),
+            (CHANNEL_CHAN35, 0o2),
+            (CHANNEL_DSALMOUT, 0o0),
+        ] {
+            mem.write_io(channel, value);
+        }
+        recorder.poll(&mut mem, 100);
+        recorder.poll(&mut mem, 200); // Next sample only after a second
+        recorder.event(300, "alarm", "01202");
+        recorder.finish();
+
+        assert_eq!(
+            *rows.0.borrow(),
+            [
+                "ch11 4",
+                "tm 1 2",
+                "ch11 0",
+                "FLAG[0] 15",
+                "alarm 01202",
+                "flush"
+            ]
+        );
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[test]
+    fn sqlite_recording_is_queryable() {
+        let path = std::env::temp_dir().join("ragc-flight-test.sqlite");
+        let path = path.to_str().unwrap();
+        let mut recorder = FlightRecorder::create(path, &VarDb::default(), 1.0).unwrap();
+        let mut mem = MemoryMap::new_blank();
+        recorder.attach(&mut mem);
+        recorder.event(100, "marker", "PDI");
+        mem.write_io(CHANNEL_DSALMOUT, 0o4);
+        recorder.poll(&mut mem, 200);
+        recorder.event(300, "marker", "TOUCHDOWN");
+        recorder.finish();
+
+        let conn = rusqlite::Connection::open(path).unwrap();
+        let value: u16 = conn
+            .query_row(
+                "SELECT value FROM channels WHERE channel = 9 AND t BETWEEN \
+                 (SELECT t FROM events WHERE detail = 'PDI') AND \
+                 (SELECT t FROM events WHERE detail = 'TOUCHDOWN')",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(value, 0o4);
+    }
+}
@@ -0,0 +1,236 @@
+//! Ground-support equipment monitor port. The real computer was bench
+//! tested through its test connector: the monitor watched registers go by
+//! and forced TCSAJ, FETCH and STORE sequences into the machine. This port
+//! does the same over a socket, one command per line, octal throughout:
+//!
+//! ```text
+//! FETCH 1030            FETCH 1030 00042
+//! STORE 1030 42         STORE 1030 00042
+//! TCSAJ 4000            TCSAJ 4000
+//! WATCH A Z 1030 @20    W 0.050 A=00000 Z=04012 1030=00042, 20 a second
+//! STOP                  OK, ends the watch
+//! ```
+//!
+//! Anything else is answered `ERROR <message>`. Watch rates are in
+//! samples per host second, 10 by default.
+use std::io::{BufRead, BufReader, ErrorKind, Write};
+use std::net::{TcpListener, TcpStream};
+use std::time::{Duration, Instant};
+
+use log::{error, info, warn};
+
+use ragc_core::cpu::UnprogSequence;
+
+use crate::runtime::RuntimeHandle;
+
+/// Highest address the monitor reaches
+const ADDRESS_MAX: usize = 0o7777;
+
+/// Central registers by the names the monitor shows them under
+const REGISTERS: [(&str, usize); 7] = [
+    ("A", 0),
+    ("L", 1),
+    ("Q", 2),
+    ("EB", 3),
+    ("FB", 4),
+    ("Z", 5),
+    ("BB", 6),
+];
+
+const DEFAULT_RATE: f64 = 10.0;
+
+/// Words being streamed to a client
+struct Watch {
+    words: Vec<(String, usize)>,
+    period: Duration,
+    start: Instant,
+    next: Instant,
+}
+
+/// Serve the monitor port on `addr` (`host:port`), one client at a time
+pub fn spawn(addr: &str, handle: RuntimeHandle) -> Result<(), String> {
+    let listener = TcpListener::bind(addr).map_err(|e| format!("{}: {}", addr, e))?;
+    if !listener.local_addr().map_or(true, |a| a.ip().is_loopback()) {
+        warn!("GSE port on {} lets anyone store into the computer", addr);
+    }
+    info!("GSE monitor port listening on {}", addr);
+    std::thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            if let Err(e) = session(stream, &handle) {
+                error!("GSE connection failed: {}", e);
+            }
+        }
+    });
+    Ok(())
+}
+
+/// Answer commands and stream watched words until the client goes
+fn session(stream: TcpStream, handle: &RuntimeHandle) -> std::io::Result<()> {
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut writer = stream;
+    let mut watch = None;
+    let mut line = String::new();
+    loop {
+        // Wait for a command only until the next sample is due
+        let timeout = watch.as_ref().map(|w: &Watch| {
+            w.next
+                .saturating_duration_since(Instant::now())
+                .max(Duration::from_millis(1))
+        });
+        reader.get_ref().set_read_timeout(timeout)?;
+        match reader.read_line(&mut line) {
+            Ok(0) => return Ok(()),
+            Ok(_) => {
+                let reply = dispatch(line.trim(), handle, &mut watch);
+                line.clear();
+                writeln!(writer, "{}", reply)?;
+            }
+            Err(e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {}
+            Err(e) => return Err(e),
+        }
+        if let Some(watch) = watch.as_mut().filter(|w| Instant::now() >= w.next) {
+            match sample(watch, handle) {
+                Some(sample) => writeln!(writer, "{}", sample)?,
+                None => return Ok(()), // Emulator gone
+            }
+            watch.next += watch.period;
+        }
+    }
+}
+
+/// Handle one command line, giving the reply line
+fn dispatch(line: &str, handle: &RuntimeHandle, watch: &mut Option<Watch>) -> String {
+    match command(line, handle, watch) {
+        Ok(reply) => reply,
+        Err(e) => format!("ERROR {}", e),
+    }
+}
+
+fn command(
+    line: &str,
+    handle: &RuntimeHandle,
+    watch: &mut Option<Watch>,
+) -> Result<String, String> {
+    let mut words = line.split_whitespace();
+    let verb = words.next().unwrap_or_default().to_uppercase();
+    let args: Vec<&str> = words.collect();
+    let gone = || "Emulator has stopped".to_string();
+    match (verb.as_str(), args.as_slice()) {
+        ("FETCH", [addr]) => {
+            let addr = address(addr)?;
+            let word = handle
+                .monitor(UnprogSequence::FETCH(addr))
+                .ok_or_else(gone)?;
+            Ok(format!("FETCH {:04o} {:05o}", addr, word))
+        }
+        ("STORE", [addr, word]) => {
+            let addr = address(addr)?;
+            let word = octal(word).filter(|w| *w <= 0o77777);
+            let word = word.ok_or_else(|| "Word must be 15-bit octal".to_string())?;
+            let word = handle
+                .monitor(UnprogSequence::STORE(addr, word as u16))
+                .ok_or_else(gone)?;
+            Ok(format!("STORE {:04o} {:05o}", addr, word))
+        }
+        ("TCSAJ", [addr]) => {
+            let addr = address(addr)?;
+            let addr = handle
+                .monitor(UnprogSequence::TCSAJ(addr as u16))
+                .ok_or_else(gone)?;
+            Ok(format!("TCSAJ {:04o}", addr))
+        }
+        ("WATCH", [names @ .., last]) if last.starts_with('@') => {
+            let rate = last[1..].parse::<f64>().ok().filter(|r| *r > 0.0);
+            let rate = rate.ok_or_else(|| format!("Invalid rate {}", last))?;
+            *watch = Some(new_watch(names, rate)?);
+            Ok("OK".to_string())
+        }
+        ("WATCH", names) => {
+            *watch = Some(new_watch(names, DEFAULT_RATE)?);
+            Ok("OK".to_string())
+        }
+        ("STOP", []) => {
+            *watch = None;
+            Ok("OK".to_string())
+        }
+        ("FETCH" | "STORE" | "TCSAJ" | "STOP", _) => Err(format!("Wrong arguments for {}", verb)),
+        _ => Err(format!("Unknown command {}", verb)),
+    }
+}
+
+fn new_watch(names: &[&str], rate: f64) -> Result<Watch, String> {
+    if names.is_empty() {
+        return Err("Nothing to watch".to_string());
+    }
+    let words = names
+        .iter()
+        .map(|name| {
+            let name = name.to_uppercase();
+            match REGISTERS.iter().find(|(reg, _)| *reg == name) {
+                Some((_, addr)) => Ok((name, *addr)),
+                None => address(&name).map(|addr| (format!("{:04o}", addr), addr)),
+            }
+        })
+        .collect::<Result<_, String>>()?;
+    let now = Instant::now();
+    Ok(Watch {
+        words,
+        period: Duration::from_secs_f64(1.0 / rate),
+        start: now,
+        next: now,
+    })
+}
+
+/// One line of watched words, e.g. `W 0.100 A=00012 Z=04012`
+fn sample(watch: &Watch, handle: &RuntimeHandle) -> Option<String> {
+    let mut line = format!("W {:.3}", watch.start.elapsed().as_secs_f64());
+    for (name, addr) in watch.words.iter() {
+        line += &format!(" {}={:05o}", name, handle.peek(*addr)?);
+    }
+    Some(line)
+}
+
+fn octal(text: &str) -> Option<usize> {
+    usize::from_str_radix(text, 8).ok()
+}
+
+fn address(text: &str) -> Result<usize, String> {
+    octal(text)
+        .filter(|addr| *addr <= ADDRESS_MAX)
+        .ok_or_else(|| format!("Invalid address {}", text))
+}
+
+#[cfg(test)]
+mod gse_tests {
+    use super::{dispatch, sample};
+    use crate::runtime::Runtime;
+    use ragc_core::cpu::Cpu;
+    use ragc_core::memory::MemoryMap;
+
+    #[test]
+    fn forces_sequences_and_samples_watched_words() {
+        let (mut runtime, handle) = Runtime::new(None, 1);
+        let client = std::thread::spawn(move || {
+            let mut watch = None;
+            let mut send = |line: &str| dispatch(line, &handle, &mut watch);
+            assert_eq!(send("STORE 1030 42"), "STORE 1030 00042");
+            assert_eq!(send("fetch 1030"), "FETCH 1030 00042");
+            assert_eq!(send("TCSAJ 4000"), "TCSAJ 4000");
+            assert_eq!(send("FETCH 20000"), "ERROR Invalid address 20000");
+            assert_eq!(send("STORE 1030"), "ERROR Wrong arguments for STORE");
+            assert!(send("WATCH A @0").starts_with("ERROR"));
+            assert_eq!(send("WATCH z 1030 @20"), "OK");
+            let line = sample(watch.as_ref().unwrap(), &handle).unwrap();
+            assert!(line.starts_with("W 0."), "{}", line);
+            assert!(line.ends_with(" Z=04000 1030=00042"), "{}", line);
+            assert_eq!(dispatch("STOP", &handle, &mut watch), "OK");
+            assert!(watch.is_none());
+        });
+        let mut cpu = Cpu::new(MemoryMap::new_blank());
+        while !client.is_finished() {
+            runtime.poll(&mut cpu);
+            std::thread::yield_now();
+        }
+        client.join().unwrap();
+    }
+}
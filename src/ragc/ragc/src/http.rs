@@ -0,0 +1,462 @@
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::Arc;
+use std::time::Duration;
+
+use log::{error, info, warn};
+use ragc_peripherals::shared_dsky::DskyState;
+use ragc_protocol_json::display::DisplayState;
+use serde_json::{json, Value};
+
+use crate::compare::{self, Step};
+use crate::control::{self, Access, Role};
+use crate::pool::Pool;
+use crate::runtime::RuntimeHandle;
+
+/// Largest request body accepted
+const BODY_MAX: usize = 64 * 1024;
+
+/// Gap between scripted key presses, long enough for PINBALL to take each
+const KEY_GAP: Duration = Duration::from_millis(250);
+
+/// How long a connection may take to send its request
+const READ_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Endpoints observers may GET, here or under a session; erasable is not
+/// among them
+const OBSERVABLE: [&str; 4] = ["state", "channels", "dsky", "sessions"];
+
+/// REST server for `ragc serve --http <port>`, for classrooms sharing one
+/// emulated AGC from their browsers. Replies are JSON; with tokens set,
+/// requests carry `Authorization: Bearer <token>` or `?token=<token>`, and
+/// observers may only GET the state, channels, DSKYs and session list. POST bodies must be sent as
+/// `application/json`, which pages on other sites can't do unasked; only
+/// with an operator token set are they told they may read the replies.
+/// Each connection is served on a thread of its own.
+///
+/// - `GET /state`: registers and cycle count
+/// - `GET /snapshot`: the state plus all of erasable memory
+/// - `GET /channels`: I/O channels with their named bits
+/// - `GET /memory/<octal addr>`: one word
+/// - `POST /keys` `{"keys": "V16N65E"}`: press DSKY keys in turn
+/// - `POST /pause`, `POST /resume`, `POST /step` `{"count": 10}`
+/// - `POST /discrete/<name>` `{"asserted": false}`: assert (by default) or
+///   release a discrete, e.g. `voltage-fail` to restart the AGC
+/// - `POST /scenario/<name>`: play a `compare` scenario on the live AGC
+/// - `POST /rope` `{"rom": "retread50"}`: swap in a bundled rope, given an
+///   operator token
+///
+/// With a pool, each student can also have an AGC of their own:
+///
+/// - `POST /sessions`: start one, replying `{"id": ...}`
+/// - `GET /sessions`, `DELETE /sessions/<id>`
+/// - `GET /sessions/<id>/dsky`: what its DSKY shows
+/// - `/sessions/<id>/...`: any endpoint above, on that AGC
+pub fn spawn(
+    port: u16,
+    access: Access,
+    handle: RuntimeHandle,
+    pool: Option<Arc<Pool>>,
+) -> Result<(), String> {
+    let listener =
+        TcpListener::bind(("0.0.0.0", port)).map_err(|e| format!("port {}: {}", port, e))?;
+    if access.operator.is_none() {
+        warn!("HTTP server on port {} is exposed without a token", port);
+    }
+    info!("HTTP server listening on port {}", port);
+    std::thread::spawn(move || accept(listener, access, handle, pool));
+    Ok(())
+}
+
+/// Serve each connection on a thread of its own, so one that sends
+/// nothing holds up nobody else
+fn accept(listener: TcpListener, access: Access, handle: RuntimeHandle, pool: Option<Arc<Pool>>) {
+    for stream in listener.incoming().flatten() {
+        let (access, handle, pool) = (access.clone(), handle.clone(), pool.clone());
+        std::thread::spawn(move || {
+            let served = stream
+                .set_read_timeout(Some(READ_TIMEOUT))
+                .map_err(|e| e.to_string())
+                .and_then(|_| serve(stream, &access, &handle, pool.as_deref()));
+            if let Err(e) = served {
+                error!("HTTP request failed: {}", e);
+            }
+        });
+    }
+}
+
+/// A parsed request: method, path, query token, bearer token and body
+struct Request {
+    method: String,
+    path: String,
+    token: Option<String>,
+    json: bool, // Content-Type is application/json
+    body: Value,
+}
+
+fn read_request(stream: &TcpStream) -> Result<Request, String> {
+    let mut reader = BufReader::new(stream);
+    let mut line = String::new();
+    reader.read_line(&mut line).map_err(|e| e.to_string())?;
+    let mut parts = line.split_whitespace();
+    let method = parts.next().unwrap_or_default().to_string();
+    let target = parts.next().unwrap_or_default();
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    let mut token = query
+        .split('&')
+        .find_map(|pair| pair.strip_prefix("token="))
+        .map(String::from);
+
+    let mut length = 0;
+    let mut json = false;
+    loop {
+        let mut header = String::new();
+        reader.read_line(&mut header).map_err(|e| e.to_string())?;
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        let (name, value) = header.split_once(':').unwrap_or((header, ""));
+        match name.trim().to_ascii_lowercase().as_str() {
+            "content-length" => length = value.trim().parse().unwrap_or(0),
+            "content-type" => {
+                let media = value.split(';').next().unwrap_or_default();
+                json = media.trim().eq_ignore_ascii_case("application/json");
+            }
+            "authorization" => {
+                token = value.trim().strip_prefix("Bearer ").map(String::from);
+            }
+            _ => {}
+        }
+    }
+    if length > BODY_MAX {
+        return Err(format!("body of {} bytes is too large", length));
+    }
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body).map_err(|e| e.to_string())?;
+    let body = match body.is_empty() {
+        true => Value::Null,
+        false => serde_json::from_slice(&body).map_err(|e| e.to_string())?,
+    };
+    Ok(Request {
+        method,
+        path: path.to_string(),
+        token,
+        json,
+        body,
+    })
+}
+
+fn serve(
+    mut stream: TcpStream,
+    access: &Access,
+    handle: &RuntimeHandle,
+    pool: Option<&Pool>,
+) -> Result<(), String> {
+    let (status, reply) = match read_request(&stream) {
+        Ok(request) => match access.role(request.token.as_deref()) {
+            None => (401, json!({ "error": "Invalid token" })),
+            Some(Role::Observer) if !observable(&request) => (
+                403,
+                json!({ "error": "Observers may only read the display, channels and state" }),
+            ),
+            Some(_) if request.path.ends_with("/rope") && !access.may_swap_ropes() => {
+                (403, json!({ "error": "Rope swaps need an operator token" }))
+            }
+            Some(_) if request.method == "POST" && !request.json => (
+                415,
+                json!({ "error": "POST bodies must be application/json" }),
+            ),
+            Some(_) => match request.path.trim_matches('/').strip_prefix("sessions") {
+                Some(rest) => sessions(&request, rest, pool),
+                None => route(&request, handle),
+            },
+        },
+        Err(e) => (400, json!({ "error": e })),
+    };
+    let body = reply.to_string();
+    let reason = match status {
+        200 => "OK",
+        202 => "Accepted",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        403 => "Forbidden",
+        404 => "Not Found",
+        415 => "Unsupported Media Type",
+        _ => "Service Unavailable",
+    };
+    // Without a token any page could read the replies
+    let cors = match access.operator {
+        Some(_) => "Access-Control-Allow-Origin: *\r\n",
+        None => "",
+    };
+    write!(
+        stream,
+        "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\
+         {}Connection: close\r\n\r\n{}",
+        status,
+        reason,
+        body.len(),
+        cors,
+        body
+    )
+    .map_err(|e| e.to_string())
+}
+
+/// Whether an observer may make `request`
+fn observable(request: &Request) -> bool {
+    let endpoint = request.path.trim_matches('/').rsplit('/').next();
+    request.method == "GET" && endpoint.is_some_and(|e| OBSERVABLE.contains(&e))
+}
+
+/// Session management, and requests for one session's AGC
+fn sessions(request: &Request, rest: &str, pool: Option<&Pool>) -> (u16, Value) {
+    let pool = match pool {
+        Some(pool) => pool,
+        None => return (404, json!({ "error": "Sessions are not enabled" })),
+    };
+    let (id, path) = rest
+        .trim_start_matches('/')
+        .split_once('/')
+        .unwrap_or((rest.trim_start_matches('/'), ""));
+    match (request.method.as_str(), id, path) {
+        ("POST", "", "") => match pool.create() {
+            Ok(id) => (200, json!({ "id": id })),
+            Err(e) => (503, json!({ "error": e })),
+        },
+        ("GET", "", "") => (200, json!(pool.sessions())),
+        ("DELETE", id, "") => match pool.destroy(id) {
+            true => (200, json!(true)),
+            false => (404, json!({ "error": "No such session" })),
+        },
+        (method, id, path) => match pool.get(id) {
+            Some((_, dsky)) if (method, path) == ("GET", "dsky") => {
+                (200, dsky_json(&dsky.snapshot()))
+            }
+            Some((handle, _)) => {
+                let request = Request {
+                    method: request.method.clone(),
+                    path: path.to_string(),
+                    token: None,
+                    json: request.json,
+                    body: request.body.clone(),
+                };
+                route(&request, &handle)
+            }
+            None => (404, json!({ "error": "No such session" })),
+        },
+    }
+}
+
+/// Display digits as shown, blanks as spaces, for browser DSKY faces
+fn dsky_json(state: &DskyState) -> Value {
+    let digits = |digits: &[Option<u8>]| -> String {
+        digits
+            .iter()
+            .map(|d| d.map_or(' ', |d| (b'0' + d) as char))
+            .collect()
+    };
+    let [prog, verb, noun] = state.display.pair_digits();
+    let registers = [0, 1, 2].map(|reg| {
+        let sign = match state.display.register_sign(reg) {
+            Some(sign) if sign < 0 => '-',
+            Some(_) => '+',
+            None => ' ',
+        };
+        format!("{}{}", sign, digits(&state.display.register_digits(reg)))
+    });
+    json!(DisplayState {
+        prog: digits(&prog),
+        verb: digits(&verb),
+        noun: digits(&noun),
+        registers,
+        relay_lights: state.relay_lights,
+        lamps: state.lamps,
+        lights: state.lights,
+        brightness: state.brightness(),
+        updates: state.updates,
+    })
+}
+
+fn route(request: &Request, handle: &RuntimeHandle) -> (u16, Value) {
+    let segments: Vec<&str> = request.path.trim_matches('/').split('/').collect();
+    let result = match (request.method.as_str(), segments.as_slice()) {
+        ("GET", ["state"]) => control::call("snapshot", &Value::Null, handle).map(|mut snap| {
+            snap.as_object_mut().map(|snap| snap.remove("erasable"));
+            snap
+        }),
+        ("GET", ["snapshot"]) => control::call("snapshot", &Value::Null, handle),
+        ("GET", ["channels"]) => control::call("channels", &Value::Null, handle),
+        ("GET", ["memory", addr]) => match u64::from_str_radix(addr, 8) {
+            Ok(addr) => control::call("peek", &json!({ "addr": addr }), handle),
+            Err(_) => return (400, json!({ "error": "Address must be octal" })),
+        },
+        ("POST", ["pause" | "resume" | "step" | "step_over" | "step_out"]) => {
+            control::call(segments[0], &request.body, handle)
+        }
+        ("POST", ["rope"]) => control::call("load_rope", &request.body, handle),
+        ("POST", ["discrete", name]) => {
+            let asserted = request.body["asserted"].as_bool().unwrap_or(true);
+            let params = json!({ "name": name, "asserted": asserted });
+            control::call("set_discrete", &params, handle)
+        }
+        ("POST", ["keys"]) => {
+            let keys = request.body["keys"].as_str().unwrap_or_default();
+            if keys.is_empty() || !keys.chars().all(|k| compare::keycode(k).is_some()) {
+                return (
+                    400,
+                    json!({ "error": "Keys must be V, N, E, C, R, K, +, - or digits" }),
+                );
+            }
+            let (keys, handle) = (keys.to_string(), handle.clone());
+            std::thread::spawn(move || press(&handle, &keys));
+            return (202, json!(true));
+        }
+        ("POST", ["scenario", name]) => match compare::scenario(name) {
+            Some(steps) => {
+                let handle = handle.clone();
+                std::thread::spawn(move || play(&handle, steps));
+                return (202, json!(true));
+            }
+            None => {
+                return (
+                    404,
+                    json!({ "error": format!("Unknown scenario: {}", name) }),
+                )
+            }
+        },
+        _ => return (404, json!({ "error": "No such endpoint" })),
+    };
+    match result {
+        Ok(value) => (200, value),
+        Err((code, message)) => (control::http_status(code), json!({ "error": message })),
+    }
+}
+
+/// Press keys in turn, as a crew member would
+fn press(handle: &RuntimeHandle, keys: &str) {
+    for code in keys.chars().filter_map(compare::keycode) {
+        if !handle.press_key(code) {
+            return;
+        }
+        std::thread::sleep(KEY_GAP);
+    }
+}
+
+/// Play a scenario against the live AGC in wall-clock time
+fn play(handle: &RuntimeHandle, steps: &[Step]) {
+    for step in steps {
+        match *step {
+            Step::Wait(ms) | Step::WaitProgram(_, ms) => {
+                std::thread::sleep(Duration::from_millis(ms as u64))
+            }
+            Step::Keys(keys) => press(handle, keys),
+            Step::Discrete(discrete, asserted) => {
+                handle.set_discrete(discrete, asserted);
+            }
+            Step::Program(program) => press(handle, &format!("V37E{:02}E", program)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod http_tests {
+    use super::{accept, observable, route, Request};
+    use crate::control::Access;
+    use crate::runtime::Runtime;
+    use ragc_core::cpu::{Cpu, RestartCause};
+    use ragc_core::memory::MemoryMap;
+    use serde_json::{json, Value};
+    use std::io::{Read, Write};
+    use std::net::{TcpListener, TcpStream};
+
+    fn request(method: &str, path: &str, body: Value) -> Request {
+        Request {
+            method: method.to_string(),
+            path: path.to_string(),
+            token: None,
+            json: true,
+            body,
+        }
+    }
+
+    #[test]
+    fn routes_to_the_runtime() {
+        let (mut runtime, handle) = Runtime::new(None, 1);
+        let client = std::thread::spawn(move || {
+            handle.poke(0o100, 0o1234);
+            let (status, reply) = route(&request("GET", "/memory/100", Value::Null), &handle);
+            assert_eq!((status, reply), (200, json!(0o1234)));
+
+            let (status, reply) = route(&request("GET", "/state", Value::Null), &handle);
+            assert_eq!(status, 200);
+            assert!(reply["erasable"].is_null() && reply["total_cycles"].is_number());
+
+            let bad = json!({ "keys": "V1X" });
+            assert_eq!(route(&request("POST", "/keys", bad), &handle).0, 400);
+            assert_eq!(
+                route(&request("GET", "/memory/9", Value::Null), &handle).0,
+                400
+            );
+            assert_eq!(
+                route(&request("POST", "/scenario/none", Value::Null), &handle).0,
+                404
+            );
+            assert_eq!(
+                route(&request("DELETE", "/state", Value::Null), &handle).0,
+                404
+            );
+
+            let unknown = request("POST", "/discrete/brownout", Value::Null);
+            assert_eq!(route(&unknown, &handle).0, 400);
+            let voltage_fail = request("POST", "/discrete/voltage-fail", Value::Null);
+            assert_eq!(route(&voltage_fail, &handle), (200, json!(true)));
+            handle.peek(0);
+        });
+
+        let mut cpu = Cpu::new(MemoryMap::new_blank());
+        while !client.is_finished() {
+            runtime.poll(&mut cpu);
+            std::thread::yield_now();
+        }
+        client.join().unwrap();
+        assert_eq!(cpu.last_restart(), Some(RestartCause::VoltageFail));
+    }
+
+    #[test]
+    fn idle_connections_hold_up_nobody() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (_, handle) = Runtime::new(None, 1);
+        std::thread::spawn(move || accept(listener, Access::default(), handle, None));
+
+        let exchange = |request: &str| {
+            let mut stream = TcpStream::connect(addr).unwrap();
+            stream.write_all(request.as_bytes()).unwrap();
+            let mut reply = String::new();
+            stream.read_to_string(&mut reply).unwrap();
+            reply
+        };
+
+        // Opened and never written to, as browsers do ahead of time
+        let _idle = TcpStream::connect(addr).unwrap();
+        let reply = exchange("GET /nowhere HTTP/1.1\r\n\r\n");
+        assert!(reply.starts_with("HTTP/1.1 404"));
+        // Nothing for other sites to read without a token
+        assert!(!reply.contains("Access-Control-Allow-Origin"));
+
+        let form = "POST /pause HTTP/1.1\r\nContent-Type: text/plain\r\n\
+                    Content-Length: 2\r\n\r\n{}";
+        assert!(exchange(form).starts_with("HTTP/1.1 415"));
+    }
+
+    #[test]
+    fn observers_see_no_erasable() {
+        let observe = |method: &str, path: &str| observable(&request(method, path, Value::Null));
+        assert!(observe("GET", "/state") && observe("GET", "/channels"));
+        assert!(observe("GET", "/sessions") && observe("GET", "/sessions/ab12/dsky"));
+        assert!(!observe("GET", "/snapshot") && !observe("GET", "/memory/100"));
+        assert!(!observe("GET", "/sessions/ab12/snapshot"));
+        assert!(!observe("POST", "/state") && !observe("POST", "/pause"));
+    }
+}
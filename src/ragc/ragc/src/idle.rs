@@ -0,0 +1,109 @@
+//! Idle loops the host passes over. While the program spins in one waiting
+//! for work, time is let pass without running it (`Cpu::idle`) and the
+//! host sleeps in longer stretches, so a quiet computer costs little.
+//! Ranges differ between ropes and come from the runtime configuration.
+use std::time::Duration;
+
+use ragc_core::constants::registers::REGISTER_COUNTER;
+use ragc_core::cpu::Cpu;
+use ragc_core::hooks::FixedAddress;
+use ragc_core::memory::Location;
+
+use crate::config::IdleConfig;
+use crate::listing::Symbols;
+
+/// Host sleep between batches while the program idles
+pub const IDLE_SLEEP: Duration = Duration::from_millis(20);
+
+#[derive(Default)]
+pub struct IdleLoops {
+    ranges: Vec<(FixedAddress, FixedAddress)>, // First and last word of each
+}
+
+impl IdleLoops {
+    /// Loops configured for `rom`; entries naming no ROM apply to any
+    pub fn new(
+        config: &[IdleConfig],
+        rom: Option<&str>,
+        symbols: &Symbols,
+    ) -> Result<Self, String> {
+        let find = |text: &str| {
+            resolve(text, symbols).ok_or_else(|| format!("Unknown idle loop address {}", text))
+        };
+        let mut ranges = Vec::new();
+        for entry in config {
+            if entry.rom.is_some() && entry.rom.as_deref() != rom {
+                continue;
+            }
+            let (from, to) = (find(&entry.from)?, find(&entry.to)?);
+            if from.bank != to.bank || from.offset > to.offset {
+                return Err(format!(
+                    "Idle loop {} to {} is not a range within one bank",
+                    entry.from, entry.to
+                ));
+            }
+            ranges.push((from, to));
+        }
+        Ok(Self { ranges })
+    }
+
+    /// Whether the next instruction is in one of the loops
+    pub fn contains(&self, cpu: &Cpu) -> bool {
+        let pc = cpu.memory().read(REGISTER_COUNTER) as usize;
+        match cpu.memory().locate(pc) {
+            Some(Location::Fixed(at)) => self.ranges.iter().any(|(from, to)| {
+                at.bank == from.bank && (from.offset..=to.offset).contains(&at.offset)
+            }),
+            _ => false,
+        }
+    }
+}
+
+/// `NAME`, `NAME+n` (decimal, as listings print them) or a fixed location
+fn resolve(text: &str, symbols: &Symbols) -> Option<FixedAddress> {
+    let (name, words) = match text.split_once('+') {
+        Some((name, n)) => (name, n.parse::<usize>().ok()?),
+        None => (text, 0),
+    };
+    let at = symbols.fixed(name)?;
+    let offset = Some(at.offset + words).filter(|&o| o < 0o2000)?;
+    Some(FixedAddress::new(at.bank, offset))
+}
+
+#[cfg(test)]
+mod idle_tests {
+    use super::IdleLoops;
+    use crate::config::IdleConfig;
+    use crate::listing::Symbols;
+    use ragc_core::cpu::Cpu;
+    use ragc_core::memory::MemoryMapBuilder;
+    use ragc_testutils::rope_image;
+
+    #[test]
+    fn passes_over_configured_loops() {
+        // 4000: CA 67; TCF 4000
+        let rope = rope_image(0o4000, &[0o30067, 0o14000]);
+        let mut cpu = Cpu::new(MemoryMapBuilder::new().rope(&rope).build());
+        let symbols = Symbols::parse("SPIN 4000").unwrap();
+        let entry = |rom: &str, from: &str, to: &str| IdleConfig {
+            rom: Some(rom.to_string()).filter(|r| !r.is_empty()),
+            from: from.to_string(),
+            to: to.to_string(),
+        };
+
+        let other = [entry("comanche055", "SPIN", "SPIN+1")];
+        let loops = IdleLoops::new(&other, Some("luminary99"), &symbols).unwrap();
+        assert!(!loops.contains(&cpu));
+
+        let config = [entry("", "SPIN+1", "SPIN+1")];
+        let loops = IdleLoops::new(&config, Some("luminary99"), &symbols).unwrap();
+        assert!(!loops.contains(&cpu));
+        cpu.step();
+        assert!(loops.contains(&cpu));
+        assert!(cpu.idle(1000) > 0);
+
+        let backwards = [entry("", "SPIN+1", "4000")];
+        assert!(IdleLoops::new(&backwards, None, &symbols).is_err());
+        assert!(IdleLoops::new(&[entry("", "NOPE", "SPIN")], None, &symbols).is_err());
+    }
+}
@@ -0,0 +1,55 @@
+//! `ragc isa`: the instruction set table, as text or JSON
+
+/// The `isa` subcommand and its options
+pub fn subcommand<'a, 'b>() -> clap::App<'a, 'b> {
+    clap::SubCommand::with_name("isa")
+        .about("List the instruction set as decoded and executed")
+        .arg(
+            clap::Arg::with_name("json")
+                .long("json")
+                .help("Machine-readable table"),
+        )
+}
+
+/// Prints the instruction set as the emulator decodes and executes it
+pub fn command(args: &clap::ArgMatches) {
+    let table = ragc_core::isa::instruction_set();
+    if args.is_present("json") {
+        let rows: Vec<serde_json::Value> = table
+            .iter()
+            .map(|e| {
+                serde_json::json!({
+                    "mnemonic": e.mnem.name(),
+                    "extended": e.extended,
+                    "opcode": e.opcode,
+                    "quarter_code": e.quarter_code(),
+                    "peripheral_code": e.peripheral_code(),
+                    "encoding": format!("{:06o}", e.word),
+                    "cycles": e.cycles,
+                    "branch_cycles": e.branch_cycles,
+                    "implemented": e.implemented,
+                })
+            })
+            .collect();
+        println!("{}", serde_json::Value::Array(rows));
+        return;
+    }
+    println!("MNEM    EXT OP QC PC ENCODING MCT IMPLEMENTED");
+    let code = |c: Option<u8>| c.map_or("-".to_string(), |c| c.to_string());
+    for e in table.iter() {
+        println!(
+            "{:<7} {:>3} {:>2} {:>2} {:>2} {:06o}   {:>3} {}",
+            e.mnem.name(),
+            if e.extended { "yes" } else { "no" },
+            e.opcode,
+            code(e.quarter_code()),
+            code(e.peripheral_code()),
+            e.word,
+            match e.cycles == e.branch_cycles {
+                true => e.cycles.to_string(),
+                false => format!("{}/{}", e.cycles, e.branch_cycles),
+            },
+            if e.implemented { "yes" } else { "no" },
+        );
+    }
+}
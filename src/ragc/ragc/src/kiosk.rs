@@ -0,0 +1,108 @@
+//! Kiosk profile for exhibits running unattended. In standby the host
+//! only looks for a key now and then, so it idles; any key wakes the
+//! computer and real time resumes. Left alone long enough, the computer
+//! gets a fresh start (V36E) so the next visitor finds it as new.
+use std::time::{Duration, Instant};
+
+use log::info;
+
+use crate::compare::keycode;
+use crate::config::KioskConfig;
+
+/// How often standby looks for a waking key
+pub const STANDBY_POLL: Duration = Duration::from_millis(250);
+
+/// Keys giving a fresh start
+const FRESH_START: &str = "V36E";
+
+/// Time between the fresh start's keys, for the software to take each
+const KEY_SPACING: Duration = Duration::from_millis(500);
+
+pub struct Kiosk {
+    idle: Option<Duration>, // Fresh start after this long without a key
+    key_presses: u32,       // The CPU's count when last seen
+    own_keys: u32,          // Pressed here, not yet counted by the CPU
+    last_key: Instant,
+    pending: Vec<u16>, // Fresh start keys still to press
+    next_key: Instant,
+}
+
+impl Kiosk {
+    pub fn new(config: &KioskConfig, now: Instant) -> Self {
+        Self {
+            idle: config
+                .idle_minutes
+                .map(|m| Duration::from_secs(m as u64 * 60)),
+            key_presses: 0,
+            own_keys: 0,
+            last_key: now,
+            pending: Vec::new(),
+            next_key: now,
+        }
+    }
+
+    /// Key to press now, if the computer has been idle long enough for a
+    /// fresh start; `key_presses` is the CPU's count so far
+    pub fn poll(&mut self, key_presses: u32, now: Instant) -> Option<u16> {
+        let new_keys = key_presses.wrapping_sub(self.key_presses);
+        self.key_presses = key_presses;
+        if new_keys > self.own_keys {
+            self.last_key = now;
+        }
+        self.own_keys = self.own_keys.saturating_sub(new_keys);
+
+        if self.pending.is_empty() {
+            let idle = self.idle?;
+            if now.duration_since(self.last_key) < idle {
+                return None;
+            }
+            info!("Kiosk idle for {} s: fresh start", idle.as_secs());
+            self.pending = FRESH_START.chars().rev().filter_map(keycode).collect();
+        }
+        if now < self.next_key {
+            return None;
+        }
+        self.next_key = now + KEY_SPACING;
+        self.own_keys += 1;
+        self.last_key = now;
+        self.pending.pop()
+    }
+}
+
+#[cfg(test)]
+mod kiosk_tests {
+    use super::{Kiosk, KEY_SPACING};
+    use crate::config::KioskConfig;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn fresh_start_after_idle_minutes() {
+        let start = Instant::now();
+        let at = |s: u64| start + Duration::from_secs(s);
+        let config = KioskConfig {
+            idle_minutes: Some(2),
+        };
+        let mut kiosk = Kiosk::new(&config, start);
+
+        assert_eq!(kiosk.poll(0, at(119)), None);
+        assert_eq!(kiosk.poll(1, at(119)), None); // A visitor presses a key
+        assert_eq!(kiosk.poll(1, at(238)), None);
+
+        let mut keys = vec![kiosk.poll(1, at(239)).unwrap()];
+        let mut now = at(239);
+        while keys.len() < 4 {
+            assert_eq!(kiosk.poll(keys.len() as u32, now), None);
+            now += KEY_SPACING;
+            keys.extend(kiosk.poll(keys.len() as u32, now));
+        }
+        assert_eq!(keys, [0o21, 3, 6, 0o34]);
+
+        // Its own keys, counted late, aren't a visitor's
+        assert_eq!(kiosk.poll(5, now + Duration::from_secs(1)), None);
+        assert!(kiosk.poll(5, now + Duration::from_secs(120)).is_some());
+        assert_eq!(
+            Kiosk::new(&KioskConfig::default(), start).poll(0, at(1 << 20)),
+            None
+        );
+    }
+}
@@ -0,0 +1,242 @@
+use std::path::Path;
+
+use log::info;
+use serde::Deserialize;
+
+use ragc_core::memory::MemoryMap;
+use ragc_core::symbols::{ErasableAddress, SymbolTable};
+
+use crate::ropes::{load_rope, rope_by_name};
+use crate::watch::{resolve_address, Read};
+use crate::{NUM_ROM_BANKS, WORDS_PER_ROM};
+
+/// Mission kit for `ragc run --kit <dir>`: a directory holding `kit.toml`
+/// and the files it names, relative to the directory. Anything also given
+/// on the command line comes from the command line instead.
+///
+/// ```toml
+/// name = "Apollo 11 landing"
+/// rom = "luminary99"            # Bundled ROM, or
+/// rope = "luminary099.bin"      # a rope image in the kit
+/// padload = "padload.txt"       # Erasable load, see `Padload`
+/// symbols = "luminary99.symtab" # NAME ADDRESS lines
+/// stream_vars = "scaling.toml"  # Variables and scale factors, as --stream-vars
+/// scenario = "landing.cap"      # DSKY capture played from power on
+/// timeline = "timeline.toml"
+/// config = "runtime.toml"       # As --config
+/// requires = ["instruction:DV", "interrupt:UPRUPT"] # Checked before loading anything
+/// ```
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct Kit {
+    pub name: String,
+    pub rom: Option<String>,
+    pub rope: Option<String>,
+    pub padload: Option<String>,
+    pub symbols: Option<String>,
+    pub stream_vars: Option<String>,
+    pub scenario: Option<String>,
+    pub timeline: Option<String>,
+    pub config: Option<String>,
+    #[serde(default)]
+    pub requires: Vec<String>, // See `Capabilities::check`
+}
+
+impl Kit {
+    pub fn load(dir: &str) -> Result<Self, String> {
+        let path = Path::new(dir).join("kit.toml");
+        let text =
+            std::fs::read_to_string(&path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        let mut kit = Self::parse(&text).map_err(|e| format!("{}: {}", path.display(), e))?;
+        kit.resolve(Path::new(dir))?;
+        Ok(kit)
+    }
+
+    /// The kit's padload, if it has one
+    pub fn padload<S: SymbolTable + ?Sized>(&self, symbols: &S) -> Result<Option<Padload>, String> {
+        self.padload
+            .as_deref()
+            .map(|path| Padload::load(path, symbols))
+            .transpose()
+    }
+
+    fn parse(text: &str) -> Result<Self, String> {
+        let kit: Self = toml::from_str(text).map_err(|e| e.to_string())?;
+        if kit.rom.is_some() == kit.rope.is_some() {
+            return Err("needs one of rom or rope".to_string());
+        }
+        if !kit.requires.is_empty() {
+            let caps = ragc_core::capabilities();
+            for need in kit.requires.iter() {
+                caps.check(need).map_err(|missing| missing.to_string())?;
+            }
+        }
+        Ok(kit)
+    }
+
+    /// Make the kit's file names relative to the working directory, and
+    /// check they are all there
+    fn resolve(&mut self, dir: &Path) -> Result<(), String> {
+        for file in [
+            &mut self.rope,
+            &mut self.padload,
+            &mut self.symbols,
+            &mut self.stream_vars,
+            &mut self.scenario,
+            &mut self.timeline,
+            &mut self.config,
+        ] {
+            let file = match file {
+                Some(file) => file,
+                None => continue,
+            };
+            let path = dir.join(&*file);
+            if !path.is_file() {
+                return Err(format!("{}: missing from the kit", path.display()));
+            }
+            *file = path.to_string_lossy().into_owned();
+        }
+        Ok(())
+    }
+}
+
+/// The `--kit` option of `run`
+pub fn arg<'a, 'b>() -> clap::Arg<'a, 'b> {
+    clap::Arg::with_name("kit")
+        .long("kit")
+        .takes_value(true)
+        .help("Mission kit directory: rope, padload, symbols and scenario in one")
+}
+
+/// The kit named by `run --kit`, if there is one
+pub fn from_args(matches: &clap::ArgMatches) -> Result<Option<Kit>, String> {
+    let run = matches.subcommand_matches("run");
+    match run.and_then(|args| args.value_of("kit")) {
+        Some(dir) => {
+            let kit = Kit::load(dir)?;
+            info!("Mission kit: {}", kit.name);
+            Ok(Some(kit))
+        }
+        None => Ok(None),
+    }
+}
+
+/// Where `run` gets its rope: an image file, or else a bundled ROM by name.
+/// Either on the command line overrides the mission kit.
+pub fn run_source<'a>(
+    args: &'a clap::ArgMatches,
+    kit: Option<&'a Kit>,
+) -> (Option<&'a str>, Option<&'a str>) {
+    let explicit = args.is_present("rope") || args.occurrences_of("rom") > 0;
+    match (args.value_of("rope"), kit.filter(|_| !explicit)) {
+        (Some(path), _) => (Some(path), None),
+        (None, Some(kit)) => (kit.rope.as_deref(), kit.rom.as_deref()),
+        (None, None) => (None, args.value_of("rom")),
+    }
+}
+
+/// The rope for `run`
+pub fn run_rope(
+    args: &clap::ArgMatches,
+    kit: Option<&Kit>,
+) -> Result<[[u16; WORDS_PER_ROM]; NUM_ROM_BANKS], String> {
+    match run_source(args, kit) {
+        (Some(path), _) => load_rope(path),
+        (None, rom) => rope_by_name(rom.unwrap_or_default())
+            .copied()
+            .ok_or_else(|| "Invalid ROM".to_string()),
+    }
+}
+
+/// Erasable words loaded before the CPU starts, as mission control would
+/// pad-load them. One run of consecutive words per line:
+/// `<address or symbol> <octal word>...`, with `#` comments.
+///
+/// ```text
+/// # Landing site
+/// RLS      14231 37102 00000 00000 01572 00641
+/// E3,1610  00012
+/// ```
+pub struct Padload {
+    runs: Vec<(ErasableAddress, Vec<u16>)>,
+}
+
+impl Padload {
+    pub fn load<S: SymbolTable + ?Sized>(path: &str, symbols: &S) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+        Self::parse(&text, symbols).map_err(|e| format!("{}: {}", path, e))
+    }
+
+    pub fn parse<S: SymbolTable + ?Sized>(text: &str, symbols: &S) -> Result<Self, String> {
+        let mut runs = Vec::new();
+        for (idx, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default();
+            let mut fields = line.split_whitespace();
+            let addr = match fields.next() {
+                Some(addr) => addr,
+                None => continue,
+            };
+            let invalid = |what: String| format!("line {}: {}", idx + 1, what);
+            let addr = resolve_address(addr, Read::Raw, symbols).map_err(invalid)?;
+            let words = fields
+                .map(|w| match u16::from_str_radix(w, 8) {
+                    Ok(word) if word <= 0o77777 => Ok(word),
+                    _ => Err(invalid(format!("Invalid word {}", w))),
+                })
+                .collect::<Result<Vec<_>, String>>()?;
+            if words.is_empty() {
+                return Err(invalid("No words to load".to_string()));
+            }
+            if addr.offset + words.len() > 0o400 {
+                return Err(invalid("Runs past the end of its bank".to_string()));
+            }
+            runs.push((addr, words));
+        }
+        Ok(Self { runs })
+    }
+
+    pub fn apply(&self, mem: &mut MemoryMap) {
+        for (addr, words) in self.runs.iter() {
+            mem.write_block(addr.bank, addr.offset, words);
+        }
+    }
+}
+
+#[cfg(test)]
+mod kit_tests {
+    use super::{Kit, Padload};
+    use ragc_core::memory::MemoryMap;
+    use ragc_core::symbols::ErasableAddress;
+
+    #[test]
+    fn kits_name_a_rope_and_padloads_fill_erasable() {
+        let kit = Kit::parse("name = \"Landing\"\nrom = \"luminary99\"\n").unwrap();
+        assert_eq!(kit.rom.as_deref(), Some("luminary99"));
+        assert!(Kit::parse("name = \"None\"\n").is_err());
+        assert!(Kit::parse("name = \"Both\"\nrom = \"a\"\nrope = \"b\"\n").is_err());
+        assert!(Kit::parse("name = \"X\"\nrom = \"a\"\nroms = \"b\"\n").is_err());
+        let needs = |requires: &str| {
+            Kit::parse(&format!(
+                "name = \"X\"\nrom = \"a\"\nrequires = {}\n",
+                requires
+            ))
+        };
+        assert!(needs("[\"instruction:DV\", \"channel:CHAN30\"]").is_ok());
+        assert_eq!(
+            needs("[\"instruction:DDOUBL\"]").unwrap_err(),
+            "ragc does not implement instruction DDOUBL"
+        );
+
+        let symbols = [("RLS", ErasableAddress::from_flat(0o1230))];
+        let text = "# Landing site\nRLS 14231 37102\n\nE3,1610 00012 # Flag\n";
+        let padload = Padload::parse(text, &symbols[..]).unwrap();
+        let mut mem = MemoryMap::new_blank();
+        padload.apply(&mut mem);
+        assert_eq!(mem.read_block(2, 0o230..0o232), [0o14231, 0o37102]);
+        assert_eq!(mem.read_block(3, 0o210..0o211), [0o12]);
+
+        for bad in ["RLS", "NOSUCH 1", "RLS 9", "RLS 100000", "E2,1777 1 2"] {
+            assert!(Padload::parse(bad, &symbols[..]).is_err(), "{}", bad);
+        }
+    }
+}
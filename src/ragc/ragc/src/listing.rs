@@ -0,0 +1,345 @@
+use std::collections::{BTreeMap, HashMap};
+use std::io::Write;
+
+use ragc_core::constants::address_space::{fixed_fixed_address, VOLATILE_END};
+use ragc_core::constants::{STORAGE_SEGMENTS, STORAGE_SEGMENT_SIZE};
+use ragc_core::disasm::{disassemble, idiom, rupt_vector, Disassembled, Operand};
+use ragc_core::hooks::FixedAddress;
+use ragc_core::instructions::Mnemonic;
+use ragc_core::memory::channels::channel_spec;
+use ragc_core::memory::rom::ReadOnlyMemory;
+use ragc_core::memory::{self, AddressSpace};
+use ragc_core::symbols::{ErasableAddress, SymbolTable};
+
+pub type Rope = [[u16; STORAGE_SEGMENT_SIZE]; STORAGE_SEGMENTS];
+
+/// An address as printed in the listing
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub enum Location {
+    Erasable(u16),       // 0000-1777
+    Fixed(usize, usize), // Logical bank and offset within it
+}
+
+impl Location {
+    /// Resolve an instruction operand, assuming switched-fixed references
+    /// stay in the bank the instruction sits in
+    pub fn resolve(k: u16, bank: usize) -> Option<Self> {
+        match AddressSpace::running_in(bank).locate(k as usize) {
+            Some(at) => Self::from_space(at),
+            // Operands in the EB window print as written
+            None if k as usize <= VOLATILE_END => Some(Location::Erasable(k)),
+            None => None,
+        }
+    }
+
+    /// Listing key of a location; erasable only below the EB window
+    pub fn from_space(at: memory::Location) -> Option<Self> {
+        let flat = AddressSpace::UNBANKED.address(at);
+        match at {
+            memory::Location::Fixed(at) => Some(Location::Fixed(at.bank, at.offset)),
+            _ => flat.map(|k| Location::Erasable(k as u16)),
+        }
+    }
+
+    /// Parse `BB,AAAA` (switched fixed) or `AAAA` (erasable or fixed-fixed)
+    pub fn parse(text: &str) -> Option<Self> {
+        match text.contains(',') {
+            true => match AddressSpace::UNBANKED.parse(text)? {
+                at @ memory::Location::Fixed(_) => Self::from_space(at),
+                _ => None,
+            },
+            // Plain switched-fixed addresses are ambiguous without a bank
+            false => Location::resolve(u16::from_str_radix(text, 8).ok()?, 2),
+        }
+    }
+}
+
+impl std::fmt::Display for Location {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match *self {
+            Location::Erasable(k) => write!(f, "{:04o}", k),
+            Location::Fixed(bank, offset) => {
+                memory::Location::Fixed(FixedAddress::new(bank, offset)).fmt(f)
+            }
+        }
+    }
+}
+
+/// Symbol names by address, loaded from `NAME ADDRESS` lines
+#[derive(Clone, Default)]
+pub struct Symbols {
+    names: HashMap<Location, String>,
+}
+
+impl Symbols {
+    pub fn load(path: &str) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+        Self::parse(&text)
+    }
+
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut names = HashMap::new();
+        for (idx, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let mut fields = line.split_whitespace();
+            let name = fields.next().unwrap_or_default();
+            let addr = fields.next().and_then(Location::parse);
+            match addr {
+                Some(addr) => {
+                    names.insert(addr, name.to_string());
+                }
+                None => return Err(format!("Invalid symbol on line {}: {}", idx + 1, line)),
+            }
+        }
+        Ok(Self { names })
+    }
+
+    pub fn get(&self, addr: &Location) -> Option<&str> {
+        self.names.get(addr).map(String::as_str)
+    }
+
+    /// Symbol at a location, or the name of the interrupt vector there
+    pub fn label(&self, at: &Location) -> Option<&str> {
+        self.get(at).or_else(|| match *at {
+            Location::Fixed(bank, offset) => {
+                match rupt_vector(fixed_fixed_address(bank, offset)?) {
+                    Some((name, 0)) => Some(name),
+                    _ => None,
+                }
+            }
+            Location::Erasable(_) => None,
+        })
+    }
+
+    /// `NAME` or `NAME+n` for the nearest symbol at or before a fixed
+    /// location in the same bank
+    pub fn describe(&self, at: &Location) -> Option<String> {
+        let (bank, offset) = match *at {
+            Location::Fixed(bank, offset) => (bank, offset),
+            Location::Erasable(_) => return self.get(at).map(String::from),
+        };
+        let (base, name) = self
+            .names
+            .iter()
+            .filter_map(|(addr, name)| match *addr {
+                Location::Fixed(b, o) if b == bank && o <= offset => Some((o, name)),
+                _ => None,
+            })
+            .max_by_key(|(o, name)| (*o, std::cmp::Reverse(name.as_str())))?;
+        Some(match offset - base {
+            0 => name.clone(),
+            n => format!("{}+{}", name, n),
+        })
+    }
+
+    /// Fixed-memory address of a symbol, or of a `BB,AAAA` or `AAAA` location
+    pub fn fixed(&self, name: &str) -> Option<FixedAddress> {
+        let location = Location::parse(name).or_else(|| {
+            self.names
+                .iter()
+                .find(|(_, symbol)| *symbol == name)
+                .map(|(addr, _)| *addr)
+        });
+        match location? {
+            Location::Fixed(bank, offset) => Some(FixedAddress::new(bank, offset)),
+            Location::Erasable(_) => None,
+        }
+    }
+}
+
+impl SymbolTable for Symbols {
+    fn erasable(&self, name: &str) -> Option<ErasableAddress> {
+        self.names.iter().find_map(|(addr, symbol)| match addr {
+            Location::Erasable(k) if symbol == name => {
+                Some(ErasableAddress::from_flat(*k as usize))
+            }
+            _ => None,
+        })
+    }
+}
+
+/// Decode every word of a fixed bank, following EXTEND prefixes
+pub fn decode_bank(rom: &ReadOnlyMemory, bank: usize) -> Vec<(u16, Option<Disassembled>)> {
+    let mut extended = false;
+    (0..STORAGE_SEGMENT_SIZE)
+        .map(|offset| {
+            let word = rom.word(bank, offset);
+            let inst = disassemble(word, extended);
+            extended = inst.is_some_and(|inst| inst.mnem == Mnemonic::EXTEND);
+            (word, inst)
+        })
+        .collect()
+}
+
+/// Write a bank-by-bank listing of `rope` with TC/TCF cross-references,
+/// using the original listings' names for idiomatic words if `idioms`
+pub fn write_listing(
+    rope: &Rope,
+    symbols: &Symbols,
+    idioms: bool,
+    out: &mut dyn Write,
+) -> std::io::Result<()> {
+    let rom = ReadOnlyMemory::new(rope);
+    let mut calls: BTreeMap<Location, Vec<Location>> = BTreeMap::new();
+    let mut jumps: BTreeMap<Location, Vec<Location>> = BTreeMap::new();
+
+    for bank in 0..STORAGE_SEGMENTS {
+        writeln!(out, "\n; Bank {:02o}", bank)?;
+        for (offset, (word, inst)) in decode_bank(&rom, bank).into_iter().enumerate() {
+            let here = Location::Fixed(bank, offset);
+            let label = symbols.label(&here).unwrap_or_default();
+
+            let mut inst = match inst {
+                Some(inst) => inst,
+                None => {
+                    writeln!(out, "{:<8} {:<12} {:05o}", here.to_string(), label, word)?;
+                    continue;
+                }
+            };
+
+            if idioms {
+                let at = memory::Location::Fixed(FixedAddress::new(bank, offset));
+                let addr = AddressSpace::running_in(bank)
+                    .address(at)
+                    .unwrap_or_default();
+                inst.pseudo = idiom(word, inst.extended, addr as u16);
+            }
+            let note = match inst.operand {
+                // NOOP and friends neither call nor jump anywhere of note
+                _ if inst.pseudo.is_some() => None,
+                Operand::Memory(k) => Location::resolve(k, bank).and_then(|target| {
+                    match inst.mnem {
+                        _ if matches!(target, Location::Erasable(_)) => {}
+                        Mnemonic::TC => calls.entry(target).or_default().push(here),
+                        Mnemonic::TCF => jumps.entry(target).or_default().push(here),
+                        _ => {}
+                    }
+                    symbols.label(&target).map(String::from)
+                }),
+                Operand::Channel(ch) => channel_spec(ch as usize).map(|spec| spec.name.to_string()),
+                Operand::None => None,
+            };
+            let mut line = format!(
+                "{:<8} {:<12} {:05o}  {:<16}",
+                here.to_string(),
+                label,
+                word,
+                inst.to_string()
+            );
+            if let Some(note) = note {
+                line.push_str("; ");
+                line.push_str(&note);
+            }
+            writeln!(out, "{}", line.trim_end())?;
+        }
+    }
+
+    write_xref(out, "TC", &calls, symbols)?;
+    write_xref(out, "TCF", &jumps, symbols)
+}
+
+fn write_xref(
+    out: &mut dyn Write,
+    kind: &str,
+    refs: &BTreeMap<Location, Vec<Location>>,
+    symbols: &Symbols,
+) -> std::io::Result<()> {
+    writeln!(out, "\n; {} cross-reference", kind)?;
+    for (target, sites) in refs.iter() {
+        let name = symbols.label(target).unwrap_or_default();
+        let sites: Vec<String> = sites.iter().map(|site| site.to_string()).collect();
+        let line = format!("{:<8} {:<12} {}", target.to_string(), name, sites.join(" "));
+        writeln!(out, "{}", line.trim_end())?;
+    }
+    Ok(())
+}
+
+/// The `disasm` subcommand and its options
+pub fn subcommand<'a, 'b>() -> clap::App<'a, 'b> {
+    clap::SubCommand::with_name("disasm")
+        .about("Write a disassembly listing of a bundled ROM")
+        .arg(
+            clap::Arg::with_name("idioms")
+                .long("idioms")
+                .help("Name idiomatic words as the original listings do (NOOP, RETURN, ...)"),
+        )
+        .arg(
+            clap::Arg::with_name("rom")
+                .required(true)
+                .possible_values(&crate::ropes::ROM_NAMES)
+                .help("ROM to disassemble"),
+        )
+        .arg(
+            clap::Arg::with_name("out")
+                .long("out")
+                .takes_value(true)
+                .help("Listing file (default: stdout)"),
+        )
+        .arg(
+            clap::Arg::with_name("symbols")
+                .long("symbols")
+                .takes_value(true)
+                .help("Symbol names, one `NAME ADDRESS` per line"),
+        )
+}
+
+/// Writes a static listing of a ROM, to a file or stdout
+pub fn command(args: &clap::ArgMatches) -> Result<(), String> {
+    let rope = crate::ropes::rope_by_name(args.value_of("rom").unwrap_or_default())
+        .ok_or("Invalid ROM")?;
+    let symbols = crate::load_symbols(args.value_of("symbols"))?;
+    let mut out: Box<dyn std::io::Write> = match args.value_of("out") {
+        Some(path) => Box::new(std::io::BufWriter::new(
+            std::fs::File::create(path).map_err(|e| format!("{}: {}", path, e))?,
+        )),
+        None => Box::new(std::io::stdout()),
+    };
+    let idioms = args.is_present("idioms");
+    write_listing(rope, &symbols, idioms, &mut out).map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod listing_tests {
+    use super::{write_listing, Location, Rope, Symbols};
+    use ragc_core::memory::rom::{encode_word, BANK_MAPPING};
+
+    #[test]
+    fn lists_instructions_and_cross_references() {
+        let mut rope: Box<Rope> = Box::new([[0; 1024]; 36]);
+        let fixed_fixed = &mut rope[BANK_MAPPING[2]];
+        fixed_fixed[0] = encode_word(0o04002); // TC 4002
+        fixed_fixed[1] = encode_word(0o00006); // EXTEND
+        fixed_fixed[2] = encode_word(0o01013); // WRITE 13
+        fixed_fixed[3] = encode_word(0o14000); // TCF 4000
+
+        let symbols = Symbols::parse("GOPROG 4000\nSUB 4002\nVAR 1234\nFAR 04,2000").unwrap();
+        assert_eq!(
+            symbols.get(&Location::Fixed(4, 0)),
+            Some("FAR"),
+            "switched fixed notation"
+        );
+
+        let mut out = Vec::new();
+        write_listing(&rope, &symbols, false, &mut out).unwrap();
+        let text = String::from_utf8(out).unwrap();
+
+        assert!(text.contains("4000     GOPROG       04002  TC      4002    ; SUB"));
+        assert!(text.contains("4002     SUB          01013  WRITE   13      ; CHAN13"));
+        assert!(text.contains("; TC cross-reference\n4002     SUB          4000"));
+        assert!(text.contains("; TCF cross-reference\n4000     GOPROG       4003"));
+        assert!(!text.contains("XXALQ"));
+
+        let mut out = Vec::new();
+        write_listing(&rope, &symbols, true, &mut out).unwrap();
+        let text = String::from_utf8(out).unwrap();
+        assert!(text.contains("4004     T6RUPT       00000  XXALQ"));
+        assert!(text.contains("4044     RADARUPT     00000  XXALQ"));
+        assert!(text.contains("4045                  00000  XXALQ"));
+        assert!(text.contains("; TC cross-reference\n4002     SUB          4000"));
+        assert!(Symbols::parse("BAD 9999").is_err());
+        assert!(Symbols::parse("BAD 2000").is_err());
+    }
+}
@@ -1,17 +1,77 @@
 extern crate clap;
 use crossbeam_channel::bounded; // Inter-thread communication
-use ctrlc; // exit using cntrl-c
-use env_logger;
-use log::error;
+use log::{error, info, warn};
 
 // Internal project modules
-use ragc_binaries;
+use ragc_core::constants::ports;
+use ragc_core::constants::registers::{
+    REGISTER_ACCUMULATOR, REGISTER_FIXED_BANK, REGISTER_LINK, REGISTER_RETURN,
+};
+use ragc_core::memory::mods::MCT_SECONDS;
+use ragc_core::rng::{streams, Rng};
 use ragc_core::{cpu, memory}; // Core emulation components
-use ragc_peripherals;
+use ragc_peripherals::pacing::Jitter;
+use ragc_peripherals::tracefile::{TraceEntry, TraceFile};
 
-// ROM configuration constants
-pub const NUM_ROM_BANKS: usize = 36;
-pub const WORDS_PER_ROM: usize = 1024;
+mod alarms;
+mod audit;
+mod autosnap;
+mod batch;
+mod calls;
+mod campaign;
+mod cfg;
+mod channels;
+mod compare;
+mod cond;
+mod config;
+mod console;
+mod control;
+mod edump;
+mod entrylog;
+mod executive;
+mod explain;
+mod flight;
+mod gse;
+mod http;
+mod idle;
+mod isa;
+mod kiosk;
+mod kit;
+mod listing;
+mod metadata;
+mod migrate;
+mod phases;
+mod pool;
+mod probe;
+mod quickboot;
+mod relay;
+mod replay;
+mod report;
+mod ropes;
+mod runtime;
+mod sched;
+mod serve;
+mod session;
+mod snapshot;
+mod stream;
+mod synth;
+mod timeline;
+mod trace;
+mod tracediff;
+mod vardb;
+mod watch;
+use ropes::{rom_info_by_name, rope_by_name};
+
+// ROM configuration constants, as the core lays out rope images
+pub const NUM_ROM_BANKS: usize = ragc_core::constants::STORAGE_SEGMENTS;
+pub const WORDS_PER_ROM: usize = ragc_core::constants::STORAGE_SEGMENT_SIZE;
+
+fn load_symbols(path: Option<&str>) -> Result<listing::Symbols, String> {
+    match path {
+        Some(path) => listing::Symbols::load(path),
+        None => Ok(listing::Symbols::default()),
+    }
+}
 
 /// Configures command-line interface using clap
 fn get_cli_config<'a>() -> clap::ArgMatches<'a> {
@@ -19,6 +79,164 @@ fn get_cli_config<'a>() -> clap::ArgMatches<'a> {
     clap::App::new("Rust AGC Emulator (RAGC)")
         .version("0.1")
         .about(description)
+        .arg(
+            clap::Arg::with_name("config")
+                .long("config")
+                .takes_value(true)
+                .help("Runtime configuration file (TOML)"),
+        )
+        .arg(
+            clap::Arg::with_name("seed")
+                .long("seed")
+                .takes_value(true)
+                .help("Seed for all randomized behaviour (default: from the clock)"),
+        )
+        .arg(
+            clap::Arg::with_name("bank-fault")
+                .long("bank-fault")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .help("Fault a fixed bank: <octal bank>:<zeros|parity> (repeatable)"),
+        )
+        .arg(
+            clap::Arg::with_name("stats")
+                .long("stats")
+                .help("Print the executed instruction mix at exit"),
+        )
+        .arg(
+            clap::Arg::with_name("latency")
+                .long("latency")
+                .takes_value(true)
+                .help("Charge extra MCTs per memory access: erasable=<n>,fixed=<n>,counter=<n>"),
+        )
+        .arg(
+            clap::Arg::with_name("quick-boot")
+                .long("quick-boot")
+                .help("Run flat out until the software is idle after its fresh start, then in real time"),
+        )
+        .arg(
+            clap::Arg::with_name("kiosk")
+                .long("kiosk")
+                .help("Exhibit profile: standby idles the host and any key wakes the computer"),
+        )
+        .arg(
+            clap::Arg::with_name("kiosk-idle")
+                .long("kiosk-idle")
+                .takes_value(true)
+                .requires("kiosk")
+                .help("Fresh start (V36E) after this many minutes without a key"),
+        )
+        .arg(
+            clap::Arg::with_name("relay-ms")
+                .long("relay-ms")
+                .takes_value(true)
+                .help("DSKY relay update cadence in milliseconds (0 = immediate)"),
+        )
+        .arg(
+            clap::Arg::with_name("speed")
+                .long("speed")
+                .takes_value(true)
+                .help("Emulation speed relative to real time, e.g. 10 to sit out the ISS turn-on delay"),
+        )
+        .arg(
+            clap::Arg::with_name("priority")
+                .long("priority")
+                .takes_value(true)
+                .possible_values(&["normal", "realtime"])
+                .help("Emulation thread scheduling class; realtime falls back to normal without privileges"),
+        )
+        .arg(
+            clap::Arg::with_name("cpus")
+                .long("cpus")
+                .takes_value(true)
+                .help("Pin the emulation thread to these cores, e.g. 2,3 or 0-1"),
+        )
+        .arg(
+            clap::Arg::with_name("drift-ppm")
+                .long("drift-ppm")
+                .takes_value(true)
+                .allow_hyphen_values(true)
+                .help("Oscillator error in parts per million; positive runs the AGC fast"),
+        )
+        .args(&config::TelemetryConfig::args())
+        .arg(
+            clap::Arg::with_name("stream-vars")
+                .long("stream-vars")
+                .takes_value(true)
+                .help("Stream the erasable variables listed in this TOML file as NDJSON"),
+        )
+        .arg(
+            clap::Arg::with_name("probe")
+                .long("probe")
+                .takes_value(true)
+                .help("Sample the erasable words and channel bits listed in this TOML file every N MCTs, to CSV"),
+        )
+        .arg(
+            clap::Arg::with_name("report")
+                .long("report")
+                .takes_value(true)
+                .help("Write a run summary at exit (HTML for .html, else markdown)"),
+        )
+        .arg(
+            clap::Arg::with_name("session")
+                .long("session")
+                .takes_value(true)
+                .help("Log display, lamp and key events as JSONL for replays and demos"),
+        )
+        .arg(
+            clap::Arg::with_name("flight-recorder")
+                .long("flight-recorder")
+                .takes_value(true)
+                .help("Record channel changes, downlink and variable samples to SQLite"),
+        )
+        .arg(
+            clap::Arg::with_name("flight-rate")
+                .long("flight-rate")
+                .takes_value(true)
+                .default_value("1")
+                .help("Flight recorder variable samples per emulated second"),
+        )
+        .arg(
+            clap::Arg::with_name("timeline")
+                .long("timeline")
+                .takes_value(true)
+                .help("Named timeline markers (TOML) to note in the log, streams and report"),
+        )
+        .args(&control::args())
+        .arg(
+            clap::Arg::with_name("gse")
+                .long("gse")
+                .takes_value(true)
+                .help("Ground-support monitor port for register watches and FETCH/STORE/TCSAJ: <host:port>"),
+        )
+        .args(&autosnap::args())
+        .arg(
+            clap::Arg::with_name("symbols")
+                .long("symbols")
+                .takes_value(true)
+                .help("Symbol table (NAME ADDRESS lines) for console watch expressions"),
+        )
+        .arg(
+            clap::Arg::with_name("vars")
+                .long("vars")
+                .takes_value(true)
+                .help("Variable database (TOML) for the rope (default: the bundled ROM's)"),
+        )
+        .args(&replay::args())
+        .args(&trace::args())
+        .arg(
+            clap::Arg::with_name("explain")
+                .long("explain")
+                .takes_value(true)
+                .help("Explain each step as a JSON line, to a file or - for stdout; runs slowly unless --speed is given"),
+        )
+        .arg(entrylog::arg())
+        .arg(
+            clap::Arg::with_name("strict")
+                .long("strict")
+                .help("Halt on any emulation anomaly: ROM writes, unknown channels, invalid instructions, missing banks"),
+        )
         .subcommand(
             clap::SubCommand::with_name("retread50")
                 .help("Execute using RETREAD50 (Apollo 11 CM pre-launch)"),
@@ -31,12 +249,75 @@ fn get_cli_config<'a>() -> clap::ArgMatches<'a> {
             clap::SubCommand::with_name("comanche55")
                 .help("Start with COMANCHE55 ROM image (Apollo 11 CM)"),
         )
+        .subcommand(replay::subcommand())
+        .subcommand(batch::subcommand())
+        .subcommand(serve::subcommand())
+        .subcommand(listing::subcommand())
+        .subcommand(cfg::subcommand())
+        .subcommand(compare::subcommand())
+        .subcommand(audit::subcommand())
+        .subcommand(session::subcommand())
+        .subcommand(snapshot::subcommand())
+        .subcommand(edump::subcommand())
+        .subcommand(trace::subcommand())
+        .subcommand(isa::subcommand())
+        .subcommand(synth::subcommand())
         .get_matches()
 }
 
+/// Step the CPU, handing the instruction to the trace writer if tracing
+fn traced_step(
+    cpu: &mut cpu::Cpu,
+    trace: &mut Option<TraceFile>,
+    explainer: &mut Option<explain::Explainer>,
+) -> cpu::StepResult {
+    let entry = trace.as_ref().map(|_| TraceEntry::capture(cpu));
+    let before = explainer.as_ref().map(|_| explain::Before::capture(cpu));
+    let step = cpu.step();
+    if let (Some(trace), Some(entry)) = (trace, entry) {
+        // Interrupt entries and counter updates run no instruction
+        if !step.took_interrupt && !step.unprogrammed {
+            trace.record(entry);
+        }
+    }
+    if let (Some(explainer), Some(before)) = (explainer, before) {
+        explainer.record(&before, cpu, &step);
+    }
+    step
+}
+
+/// Report the fault a halting run stopped on
+fn strict_halt(cpu: &cpu::Cpu, fault: cpu::CpuFault) {
+    let mem = cpu.memory();
+    error!(
+        "Halted after {} MCTs on {:?} (bank {:02o}, A={:05o} L={:05o} Q={:05o})",
+        cpu.total_cycles,
+        fault,
+        mem.banks().fixed,
+        mem.read(REGISTER_ACCUMULATOR),
+        mem.read(REGISTER_LINK),
+        mem.read(REGISTER_RETURN),
+    );
+}
+
+/// Log to stderr, filtered by RUST_LOG
+#[cfg(not(feature = "tracing"))]
+fn init_logging() {
+    env_logger::init();
+}
+
+/// Log to stderr through tracing, filtered by RUST_LOG, which also takes
+/// span filters such as `ragc_core[io{channel=10}]=trace`
+#[cfg(feature = "tracing")]
+fn init_logging() {
+    tracing_subscriber::fmt()
+        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+        .init();
+}
+
 /// Main entry point for AGC emulator
 fn main() {
-    env_logger::init();
+    init_logging();
 
     // Set up Ctrl-C handler with channel communication
     let (signal_sender, signal_receiver) = bounded(1);
@@ -55,66 +336,799 @@ fn main() {
     // Parse command-line arguments
     let cli_matches = get_cli_config();
 
-    // Load appropriate ROM image
-    let rom_data = match cli_matches.subcommand_name() {
-        Some("retread50") => *ragc_binaries::RETREAD50_ROPE,
-        _ => {
-            error!("Invalid ROM specified");
+    // A mission kit supplies whatever files the command line leaves out
+    let kit = match kit::from_args(&cli_matches) {
+        Ok(kit) => kit,
+        Err(e) => {
+            error!("Invalid mission kit: {}", e);
             return;
         }
-        Some("luminary99") => *ragc_binaries::LUMINARY99_ROPE,
-        _ => {
-            error!("Invalid ROM specified");
+    };
+    let kit_file = |file: fn(&kit::Kit) -> Option<&str>| kit.as_ref().and_then(file);
+
+    // Fail before loading anything if the emulator lacks what the run needs
+    if let Some(needs) = cli_matches
+        .subcommand_matches("run")
+        .and_then(|args| args.values_of("require"))
+    {
+        let caps = ragc_core::capabilities();
+        if let Some(missing) = needs.filter_map(|need| caps.check(need).err()).next() {
+            error!("{}", missing);
             return;
         }
-        Some("comanche55") => *ragc_binaries::COMANCHE55_ROPE,
-        _ => {
-            error!("Invalid ROM specified");
+    }
+
+    let config_path = cli_matches
+        .value_of("config")
+        .or(kit_file(|k| k.config.as_deref()));
+    let mut runtime_config = match config_path {
+        Some(path) => match config::RuntimeConfig::load(path) {
+            Ok(x) => x,
+            Err(e) => {
+                error!("Invalid runtime configuration: {}", e);
+                return;
+            }
+        },
+        None => config::RuntimeConfig::default(),
+    };
+    if let Some(arg) = cli_matches.value_of("latency") {
+        match config::LatencyConfig::parse(arg) {
+            Ok(latency) => runtime_config.latency = Some(latency),
+            Err(e) => {
+                error!("{}", e);
+                return;
+            }
+        }
+    }
+    if cli_matches.is_present("kiosk") {
+        let kiosk = runtime_config.kiosk.get_or_insert_with(Default::default);
+        if let Some(minutes) = cli_matches.value_of("kiosk-idle") {
+            match minutes.parse::<u32>() {
+                Ok(minutes) if minutes > 0 => kiosk.idle_minutes = Some(minutes),
+                _ => {
+                    error!("Invalid kiosk idle time: {}", minutes);
+                    return;
+                }
+            }
+        }
+    }
+    for arg in cli_matches.values_of("bank-fault").into_iter().flatten() {
+        match config::BankFaultConfig::parse(arg) {
+            Ok(fault) => runtime_config.bank_faults.push(fault),
+            Err(e) => {
+                error!("{}", e);
+                return;
+            }
+        }
+    }
+
+    if let Err(e) = runtime_config.telemetry.override_from(&cli_matches) {
+        error!("{}", e);
+        return;
+    }
+    let telemetry = &runtime_config.telemetry;
+    let telemetry_queue = &runtime_config.queues.telemetry;
+    let telemetry = match telemetry.build().and_then(|mut telemetry| {
+        telemetry.queue = config::QueuesConfig::build(telemetry_queue, telemetry.queue)?;
+        Ok(telemetry)
+    }) {
+        Ok(x) => x,
+        Err(e) => {
+            error!("Invalid telemetry configuration: {}", e);
+            return;
+        }
+    };
+    let dsky_queue = match config::QueuesConfig::build(
+        &runtime_config.queues.dsky,
+        ragc_peripherals::dsky::DEFAULT_DSKY_QUEUE,
+    ) {
+        Ok(x) => x,
+        Err(e) => {
+            error!("Invalid DSKY queue configuration: {}", e);
             return;
         }
     };
+    let dsky_rate = match runtime_config.dsky.build() {
+        Ok(x) => x,
+        Err(e) => {
+            error!("Invalid DSKY configuration: {}", e);
+            return;
+        }
+    };
+
+    // Every stochastic feature draws from streams of this one seed
+    let seed = match cli_matches.value_of("seed").map(|s| s.parse::<u64>()) {
+        Some(Ok(seed)) => seed,
+        Some(Err(_)) => {
+            error!("Invalid seed");
+            return;
+        }
+        None => runtime_config.seed.unwrap_or_else(|| {
+            std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map(|d| d.as_nanos() as u64)
+                .unwrap_or(0)
+        }),
+    };
+    info!("RNG seed: {}", seed);
+    let rng = Rng::new(seed);
+
+    // Emulator development and ROM verification tolerate no anomalies
+    let fault_policy = match cli_matches.is_present("strict") {
+        true => cpu::FaultPolicy::Strict,
+        false => cpu::FaultPolicy::Log,
+    };
+
+    // Synthetic telemetry needs no ROM and no CPU
+    if let Some(args) = cli_matches.subcommand_matches("synth-downlink") {
+        synth::command(args, telemetry, &rng, &signal_receiver);
+        return;
+    }
+
+    if let Some(args) = cli_matches.subcommand_matches("audit") {
+        if let Err(e) = audit::command(args, seed) {
+            error!("Determinism audit failed: {}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    // CI runs exit with a status instead of opening a DSKY
+    match batch::from_args(&cli_matches) {
+        Ok(Some(headless)) => {
+            let randomize = runtime_config.randomize_erasable;
+            let symbols = cli_matches
+                .value_of("symbols")
+                .or(kit_file(|k| k.symbols.as_deref()));
+            let status = headless.run(symbols, seed, randomize, fault_policy, kit.as_ref());
+            std::process::exit(status);
+        }
+        Ok(None) => {}
+        Err(e) => {
+            error!("{}", e);
+            return;
+        }
+    }
+
+    // Static analysis needs no CPU
+    if let Some(args) = cli_matches.subcommand_matches("disasm") {
+        if let Err(e) = listing::command(args) {
+            error!("Disassembly failed: {}", e);
+        }
+        return;
+    }
+    if let Some(args) = cli_matches.subcommand_matches("compare") {
+        if let Err(e) = compare::command(args) {
+            error!("Comparison failed: {}", e);
+        }
+        return;
+    }
+    if let Some(args) = cli_matches.subcommand_matches("render-session") {
+        if let Err(e) = session::command(args) {
+            error!("Session rendering failed: {}", e);
+        }
+        return;
+    }
+    if let Some(args) = cli_matches.subcommand_matches("snapshot") {
+        snapshot::command(args);
+        return;
+    }
+    if let Some(args) = cli_matches.subcommand_matches("erasable") {
+        edump::command(args);
+        return;
+    }
+    if let Some(args) = cli_matches.subcommand_matches("trace") {
+        trace::command(args);
+        return;
+    }
+    if let Some(args) = cli_matches.subcommand_matches("isa") {
+        isa::command(args);
+        return;
+    }
+    if let Some(args) = cli_matches.subcommand_matches("cfg") {
+        if let Err(e) = cfg::command(args) {
+            error!("Graph export failed: {}", e);
+        }
+        return;
+    }
+
+    // Load appropriate ROM image. Bundled ropes are kept by reference for
+    // the serve pool.
+    let replay_args = cli_matches.subcommand_matches("replay");
+    let run_args = cli_matches.subcommand_matches("run");
+    let serve_args = cli_matches.subcommand_matches("serve");
+    let rom_name = match (replay_args.or(serve_args), run_args) {
+        (Some(args), _) => args.value_of("rom"),
+        (_, Some(args)) => kit::run_source(args, kit.as_ref()).1,
+        _ => cli_matches.subcommand_name(),
+    };
+    let bundled = rom_name.and_then(rope_by_name);
+    let rom_data = match run_args.map(|args| kit::run_rope(args, kit.as_ref())) {
+        Some(Ok(rope)) => rope,
+        Some(Err(e)) => {
+            error!("{}", e);
+            return;
+        }
+        None => match bundled {
+            Some(rope) => *rope,
+            None => {
+                error!("Invalid ROM specified");
+                return;
+            }
+        },
+    };
+
+    // Fault-injection campaigns run headless and exit when done
+    if let Some(campaign_config) = &runtime_config.campaign {
+        let randomize = runtime_config.randomize_erasable;
+        if let Err(e) = campaign::run(&rom_data, campaign_config, &rng, randomize) {
+            error!("Campaign failed: {}", e);
+        }
+        return;
+    }
+
+    // Channel taps must outlive the memory map they are registered with
+    let mut channel_taps: Vec<_> = runtime_config.taps.iter().map(|t| t.build()).collect();
+    let report_path = cli_matches.value_of("report");
+    let mut dsky_log = report_path.map(|_| report::DskyLog::default());
+    let session_log = match cli_matches
+        .value_of("session")
+        .map(session::SessionLog::create)
+    {
+        Some(Ok(x)) => Some(x),
+        Some(Err(e)) => {
+            error!("Cannot create session log: {}", e);
+            return;
+        }
+        None => None,
+    };
+    let mut session_taps = session_log.as_ref().map(|log| log.taps());
 
     // Initialize hardware components
-    let mut queue_instance = heapless::spsc::Queue::new();
-    let (rupt_line, _) = queue_instance.split(); // RUPT line communication
 
-    let mut display_unit = ragc_peripherals::dsky::DskyDisplay::new();
+    let capture = match replay::capture(&cli_matches) {
+        Ok(x) => x,
+        Err(e) => {
+            error!("{}", e);
+            return;
+        }
+    };
+    let mut display_unit =
+        ragc_peripherals::dsky::DskyDisplay::with_link(capture, dsky_queue, dsky_rate);
+    let dropped_packets = display_unit.dropped_packets();
+
+    // Recorded input is fed on the emulated clock, optionally faster than real time
+    let scenario = kit_file(|k| k.scenario.as_deref());
+    let mut replay = match replay::from_args(&cli_matches, scenario) {
+        Ok(x) => x,
+        Err(e) => {
+            error!("{}", e);
+            return;
+        }
+    };
+    let speed = replay_args.unwrap_or(&cli_matches).value_of("speed");
+    let default_speed = match cli_matches.is_present("explain") {
+        true => explain::EXPLAIN_SPEED.to_string(),
+        false => "1".to_string(),
+    };
+    let speed = match speed.unwrap_or(&default_speed).parse::<f64>() {
+        Ok(speed) if speed.is_finite() && speed > 0.0 => speed,
+        _ => {
+            error!("Invalid speed");
+            return;
+        }
+    };
+
+    // Command queue for frontends; processed between instructions
+    let (mut runtime, runtime_handle) =
+        runtime::Runtime::new(Some(display_unit.keypress_sender()), seed);
+    runtime.set_speed(speed);
+
+    // External tools attach through the control socket
+    if let Some((addr, access)) = control::from_args(&cli_matches, runtime_config.control.as_ref())
+    {
+        if let Err(e) = control::spawn(&addr, access, runtime_handle.clone()) {
+            error!("Control socket failed: {}", e);
+            return;
+        }
+    }
+    if let Some(addr) = cli_matches.value_of("gse") {
+        if let Err(e) = gse::spawn(addr, runtime_handle.clone()) {
+            error!("GSE port failed: {}", e);
+            return;
+        }
+    }
+    if let (Some(args), Some(rope)) = (serve_args, bundled) {
+        let rom_info = rom_name.and_then(rom_info_by_name).unwrap_or_default();
+        if let Err(e) = serve::spawn(args, rope, rom_info, runtime_handle.clone()) {
+            error!("{}", e);
+            return;
+        }
+    }
+
+    // Operator console for commanding hardware restarts
+    let symbols_path = cli_matches
+        .value_of("symbols")
+        .or(kit_file(|k| k.symbols.as_deref()));
+    let symbols = match load_symbols(symbols_path) {
+        Ok(x) => x,
+        Err(e) => {
+            error!("Invalid symbol table: {}", e);
+            return;
+        }
+    };
+    // Timeline conditions may name symbols too
+    let mut timeline = match cli_matches
+        .value_of("timeline")
+        .or(kit_file(|k| k.timeline.as_deref()))
+        .map(|path| timeline::Timeline::load(path, &symbols))
+    {
+        Some(Ok(x)) => Some(x),
+        Some(Err(e)) => {
+            error!("Invalid timeline: {}", e);
+            return;
+        }
+        None => None,
+    };
+    runtime.set_call_markers(calls::Markers::new(&symbols));
+    let mut explainer = match cli_matches
+        .value_of("explain")
+        .map(|path| explain::Explainer::create(path, symbols.clone()))
+    {
+        Some(Ok(x)) => Some(x),
+        Some(Err(e)) => {
+            error!("Cannot explain: {}", e);
+            return;
+        }
+        None => None,
+    };
+    let mut entry_logs = match entrylog::from_args(&cli_matches, &symbols) {
+        Ok(x) => x,
+        Err(e) => {
+            error!("{}", e);
+            return;
+        }
+    };
+    let padload = match kit.as_ref().map(|kit| kit.padload(&symbols)) {
+        Some(Ok(x)) => x,
+        Some(Err(e)) => {
+            error!("Invalid padload: {}", e);
+            return;
+        }
+        None => None,
+    };
+
+    // Named variables for streams, watches and the report
+    let vars = match cli_matches.value_of("vars") {
+        Some(path) => match vardb::VarDb::load(path, &symbols) {
+            Ok(x) => x,
+            Err(e) => {
+                error!("Invalid variable database: {}", e);
+                return;
+            }
+        },
+        None => rom_name
+            .and_then(|rom| vardb::VarDb::bundled(rom, &symbols))
+            .unwrap_or_default(),
+    };
+    if let (Some(written_for), Some(rom)) = (vars.rom.as_deref(), rom_name) {
+        if written_for != rom {
+            warn!("Variable database is for {}, running {}", written_for, rom);
+        }
+    }
+    if !vars.missing.is_empty() {
+        info!(
+            "Variables not in the symbol table: {}",
+            vars.missing.join(", ")
+        );
+    }
+    let mut quick_boot = match (cli_matches.is_present("quick-boot"), &vars.ready) {
+        (true, Some(ready)) => Some(quickboot::QuickBoot::new(ready.clone())),
+        (true, None) => {
+            warn!("No idle state known for this rope (ready in --vars), booting in real time");
+            None
+        }
+        (false, _) => None,
+    };
+    // Features the rope's metadata can't support fall back to raw addresses
+    let metadata = metadata::Metadata {
+        symbols: &symbols,
+        vars: &vars,
+    };
+    let metadata::Negotiated {
+        capabilities,
+        modreg,
+        failreg,
+    } = metadata.negotiate(&runtime_config);
+    let alarm_decoder = alarms::AlarmDecoder::new(
+        rom_name.and_then(alarms::alarm_table),
+        &runtime_config.alarms,
+    );
+    let idle_loops = match idle::IdleLoops::new(&runtime_config.idle, rom_name, &symbols) {
+        Ok(x) => x,
+        Err(e) => {
+            error!("Invalid idle loop: {}", e);
+            return;
+        }
+    };
+    let replay_handle = runtime_handle.clone();
+    let kiosk_handle = runtime_handle.clone();
+    let console_vars = vars.clone();
+    console::spawn(runtime_handle, symbols, console_vars, capabilities);
 
-    let mut rupt_handler = ragc_peripherals::downrupt::DownruptPeriph::new();
+    let mut rupt_handler = ragc_peripherals::downrupt::DownruptPeriph::with_config(telemetry);
+    let dropped_pairs = rupt_handler.dropped_pairs();
 
     // Configure memory map with ROM and peripherals
-    let memory_map =
-        memory::MemoryMap::new(&rom_data, &mut rupt_handler, &mut display_unit, rupt_line);
+    let mut rom_info = rom_name.and_then(rom_info_by_name).unwrap_or_default();
+    if let Some(newjob) = runtime_config.newjob {
+        rom_info.newjob = newjob;
+    }
+    if modreg.is_some() {
+        rom_info.modreg = modreg;
+    }
+    if failreg.is_some() {
+        rom_info.failreg = failreg;
+    }
+    let mut builder = memory::MemoryMapBuilder::new()
+        .rope(&rom_data)
+        .rom_info(rom_info)
+        .downlink(&mut rupt_handler)
+        .dsky(&mut display_unit);
+    for (channel, tap) in channel_taps.iter_mut() {
+        builder = builder.tap(*channel, tap);
+    }
+    if let Some(log) = &mut dsky_log {
+        builder = builder.tap(ports::CHANNEL_DSKY, log);
+    }
+    for (channel, tap) in session_taps.iter_mut().flatten() {
+        builder = builder.tap(*channel, tap);
+    }
+    for fault in runtime_config.bank_faults.iter() {
+        builder = builder.bank_fault(fault.bank, fault.fault());
+    }
+    let mut memory_map = builder.build();
+    if runtime_config.randomize_erasable {
+        memory_map.randomize_erasable(&mut rng.fork(streams::ERASABLE_INIT));
+    }
+    if let Some(padload) = &padload {
+        padload.apply(&mut memory_map);
+    }
 
     // Create and initialize CPU core
     let mut agc_cpu = cpu::Cpu::new(memory_map);
+    agc_cpu.set_fault_policy(fault_policy);
+    agc_cpu.set_latency(runtime_config.latency.map(|l| l.model()));
+    agc_cpu.set_wake_on_key(runtime_config.kiosk.is_some());
+    for (at, log) in entry_logs.iter_mut() {
+        agc_cpu.add_hook(*at, log);
+    }
     agc_cpu.reset(); // Perform AGC cold start
 
+    if let Err(e) = relay::configure(&cli_matches, &runtime_config, agc_cpu.memory_mut()) {
+        error!("{}", e);
+        return;
+    }
+
+    // A drifting oscillator runs every AGC clock fast or slow against
+    // scenario time, for exercising clock updates (P27, V55)
+    let drift_ppm = match cli_matches.value_of("drift-ppm").map(str::parse::<f64>) {
+        Some(Ok(ppm)) => Some(ppm),
+        Some(Err(_)) => {
+            error!("Invalid drift");
+            return;
+        }
+        None => runtime_config.drift_ppm,
+    };
+    let drift = match drift_ppm.map(config::drift_rate).unwrap_or(Ok(1.0)) {
+        Ok(x) => x,
+        Err(e) => {
+            error!("{}", e);
+            return;
+        }
+    };
+    if let Some(ppm) = drift_ppm {
+        info!("Oscillator drifting {:+} ppm", ppm);
+    }
+
+    // Live variable export for analysis tools
+    let stream_path = cli_matches
+        .value_of("stream-vars")
+        .or(kit_file(|k| k.stream_vars.as_deref()));
+    let mut var_stream = match stream_path {
+        Some(path) => {
+            match stream::StreamConfig::load(path, &vars).and_then(stream::VarStream::open) {
+                Ok(x) => Some(x),
+                Err(e) => {
+                    error!("Invalid variable stream: {}", e);
+                    return;
+                }
+            }
+        }
+        None => None,
+    };
+
+    let mut probe = match cli_matches.value_of("probe").map(|path| {
+        probe::ProbeConfig::load(path, &vars).and_then(|config| probe::Probe::open(config, &vars))
+    }) {
+        Some(Ok(x)) => Some(x),
+        Some(Err(e)) => {
+            error!("Invalid probe: {}", e);
+            return;
+        }
+        None => None,
+    };
+
+    let flight_rate = match cli_matches.value_of("flight-rate").unwrap_or("1").parse() {
+        Ok(x) => x,
+        Err(e) => {
+            error!("Invalid flight recorder rate: {}", e);
+            return;
+        }
+    };
+    let mut flight = match cli_matches
+        .value_of("flight-recorder")
+        .map(|path| flight::FlightRecorder::create(path, &vars, flight_rate))
+    {
+        Some(Ok(x)) => Some(x),
+        Some(Err(e)) => {
+            error!("Cannot create flight recorder: {}", e);
+            return;
+        }
+        None => None,
+    };
+    if let Some(fr) = &flight {
+        fr.attach(agc_cpu.memory_mut());
+    }
+
+    let mut recorder = report_path.map(|_| report::RunRecorder::new());
+
+    let mut trace = match trace::from_args(&cli_matches) {
+        Ok(x) => x,
+        Err(e) => {
+            error!("Cannot create trace: {}", e);
+            return;
+        }
+    };
+
+    let mut autosnap = match autosnap::from_args(&cli_matches) {
+        Ok(x) => x,
+        Err(e) => {
+            error!("Invalid auto-snapshot settings: {}", e);
+            return;
+        }
+    };
+
+    let mut kiosk = runtime_config
+        .kiosk
+        .map(|k| kiosk::Kiosk::new(&k, std::time::Instant::now()));
+    let (strategy, mut pacer) = match runtime_config.pacing.build() {
+        Ok(x) => x,
+        Err(e) => {
+            error!("Invalid pacing: {}", e);
+            return;
+        }
+    };
+    let mut jitter = Jitter::default();
+    let scheduling = match runtime_config.scheduling.build() {
+        Ok(x) => x,
+        Err(e) => {
+            error!("Invalid scheduling: {}", e);
+            return;
+        }
+    };
+    let scheduling = match scheduling.with_args(&cli_matches) {
+        Ok(x) => x,
+        Err(e) => {
+            error!("{}", e);
+            return;
+        }
+    };
+    // Helper threads are all running by now and keep their own scheduling
+    scheduling.apply();
+
     // Main emulation loop
     let mut cycle_timer = std::time::Instant::now();
-    loop {
+    let mut alarms_seen = 0;
+    let mut idling = false;
+    'emulation: loop {
         if !signal_receiver.is_empty() {
             break;
         }
 
         // Timing control for cycle-accurate emulation
-        let elapsed_time = cycle_timer.elapsed();
-        if elapsed_time.as_millis() == 0 {
-            // Prevent busy-waiting at high speeds
+        if runtime.is_paused() {
+            // Keep servicing commands (single steps) while halted
+            while runtime.is_paused() && runtime.poll(&mut agc_cpu) {
+                traced_step(&mut agc_cpu, &mut trace, &mut explainer);
+            }
             std::thread::sleep(std::time::Duration::from_micros(5000));
+            cycle_timer = std::time::Instant::now();
             continue;
         }
 
-        // Calculate target cycles based on AGC clock speed (11.7µs/cycle)
-        let target_cycles = (elapsed_time.as_micros() as f64 / 11.7) as i64;
+        if let Some(kiosk) = &mut kiosk {
+            if agc_cpu.in_standby() {
+                // Nothing runs in standby; a step now and then sees a key
+                std::thread::sleep(kiosk::STANDBY_POLL);
+                agc_cpu.step();
+                cycle_timer = std::time::Instant::now();
+                continue;
+            }
+            let key_presses = agc_cpu.key_presses();
+            if let Some(key) = kiosk.poll(key_presses, std::time::Instant::now()) {
+                kiosk_handle.press_key(key);
+            }
+        }
+
+        // Wait out the frame; an idle program can wait longer, and a quick
+        // boot doesn't wait at all
+        match (&quick_boot, idling) {
+            (Some(_), _) => {}
+            (None, true) => std::thread::sleep(idle::IDLE_SLEEP),
+            (None, false) => pacer.wait(cycle_timer),
+        }
+
+        // Calculate target cycles based on AGC clock speed (11.71875µs/cycle).
+        // At low speeds, let time build up to a whole MCT.
+        let elapsed_time = cycle_timer.elapsed();
+        let target_cycles = match quick_boot {
+            Some(_) => quickboot::BOOT_BATCH,
+            None => (elapsed_time.as_secs_f64() * runtime.speed() * drift / MCT_SECONDS) as i64,
+        };
+        if target_cycles == 0 {
+            continue;
+        }
+        jitter.record(std::time::Instant::now());
         let mut executed_cycles = 0;
 
         // Execute instructions until catching up with real time
         while executed_cycles < target_cycles {
-            executed_cycles += agc_cpu.step() as i64;
+            if let Some(rp) = &mut replay {
+                rp.poll(agc_cpu.total_cycles, &replay_handle);
+                if rp.is_done() {
+                    info!("Replay finished");
+                    replay = None;
+                }
+            }
+            if !runtime.poll(&mut agc_cpu) {
+                break;
+            }
+            // Traces and explanations want every instruction
+            idling = trace.is_none() && explainer.is_none() && idle_loops.contains(&agc_cpu);
+            if idling {
+                let idled = agc_cpu.idle((target_cycles - executed_cycles) as u32);
+                if idled > 0 {
+                    executed_cycles += idled as i64;
+                    continue;
+                }
+            }
+            let fb = agc_cpu.memory().read(REGISTER_FIXED_BANK);
+            let step = traced_step(&mut agc_cpu, &mut trace, &mut explainer);
+            executed_cycles += step.cycles as i64;
+            if let Some(fault) = step.fault.filter(|_| agc_cpu.halted()) {
+                strict_halt(&agc_cpu, fault);
+                break 'emulation;
+            }
+            if let Some(rec) = &mut recorder {
+                rec.record(&agc_cpu, fb, &step);
+            }
+            if agc_cpu.alarm_count() != alarms_seen {
+                alarms_seen = agc_cpu.alarm_count();
+                if let Some(code) = agc_cpu.last_alarm() {
+                    info!("{}", alarm_decoder.message(code));
+                    if let Some(fr) = &mut flight {
+                        let code = format!("{:05o}", code);
+                        fr.event(agc_cpu.total_cycles, "alarm", &code);
+                    }
+                }
+            }
+            if let Some(vs) = &mut var_stream {
+                vs.poll(agc_cpu.memory(), agc_cpu.total_cycles);
+            }
+            if let Some(probe) = &mut probe {
+                probe.poll(agc_cpu.memory(), agc_cpu.total_cycles as u64);
+            }
+            if let Some(log) = &session_log {
+                log.set_time(agc_cpu.total_cycles);
+            }
+            if let Some(fr) = &mut flight {
+                let total_cycles = agc_cpu.total_cycles;
+                fr.poll(agc_cpu.memory_mut(), total_cycles);
+            }
+            for marker in timeline.iter_mut().flat_map(|t| t.poll(&agc_cpu)) {
+                info!("Timeline: {} at {:.3} s", marker.name, marker.time());
+                if let Some(rec) = &mut recorder {
+                    rec.mark(&marker);
+                }
+                if let Some(vs) = &mut var_stream {
+                    vs.mark(&marker);
+                }
+                if let Some(log) = &session_log {
+                    log.mark(&marker);
+                }
+                if let Some(fr) = &mut flight {
+                    fr.mark(&marker);
+                }
+            }
+            if let Some(auto) = &mut autosnap {
+                auto.poll(&mut agc_cpu, seed);
+            }
+        }
+
+        if let Some(boot) = quick_boot.as_ref().filter(|boot| boot.done(&agc_cpu)) {
+            let seconds = quickboot::QuickBoot::seconds(&agc_cpu);
+            match boot.ready(&agc_cpu) {
+                true => info!("Software idle after {:.1} s, now in real time", seconds),
+                false => warn!(
+                    "Software not idle ({}) after {:.1} s, now in real time",
+                    boot.condition(),
+                    seconds
+                ),
+            }
+            quick_boot = None;
         }
 
         // Reset timing for next frame
         cycle_timer = std::time::Instant::now();
     }
+
+    if cli_matches.is_present("stats") {
+        print!("{}", agc_cpu.instruction_stats());
+        println!(
+            "Counter updates: {} MCTs stolen, {} dropped",
+            agc_cpu.total_stolen_cycles(),
+            agc_cpu.dropped_counters()
+        );
+        if agc_cpu.latency().is_some() {
+            println!(
+                "Memory latency: {} of {} MCTs added",
+                agc_cpu.latency_cycles(),
+                agc_cpu.total_cycles
+            );
+        }
+        let access = agc_cpu.memory().access_stats();
+        println!(
+            "Illegal accesses: {} unmapped, {} fixed memory writes, {} missing banks, {} unassigned channels, {} output channel reads, {} input channel writes",
+            access.unmapped,
+            access.rom_writes,
+            access.missing_banks,
+            access.unknown_channels,
+            access.output_reads,
+            access.input_writes
+        );
+        println!(
+            "Peripheral queues: {} DSKY packets, {} downlink pairs dropped",
+            dropped_packets.get(),
+            dropped_pairs.get()
+        );
+        println!("Pacing ({:?}): {}", strategy, jitter);
+    }
+
+    if let Some(fr) = flight {
+        fr.finish();
+    }
+
+    if let Some(probe) = probe {
+        probe.finish();
+    }
+
+    if let Some(trace) = trace {
+        if let Err(e) = trace.finish() {
+            error!("Trace failed: {}", e);
+        }
+    }
+
+    if let (Some(path), Some(rec)) = (report_path, recorder) {
+        let mut summary = rec.finish(&agc_cpu, &rom_data);
+        summary.add_vars(&vars, agc_cpu.memory());
+        drop(agc_cpu); // Releases the DSKY log tap
+        if let Some(log) = dsky_log {
+            summary.add_dsky(log);
+        }
+        match summary.write(path) {
+            Ok(()) => info!("Run report written to {}", path),
+            Err(e) => error!("Run report failed: {}", e),
+        }
+    }
 }
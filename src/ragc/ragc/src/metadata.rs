@@ -0,0 +1,240 @@
+//! What the loaded rope's metadata lets the symbol-dependent features do.
+//! A hand-assembled or unlabelled rope lacks some of the symbols they look
+//! for; rather than failing, each feature is negotiated up front against
+//! the symbol table and the variable database and either runs in full or
+//! falls back to raw addresses, and the report says which and why.
+use std::io::Write;
+
+use ragc_core::symbols::{ErasableAddress, SymbolTable};
+
+use log::info;
+
+use crate::alarms;
+use crate::config::RuntimeConfig;
+use crate::listing::Symbols;
+use crate::vardb::VarDb;
+
+/// A feature and the erasable symbols it needs
+struct Feature {
+    name: &'static str,
+    needs: &'static [&'static str],
+    fallback: &'static str, // What is left without them
+}
+
+const FEATURES: [Feature; 5] = [
+    Feature {
+        name: "executive",
+        needs: &["PRIORITY", "LOC", "BANKSET", "LST1", "LST2"],
+        fallback: "exec <address> dumps the core sets as raw words",
+    },
+    Feature {
+        name: "modreg",
+        needs: &["MODREG"],
+        fallback: "no program tracking unless modreg is configured",
+    },
+    Feature {
+        name: "alarms",
+        needs: &["FAILREG"],
+        fallback: "no alarm decoding unless failreg is configured",
+    },
+    Feature {
+        name: "state-vector",
+        needs: &["RN", "VN", "PIPTIME"],
+        fallback: "watch RN/VN by address instead",
+    },
+    Feature {
+        name: "phases",
+        needs: &[], // Restart groups come from the variable database
+        fallback: "no restart groups; poke the phase tables by address",
+    },
+];
+
+/// How a feature will run
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Support {
+    Full,
+    Configured,                         // Addresses given in the configuration
+    Raw { missing: Vec<&'static str> }, // Symbols the rope doesn't give
+}
+
+/// The symbol table first, then the variable database's addresses
+pub struct Metadata<'a> {
+    pub symbols: &'a Symbols,
+    pub vars: &'a VarDb,
+}
+
+/// What negotiation settled: the features, and the MODREG and FAILREG
+/// addresses to track
+pub struct Negotiated {
+    pub capabilities: Capabilities,
+    pub modreg: Option<usize>,
+    pub failreg: Option<usize>,
+}
+
+impl Metadata<'_> {
+    /// Negotiate every feature, taking registers the configuration gives
+    /// over the rope's, and log any that fall back to raw addresses
+    pub fn negotiate(&self, config: &RuntimeConfig) -> Negotiated {
+        let mut capabilities = Capabilities::negotiate(self);
+        if config.modreg.is_some() {
+            capabilities.configure("modreg");
+        }
+        if config.failreg.is_some() {
+            capabilities.configure("alarms");
+        }
+        let degraded: Vec<_> = capabilities.degraded().collect();
+        if !degraded.is_empty() {
+            info!(
+                "Rope metadata incomplete, raw addresses for: {} (console: caps)",
+                degraded.join(", ")
+            );
+        }
+        Negotiated {
+            capabilities,
+            modreg: config.modreg.or_else(|| modreg(self)),
+            failreg: config.failreg.or_else(|| alarms::failreg(self)),
+        }
+    }
+}
+
+impl SymbolTable for Metadata<'_> {
+    fn erasable(&self, name: &str) -> Option<ErasableAddress> {
+        self.symbols
+            .erasable(name)
+            .or_else(|| self.vars.erasable(name))
+    }
+}
+
+/// The outcome of negotiating every feature
+#[derive(Clone, PartialEq, Debug)]
+pub struct Capabilities {
+    features: Vec<(&'static str, Support)>,
+}
+
+impl Capabilities {
+    pub fn negotiate(metadata: &Metadata) -> Self {
+        let features = FEATURES
+            .iter()
+            .map(|feature| {
+                let mut missing: Vec<_> = feature
+                    .needs
+                    .iter()
+                    .copied()
+                    .filter(|name| metadata.erasable(name).is_none())
+                    .collect();
+                if feature.name == "phases" && metadata.vars.restart.is_empty() {
+                    missing.push("restart groups");
+                }
+                let support = match missing.is_empty() {
+                    true => Support::Full,
+                    false => Support::Raw { missing },
+                };
+                (feature.name, support)
+            })
+            .collect();
+        Self { features }
+    }
+
+    /// Mark a feature as set up by hand, whatever the symbols say
+    pub fn configure(&mut self, name: &str) {
+        if let Some((_, support)) = self.features.iter_mut().find(|(n, _)| *n == name) {
+            *support = Support::Configured;
+        }
+    }
+
+    pub fn degraded(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.features
+            .iter()
+            .filter(|(_, support)| matches!(support, Support::Raw { .. }))
+            .map(|(name, _)| *name)
+    }
+
+    /// One line per feature, e.g. `modreg       raw (no MODREG): ...`
+    pub fn write(&self, out: &mut dyn Write) -> std::io::Result<()> {
+        for ((name, support), feature) in self.features.iter().zip(FEATURES.iter()) {
+            let line = match support {
+                Support::Full => "full".to_string(),
+                Support::Configured => "configured".to_string(),
+                Support::Raw { missing } => {
+                    format!("raw (no {}): {}", missing.join(", "), feature.fallback)
+                }
+            };
+            writeln!(out, "{:<12} {}", name, line)?;
+        }
+        Ok(())
+    }
+}
+
+/// MODREG from the rope's metadata, when it is in unswitched erasable
+pub fn modreg<S: SymbolTable + ?Sized>(symbols: &S) -> Option<usize> {
+    let addr = symbols.erasable("MODREG")?;
+    let flat = (addr.bank << 8) | addr.offset;
+    (flat < 0o1400).then_some(flat)
+}
+
+#[cfg(test)]
+mod metadata_tests {
+    use super::{modreg, Capabilities, Metadata};
+    use crate::config::RuntimeConfig;
+    use crate::listing::Symbols;
+    use crate::vardb::VarDb;
+
+    #[test]
+    fn missing_symbols_degrade_to_raw() {
+        let symbols = Symbols::parse("PRIORITY 0100\nLOC 0101\nMODREG 1107\nRN 1000").unwrap();
+        let vars = VarDb::parse(
+            "[[vars]]\nname = \"VN\"\naddr = \"E6,1406\"\nformat = \"vector\"\n",
+            &symbols,
+        )
+        .unwrap();
+        let metadata = Metadata {
+            symbols: &symbols,
+            vars: &vars,
+        };
+        let mut caps = Capabilities::negotiate(&metadata);
+        assert_eq!(modreg(&metadata), Some(0o1107));
+        assert_eq!(
+            caps.degraded().collect::<Vec<_>>(),
+            ["executive", "alarms", "state-vector", "phases"]
+        );
+        caps.configure("alarms");
+        assert_eq!(
+            caps.degraded().collect::<Vec<_>>(),
+            ["executive", "state-vector", "phases"]
+        );
+
+        let mut out = Vec::new();
+        caps.write(&mut out).unwrap();
+        let text = String::from_utf8(out).unwrap();
+        assert!(text.contains("modreg       full\n"));
+        assert!(text.contains("alarms       configured\n"));
+        assert!(text.contains("executive    raw (no BANKSET, LST1, LST2): exec <address>"));
+        // VN comes from the variable database; only PIPTIME is missing
+        assert!(text.contains("state-vector raw (no PIPTIME): "));
+    }
+
+    #[test]
+    fn configured_registers_win() {
+        let symbols = Symbols::parse(
+            "MODREG 1107
+FAILREG 1130",
+        )
+        .unwrap();
+        let vars = VarDb::default();
+        let metadata = Metadata {
+            symbols: &symbols,
+            vars: &vars,
+        };
+        let config = RuntimeConfig {
+            modreg: Some(0o1234),
+            ..RuntimeConfig::default()
+        };
+        let negotiated = metadata.negotiate(&config);
+        assert_eq!(negotiated.modreg, Some(0o1234));
+        assert_eq!(negotiated.failreg, Some(0o1130));
+        assert!(!negotiated
+            .capabilities
+            .degraded()
+            .any(|name| name == "alarms"));
+    }
+}
@@ -0,0 +1,136 @@
+use serde::Deserialize;
+
+use ragc_core::memory::MemoryMap;
+use ragc_core::symbols::ErasableAddress;
+
+use crate::runtime::Snapshot;
+
+/// Erasable variables carried from a snapshot onto a different rope with
+/// `load <snapshot> <mapping>`. Only the listed words are written; the
+/// loaded software keeps its own registers and the rest of its erasable.
+///
+/// ```toml
+/// from_rom = "1f2e3d4c5b6a7988" # ROM hash the snapshot must come from (optional)
+///
+/// [[vars]]
+/// name = "TIME2"
+/// from = 0o24     # Flat erasable address on the snapshot's rope
+/// to = 0o24       # Flat erasable address on the loaded rope
+/// words = 2
+///
+/// [[vars]]
+/// name = "REFSMMAT"
+/// from = 0o1733
+/// to = 0o1735
+/// words = 18
+/// ```
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct Migration {
+    pub from_rom: Option<String>,
+    pub vars: Vec<MappedVar>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct MappedVar {
+    pub name: String,
+    pub from: usize,
+    pub to: usize,
+    pub words: Option<usize>,
+}
+
+impl MappedVar {
+    fn words(&self) -> usize {
+        self.words.unwrap_or(1)
+    }
+}
+
+impl Migration {
+    pub fn load(path: &str) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+        Self::parse(&text).map_err(|e| format!("{}: {}", path, e))
+    }
+
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let migration: Self = toml::from_str(text).map_err(|e| e.to_string())?;
+        if let Some(hash) = &migration.from_rom {
+            u64::from_str_radix(hash, 16).map_err(|_| format!("Invalid ROM hash: {}", hash))?;
+        }
+        for var in migration.vars.iter() {
+            for addr in [var.from, var.to] {
+                let start = ErasableAddress::from_flat(addr);
+                if addr >= 0o4000 || var.words() == 0 || start.offset + var.words() > 0o400 {
+                    return Err(format!("{} is not within one erasable bank", var.name));
+                }
+            }
+        }
+        Ok(migration)
+    }
+
+    /// Copy the mapped variables out of `snap` into `mem`, returning the
+    /// number of words written
+    pub fn apply(&self, snap: &Snapshot, mem: &mut MemoryMap) -> Result<usize, String> {
+        let from_rom = self
+            .from_rom
+            .as_deref()
+            .and_then(|h| u64::from_str_radix(h, 16).ok());
+        if let Some(hash) = from_rom.filter(|h| *h != snap.rom_hash) {
+            return Err(format!(
+                "Mapping is for ROM {:016x}, snapshot is of {:016x}",
+                hash, snap.rom_hash
+            ));
+        }
+        let mut written = 0;
+        for var in self.vars.iter() {
+            let from = ErasableAddress::from_flat(var.from);
+            let to = ErasableAddress::from_flat(var.to);
+            let words = snap
+                .erasable
+                .get(from.bank)
+                .map(|bank| &bank[from.offset..from.offset + var.words()])
+                .ok_or_else(|| format!("Snapshot has no E{} for {}", from.bank, var.name))?;
+            mem.write_block(to.bank, to.offset, words);
+            written += words.len();
+        }
+        Ok(written)
+    }
+}
+
+#[cfg(test)]
+mod migrate_tests {
+    use super::Migration;
+    use crate::runtime::{Command, Runtime};
+    use crossbeam_channel::bounded;
+    use ragc_core::cpu::Cpu;
+    use ragc_core::memory::MemoryMap;
+
+    #[test]
+    fn carries_mapped_words_to_new_addresses() {
+        let mut cpu = Cpu::new(MemoryMap::new_blank());
+        let (mut runtime, handle) = Runtime::new(None, 1);
+        handle.poke(0o1233, 0o111);
+        handle.poke(0o1234, 0o222);
+        handle.poke(0o1200, 0o333);
+        let (reply_tx, reply_rx) = bounded(1);
+        handle.send(Command::Snapshot(reply_tx));
+        runtime.poll(&mut cpu);
+        let snap = reply_rx.recv().unwrap();
+
+        let text = "[[vars]]\nname = \"REFSMMAT\"\nfrom = 0o1233\nto = 0o1235\nwords = 2\n";
+        let mut mem = MemoryMap::new_blank();
+        let migration = Migration::parse(text).unwrap();
+        assert_eq!(migration.apply(&snap, &mut mem), Ok(2));
+        assert_eq!(mem.read_block(2, 0o235..0o237), [0o111, 0o222]);
+        assert_eq!(mem.read_block(2, 0o200..0o201), [0]);
+
+        let pinned = format!("from_rom = \"{:x}\"\n{}", snap.rom_hash ^ 1, text);
+        let pinned = Migration::parse(&pinned).unwrap();
+        assert!(pinned
+            .apply(&snap, &mut mem)
+            .unwrap_err()
+            .contains("Mapping is for ROM"));
+        let straddling = "[[vars]]\nname = \"X\"\nfrom = 0o377\nto = 0o1000\nwords = 2\n";
+        assert!(Migration::parse(straddling).is_err());
+    }
+}
@@ -0,0 +1,135 @@
+//! The restart groups' phases, which decide what the flight software picks
+//! up again after a restart. A group whose phase and complement disagree
+//! makes the restart a fresh start instead.
+use std::io::Write;
+
+use ragc_core::symbols::ErasableAddress;
+
+use crate::executive::fixed_location;
+use crate::listing::Symbols;
+use crate::runtime::ERASABLE_BANK_WORDS;
+use crate::vardb::RestartGroup;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum PhaseState {
+    Inactive, // Nothing to restart
+    Restarts,
+    Corrupt, // Phase and complement disagree
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Phase {
+    pub group: u8,
+    pub phase: u16,
+    pub state: PhaseState,
+    pub point: Option<(u16, u16)>, // 2CADR of the restart point, if set
+}
+
+pub fn phases(groups: &[RestartGroup], erasable: &[[u16; ERASABLE_BANK_WORDS]]) -> Vec<Phase> {
+    groups
+        .iter()
+        .map(|group| {
+            let word = |addr: ErasableAddress| erasable[addr.bank][addr.offset];
+            let (phase, check) = (word(group.phase), word(group.check));
+            let state = match (phase, check) {
+                _ if check != !phase & 0o77777 => PhaseState::Corrupt,
+                (0, _) => PhaseState::Inactive,
+                _ => PhaseState::Restarts,
+            };
+            let point = group
+                .point
+                .map(|addr| (word(addr), word(addr.offset_by(1))))
+                .filter(|(addr, _)| *addr != 0);
+            Phase {
+                group: group.group,
+                phase,
+                state,
+                point,
+            }
+        })
+        .collect()
+}
+
+pub fn write_phases(
+    groups: &[RestartGroup],
+    erasable: &[[u16; ERASABLE_BANK_WORDS]],
+    symbols: &Symbols,
+    out: &mut dyn Write,
+) -> std::io::Result<()> {
+    writeln!(out, "GROUP PHASE STATE    POINT")?;
+    for phase in phases(groups, erasable) {
+        let point = match phase
+            .point
+            .and_then(|(addr, bbank)| fixed_location(addr, bbank))
+        {
+            Some(at) => format!(
+                "{:<8} {}",
+                at.to_string(),
+                symbols.describe(&at).unwrap_or_default()
+            ),
+            None => String::new(),
+        };
+        let line = format!(
+            "{:>5} {:05o} {:<8} {}",
+            phase.group,
+            phase.phase,
+            format!("{:?}", phase.state).to_lowercase(),
+            point
+        );
+        writeln!(out, "{}", line.trim_end())?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod phases_tests {
+    use super::{write_phases, PhaseState};
+    use crate::listing::Symbols;
+    use crate::vardb::VarDb;
+
+    #[test]
+    fn shows_which_groups_restart() {
+        let symbols = Symbols::parse(
+            "PHASE1 0100\n-PHASE1 0101\nPHSNAME1 0102\nPHASE2 0104\n-PHASE2 0105\n\
+             PHASE3 0106\n-PHASE3 0107\nP63LM 22,2000",
+        )
+        .unwrap();
+        let text = "vars = []\n\
+            [[restart]]\ngroup = 1\nphase = \"PHASE1\"\ncheck = \"-PHASE1\"\npoint = \"PHSNAME1\"\n\
+            [[restart]]\ngroup = 2\nphase = \"PHASE2\"\ncheck = \"-PHASE2\"\npoint = \"PHSNAME2\"\n\
+            [[restart]]\ngroup = 3\nphase = \"PHASE3\"\ncheck = \"-PHASE3\"\n";
+        let db = VarDb::parse(text, &symbols).unwrap();
+        assert_eq!(db.missing, ["PHSNAME2"]);
+        assert_eq!(db.restart.len(), 3);
+
+        let mut erasable = vec![[0; 256]; 8];
+        erasable[0][0o100..0o110].copy_from_slice(&[
+            0o5,
+            0o77772,
+            0o2005,
+            0o22 << 10, // Group 1: phase 5, restarting in P63LM
+            0,
+            0o77777, // Group 2 inactive
+            0o3,
+            0o3, // Group 3 corrupt
+        ]);
+        let states: Vec<PhaseState> = super::phases(&db.restart, &erasable)
+            .iter()
+            .map(|p| p.state)
+            .collect();
+        assert_eq!(
+            states,
+            [
+                PhaseState::Restarts,
+                PhaseState::Inactive,
+                PhaseState::Corrupt
+            ]
+        );
+
+        let mut out = Vec::new();
+        write_phases(&db.restart, &erasable, &symbols, &mut out).unwrap();
+        let text = String::from_utf8(out).unwrap();
+        assert!(text.contains("    1 00005 restarts 22,2005  P63LM+5\n"));
+        assert!(text.contains("    3 00003 corrupt\n"));
+    }
+}
@@ -0,0 +1,235 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use log::info;
+
+use ragc_core::cpu::Cpu;
+use ragc_core::memory::mods::EmuTime;
+use ragc_core::memory::rom::{RomInfo, RopeImage};
+use ragc_core::memory::MemoryMapBuilder;
+use ragc_core::rng::Rng;
+use ragc_peripherals::dsky::DskyDisplay;
+use ragc_peripherals::shared_dsky::SharedDsky;
+
+use crate::runtime::{Runtime, RuntimeHandle};
+
+/// How often idle instances are looked for
+const REAP_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Emulated time run between sleeps
+const SLICE: Duration = Duration::from_millis(10);
+
+/// Limits on what a pool hands out
+#[derive(Clone, Copy)]
+pub struct PoolConfig {
+    pub max_instances: usize,
+    pub idle_timeout: Duration, // Unused instances are destroyed after this
+}
+
+/// One running emulator with its own memory, peripherals and runtime
+struct Instance {
+    handle: RuntimeHandle,
+    dsky: SharedDsky,
+    stop: Arc<AtomicBool>,
+    thread: JoinHandle<()>,
+    last_used: Instant,
+}
+
+impl Instance {
+    fn spawn(rope: &'static RopeImage, rom_info: RomInfo, seed: u64) -> Self {
+        let dsky = SharedDsky::new();
+        let stop = Arc::new(AtomicBool::new(false));
+        let mut display = DskyDisplay::detached();
+        let (runtime, handle) = Runtime::new(Some(display.keypress_sender()), seed);
+        let (shared, stopped) = (dsky.clone(), stop.clone());
+        let thread = std::thread::spawn(move || {
+            let mut taps = shared.taps();
+            let mut builder = MemoryMapBuilder::new()
+                .rope(rope)
+                .rom_info(rom_info)
+                .dsky(&mut display);
+            for (channel, tap) in taps.iter_mut() {
+                builder = builder.tap(*channel, tap);
+            }
+            let mut cpu = Cpu::new(builder.build());
+            cpu.reset();
+            run(&mut cpu, runtime, &stopped);
+        });
+        Self {
+            handle,
+            dsky,
+            stop,
+            thread,
+            last_used: Instant::now(),
+        }
+    }
+
+    fn destroy(self) {
+        self.stop.store(true, Ordering::Relaxed);
+        let _ = self.thread.join();
+    }
+}
+
+/// Run in real time, at the runtime's speed, until told to stop
+fn run(cpu: &mut Cpu, mut runtime: Runtime, stop: &AtomicBool) {
+    while !stop.load(Ordering::Relaxed) {
+        let start = Instant::now();
+        let target = EmuTime::mcts_in_secs(SLICE.as_secs_f64() * runtime.speed());
+        let mut cycles = 0;
+        while cycles < target && runtime.poll(cpu) {
+            cycles += cpu.step_cycles() as u64;
+        }
+        std::thread::sleep(SLICE.saturating_sub(start.elapsed()));
+    }
+}
+
+/// Independent emulator instances for server mode, one per client session,
+/// created and destroyed on request and reaped when left idle
+pub struct Pool {
+    config: PoolConfig,
+    rope: &'static RopeImage,
+    rom_info: RomInfo,
+    instances: Mutex<HashMap<String, Instance>>,
+    ids: Mutex<Rng>,
+}
+
+impl Pool {
+    /// A pool of instances running `rope`, with a thread reaping idle ones
+    pub fn spawn(config: PoolConfig, rope: &'static RopeImage, rom_info: RomInfo) -> Arc<Self> {
+        let seed = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_nanos() as u64);
+        let pool = Arc::new(Self {
+            config,
+            rope,
+            rom_info,
+            instances: Mutex::new(HashMap::new()),
+            ids: Mutex::new(Rng::new(seed)),
+        });
+        let reaper = Arc::downgrade(&pool);
+        std::thread::spawn(move || {
+            while let Some(pool) = reaper.upgrade() {
+                pool.reap_idle();
+                drop(pool);
+                std::thread::sleep(REAP_INTERVAL);
+            }
+        });
+        pool
+    }
+
+    /// Start an instance, returning its session id
+    pub fn create(&self) -> Result<String, String> {
+        let mut instances = self
+            .instances
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        if instances.len() >= self.config.max_instances {
+            return Err(format!(
+                "All {} instances are in use",
+                self.config.max_instances
+            ));
+        }
+        let (id, seed) = {
+            let mut rng = self.ids.lock().unwrap_or_else(PoisonError::into_inner);
+            (format!("{:016x}", rng.next_u64()), rng.next_u64())
+        };
+        instances.insert(id.clone(), Instance::spawn(self.rope, self.rom_info, seed));
+        info!("Session {} started ({} running)", id, instances.len());
+        Ok(id)
+    }
+
+    /// Handle and DSKY state of a session, which counts as using it
+    pub fn get(&self, id: &str) -> Option<(RuntimeHandle, SharedDsky)> {
+        let mut instances = self
+            .instances
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        let instance = instances.get_mut(id)?;
+        instance.last_used = Instant::now();
+        Some((instance.handle.clone(), instance.dsky.clone()))
+    }
+
+    pub fn destroy(&self, id: &str) -> bool {
+        let instance = self
+            .instances
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .remove(id);
+        match instance {
+            Some(instance) => {
+                instance.destroy();
+                info!("Session {} ended", id);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn sessions(&self) -> Vec<String> {
+        let instances = self
+            .instances
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        instances.keys().cloned().collect()
+    }
+
+    /// Destroy instances unused for longer than the idle timeout
+    fn reap_idle(&self) {
+        let timeout = self.config.idle_timeout;
+        let idle: Vec<String> = {
+            let instances = self
+                .instances
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner);
+            instances
+                .iter()
+                .filter(|(_, instance)| instance.last_used.elapsed() > timeout)
+                .map(|(id, _)| id.clone())
+                .collect()
+        };
+        for id in idle {
+            info!("Session {} idle for {:?}", id, timeout);
+            self.destroy(&id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod pool_tests {
+    use super::{Pool, PoolConfig};
+    use ragc_core::memory::rom::{RomInfo, RopeImage};
+    use std::time::Duration;
+
+    #[test]
+    fn instances_are_isolated_capped_and_reaped() {
+        let rope: &'static RopeImage = Box::leak(Box::new([[0; 1024]; 36]));
+        let config = PoolConfig {
+            max_instances: 2,
+            idle_timeout: Duration::from_millis(50),
+        };
+        let pool = Pool::spawn(config, rope, RomInfo::BLOCK_II);
+        let first = pool.create().unwrap();
+        let second = pool.create().unwrap();
+        assert_ne!(first, second);
+        assert!(pool.create().is_err());
+
+        let (a, _) = pool.get(&first).unwrap();
+        let (b, _) = pool.get(&second).unwrap();
+        a.pause();
+        b.pause();
+        a.poke(0o100, 0o1234);
+        assert_eq!(a.peek(0o100), Some(0o1234));
+        assert_eq!(b.peek(0o100), Some(0));
+
+        assert!(pool.destroy(&first));
+        assert!(!pool.destroy(&first));
+        assert!(pool.get(&first).is_none());
+
+        std::thread::sleep(Duration::from_millis(60));
+        pool.reap_idle();
+        assert!(pool.sessions().is_empty());
+    }
+}
@@ -0,0 +1,302 @@
+use std::io::Write;
+
+use log::{error, info};
+use serde::Deserialize;
+
+use ragc_core::memory::mods::MCT_SECONDS;
+use ragc_core::memory::MemoryMap;
+use ragc_core::symbols::ErasableAddress;
+
+use crate::vardb::VarDb;
+
+/// An oscilloscope on the computer, set up with `--probe <file>`. Each
+/// signal is sampled every `every_mcts` into a buffer holding the last
+/// `samples` of them, written as CSV at exit; `stream` also sends each
+/// sample as it is taken. Cheap enough to watch DAP error signals or
+/// counter activity at rates a trace can't keep up with.
+///
+/// ```toml
+/// every_mcts = 10          # Sample period
+/// samples = 8192           # Buffer length; older samples are overwritten
+/// output = "probe.csv"     # CSV of the buffer at exit
+/// stream = "/tmp/probe.fifo" # CSV lines as they are taken
+///
+/// [[signals]]
+/// name = "ERRORX"
+/// addr = 0o1234            # Flat erasable address, or from the variable database
+/// raw = false              # The word as stored rather than its signed value
+///
+/// [[signals]]
+/// name = "T3 enabled"
+/// channel = 0o13
+/// bits = 0o4000            # Shown shifted down to the lowest set bit
+/// ```
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ProbeConfig {
+    pub every_mcts: Option<u64>,
+    pub samples: Option<usize>,
+    pub output: Option<String>,
+    pub stream: Option<String>,
+    pub signals: Vec<SignalConfig>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SignalConfig {
+    pub name: String,
+    pub addr: Option<usize>,
+    #[serde(default)]
+    pub raw: bool,
+    pub channel: Option<usize>,
+    pub bits: Option<u16>,
+}
+
+/// Where a signal comes from
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Source {
+    Erasable { addr: ErasableAddress, raw: bool },
+    Channel { channel: usize, bits: u16 },
+}
+
+impl Source {
+    fn sample(&self, mem: &MemoryMap) -> i32 {
+        match *self {
+            Source::Erasable { addr, raw } => {
+                // Registers and counters aren't stored in the RAM banks
+                let word = match addr.bank {
+                    0 => mem.read(addr.offset) & 0o77777,
+                    _ => mem.read_block(addr.bank, addr.offset..addr.offset + 1)[0],
+                };
+                match (raw, word & 0o40000) {
+                    (true, _) | (false, 0) => word as i32,
+                    (false, _) => -((!word & 0o37777) as i32),
+                }
+            }
+            Source::Channel { channel, bits } => {
+                ((mem.peek_channel(channel) & bits) >> bits.trailing_zeros().min(15)) as i32
+            }
+        }
+    }
+}
+
+impl ProbeConfig {
+    pub fn load(path: &str, db: &VarDb) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+        let config: Self = toml::from_str(&text).map_err(|e| format!("{}: {}", path, e))?;
+        config.sources(db).map_err(|e| format!("{}: {}", path, e))?;
+        Ok(config)
+    }
+
+    fn sources(&self, db: &VarDb) -> Result<Vec<Source>, String> {
+        if self.signals.is_empty() {
+            return Err("no signals to probe".to_string());
+        }
+        if self.every_mcts == Some(0) || self.samples == Some(0) {
+            return Err("every_mcts and samples must be at least 1".to_string());
+        }
+        if self.output.is_none() && self.stream.is_none() {
+            return Err("give an output file, a stream or both".to_string());
+        }
+        self.signals
+            .iter()
+            .map(|signal| match (signal.addr, signal.channel) {
+                (_, Some(channel)) if signal.addr.is_some() => Err(format!(
+                    "{} has both addr and channel {:o}",
+                    signal.name, channel
+                )),
+                (_, Some(channel)) if channel > 0o377 => {
+                    Err(format!("{}: no channel {:o}", signal.name, channel))
+                }
+                (_, Some(channel)) => Ok(Source::Channel {
+                    channel,
+                    bits: signal.bits.unwrap_or(0o77777),
+                }),
+                (Some(addr), None) if addr >= 0o4000 => {
+                    Err(format!("{}: {:o} is not erasable", signal.name, addr))
+                }
+                (Some(addr), None) => Ok(Source::Erasable {
+                    addr: ErasableAddress::from_flat(addr),
+                    raw: signal.raw,
+                }),
+                (None, None) => match db.get(&signal.name) {
+                    Some(var) => Ok(Source::Erasable {
+                        addr: var.addr,
+                        raw: signal.raw,
+                    }),
+                    None => Err(format!(
+                        "{} has no addr or channel and is not in the variable database",
+                        signal.name
+                    )),
+                },
+            })
+            .collect()
+    }
+}
+
+/// Sampled signals, the newest `samples` of them kept
+pub struct Probe {
+    names: Vec<String>,
+    sources: Vec<Source>,
+    period: u64,
+    next: u64, // MCT count of the next sample
+    samples: usize,
+    times: Vec<u64>,
+    values: Vec<i32>, // One row of signals per sample
+    head: usize,      // Oldest sample once the buffer is full
+    output: Option<String>,
+    stream: Option<Box<dyn Write>>,
+}
+
+impl Probe {
+    pub fn open(config: ProbeConfig, db: &VarDb) -> Result<Self, String> {
+        let sources = config.sources(db)?;
+        let width = sources.len();
+        let names: Vec<String> = config.signals.into_iter().map(|s| s.name).collect();
+        let mut stream = None;
+        if let Some(path) = &config.stream {
+            let mut out = std::fs::File::create(path).map_err(|e| format!("{}: {}", path, e))?;
+            writeln!(out, "t,{}", names.join(",")).map_err(|e| format!("{}: {}", path, e))?;
+            stream = Some(Box::new(out) as Box<dyn Write>);
+        }
+        let period = config.every_mcts.unwrap_or(1);
+        let samples = config.samples.unwrap_or(8192);
+        info!(
+            "Probing {} signals every {} MCTs, keeping {} samples",
+            names.len(),
+            period,
+            samples
+        );
+        Ok(Self {
+            names,
+            sources,
+            period,
+            next: 0,
+            samples,
+            times: Vec::with_capacity(samples),
+            values: Vec::with_capacity(samples * width),
+            head: 0,
+            output: config.output,
+            stream,
+        })
+    }
+
+    /// Take a sample if one is due at `total_cycles`
+    pub fn poll(&mut self, mem: &MemoryMap, total_cycles: u64) {
+        if total_cycles < self.next {
+            return;
+        }
+        // On a grid of the period, so long instructions don't drift it
+        self.next = (total_cycles / self.period + 1) * self.period;
+        let width = self.sources.len();
+        let row = match self.times.len() < self.samples {
+            true => {
+                self.times.push(total_cycles);
+                self.values.resize(self.values.len() + width, 0);
+                self.times.len() - 1
+            }
+            false => {
+                let row = self.head;
+                self.times[row] = total_cycles;
+                self.head = (self.head + 1) % self.times.len();
+                row
+            }
+        };
+        let values = &mut self.values[row * width..(row + 1) * width];
+        for (value, source) in values.iter_mut().zip(self.sources.iter()) {
+            *value = source.sample(mem);
+        }
+        if let Some(out) = &mut self.stream {
+            let line = csv_line(total_cycles, values);
+            if let Err(e) = writeln!(out, "{}", line).and_then(|_| out.flush()) {
+                error!("Probe stream closed: {}", e);
+                self.stream = None;
+            }
+        }
+    }
+
+    /// The buffered samples, oldest first, as CSV
+    pub fn write_csv(&self, out: &mut dyn Write) -> std::io::Result<()> {
+        let width = self.sources.len();
+        writeln!(out, "t,{}", self.names.join(","))?;
+        for i in 0..self.times.len() {
+            let row = (self.head + i) % self.times.len();
+            let values = &self.values[row * width..(row + 1) * width];
+            writeln!(out, "{}", csv_line(self.times[row], values))?;
+        }
+        Ok(())
+    }
+
+    /// Write the buffer to the output file, if there is one
+    pub fn finish(self) {
+        if let Some(path) = &self.output {
+            let written =
+                std::fs::File::create(path).and_then(|mut file| self.write_csv(&mut file));
+            match written {
+                Ok(()) => info!("{} probe samples written to {}", self.times.len(), path),
+                Err(e) => error!("Probe output {}: {}", path, e),
+            }
+        }
+    }
+}
+
+fn csv_line(mcts: u64, values: &[i32]) -> String {
+    let mut line = format!("{:.6}", mcts as f64 * MCT_SECONDS);
+    for value in values {
+        line += &format!(",{}", value);
+    }
+    line
+}
+
+#[cfg(test)]
+mod probe_tests {
+    use super::{Probe, ProbeConfig};
+    use crate::vardb::VarDb;
+    use ragc_core::memory::MemoryMap;
+
+    #[test]
+    fn keeps_the_newest_samples_of_words_and_bits() {
+        let config: ProbeConfig = toml::from_str(
+            r#"
+            every_mcts = 10
+            samples = 3
+            output = "unused.csv"
+
+            [[signals]]
+            name = "ERR"
+            addr = 0o100
+
+            [[signals]]
+            name = "BIT"
+            channel = 0o13
+            bits = 0o4000
+            "#,
+        )
+        .unwrap();
+        let db = VarDb::default();
+        let mut probe = Probe::open(config, &db).unwrap();
+        let mut mem = MemoryMap::new_blank();
+        for (t, (err, chan13)) in [(5, 0), (0o77772, 0o4000), (3, 0o4000), (0o77777, 0)]
+            .iter()
+            .enumerate()
+        {
+            mem.write_block(0, 0o100, &[*err]);
+            mem.write_io(0o13, *chan13);
+            probe.poll(&mem, t as u64 * 10);
+            probe.poll(&mem, t as u64 * 10 + 5); // Not yet due
+        }
+        let mut out = Vec::new();
+        probe.write_csv(&mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "t,ERR,BIT\n0.000117,-5,1\n0.000234,3,1\n0.000352,0,0\n"
+        );
+
+        let bad: ProbeConfig =
+            toml::from_str("stream = \"x\"\n[[signals]]\nname = \"NOWHERE\"").unwrap();
+        assert!(bad.sources(&db).is_err());
+        let bad: ProbeConfig =
+            toml::from_str("stream = \"x\"\n[[signals]]\nname = \"X\"\nchannel = 0o400").unwrap();
+        assert!(bad.sources(&db).is_err());
+    }
+}
@@ -0,0 +1,71 @@
+//! Quick boot: from a fresh start, run flat out until the software settles
+//! into its idle state, then drop to real time. A fresh start spends a long
+//! while in self-checks and the ISS turn-on delay with the display blank;
+//! this gets through them in a few seconds. What idle looks like differs
+//! between ropes and comes from the variable database (`ready`).
+use ragc_core::cpu::Cpu;
+use ragc_core::memory::mods::{EmuTime, MCT_SECONDS};
+
+use crate::cond::Condition;
+
+/// MCTs run between checks while booting (about a second)
+pub const BOOT_BATCH: i64 = EmuTime::mcts_in_millis(1000) as i64;
+
+/// AGC time after which booting gives up waiting (five minutes)
+const BOOT_LIMIT: usize = EmuTime::mcts_in_millis(300_000) as usize;
+
+pub struct QuickBoot {
+    ready: Condition,
+}
+
+impl QuickBoot {
+    pub fn new(ready: Condition) -> Self {
+        Self { ready }
+    }
+
+    /// Whether the software has reached its idle state
+    pub fn ready(&self, cpu: &Cpu) -> bool {
+        self.ready.is_true(cpu)
+    }
+
+    /// Whether booting is over: the software is idle, or has taken too
+    /// long to get there
+    pub fn done(&self, cpu: &Cpu) -> bool {
+        cpu.total_cycles >= BOOT_LIMIT || self.ready(cpu)
+    }
+
+    pub fn condition(&self) -> &str {
+        &self.ready.text
+    }
+
+    /// AGC seconds since power on
+    pub fn seconds(cpu: &Cpu) -> f64 {
+        cpu.total_cycles as f64 * MCT_SECONDS
+    }
+}
+
+#[cfg(test)]
+mod quickboot_tests {
+    use super::QuickBoot;
+    use crate::cond::Condition;
+    use ragc_core::cpu::Cpu;
+    use ragc_core::memory::MemoryMapBuilder;
+    use ragc_core::symbols::ErasableAddress;
+    use ragc_testutils::rope_image;
+
+    #[test]
+    fn boot_ends_when_the_software_idles() {
+        // 4000: CA 4003; XCH READY; TCF 4002; 4003: DEC 5
+        let rope = rope_image(0o4000, &[0o34003, 0o56100, 0o14002, 0o5]);
+        let mut cpu = Cpu::new(MemoryMapBuilder::new().rope(&rope).build());
+        let symbols = [("READY", ErasableAddress::new(0, 0o100))];
+        let boot = QuickBoot::new(Condition::parse("READY == 5", &symbols[..]).unwrap());
+
+        assert!(!boot.done(&cpu));
+        for _ in 0..4 {
+            cpu.step();
+        }
+        assert!(boot.done(&cpu) && boot.ready(&cpu));
+        assert!(QuickBoot::seconds(&cpu) > 0.0);
+    }
+}
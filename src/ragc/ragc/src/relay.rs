@@ -0,0 +1,33 @@
+//! DSKY relay cadence and uplink/downlink word pacing, from the command
+//! line or the runtime configuration
+use ragc_core::memory::pacing::{DOWNLINK_PAIR_DEFAULT, UPLINK_WORD_DEFAULT};
+use ragc_core::memory::MemoryMap;
+
+use crate::config::{ms_to_mcts, RuntimeConfig};
+
+/// Set the relay cadence and link pacing on `memory`; `--relay-ms` wins
+/// over the configured cadence
+pub fn configure(
+    matches: &clap::ArgMatches,
+    config: &RuntimeConfig,
+    memory: &mut MemoryMap<'_>,
+) -> Result<(), String> {
+    let relay_ms = match matches.value_of("relay-ms") {
+        Some(ms) => Some(
+            ms.parse::<u32>()
+                .map_err(|_| format!("Invalid relay cadence: {}", ms))?,
+        ),
+        None => config.relay_ms,
+    };
+    if let Some(ms) = relay_ms {
+        memory.set_relay_cadence(ms_to_mcts(ms));
+    }
+    if config.uplink_word_ms.is_some() || config.downlink_pair_ms.is_some() {
+        let pacing = |ms: Option<u32>, default| ms.map_or(default, |ms| ms_to_mcts(ms) as u64);
+        memory.set_link_pacing(
+            pacing(config.uplink_word_ms, UPLINK_WORD_DEFAULT),
+            pacing(config.downlink_pair_ms, DOWNLINK_PAIR_DEFAULT),
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,189 @@
+use log::{info, warn};
+
+use std::io::Write;
+
+use dsky_protocol::capture::{
+    parse_capture, CaptureRecord, CaptureWriter, CAPTURE_KEY, CAPTURE_PROCEED, CAPTURE_UPLINK,
+};
+use dsky_protocol::uplink;
+use ragc_core::memory::mods::EmuTime;
+
+use crate::ropes::ROM_NAMES;
+use crate::runtime::RuntimeHandle;
+
+/// The `replay` subcommand and its options
+pub fn subcommand<'a, 'b>() -> clap::App<'a, 'b> {
+    clap::SubCommand::with_name("replay")
+        .about("Replay a DSKY capture into a fresh run")
+        .arg(
+            clap::Arg::with_name("file")
+                .required(true)
+                .help("Capture file recorded with --capture"),
+        )
+        .arg(
+            clap::Arg::with_name("rom")
+                .long("rom")
+                .takes_value(true)
+                .possible_values(&ROM_NAMES)
+                .default_value("luminary99")
+                .help("ROM the capture was recorded against"),
+        )
+        .arg(
+            clap::Arg::with_name("speed")
+                .long("speed")
+                .takes_value(true)
+                .default_value("1")
+                .help("Emulation speed relative to real time"),
+        )
+}
+
+/// `--capture`, and `--uplink` with its bit rate
+pub fn args<'a, 'b>() -> [clap::Arg<'a, 'b>; 3] {
+    [
+        clap::Arg::with_name("capture")
+            .long("capture")
+            .takes_value(true)
+            .help("Record DSKY input packets to a capture file for replay"),
+        clap::Arg::with_name("uplink")
+            .long("uplink")
+            .takes_value(true)
+            .help("Send an uplink script (keystrokes, V71/V72 loads) from power on"),
+        clap::Arg::with_name("uplink-rate")
+            .long("uplink-rate")
+            .takes_value(true)
+            .help("Uplink bit rate in bits per second (default: 1000)"),
+    ]
+}
+
+/// Where `--capture` records DSKY input, if anywhere
+pub fn capture(
+    matches: &clap::ArgMatches,
+) -> Result<Option<CaptureWriter<Box<dyn Write + Send>>>, String> {
+    let path = match matches.value_of("capture") {
+        Some(path) => path,
+        None => return Ok(None),
+    };
+    std::fs::File::create(path)
+        .and_then(|f| CaptureWriter::new(Box::new(f) as Box<dyn Write + Send>))
+        .map(Some)
+        .map_err(|e| format!("Cannot create capture {}: {}", path, e))
+}
+
+/// The capture `replay` names, or else a mission kit's `scenario`, with
+/// any `--uplink` script merged in; nothing if there is neither
+pub fn from_args(
+    matches: &clap::ArgMatches,
+    scenario: Option<&str>,
+) -> Result<Option<Replay>, String> {
+    let path = match matches.subcommand_matches("replay") {
+        Some(args) => args.value_of("file"),
+        None => scenario,
+    };
+    let mut replay = match path {
+        Some(path) => Some(Replay::load(path).map_err(|e| format!("Invalid capture: {}", e))?),
+        None => None,
+    };
+    if let Some(path) = matches.value_of("uplink") {
+        let records = uplink_records(path, matches.value_of("uplink-rate"))
+            .map_err(|e| format!("Invalid uplink script: {}", e))?;
+        info!("Uplinking {} words from {}", records.len(), path);
+        replay = Some(replay.unwrap_or_default().merge(records));
+    }
+    Ok(replay)
+}
+
+/// Words of an uplink script, timed at the given bit rate
+fn uplink_records(path: &str, rate: Option<&str>) -> Result<Vec<CaptureRecord>, String> {
+    let rate = match rate.map(str::parse::<u32>) {
+        None => uplink::UPLINK_BIT_RATE,
+        Some(Ok(rate)) if rate > 0 => rate,
+        Some(_) => return Err("Invalid uplink rate".to_string()),
+    };
+    let text = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+    let items = uplink::parse_uplink(&text).map_err(|e| format!("{}: {}", path, e))?;
+    Ok(uplink::uplink_records(&items, 0, rate))
+}
+
+/// Feeds a recorded DSKY capture into the emulator on the emulated clock
+///
+/// Records are delivered when the CPU reaches their capture time, so a
+/// replay is reproducible whatever the host speed or `--speed` factor.
+#[derive(Default)]
+pub struct Replay {
+    records: Vec<CaptureRecord>,
+    next: usize,
+}
+
+impl Replay {
+    pub fn load(path: &str) -> Result<Self, String> {
+        let bytes = std::fs::read(path).map_err(|e| format!("{}: {}", path, e))?;
+        let records = parse_capture(&bytes).map_err(|e| format!("{}: {}", path, e))?;
+        info!("Replaying {} packets from {}", records.len(), path);
+        Ok(Self::new(records))
+    }
+
+    pub fn new(records: Vec<CaptureRecord>) -> Self {
+        Self { records, next: 0 }
+    }
+
+    /// Interleave more records, e.g. an uplink script, by time
+    pub fn merge(mut self, records: Vec<CaptureRecord>) -> Self {
+        self.records.extend(records);
+        self.records[self.next..].sort_by_key(|rec| rec.time_us);
+        self
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.next == self.records.len()
+    }
+
+    /// Queue every record due by `total_cycles` MCTs of emulated time
+    pub fn poll(&mut self, total_cycles: usize, handle: &RuntimeHandle) {
+        let now_us = EmuTime::from_mcts(total_cycles as u64).as_micros();
+        while let Some(rec) = self.records.get(self.next) {
+            if rec.time_us > now_us {
+                break;
+            }
+            match rec.channel {
+                CAPTURE_KEY => handle.press_key(rec.value),
+                CAPTURE_PROCEED => handle.press_key(rec.value | 0o40000),
+                CAPTURE_UPLINK => handle.inject_uplink(rec.value),
+                other => {
+                    warn!("Skipping capture packet on channel {:o}", other);
+                    true
+                }
+            };
+            self.next += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod replay_tests {
+    use super::Replay;
+    use crate::runtime::Runtime;
+    use dsky_protocol::capture::{CaptureRecord, CAPTURE_UPLINK};
+    use ragc_core::cpu::Cpu;
+    use ragc_core::memory::MemoryMap;
+
+    #[test]
+    fn delivers_records_on_emulated_time() {
+        let mut cpu = Cpu::new(MemoryMap::new_blank());
+        let (mut runtime, handle) = Runtime::new(None, 1);
+
+        let mut replay = Replay::new(vec![CaptureRecord {
+            time_us: 1170,
+            channel: CAPTURE_UPLINK,
+            value: 0o4321,
+        }]);
+        replay.poll(99, &handle);
+        runtime.poll(&mut cpu);
+        assert!(!replay.is_done());
+        assert_eq!(cpu.read(0o45), 0);
+
+        replay.poll(100, &handle);
+        runtime.poll(&mut cpu);
+        assert!(replay.is_done());
+        assert_eq!(cpu.read(0o45), 0o4321);
+    }
+}
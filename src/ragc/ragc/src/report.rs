@@ -0,0 +1,373 @@
+use std::collections::BTreeMap;
+use std::time::Instant;
+
+use dsky_protocol::pinball::DisplayState;
+use ragc_core::cpu::{Cpu, RestartCause, StepResult};
+use ragc_core::memory::mods::MCT_SECONDS;
+use ragc_core::memory::rom::decode_word;
+use ragc_core::memory::tap::{ChannelTap, TapAction};
+use ragc_core::memory::{AccessStats, AddressSpace, Location, MemoryMap};
+
+use crate::timeline::Marker;
+use crate::vardb::VarDb;
+
+/// Relay row 12 carries the PROG alarm light in bit 9
+const RELAY_ROW_LIGHTS: u16 = 12;
+const LIGHT_PROG: u16 = 0o400;
+
+/// Fixed memory words covered, 36 banks of 1024
+const FIXED_WORDS: usize = 36 * 1024;
+
+/// Follows the DSKY relay words: programs shown in PROG (PINBALL displays
+/// MODREG there), verbs shown in VERB and PROG alarm light-ups
+#[derive(Default)]
+pub struct DskyLog {
+    display: DisplayState,
+    programs: Vec<u8>,        // Major modes, in the order entered
+    verbs: BTreeMap<u8, u32>, // Times each verb was shown
+    alarms: u32,              // PROG light turned on
+    prog_lit: bool,
+}
+
+impl DskyLog {
+    /// Times the PROG alarm light came on
+    pub fn alarms(&self) -> u32 {
+        self.alarms
+    }
+}
+
+impl ChannelTap for DskyLog {
+    fn write(&mut self, _channel: usize, value: u16) -> TapAction {
+        let (prog, verb) = (self.display.prog(), self.display.verb());
+        self.display.apply_relay_word(value);
+        if let Some(p) = self.display.prog().filter(|p| Some(*p) != prog) {
+            self.programs.push(p);
+        }
+        if let Some(v) = self.display.verb().filter(|v| Some(*v) != verb) {
+            *self.verbs.entry(v).or_default() += 1;
+        }
+        if value >> 11 == RELAY_ROW_LIGHTS {
+            let lit = value & LIGHT_PROG != 0;
+            if lit && !self.prog_lit {
+                self.alarms += 1;
+            }
+            self.prog_lit = lit;
+        }
+        TapAction::Pass(value)
+    }
+}
+
+/// Per-step bookkeeping for `--report`: fixed-memory coverage and restarts
+pub struct RunRecorder {
+    started: Instant,
+    covered: Vec<bool>, // Executed fixed words, by bank * 1024 + offset
+    restarts: Vec<(f64, RestartCause)>,
+    seen_restarts: u32,
+    markers: Vec<Marker>,
+}
+
+impl RunRecorder {
+    pub fn new() -> Self {
+        Self {
+            started: Instant::now(),
+            covered: vec![false; FIXED_WORDS],
+            restarts: Vec::new(),
+            seen_restarts: 0,
+            markers: Vec::new(),
+        }
+    }
+
+    /// Account for a step, given FB as it was before the step
+    pub fn record(&mut self, cpu: &Cpu, fb: u16, step: &StepResult) {
+        if !step.unprogrammed && !step.took_interrupt {
+            // Erasable isn't covered
+            if let Some(Location::Fixed(at)) = AddressSpace::from_fb(fb).locate(step.pc as usize) {
+                self.covered[at.bank * 1024 + at.offset] = true;
+            }
+        }
+        if cpu.restart_count() != self.seen_restarts {
+            self.seen_restarts = cpu.restart_count();
+            if let Some(cause) = cpu.last_restart() {
+                let time = cpu.total_cycles as f64 * MCT_SECONDS;
+                self.restarts.push((time, cause));
+            }
+        }
+    }
+
+    /// Note a timeline marker for the report
+    pub fn mark(&mut self, marker: &Marker) {
+        self.markers.push(marker.clone());
+    }
+
+    /// Gather the report. `rope` supplies the coverage denominator: every
+    /// non-zero word of the image.
+    pub fn finish(self, cpu: &Cpu, rope: &[[u16; 1024]; 36]) -> Report {
+        let used = rope
+            .iter()
+            .flatten()
+            .filter(|w| decode_word(**w) != 0)
+            .count();
+        let mix = cpu.instruction_stats();
+        let total = mix.total().max(1) as f64;
+        Report {
+            emulated: cpu.total_cycles as f64 * MCT_SECONDS,
+            host: self.started.elapsed().as_secs_f64(),
+            restarts: self.restarts,
+            markers: self.markers,
+            covered: self.covered.iter().filter(|c| **c).count(),
+            used,
+            mix: mix
+                .iter()
+                .map(|(mnem, count)| (mnem.name().to_string(), count, count as f64 * 100.0 / total))
+                .collect(),
+            programs: Vec::new(),
+            verbs: BTreeMap::new(),
+            alarms: 0,
+            vars: Vec::new(),
+            access: cpu.memory().access_stats(),
+        }
+    }
+}
+
+/// Summary of a run, written at shutdown as markdown or HTML
+pub struct Report {
+    emulated: f64, // Seconds
+    host: f64,
+    restarts: Vec<(f64, RestartCause)>,
+    markers: Vec<Marker>,
+    covered: usize,
+    used: usize,
+    mix: Vec<(String, u64, f64)>, // Mnemonic, count, share in percent
+    programs: Vec<u8>,
+    verbs: BTreeMap<u8, u32>,
+    alarms: u32,
+    vars: Vec<(String, String)>, // Name, final value
+    access: AccessStats,         // Accesses the emulator carried on from
+}
+
+/// Table with a heading, rendered in either format
+struct Table {
+    title: &'static str,
+    columns: &'static [&'static str],
+    rows: Vec<Vec<String>>,
+}
+
+impl Report {
+    /// Take in what the DSKY showed over the run
+    pub fn add_dsky(&mut self, log: DskyLog) {
+        self.programs = log.programs;
+        self.verbs = log.verbs;
+        self.alarms = log.alarms;
+    }
+
+    /// Take the final values of the database's variables
+    pub fn add_vars(&mut self, db: &VarDb, mem: &MemoryMap) {
+        self.vars = db
+            .vars()
+            .iter()
+            .map(|var| (var.name.clone(), var.describe(mem)))
+            .collect();
+    }
+
+    fn tables(&self) -> Vec<Table> {
+        let coverage = self.covered as f64 * 100.0 / self.used.max(1) as f64;
+        let summary = vec![
+            (
+                "Emulated time".to_string(),
+                format!("{:.1} s", self.emulated),
+            ),
+            ("Host time".to_string(), format!("{:.1} s", self.host)),
+            ("Restarts".to_string(), self.restarts.len().to_string()),
+            ("PROG alarms".to_string(), self.alarms.to_string()),
+            (
+                "Illegal accesses".to_string(),
+                self.access.total().to_string(),
+            ),
+            (
+                "Coverage".to_string(),
+                format!("{:.1}% ({} of {} words)", coverage, self.covered, self.used),
+            ),
+        ];
+        let programs = match self.programs.is_empty() {
+            true => "none".to_string(),
+            false => {
+                let names: Vec<_> = self.programs.iter().map(|p| format!("P{:02}", p)).collect();
+                names.join(", ")
+            }
+        };
+        vec![
+            Table {
+                title: "Summary",
+                columns: &["", ""],
+                rows: summary
+                    .into_iter()
+                    .chain(std::iter::once(("Programs".to_string(), programs)))
+                    .map(|(k, v)| vec![k, v])
+                    .collect(),
+            },
+            Table {
+                title: "Timeline",
+                columns: &["Time (s)", "Marker"],
+                rows: self
+                    .markers
+                    .iter()
+                    .map(|m| vec![format!("{:.3}", m.time()), m.name.clone()])
+                    .collect(),
+            },
+            Table {
+                title: "Restarts",
+                columns: &["Time (s)", "Cause"],
+                rows: self
+                    .restarts
+                    .iter()
+                    .map(|(t, cause)| vec![format!("{:.3}", t), format!("{:?}", cause)])
+                    .collect(),
+            },
+            Table {
+                title: "Verbs",
+                columns: &["Verb", "Times"],
+                rows: self
+                    .verbs
+                    .iter()
+                    .map(|(v, n)| vec![format!("V{:02}", v), n.to_string()])
+                    .collect(),
+            },
+            Table {
+                title: "Final variables",
+                columns: &["Variable", "Value"],
+                rows: self
+                    .vars
+                    .iter()
+                    .map(|(n, v)| vec![n.clone(), v.clone()])
+                    .collect(),
+            },
+            Table {
+                title: "Illegal accesses",
+                columns: &["Access", "Count"],
+                rows: match self.access.total() {
+                    0 => Vec::new(),
+                    _ => [
+                        ("Unmapped addresses", self.access.unmapped),
+                        ("Fixed memory writes", self.access.rom_writes),
+                        ("Missing fixed banks", self.access.missing_banks),
+                        ("Unassigned channels", self.access.unknown_channels),
+                        ("Output channel reads", self.access.output_reads),
+                        ("Input channel writes", self.access.input_writes),
+                    ]
+                    .iter()
+                    .map(|(k, n)| vec![k.to_string(), n.to_string()])
+                    .collect(),
+                },
+            },
+            Table {
+                title: "Instruction mix",
+                columns: &["Instruction", "Count", "Share"],
+                rows: self
+                    .mix
+                    .iter()
+                    .map(|(m, n, s)| vec![m.clone(), n.to_string(), format!("{:.2}%", s)])
+                    .collect(),
+            },
+        ]
+    }
+
+    pub fn markdown(&self) -> String {
+        let mut out = String::from("# AGC run report\n");
+        for table in self.tables().iter().filter(|t| !t.rows.is_empty()) {
+            out += &format!(
+                "\n## {}\n\n| {} |\n",
+                table.title,
+                table.columns.join(" | ")
+            );
+            out += &format!("|{}\n", "---|".repeat(table.columns.len()));
+            for row in table.rows.iter() {
+                out += &format!("| {} |\n", row.join(" | "));
+            }
+        }
+        out
+    }
+
+    pub fn html(&self) -> String {
+        let cells = |tag: &str, row: &[String]| -> String {
+            let cells: Vec<_> = row
+                .iter()
+                .map(|c| format!("<{0}>{1}</{0}>", tag, c))
+                .collect();
+            format!("<tr>{}</tr>\n", cells.concat())
+        };
+        let mut out = String::from(
+            "<!DOCTYPE html>\n<html><head><meta charset=\"utf-8\"><title>AGC run report</title></head>\n<body>\n<h1>AGC run report</h1>\n",
+        );
+        for table in self.tables().iter().filter(|t| !t.rows.is_empty()) {
+            let columns: Vec<_> = table.columns.iter().map(|c| c.to_string()).collect();
+            out += &format!("<h2>{}</h2>\n<table>\n", table.title);
+            out += &cells("th", &columns);
+            for row in table.rows.iter() {
+                out += &cells("td", row);
+            }
+            out += "</table>\n";
+        }
+        out + "</body></html>\n"
+    }
+
+    /// Write the report, as HTML for `.html`/`.htm` paths, else markdown
+    pub fn write(&self, path: &str) -> Result<(), String> {
+        let text = match path.ends_with(".html") || path.ends_with(".htm") {
+            true => self.html(),
+            false => self.markdown(),
+        };
+        std::fs::write(path, text).map_err(|e| format!("{}: {}", path, e))
+    }
+}
+
+#[cfg(test)]
+mod report_tests {
+    use super::{DskyLog, RunRecorder};
+    use crate::vardb::VarDb;
+    use ragc_core::cpu::Cpu;
+    use ragc_core::memory::tap::ChannelTap;
+    use ragc_core::memory::MemoryMap;
+    use ragc_core::symbols::ErasableAddress;
+
+    #[test]
+    fn reports_programs_verbs_and_alarms() {
+        let mut log = DskyLog::default();
+        // PROG 63, VERB 16 twice with a blank between, PROG light on, off, on
+        for word in [
+            (11 << 11) | (28 << 5) | 27,
+            (10 << 11) | (3 << 5) | 28,
+            (10 << 11) | (3 << 5),
+            (10 << 11) | (3 << 5) | 28,
+            (12 << 11) | 0o400,
+            12 << 11,
+            (12 << 11) | 0o400,
+        ]
+        .iter()
+        {
+            log.write(0o10, *word);
+        }
+
+        let cpu = Cpu::new(MemoryMap::new_blank());
+        let mut rope = [[0; 1024]; 36];
+        rope[2][0] = 0o6000_u16.to_be();
+        let mut report = RunRecorder::new().finish(&cpu, &rope);
+        report.add_dsky(log);
+        let symbols: [(&str, ErasableAddress); 0] = [];
+        let db = VarDb::parse(
+            "[[vars]]\nname = \"FLAG\"\naddr = \"100\"\nformat = \"raw\"\n",
+            &symbols[..],
+        );
+        report.add_vars(&db.unwrap(), cpu.memory());
+
+        let md = report.markdown();
+        assert!(md.contains("| PROG alarms | 2 |"), "{}", md);
+        assert!(md.contains("| Programs | P63 |"), "{}", md);
+        assert!(md.contains("| V16 | 2 |"), "{}", md);
+        assert!(md.contains("0.0% (0 of 1 words)"), "{}", md);
+        assert!(!md.contains("## Restarts"), "{}", md);
+        assert!(md.contains("| Illegal accesses | 0 |"), "{}", md);
+        assert!(!md.contains("## Illegal accesses"), "{}", md);
+        assert!(md.contains("| FLAG | 00000 |"), "{}", md);
+        assert!(report.html().contains("<tr><td>V16</td><td>2</td></tr>"));
+    }
+}
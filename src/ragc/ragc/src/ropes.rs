@@ -0,0 +1,59 @@
+//! Where ropes come from: the bundled images by name, or image files
+use ragc_core::constants::{STORAGE_SEGMENTS, STORAGE_SEGMENT_SIZE};
+use ragc_core::memory::rom::{RomInfo, RopeImage};
+
+/// Bundled ROM images selectable by name
+pub const ROM_NAMES: [&str; 3] = ["retread50", "luminary99", "comanche55"];
+
+pub fn rope_by_name(name: &str) -> Option<&'static RopeImage> {
+    match name {
+        "retread50" => Some(ragc_binaries::RETREAD50_ROPE),
+        "luminary99" => Some(ragc_binaries::LUMINARY99_ROPE),
+        "comanche55" => Some(ragc_binaries::COMANCHE55_ROPE),
+        _ => None,
+    }
+}
+
+/// Bundled ROMs flown in the LM; COMANCHE is CM software and RETREAD a
+/// test program
+pub fn is_lm_rom(name: &str) -> bool {
+    name == "luminary99"
+}
+
+/// Reads a rope image in the bundled layout: 36 banks of 1024 big-endian
+/// words, each shifted left over its parity bit. Short images are padded
+/// with zeros.
+pub fn load_rope(path: &str) -> Result<RopeImage, String> {
+    let bytes = std::fs::read(path).map_err(|e| format!("{}: {}", path, e))?;
+    if bytes.len() > STORAGE_SEGMENTS * STORAGE_SEGMENT_SIZE * 2 || bytes.len() % 2 != 0 {
+        return Err(format!(
+            "{}: not a rope image ({} bytes)",
+            path,
+            bytes.len()
+        ));
+    }
+    let mut rope = [[0; STORAGE_SEGMENT_SIZE]; STORAGE_SEGMENTS];
+    for (n, pair) in bytes.chunks_exact(2).enumerate() {
+        rope[n / STORAGE_SEGMENT_SIZE][n % STORAGE_SEGMENT_SIZE] =
+            u16::from_ne_bytes([pair[0], pair[1]]);
+    }
+    Ok(rope)
+}
+
+/// Hardware configuration of a bundled ROM; all of them target Block II
+pub fn rom_info_by_name(name: &str) -> Option<RomInfo> {
+    ROM_NAMES.contains(&name).then_some(RomInfo::BLOCK_II)
+}
+
+/// A bundled rope to swap in, with the configuration it was built for.
+/// Swaps are asked for over the network, so they never name files here.
+pub fn swap_source(name: &str) -> Result<(Box<RopeImage>, RomInfo), String> {
+    match (rope_by_name(name), rom_info_by_name(name)) {
+        (Some(rope), Some(info)) => Ok((Box::new(*rope), info)),
+        _ => Err(format!(
+            "Unknown ROM: {} (one of {})",
+            name,
+            ROM_NAMES.join(", ")
+        )),
+    }
+}
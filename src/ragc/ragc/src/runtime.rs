@@ -0,0 +1,765 @@
+use crossbeam_channel::{bounded, unbounded, Receiver, Sender};
+use log::{debug, warn};
+
+use ragc_core::constants::address_space::VOLATILE_START;
+use ragc_core::constants::ports::CHANNEL_SUPERBNK;
+use ragc_core::constants::registers::{REGISTER_ERASABLE_BANK, REGISTER_FIXED_BANK, REGISTER_MAX};
+use ragc_core::cpu::{Cpu, RestartCause, UnprogSequence};
+use ragc_core::memory::channels::{Axis, Discrete};
+use ragc_core::memory::rom::{ReadOnlyMemory, RomInfo, RopeImage};
+use ragc_core::memory::{bank_register_bits, superbank_fixed_bank, AddressSpace, Location};
+use ragc_core::protect::{ProtectHit, ProtectedRange};
+use ragc_core::rng::{streams, Rng};
+
+use crate::calls::{Backtrace, CallTracker, Markers};
+use crate::channels::{self, ChannelValues};
+use crate::cond::Condition;
+use crate::migrate::Migration;
+use crate::watch::{WatchExpr, Watches};
+
+/// Number of erasable banks captured in a snapshot
+const ERASABLE_BANKS: usize = 8;
+pub const ERASABLE_BANK_WORDS: usize = 256;
+
+/// Bank numbers EB, FB and the superbank bits of channel 7 can select
+const FIXED_BANKS: u16 = 0o40;
+const SUPERBANKS: u16 = 0o10;
+
+/// Commands accepted by the emulation thread
+pub enum Command {
+    Pause,
+    Resume,
+    Step(u32),
+    StepOver,
+    StepOut,
+    Backtrace(Sender<Backtrace>),
+    PressKey(u16),
+    InjectUplink(u16),
+    Restart(RestartCause),
+    Snapshot(Sender<Snapshot>),
+    Restore(Box<Snapshot>, Sender<Result<(), String>>),
+    Migrate(Box<Snapshot>, Box<Migration>, Sender<Result<usize, String>>),
+    SwapRope(Box<RopeSwap>, Sender<u64>),
+    Peek(usize, Sender<u16>),
+    Poke(usize, u16),
+    Monitor(UnprogSequence, Sender<Option<u16>>),
+    SetBanks(u16, u16, u16),
+    Channels(Sender<ChannelValues>),
+    SetSpeed(f64),
+    SetDiscrete(Discrete, bool),
+    SetAxis(Axis, i16),
+    Watch(WatchExpr),
+    ClearWatches,
+    Break(Condition),
+    ClearBreaks,
+    Protect(ProtectedRange),
+    ClearProtect,
+}
+
+/// Copy of CPU state taken at an instruction boundary
+#[derive(Clone, Debug)]
+pub struct Snapshot {
+    pub seed: u64, // Run seed, needed to reproduce from this point
+    pub total_cycles: usize,
+    pub a: u16,
+    pub l: u16,
+    pub q: u16,
+    pub z: u16,
+    pub erasable: Vec<[u16; ERASABLE_BANK_WORDS]>,
+    pub state_hash: u64, // Cpu::state_hash when taken
+    pub rom_hash: u64,   // MemoryMap::rom_hash of the rope it was taken on
+    pub ir: u16,
+    pub superbank: u16, // Channel 7
+    pub ec_flag: bool,
+    pub gint: bool,
+    pub is_irupt: bool,
+    pub periphs: Vec<([u8; 4], Vec<u8>)>, // I/O channel and peripheral state by tag
+}
+
+impl Snapshot {
+    pub fn capture(cpu: &mut Cpu, seed: u64) -> Self {
+        let mut erasable = vec![[0; ERASABLE_BANK_WORDS]; ERASABLE_BANKS];
+        for (bank, words) in erasable.iter_mut().enumerate() {
+            words.copy_from_slice(&cpu.memory().read_block(bank, 0..ERASABLE_BANK_WORDS));
+        }
+        let mut periphs = Vec::new();
+        cpu.memory_mut()
+            .save_periph_states(|tag, data| periphs.push((tag, data.to_vec())));
+
+        Self {
+            seed,
+            total_cycles: cpu.total_cycles,
+            a: cpu.read(0o0),
+            l: cpu.read(0o1),
+            q: cpu.read(0o2),
+            z: cpu.read(0o5),
+            erasable,
+            state_hash: cpu.state_hash(),
+            rom_hash: cpu.memory().rom_hash(),
+            ir: cpu.ir,
+            superbank: cpu.memory().peek_channel(CHANNEL_SUPERBNK),
+            ec_flag: cpu.ec_flag,
+            gint: cpu.gint,
+            is_irupt: cpu.is_irupt,
+            periphs,
+        }
+    }
+
+    /// Put the CPU back in the captured state, with the I/O channels and
+    /// any peripherals that saved theirs. Timers and queued counter updates
+    /// are left as they are, as is state no attached peripheral claims.
+    fn restore(&self, cpu: &mut Cpu) -> Result<(), String> {
+        let rom_hash = cpu.memory().rom_hash();
+        if self.rom_hash != rom_hash {
+            return Err(format!(
+                "Snapshot was taken on a different ROM ({:016x}, loaded {:016x})",
+                self.rom_hash, rom_hash
+            ));
+        }
+        if self.erasable.len() > ERASABLE_BANKS {
+            return Err(format!(
+                "Snapshot has {} erasable banks",
+                self.erasable.len()
+            ));
+        }
+        for (bank, words) in self.erasable.iter().enumerate() {
+            match bank {
+                // Storing into E0's edit registers would edit them again,
+                // and timers and counters are left as they are
+                0 => {
+                    let mem = cpu.memory_mut();
+                    mem.write_block(0, 0, &words[..REGISTER_MAX]);
+                    mem.write_block(0, VOLATILE_START, &words[VOLATILE_START..]);
+                }
+                _ => cpu.memory_mut().write_block(bank, 0, words),
+            }
+        }
+        cpu.write_io(CHANNEL_SUPERBNK, self.superbank);
+        for (tag, data) in self.periphs.iter() {
+            let name = String::from_utf8_lossy(tag);
+            match cpu.memory_mut().restore_periph_state(*tag, data) {
+                Ok(true) => {}
+                Ok(false) => warn!("No peripheral here for {} state, skipped", name),
+                Err(e) => return Err(format!("Cannot restore {} state: {}", name, e)),
+            }
+        }
+        cpu.total_cycles = self.total_cycles;
+        cpu.ir = self.ir;
+        cpu.ec_flag = self.ec_flag;
+        cpu.gint = self.gint;
+        cpu.is_irupt = self.is_irupt;
+        Ok(())
+    }
+}
+
+/// What a rope swap leaves in erasable memory
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ErasablePolicy {
+    Keep,   // As the last rope left it, e.g. to carry a padload over
+    Clear,  // All zeros
+    Random, // As at power on, from the run seed
+}
+
+impl core::str::FromStr for ErasablePolicy {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, String> {
+        match s {
+            "keep" => Ok(ErasablePolicy::Keep),
+            "clear" => Ok(ErasablePolicy::Clear),
+            "random" => Ok(ErasablePolicy::Random),
+            _ => Err(format!("Unknown erasable policy: {}", s)),
+        }
+    }
+}
+
+/// A rope to load in place of the running one. The peripherals and the
+/// clients attached to them stay; the CPU takes a GOJAM into the new rope.
+pub struct RopeSwap {
+    pub rope: Box<RopeImage>,
+    pub info: RomInfo,
+    pub erasable: ErasablePolicy,
+}
+
+impl RopeSwap {
+    fn apply(self, cpu: &mut Cpu, seed: u64) -> u64 {
+        let mem = cpu.memory_mut();
+        mem.swap_rope(ReadOnlyMemory::owned(self.rope), self.info);
+        match self.erasable {
+            ErasablePolicy::Keep => {}
+            ErasablePolicy::Clear => {
+                for bank in 0..ERASABLE_BANKS {
+                    mem.write_block(bank, 0, &[0; ERASABLE_BANK_WORDS]);
+                }
+            }
+            ErasablePolicy::Random => {
+                mem.randomize_erasable(&mut Rng::new(seed).fork(streams::ERASABLE_INIT))
+            }
+        }
+        cpu.gojam(RestartCause::Manual);
+        cpu.memory().rom_hash()
+    }
+}
+
+/// Thread-safe handle used by frontends to control the running emulator
+#[derive(Clone)]
+pub struct RuntimeHandle {
+    tx: Sender<Command>,
+}
+
+impl RuntimeHandle {
+    pub fn send(&self, cmd: Command) -> bool {
+        self.tx.send(cmd).is_ok()
+    }
+
+    pub fn pause(&self) -> bool {
+        self.send(Command::Pause)
+    }
+
+    pub fn resume(&self) -> bool {
+        self.send(Command::Resume)
+    }
+
+    pub fn step(&self, count: u32) -> bool {
+        self.send(Command::Step(count))
+    }
+
+    /// Step, running a called subroutine through to its return
+    pub fn step_over(&self) -> bool {
+        self.send(Command::StepOver)
+    }
+
+    /// Run until the current subroutine returns
+    pub fn step_out(&self) -> bool {
+        self.send(Command::StepOut)
+    }
+
+    /// The calls the CPU is in, as far as they can be told
+    pub fn backtrace(&self) -> Option<Backtrace> {
+        let (reply_tx, reply_rx) = bounded(1);
+        if !self.send(Command::Backtrace(reply_tx)) {
+            return None;
+        }
+        reply_rx.recv().ok()
+    }
+
+    pub fn press_key(&self, keycode: u16) -> bool {
+        self.send(Command::PressKey(keycode))
+    }
+
+    pub fn inject_uplink(&self, word: u16) -> bool {
+        self.send(Command::InjectUplink(word))
+    }
+
+    pub fn restart(&self, cause: RestartCause) -> bool {
+        self.send(Command::Restart(cause))
+    }
+
+    /// Request a snapshot and wait for the emulation thread to take it
+    pub fn snapshot(&self) -> Option<Snapshot> {
+        let (reply_tx, reply_rx) = bounded(1);
+        if !self.send(Command::Snapshot(reply_tx)) {
+            return None;
+        }
+        reply_rx.recv().ok()
+    }
+
+    /// Load a snapshot into the emulator and wait for the outcome
+    pub fn restore(&self, snap: Snapshot) -> Option<Result<(), String>> {
+        let (reply_tx, reply_rx) = bounded(1);
+        if !self.send(Command::Restore(Box::new(snap), reply_tx)) {
+            return None;
+        }
+        reply_rx.recv().ok()
+    }
+
+    /// Carry mapped erasable variables from a snapshot, possibly of another
+    /// rope, into the running software; replies with the words written
+    pub fn migrate(&self, snap: Snapshot, migration: Migration) -> Option<Result<usize, String>> {
+        let (reply_tx, reply_rx) = bounded(1);
+        if !self.send(Command::Migrate(
+            Box::new(snap),
+            Box::new(migration),
+            reply_tx,
+        )) {
+            return None;
+        }
+        reply_rx.recv().ok()
+    }
+
+    /// Load another rope and restart into it; replies with its hash
+    pub fn swap_rope(&self, swap: RopeSwap) -> Option<u64> {
+        let (reply_tx, reply_rx) = bounded(1);
+        if !self.send(Command::SwapRope(Box::new(swap), reply_tx)) {
+            return None;
+        }
+        reply_rx.recv().ok()
+    }
+
+    /// Print the expression's value now and whenever its words change
+    pub fn watch(&self, expr: WatchExpr) -> bool {
+        self.send(Command::Watch(expr))
+    }
+
+    pub fn clear_watches(&self) -> bool {
+        self.send(Command::ClearWatches)
+    }
+
+    /// Pause whenever the condition comes true
+    pub fn break_when(&self, condition: Condition) -> bool {
+        self.send(Command::Break(condition))
+    }
+
+    pub fn clear_breaks(&self) -> bool {
+        self.send(Command::ClearBreaks)
+    }
+
+    /// Pause on any program write to the range, dropping the write if the
+    /// range suppresses them
+    pub fn protect(&self, range: ProtectedRange) -> bool {
+        self.send(Command::Protect(range))
+    }
+
+    pub fn clear_protect(&self) -> bool {
+        self.send(Command::ClearProtect)
+    }
+
+    /// Read a word at a CPU address (bank-switched, as the program sees it)
+    pub fn peek(&self, addr: usize) -> Option<u16> {
+        let (reply_tx, reply_rx) = bounded(1);
+        if !self.send(Command::Peek(addr, reply_tx)) {
+            return None;
+        }
+        reply_rx.recv().ok()
+    }
+
+    /// Write a word at a CPU address
+    pub fn poke(&self, addr: usize, value: u16) -> bool {
+        self.send(Command::Poke(addr, value))
+    }
+
+    /// Force a TCSAJ, FETCH or STORE as the ground-support monitor would;
+    /// replies with the word it moved or the address it sent control to
+    pub fn monitor(&self, seq: UnprogSequence) -> Option<u16> {
+        let (reply_tx, reply_rx) = bounded(1);
+        if !self.send(Command::Monitor(seq, reply_tx)) {
+            return None;
+        }
+        reply_rx.recv().ok().flatten()
+    }
+
+    /// Switch to erasable bank `eb`, fixed bank `fb` and `superbank`, by
+    /// bank number, through the same register writes a program would make
+    pub fn set_banks(&self, eb: u16, fb: u16, superbank: u16) -> bool {
+        self.send(Command::SetBanks(eb, fb, superbank))
+    }
+
+    /// Current value of every I/O channel
+    pub fn channels(&self) -> Option<ChannelValues> {
+        let (reply_tx, reply_rx) = bounded(1);
+        if !self.send(Command::Channels(reply_tx)) {
+            return None;
+        }
+        reply_rx.recv().ok()
+    }
+
+    /// Run at `speed` times real time
+    pub fn set_speed(&self, speed: f64) -> bool {
+        self.send(Command::SetSpeed(speed))
+    }
+
+    /// Assert or release an input discrete
+    pub fn set_discrete(&self, discrete: Discrete, asserted: bool) -> bool {
+        self.send(Command::SetDiscrete(discrete, asserted))
+    }
+
+    /// Deflect a hand controller axis, in counts
+    pub fn set_axis(&self, axis: Axis, deflection: i16) -> bool {
+        self.send(Command::SetAxis(axis, deflection))
+    }
+}
+
+/// Emulation-thread side of the command queue
+pub struct Runtime {
+    rx: Receiver<Command>,
+    keypress_tx: Option<Sender<u16>>,
+    seed: u64,
+    paused: bool,
+    pending_steps: u32,
+    calls: CallTracker,
+    until_depth: Option<usize>, // Pause once returned to this call depth
+    speed: f64,                 // Relative to real time
+    watches: Watches,
+    breaks: Vec<(Condition, bool)>, // With the value last seen
+}
+
+impl Runtime {
+    /// Create the runtime and its handle. Keypresses are forwarded to the
+    /// display unit through `keypress_tx` when one is attached; `seed` is the
+    /// run seed recorded in snapshots.
+    pub fn new(keypress_tx: Option<Sender<u16>>, seed: u64) -> (Self, RuntimeHandle) {
+        let (tx, rx) = unbounded();
+        let runtime = Self {
+            rx,
+            keypress_tx,
+            seed,
+            paused: false,
+            pending_steps: 0,
+            calls: CallTracker::default(),
+            until_depth: None,
+            speed: 1.0,
+            watches: Watches::default(),
+            breaks: Vec::new(),
+        };
+        (runtime, RuntimeHandle { tx })
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    /// Routines the call tracker must know of, such as BANKCALL
+    pub fn set_call_markers(&mut self, markers: Markers) {
+        self.calls.set_markers(markers);
+    }
+
+    pub fn speed(&self) -> f64 {
+        self.speed
+    }
+
+    /// Non-positive and non-finite speeds are ignored
+    pub fn set_speed(&mut self, speed: f64) {
+        match speed.is_finite() && speed > 0.0 {
+            true => self.speed = speed,
+            false => warn!("Ignoring invalid speed {}", speed),
+        }
+    }
+
+    /// Process queued commands. Must be called between instructions.
+    /// Returns whether the CPU may execute the next instruction.
+    pub fn poll(&mut self, cpu: &mut Cpu) -> bool {
+        while let Ok(cmd) = self.rx.try_recv() {
+            self.handle(cpu, cmd);
+        }
+        if !self.watches.is_empty() {
+            for line in self.watches.check(cpu.memory()) {
+                println!("watch: {}", line);
+            }
+        }
+        let mut hit = false;
+        for (condition, was_true) in self.breaks.iter_mut() {
+            let is_true = condition.is_true(cpu);
+            if is_true && !*was_true {
+                println!("break: {}", condition.text);
+                hit = true;
+            }
+            *was_true = is_true;
+        }
+        if hit {
+            self.stop();
+        }
+        if let Some(hit) = cpu.take_protect_hit() {
+            println!("protect: {}", describe_hit(&hit));
+            self.stop();
+        }
+        if self.calls.returned(cpu) && self.until_depth >= Some(self.calls.depth()) {
+            self.until_depth = None;
+        }
+
+        let run = match self.pending_steps {
+            _ if !self.paused || self.until_depth.is_some() => true,
+            0 => false,
+            _ => {
+                self.pending_steps -= 1;
+                true
+            }
+        };
+        if run {
+            self.calls.entering(cpu);
+        }
+        run
+    }
+
+    fn stop(&mut self) {
+        self.paused = true;
+        self.pending_steps = 0;
+        self.until_depth = None;
+    }
+
+    fn handle(&mut self, cpu: &mut Cpu, cmd: Command) {
+        match cmd {
+            Command::Pause => {
+                self.paused = true;
+                self.until_depth = None;
+            }
+            Command::Resume => {
+                self.stop();
+                self.paused = false;
+            }
+            Command::Step(count) => {
+                self.paused = true;
+                self.pending_steps += count;
+            }
+            Command::StepOver => {
+                self.paused = true;
+                match self.calls.is_call(cpu) {
+                    true => self.until_depth = Some(self.calls.depth()),
+                    false => self.pending_steps += 1,
+                }
+            }
+            Command::StepOut => {
+                self.paused = true;
+                self.calls.assume_caller(cpu);
+                self.until_depth = Some(self.calls.depth() - 1);
+            }
+            Command::Backtrace(reply) => {
+                let _ = reply.send(self.calls.backtrace(cpu));
+            }
+            Command::PressKey(keycode) => match &self.keypress_tx {
+                Some(tx) => {
+                    let _ = tx.send(keycode);
+                }
+                None => warn!("No display unit attached for keypress {:o}", keycode),
+            },
+            Command::InjectUplink(word) => {
+                debug!("Uplink word: {:o}", word);
+                cpu.memory_mut().receive_uplink(word);
+            }
+            Command::Restart(cause) => cpu.gojam(cause),
+            Command::Snapshot(reply) => {
+                let _ = reply.send(Snapshot::capture(cpu, self.seed));
+            }
+            Command::Restore(snap, reply) => {
+                let _ = reply.send(snap.restore(cpu));
+            }
+            Command::Migrate(snap, migration, reply) => {
+                let _ = reply.send(migration.apply(&snap, cpu.memory_mut()));
+            }
+            Command::SwapRope(swap, reply) => {
+                // Call markers and the call stack belong to the old rope
+                self.calls = CallTracker::default();
+                self.until_depth = None;
+                let _ = reply.send(swap.apply(cpu, self.seed));
+            }
+            Command::Peek(addr, reply) => {
+                let _ = reply.send(cpu.memory().read(addr));
+            }
+            Command::Poke(addr, value) => {
+                check_bank_poke(cpu, addr, value);
+                cpu.poke(addr, value);
+            }
+            Command::Monitor(seq, reply) => {
+                let _ = reply.send(cpu.monitor(seq));
+            }
+            Command::SetBanks(eb, fb, superbank) => set_banks(cpu, eb, fb, superbank),
+            Command::Channels(reply) => {
+                let _ = reply.send(channels::capture(cpu.memory()));
+            }
+            Command::SetSpeed(speed) => self.set_speed(speed),
+            Command::SetDiscrete(discrete, asserted) => {
+                debug!("Discrete {}: {}", discrete.name(), asserted);
+                cpu.set_discrete(discrete, asserted);
+            }
+            Command::SetAxis(axis, deflection) => cpu.memory_mut().set_axis(axis, deflection),
+            Command::Watch(expr) => self.watches.add(expr),
+            Command::ClearWatches => self.watches.clear(),
+            Command::Break(condition) => self.breaks.push((condition, false)),
+            Command::ClearBreaks => self.breaks.clear(),
+            Command::Protect(range) => {
+                cpu.protect(range);
+            }
+            Command::ClearProtect => cpu.unprotect_all(),
+        }
+    }
+}
+
+/// e.g. `write of 12345 to E4,1402 from 04001 (suppressed)`
+fn describe_hit(hit: &ProtectHit) -> String {
+    format!(
+        "write of {:05o} to E{},{:04o} from {:05o}{}",
+        hit.value & 0o77777,
+        hit.addr.bank,
+        AddressSpace {
+            eb: Some(hit.addr.bank),
+            ..AddressSpace::UNBANKED
+        }
+        .address(Location::Erasable(hit.addr))
+        .unwrap_or_default(),
+        hit.pc,
+        if hit.suppressed { " (suppressed)" } else { "" }
+    )
+}
+
+/// Warn when a poke to a bank register loses bits or selects a fixed bank
+/// the rope doesn't have; the write still goes ahead, as on the hardware
+fn check_bank_poke(cpu: &Cpu, addr: usize, value: u16) {
+    let bits = match bank_register_bits(addr) {
+        Some(bits) => bits,
+        None => return,
+    };
+    if value & !bits != 0 {
+        warn!(
+            "Bank register {:o} keeps only {:05o} of {:05o}",
+            addr,
+            value & bits,
+            value
+        );
+    }
+    let fb = ((value & bits & 0o76000) >> 10) as usize;
+    let superbank = cpu.memory().peek_channel(CHANNEL_SUPERBNK);
+    let fixed = superbank_fixed_bank(fb, superbank);
+    if addr != REGISTER_ERASABLE_BANK && fixed >= cpu.memory().fixed_bank_count() {
+        warn!("Fixed bank {:o} is beyond the rope", fixed);
+    }
+}
+
+fn set_banks(cpu: &mut Cpu, eb: u16, fb: u16, superbank: u16) {
+    let banks = ERASABLE_BANKS as u16;
+    if eb >= banks || fb >= FIXED_BANKS || superbank >= SUPERBANKS {
+        warn!(
+            "Ignoring out-of-range banks E{:o} F{:o} S{:o}",
+            eb, fb, superbank
+        );
+        return;
+    }
+    cpu.write_io(CHANNEL_SUPERBNK, superbank << 4);
+    check_bank_poke(cpu, REGISTER_FIXED_BANK, fb << 10);
+    cpu.write(REGISTER_ERASABLE_BANK, eb << 8);
+    cpu.write(REGISTER_FIXED_BANK, fb << 10);
+}
+
+#[cfg(test)]
+mod runtime_tests {
+    use super::{Command, ErasablePolicy, RopeSwap, Runtime};
+    use crate::cond::Condition;
+    use crossbeam_channel::bounded;
+    use ragc_core::cpu::Cpu;
+    use ragc_core::memory::rom::RomInfo;
+    use ragc_core::memory::{MemoryMap, MemoryMapBuilder};
+    use ragc_core::protect::ProtectedRange;
+    use ragc_core::symbols::ErasableAddress;
+    use ragc_testutils::rope_image;
+
+    #[test]
+    fn pause_step_and_snapshot() {
+        let mut cpu = Cpu::new(MemoryMap::new_blank());
+        let (mut runtime, handle) = Runtime::new(None, 1);
+
+        assert!(runtime.poll(&mut cpu));
+        handle.step(2);
+        assert!(runtime.poll(&mut cpu));
+        assert!(runtime.poll(&mut cpu));
+        assert!(!runtime.poll(&mut cpu));
+
+        handle.inject_uplink(0o123);
+        let (reply_tx, reply_rx) = bounded(1);
+        handle.send(Command::Snapshot(reply_tx));
+        runtime.poll(&mut cpu);
+        let snap = reply_rx.recv().unwrap();
+        assert_eq!(snap.erasable.len(), 8);
+        assert_eq!(snap.seed, 1);
+        assert_eq!(cpu.read(0o45), 0o123);
+
+        handle.resume();
+        assert!(runtime.poll(&mut cpu));
+
+        handle.set_speed(4.0);
+        handle.set_speed(-1.0);
+        runtime.poll(&mut cpu);
+        assert_eq!(runtime.speed(), 4.0);
+
+        // Bank switches go through the register file, so BB follows
+        handle.set_banks(5, 0o21, 3);
+        handle.set_banks(0o10, 0, 0);
+        runtime.poll(&mut cpu);
+        assert_eq!((cpu.read(0o3), cpu.read(0o4)), (0o2400, 0o42000));
+        assert_eq!(cpu.read(0o6), 0o42005);
+        assert_eq!(cpu.read_io(0o7), 0o60);
+
+        // Breakpoints pause on the way into their condition, once
+        let none: &[(&str, ErasableAddress)] = &[];
+        handle.break_when(Condition::parse("E2,1630 > 0o10", none).unwrap());
+        assert!(runtime.poll(&mut cpu));
+        cpu.memory_mut().write_block(2, 0o230, &[0o11]);
+        assert!(!runtime.poll(&mut cpu));
+        handle.resume();
+        assert!(runtime.poll(&mut cpu));
+
+        // Program writes to protected words pause, debugger pokes don't
+        handle.protect(ProtectedRange {
+            start: ErasableAddress::new(2, 0o30),
+            len: 1,
+            suppress: false,
+        });
+        handle.poke(0o1030, 1);
+        assert!(runtime.poll(&mut cpu));
+        cpu.write(0o1030, 2);
+        assert!(!runtime.poll(&mut cpu));
+        assert_eq!(cpu.read(0o1030), 2);
+    }
+
+    #[test]
+    fn step_over_and_out_of_calls() {
+        // 4000: TC 4010; TCF 4001
+        // 4010: CA Q; XCH 100; TC 4020; CA 100; XCH Q; TC Q
+        // 4020: TC Q
+        let mut words = [0; 0o21];
+        words[..2].copy_from_slice(&[0o04010, 0o14001]);
+        words[0o10..0o16].copy_from_slice(&[0o30002, 0o56100, 0o04020, 0o30100, 0o56002, 0o00002]);
+        words[0o20] = 0o00002;
+        let rope = rope_image(0o4000, &words);
+        let mut cpu = Cpu::new(MemoryMapBuilder::new().rope(&rope).build());
+        cpu.reset();
+        let (mut runtime, handle) = Runtime::new(None, 1);
+        let run = |runtime: &mut Runtime, cpu: &mut Cpu| {
+            while runtime.poll(cpu) {
+                cpu.step();
+            }
+            cpu.read(0o5)
+        };
+
+        handle.step(1);
+        assert_eq!(run(&mut runtime, &mut cpu), 0o4010);
+        handle.step_over(); // Not a call
+        assert_eq!(run(&mut runtime, &mut cpu), 0o4011);
+        handle.step(1);
+        assert_eq!(run(&mut runtime, &mut cpu), 0o4012);
+        handle.step_over();
+        assert_eq!(run(&mut runtime, &mut cpu), 0o4013);
+        handle.step_out();
+        assert_eq!(run(&mut runtime, &mut cpu), 0o4001);
+    }
+
+    #[test]
+    fn swapped_rope_restarts_with_erasable_policy() {
+        let idle = rope_image(0o4000, &[0o14000]); // TCF 4000
+        let mut cpu = Cpu::new(MemoryMapBuilder::new().rope(&idle).build());
+        cpu.reset();
+        let (mut runtime, handle) = Runtime::new(None, 1);
+        let mut swap = |cpu: &mut Cpu, words: &[u16], erasable: ErasablePolicy| {
+            let (reply_tx, reply_rx) = bounded(1);
+            let swap = RopeSwap {
+                rope: rope_image(0o4000, words),
+                info: RomInfo::BLOCK_II,
+                erasable,
+            };
+            handle.send(Command::SwapRope(Box::new(swap), reply_tx));
+            runtime.poll(cpu);
+            reply_rx.recv().unwrap()
+        };
+
+        // 4000: CA 4003; XCH 100; TCF 4002; 4003: 42
+        let store = [0o34003, 0o56100, 0o14002, 0o00042];
+        cpu.write(0o101, 0o555);
+        let hash = swap(&mut cpu, &store, ErasablePolicy::Keep);
+        assert_eq!(hash, cpu.memory().rom_hash());
+        assert_ne!(hash, MemoryMapBuilder::new().rope(&idle).build().rom_hash());
+        for _ in 0..6 {
+            cpu.step();
+        }
+        assert_eq!((cpu.read(0o100), cpu.read(0o101)), (0o42, 0o555));
+        assert_eq!(cpu.restart_count(), 1);
+
+        swap(&mut cpu, &[0o14000], ErasablePolicy::Clear);
+        assert_eq!((cpu.read(0o100), cpu.read(0o101)), (0, 0));
+    }
+}
@@ -0,0 +1,164 @@
+//! Host scheduling of the emulation thread. On a loaded host it shares
+//! cores with the trace writer, telemetry and logging threads and drifts
+//! behind real time; a realtime class and cores of its own keep it ahead.
+//!
+//! Threads inherit their creator's class and affinity, so this is applied
+//! once the helper threads are running, just before the emulation loop.
+use log::{info, warn};
+
+/// Scheduling class for the emulation thread
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Priority {
+    Normal,
+    Realtime, // Lowest realtime priority: above every normal thread only
+}
+
+impl core::str::FromStr for Priority {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, String> {
+        match s {
+            "normal" => Ok(Priority::Normal),
+            "realtime" => Ok(Priority::Realtime),
+            _ => Err(format!("Unknown priority: {}", s)),
+        }
+    }
+}
+
+/// How the emulation thread should be scheduled
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Scheduling {
+    pub priority: Priority,
+    pub cpus: Vec<usize>, // Cores it may run on; empty for any
+}
+
+impl Default for Scheduling {
+    fn default() -> Self {
+        Self {
+            priority: Priority::Normal,
+            cpus: Vec::new(),
+        }
+    }
+}
+
+/// Parse a core list: numbers and ranges, e.g. `2,3` or `0-1,6`
+pub fn parse_cpus(text: &str) -> Result<Vec<usize>, String> {
+    let mut cpus = Vec::new();
+    for part in text.split(',').map(str::trim) {
+        let err = || format!("Invalid CPU list entry: {}", part);
+        let (first, last) = match part.split_once('-') {
+            Some((first, last)) => (first.parse::<usize>(), last.parse()),
+            None => (part.parse::<usize>(), part.parse()),
+        };
+        match (first, last) {
+            (Ok(first), Ok(last)) if first <= last => cpus.extend(first..=last),
+            _ => return Err(err()),
+        }
+    }
+    cpus.sort_unstable();
+    cpus.dedup();
+    Ok(cpus)
+}
+
+impl Scheduling {
+    /// Override with `--priority` and `--cpus`
+    pub fn with_args(mut self, matches: &clap::ArgMatches) -> Result<Self, String> {
+        if let Some(priority) = matches.value_of("priority") {
+            self.priority = priority.parse()?;
+        }
+        if let Some(cpus) = matches.value_of("cpus") {
+            self.cpus = parse_cpus(cpus)?;
+        }
+        Ok(self)
+    }
+
+    /// Apply to the calling thread. A realtime class the host refuses, as
+    /// without the privilege for it, falls back to normal with a warning;
+    /// the priority actually in effect is returned.
+    pub fn apply(&self) -> Priority {
+        if !self.cpus.is_empty() {
+            match set_affinity(&self.cpus) {
+                Ok(()) => info!("Emulation thread pinned to CPUs {:?}", self.cpus),
+                Err(e) => warn!("Cannot pin the emulation thread: {}", e),
+            }
+        }
+        match self.priority {
+            Priority::Normal => Priority::Normal,
+            Priority::Realtime => match set_realtime() {
+                Ok(()) => {
+                    info!("Emulation thread running at realtime priority");
+                    Priority::Realtime
+                }
+                Err(e) => {
+                    warn!("No realtime priority, running at normal: {}", e);
+                    Priority::Normal
+                }
+            },
+        }
+    }
+}
+
+#[cfg(unix)]
+fn set_realtime() -> Result<(), String> {
+    // SAFETY: plain calls on the current thread with a valid sched_param
+    unsafe {
+        let param = libc::sched_param {
+            sched_priority: libc::sched_get_priority_min(libc::SCHED_RR),
+        };
+        match libc::pthread_setschedparam(libc::pthread_self(), libc::SCHED_RR, &param) {
+            0 => Ok(()),
+            errno => Err(std::io::Error::from_raw_os_error(errno).to_string()),
+        }
+    }
+}
+
+#[cfg(not(unix))]
+fn set_realtime() -> Result<(), String> {
+    Err("realtime scheduling is not supported here".to_string())
+}
+
+#[cfg(target_os = "linux")]
+fn set_affinity(cpus: &[usize]) -> Result<(), String> {
+    // SAFETY: the set is zeroed before use and only CPUs within it are set
+    unsafe {
+        let mut set: libc::cpu_set_t = std::mem::zeroed();
+        for &cpu in cpus {
+            if cpu >= libc::CPU_SETSIZE as usize {
+                return Err(format!("CPU {} is out of range", cpu));
+            }
+            libc::CPU_SET(cpu, &mut set);
+        }
+        match libc::sched_setaffinity(0, std::mem::size_of::<libc::cpu_set_t>(), &set) {
+            0 => Ok(()),
+            _ => Err(std::io::Error::last_os_error().to_string()),
+        }
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn set_affinity(_cpus: &[usize]) -> Result<(), String> {
+    Err("CPU affinity is not supported here".to_string())
+}
+
+#[cfg(test)]
+mod sched_tests {
+    use super::{parse_cpus, Priority, Scheduling};
+
+    #[test]
+    fn parses_cpu_lists_and_falls_back_to_normal() {
+        assert_eq!(parse_cpus("3, 0-1,1").unwrap(), vec![0, 1, 3]);
+        assert!(parse_cpus("2-1").is_err());
+        assert!(parse_cpus("").is_err());
+        assert_eq!("realtime".parse(), Ok(Priority::Realtime));
+        assert!("high".parse::<Priority>().is_err());
+
+        // Realtime needs privileges a test run may lack; either way the
+        // thread keeps running
+        let applied = Scheduling {
+            priority: Priority::Realtime,
+            cpus: Vec::new(),
+        }
+        .apply();
+        assert!(matches!(applied, Priority::Realtime | Priority::Normal));
+        assert_eq!(Scheduling::default().apply(), Priority::Normal);
+    }
+}
@@ -0,0 +1,99 @@
+//! The `serve` subcommand: the REST API on the shared AGC, and optionally a
+//! pool of private ones on the same rope
+use std::time::Duration;
+
+use ragc_core::memory::rom::{RomInfo, RopeImage};
+
+use crate::control::Access;
+use crate::pool::{Pool, PoolConfig};
+use crate::ropes::ROM_NAMES;
+use crate::runtime::RuntimeHandle;
+
+/// The `serve` subcommand and its options
+pub fn subcommand<'a, 'b>() -> clap::App<'a, 'b> {
+    clap::SubCommand::with_name("serve")
+        .about("Run a bundled ROM shared over a REST API, e.g. for remote labs")
+        .arg(
+            clap::Arg::with_name("rom")
+                .long("rom")
+                .takes_value(true)
+                .possible_values(&ROM_NAMES)
+                .default_value("luminary99")
+                .help("Bundled ROM to run"),
+        )
+        .arg(
+            clap::Arg::with_name("http")
+                .long("http")
+                .takes_value(true)
+                .default_value("8080")
+                .help("Port to serve the REST API on"),
+        )
+        .arg(
+            clap::Arg::with_name("token")
+                .long("token")
+                .takes_value(true)
+                .help("Token clients must present as a bearer token or ?token="),
+        )
+        .arg(
+            clap::Arg::with_name("observer-token")
+                .long("observer-token")
+                .takes_value(true)
+                .requires("token")
+                .help("Token for clients that may only read the display, channels and state"),
+        )
+        .arg(
+            clap::Arg::with_name("public")
+                .long("public")
+                .requires("token")
+                .help("Let clients without a token observe"),
+        )
+        .arg(
+            clap::Arg::with_name("max-sessions")
+                .long("max-sessions")
+                .takes_value(true)
+                .help("Also run up to this many private AGCs, one per session"),
+        )
+        .arg(
+            clap::Arg::with_name("idle-timeout")
+                .long("idle-timeout")
+                .takes_value(true)
+                .default_value("1800")
+                .help("Seconds before an unused session is ended"),
+        )
+}
+
+/// Start the HTTP server for `serve` arguments. The rope and its info are
+/// the ones the shared AGC runs, resolved from `--rom` beforehand.
+pub fn spawn(
+    args: &clap::ArgMatches,
+    rope: &'static RopeImage,
+    rom_info: RomInfo,
+    handle: RuntimeHandle,
+) -> Result<(), String> {
+    let port = args
+        .value_of("http")
+        .unwrap_or("8080")
+        .parse()
+        .map_err(|e| format!("Invalid HTTP port: {}", e))?;
+    let access = Access {
+        operator: args.value_of("token").map(String::from),
+        observer: args.value_of("observer-token").map(String::from),
+        public: args.is_present("public"),
+    };
+    let pool = match args.value_of("max-sessions") {
+        Some(max) => {
+            let config = pool_config(max, args.value_of("idle-timeout").unwrap_or("1800"))
+                .map_err(|e| format!("Invalid session limits: {}", e))?;
+            Some(Pool::spawn(config, rope, rom_info))
+        }
+        None => None,
+    };
+    crate::http::spawn(port, access, handle, pool).map_err(|e| format!("HTTP server failed: {}", e))
+}
+
+fn pool_config(max: &str, timeout: &str) -> Result<PoolConfig, std::num::ParseIntError> {
+    Ok(PoolConfig {
+        max_instances: max.parse()?,
+        idle_timeout: Duration::from_secs(timeout.parse()?),
+    })
+}
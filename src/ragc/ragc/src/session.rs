@@ -0,0 +1,350 @@
+use std::cell::RefCell;
+use std::io::Write;
+use std::rc::Rc;
+use std::time::Instant;
+
+use log::error;
+use serde_json::{json, Value};
+
+use dsky_protocol::pinball::DisplayState;
+use ragc_core::constants::ports;
+use ragc_core::memory::channels::channel_bits;
+use ragc_core::memory::mods::MCT_SECONDS;
+use ragc_core::memory::tap::{ChannelTap, TapAction};
+
+use crate::timeline::Marker;
+
+/// Relay row 12 drives the caution and status lamps
+const RELAY_ROW_LIGHTS: u16 = 12;
+const RELAY_LAMPS: [(u16, &str); 8] = [
+    (0o001, "PRIO DISP"),
+    (0o002, "NO DAP"),
+    (0o004, "VEL"),
+    (0o010, "NO ATT"),
+    (0o020, "ALT"),
+    (0o040, "GIMBAL LOCK"),
+    (0o200, "TRACKER"),
+    (0o400, "PROG"),
+];
+
+/// Channel 11 bits that light DSKY lamps
+const DSALMOUT_LAMPS: u16 = 0o176;
+
+/// DSKY keys by keycode, for the key log
+const KEY_LEGENDS: [(u16, &str); 18] = [
+    (0o21, "VERB"),
+    (0o37, "NOUN"),
+    (0o34, "ENTR"),
+    (0o36, "CLR"),
+    (0o22, "RSET"),
+    (0o31, "KEY REL"),
+    (0o32, "+"),
+    (0o33, "-"),
+    (0o20, "0"),
+    (1, "1"),
+    (2, "2"),
+    (3, "3"),
+    (4, "4"),
+    (5, "5"),
+    (6, "6"),
+    (7, "7"),
+    (8, "8"),
+    (9, "9"),
+];
+
+/// Replay page; the events replace the marker
+const PAGE: &str = include_str!("session.html");
+const EVENTS_MARKER: &str = "/*EVENTS*/[]";
+
+struct Recorder {
+    out: Option<Box<dyn Write>>,
+    started: Instant,
+    mcts: usize, // Emulated time, kept current by the emulation loop
+    display: DisplayState,
+    shown: Value, // Display fields last written
+    relay_lights: u16,
+    lamps: u16,
+    lights: u16,
+    lit: Vec<&'static str>,
+    proceed: bool,
+}
+
+impl Recorder {
+    fn emit(&mut self, kind: &str, fields: Value) {
+        let mut event = json!({
+            "type": kind,
+            "wall": self.started.elapsed().as_secs_f64(),
+            "t": self.mcts as f64 * MCT_SECONDS,
+        });
+        if let (Some(event), Value::Object(fields)) = (event.as_object_mut(), fields) {
+            event.extend(fields);
+        }
+        if let Some(out) = &mut self.out {
+            if let Err(e) = writeln!(out, "{}", event).and_then(|_| out.flush()) {
+                error!("Session log closed: {}", e);
+                self.out = None;
+            }
+        }
+    }
+
+    fn display_fields(&self) -> Value {
+        let digits = |ds: &[Option<u8>]| -> String {
+            ds.iter()
+                .map(|d| d.map_or(' ', |d| (b'0' + d) as char))
+                .collect()
+        };
+        let [prog, verb, noun] = self.display.pair_digits();
+        let mut fields = json!({
+            "prog": digits(&prog),
+            "verb": digits(&verb),
+            "noun": digits(&noun),
+        });
+        for reg in 0..3 {
+            let sign = match self.display.register_sign(reg) {
+                Some(1) => '+',
+                Some(_) => '-',
+                None => ' ',
+            };
+            let text = format!("{}{}", sign, digits(&self.display.register_digits(reg)));
+            fields[format!("r{}", reg + 1)] = json!(text);
+        }
+        fields
+    }
+
+    fn lamp_names(&self) -> Vec<&'static str> {
+        let named = |channel: usize, value: u16| {
+            channel_bits(channel)
+                .iter()
+                .filter(move |field| value & field.mask != 0)
+                .map(|field| field.name)
+        };
+        let mut lit: Vec<_> = RELAY_LAMPS
+            .iter()
+            .filter(|(mask, _)| self.relay_lights & mask != 0)
+            .map(|(_, name)| *name)
+            .chain(named(ports::CHANNEL_DSALMOUT, self.lamps & DSALMOUT_LAMPS))
+            .chain(named(ports::CHANNEL_DSKY_LIGHTS, self.lights))
+            .collect();
+        lit.sort_unstable();
+        lit.dedup();
+        lit
+    }
+
+    fn output(&mut self, channel: usize, value: u16) {
+        match channel {
+            ports::CHANNEL_DSKY if value >> 11 == RELAY_ROW_LIGHTS => {
+                self.relay_lights = value & 0o3777
+            }
+            ports::CHANNEL_DSKY => {
+                self.display.apply_relay_word(value);
+                let fields = self.display_fields();
+                if fields != self.shown {
+                    self.shown = fields.clone();
+                    self.emit("display", fields);
+                }
+                return;
+            }
+            ports::CHANNEL_DSALMOUT => self.lamps = value,
+            ports::CHANNEL_DSKY_LIGHTS => self.lights = value,
+            _ => return,
+        }
+        let lit = self.lamp_names();
+        if lit != self.lit {
+            self.lit = lit;
+            let lamps = json!({ "lamps": self.lit });
+            self.emit("lamps", lamps);
+        }
+    }
+
+    fn input(&mut self, channel: usize, value: u16) {
+        match channel {
+            ports::CHANNEL_MNKEYIN => {
+                let code = value & 0o37;
+                if let Some((_, legend)) = KEY_LEGENDS.iter().find(|(c, _)| *c == code) {
+                    self.emit("key", json!({ "key": legend }));
+                }
+            }
+            ports::CHANNEL_CHAN32 => {
+                let held = value & ports::CHAN32_PROCEED == 0;
+                if held != self.proceed {
+                    self.proceed = held;
+                    self.emit("key", json!({ "key": "PRO", "held": held }));
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Demonstration log for `--session <file>`: one JSON line per display
+/// change, lamp change and key press, stamped with wall-clock and emulated
+/// seconds. Keys are logged as the computer reads them.
+#[derive(Clone)]
+pub struct SessionLog {
+    recorder: Rc<RefCell<Recorder>>,
+}
+
+impl SessionLog {
+    pub fn new(out: Box<dyn Write>) -> Self {
+        let recorder = Recorder {
+            out: Some(out),
+            started: Instant::now(),
+            mcts: 0,
+            display: DisplayState::new(),
+            shown: Value::Null,
+            relay_lights: 0,
+            lamps: 0,
+            lights: 0,
+            lit: Vec::new(),
+            proceed: false,
+        };
+        Self {
+            recorder: Rc::new(RefCell::new(recorder)),
+        }
+    }
+
+    pub fn create(path: &str) -> Result<Self, String> {
+        let file = std::fs::File::create(path).map_err(|e| format!("{}: {}", path, e))?;
+        Ok(Self::new(Box::new(file)))
+    }
+
+    /// One tap per DSKY channel, each sharing this log
+    pub fn taps(&self) -> [(usize, SessionLog); 5] {
+        [
+            (ports::CHANNEL_DSKY, self.clone()),
+            (ports::CHANNEL_DSALMOUT, self.clone()),
+            (ports::CHANNEL_MNKEYIN, self.clone()),
+            (ports::CHANNEL_CHAN32, self.clone()),
+            (ports::CHANNEL_DSKY_LIGHTS, self.clone()),
+        ]
+    }
+
+    /// Log a timeline marker, stamped at the current time
+    pub fn mark(&self, marker: &Marker) {
+        self.recorder
+            .borrow_mut()
+            .emit("marker", json!({ "name": marker.name }));
+    }
+
+    /// Advance the emulated clock used to stamp events
+    pub fn set_time(&self, total_cycles: usize) {
+        self.recorder.borrow_mut().mcts = total_cycles;
+    }
+}
+
+impl ChannelTap for SessionLog {
+    fn read(&mut self, channel: usize, value: u16) -> TapAction {
+        self.recorder.borrow_mut().input(channel, value);
+        TapAction::Pass(value)
+    }
+
+    fn write(&mut self, channel: usize, value: u16) -> TapAction {
+        self.recorder.borrow_mut().output(channel, value);
+        TapAction::Pass(value)
+    }
+}
+
+/// Self-contained HTML page replaying a session log
+pub fn render(log: &str) -> Result<String, String> {
+    let mut events = Vec::new();
+    for (n, line) in log
+        .lines()
+        .enumerate()
+        .filter(|(_, l)| !l.trim().is_empty())
+    {
+        let event: Value =
+            serde_json::from_str(line).map_err(|e| format!("line {}: {}", n + 1, e))?;
+        if !event["t"].is_number() || !event["wall"].is_number() {
+            return Err(format!("line {}: missing timestamps", n + 1));
+        }
+        events.push(event);
+    }
+    // Keep the data from closing the script element
+    let data = Value::Array(events).to_string().replace("</", "<\\/");
+    Ok(PAGE.replace(EVENTS_MARKER, &data))
+}
+
+/// The `render-session` subcommand and its options
+pub fn subcommand<'a, 'b>() -> clap::App<'a, 'b> {
+    clap::SubCommand::with_name("render-session")
+        .about("Turn a --session log into an HTML replay page")
+        .arg(
+            clap::Arg::with_name("log")
+                .required(true)
+                .help("Session log recorded with --session"),
+        )
+        .arg(
+            clap::Arg::with_name("out")
+                .long("out")
+                .takes_value(true)
+                .help("HTML file (default: stdout)"),
+        )
+}
+
+/// Writes the replay page for a session log
+pub fn command(args: &clap::ArgMatches) -> Result<(), String> {
+    let path = args.value_of("log").unwrap_or_default();
+    let log = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+    let page = render(&log).map_err(|e| format!("{}: {}", path, e))?;
+    match args.value_of("out") {
+        Some(out) => std::fs::write(out, page).map_err(|e| format!("{}: {}", out, e)),
+        None => std::io::Write::write_all(&mut std::io::stdout(), page.as_bytes())
+            .map_err(|e| e.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod session_tests {
+    use super::{render, SessionLog};
+    use ragc_core::memory::mods::EmuTime;
+    use ragc_core::memory::tap::ChannelTap;
+    use serde_json::Value;
+    use std::cell::RefCell;
+    use std::io::Write;
+    use std::rc::Rc;
+
+    struct Buffer(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for Buffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.borrow_mut().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn logs_changes_and_renders_a_replay_page() {
+        let buffer = Rc::new(RefCell::new(Vec::new()));
+        let log = SessionLog::new(Box::new(Buffer(buffer.clone())));
+        let [(_, mut relays), (_, mut lamps), (_, mut keys), _, _] = log.taps();
+
+        keys.read(0o15, 0o21);
+        log.set_time(EmuTime::mcts_in_millis(1000) as usize); // 1 s
+        relays.write(0o10, (10 << 11) | (3 << 5) | 28); // VERB 16
+        relays.write(0o10, (10 << 11) | (3 << 5) | 28); // Refresh, no change
+        relays.write(0o10, (12 << 11) | 0o400);
+        lamps.write(0o11, 0o20);
+
+        let text = String::from_utf8(buffer.borrow().clone()).unwrap();
+        let events: Vec<Value> = text
+            .lines()
+            .map(|l| serde_json::from_str(l).unwrap())
+            .collect();
+        assert_eq!(events.len(), 4, "{}", text);
+        assert_eq!(events[0]["key"], "VERB");
+        assert_eq!(events[1]["type"], "display");
+        assert_eq!(events[1]["verb"], "16");
+        assert!((events[1]["t"].as_f64().unwrap() - 1.0).abs() < 1e-3);
+        assert_eq!(events[2]["lamps"], serde_json::json!(["PROG"]));
+        assert_eq!(events[3]["lamps"], serde_json::json!(["KEY REL", "PROG"]));
+
+        let page = render(&text).unwrap();
+        assert!(page.contains(r#""verb":"16""#));
+        assert!(!page.contains("/*EVENTS*/"));
+        assert!(render("{\"type\":\"key\"}").is_err());
+    }
+}
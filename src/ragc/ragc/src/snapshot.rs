@@ -0,0 +1,371 @@
+//! Snapshot files. A fixed header identifies the format, its version, the
+//! rope the snapshot was taken on and any features a reader must support;
+//! the state follows as tagged, length-prefixed chunks. Readers skip chunks
+//! they don't know, so later versions can add state that older snapshots
+//! simply lack. All integers are little-endian.
+use std::convert::{TryFrom, TryInto};
+use std::io::Write;
+
+use log::error;
+
+use ragc_core::memory;
+use ragc_core::symbols::ErasableAddress;
+
+use crate::listing::{Location, Symbols};
+use crate::runtime::{Snapshot, ERASABLE_BANK_WORDS};
+
+const MAGIC: &[u8; 8] = b"RAGCSNAP";
+
+/// Format version written; files up to this version can be read
+pub const VERSION: u16 = 1;
+
+/// Feature flags this reader understands. A writer sets a flag when the
+/// snapshot can't be restored faithfully without that feature.
+const KNOWN_FLAGS: u32 = 0;
+
+const CHUNK_CPU: [u8; 4] = *b"CPU ";
+const CHUNK_ERASABLE: [u8; 4] = *b"ERAS";
+const CHUNK_PERIPHS: [u8; 4] = *b"PERI"; // Tagged, length-prefixed states
+
+/// Flag bits in the CPU chunk
+const CPU_EXTEND: u8 = 1 << 0;
+const CPU_GINT: u8 = 1 << 1;
+const CPU_IRUPT: u8 = 1 << 2;
+
+/// Magic, version, flags and ROM hash
+const HEADER_LEN: usize = 8 + 2 + 4 + 8;
+
+pub fn encode(snap: &Snapshot) -> Vec<u8> {
+    let mut out = Vec::new();
+    out.extend_from_slice(MAGIC);
+    out.extend_from_slice(&VERSION.to_le_bytes());
+    out.extend_from_slice(&0u32.to_le_bytes());
+    out.extend_from_slice(&snap.rom_hash.to_le_bytes());
+
+    let mut cpu = Vec::new();
+    cpu.extend_from_slice(&snap.seed.to_le_bytes());
+    cpu.extend_from_slice(&(snap.total_cycles as u64).to_le_bytes());
+    cpu.extend_from_slice(&snap.state_hash.to_le_bytes());
+    cpu.extend_from_slice(&snap.ir.to_le_bytes());
+    cpu.extend_from_slice(&snap.superbank.to_le_bytes());
+    let flags = [
+        (snap.ec_flag, CPU_EXTEND),
+        (snap.gint, CPU_GINT),
+        (snap.is_irupt, CPU_IRUPT),
+    ];
+    cpu.push(
+        flags
+            .iter()
+            .filter(|(set, _)| *set)
+            .fold(0, |f, (_, bit)| f | bit),
+    );
+    push_chunk(&mut out, CHUNK_CPU, &cpu);
+
+    let words: Vec<u8> = snap
+        .erasable
+        .iter()
+        .flatten()
+        .flat_map(|w| w.to_le_bytes())
+        .collect();
+    push_chunk(&mut out, CHUNK_ERASABLE, &words);
+
+    let mut periphs = Vec::new();
+    for (tag, state) in snap.periphs.iter() {
+        push_chunk(&mut periphs, *tag, state);
+    }
+    push_chunk(&mut out, CHUNK_PERIPHS, &periphs);
+    out
+}
+
+fn push_chunk(out: &mut Vec<u8>, tag: [u8; 4], body: &[u8]) {
+    out.extend_from_slice(&tag);
+    out.extend_from_slice(&(body.len() as u32).to_le_bytes());
+    out.extend_from_slice(body);
+}
+
+/// Little-endian reader over a byte slice
+struct Cursor<'a>(&'a [u8]);
+
+impl<'a> Cursor<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8], String> {
+        if self.0.len() < n {
+            return Err("Snapshot is truncated".to_string());
+        }
+        let (head, rest) = self.0.split_at(n);
+        self.0 = rest;
+        Ok(head)
+    }
+
+    fn u8(&mut self) -> Result<u8, String> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, String> {
+        Ok(u16::from_le_bytes(self.take(2)?.try_into().unwrap()))
+    }
+
+    fn u32(&mut self) -> Result<u32, String> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn u64(&mut self) -> Result<u64, String> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+}
+
+pub fn decode(data: &[u8]) -> Result<Snapshot, String> {
+    if data.len() < HEADER_LEN || &data[..MAGIC.len()] != MAGIC {
+        return Err("Not a ragc snapshot".to_string());
+    }
+    let mut cursor = Cursor(&data[MAGIC.len()..]);
+    let version = cursor.u16()?;
+    if version > VERSION {
+        return Err(format!(
+            "Snapshot format {} is newer than this emulator reads ({})",
+            version, VERSION
+        ));
+    }
+    let flags = cursor.u32()?;
+    if flags & !KNOWN_FLAGS != 0 {
+        return Err(format!(
+            "Snapshot needs unsupported features (flags {:08x})",
+            flags & !KNOWN_FLAGS
+        ));
+    }
+    let rom_hash = cursor.u64()?;
+
+    let mut cpu = None;
+    let mut erasable = None;
+    let mut periphs = Vec::new();
+    while !cursor.0.is_empty() {
+        let tag = cursor.take(4)?;
+        let len = cursor.u32()? as usize;
+        let mut body = Cursor(cursor.take(len)?);
+        match <[u8; 4]>::try_from(tag).unwrap() {
+            CHUNK_CPU => cpu = Some(body),
+            CHUNK_ERASABLE => {
+                let bank_bytes = 2 * ERASABLE_BANK_WORDS;
+                if body.0.is_empty() || body.0.len() % bank_bytes != 0 {
+                    return Err("Erasable chunk is not whole banks".to_string());
+                }
+                let mut banks = vec![[0; ERASABLE_BANK_WORDS]; body.0.len() / bank_bytes];
+                for word in banks.iter_mut().flatten() {
+                    *word = body.u16()?;
+                }
+                erasable = Some(banks);
+            }
+            CHUNK_PERIPHS => {
+                while !body.0.is_empty() {
+                    let tag = <[u8; 4]>::try_from(body.take(4)?).unwrap();
+                    let len = body.u32()? as usize;
+                    periphs.push((tag, body.take(len)?.to_vec()));
+                }
+            }
+            _ => {} // Written by a later version
+        }
+    }
+
+    let mut cpu = cpu.ok_or("Snapshot has no CPU chunk")?;
+    let erasable = erasable.ok_or("Snapshot has no erasable chunk")?;
+    let seed = cpu.u64()?;
+    let total_cycles = cpu.u64()? as usize;
+    let state_hash = cpu.u64()?;
+    let ir = cpu.u16()?;
+    let superbank = cpu.u16()?;
+    let flags = cpu.u8()?;
+    Ok(Snapshot {
+        seed,
+        total_cycles,
+        a: erasable[0][0o0],
+        l: erasable[0][0o1],
+        q: erasable[0][0o2],
+        z: erasable[0][0o5],
+        erasable,
+        state_hash,
+        rom_hash,
+        ir,
+        superbank,
+        ec_flag: flags & CPU_EXTEND != 0,
+        gint: flags & CPU_GINT != 0,
+        is_irupt: flags & CPU_IRUPT != 0,
+        periphs,
+    })
+}
+
+pub fn save(path: &str, snap: &Snapshot) -> Result<(), String> {
+    std::fs::write(path, encode(snap)).map_err(|e| format!("{}: {}", path, e))
+}
+
+pub fn load(path: &str) -> Result<Snapshot, String> {
+    let data = std::fs::read(path).map_err(|e| format!("{}: {}", path, e))?;
+    decode(&data).map_err(|e| format!("{}: {}", path, e))
+}
+
+/// Erasable words that differ between two snapshots, as flat address, old
+/// and new value
+pub fn diff(old: &Snapshot, new: &Snapshot) -> Vec<(usize, u16, u16)> {
+    let words = |snap: &Snapshot| snap.erasable.iter().flatten().copied().collect::<Vec<_>>();
+    words(old)
+        .into_iter()
+        .zip(words(new))
+        .enumerate()
+        .filter(|(_, (a, b))| a != b)
+        .map(|(flat, (a, b))| (flat, a, b))
+        .collect()
+}
+
+/// Print the erasable changes from `old` to `new`, one word per line with
+/// its symbol name if it has one
+pub fn write_diff(
+    old: &Snapshot,
+    new: &Snapshot,
+    symbols: &Symbols,
+    out: &mut dyn Write,
+) -> std::io::Result<()> {
+    if old.rom_hash != new.rom_hash {
+        writeln!(out, "# Snapshots were taken on different ropes")?;
+    }
+    let changes = diff(old, new);
+    writeln!(
+        out,
+        "# {:+} MCTs, {} words changed",
+        new.total_cycles as i64 - old.total_cycles as i64,
+        changes.len()
+    )?;
+    for (flat, a, b) in changes {
+        // Banks E3 and up are only reachable through the EB window
+        let addr = memory::Location::Erasable(ErasableAddress::from_flat(flat)).to_string();
+        let name = match flat {
+            0o0000..=0o1777 => symbols.get(&Location::Erasable(flat as u16)),
+            _ => None,
+        };
+        writeln!(
+            out,
+            "{:<8} {:<8} {:05o} -> {:05o}",
+            addr,
+            name.unwrap_or_default(),
+            a,
+            b
+        )?;
+    }
+    Ok(())
+}
+
+/// The `snapshot` subcommand and its options
+pub fn subcommand<'a, 'b>() -> clap::App<'a, 'b> {
+    clap::SubCommand::with_name("snapshot")
+        .about("Inspect saved snapshots")
+        .setting(clap::AppSettings::SubcommandRequiredElseHelp)
+        .subcommand(
+            clap::SubCommand::with_name("diff")
+                .about("List the erasable words that changed between two snapshots")
+                .arg(
+                    clap::Arg::with_name("old")
+                        .required(true)
+                        .help("Earlier snapshot"),
+                )
+                .arg(
+                    clap::Arg::with_name("new")
+                        .required(true)
+                        .help("Later snapshot"),
+                )
+                .arg(
+                    clap::Arg::with_name("symbols")
+                        .long("symbols")
+                        .takes_value(true)
+                        .help("Symbol names, one `NAME ADDRESS` per line"),
+                ),
+        )
+}
+
+/// Runs `snapshot diff`
+pub fn command(args: &clap::ArgMatches) {
+    if let Some(args) = args.subcommand_matches("diff") {
+        if let Err(e) = diff_files(args) {
+            error!("Snapshot diff failed: {}", e);
+        }
+    }
+}
+
+/// Prints the erasable changes between two snapshot files
+fn diff_files(args: &clap::ArgMatches) -> Result<(), String> {
+    let old = load(args.value_of("old").unwrap_or_default())?;
+    let new = load(args.value_of("new").unwrap_or_default())?;
+    let symbols = crate::load_symbols(args.value_of("symbols"))?;
+    write_diff(&old, &new, &symbols, &mut std::io::stdout()).map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod snapshot_tests {
+    use super::{decode, diff, encode, push_chunk, write_diff};
+    use crate::listing::Symbols;
+    use crate::runtime::{Command, Runtime};
+    use crossbeam_channel::bounded;
+    use ragc_core::cpu::Cpu;
+    use ragc_core::memory::MemoryMap;
+
+    #[test]
+    fn round_trips_and_rejects_what_it_cannot_restore() {
+        let mut cpu = Cpu::new(MemoryMap::new_blank());
+        let (mut runtime, handle) = Runtime::new(None, 9);
+        handle.poke(0o1234, 0o7070);
+        let (reply_tx, reply_rx) = bounded(1);
+        handle.send(Command::Snapshot(reply_tx));
+        runtime.poll(&mut cpu);
+        let snap = reply_rx.recv().unwrap();
+
+        // Chunks from a later version are skipped
+        let mut data = encode(&snap);
+        push_chunk(&mut data, *b"NEW!", &[1, 2, 3]);
+        let loaded = decode(&data).unwrap();
+        assert_eq!(loaded.erasable, snap.erasable);
+        assert_eq!((loaded.seed, loaded.rom_hash), (9, snap.rom_hash));
+        assert_eq!(loaded.erasable[2][0o234], 0o7070);
+        assert_eq!(loaded.periphs, snap.periphs);
+        assert_eq!(snap.periphs[0].0, *b"CHAN");
+
+        let mut newer = encode(&snap);
+        newer[8] = 2;
+        assert!(decode(&newer).unwrap_err().contains("newer"));
+        let mut flagged = encode(&snap);
+        flagged[10] = 1;
+        assert!(decode(&flagged).unwrap_err().contains("features"));
+        assert!(decode(&data[..40]).is_err());
+        assert!(decode(b"not a snapshot at all...").is_err());
+
+        // Restoring checks the rope, then brings the memory back
+        handle.poke(0o1234, 0);
+        let (reply_tx, reply_rx) = bounded(2);
+        let mut other = loaded.clone();
+        other.rom_hash ^= 1;
+        handle.send(Command::Restore(Box::new(other), reply_tx.clone()));
+        handle.send(Command::Restore(Box::new(loaded), reply_tx));
+        runtime.poll(&mut cpu);
+        assert!(reply_rx.recv().unwrap().unwrap_err().contains("ROM"));
+        assert_eq!(reply_rx.recv().unwrap(), Ok(()));
+        assert_eq!(cpu.memory().read_block(2, 0o234..0o235), [0o7070]);
+    }
+
+    #[test]
+    fn diff_names_changed_words() {
+        let mut cpu = Cpu::new(MemoryMap::new_blank());
+        let (mut runtime, handle) = Runtime::new(None, 0);
+        let (reply_tx, reply_rx) = bounded(2);
+        handle.send(Command::Snapshot(reply_tx.clone()));
+        handle.poke(0o1234, 0o7070);
+        handle.send(Command::Snapshot(reply_tx));
+        runtime.poll(&mut cpu);
+        let (old, mut new) = (reply_rx.recv().unwrap(), reply_rx.recv().unwrap());
+        new.erasable[7][0o153] = 0o31;
+        assert_eq!(diff(&old, &new), [(0o1234, 0, 0o7070), (0o3553, 0, 0o31)]);
+
+        let symbols = Symbols::parse("RN 1234").unwrap();
+        let mut out = Vec::new();
+        write_diff(&old, &new, &symbols, &mut out).unwrap();
+        let text = String::from_utf8(out).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines[0], "# +0 MCTs, 2 words changed");
+        assert_eq!(lines[1], "1234     RN       00000 -> 07070");
+        assert_eq!(lines[2], "E7,1553           00000 -> 00031");
+    }
+}
@@ -0,0 +1,279 @@
+use std::io::Write;
+
+use log::{error, info};
+use serde::Deserialize;
+
+use ragc_core::memory::mods::MCT_SECONDS;
+use ragc_core::memory::MemoryMap;
+use ragc_core::state_vector::read_dp_fraction;
+use ragc_core::symbols::ErasableAddress;
+
+use crate::timeline::Marker;
+use crate::vardb::{VarDb, VarFormat};
+
+/// Variables streamed with `--stream-vars <file>`, one NDJSON line per
+/// sample: `{"t":12.5,"RN":[...],"FLAGWRD0":12345}`
+///
+/// ```toml
+/// rate_hz = 10.0            # Samples per emulated second
+/// output = "/tmp/agc.fifo"  # File or named pipe (default: stdout)
+///
+/// [[vars]]
+/// name = "RN"
+/// addr = 0o1234   # Flat erasable address
+/// kind = "dp"     # "sp" and "dp" fractions, or the "raw" word
+/// count = 3       # Consecutive values, as an array
+/// scale = 536870912.0
+///
+/// [[vars]]
+/// name = "VN"     # Address, kind, count and scale from the variable database
+/// ```
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct StreamConfig {
+    pub rate_hz: Option<f64>,
+    pub output: Option<String>,
+    pub vars: Vec<VarConfig>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct VarConfig {
+    pub name: String,
+    pub addr: Option<usize>,
+    pub kind: Option<VarKind>,
+    pub count: Option<usize>,
+    pub scale: Option<f64>,
+}
+
+#[derive(Deserialize, Clone, Copy, PartialEq, Debug, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum VarKind {
+    #[default]
+    Sp,
+    Dp,
+    Raw,
+}
+
+impl VarKind {
+    fn words(&self) -> usize {
+        match self {
+            VarKind::Dp => 2,
+            _ => 1,
+        }
+    }
+}
+
+impl StreamConfig {
+    pub fn load(path: &str, db: &VarDb) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+        let mut config: Self = toml::from_str(&text).map_err(|e| format!("{}: {}", path, e))?;
+        config.resolve(db);
+        config.validate().map_err(|e| format!("{}: {}", path, e))?;
+        Ok(config)
+    }
+
+    /// Take what variables without an address leave out from the database
+    fn resolve(&mut self, db: &VarDb) {
+        for var in self.vars.iter_mut().filter(|var| var.addr.is_none()) {
+            let entry = match db.get(&var.name) {
+                Some(entry) => entry,
+                None => continue,
+            };
+            let (kind, count) = match entry.format {
+                VarFormat::Sp => (VarKind::Sp, None),
+                VarFormat::Dp => (VarKind::Dp, None),
+                VarFormat::Vector => (VarKind::Dp, Some(3)),
+                VarFormat::Raw => (VarKind::Raw, None),
+            };
+            var.addr = Some((entry.addr.bank << 8) | entry.addr.offset);
+            var.kind = var.kind.or(Some(kind));
+            var.count = var.count.or(count);
+            var.scale = var.scale.or(Some(entry.scale));
+        }
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if self.rate_hz.is_some_and(|r| r <= 0.0) {
+            return Err("rate_hz must be positive".to_string());
+        }
+        for var in self.vars.iter() {
+            let addr = var.addr.ok_or_else(|| {
+                format!(
+                    "{} has no addr and is not in the variable database",
+                    var.name
+                )
+            })?;
+            let words = var.kind.unwrap_or_default().words() * var.count.unwrap_or(1);
+            let start = ErasableAddress::from_flat(addr);
+            if addr >= 0o4000 || start.offset + words > 0o400 {
+                return Err(format!("{} is not within one erasable bank", var.name));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Ones' complement single precision fraction (-1, 1)
+fn sp_fraction(word: u16) -> f64 {
+    let value = if word & 0o40000 != 0 {
+        -(((!word) & 0o37777) as i32)
+    } else {
+        (word & 0o37777) as i32
+    };
+    value as f64 / (1 << 14) as f64
+}
+
+/// Samples the configured variables on the emulated clock
+pub struct VarStream {
+    vars: Vec<VarConfig>,
+    period_mcts: usize,
+    next: usize, // MCT count of the next sample
+    out: Option<Box<dyn Write>>,
+}
+
+impl VarStream {
+    pub fn open(config: StreamConfig) -> Result<Self, String> {
+        let out: Box<dyn Write> = match &config.output {
+            Some(path) => {
+                Box::new(std::fs::File::create(path).map_err(|e| format!("{}: {}", path, e))?)
+            }
+            None => Box::new(std::io::stdout()),
+        };
+        let rate = config.rate_hz.unwrap_or(10.0);
+        info!("Streaming {} variables at {} Hz", config.vars.len(), rate);
+        Ok(Self {
+            vars: config.vars,
+            period_mcts: ((1.0 / rate / MCT_SECONDS) as usize).max(1),
+            next: 0,
+            out: Some(out),
+        })
+    }
+
+    /// One sample of every variable as an NDJSON line
+    fn sample(&self, mem: &MemoryMap, time: f64) -> String {
+        let mut line = serde_json::Map::new();
+        line.insert("t".to_string(), time.into());
+        for var in self.vars.iter() {
+            let start = ErasableAddress::from_flat(var.addr.unwrap_or_default());
+            let kind = var.kind.unwrap_or_default();
+            let scale = var.scale.unwrap_or(1.0);
+            let values: Vec<serde_json::Value> = (0..var.count.unwrap_or(1))
+                .map(|i| {
+                    let addr = start.offset_by(i * kind.words());
+                    let word = mem.read_block(addr.bank, addr.offset..addr.offset + 1)[0];
+                    match kind {
+                        VarKind::Sp => (sp_fraction(word) * scale).into(),
+                        VarKind::Dp => (read_dp_fraction(mem, addr) * scale).into(),
+                        VarKind::Raw => word.into(),
+                    }
+                })
+                .collect();
+            let value = match var.count {
+                Some(_) => values.into(),
+                None => values.into_iter().next().unwrap_or_default(),
+            };
+            line.insert(var.name.clone(), value);
+        }
+        serde_json::Value::Object(line).to_string()
+    }
+
+    /// Write a sample if one is due at `total_cycles`. A reader going
+    /// away ends the stream without stopping the emulator.
+    pub fn poll(&mut self, mem: &MemoryMap, total_cycles: usize) {
+        if total_cycles < self.next || self.out.is_none() {
+            return;
+        }
+        self.next = total_cycles + self.period_mcts;
+        let line = self.sample(mem, total_cycles as f64 * MCT_SECONDS);
+        self.write_line(&line);
+    }
+
+    /// Interleave a timeline marker with the samples
+    pub fn mark(&mut self, marker: &Marker) {
+        let line = serde_json::json!({ "t": marker.time(), "marker": marker.name });
+        self.write_line(&line.to_string());
+    }
+
+    fn write_line(&mut self, line: &str) {
+        if let Some(out) = &mut self.out {
+            if let Err(e) = writeln!(out, "{}", line).and_then(|_| out.flush()) {
+                error!("Variable stream closed: {}", e);
+                self.out = None;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod stream_tests {
+    use super::{StreamConfig, VarStream};
+    use crate::vardb::VarDb;
+    use ragc_core::constants::timers::TIMER_1_ADDRESS;
+    use ragc_core::memory::MemoryMap;
+    use ragc_core::symbols::ErasableAddress;
+
+    #[test]
+    fn samples_scaled_variables() {
+        let config: StreamConfig = toml::from_str(
+            r#"
+            [[vars]]
+            name = "RN"
+            addr = 0o1400
+            kind = "dp"
+            count = 2
+            scale = 4.0
+
+            [[vars]]
+            name = "FLAG"
+            addr = 0o100
+            kind = "raw"
+
+            [[vars]]
+            name = "HALF"
+            addr = 0o101
+            "#,
+        )
+        .unwrap();
+        assert!(config.validate().is_ok());
+
+        let mut mem = MemoryMap::new_blank();
+        mem.write_block(3, 0, &[0o20000, 0, 0o57777, 0o77777]); // 1/2, -1/2
+        mem.write_block(0, 0o100, &[0o12345, 0o20000]);
+        let stream = VarStream {
+            out: None,
+            ..VarStream::open(config).unwrap()
+        };
+        assert_eq!(
+            stream.sample(&mem, 1.5),
+            r#"{"FLAG":5349,"HALF":0.5,"RN":[2.0,-2.0],"t":1.5}"#
+        );
+
+        // Timers stream from the timers, not the RAM behind them
+        let config: StreamConfig =
+            toml::from_str("[[vars]]\nname = \"TIME1\"\naddr = 0o25\nkind = \"raw\"").unwrap();
+        let stream = VarStream {
+            out: None,
+            ..VarStream::open(config).unwrap()
+        };
+        mem.write(TIMER_1_ADDRESS, 0o1234);
+        assert_eq!(stream.sample(&mem, 0.0), r#"{"TIME1":668,"t":0.0}"#);
+
+        let bad: StreamConfig =
+            toml::from_str("[[vars]]\nname = \"X\"\naddr = 0o377\nkind = \"dp\"").unwrap();
+        assert!(bad.validate().is_err());
+
+        // Names alone come from the variable database
+        let symbols = [("VN", ErasableAddress::new(3, 0))];
+        let db = VarDb::parse(
+            "[[vars]]\nname = \"VN\"\nformat = \"vector\"\n",
+            &symbols[..],
+        );
+        let mut config: StreamConfig =
+            toml::from_str("[[vars]]\nname = \"VN\"\n[[vars]]\nname = \"X\"\n").unwrap();
+        config.resolve(&db.unwrap());
+        assert_eq!(config.vars[0].addr, Some(0o1400));
+        assert_eq!(config.vars[0].count, Some(3));
+        assert!(config.validate().is_err());
+    }
+}
@@ -0,0 +1,155 @@
+//! `ragc synth-downlink`: synthetic downlists for testing ground tools
+use crossbeam_channel::Receiver;
+use log::{error, info};
+
+use ragc_core::memory::mods::IoPeriph;
+use ragc_core::rng::{streams, Rng};
+use ragc_peripherals::downlist::{DownlistSynth, Pattern, DOWNRUPT_MS};
+use ragc_peripherals::downrupt::{DownruptPeriph, TelemetryConfig};
+
+/// The `synth-downlink` subcommand and its options
+pub fn subcommand<'a, 'b>() -> clap::App<'a, 'b> {
+    clap::SubCommand::with_name("synth-downlink")
+        .about("Send synthetic downlists over the telemetry transport")
+        .arg(
+            clap::Arg::with_name("id")
+                .long("id")
+                .takes_value(true)
+                .default_value("77777")
+                .help("Downlist ID word (octal)"),
+        )
+        .arg(
+            clap::Arg::with_name("pattern")
+                .long("pattern")
+                .takes_value(true)
+                .default_value("counter")
+                .help("Data words: counter, frame, random or constant:<octal>"),
+        )
+        .arg(
+            clap::Arg::with_name("frames")
+                .long("frames")
+                .takes_value(true)
+                .default_value("0")
+                .help("Downlists to send (0 = until interrupted)"),
+        )
+}
+
+/// Runs `synth-downlink` over the configured telemetry transport
+pub fn command(
+    args: &clap::ArgMatches,
+    telemetry: TelemetryConfig,
+    rng: &Rng,
+    shutdown: &Receiver<()>,
+) {
+    let id = u16::from_str_radix(args.value_of("id").unwrap_or_default(), 8);
+    let pattern = parse_pattern(args.value_of("pattern").unwrap_or_default());
+    let frames = args.value_of("frames").unwrap_or_default().parse::<u32>();
+    match (id, pattern, frames) {
+        (Ok(id), Ok(pattern), Ok(frames)) => run(telemetry, id, pattern, frames, rng, shutdown),
+        (_, Err(e), _) => error!("{}", e),
+        _ => error!("Invalid downlist ID or frame count"),
+    }
+}
+
+/// Parse a pattern name: counter, frame, random or constant:<octal>
+pub fn parse_pattern(arg: &str) -> Result<Pattern, String> {
+    match arg.split_once(':') {
+        Some(("constant", word)) => u16::from_str_radix(word, 8)
+            .map(Pattern::Constant)
+            .map_err(|_| format!("Invalid constant word: {}", word)),
+        None if arg == "counter" => Ok(Pattern::Counter),
+        None if arg == "frame" => Ok(Pattern::Frame),
+        None if arg == "random" => Ok(Pattern::Random),
+        _ => Err(format!("Unknown downlist pattern: {}", arg)),
+    }
+}
+
+/// Stream synthetic downlists at the DOWNRUPT rate until `frames` are sent
+/// (0 = forever) or the shutdown signal arrives
+pub fn run(
+    telemetry: TelemetryConfig,
+    id: u16,
+    pattern: Pattern,
+    frames: u32,
+    rng: &Rng,
+    shutdown: &Receiver<()>,
+) {
+    info!(
+        "Synthesizing downlist {:05o} ({:?}) on {}",
+        id, pattern, telemetry.addr
+    );
+    let mut periph = DownruptPeriph::with_config(telemetry);
+    let mut synth = DownlistSynth::new(id, pattern, rng.fork(streams::SYNTHETIC_DATA));
+
+    let period = std::time::Duration::from_millis(DOWNRUPT_MS);
+    let mut deadline = std::time::Instant::now();
+    while shutdown.is_empty() && (frames == 0 || synth.frames() < frames) {
+        periph.downlink(synth.next_pair());
+        deadline += period;
+        if let Some(wait) = deadline.checked_duration_since(std::time::Instant::now()) {
+            std::thread::sleep(wait);
+        }
+    }
+    info!("Sent {} downlists", synth.frames());
+
+    // The telemetry thread may still hold the tail of the stream, or all of
+    // it if no ground tool has connected yet
+    if let Some(writer) = periph.close() {
+        if !writer.is_finished() {
+            info!("Waiting for the ground tool to take the queued downlists");
+        }
+        while !writer.is_finished() && shutdown.is_empty() {
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+        if writer.is_finished() {
+            let _ = writer.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod synth_tests {
+    use super::{parse_pattern, run};
+    use ragc_core::rng::Rng;
+    use ragc_peripherals::downlist::{Pattern, DOWNLIST_WORDS};
+    use ragc_peripherals::downrupt::{TelemetryConfig, TelemetryFraming, TelemetryMode};
+    use std::io::Read;
+    use std::net::TcpListener;
+
+    #[test]
+    fn parses_patterns() {
+        assert!(matches!(parse_pattern("counter"), Ok(Pattern::Counter)));
+        assert!(matches!(
+            parse_pattern("constant:12345"),
+            Ok(Pattern::Constant(0o12345))
+        ));
+        assert!(parse_pattern("constant:9").is_err());
+        assert!(parse_pattern("zigzag").is_err());
+    }
+
+    #[test]
+    fn delivers_every_frame_before_returning() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let telemetry = TelemetryConfig {
+            addr: listener.local_addr().unwrap().to_string(),
+            mode: TelemetryMode::Connect,
+            framing: TelemetryFraming::Words,
+            ..TelemetryConfig::default()
+        };
+        let (_stop, shutdown) = crossbeam_channel::bounded(1);
+        run(
+            telemetry,
+            0o77774,
+            Pattern::Counter,
+            1,
+            &Rng::new(1),
+            &shutdown,
+        );
+
+        // The writer has hung up with the whole downlist sent
+        let (mut client, _) = listener.accept().unwrap();
+        let mut bytes = Vec::new();
+        client.read_to_end(&mut bytes).unwrap();
+        assert_eq!(bytes.len(), DOWNLIST_WORDS * 2);
+    }
+}
@@ -0,0 +1,192 @@
+use serde::Deserialize;
+
+use ragc_core::cpu::Cpu;
+use ragc_core::memory::mods::MCT_SECONDS;
+use ragc_core::symbols::SymbolTable;
+
+use crate::cond::Condition;
+
+/// Named mission timeline markers for `--timeline <file>`. Each fires
+/// once: at an MCT count, when the software enters a program, when an
+/// erasable word (read as a signed integer) crosses a threshold, or when a
+/// condition expression comes true.
+///
+/// ```toml
+/// [[markers]]
+/// name = "PDI"
+/// cycles = 10256410
+///
+/// [[markers]]
+/// name = "Touchdown"
+/// program = 68
+///
+/// [[markers]]
+/// name = "Low gate"
+/// addr = 0o1234   # Flat erasable address
+/// below = 500
+///
+/// [[markers]]
+/// name = "Landing radar"
+/// when = "chan(0o33).bit(5) == 0"
+/// ```
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+struct TimelineFile {
+    markers: Vec<MarkerConfig>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+struct MarkerConfig {
+    name: String,
+    when: Option<String>,
+    cycles: Option<usize>,
+    program: Option<u8>,
+    addr: Option<usize>,
+    below: Option<i16>,
+    above: Option<i16>,
+}
+
+impl MarkerConfig {
+    /// The marker's trigger as a condition
+    fn condition<S: SymbolTable + ?Sized>(&self, symbols: &S) -> Result<Condition, String> {
+        let text = match (
+            &self.when,
+            self.cycles,
+            self.program,
+            self.addr,
+            self.below,
+            self.above,
+        ) {
+            (Some(when), None, None, None, None, None) => when.clone(),
+            (None, Some(cycles), None, None, None, None) => format!("cycles >= {}", cycles),
+            (None, None, Some(program), None, None, None) => format!("program == {}", program),
+            (None, None, None, Some(flat), Some(below), None) => {
+                format!("sp({:o}) < {}", flat, below)
+            }
+            (None, None, None, Some(flat), None, Some(above)) => {
+                format!("sp({:o}) > {}", flat, above)
+            }
+            _ => {
+                return Err(format!(
+                    "{}: needs one of when, cycles, program, or addr with below or above",
+                    self.name
+                ))
+            }
+        };
+        Condition::parse(&text, symbols).map_err(|e| format!("{}: {}", self.name, e))
+    }
+}
+
+/// A marker as it fired
+#[derive(Clone, PartialEq, Debug)]
+pub struct Marker {
+    pub name: String,
+    pub cycles: usize,
+}
+
+impl Marker {
+    /// Emulated seconds since power on
+    pub fn time(&self) -> f64 {
+        self.cycles as f64 * MCT_SECONDS
+    }
+}
+
+/// Markers still waiting to fire
+pub struct Timeline {
+    pending: Vec<(String, Condition)>,
+}
+
+impl Timeline {
+    pub fn load<S: SymbolTable + ?Sized>(path: &str, symbols: &S) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+        Self::parse(&text, symbols).map_err(|e| format!("{}: {}", path, e))
+    }
+
+    pub fn parse<S: SymbolTable + ?Sized>(text: &str, symbols: &S) -> Result<Self, String> {
+        let file: TimelineFile = toml::from_str(text).map_err(|e| e.to_string())?;
+        let pending = file
+            .markers
+            .iter()
+            .map(|m| Ok((m.name.clone(), m.condition(symbols)?)))
+            .collect::<Result<_, String>>()?;
+        Ok(Self { pending })
+    }
+
+    /// A single marker, named after its condition, e.g. for `--until`
+    pub fn condition<S: SymbolTable + ?Sized>(text: &str, symbols: &S) -> Result<Self, String> {
+        let condition = Condition::parse(text, symbols)?;
+        Ok(Self {
+            pending: vec![(condition.text.clone(), condition)],
+        })
+    }
+
+    /// Markers whose trigger has come up, in file order. Call between
+    /// instructions.
+    pub fn poll(&mut self, cpu: &Cpu) -> Vec<Marker> {
+        let mut fired = Vec::new();
+        self.pending.retain(|(name, condition)| {
+            let due = condition.is_true(cpu);
+            if due {
+                fired.push(Marker {
+                    name: name.clone(),
+                    cycles: cpu.total_cycles,
+                });
+            }
+            !due
+        });
+        fired
+    }
+}
+
+#[cfg(test)]
+mod timeline_tests {
+    use super::Timeline;
+    use ragc_core::cpu::Cpu;
+    use ragc_core::memory::MemoryMap;
+    use ragc_core::symbols::ErasableAddress;
+
+    #[test]
+    fn markers_fire_once_on_their_trigger() {
+        let text = r#"
+            [[markers]]
+            name = "PDI"
+            cycles = 100
+
+            [[markers]]
+            name = "Low gate"
+            addr = 0o1230
+            below = 500
+        "#;
+        let none: &[(&str, ErasableAddress)] = &[];
+        let mut timeline = Timeline::parse(text, none).unwrap();
+        let mut mem = MemoryMap::new_blank();
+        mem.write_block(2, 0o230, &[1000]);
+        let mut cpu = Cpu::new(mem);
+        assert!(timeline.poll(&cpu).is_empty());
+
+        cpu.total_cycles = 120;
+        let fired = timeline.poll(&cpu);
+        assert_eq!(fired.len(), 1);
+        assert_eq!((fired[0].name.as_str(), fired[0].cycles), ("PDI", 120));
+        assert!(timeline.poll(&cpu).is_empty());
+
+        cpu.memory_mut().write_block(2, 0o230, &[0o77776]); // -1
+        assert_eq!(timeline.poll(&cpu)[0].name, "Low gate");
+
+        let ambiguous = "[[markers]]\nname = \"X\"\ncycles = 1\nprogram = 63\n";
+        assert!(Timeline::parse(ambiguous, none).is_err());
+        let unbounded = "[[markers]]\nname = \"X\"\naddr = 0o100\n";
+        assert!(Timeline::parse(unbounded, none).is_err());
+
+        // Condition expressions, in a file or on their own
+        let when = "[[markers]]\nname = \"Up\"\nwhen = \"sp(1230) > 5\"\n";
+        let mut until = Timeline::parse(when, none).unwrap();
+        assert!(until.poll(&cpu).is_empty());
+        cpu.memory_mut().write_block(2, 0o230, &[6]);
+        assert_eq!(until.poll(&cpu)[0].name, "Up");
+        assert!(until.poll(&cpu).is_empty(), "markers fire once");
+        assert!(Timeline::condition("program == 63", none).is_ok());
+        assert!(Timeline::condition("sp(4000) < 1", none).is_err());
+    }
+}
@@ -0,0 +1,148 @@
+//! Instruction traces: the `--trace` writer for a run, and `ragc trace`
+//! for packed traces expanded to text and traces compared against other
+//! emulators'.
+use std::io::Write;
+
+use log::error;
+
+use ragc_peripherals::tracefile::{RuptMarks, TraceFile, DEFAULT_TRACE_DEPTH};
+use ragc_peripherals::tracepack::TraceDecoder;
+
+use crate::tracediff;
+
+/// `--trace` and the format it writes in
+pub fn args<'a, 'b>() -> [clap::Arg<'a, 'b>; 2] {
+    [
+        clap::Arg::with_name("trace")
+            .long("trace")
+            .takes_value(true)
+            .help("Write an instruction trace to a file (from a writer thread)"),
+        clap::Arg::with_name("trace-format")
+            .long("trace-format")
+            .takes_value(true)
+            .possible_values(&["text", "packed"])
+            .default_value("text")
+            .help("Trace as text, or packed binary for `ragc trace expand`"),
+    ]
+}
+
+/// The trace writer `--trace` asks for, if any
+pub fn from_args(matches: &clap::ArgMatches) -> Result<Option<TraceFile>, String> {
+    let path = match matches.value_of("trace") {
+        Some(path) => path,
+        None => return Ok(None),
+    };
+    match matches.value_of("trace-format") {
+        Some("packed") => TraceFile::create_packed(path, DEFAULT_TRACE_DEPTH),
+        _ => TraceFile::create(path, DEFAULT_TRACE_DEPTH),
+    }
+    .map(Some)
+    .map_err(|e| format!("{}: {}", path, e))
+}
+
+/// The `trace` subcommand and its options
+pub fn subcommand<'a, 'b>() -> clap::App<'a, 'b> {
+    clap::SubCommand::with_name("trace")
+        .about("Work with instruction traces")
+        .setting(clap::AppSettings::SubcommandRequiredElseHelp)
+        .subcommand(
+            clap::SubCommand::with_name("expand")
+                .about("Convert a packed trace to text")
+                .arg(
+                    clap::Arg::with_name("trace")
+                        .required(true)
+                        .help("Packed trace"),
+                )
+                .arg(
+                    clap::Arg::with_name("format")
+                        .long("format")
+                        .takes_value(true)
+                        .possible_values(&["text", "yaagc"])
+                        .default_value("text")
+                        .help("Lines as from --trace, or laid out to diff against yaAGC"),
+                )
+                .arg(
+                    clap::Arg::with_name("out")
+                        .long("out")
+                        .takes_value(true)
+                        .help("Output file (default: stdout)"),
+                ),
+        )
+        .subcommand(
+            clap::SubCommand::with_name("diff")
+                .about("Compare a trace against another emulator's, ignoring cycle counts")
+                .arg(
+                    clap::Arg::with_name("ours")
+                        .required(true)
+                        .help("Our trace, packed or text"),
+                )
+                .arg(
+                    clap::Arg::with_name("reference")
+                        .required(true)
+                        .help("Reference trace: ragc text or `[FB,]PC WORD A= L= Q=` lines"),
+                )
+                .arg(
+                    clap::Arg::with_name("window")
+                        .long("window")
+                        .takes_value(true)
+                        .default_value("1000")
+                        .help("Instructions to look ahead for a resync"),
+                ),
+        )
+}
+
+/// Runs `trace expand` or `trace diff`; a diff that finds the traces
+/// parting exits with status 1
+pub fn command(args: &clap::ArgMatches) {
+    if let Some(args) = args.subcommand_matches("expand") {
+        if let Err(e) = expand(args) {
+            error!("Trace expansion failed: {}", e);
+        }
+    }
+    if let Some(args) = args.subcommand_matches("diff") {
+        match diff(args) {
+            Ok(true) => {}
+            Ok(false) => std::process::exit(1),
+            Err(e) => error!("Trace comparison failed: {}", e),
+        }
+    }
+}
+
+/// Unpacks a packed trace into text
+fn expand(args: &clap::ArgMatches) -> Result<(), String> {
+    let path = args.value_of("trace").unwrap_or_default();
+    let file = std::fs::File::open(path).map_err(|e| format!("{}: {}", path, e))?;
+    let entries = TraceDecoder::new(Box::new(file)).map_err(|e| format!("{}: {}", path, e))?;
+    let mut out: Box<dyn Write> = match args.value_of("out") {
+        Some(path) => Box::new(std::io::BufWriter::new(
+            std::fs::File::create(path).map_err(|e| format!("{}: {}", path, e))?,
+        )),
+        None => Box::new(std::io::BufWriter::new(std::io::stdout())),
+    };
+    let yaagc = args.value_of("format") == Some("yaagc");
+    let mut marks = RuptMarks::default();
+    for entry in entries {
+        let entry = entry.map_err(|e| format!("{}: {}", path, e))?;
+        match yaagc {
+            true => entry.write_yaagc(&mut out),
+            false => marks.write(&entry, &mut out),
+        }
+        .map_err(|e| e.to_string())?;
+    }
+    out.flush().map_err(|e| e.to_string())
+}
+
+/// Compares two traces; true if they agree
+fn diff(args: &clap::ArgMatches) -> Result<bool, String> {
+    let ours = tracediff::load(args.value_of("ours").unwrap_or_default())?;
+    let theirs = tracediff::load(args.value_of("reference").unwrap_or_default())?;
+    let window = args
+        .value_of("window")
+        .unwrap_or_default()
+        .parse()
+        .map_err(|e| format!("Invalid window: {}", e))?;
+    let result = tracediff::compare(&ours, &theirs, window);
+    tracediff::write_report(&result, ours.len(), theirs.len(), &mut std::io::stdout())
+        .map_err(|e| e.to_string())?;
+    Ok(result.first.is_none())
+}
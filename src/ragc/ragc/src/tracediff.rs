@@ -0,0 +1,282 @@
+//! Instruction trace comparison against other emulators. Cycle counts are
+//! ignored, since no two emulators agree on them; traces are walked by
+//! instruction, and where the PCs part the walk resynchronizes on the
+//! nearest run of instructions both traces execute in the same order.
+use std::collections::BTreeMap;
+use std::io::{Read, Write};
+
+use ragc_core::memory::AddressSpace;
+use ragc_peripherals::tracepack::{is_packed, TraceDecoder};
+
+/// Instructions that must match in a row to count as back in step
+const LANDMARK_RUN: usize = 4;
+
+/// One executed instruction, as far as a trace records it
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Step {
+    pub line: usize,     // Line or record number, from 1
+    pub fb: Option<u16>, // Fixed bank number, where known
+    pub pc: u16,
+    pub word: u16,
+    pub a: Option<u16>,
+    pub l: Option<u16>,
+    pub q: Option<u16>,
+}
+
+impl Step {
+    /// Same instruction at the same address
+    fn lands_with(&self, other: &Step) -> bool {
+        let banked = (0o2000..=0o3777).contains(&self.pc);
+        self.pc == other.pc
+            && self.word == other.word
+            && !(banked && matches!((self.fb, other.fb), (Some(a), Some(b)) if a != b))
+    }
+
+    fn location(&self) -> String {
+        let space = AddressSpace {
+            fixed: self.fb.map(usize::from),
+            ..AddressSpace::UNBANKED
+        };
+        match space.locate(self.pc as usize) {
+            Some(at) => at.to_string(),
+            None => format!("{:04o}", self.pc),
+        }
+    }
+}
+
+/// Load a trace: packed, `--trace` text, or yaAGC-style lines
+pub fn load(path: &str) -> Result<Vec<Step>, String> {
+    let mut bytes = Vec::new();
+    std::fs::File::open(path)
+        .and_then(|mut file| file.read_to_end(&mut bytes))
+        .map_err(|e| format!("{}: {}", path, e))?;
+    if !is_packed(&bytes) {
+        let text = String::from_utf8_lossy(&bytes);
+        return Ok(text
+            .lines()
+            .enumerate()
+            .filter_map(|(n, line)| parse_line(n + 1, line))
+            .collect());
+    }
+    let decoder = TraceDecoder::new(Box::new(std::io::Cursor::new(bytes)))
+        .map_err(|e| format!("{}: {}", path, e))?;
+    decoder
+        .enumerate()
+        .map(|(n, entry)| {
+            let entry = entry.map_err(|e| format!("{}: {}", path, e))?;
+            Ok(Step {
+                line: n + 1,
+                fb: AddressSpace::from_fb(entry.fb)
+                    .fixed
+                    .map(|bank| bank as u16),
+                pc: entry.pc,
+                word: entry.ir & 0o77777,
+                a: Some(entry.a),
+                l: Some(entry.l),
+                q: Some(entry.q),
+            })
+        })
+        .collect()
+}
+
+/// Parse `<cycles> FB:PC WORD ...` or `[FB,]PC WORD ...`, with registers
+/// as `A=`, `L=` and `Q=` anywhere after. Other lines are skipped.
+fn parse_line(line: usize, text: &str) -> Option<Step> {
+    let tokens: Vec<&str> = text.split_whitespace().collect();
+    let at = match tokens.first()?.bytes().all(|b| b.is_ascii_digit()) && tokens.len() > 1 {
+        true if tokens[1].contains(':') => 1,
+        _ => 0,
+    };
+    let octal = |s: &str| u16::from_str_radix(s, 8).ok();
+    let (fb, pc) = match tokens[at].split_once([':', ',']) {
+        Some((fb, pc)) => (Some(octal(fb)?), octal(pc)?),
+        None => (None, octal(tokens[at])?),
+    };
+    let register = |name: &str| tokens.iter().find_map(|t| octal(t.strip_prefix(name)?));
+    Some(Step {
+        line,
+        fb,
+        pc,
+        word: octal(tokens.get(at + 1)?)? & 0o77777,
+        a: register("A="),
+        l: register("L="),
+        q: register("Q="),
+    })
+}
+
+/// Outcome of walking two traces side by side
+#[derive(Default, Debug)]
+pub struct Comparison {
+    pub matched: usize,
+    pub first: Option<String>, // First architectural divergence
+    pub classes: BTreeMap<&'static str, usize>,
+    pub lost_sync: Option<(usize, usize)>, // Lines where no resync was found
+}
+
+impl Comparison {
+    fn diverge(&mut self, class: &'static str, count: usize, detail: impl FnOnce() -> String) {
+        if count == 0 {
+            return;
+        }
+        *self.classes.entry(class).or_default() += count;
+        if self.first.is_none() {
+            self.first = Some(detail());
+        }
+    }
+}
+
+/// Walk both traces, resynchronizing within `window` instructions
+pub fn compare(ours: &[Step], theirs: &[Step], window: usize) -> Comparison {
+    let mut result = Comparison::default();
+    let (mut i, mut j) = (0, 0);
+    while i < ours.len() && j < theirs.len() {
+        let (a, b) = (&ours[i], &theirs[j]);
+        if a.lands_with(b) {
+            result.matched += 1;
+            for (class, x, y) in [("A", a.a, b.a), ("L", a.l, b.l), ("Q", a.q, b.q)] {
+                let (x, y) = match (x, y) {
+                    (Some(x), Some(y)) if x != y => (x, y),
+                    _ => continue,
+                };
+                result.diverge(class, 1, || {
+                    format!(
+                        "ours line {}, reference line {} at {}: {} {:05o} vs {:05o}",
+                        a.line,
+                        b.line,
+                        a.location(),
+                        class,
+                        x,
+                        y
+                    )
+                });
+            }
+            i += 1;
+            j += 1;
+            continue;
+        }
+        let detail = || {
+            format!(
+                "ours line {}, reference line {}: {} {:05o} vs {} {:05o}",
+                a.line,
+                b.line,
+                a.location(),
+                a.word,
+                b.location(),
+                b.word
+            )
+        };
+        match resync(&ours[i..], &theirs[j..], window) {
+            Some((skip_ours, skip_theirs)) => {
+                result.diverge("control flow", 1, detail);
+                result.diverge("ours only", skip_ours, detail);
+                result.diverge("reference only", skip_theirs, detail);
+                i += skip_ours;
+                j += skip_theirs;
+            }
+            None => {
+                result.diverge("control flow", 1, detail);
+                result.lost_sync = Some((a.line, b.line));
+                return result;
+            }
+        }
+    }
+    result.diverge("ours only", ours.len() - i, || {
+        "Reference trace ends first".into()
+    });
+    result.diverge("reference only", theirs.len() - j, || {
+        "Our trace ends first".into()
+    });
+    result
+}
+
+/// Fewest instructions to skip in each trace to reach a landmark run, by
+/// total skipped; None if there is none within the window
+fn resync(ours: &[Step], theirs: &[Step], window: usize) -> Option<(usize, usize)> {
+    let run = |x: usize, y: usize| {
+        (0..LANDMARK_RUN).all(|k| match (ours.get(x + k), theirs.get(y + k)) {
+            (Some(a), Some(b)) => a.lands_with(b),
+            // A trace may end within the run
+            _ => k > 0,
+        })
+    };
+    (1..=window).find_map(|total| {
+        (0..=total)
+            .map(|x| (x, total - x))
+            .find(|&(x, y)| run(x, y))
+    })
+}
+
+pub fn write_report(
+    result: &Comparison,
+    ours: usize,
+    theirs: usize,
+    out: &mut dyn Write,
+) -> std::io::Result<()> {
+    writeln!(
+        out,
+        "# {} instructions matched (ours {}, reference {})",
+        result.matched, ours, theirs
+    )?;
+    match &result.first {
+        Some(first) => writeln!(out, "First divergence: {}", first)?,
+        None => writeln!(out, "No divergence")?,
+    }
+    for (class, count) in result.classes.iter() {
+        writeln!(out, "{:<16} {}", class, count)?;
+    }
+    if let Some((a, b)) = result.lost_sync {
+        writeln!(
+            out,
+            "Lost sync at ours line {}, reference line {}; rest not compared",
+            a, b
+        )?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tracediff_tests {
+    use super::{compare, parse_line, Step};
+
+    fn steps(text: &str) -> Vec<Step> {
+        text.lines()
+            .enumerate()
+            .filter_map(|(n, line)| parse_line(n + 1, line))
+            .collect()
+    }
+
+    #[test]
+    fn resyncs_past_extra_instructions() {
+        let ours = steps(
+            "           0 00:4000 00006 EXTEND  A=00000 L=00000 Q=00000
+                        2 00:4001 00030 READ    A=00000 L=00000 Q=00000
+                        4 02:2000 30001 CA      A=00001 L=00000 Q=00000
+                        6 00:4002 00006 EXTEND  A=00003 L=00000 Q=00000
+                        8 00:4003 01011 WRITE   A=00001 L=00000 Q=00000
+                        9 00:4004 14000 TCF     A=00001 L=00000 Q=00000
+                       10 00:4000 00006 EXTEND  A=00001 L=00000 Q=00000",
+        );
+        // The reference takes an interrupt in between and never runs 02,2000
+        let theirs = steps(
+            "# yaAGC
+             4000 00006 A=00000 L=00000 Q=00000
+             4001 00030 A=00000 L=00000 Q=00000
+             4000 00006 A=00000 L=00000 Q=00000
+             4002 00006 A=00001 L=00000 Q=00000
+             4003 01011 A=00001 L=00000 Q=00000
+             4004 14000 A=00001 L=00000 Q=00000
+             4000 00006 A=00001 L=00000 Q=00000",
+        );
+        assert_eq!(theirs.len(), 7);
+        assert_eq!(ours[2].fb, Some(2));
+
+        let result = compare(&ours, &theirs, 8);
+        assert_eq!(result.matched, 6);
+        assert_eq!(result.classes["ours only"], 1);
+        assert_eq!(result.classes["reference only"], 1);
+        assert_eq!(result.classes["A"], 1);
+        // Bank 2 switched in at 2000 is the fixed-fixed word at 4000
+        assert!(result.first.unwrap().contains(": 4000 30001 vs 4000 00006"));
+        assert!(result.lost_sync.is_none());
+    }
+}
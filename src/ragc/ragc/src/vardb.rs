@@ -0,0 +1,294 @@
+use serde::Deserialize;
+
+use ragc_core::memory::MemoryMap;
+use ragc_core::symbols::{ErasableAddress, SymbolTable};
+
+use crate::cond::Condition;
+use crate::watch::{resolve_address, Read};
+
+/// Variable databases shipped for the bundled ROMs
+const BUNDLED: [(&str, &str); 2] = [
+    ("luminary99", include_str!("../vars/luminary99.toml")),
+    ("comanche55", include_str!("../vars/comanche55.toml")),
+];
+
+/// Erasable variables of one rope with their formats and scalings, read
+/// from a data file so a new rope needs data rather than code. Streams,
+/// watches and reports take names, formats and units from here.
+///
+/// ```toml
+/// rom = "luminary99"
+/// ready = "MODREG == 0o77777" # Idle after a fresh start, for --quick-boot
+///
+/// [[vars]]
+/// name = "RN"
+/// addr = "E6,1400"    # As in watches (default: from the symbol table)
+/// format = "vector"   # "sp" or "dp" fraction, 3 DP "vector", or "raw" word
+/// scale = 134217728.0 # Value of a fraction of one, in `unit`
+/// unit = "m"
+///
+/// [[restart]]
+/// group = 1
+/// phase = "PHASE1"    # Phase register, then its complement
+/// check = "-PHASE1"
+/// point = "PHSNAME1"  # 2CADR of the restart point (optional)
+/// ```
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct VarFile {
+    rom: Option<String>,
+    ready: Option<String>,
+    vars: Vec<VarEntry>,
+    #[serde(default)]
+    restart: Vec<RestartEntry>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RestartEntry {
+    group: u8,
+    phase: String,
+    check: String,
+    point: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct VarEntry {
+    name: String,
+    addr: Option<String>,
+    #[serde(default)]
+    format: VarFormat,
+    scale: Option<f64>,
+    unit: Option<String>,
+}
+
+#[derive(Deserialize, Clone, Copy, PartialEq, Debug, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum VarFormat {
+    #[default]
+    Sp,
+    Dp,
+    Vector,
+    Raw,
+}
+
+impl VarFormat {
+    /// How each value is read, and values per variable
+    pub fn layout(&self) -> (Read, usize) {
+        match self {
+            VarFormat::Sp => (Read::Sp, 1),
+            VarFormat::Dp => (Read::Dp, 1),
+            VarFormat::Vector => (Read::Dp, 3),
+            VarFormat::Raw => (Read::Raw, 1),
+        }
+    }
+
+    /// Value of one unit of the integer read, as a fraction
+    pub fn lsb(&self) -> f64 {
+        match self {
+            VarFormat::Sp => 1.0 / (1u64 << 14) as f64,
+            VarFormat::Dp | VarFormat::Vector => 1.0 / (1u64 << 28) as f64,
+            VarFormat::Raw => 1.0,
+        }
+    }
+}
+
+/// One variable, located in erasable memory
+#[derive(Clone, Debug)]
+pub struct Var {
+    pub name: String,
+    pub addr: ErasableAddress,
+    pub format: VarFormat,
+    pub scale: f64,
+    pub unit: Option<String>,
+}
+
+impl Var {
+    /// Current value, or the vector's components, in the variable's unit
+    pub fn read(&self, mem: &MemoryMap) -> Vec<f64> {
+        let (read, count) = self.format.layout();
+        let words = if read == Read::Dp { 2 } else { 1 };
+        (0..count)
+            .map(|i| {
+                let value = read.read(mem, self.addr.offset_by(i * words)) as f64;
+                match self.format {
+                    VarFormat::Raw => value,
+                    _ => value * self.format.lsb() * self.scale,
+                }
+            })
+            .collect()
+    }
+
+    /// Current value for display, with the unit
+    pub fn describe(&self, mem: &MemoryMap) -> String {
+        let values: Vec<String> = match self.format {
+            VarFormat::Raw => self
+                .read(mem)
+                .iter()
+                .map(|v| format!("{:05o}", *v as u16))
+                .collect(),
+            _ => self.read(mem).iter().map(|v| format!("{}", v)).collect(),
+        };
+        let unit = self
+            .unit
+            .as_ref()
+            .map_or(String::new(), |u| format!(" {}", u));
+        format!("{}{}", values.join(", "), unit)
+    }
+}
+
+/// Where one restart group keeps its phase
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct RestartGroup {
+    pub group: u8,
+    pub phase: ErasableAddress,
+    pub check: ErasableAddress, // Complement of the phase while it is valid
+    pub point: Option<ErasableAddress>,
+}
+
+#[derive(Clone, Default)]
+pub struct VarDb {
+    pub rom: Option<String>,
+    vars: Vec<Var>,
+    pub restart: Vec<RestartGroup>,
+    pub ready: Option<Condition>, // The software idling after a fresh start
+    pub missing: Vec<String>,     // Declared, but without an address here
+}
+
+impl VarDb {
+    /// The database shipped for a bundled ROM
+    pub fn bundled<S: SymbolTable + ?Sized>(rom: &str, symbols: &S) -> Option<Self> {
+        let (_, text) = BUNDLED.iter().find(|(name, _)| *name == rom)?;
+        Self::parse(text, symbols).ok()
+    }
+
+    pub fn load<S: SymbolTable + ?Sized>(path: &str, symbols: &S) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+        Self::parse(&text, symbols).map_err(|e| format!("{}: {}", path, e))
+    }
+
+    /// Parse a database, locating variables without an `addr` through the
+    /// rope's symbols. Those the symbols lack are left out and listed in
+    /// `missing`.
+    pub fn parse<S: SymbolTable + ?Sized>(text: &str, symbols: &S) -> Result<Self, String> {
+        let file: VarFile = toml::from_str(text).map_err(|e| e.to_string())?;
+        let mut db = Self {
+            rom: file.rom,
+            ..Self::default()
+        };
+        for entry in file.vars {
+            let (read, count) = entry.format.layout();
+            let addr = match &entry.addr {
+                Some(addr) => resolve_address(addr, read, symbols)?,
+                None => match symbols.erasable(&entry.name) {
+                    Some(addr) => addr,
+                    None => {
+                        db.missing.push(entry.name);
+                        continue;
+                    }
+                },
+            };
+            if addr.offset + count * if read == Read::Dp { 2 } else { 1 } > 0o400 {
+                return Err(format!("{} runs past the end of its bank", entry.name));
+            }
+            db.vars.push(Var {
+                name: entry.name,
+                addr,
+                format: entry.format,
+                scale: entry.scale.unwrap_or(1.0),
+                unit: entry.unit,
+            });
+        }
+        for entry in file.restart {
+            let mut locate = |name: &str, read| match resolve_address(name, read, symbols) {
+                Ok(addr) => Some(addr),
+                Err(_) => {
+                    db.missing.push(name.to_string());
+                    None
+                }
+            };
+            let phase = locate(&entry.phase, Read::Raw);
+            let check = locate(&entry.check, Read::Raw);
+            let point = entry
+                .point
+                .as_deref()
+                .and_then(|name| locate(name, Read::Dp));
+            if let (Some(phase), Some(check)) = (phase, check) {
+                db.restart.push(RestartGroup {
+                    group: entry.group,
+                    phase,
+                    check,
+                    point,
+                });
+            }
+        }
+        db.ready = match file.ready.map(|text| Condition::parse(&text, symbols)) {
+            Some(Ok(ready)) => Some(ready),
+            Some(Err(e)) => {
+                db.missing.push(format!("ready ({})", e));
+                None
+            }
+            None => None,
+        };
+        Ok(db)
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Var> {
+        self.vars.iter().find(|var| var.name == name)
+    }
+
+    pub fn vars(&self) -> &[Var] {
+        &self.vars
+    }
+}
+
+/// Variables by name, e.g. for `StateVectorLayout::from_symbols`
+impl SymbolTable for VarDb {
+    fn erasable(&self, name: &str) -> Option<ErasableAddress> {
+        self.get(name).map(|var| var.addr)
+    }
+}
+
+#[cfg(test)]
+mod vardb_tests {
+    use super::{VarDb, VarFormat, BUNDLED};
+    use ragc_core::memory::MemoryMap;
+    use ragc_core::state_vector::StateVectorLayout;
+    use ragc_core::symbols::ErasableAddress;
+
+    #[test]
+    fn variables_read_in_their_units() {
+        let symbols = [
+            ("RN", ErasableAddress::new(6, 0o100)),
+            ("VN", ErasableAddress::new(6, 0o106)),
+        ];
+        let text = "rom = \"test\"\nready = \"RN == 0\"\n\
+            [[vars]]\nname = \"RN\"\nformat = \"vector\"\nscale = 4.0\nunit = \"m\"\n\
+            [[vars]]\nname = \"VN\"\nformat = \"vector\"\n\
+            [[vars]]\nname = \"PIPTIME\"\naddr = \"E6,1514\"\nformat = \"dp\"\n\
+            [[vars]]\nname = \"FLAG\"\naddr = \"100\"\nformat = \"raw\"\n\
+            [[vars]]\nname = \"NOSUCH\"\n";
+        let db = VarDb::parse(text, &symbols[..]).unwrap();
+        assert_eq!(db.missing, ["NOSUCH"]);
+        assert_eq!(db.ready.as_ref().unwrap().text, "RN == 0");
+        assert_eq!(db.get("PIPTIME").unwrap().format, VarFormat::Dp);
+        assert!(StateVectorLayout::from_symbols(&db).is_some());
+
+        let mut mem = MemoryMap::new_blank();
+        mem.write_block(6, 0o100, &[0o20000, 0, 0o67777, 0o77777, 0, 0]);
+        mem.write_block(0, 0o100, &[0o12345]);
+        let rn = db.get("RN").unwrap();
+        assert_eq!(rn.read(&mem), [2.0, -1.0, 0.0]);
+        assert_eq!(rn.describe(&mem), "2, -1, 0 m");
+        assert_eq!(db.get("FLAG").unwrap().describe(&mem), "12345");
+
+        let bad = "[[vars]]\nname = \"RN\"\naddr = \"1374\"\nformat = \"vector\"\n";
+        assert!(VarDb::parse(bad, &symbols[..]).is_err());
+        for (rom, text) in BUNDLED.iter() {
+            let db = VarDb::parse(text, &symbols[..]).unwrap();
+            assert_eq!(db.rom.as_deref(), Some(*rom));
+            assert!(db.get("RN").is_some());
+        }
+    }
+}
@@ -0,0 +1,292 @@
+use ragc_core::memory::{AddressSpace, Location, MemoryMap};
+use ragc_core::protect::ProtectedRange;
+use ragc_core::symbols::{ErasableAddress, SymbolTable};
+use ragc_core::word::Word15;
+
+use crate::vardb::{Var, VarFormat};
+
+/// How a watch reads its words
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Read {
+    Raw, // The word as stored
+    Sp,  // Signed single precision integer
+    Dp,  // Signed double precision integer, high word first
+}
+
+impl Read {
+    pub fn parse(name: &str) -> Result<Self, String> {
+        match name {
+            "raw" => Ok(Read::Raw),
+            "sp" => Ok(Read::Sp),
+            "dp" => Ok(Read::Dp),
+            other => Err(format!("Unknown read {}, expected sp, dp or raw", other)),
+        }
+    }
+
+    fn words(&self) -> usize {
+        match self {
+            Read::Dp => 2,
+            _ => 1,
+        }
+    }
+
+    /// The words an expression depends on
+    fn fetch(&self, mem: &MemoryMap, addr: ErasableAddress) -> [u16; 2] {
+        let mut words = [0; 2];
+        for (i, word) in words.iter_mut().enumerate().take(self.words()) {
+            let addr = addr.offset_by(i);
+            // Registers and counters aren't stored in the RAM banks
+            *word = match addr.bank {
+                0 => mem.read(addr.offset),
+                _ => mem.read_block(addr.bank, addr.offset..addr.offset + 1)[0],
+            };
+        }
+        words
+    }
+
+    fn value(&self, words: [u16; 2]) -> i64 {
+        let [high, low] = words.map(|word| Word15::new(word).to_signed().get() as i64);
+        match self {
+            Read::Raw => words[0] as i64,
+            Read::Sp => high,
+            Read::Dp => high * (1 << 14) + low,
+        }
+    }
+
+    /// Read and convert the word(s) at `addr`
+    pub fn read(&self, mem: &MemoryMap, addr: ErasableAddress) -> i64 {
+        self.value(self.fetch(mem, addr))
+    }
+}
+
+/// Resolve a watch or condition operand: an erasable symbol, a flat octal
+/// address, or `E<bank>,<octal address>` as the software writes it (the
+/// address is in the switched window 1400-1777, or in the bank's own
+/// unswitched range for E0-E2). Checks that `read` fits in the bank.
+pub fn resolve_address<S: SymbolTable + ?Sized>(
+    operand: &str,
+    read: Read,
+    symbols: &S,
+) -> Result<ErasableAddress, String> {
+    let operand = operand.trim();
+    let banked = operand.starts_with('E') && operand.contains(',');
+    let addr = match (banked, usize::from_str_radix(operand, 8)) {
+        (true, _) => match AddressSpace::UNBANKED.parse(operand) {
+            Some(Location::Erasable(addr)) => addr,
+            Some(Location::Central(idx)) => ErasableAddress::from_flat(idx),
+            _ => return Err(format!("Invalid banked address {}", operand)),
+        },
+        (false, Ok(flat)) if flat < 0o4000 => ErasableAddress::from_flat(flat),
+        (false, Ok(_)) => return Err(format!("{} is not an erasable address", operand)),
+        (false, Err(_)) => symbols
+            .erasable(operand)
+            .ok_or_else(|| format!("Unknown symbol {}", operand))?,
+    };
+    if addr.offset + read.words() > 0o400 {
+        return Err(format!("{} runs past the end of its bank", operand));
+    }
+    Ok(addr)
+}
+
+/// Write-protected range for the debugger: an address as in watches, then
+/// optionally a decimal word count and `suppress` to drop trapped writes,
+/// e.g. `E4,1400 20 suppress`
+pub fn parse_protect<S: SymbolTable + ?Sized>(
+    text: &str,
+    symbols: &S,
+) -> Result<ProtectedRange, String> {
+    let mut fields = text.split_whitespace();
+    let operand = fields.next().ok_or("Missing address")?;
+    let (mut len, mut suppress) = (1, false);
+    for field in fields {
+        match field {
+            "suppress" => suppress = true,
+            count => {
+                len = count
+                    .parse()
+                    .ok()
+                    .filter(|&n| n > 0)
+                    .ok_or_else(|| format!("Invalid word count {}", count))?
+            }
+        }
+    }
+    let start = resolve_address(operand, Read::Raw, symbols)?;
+    if start.offset + len > 0o400 {
+        return Err(format!(
+            "{} words at {} run past the end of its bank",
+            len, operand
+        ));
+    }
+    Ok(ProtectedRange {
+        start,
+        len,
+        suppress,
+    })
+}
+
+/// Debugger watch expression: `sp(SYM)`, `dp(SYM)` or `raw(SYM)`, then any
+/// number of `*k` and `/k` factors, optionally followed by `as <unit>`,
+/// e.g. `dp(TTOGO)*0.01 as seconds`. SYM is an erasable symbol or a flat
+/// octal address.
+#[derive(Clone, Debug)]
+pub struct WatchExpr {
+    pub text: String,
+    read: Read,
+    addr: ErasableAddress,
+    scale: f64,
+    unit: Option<String>,
+}
+
+impl WatchExpr {
+    pub fn parse<S: SymbolTable + ?Sized>(text: &str, symbols: &S) -> Result<Self, String> {
+        let text = text.trim();
+        let (expr, unit) = match text.split_once(" as ") {
+            Some((expr, unit)) => (expr.trim(), Some(unit.trim().to_string())),
+            None => (text, None),
+        };
+        let invalid = || format!("Invalid watch expression: {}", text);
+
+        let (func, rest) = expr.split_once('(').ok_or_else(invalid)?;
+        let (operand, mut factors) = rest.split_once(')').ok_or_else(invalid)?;
+        let read = Read::parse(func.trim())?;
+        let addr = resolve_address(operand, read, symbols)?;
+
+        let mut scale = 1.0;
+        while let Some(op) = factors.trim_start().chars().next() {
+            let rest = &factors.trim_start()[op.len_utf8()..];
+            let end = rest.find(['*', '/']).unwrap_or(rest.len());
+            let factor: f64 = rest[..end].trim().parse().map_err(|_| invalid())?;
+            match op {
+                '*' => scale *= factor,
+                '/' => scale /= factor,
+                _ => return Err(invalid()),
+            }
+            factors = &rest[end..];
+        }
+
+        Ok(Self {
+            text: expr.to_string(),
+            read,
+            addr,
+            scale,
+            unit,
+        })
+    }
+
+    /// Watch a database variable in its own unit; vectors are watched by
+    /// component with `dp(...)`
+    pub fn var(var: &Var) -> Result<Self, String> {
+        let (read, count) = var.format.layout();
+        if count != 1 {
+            return Err(format!(
+                "{} is a vector; watch its components with dp()",
+                var.name
+            ));
+        }
+        let scale = match var.format {
+            VarFormat::Raw => 1.0,
+            format => format.lsb() * var.scale,
+        };
+        Ok(Self {
+            text: var.name.clone(),
+            read,
+            addr: var.addr,
+            scale,
+            unit: var.unit.clone(),
+        })
+    }
+
+    /// The words the expression depends on
+    fn words(&self, mem: &MemoryMap) -> [u16; 2] {
+        self.read.fetch(mem, self.addr)
+    }
+
+    fn value(&self, words: [u16; 2]) -> f64 {
+        self.read.value(words) as f64 * self.scale
+    }
+
+    fn describe(&self, words: [u16; 2]) -> String {
+        let unit = self
+            .unit
+            .as_ref()
+            .map_or(String::new(), |u| format!(" {}", u));
+        format!("{} = {}{}", self.text, self.value(words), unit)
+    }
+}
+
+/// Watch expressions registered with the debugger, each with the words it
+/// last saw
+#[derive(Default)]
+pub struct Watches {
+    exprs: Vec<(WatchExpr, Option<[u16; 2]>)>,
+}
+
+impl Watches {
+    pub fn add(&mut self, expr: WatchExpr) {
+        self.exprs.push((expr, None));
+    }
+
+    pub fn clear(&mut self) {
+        self.exprs.clear();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.exprs.is_empty()
+    }
+
+    /// Re-evaluate the expressions whose words changed since the last check
+    /// (and any new ones), returning a line for each
+    pub fn check(&mut self, mem: &MemoryMap) -> Vec<String> {
+        let mut lines = Vec::new();
+        for (expr, seen) in self.exprs.iter_mut() {
+            let words = expr.words(mem);
+            if *seen != Some(words) {
+                *seen = Some(words);
+                lines.push(expr.describe(words));
+            }
+        }
+        lines
+    }
+}
+
+#[cfg(test)]
+mod watch_tests {
+    use super::{parse_protect, WatchExpr, Watches};
+    use ragc_core::memory::MemoryMap;
+    use ragc_core::symbols::ErasableAddress;
+
+    #[test]
+    fn scaled_watches_report_changes() {
+        let symbols = [("TTOGO", ErasableAddress::from_flat(0o1230))];
+        let expr = WatchExpr::parse("dp(TTOGO)*0.01 as seconds", &symbols[..]).unwrap();
+        let mut mem = MemoryMap::new_blank();
+        let mut watches = Watches::default();
+        watches.add(expr);
+        watches.add(WatchExpr::parse("sp(24) / 2", &symbols[..]).unwrap());
+
+        assert_eq!(watches.check(&mem).len(), 2);
+        assert!(watches.check(&mem).is_empty());
+        mem.write_block(2, 0o230, &[1, 0o77776]); // 16384 - 1 centiseconds
+        assert_eq!(
+            watches.check(&mem),
+            ["dp(TTOGO)*0.01 = 163.83 seconds".to_string()]
+        );
+
+        assert!(WatchExpr::parse("dp(NOSUCH)", &symbols[..]).is_err());
+        assert!(WatchExpr::parse("tp(TTOGO)", &symbols[..]).is_err());
+        assert!(WatchExpr::parse("dp(TTOGO)*x", &symbols[..]).is_err());
+        assert!(WatchExpr::parse("dp(377)", &symbols[..]).is_err());
+        assert!(WatchExpr::parse("sp(E2,1630)", &symbols[..]).is_ok());
+        assert!(WatchExpr::parse("sp(E3,1230)", &symbols[..]).is_err());
+
+        let range = parse_protect("E4,1770 8 suppress", &symbols[..]).unwrap();
+        assert_eq!(
+            (range.start, range.len),
+            (ErasableAddress::new(4, 0o370), 8)
+        );
+        assert!(range.suppress);
+        assert!(!parse_protect("TTOGO", &symbols[..]).unwrap().suppress);
+        assert!(parse_protect("E4,1770 9", &symbols[..]).is_err());
+        assert!(parse_protect("TTOGO 0", &symbols[..]).is_err());
+    }
+}
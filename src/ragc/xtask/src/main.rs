@@ -0,0 +1,330 @@
+//! Repository chores, run as `cargo xtask <task>` from `src/ragc`.
+//!
+//! `check-features` builds and tests the feature combinations that matter:
+//! the no_std core on its own, with alloc, std and tracing, on wasm, and the
+//! optional frontends. Features interact (tracing replaces log, the minimal
+//! profile must stay no_std, peripherals pull in std), and building each
+//! crate with its defaults alone doesn't show when a combination breaks.
+//!
+//! `trace-overhead` runs the `trace_overhead` example in release, which
+//! fails when tracing to a file takes more than its share of each frame.
+use std::path::{Path, PathBuf};
+use std::process::{Command, ExitStatus};
+
+/// What a combination is checked with
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Check {
+    Test,  // Build and run the tests
+    Build, // Build only: a foreign target, or system libraries the tests need
+}
+
+/// One feature combination of one crate
+struct Combo {
+    name: &'static str,
+    krate: &'static str,
+    default_features: bool,
+    features: &'static [&'static str],
+    target: Option<&'static str>, // Cross target, skipped when not installed
+    check: Check,
+}
+
+const fn combo(
+    name: &'static str,
+    krate: &'static str,
+    default_features: bool,
+    features: &'static [&'static str],
+    check: Check,
+) -> Combo {
+    Combo {
+        name,
+        krate,
+        default_features,
+        features,
+        target: None,
+        check,
+    }
+}
+
+const fn cross(name: &'static str, krate: &'static str, target: &'static str) -> Combo {
+    Combo {
+        target: Some(target),
+        ..combo(name, krate, false, &[], Check::Build)
+    }
+}
+
+/// The feature matrix. Add a row with each feature, or each pair of
+/// features, that changes what compiles.
+const MATRIX: &[Combo] = &[
+    combo("core-nostd", "ragc-core", false, &[], Check::Test),
+    combo("core-alloc", "ragc-core", false, &["alloc"], Check::Test),
+    combo("core-default", "ragc-core", true, &[], Check::Test),
+    combo(
+        "core-std-trace",
+        "ragc-core",
+        true,
+        &["std", "events", "ringtrace"],
+        Check::Test,
+    ),
+    combo(
+        "core-tracing",
+        "ragc-core",
+        false,
+        &["tracing"],
+        Check::Test,
+    ),
+    combo(
+        "core-strict",
+        "ragc-core",
+        true,
+        &["strict-words"],
+        Check::Test,
+    ),
+    cross("minimal-bare-metal", "ragc-minimal", "thumbv7em-none-eabi"),
+    cross("core-wasm", "ragc-core", "wasm32-unknown-unknown"),
+    combo("protocol", "dsky-protocol", true, &[], Check::Test),
+    combo(
+        "protocol-cli",
+        "dsky-protocol",
+        true,
+        &["cli"],
+        Check::Build,
+    ),
+    combo("periph-default", "ragc-peripherals", true, &[], Check::Test),
+    combo(
+        "periph-vagc",
+        "ragc-peripherals",
+        true,
+        &["vagc-peripherals"],
+        Check::Test,
+    ),
+    combo(
+        "periph-input-map",
+        "ragc-peripherals",
+        true,
+        &["input-map"],
+        Check::Test,
+    ),
+    combo(
+        "periph-tokio",
+        "ragc-peripherals",
+        true,
+        &["tokio"],
+        Check::Test,
+    ),
+    combo(
+        "periph-zstd",
+        "ragc-peripherals",
+        true,
+        &["zstd"],
+        Check::Test,
+    ),
+    combo("ragc", "ragc", true, &[], Check::Test),
+    combo("ragc-tracing", "ragc", true, &["tracing"], Check::Test),
+    combo("ragc-zstd", "ragc", true, &["zstd"], Check::Test),
+    combo("ragc-sqlite", "ragc", true, &["sqlite"], Check::Build),
+    combo("ragc-gui", "ragc", true, &["gui"], Check::Build),
+    combo(
+        "periph-gui",
+        "ragc-peripherals",
+        true,
+        &["egui-example"],
+        Check::Build,
+    ),
+    combo(
+        "protocol-json",
+        "ragc-protocol-json",
+        true,
+        &[],
+        Check::Test,
+    ),
+    combo("sim", "ragc-sim", true, &[], Check::Test),
+];
+
+impl Combo {
+    fn command(&self, root: &Path) -> Command {
+        let mut cmd = Command::new(std::env::var("CARGO").unwrap_or_else(|_| "cargo".into()));
+        cmd.arg(match self.check {
+            Check::Test => "test",
+            Check::Build => "build",
+        });
+        cmd.arg("--manifest-path")
+            .arg(root.join(self.krate).join("Cargo.toml"));
+        if !self.default_features {
+            cmd.arg("--no-default-features");
+        }
+        if !self.features.is_empty() {
+            cmd.arg("--features").arg(self.features.join(","));
+        }
+        if let Some(target) = self.target {
+            cmd.arg("--target").arg(target);
+        }
+        cmd
+    }
+
+    fn describe(&self) -> String {
+        let mut text = format!("{:?} {}", self.check, self.krate).to_lowercase();
+        if !self.default_features {
+            text += " --no-default-features";
+        }
+        if !self.features.is_empty() {
+            text += &format!(" --features {}", self.features.join(","));
+        }
+        if let Some(target) = self.target {
+            text += &format!(" --target {}", target);
+        }
+        text
+    }
+}
+
+/// Directory holding the crates: the parent of this one
+fn crates_root() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .parent()
+        .expect("xtask lives inside the crates directory")
+        .to_path_buf()
+}
+
+/// Targets rustup has installed; None without rustup
+fn installed_targets() -> Option<Vec<String>> {
+    let out = Command::new("rustup")
+        .args(["target", "list", "--installed"])
+        .output()
+        .ok()?;
+    let text = String::from_utf8_lossy(&out.stdout);
+    Some(text.lines().map(|l| l.trim().to_string()).collect())
+}
+
+fn check_features(args: &[String]) -> Result<(), String> {
+    let list = args.iter().any(|a| a == "--list");
+    let names: Vec<&String> = args.iter().filter(|a| !a.starts_with("--")).collect();
+    if let Some(name) = names
+        .iter()
+        .find(|n| !MATRIX.iter().any(|c| c.name == n.as_str()))
+    {
+        return Err(format!("No combination named {} (see --list)", name));
+    }
+    let selected: Vec<&Combo> = MATRIX
+        .iter()
+        .filter(|c| names.is_empty() || names.iter().any(|n| n.as_str() == c.name))
+        .collect();
+    if list {
+        for combo in selected {
+            println!("{:20} {}", combo.name, combo.describe());
+        }
+        return Ok(());
+    }
+
+    let root = crates_root();
+    let targets = installed_targets();
+    let mut failed = Vec::new();
+    let mut skipped = Vec::new();
+    for combo in selected.iter() {
+        let installed = match (combo.target, &targets) {
+            (Some(target), Some(targets)) => targets.iter().any(|t| t == target),
+            (Some(_), None) => false,
+            (None, _) => true,
+        };
+        if !installed {
+            skipped.push(combo.name);
+            continue;
+        }
+        println!("== {}: {}", combo.name, combo.describe());
+        let status: Result<ExitStatus, _> = combo.command(&root).status();
+        match status {
+            Ok(status) if status.success() => {}
+            Ok(_) => failed.push(combo.name),
+            Err(e) => return Err(format!("Cannot run cargo: {}", e)),
+        }
+    }
+
+    let passed = selected.len() - failed.len() - skipped.len();
+    println!(
+        "{} passed, {} failed, {} skipped",
+        passed,
+        failed.len(),
+        skipped.len()
+    );
+    if !skipped.is_empty() {
+        println!("Skipped (target not installed): {}", skipped.join(", "));
+    }
+    match failed.is_empty() {
+        true => Ok(()),
+        false => Err(format!("Failed: {}", failed.join(", "))),
+    }
+}
+
+fn trace_overhead() -> Result<(), String> {
+    let mut cmd = Command::new(std::env::var("CARGO").unwrap_or_else(|_| "cargo".into()));
+    cmd.args(["run", "--release", "--manifest-path"])
+        .arg(crates_root().join("ragc-peripherals").join("Cargo.toml"))
+        .args([
+            "--features",
+            "vagc-peripherals",
+            "--example",
+            "trace_overhead",
+        ]);
+    match cmd.status() {
+        Ok(status) if status.success() => Ok(()),
+        Ok(_) => Err("Failed: trace-overhead".to_string()),
+        Err(e) => Err(format!("Cannot run cargo: {}", e)),
+    }
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let result = match args.first().map(String::as_str) {
+        Some("check-features") => check_features(&args[1..]),
+        Some("trace-overhead") => trace_overhead(),
+        _ => {
+            Err("Usage: cargo xtask check-features [--list] [NAME...] | trace-overhead".to_string())
+        }
+    };
+    if let Err(e) = result {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
+}
+
+#[cfg(test)]
+mod xtask_tests {
+    use super::{crates_root, MATRIX};
+
+    /// Feature names declared in a manifest's [features] table, and its
+    /// optional dependencies, which are features too
+    fn declared(manifest: &str) -> Vec<String> {
+        let mut names = Vec::new();
+        let mut in_features = false;
+        for line in manifest.lines().map(str::trim) {
+            if line.starts_with('[') {
+                in_features = line == "[features]";
+                continue;
+            }
+            if let Some((key, value)) = line.split_once('=') {
+                if in_features || value.contains("optional = true") {
+                    names.push(key.trim().to_string());
+                }
+            }
+        }
+        names
+    }
+
+    #[test]
+    fn matrix_names_real_crates_and_features() {
+        for combo in MATRIX {
+            let path = crates_root().join(combo.krate).join("Cargo.toml");
+            let manifest = std::fs::read_to_string(&path).unwrap();
+            let features = declared(&manifest);
+            for feature in combo.features {
+                assert!(
+                    features.iter().any(|f| f == feature),
+                    "{}: {} has no feature {}",
+                    combo.name,
+                    combo.krate,
+                    feature
+                );
+            }
+            let unique = MATRIX.iter().filter(|c| c.name == combo.name).count();
+            assert_eq!(unique, 1, "{} listed twice", combo.name);
+        }
+    }
+}